@@ -0,0 +1,249 @@
+//! Files attached to tasks and notes. Attachments live in a
+//! content-addressed store next to the database: each file is copied in
+//! once under its SHA-256, and items record the hash with the name it was
+//! attached under.
+//!
+//! The SHA-256 is written out here, checked against the NIST vectors below,
+//! as no `sha2` crate can be vendored into this build yet. It names and
+//! checks contents rather than guarding anything secret; swap it for
+//! `sha2` once that can be had, which changes no stored hash.
+
+use std::fs;
+use std::io::ErrorKind as IOErrorKind;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{self, RegiaError};
+
+/// A file attached to a task or note.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Attachment {
+    /// Hex SHA-256 of the contents, which names the file in the store
+    pub hash: String,
+    /// The name the file had when it was attached
+    pub filename: String,
+    pub added: DateTime<Utc>,
+}
+
+impl Attachment {
+    /// Whether `wanted` names this attachment, by hash prefix or filename.
+    pub fn matches(&self, wanted: &str) -> bool {
+        self.hash.starts_with(&wanted.to_lowercase()) || self.filename == wanted
+    }
+
+    /// Whether the hash is one [`sha256`] could have made. Attachments come
+    /// from the database, which sync and batch edits can fill with anything.
+    pub fn has_valid_hash(&self) -> bool {
+        self.hash.len() == 64
+            && self
+                .hash
+                .bytes()
+                .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+    }
+
+    /// The start of the hash, enough to tell attachments apart by.
+    pub fn short_hash(&self) -> &str {
+        self.hash.get(..8).unwrap_or(&self.hash)
+    }
+
+    /// The filename without any directories, for writing the file out
+    /// under; `None` when nothing is left of it.
+    pub fn file_name(&self) -> Option<&str> {
+        Path::new(&self.filename)
+            .file_name()
+            .and_then(|name| name.to_str())
+    }
+}
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// The hex SHA-256 digest of `data`.
+pub fn sha256(data: &[u8]) -> String {
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (word, add) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(add);
+        }
+    }
+
+    state.iter().map(|word| format!("{:08x}", word)).collect()
+}
+
+/// The directory attachments are copied into.
+pub struct Store {
+    dir: PathBuf,
+}
+
+impl Store {
+    /// The store for the database at `db_path`: a directory beside it named
+    /// after the database, e.g. `.regia.db.attachments`.
+    pub fn for_db(db_path: &Path) -> Self {
+        let mut name = db_path
+            .file_name()
+            .map(|name| name.to_os_string())
+            .unwrap_or_default();
+        name.push(".attachments");
+        Self {
+            dir: db_path.with_file_name(name),
+        }
+    }
+
+    /// Where the contents of `attachment` are kept.
+    pub fn path(&self, attachment: &Attachment) -> error::Result<PathBuf> {
+        if !attachment.has_valid_hash() {
+            return Err(RegiaError::corrupt(format!(
+                "attachment {} has a bad hash: {}",
+                attachment.filename, attachment.hash
+            )));
+        }
+        Ok(self.dir.join(&attachment.hash[..2]).join(&attachment.hash))
+    }
+
+    /// Copies the file at `source` into the store, unless the same contents
+    /// are already there or this is a dry run.
+    pub fn add(&self, source: &Path) -> error::Result<Attachment> {
+        let data = fs::read(source)?;
+        let filename = match source.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => {
+                return Err(RegiaError::Parse(format!(
+                    "not a file: {}",
+                    source.display()
+                )))
+            }
+        };
+        let attachment = Attachment {
+            hash: sha256(&data),
+            filename,
+            added: Utc::now(),
+        };
+        let path = self.path(&attachment)?;
+        match fs::metadata(&path) {
+            Ok(_) => {}
+            Err(err) if err.kind() == IOErrorKind::NotFound => {
+                if !crate::db::is_dry() {
+                    fs::create_dir_all(path.parent().unwrap())?;
+                    crate::db::write_to_disk(&path, &data)?;
+                }
+            }
+            Err(err) => return Err(err.into()),
+        }
+        Ok(attachment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha256_known_digests() {
+        assert_eq!(
+            sha256(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // Spans two blocks once padded
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn stores_each_content_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::for_db(&dir.path().join("regia.db"));
+        let first = dir.path().join("a.txt");
+        let second = dir.path().join("b.txt");
+        fs::write(&first, "same").unwrap();
+        fs::write(&second, "same").unwrap();
+
+        let a = store.add(&first).unwrap();
+        let b = store.add(&second).unwrap();
+        assert_eq!(a.hash, b.hash);
+        assert_eq!(b.filename, "b.txt");
+        assert_eq!(fs::read_to_string(store.path(&a).unwrap()).unwrap(), "same");
+        assert!(store
+            .path(&a)
+            .unwrap()
+            .starts_with(dir.path().join("regia.db.attachments")));
+        assert!(a.matches(&a.hash[..6]) && a.matches("a.txt") && !a.matches("b.txt"));
+    }
+
+    #[test]
+    fn distrusts_stored_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::for_db(&dir.path().join("regia.db"));
+        let stored = |hash: &str, filename: &str| Attachment {
+            hash: hash.to_string(),
+            filename: filename.to_string(),
+            added: Utc::now(),
+        };
+        for hash in ["a", "../../../../etc/passwd", &"A".repeat(64)] {
+            assert!(store.path(&stored(hash, "x")).is_err(), "{}", hash);
+        }
+        let hash = sha256(b"x");
+        assert_eq!(stored(&hash, "../../.bashrc").file_name(), Some(".bashrc"));
+        assert_eq!(stored(&hash, "/etc/cron.d/job").file_name(), Some("job"));
+        assert_eq!(stored(&hash, "..").file_name(), None);
+    }
+}
@@ -0,0 +1,158 @@
+//! Snapshots of the database for `regia backup` and `regia restore`.
+//!
+//! A backup is one file: `RGBK`, the hex SHA-256 of the database file it
+//! holds, then that file, compacted and [`compress`]ed. The checksum is
+//! checked before anything is restored from it. Backups are named after
+//! the database and when they were taken, so that they sort oldest first.
+
+use std::fs;
+use std::io::ErrorKind as IOErrorKind;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+
+use crate::attach;
+use crate::compress;
+use crate::db::{self, Database};
+use crate::error::{self, RegiaError};
+
+const MAGIC: &[u8; 4] = b"RGBK";
+/// How long the hex checksum after the magic is.
+const CHECKSUM: usize = 64;
+const SUFFIX: &str = ".backup";
+
+/// The directory backups go to unless `backup.dir` says otherwise:
+/// `<db>.backups` beside the database.
+pub fn default_dir(db_path: &Path) -> PathBuf {
+    crate::db::sibling_path(db_path, ".backups")
+}
+
+/// What every backup of the database at `db_path` is named starting with.
+fn prefix(db_path: &Path) -> String {
+    let name = db_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    format!("{}-", name.trim_start_matches('.'))
+}
+
+/// The name of a backup of the database at `db_path` taken `at`.
+pub fn file_name(db_path: &Path, at: DateTime<Utc>) -> String {
+    format!(
+        "{}{}{}",
+        prefix(db_path),
+        at.format("%Y%m%dT%H%M%S%.3fZ"),
+        SUFFIX
+    )
+}
+
+/// `db` as a backup file holds it.
+pub fn encode(db: &Database) -> error::Result<Vec<u8>> {
+    let file = db.to_bytes()?;
+    let mut buf = MAGIC.to_vec();
+    buf.extend_from_slice(attach::sha256(&file).as_bytes());
+    buf.extend_from_slice(&compress::compress(&file));
+    Ok(buf)
+}
+
+/// The database in a backup file, once its checksum matches.
+pub fn decode(buf: &[u8]) -> error::Result<Database> {
+    let rest = match buf.strip_prefix(&MAGIC[..]) {
+        Some(rest) if rest.len() >= CHECKSUM => rest,
+        Some(_) => return Err(RegiaError::corrupt("Truncated backup header")),
+        None => return Err(RegiaError::corrupt("Not a regia backup")),
+    };
+    let (checksum, packed) = rest.split_at(CHECKSUM);
+    let file = compress::decompress(packed)?;
+    if attach::sha256(&file).as_bytes() != checksum {
+        return Err(RegiaError::corrupt(
+            "Backup checksum doesn't match, the file is damaged",
+        ));
+    }
+    Database::from_bytes(&file)
+}
+
+/// Writes a backup of `db`, the database at `db_path`, into `dir`, and
+/// returns where.
+pub fn write(
+    dir: &Path,
+    db_path: &Path,
+    db: &Database,
+    at: DateTime<Utc>,
+) -> error::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(file_name(db_path, at));
+    db::write_to_disk(&path, &encode(db)?)?;
+    Ok(path)
+}
+
+/// The backups of the database at `db_path` in `dir`, oldest first.
+pub fn list(dir: &Path, db_path: &Path) -> error::Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == IOErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let prefix = prefix(db_path);
+    let mut found = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if name.starts_with(&prefix) && name.ends_with(SUFFIX) {
+            found.push(path);
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Deletes all but the `keep` newest backups of the database at `db_path`
+/// in `dir`, returning those it deleted.
+pub fn rotate(dir: &Path, db_path: &Path, keep: usize) -> error::Result<Vec<PathBuf>> {
+    let mut found = list(dir, db_path)?;
+    let excess = found.len().saturating_sub(keep);
+    found.truncate(excess);
+    for path in found.iter() {
+        fs::remove_file(path)?;
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::Task;
+    use chrono::Duration;
+
+    #[test]
+    fn backups_check_out_and_rotate() {
+        let mut db = Database::default();
+        db.tasks.add(Task::new(String::from("water the plants"), 0));
+        let mut buf = encode(&db).unwrap();
+        assert_eq!(decode(&buf).unwrap(), db);
+
+        let last = buf.len() - 1;
+        buf[last] ^= 1;
+        assert!(decode(&buf).is_err());
+        assert!(decode(b"RGDB").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join(".regia.db");
+        let backups = default_dir(&db_path);
+        let start = Utc::now();
+        for minutes in 0..4 {
+            write(&backups, &db_path, &db, start + Duration::minutes(minutes)).unwrap();
+        }
+        fs::write(backups.join("notes.txt"), "not a backup").unwrap();
+        let found = list(&backups, &db_path).unwrap();
+        assert_eq!(found.len(), 4);
+
+        let removed = rotate(&backups, &db_path, 3).unwrap();
+        assert_eq!(removed, found[..1]);
+        assert_eq!(list(&backups, &db_path).unwrap(), found[1..]);
+        assert!(backups.join("notes.txt").exists());
+    }
+}
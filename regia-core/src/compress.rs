@@ -0,0 +1,200 @@
+//! A small LZ77 codec in the manner of LZ4's block format, for backups.
+//! It trades ratio for being simple and fast, and does well on what regia
+//! writes: msgpack with the same keys and words over and over.
+//!
+//! A compressed buffer is the original length as a little-endian `u64`,
+//! then sequences of a token byte, literals and a match. The token's high
+//! nibble is the number of literals and its low one the match length less
+//! [`MIN_MATCH`]; a nibble of 15 continues in the bytes after, each adding
+//! up to 255. The literals follow, then the match as a little-endian `u16`
+//! of how far back it starts, then any more of the match length. The last
+//! sequence is literals only.
+
+use crate::error::{self, RegiaError};
+
+/// The shortest repeat worth a match.
+const MIN_MATCH: usize = 4;
+/// How far back a match can start.
+const WINDOW: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 14;
+/// How long the compressed buffer's header is.
+const HEADER: usize = 8;
+
+fn hash(four: &[u8]) -> usize {
+    let word = u32::from_le_bytes([four[0], four[1], four[2], four[3]]);
+    (word.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+/// Writes the rest of a length a token's nibble started.
+fn push_length(out: &mut Vec<u8>, mut rest: usize) {
+    while rest >= 255 {
+        out.push(255);
+        rest -= 255;
+    }
+    out.push(rest as u8);
+}
+
+fn push_sequence(out: &mut Vec<u8>, literals: &[u8], found: Option<(usize, usize)>) {
+    let extra = found.map_or(0, |(_, len)| len - MIN_MATCH);
+    out.push(((literals.len().min(15) as u8) << 4) | extra.min(15) as u8);
+    if literals.len() >= 15 {
+        push_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = found {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if extra >= 15 {
+            push_length(out, extra - 15);
+        }
+    }
+}
+
+/// `data`, compressed.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER + data.len() / 2);
+    out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    // Where each hash of four bytes was last seen
+    let mut seen = vec![usize::MAX; 1 << HASH_BITS];
+    let (mut at, mut literals) = (0, 0);
+    while at + MIN_MATCH <= data.len() {
+        let key = hash(&data[at..]);
+        let candidate = seen[key];
+        seen[key] = at;
+        if candidate != usize::MAX
+            && at - candidate <= WINDOW
+            && data[candidate..candidate + MIN_MATCH] == data[at..at + MIN_MATCH]
+        {
+            let mut len = MIN_MATCH;
+            while at + len < data.len() && data[candidate + len] == data[at + len] {
+                len += 1;
+            }
+            push_sequence(&mut out, &data[literals..at], Some((at - candidate, len)));
+            at += len;
+            literals = at;
+        } else {
+            at += 1;
+        }
+    }
+    push_sequence(&mut out, &data[literals..], None);
+    out
+}
+
+fn truncated() -> RegiaError {
+    RegiaError::corrupt("Compressed data is truncated")
+}
+
+/// Reads the rest of a length a token's nibble started.
+fn read_length(buf: &[u8], at: &mut usize, nibble: usize) -> error::Result<usize> {
+    let mut len = nibble;
+    if nibble == 15 {
+        loop {
+            let byte = *buf.get(*at).ok_or_else(truncated)?;
+            *at += 1;
+            len += byte as usize;
+            if byte != 255 {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
+
+/// What `compress` made `buf` from. Fails rather than panics on anything
+/// it didn't make, and never grows past the length the header gives.
+pub fn decompress(buf: &[u8]) -> error::Result<Vec<u8>> {
+    if buf.len() < HEADER {
+        return Err(truncated());
+    }
+    let mut len = [0u8; HEADER];
+    len.copy_from_slice(&buf[..HEADER]);
+    let len = u64::from_le_bytes(len) as usize;
+    let too_long = || RegiaError::corrupt("Compressed data is longer than it says");
+
+    // Don't trust the header with the allocation, only as a limit
+    let mut out = Vec::with_capacity(len.min(buf.len().saturating_mul(4)));
+    let mut at = HEADER;
+    loop {
+        let token = *buf.get(at).ok_or_else(truncated)?;
+        at += 1;
+        let count = read_length(buf, &mut at, (token >> 4) as usize)?;
+        let literals = buf.get(at..at + count).ok_or_else(truncated)?;
+        if out.len() + count > len {
+            return Err(too_long());
+        }
+        out.extend_from_slice(literals);
+        at += count;
+        if at == buf.len() {
+            break;
+        }
+
+        let offset = buf.get(at..at + 2).ok_or_else(truncated)?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        at += 2;
+        let count = read_length(buf, &mut at, (token & 0x0f) as usize)? + MIN_MATCH;
+        if offset == 0 || offset > out.len() {
+            return Err(RegiaError::corrupt("Compressed data refers outside itself"));
+        }
+        if out.len() + count > len {
+            return Err(too_long());
+        }
+        // Byte by byte, since a match may overlap what it copies out
+        let start = out.len() - offset;
+        for index in start..start + count {
+            out.push(out[index]);
+        }
+    }
+    if out.len() != len {
+        return Err(RegiaError::corrupt(
+            "Compressed data is shorter than it says",
+        ));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let text = "content: water the plants\n".repeat(200);
+        let mut noisy = Vec::new();
+        let mut seed: u32 = 7;
+        for _ in 0..5000 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            noisy.push((seed >> 16) as u8);
+        }
+        let long_run = vec![b'a'; 70_000];
+        for data in [&b""[..], b"abc", text.as_bytes(), &noisy, &long_run] {
+            let packed = compress(data);
+            assert_eq!(decompress(&packed).unwrap(), data);
+        }
+        assert!(compress(text.as_bytes()).len() < text.len() / 10);
+    }
+
+    /// A block as this codec wrote it, which every later regia must read
+    /// the same: six literals, then a match six back for the rest.
+    pub(crate) const STORED: [u8; 18] = [
+        23, 0, 0, 0, 0, 0, 0, 0, 0x6d, b'r', b'e', b'g', b'i', b'a', b' ', 6, 0, 0,
+    ];
+
+    #[test]
+    fn reads_stored_blocks() {
+        assert_eq!(decompress(&STORED).unwrap(), b"regia regia regia regia");
+        assert_eq!(compress(b"regia regia regia regia"), STORED);
+    }
+
+    #[test]
+    fn damage_is_an_error() {
+        let packed = compress("the same words, the same words".as_bytes());
+        assert!(decompress(&packed[..packed.len() - 3]).is_err());
+        assert!(decompress(&packed[..4]).is_err());
+
+        let mut longer = packed.clone();
+        longer[0] += 1;
+        assert!(decompress(&longer).is_err());
+        let mut shorter = packed;
+        shorter[0] -= 1;
+        assert!(decompress(&shorter).is_err());
+    }
+}
@@ -0,0 +1,1225 @@
+//! The user config and the settings read from it.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{Duration, NaiveTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::dates::{self, Clock, Locale};
+use crate::error::{self, RegiaError};
+use crate::theme::{self, Element, Theme, THEMES};
+use crate::urgency::{Curve, Rule, Urgency};
+use crate::workflow::{Workflow, WORKFLOW};
+
+/// Where the database lives, and how it is written.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Contents {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regia_db: Option<String>,
+    /// Compress the database file, for databases with much in their notes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compress: Option<bool>,
+}
+
+/// How many days an open task goes untouched before it is stale, unless
+/// `tasks.stale_after` says.
+pub const STALE_DAYS: i64 = 30;
+
+/// How many hours of estimated work `regia plan` fits into a day, unless
+/// `tasks.capacity` says.
+pub const CAPACITY_HOURS: i64 = 6;
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TaskSettings {
+    /// How far ahead `task ls` highlights due dates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_soon: Option<String>,
+    /// The name `task comment` signs comments with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// The statuses of `task move` and `regia board`, comma separated, the
+    /// last meaning done
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workflow: Option<String>,
+    /// How long an open task goes untouched before it is stale, or
+    /// `never`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stale_after: Option<String>,
+    /// How much estimated work `regia plan` fits into a day
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<String>,
+}
+
+/// How dates and times are shown, and which day weeks start on, see
+/// [`dates::Locale`].
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DateSettings {
+    /// A strftime format for dates, like `%d.%m.%Y`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// `mon` or `sun`, or any other day
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub week_start: Option<String>,
+    /// `24h` or `12h`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clock: Option<String>,
+}
+
+/// The colors output is shown in: a built-in theme, and colors for any of
+/// its elements to change, each a color name or `none`. See [`theme`].
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeSettings {
+    /// One of [`THEMES`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overdue: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_soon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub done: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight: Option<String>,
+    /// The priority levels, unless `colors` sets thresholds of its own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub low: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub medium: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub high: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub critical: Option<String>,
+}
+
+impl ThemeSettings {
+    /// The color set for `element`, if any.
+    pub fn color(&self, element: Element) -> Option<&str> {
+        let color = match element {
+            Element::Header => &self.header,
+            Element::Accent => &self.accent,
+            Element::Tag => &self.tag,
+            Element::Context => &self.context,
+            Element::Overdue => &self.overdue,
+            Element::DueSoon => &self.due_soon,
+            Element::Done => &self.done,
+            Element::Error => &self.error,
+            Element::Warning => &self.warning,
+            Element::Highlight => &self.highlight,
+            Element::Low => &self.low,
+            Element::Medium => &self.medium,
+            Element::High => &self.high,
+            Element::Critical => &self.critical,
+        };
+        color.as_deref()
+    }
+}
+
+/// A section with only an age after which something happens automatically.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AfterSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonSettings {
+    /// How long before a due date to send a reminder
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remind: Option<String>,
+    /// How often to check for reminders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Commit after every command that changes the database
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto: Option<bool>,
+    #[serde(skip_serializing_if = "CaldavSettings::is_empty")]
+    pub caldav: CaldavSettings,
+}
+
+/// The CalDAV calendar `regia sync caldav` keeps dated tasks in.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaldavSettings {
+    /// The calendar collection, e.g. `https://dav.example.com/cal/tasks/`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+impl CaldavSettings {
+    pub fn is_empty(&self) -> bool {
+        self == &CaldavSettings::default()
+    }
+}
+
+/// The weights `regia next` scores tasks with.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NextSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocked: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+}
+
+/// How many days ahead of its due date a task starts gaining urgency,
+/// unless `urgency.due_within` says.
+pub const URGENCY_DUE_DAYS: i64 = 14;
+
+/// How many days a task ages before it has all its urgency for age, unless
+/// `urgency.age_over` says.
+pub const URGENCY_AGE_DAYS: i64 = 30;
+
+/// How priorities rise as tasks near their due dates and age, see
+/// [`urgency`]. Each rule is off until its curve is set.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct UrgencySettings {
+    /// `linear`, `exponential` or `logarithmic`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_curve: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_within: Option<String>,
+    /// The most points the due date adds to a priority, all of them once
+    /// the task is due
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_raise: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_curve: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_over: Option<String>,
+    /// The most points age adds to a priority
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_raise: Option<u32>,
+}
+
+/// What `regia journal` starts each day's entry with.
+pub const JOURNAL_TEMPLATE: &str = "# {weekday} {date}\n\n{open} open tasks, {overdue} overdue";
+
+/// The daily notes `regia journal` keeps.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct JournalSettings {
+    /// The start of each new entry, with `{date}`, `{weekday}`, `{open}`,
+    /// `{overdue}` and `{due_today}` filled in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// The tag that marks notes as journal entries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+/// How much of a long note `note ls` shows, in characters, unless unset.
+pub const NOTE_PREVIEW: usize = 200;
+
+/// How many earlier revisions of a note are kept, unless unset.
+pub const NOTE_REVISIONS: usize = 20;
+
+/// How notes are listed and kept.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoteSettings {
+    /// The characters of a note `note ls` shows before cutting it short
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview: Option<usize>,
+    /// The earlier revisions of each note kept for `note history`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revisions: Option<usize>,
+}
+
+/// How many backups `regia backup` keeps, unless unset.
+pub const BACKUP_KEEP: usize = 7;
+
+/// Where `regia backup` writes, and how many it keeps.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupSettings {
+    /// The directory backups go to; `<db>.backups` beside the database
+    /// when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+    /// How many of the newest backups are kept; older ones are deleted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep: Option<usize>,
+}
+
+/// Commands or webhooks run when something happens to a task or note.
+/// Each is an `http://` or `https://` URL to POST to, or else a shell
+/// command; either way the item comes as JSON, in the request body or on
+/// stdin.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HookSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_added: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_completed: Option<String>,
+    /// Run by `regia daemon` when a task's due date passes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_overdue: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note_added: Option<String>,
+    /// Run for each task that completing another left with nothing to
+    /// wait on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_unblocked: Option<String>,
+}
+
+/// Where `regia notify` and the daemon post agendas and overdue alerts:
+/// a Slack incoming webhook, a Matrix room, or both.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifySettings {
+    /// The incoming webhook URL of a Slack channel
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slack: Option<String>,
+    #[serde(skip_serializing_if = "MatrixSettings::is_empty")]
+    pub matrix: MatrixSettings,
+    /// The local time, like `08:00`, the daemon posts each day's agenda
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agenda_at: Option<String>,
+    /// Whether the daemon posts tasks as they become overdue
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overdue: Option<bool>,
+}
+
+/// The Matrix room notifications go to, and the account they come from.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatrixSettings {
+    /// The homeserver's base URL, e.g. `https://matrix.example.com`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub homeserver: Option<String>,
+    /// The room id, e.g. `!abcdef:example.com`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub room: Option<String>,
+    /// An access token of the account to post as
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl MatrixSettings {
+    pub fn is_empty(&self) -> bool {
+        self == &MatrixSettings::default()
+    }
+}
+
+/// The account `import --from github` and `task sync-github` read issues
+/// as.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct GithubSettings {
+    /// A personal access token; `$GITHUB_TOKEN` when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// The login whose assigned issues are imported; the token's owner
+    /// when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+/// The parsed YAML config. Every setting is optional, and whoever reads
+/// one supplies its default; [`Config::defaults`] lists them all. Parsing
+/// fails on unknown keys, and [`Config::check`] catches bad values that
+/// serde takes as plain strings.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub contents: Contents,
+    /// Database files by name, for `--db` to pick from, see [`database`]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub databases: BTreeMap<String, String>,
+    /// Priority thresholds to color names, see [`priority_colors`]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub colors: BTreeMap<String, String>,
+    pub theme: ThemeSettings,
+    /// The tz database zone, like `Europe/Berlin`, that dates are typed
+    /// and shown in; the system's when unset. See [`apply_timezone`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    pub dates: DateSettings,
+    pub tasks: TaskSettings,
+    pub archive: AfterSettings,
+    pub trash: AfterSettings,
+    pub daemon: DaemonSettings,
+    pub sync: SyncSettings,
+    pub next: NextSettings,
+    pub urgency: UrgencySettings,
+    pub journal: JournalSettings,
+    pub notes: NoteSettings,
+    pub backup: BackupSettings,
+    pub hooks: HookSettings,
+    pub notify: NotifySettings,
+    pub github: GithubSettings,
+    /// The project picked for this invocation with `--project`
+    #[serde(skip)]
+    pub project: Option<String>,
+    /// The format picked for this invocation with `--output`
+    #[serde(skip)]
+    pub output: Option<String>,
+}
+
+/// Every `section.key` the config takes, besides `colors.<threshold>` and
+/// `databases.<name>`, and the one key outside a section.
+pub const KEYS: [&str; 69] = [
+    "contents.regia_db",
+    "contents.compress",
+    "theme.name",
+    "theme.header",
+    "theme.accent",
+    "theme.tag",
+    "theme.context",
+    "theme.overdue",
+    "theme.due_soon",
+    "theme.done",
+    "theme.error",
+    "theme.warning",
+    "theme.highlight",
+    "theme.low",
+    "theme.medium",
+    "theme.high",
+    "theme.critical",
+    "timezone",
+    "dates.format",
+    "dates.week_start",
+    "dates.clock",
+    "tasks.due_soon",
+    "tasks.author",
+    "tasks.workflow",
+    "tasks.stale_after",
+    "tasks.capacity",
+    "archive.after",
+    "trash.after",
+    "daemon.remind",
+    "daemon.interval",
+    "sync.repo",
+    "sync.remote",
+    "sync.branch",
+    "sync.auto",
+    "sync.caldav.url",
+    "sync.caldav.user",
+    "sync.caldav.password",
+    "next.priority",
+    "next.due",
+    "next.age",
+    "next.blocked",
+    "next.count",
+    "urgency.due_curve",
+    "urgency.due_within",
+    "urgency.due_raise",
+    "urgency.age_curve",
+    "urgency.age_over",
+    "urgency.age_raise",
+    "journal.template",
+    "journal.tag",
+    "notes.preview",
+    "notes.revisions",
+    "backup.dir",
+    "backup.keep",
+    "hooks.task_added",
+    "hooks.task_completed",
+    "hooks.task_overdue",
+    "hooks.note_added",
+    "hooks.task_unblocked",
+    "notify.slack",
+    "notify.matrix.homeserver",
+    "notify.matrix.room",
+    "notify.matrix.token",
+    "notify.agenda_at",
+    "notify.overdue",
+    "github.token",
+    "github.user",
+    // Not settings of their own, but `config get colors` shows them all
+    "colors",
+    "databases",
+];
+
+/// Where tz database zones are looked up, as chrono does.
+fn zoneinfo_dir() -> PathBuf {
+    std::env::var_os("TZDIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/usr/share/zoneinfo"))
+}
+
+fn check_timezone(value: &Option<String>) -> Result<(), String> {
+    match value {
+        Some(zone) if zone.trim().is_empty() || zone.contains("..") => {
+            Err(format!("bad timezone: {}", zone))
+        }
+        Some(zone) if zone != "UTC" && !zoneinfo_dir().join(zone).is_file() => {
+            Err(format!("unknown timezone: {}", zone))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_duration(key: &str, value: &Option<String>, never: bool) -> Result<(), String> {
+    match value {
+        Some(value) if never && value.trim() == "never" => Ok(()),
+        Some(value) if dates::parse_duration(value).is_none() => {
+            Err(format!("bad {} duration: {}", key, value))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl Config {
+    /// Reads a config from its sections as JSON objects, checking it.
+    pub fn from_value(value: Value) -> Result<Config, String> {
+        let config: Config = serde_json::from_value(value).map_err(|err| err.to_string())?;
+        config.check()?;
+        Ok(config)
+    }
+
+    /// Checks the settings that serde takes as plain strings.
+    pub fn check(&self) -> Result<(), String> {
+        for (threshold, color) in self.colors.iter() {
+            if threshold.trim().parse::<u32>().is_err() {
+                return Err(format!("bad colors threshold: {}", threshold));
+            }
+            if theme::parse_color(color).is_none() {
+                return Err(format!("bad colors.{} color: {}", threshold, color));
+            }
+        }
+        if let Some(name) = &self.theme.name {
+            if Theme::named(name).is_none() {
+                return Err(format!(
+                    "theme.name must be one of {}: {}",
+                    THEMES.join(", "),
+                    name
+                ));
+            }
+        }
+        for element in Element::ALL {
+            match self.theme.color(element) {
+                Some(color) if theme::parse_color(color).is_none() => {
+                    return Err(format!("bad theme.{} color: {}", element.key(), color));
+                }
+                _ => {}
+            }
+        }
+        for (name, path) in self.databases.iter() {
+            if name.trim().is_empty() || path.trim().is_empty() {
+                return Err(format!("bad databases entry: {}: {}", name, path));
+            }
+        }
+        check_timezone(&self.timezone)?;
+        if let Some(format) = &self.dates.format {
+            if !dates::is_date_format(format) {
+                return Err(format!("bad dates.format: {}", format));
+            }
+        }
+        if let Some(day) = &self.dates.week_start {
+            if dates::parse_weekday(&day.trim().to_lowercase()).is_none() {
+                return Err(format!("bad dates.week_start day: {}", day));
+            }
+        }
+        if let Some(clock) = &self.dates.clock {
+            if Clock::parse(clock).is_none() {
+                return Err(format!("dates.clock must be 24h or 12h: {}", clock));
+            }
+        }
+        check_duration("tasks.due_soon", &self.tasks.due_soon, false)?;
+        check_duration("tasks.stale_after", &self.tasks.stale_after, true)?;
+        check_duration("tasks.capacity", &self.tasks.capacity, false)?;
+        check_duration("archive.after", &self.archive.after, false)?;
+        check_duration("trash.after", &self.trash.after, true)?;
+        check_duration("daemon.remind", &self.daemon.remind, false)?;
+        check_duration("daemon.interval", &self.daemon.interval, false)?;
+        check_duration("urgency.due_within", &self.urgency.due_within, false)?;
+        check_duration("urgency.age_over", &self.urgency.age_over, false)?;
+        for (key, curve) in [
+            ("urgency.due_curve", &self.urgency.due_curve),
+            ("urgency.age_curve", &self.urgency.age_curve),
+        ] {
+            match curve {
+                Some(curve) if Curve::parse(curve).is_none() => {
+                    return Err(format!(
+                        "{} must be one of {}: {}",
+                        key,
+                        Curve::NAMES.join(", "),
+                        curve
+                    ));
+                }
+                _ => {}
+            }
+        }
+        if let Some(workflow) = &self.tasks.workflow {
+            Workflow::parse(workflow)?;
+        }
+        for (key, hook) in [
+            ("hooks.task_added", &self.hooks.task_added),
+            ("hooks.task_completed", &self.hooks.task_completed),
+            ("hooks.task_overdue", &self.hooks.task_overdue),
+            ("hooks.note_added", &self.hooks.note_added),
+            ("hooks.task_unblocked", &self.hooks.task_unblocked),
+        ] {
+            if hook.as_ref().is_some_and(|hook| hook.trim().is_empty()) {
+                return Err(format!("{} is empty", key));
+            }
+        }
+        if let Some(at) = &self.notify.agenda_at {
+            agenda_at(at)?;
+        }
+        let matrix = &self.notify.matrix;
+        let set = [&matrix.homeserver, &matrix.room, &matrix.token];
+        if !matrix.is_empty() && set.iter().any(|value| value.is_none()) {
+            return Err(String::from(
+                "notify.matrix needs all of homeserver, room and token",
+            ));
+        }
+        if self.notes.preview == Some(0) {
+            return Err(String::from("notes.preview must be at least 1"));
+        }
+        if self.notes.revisions == Some(0) {
+            return Err(String::from("notes.revisions must be at least 1"));
+        }
+        if self.backup.keep == Some(0) {
+            return Err(String::from("backup.keep must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// Every setting that has a default, set to it. This is what
+    /// `regia config init` writes.
+    pub fn defaults() -> Config {
+        Config {
+            contents: Contents {
+                regia_db: Some(String::from(LOCAL_DB)),
+                compress: Some(false),
+            },
+            databases: BTreeMap::new(),
+            colors: BTreeMap::new(),
+            theme: ThemeSettings {
+                name: Some(String::from(THEMES[0])),
+                ..ThemeSettings::default()
+            },
+            timezone: None,
+            dates: DateSettings {
+                format: Some(String::from("%Y-%m-%d")),
+                week_start: Some(String::from("mon")),
+                clock: Some(String::from("24h")),
+            },
+            tasks: TaskSettings {
+                due_soon: Some(String::from("1d")),
+                author: None,
+                workflow: Some(String::from(WORKFLOW)),
+                stale_after: Some(format!("{}d", STALE_DAYS)),
+                capacity: Some(format!("{}h", CAPACITY_HOURS)),
+            },
+            archive: AfterSettings::default(),
+            trash: AfterSettings {
+                after: Some(String::from("30d")),
+            },
+            daemon: DaemonSettings {
+                remind: Some(String::from("15m")),
+                interval: Some(String::from("1m")),
+            },
+            sync: SyncSettings {
+                repo: None,
+                remote: None,
+                branch: Some(String::from("main")),
+                auto: Some(true),
+                caldav: CaldavSettings::default(),
+            },
+            next: NextSettings {
+                priority: Some(1.0),
+                due: Some(4.0),
+                age: Some(1.0),
+                blocked: Some(10.0),
+                count: Some(5),
+            },
+            urgency: UrgencySettings {
+                due_curve: None,
+                due_within: Some(format!("{}d", URGENCY_DUE_DAYS)),
+                due_raise: Some(3),
+                age_curve: None,
+                age_over: Some(format!("{}d", URGENCY_AGE_DAYS)),
+                age_raise: Some(1),
+            },
+            journal: JournalSettings {
+                template: Some(String::from(JOURNAL_TEMPLATE)),
+                tag: Some(String::from("journal")),
+            },
+            notes: NoteSettings {
+                preview: Some(NOTE_PREVIEW),
+                revisions: Some(NOTE_REVISIONS),
+            },
+            backup: BackupSettings {
+                dir: None,
+                keep: Some(BACKUP_KEEP),
+            },
+            hooks: HookSettings::default(),
+            notify: NotifySettings {
+                slack: None,
+                matrix: MatrixSettings::default(),
+                agenda_at: None,
+                overdue: Some(true),
+            },
+            github: GithubSettings::default(),
+            project: None,
+            output: None,
+        }
+    }
+
+    /// The sections as JSON objects, leaving out unset keys.
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+
+    /// The value of `section.key` (or `section.group.key`, or `timezone`),
+    /// `None` when it
+    /// is unset. Fails for keys the config doesn't have.
+    pub fn get(&self, key: &str) -> Result<Option<Value>, String> {
+        let known = match key.split_once('.') {
+            Some(("colors", threshold)) => threshold.parse::<u32>().is_ok(),
+            Some(("databases", name)) => !name.is_empty() && !name.contains('.'),
+            _ => KEYS.contains(&key),
+        };
+        if !known {
+            return Err(format!("no such setting: {}", key));
+        }
+        Ok(self.to_value().pointer(&pointer(key)).cloned())
+    }
+
+    /// Sets `section.key` to `value`, which is parsed as YAML would so that
+    /// numbers and booleans keep their types. The result must still be a
+    /// valid config.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let section = match key.split_once('.') {
+            Some((section, _)) => section,
+            None if KEYS.contains(&key) => key,
+            None => return Err(format!("give a key as section.name: {}", key)),
+        };
+        self.get(key)?;
+        let parsed = if section == "colors"
+            || section == "databases"
+            || section == "theme"
+            || section == "dates"
+            || key == "contents.regia_db"
+            || key == "backup.dir"
+            || key == "timezone"
+        {
+            Value::String(value.to_string())
+        } else {
+            match value.trim() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                text => match text.parse::<serde_json::Number>() {
+                    Ok(number) => Value::Number(number),
+                    Err(_) => Value::String(value.to_string()),
+                },
+            }
+        };
+        let mut whole = self.to_value();
+        let (path, name) = key.rsplit_once('.').unwrap_or(("", key));
+        let mut values = &mut whole;
+        for part in path.split('.').filter(|part| !part.is_empty()) {
+            values = match values {
+                Value::Object(map) => map
+                    .entry(part.to_string())
+                    .or_insert_with(|| Value::Object(Default::default())),
+                _ => break,
+            };
+        }
+        if let Some(values) = values.as_object_mut() {
+            values.insert(name.to_string(), parsed);
+        }
+        let updated = Config::from_value(whole)?;
+        *self = Config {
+            project: self.project.take(),
+            output: self.output.take(),
+            ..updated
+        };
+        Ok(())
+    }
+
+    /// Lays `local` over this config: its settings replace these key by
+    /// key.
+    fn merge(&mut self, local: &Config) {
+        let mut merged = self.to_value();
+        merge_values(&mut merged, local.to_value());
+        if let Ok(updated) = serde_json::from_value::<Config>(merged) {
+            *self = Config {
+                project: self.project.take(),
+                output: self.output.take(),
+                ..updated
+            };
+        }
+    }
+}
+
+/// The JSON pointer to a dotted config key.
+fn pointer(key: &str) -> String {
+    format!("/{}", key.replace('.', "/"))
+}
+
+/// Lays the objects in `theirs` over those in `ours`, key by key at every
+/// level; anything else in `theirs` replaces what `ours` has.
+fn merge_values(ours: &mut Value, theirs: Value) {
+    match (ours, theirs) {
+        (Value::Object(ours), Value::Object(theirs)) => {
+            for (name, value) in theirs {
+                match ours.get_mut(&name) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        ours.insert(name, value);
+                    }
+                }
+            }
+        }
+        (ours, theirs) => *ours = theirs,
+    }
+}
+
+/// The config file a project directory may carry.
+pub const LOCAL_CONFIG: &str = ".regia.yml";
+
+/// The database a project directory may carry.
+pub const LOCAL_DB: &str = ".regia.db";
+
+/// Walks up from `dir` to the nearest directory holding a `.regia.yml` or
+/// a `.regia.db`, the way git finds `.git`.
+pub fn discover(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| dir.join(LOCAL_CONFIG).is_file() || dir.join(LOCAL_DB).is_file())
+        .map(Path::to_path_buf)
+}
+
+/// The sections a `.regia.yml` may not set. A project directory can come
+/// from anyone, with a cloned repository, and these run commands or send
+/// tasks and credentials elsewhere, so they stay as the user's own config
+/// has them.
+pub const GLOBAL_ONLY: [&str; 4] = ["hooks", "notify", "github", "sync"];
+
+/// Layers the project directory `dir` and its parsed `.regia.yml` (empty
+/// when it has none) over `doc`. Its settings replace the global ones key
+/// by key, and the database becomes its `regia_db`, taken relative to
+/// `dir`, or else `dir/.regia.db`. Returns the [`GLOBAL_ONLY`] sections it
+/// set, which are left out.
+pub fn overlay(doc: &mut Config, dir: &Path, local: Config) -> Vec<&'static str> {
+    let db = dir.join(local.contents.regia_db.as_deref().unwrap_or(LOCAL_DB));
+    let mut values = local.to_value();
+    let mut ignored = Vec::new();
+    if let Some(sections) = values.as_object_mut() {
+        for section in GLOBAL_ONLY {
+            let set = sections
+                .remove(section)
+                .is_some_and(|values| values.as_object().is_none_or(|values| !values.is_empty()));
+            if set {
+                ignored.push(section);
+            }
+        }
+    }
+    if let Ok(local) = serde_json::from_value::<Config>(values) {
+        doc.merge(&local);
+    }
+    doc.contents.regia_db = Some(db.to_string_lossy().into_owned());
+    ignored
+}
+
+/// Reads the `colors` section, which maps priority thresholds to color
+/// names, into the ascending `(threshold, color)` list `Task::fmt` expects:
+///
+/// ```yaml
+/// colors:
+///   "2": white
+///   "5": yellow
+///   "10": red
+/// ```
+///
+/// A task takes the color of the first threshold its priority is below.
+pub fn priority_colors(doc: &Config) -> error::Result<Vec<(u32, String)>> {
+    let mut colors = Vec::new();
+    for (threshold, color) in doc.colors.iter() {
+        match threshold.trim().parse::<u32>() {
+            Ok(threshold) => colors.push((threshold, color.clone())),
+            Err(_) => {
+                return Err(RegiaError::Config(format!(
+                    "bad colors threshold: {}",
+                    threshold
+                )))
+            }
+        }
+    }
+    colors.sort();
+    Ok(colors)
+}
+
+/// Borrows a `priority_colors` list in the form `Task::fmt` takes.
+pub fn as_priority_map(colors: &[(u32, String)]) -> Vec<(u32, &str)> {
+    colors
+        .iter()
+        .map(|(threshold, color)| (*threshold, color.as_str()))
+        .collect()
+}
+
+/// Parses the duration setting `key` holding `value`, if it is set.
+pub fn duration(key: &str, value: &Option<String>) -> error::Result<Option<Duration>> {
+    match value {
+        Some(value) => match dates::parse_duration(value) {
+            Some(span) => Ok(Some(span)),
+            None => Err(RegiaError::Config(format!(
+                "bad {} duration: {}",
+                key, value
+            ))),
+        },
+        None => Ok(None),
+    }
+}
+
+/// How far ahead `task ls` highlights due dates, from `tasks.due_soon`
+/// (default `1d`).
+pub fn due_soon(doc: &Config) -> error::Result<Duration> {
+    Ok(duration("tasks.due_soon", &doc.tasks.due_soon)?.unwrap_or(Duration::days(1)))
+}
+
+/// How long open tasks go untouched before they are stale, from
+/// `tasks.stale_after` (default [`STALE_DAYS`] days); `None` for `never`.
+pub fn stale_after(doc: &Config) -> error::Result<Option<Duration>> {
+    match doc.tasks.stale_after.as_deref() {
+        Some(value) if value.trim() == "never" => Ok(None),
+        _ => Ok(Some(
+            duration("tasks.stale_after", &doc.tasks.stale_after)?
+                .unwrap_or(Duration::days(STALE_DAYS)),
+        )),
+    }
+}
+
+/// How much estimated work a day holds, from `tasks.capacity` (default
+/// [`CAPACITY_HOURS`] hours).
+pub fn capacity(doc: &Config) -> error::Result<Duration> {
+    Ok(duration("tasks.capacity", &doc.tasks.capacity)?.unwrap_or(Duration::hours(CAPACITY_HOURS)))
+}
+
+/// Points [`dates::Local`] at `timezone`, when it is set, for the rest of
+/// this process. chrono reads the zone from `TZ`, so this sets that; call
+/// it once at startup, before any other threads.
+pub fn apply_timezone(doc: &Config) {
+    if let Some(zone) = &doc.timezone {
+        std::env::set_var("TZ", zone);
+    }
+}
+
+/// How dates are shown and weeks counted, from the `dates` section.
+pub fn locale(doc: &Config) -> Locale {
+    let fallback = Locale::default();
+    Locale {
+        date_format: doc.dates.format.clone().unwrap_or(fallback.date_format),
+        week_start: doc
+            .dates
+            .week_start
+            .as_deref()
+            .and_then(|day| dates::parse_weekday(&day.trim().to_lowercase()))
+            .unwrap_or(fallback.week_start),
+        clock: doc
+            .dates
+            .clock
+            .as_deref()
+            .and_then(Clock::parse)
+            .unwrap_or(fallback.clock),
+    }
+}
+
+/// Makes the locale of the `dates` section the one dates are shown in.
+pub fn apply_locale(doc: &Config) {
+    dates::set_locale(locale(doc));
+}
+
+/// The colors of the `theme` section: its named theme, default
+/// [`THEMES`]`[0]`, with the colors it sets laid over.
+pub fn theme(doc: &Config) -> Theme {
+    let mut theme = doc
+        .theme
+        .name
+        .as_deref()
+        .and_then(Theme::named)
+        .unwrap_or_default();
+    for element in Element::ALL {
+        if let Some(color) = doc.theme.color(element).and_then(theme::parse_color) {
+            theme.set(element, color);
+        }
+    }
+    theme
+}
+
+/// Makes the colors of the `theme` section the ones output is shown in.
+pub fn apply_theme(doc: &Config) {
+    theme::set_theme(theme(doc));
+}
+
+/// One rule of the `urgency` section, if its curve is set.
+fn urgency_rule(
+    curve: &Option<String>,
+    key: &str,
+    window: &Option<String>,
+    default_days: i64,
+    raise: u32,
+) -> error::Result<Option<Rule>> {
+    let curve = match curve {
+        Some(name) => Curve::parse(name)
+            .ok_or_else(|| RegiaError::Config(format!("bad urgency curve: {}", name)))?,
+        None => return Ok(None),
+    };
+    Ok(Some(Rule {
+        curve,
+        window: duration(key, window)?.unwrap_or(Duration::days(default_days)),
+        raise,
+    }))
+}
+
+/// The rules of the `urgency` section, or `None` while neither has a
+/// curve. A due date adds up to 3 points over the last
+/// [`URGENCY_DUE_DAYS`] days and age up to 1 over [`URGENCY_AGE_DAYS`],
+/// unless the section says otherwise.
+pub fn urgency(doc: &Config) -> error::Result<Option<Urgency>> {
+    let settings = &doc.urgency;
+    let urgency = Urgency {
+        due: urgency_rule(
+            &settings.due_curve,
+            "urgency.due_within",
+            &settings.due_within,
+            URGENCY_DUE_DAYS,
+            settings.due_raise.unwrap_or(3),
+        )?,
+        age: urgency_rule(
+            &settings.age_curve,
+            "urgency.age_over",
+            &settings.age_over,
+            URGENCY_AGE_DAYS,
+            settings.age_raise.unwrap_or(1),
+        )?,
+    };
+    if urgency.due.is_none() && urgency.age.is_none() {
+        Ok(None)
+    } else {
+        Ok(Some(urgency))
+    }
+}
+
+/// The statuses tasks move through, from `tasks.workflow` (default
+/// [`WORKFLOW`]).
+pub fn workflow(doc: &Config) -> error::Result<Workflow> {
+    match &doc.tasks.workflow {
+        Some(workflow) => Workflow::parse(workflow).map_err(RegiaError::Config),
+        None => Ok(Workflow::default()),
+    }
+}
+
+/// Reads `notify.agenda_at`, a local time of day like `08:00`.
+pub fn agenda_at(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map_err(|_| format!("bad notify.agenda_at, give a time like 08:00: {}", value))
+}
+
+/// How many characters of a note `note ls` shows, from `notes.preview`
+/// (default [`NOTE_PREVIEW`]).
+pub fn note_preview(doc: &Config) -> usize {
+    doc.notes.preview.unwrap_or(NOTE_PREVIEW)
+}
+
+/// How many earlier revisions of each note to keep, from
+/// `notes.revisions` (default [`NOTE_REVISIONS`]).
+pub fn note_revisions(doc: &Config) -> usize {
+    doc.notes.revisions.unwrap_or(NOTE_REVISIONS)
+}
+
+/// How many backups to keep, from `backup.keep` (default
+/// [`BACKUP_KEEP`]).
+pub fn backup_keep(doc: &Config) -> usize {
+    doc.backup.keep.unwrap_or(BACKUP_KEEP)
+}
+
+/// The database file `--db` means by `name`: the path `databases` gives
+/// it, or else `name` itself as a path.
+pub fn database<'a>(doc: &'a Config, name: &'a str) -> &'a str {
+    doc.databases.get(name).map_or(name, String::as_str)
+}
+
+/// Whether to compress the database file, from `contents.compress`
+/// (default off).
+pub fn compress(doc: &Config) -> bool {
+    doc.contents.compress.unwrap_or(false)
+}
+
+/// Who comments are from: `tasks.author`, or else the login name.
+pub fn author(doc: &Config) -> String {
+    doc.tasks
+        .author
+        .clone()
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_else(|| String::from("me"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discovers_and_overlays_project_dirs() {
+        let root = tempfile::tempdir().unwrap();
+        let project = root.path().join("project");
+        let nested = project.join("src/deep");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        assert_eq!(discover(&nested), None);
+        std::fs::write(project.join(LOCAL_DB), b"").unwrap();
+        assert_eq!(discover(&nested), Some(project.clone()));
+
+        let mut doc = Config::default();
+        doc.contents.regia_db = Some(String::from("/global.db"));
+        doc.colors.insert(String::from("5"), String::from("red"));
+        doc.tasks.due_soon = Some(String::from("2d"));
+        let mut global = doc.clone();
+
+        overlay(&mut doc, &project, Config::default());
+        assert_eq!(
+            doc.contents.regia_db.as_deref().unwrap(),
+            project.join(LOCAL_DB).to_string_lossy()
+        );
+        assert_eq!(doc.colors["5"], "red");
+        assert!(overlay(&mut doc, &project, Config::default()).is_empty());
+
+        let mut local = Config::default();
+        local.contents.regia_db = Some(String::from("tasks.db"));
+        local.colors.insert(String::from("5"), String::from("blue"));
+        local.tasks.due_soon = Some(String::from("3d"));
+        local.sync.branch = Some(String::from("work"));
+        local.hooks.task_added = Some(String::from("touch /tmp/pwned"));
+        local.notify.slack = Some(String::from("https://example.com/hook"));
+        local.github.token = Some(String::from("theirs"));
+        assert_eq!(
+            overlay(&mut global, &project, local),
+            ["hooks", "notify", "github", "sync"]
+        );
+        assert_eq!(
+            global.contents.regia_db.as_deref().unwrap(),
+            project.join("tasks.db").to_string_lossy()
+        );
+        assert_eq!(global.colors["5"], "blue");
+        assert_eq!(global.tasks.due_soon.as_deref(), Some("3d"));
+        // A project directory can't run commands or send data away
+        assert_eq!(global.hooks, HookSettings::default());
+        assert_eq!(global.notify.slack, None);
+        assert_eq!(global.github.token, None);
+        assert_eq!(global.sync.branch, None);
+    }
+
+    #[test]
+    fn rejects_bad_config_and_sets_keys() {
+        let parse = |text: &str| Config::from_value(serde_json::from_str(text).unwrap());
+        let err = parse(r#"{"contents": {"regia_bd": "x"}}"#).unwrap_err();
+        assert!(err.contains("unknown field `regia_bd`"), "{}", err);
+        assert!(parse(r#"{"trash": {"after": "soon"}}"#).is_err());
+        assert!(parse(r#"{"colors": {"high": "red"}}"#).is_err());
+
+        let mut doc = parse(r#"{"trash": {"after": "never"}}"#).unwrap();
+        doc.set("next.count", "3").unwrap();
+        doc.set("sync.auto", "false").unwrap();
+        doc.set("colors.5", "red").unwrap();
+        doc.set("databases.work", "/work.db").unwrap();
+        assert_eq!(database(&doc, "work"), "/work.db");
+        assert_eq!(database(&doc, "other.db"), "other.db");
+        assert!(doc.set("databases.work", " ").is_err());
+        assert_eq!(doc.next.count, Some(3));
+        assert_eq!(doc.sync.auto, Some(false));
+        assert_eq!(doc.get("colors.5").unwrap(), Some(Value::from("red")));
+        assert_eq!(doc.get("trash.after").unwrap(), Some(Value::from("never")));
+        assert_eq!(doc.get("sync.remote").unwrap(), None);
+        assert!(doc.get("sync.bogus").is_err());
+        assert!(doc.set("next.count", "many").is_err());
+        assert!(doc.set("daemon.remind", "later").is_err());
+        assert_eq!(doc.next.count, Some(3));
+
+        doc.set("dates.week_start", "Sunday").unwrap();
+        doc.set("dates.clock", "12h").unwrap();
+        assert_eq!(locale(&doc).week_start, chrono::Weekday::Sun);
+        assert_eq!(locale(&doc).clock, Clock::H12);
+        assert!(doc.set("dates.clock", "13h").is_err());
+        assert!(doc.set("dates.format", "%Q").is_err());
+        doc.set("theme.name", "mono").unwrap();
+        doc.set("theme.overdue", "bright red").unwrap();
+        assert_eq!(
+            theme(&doc).color(Element::Overdue),
+            Some(colored::Color::BrightRed)
+        );
+        assert_eq!(theme(&doc).color(Element::Header), None);
+        assert!(doc.set("theme.name", "neon").is_err());
+        assert!(doc.set("theme.header", "mauve").is_err());
+        assert!(doc.set("colors.7", "mauve").is_err());
+        doc.set("timezone", "UTC").unwrap();
+        assert_eq!(doc.get("timezone").unwrap(), Some(Value::from("UTC")));
+        assert!(doc.set("timezone", "Mars/Olympus_Mons").is_err());
+        assert!(doc.set("bogus", "1").is_err());
+        doc.set("notes.preview", "80").unwrap();
+        assert_eq!(note_preview(&doc), 80);
+        assert!(doc.set("notes.preview", "0").is_err());
+        doc.set("notify.agenda_at", "08:30").unwrap();
+        assert!(doc.set("notify.agenda_at", "half eight").is_err());
+        assert!(doc.set("notify.matrix.room", "!room:example.com").is_err());
+        assert_eq!(urgency(&doc).unwrap(), None);
+        doc.set("urgency.due_curve", "exponential").unwrap();
+        doc.set("urgency.due_raise", "6").unwrap();
+        let due = urgency(&doc).unwrap().unwrap().due.unwrap();
+        assert_eq!((due.window, due.raise), (Duration::days(14), 6));
+        assert!(doc.set("urgency.age_curve", "sigmoid").is_err());
+        assert!(doc.set("urgency.age_over", "a while").is_err());
+
+        doc.set("sync.caldav.url", "https://dav.example.com/tasks/")
+            .unwrap();
+        assert_eq!(
+            doc.get("sync.caldav.url").unwrap(),
+            Some(Value::from("https://dav.example.com/tasks/"))
+        );
+        assert_eq!(doc.get("sync.caldav.user").unwrap(), None);
+        assert!(doc.get("sync.caldav.bogus").is_err());
+
+        // Project settings in a group keep the global ones beside them
+        let mut local = Config::default();
+        local.sync.caldav.user = Some(String::from("me"));
+        doc.merge(&local);
+        assert_eq!(doc.sync.caldav.user.as_deref(), Some("me"));
+        assert!(doc.sync.caldav.url.is_some());
+    }
+}
@@ -0,0 +1,35 @@
+//! `@context` labels for where a task can be done, like `@home` or
+//! `@errands`. Unlike tags they narrow a listing to what fits where you
+//! are, so a task with no context fits anywhere.
+
+use std::collections::HashSet;
+
+use crate::theme::Themed;
+
+/// Contexts are stored without a leading `@` so `@home` and `home` agree.
+pub fn normalize(context: &str) -> String {
+    context.trim().trim_start_matches('@').to_string()
+}
+
+pub fn from_args<'a, I: IntoIterator<Item = &'a str>>(args: I) -> HashSet<String> {
+    args.into_iter()
+        .map(normalize)
+        .filter(|context| !context.is_empty())
+        .collect()
+}
+
+/// True when a task with `contexts` can be done in `wanted`: it names
+/// that context or none at all.
+pub fn fits(contexts: &HashSet<String>, wanted: &str) -> bool {
+    contexts.is_empty() || contexts.contains(wanted)
+}
+
+pub fn fmt_contexts(contexts: &HashSet<String>) -> String {
+    let mut sorted: Vec<&String> = contexts.iter().collect();
+    sorted.sort();
+    sorted
+        .iter()
+        .map(|context| format!("@{}", context).context().to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
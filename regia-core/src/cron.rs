@@ -0,0 +1,204 @@
+//! Five-field cron expressions (`minute hour day-of-month month
+//! day-of-week`) for repeated tasks, evaluated on local clocks.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Timelike, Utc};
+
+use crate::dates;
+
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// How far ahead `next_after` looks before deciding an expression never
+/// fires. Four years, so that `29 2` still matches.
+const HORIZON_DAYS: u32 = 4 * 366;
+
+/// A parsed expression: one bit per allowed value of each field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    /// Bit 0 is Sunday
+    weekdays: u64,
+    /// Cron matches a day on either field when both are restricted, and
+    /// only on the restricted one otherwise
+    any_day: bool,
+    any_weekday: bool,
+}
+
+fn value(text: &str, names: &[&str]) -> Option<u32> {
+    let lower = text.to_ascii_lowercase();
+    match names.iter().position(|name| *name == lower) {
+        Some(index) => Some(index as u32),
+        None => text.parse().ok(),
+    }
+}
+
+/// Parses one field of comma-separated values, `lo-hi` ranges, `*` and
+/// `/step`s into a bit mask.
+fn field(text: &str, min: u32, max: u32, names: &[&str]) -> Option<u64> {
+    let mut mask = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<usize>().ok().filter(|step| *step > 0)?),
+            None => (part, 1),
+        };
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((lo, hi)) = range.split_once('-') {
+            (value(lo, names)?, value(hi, names)?)
+        } else {
+            let start = value(range, names)?;
+            // `5/15` runs from 5 to the end of the field
+            (start, if step > 1 { max } else { start })
+        };
+        if lo < min || hi > max || lo > hi {
+            return None;
+        }
+        for allowed in (lo..=hi).step_by(step) {
+            mask |= 1 << allowed;
+        }
+    }
+    Some(mask)
+}
+
+impl Cron {
+    /// Parses an expression such as `0 9 * * mon-fri` or `*/15 * * * *`.
+    pub fn parse(expr: &str) -> Option<Cron> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return None;
+        }
+        let mut weekdays = field(fields[4], 0, 7, &WEEKDAYS)?;
+        // 7 is Sunday too
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Some(Cron {
+            minutes: field(fields[0], 0, 59, &[])?,
+            hours: field(fields[1], 0, 23, &[])?,
+            days: field(fields[2], 1, 31, &[])?,
+            months: field(fields[3], 1, 12, &[])?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// The first minute strictly after `after` that matches on the clocks
+    /// of `zone`, or `None` if the expression never fires (like `0 0 30 2
+    /// *`).
+    pub fn next_after<Tz: TimeZone>(
+        &self,
+        after: DateTime<Utc>,
+        zone: &Tz,
+    ) -> Option<DateTime<Utc>> {
+        let local = after.with_timezone(zone).naive_local();
+        let start = local.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut date = start.date();
+        for _ in 0..HORIZON_DAYS {
+            if self.day_matches(date) {
+                let first_day = date == start.date();
+                let from_hour = if first_day { start.hour() } else { 0 };
+                for hour in (from_hour..24).filter(|hour| self.hours & (1 << hour) != 0) {
+                    let from_minute = if first_day && hour == from_hour {
+                        start.minute()
+                    } else {
+                        0
+                    };
+                    if let Some(minute) =
+                        (from_minute..60).find(|minute| self.minutes & (1 << minute) != 0)
+                    {
+                        let time = date.and_hms_opt(hour, minute, 0)?;
+                        return Some(dates::from_local(zone, &time));
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dates::tests::Berlin;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn finds_next_matching_minute() {
+        // 2019-10-04 is a Friday
+        let weekday_mornings = Cron::parse("0 9 * * mon-fri").unwrap();
+        assert_eq!(
+            weekday_mornings.next_after(at(2019, 10, 4, 8, 30), &Utc),
+            Some(at(2019, 10, 4, 9, 0))
+        );
+        assert_eq!(
+            weekday_mornings.next_after(at(2019, 10, 4, 9, 0), &Utc),
+            Some(at(2019, 10, 7, 9, 0))
+        );
+
+        let quarter_hours = Cron::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            quarter_hours.next_after(at(2019, 10, 4, 23, 50), &Utc),
+            Some(at(2019, 10, 5, 0, 0))
+        );
+
+        // Either the 1st or a Sunday when both day fields are restricted
+        let either = Cron::parse("30 12 1 * 0").unwrap();
+        assert_eq!(
+            either.next_after(at(2019, 10, 1, 13, 0), &Utc),
+            Some(at(2019, 10, 6, 12, 30))
+        );
+
+        let leap = Cron::parse("0 0 29 2 *").unwrap();
+        assert_eq!(
+            leap.next_after(at(2019, 10, 4, 0, 0), &Utc),
+            Some(at(2020, 2, 29, 0, 0))
+        );
+        assert_eq!(
+            Cron::parse("0 0 30 2 *")
+                .unwrap()
+                .next_after(at(2019, 1, 1, 0, 0), &Utc),
+            None
+        );
+    }
+
+    #[test]
+    fn follows_local_clocks() {
+        // Nine in Berlin is 07:00 UTC in summer and 08:00 UTC after it
+        let nine = Cron::parse("0 9 * * *").unwrap();
+        assert_eq!(
+            nine.next_after(at(2019, 10, 26, 7, 0), &Berlin),
+            Some(at(2019, 10, 27, 8, 0))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_fields() {
+        assert!(Cron::parse("0 9 * *").is_none());
+        assert!(Cron::parse("60 9 * * *").is_none());
+        assert!(Cron::parse("0 9 0 * *").is_none());
+        assert!(Cron::parse("0 9 * * fri-mon").is_none());
+        assert!(Cron::parse("*/0 * * * *").is_none());
+        assert_eq!(Cron::parse("0 0 * * 7"), Cron::parse("0 0 * * sun"));
+    }
+}
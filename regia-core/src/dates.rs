@@ -0,0 +1,523 @@
+//! Parsing and formatting of due dates and durations.
+//!
+//! Dates are stored in UTC but typed and shown in a timezone: the
+//! system's, as [`Local`] reads it, which the `timezone` config key sets.
+//! How they are shown, and which day weeks start on, is the [`Locale`].
+
+use std::sync::{Mutex, PoisonError};
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{
+    DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone, Utc, Weekday,
+};
+
+pub use chrono::Local;
+
+/// Whether times are shown on a 24-hour clock or a 12-hour one.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Clock {
+    #[default]
+    H24,
+    H12,
+}
+
+impl Clock {
+    /// Reads `24h` or `12h`.
+    pub fn parse(word: &str) -> Option<Clock> {
+        match word.trim().to_lowercase().as_str() {
+            "24h" | "24" => Some(Clock::H24),
+            "12h" | "12" => Some(Clock::H12),
+            _ => None,
+        }
+    }
+}
+
+/// How dates and times are shown and weeks counted, from the `dates`
+/// config section. Like the timezone it holds for the whole process, see
+/// [`set_locale`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locale {
+    /// A strftime format for dates, like `%d.%m.%Y`
+    pub date_format: String,
+    pub week_start: Weekday,
+    pub clock: Clock,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            date_format: String::from("%Y-%m-%d"),
+            week_start: Weekday::Mon,
+            clock: Clock::H24,
+        }
+    }
+}
+
+impl Locale {
+    pub fn day(&self, date: NaiveDate) -> String {
+        date.format(&self.date_format).to_string()
+    }
+
+    /// A time of day, as `17:30` or `5:30pm`, both of which [`parse`]
+    /// reads back.
+    pub fn time(&self, time: NaiveTime) -> String {
+        let format = match self.clock {
+            Clock::H24 => "%H:%M",
+            Clock::H12 => "%-I:%M%P",
+        };
+        time.format(format).to_string()
+    }
+
+    pub fn datetime(&self, naive: NaiveDateTime) -> String {
+        format!("{} {}", self.day(naive.date()), self.time(naive.time()))
+    }
+
+    /// The first day of the week `date` is in.
+    pub fn week_of(&self, date: NaiveDate) -> NaiveDate {
+        let since = (7 + date.weekday().num_days_from_monday()
+            - self.week_start.num_days_from_monday())
+            % 7;
+        date - Duration::days(i64::from(since))
+    }
+}
+
+static LOCALE: Mutex<Option<Locale>> = Mutex::new(None);
+
+/// Sets how dates are shown and weeks counted from here on.
+pub fn set_locale(locale: Locale) {
+    *LOCALE.lock().unwrap_or_else(PoisonError::into_inner) = Some(locale);
+}
+
+/// The locale [`set_locale`] set, or the default one.
+pub fn locale() -> Locale {
+    LOCALE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .unwrap_or_default()
+}
+
+/// Whether `format` is a strftime format chrono can write.
+pub fn is_date_format(format: &str) -> bool {
+    !format.trim().is_empty() && StrftimeItems::new(format).all(|item| item != Item::Error)
+}
+
+/// Date-only inputs are due at the end of that day rather than at midnight,
+/// so "tomorrow" does not count as overdue for the whole of tomorrow.
+fn end_of_day() -> NaiveTime {
+    NaiveTime::from_hms_opt(23, 59, 59).unwrap()
+}
+
+/// The instant `naive` reads on the clocks of `zone`. A time the clocks
+/// pass twice as they go back is the first of them; one they skip as they
+/// go forward is read with the offset from before, landing as much after
+/// the jump as it was meant to be after the last time before it.
+pub fn from_local<Tz: TimeZone>(zone: &Tz, naive: &NaiveDateTime) -> DateTime<Utc> {
+    match zone.from_local_datetime(naive) {
+        LocalResult::Single(dt) => dt.with_timezone(&Utc),
+        // chrono's `Local` doesn't always give the earlier one first
+        LocalResult::Ambiguous(one, other) => one.min(other).with_timezone(&Utc),
+        LocalResult::None => {
+            let before = zone.offset_from_utc_datetime(&(*naive - Duration::days(1)));
+            let offset = Duration::seconds(i64::from(before.fix().local_minus_utc()));
+            Utc.from_utc_datetime(&(*naive - offset))
+        }
+    }
+}
+
+/// Parses a due date as typed on the command line, in local time unless it
+/// says otherwise.
+///
+/// Accepts RFC2822, RFC3339 and ISO-8601 dates (`2019-10-04`,
+/// `2019-10-04 17:00`), relative offsets (`in 3 days`, `2h`), and day words
+/// with an optional time (`tomorrow`, `next friday 5pm`, `mon at 9:30`).
+pub fn parse(input: &str) -> Option<DateTime<Utc>> {
+    parse_relative(input, Utc::now(), &Local)
+}
+
+/// `parse` with an explicit notion of "now", which relative inputs count
+/// from, and of the timezone dates and times without an offset are in.
+pub fn parse_relative<Tz: TimeZone>(
+    input: &str,
+    now: DateTime<Utc>,
+    zone: &Tz,
+) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc2822(input) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    if let Some(naive) = parse_iso(input) {
+        return Some(from_local(zone, &naive));
+    }
+
+    let lower = input.to_ascii_lowercase();
+    if lower == "now" {
+        return Some(now);
+    }
+    if let Some(rest) = lower.strip_prefix("in ") {
+        return parse_duration(rest).map(|offset| now + offset);
+    }
+    if let Some(offset) = parse_duration(&lower) {
+        return Some(now + offset);
+    }
+
+    let today = now.with_timezone(zone).date_naive();
+    parse_words(&lower, today).map(|naive| from_local(zone, &naive))
+}
+
+fn parse_iso(input: &str) -> Option<NaiveDateTime> {
+    for fmt in &[
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(input, fmt) {
+            return Some(naive);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Some(date.and_time(end_of_day()));
+    }
+    None
+}
+
+/// Parses spans like `30m`, `2h`, `3 days`, `1w` or `2 weeks`.
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim().to_ascii_lowercase();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (count, unit) = input.split_at(split);
+    let count: i64 = count.parse().ok()?;
+    match unit.trim() {
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(Duration::minutes(count)),
+        "h" | "hr" | "hrs" | "hour" | "hours" => Some(Duration::hours(count)),
+        "d" | "day" | "days" => Some(Duration::days(count)),
+        "w" | "wk" | "wks" | "week" | "weeks" => Some(Duration::weeks(count)),
+        _ => None,
+    }
+}
+
+/// Formats a span compactly using its two largest units, e.g. `2d 3h`.
+pub fn fmt_duration(span: Duration) -> String {
+    let minutes = span.num_minutes().abs();
+    let (days, hours, mins) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    if days > 0 && hours > 0 {
+        format!("{}d {}h", days, hours)
+    } else if days > 0 {
+        format!("{}d", days)
+    } else if hours > 0 && mins > 0 {
+        format!("{}h {}m", hours, mins)
+    } else if hours > 0 {
+        format!("{}h", hours)
+    } else {
+        format!("{}m", mins)
+    }
+}
+
+/// 00:00 UTC on the first day of the week containing `now`, as `locale`
+/// counts weeks.
+pub fn week_start(now: DateTime<Utc>, locale: &Locale) -> DateTime<Utc> {
+    let first = locale.week_of(now.date_naive());
+    Utc.from_utc_datetime(&first.and_time(NaiveTime::MIN))
+}
+
+/// Reads a day of the week, like `mon` or `sunday`, in lower case.
+pub fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thur" | "thurs" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Parses `5pm`, `5:30pm`, `17:00`, `noon` and `midnight`.
+fn parse_time(word: &str) -> Option<NaiveTime> {
+    match word {
+        "noon" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => {}
+    }
+
+    let (clock, offset) = if let Some(clock) = word.strip_suffix("am") {
+        (clock, Some(0))
+    } else if let Some(clock) = word.strip_suffix("pm") {
+        (clock, Some(12))
+    } else {
+        (word, None)
+    };
+
+    let mut parts = clock.splitn(2, ':');
+    let hour: u32 = parts.next()?.parse().ok()?;
+    let minute: u32 = match parts.next() {
+        Some(minute) => minute.parse().ok()?,
+        // A bare number is only a time with an am/pm marker
+        None if offset.is_none() => return None,
+        None => 0,
+    };
+
+    let hour = match offset {
+        Some(offset) if (1..=12).contains(&hour) => hour % 12 + offset,
+        Some(_) => return None,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+fn parse_words(input: &str, today: NaiveDate) -> Option<NaiveDateTime> {
+    let mut words = input.split_whitespace().peekable();
+    let mut date = None;
+    let mut time = None;
+
+    while let Some(word) = words.next() {
+        match word {
+            "at" | "on" => continue,
+            "today" => date = Some(today),
+            "tomorrow" => date = Some(today + Duration::days(1)),
+            "next" => {
+                let target = words.next()?;
+                if target == "week" {
+                    date = Some(today + Duration::weeks(1));
+                } else {
+                    let weekday = parse_weekday(target)?;
+                    date = Some(next_weekday(today, weekday, false));
+                }
+            }
+            word => {
+                if let Some(weekday) = parse_weekday(word) {
+                    date = Some(next_weekday(today, weekday, true));
+                } else if let Some(meridiem) = words.next_if(|next| *next == "am" || *next == "pm")
+                {
+                    // Allow "5 pm" as well as "5pm"
+                    time = Some(parse_time(&format!("{}{}", word, meridiem))?);
+                } else if let Some(parsed) = parse_time(word) {
+                    time = Some(parsed);
+                } else {
+                    return None;
+                }
+            }
+        }
+    }
+
+    match (date, time) {
+        (Some(date), Some(time)) => Some(date.and_time(time)),
+        (Some(date), None) => Some(date.and_time(end_of_day())),
+        (None, Some(time)) => Some(today.and_time(time)),
+        (None, None) => None,
+    }
+}
+
+/// The next `weekday` after `from`, or `from` itself when `inclusive`.
+fn next_weekday(from: NaiveDate, weekday: Weekday, inclusive: bool) -> NaiveDate {
+    let current = from.weekday().num_days_from_monday() as i64;
+    let target = weekday.num_days_from_monday() as i64;
+    let mut ahead = (target - current + 7) % 7;
+    if ahead == 0 && !inclusive {
+        ahead = 7;
+    }
+    from + Duration::days(ahead)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn last_sunday(year: i32, month: u32) -> NaiveDate {
+        let last = NaiveDate::from_ymd_opt(year, month + 1, 1)
+            .unwrap()
+            .pred_opt()
+            .unwrap();
+        last - Duration::days(i64::from(last.weekday().num_days_from_sunday()))
+    }
+
+    /// Central European time, for testing changes to and from summer time
+    /// without the tz database: UTC+1, or UTC+2 from 01:00 UTC on the last
+    /// Sunday of March until 01:00 UTC on the last Sunday of October.
+    #[derive(Clone, Copy, Debug)]
+    pub(crate) struct Berlin;
+
+    impl TimeZone for Berlin {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            Berlin
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            let fits: Vec<FixedOffset> = [2, 1]
+                .iter()
+                .map(|hours| FixedOffset::east_opt(hours * 3600).unwrap())
+                .filter(|offset| {
+                    let utc = *local - Duration::seconds(i64::from(offset.local_minus_utc()));
+                    self.offset_from_utc_datetime(&utc) == *offset
+                })
+                .collect();
+            match fits[..] {
+                [offset] => LocalResult::Single(offset),
+                [earlier, later] => LocalResult::Ambiguous(earlier, later),
+                _ => LocalResult::None,
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            let change = |month| last_sunday(utc.year(), month).and_hms_opt(1, 0, 0).unwrap();
+            let summer = *utc >= change(3) && *utc < change(10);
+            FixedOffset::east_opt(if summer { 7200 } else { 3600 }).unwrap()
+        }
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    // 2019-10-02 is a Wednesday
+    fn now() -> DateTime<Utc> {
+        at(2019, 10, 2, 10, 30)
+    }
+
+    #[test]
+    fn absolute_formats() {
+        assert_eq!(
+            parse_relative("Fri, 04 Oct 2019 17:00:00 +0000", now(), &Utc),
+            Some(at(2019, 10, 4, 17, 0))
+        );
+        assert_eq!(
+            parse_relative("2019-10-04T17:00:00Z", now(), &Utc),
+            Some(at(2019, 10, 4, 17, 0))
+        );
+        assert_eq!(
+            parse_relative("2019-10-04 17:00", now(), &Utc),
+            Some(at(2019, 10, 4, 17, 0))
+        );
+        assert_eq!(
+            parse_relative("2019-10-04", now(), &Utc),
+            Some(Utc.with_ymd_and_hms(2019, 10, 4, 23, 59, 59).unwrap())
+        );
+    }
+
+    #[test]
+    fn relative_offsets() {
+        assert_eq!(
+            parse_relative("in 3 days", now(), &Utc),
+            Some(at(2019, 10, 5, 10, 30))
+        );
+        assert_eq!(
+            parse_relative("in 2h", now(), &Utc),
+            Some(at(2019, 10, 2, 12, 30))
+        );
+        assert_eq!(
+            parse_relative("1w", now(), &Utc),
+            Some(at(2019, 10, 9, 10, 30))
+        );
+        assert_eq!(parse_duration("30 minutes"), Some(Duration::minutes(30)));
+        assert_eq!(parse_duration("soon"), None);
+        assert_eq!(fmt_duration(Duration::minutes(30)), "30m");
+        assert_eq!(fmt_duration(Duration::minutes(-90)), "1h 30m");
+        assert_eq!(fmt_duration(Duration::hours(51)), "2d 3h");
+        assert_eq!(week_start(now(), &Locale::default()), at(2019, 9, 30, 0, 0));
+        let sunday = Locale {
+            week_start: Weekday::Sun,
+            ..Locale::default()
+        };
+        assert_eq!(week_start(now(), &sunday), at(2019, 9, 29, 0, 0));
+    }
+
+    #[test]
+    fn locales_show_dates_their_way() {
+        let locale = Locale {
+            date_format: String::from("%d.%m.%Y"),
+            week_start: Weekday::Sun,
+            clock: Clock::H12,
+        };
+        let naive = now().naive_utc();
+        assert_eq!(locale.datetime(naive), "02.10.2019 10:30am");
+        let evening = NaiveTime::from_hms_opt(17, 5, 0).unwrap();
+        assert_eq!(locale.time(evening), "5:05pm");
+        assert_eq!(Locale::default().time(evening), "17:05");
+        assert_eq!(parse_time(&locale.time(evening)), Some(evening));
+
+        let saturday = NaiveDate::from_ymd_opt(2019, 10, 5).unwrap();
+        assert_eq!(locale.week_of(saturday).day(), 29);
+        assert_eq!(Locale::default().week_of(saturday).day(), 30);
+        assert!(is_date_format("%a %e %b"));
+        assert!(!is_date_format("%Q"));
+        assert_eq!(Clock::parse("12H"), Some(Clock::H12));
+    }
+
+    #[test]
+    fn day_words() {
+        assert_eq!(
+            parse_relative("tomorrow 9am", now(), &Utc),
+            Some(at(2019, 10, 3, 9, 0))
+        );
+        assert_eq!(
+            parse_relative("next friday 5pm", now(), &Utc),
+            Some(at(2019, 10, 4, 17, 0))
+        );
+        assert_eq!(
+            parse_relative("next wed at 5:30 pm", now(), &Utc),
+            Some(at(2019, 10, 9, 17, 30))
+        );
+        assert_eq!(
+            parse_relative("wednesday noon", now(), &Utc),
+            Some(at(2019, 10, 2, 12, 0))
+        );
+        assert_eq!(
+            parse_relative("17:15", now(), &Utc),
+            Some(at(2019, 10, 2, 17, 15))
+        );
+        assert_eq!(parse_relative("next blursday", now(), &Utc), None);
+        assert_eq!(parse_relative("13pm", now(), &Utc), None);
+    }
+
+    #[test]
+    fn local_times() {
+        // Summer time, two hours ahead of UTC
+        assert_eq!(
+            parse_relative("tomorrow 9am", now(), &Berlin),
+            Some(at(2019, 10, 3, 7, 0))
+        );
+        assert_eq!(
+            parse_relative("2019-10-04T17:00:00Z", now(), &Berlin),
+            Some(at(2019, 10, 4, 17, 0))
+        );
+        // Past 22:00 UTC it is already tomorrow in Berlin
+        let late = at(2019, 12, 2, 23, 30);
+        assert_eq!(
+            parse_relative("today", late, &Berlin),
+            Some(Utc.with_ymd_and_hms(2019, 12, 3, 22, 59, 59).unwrap())
+        );
+        // 02:30 is skipped on 2019-03-31 and passed twice on 2019-10-27
+        assert_eq!(
+            parse_relative("2019-03-31 02:30", now(), &Berlin),
+            Some(at(2019, 3, 31, 1, 30))
+        );
+        assert_eq!(
+            parse_relative("2019-10-27 02:30", now(), &Berlin),
+            Some(at(2019, 10, 27, 0, 30))
+        );
+    }
+}
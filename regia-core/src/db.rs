@@ -0,0 +1,1052 @@
+//! The on-disk database: loading, saving, projects and imports.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{
+    BufReader, BufWriter, Error as IOError, ErrorKind as IOErrorKind, Read, Result as IOResult,
+    Write,
+};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+use uuid::Uuid;
+
+use crate::compress;
+use crate::conf::Config;
+use crate::error::{self, RegiaError};
+use crate::history;
+use crate::inbox;
+use crate::links;
+use crate::note::Notes;
+use crate::todo::Tasks;
+use crate::trash::Trash;
+use crate::view::View;
+use crate::wal;
+
+/// Writes `buf` to a temporary file beside `path`, syncs it and renames it
+/// over `path`, so a crash mid-write leaves the old contents in place.
+pub fn write_to_disk<P: AsRef<Path>>(path: P, buf: &[u8]) -> Result<(), IOError> {
+    let path = path.as_ref();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut temp = NamedTempFile::new_in(dir)?;
+    {
+        let mut stream = BufWriter::new(temp.as_file_mut());
+        stream.write_all(buf)?;
+        stream.flush()?;
+    }
+    temp.as_file().sync_all()?;
+    temp.persist(path).map_err(|err| err.error)?;
+
+    // Make the rename itself durable
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+/// A file kept next to the database, as `<db><suffix>`.
+pub fn sibling_path(db_path: &Path, suffix: &str) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// The previous version of the database, as it was before the last save,
+/// is kept as `<db>.bak`, with its log as `<db>.bak.wal`.
+pub fn backup_path(db_path: &Path) -> PathBuf {
+    sibling_path(db_path, ".bak")
+}
+
+/// Replaces `backup` with the current contents of `path`, if any.
+fn replace_backup(path: &Path, backup: &Path) -> Result<(), IOError> {
+    match fs::remove_file(backup) {
+        Err(err) if err.kind() != IOErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    // A hard link is free; fall back to a copy where links are unsupported
+    match fs::hard_link(path, backup) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == IOErrorKind::NotFound => Ok(()),
+        Err(_) => fs::copy(path, backup).map(|_| ()),
+    }
+}
+
+/// Replaces the backup of `path` and of its log with their current
+/// contents, so the backup reads back as the database did.
+fn rotate_backup(path: &Path) -> Result<(), IOError> {
+    let backup = backup_path(path);
+    replace_backup(path, &backup)?;
+    replace_backup(&wal::log_path(path), &wal::log_path(&backup))
+}
+
+/// [`rotate_backup`] for a save about to append to the log, which is
+/// written in place, so the log is copied rather than linked.
+fn rotate_backup_of_log(path: &Path) -> Result<(), IOError> {
+    let backup = backup_path(path);
+    replace_backup(path, &backup)?;
+    let log_backup = wal::log_path(&backup);
+    match fs::remove_file(&log_backup) {
+        Err(err) if err.kind() != IOErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    match fs::copy(wal::log_path(path), &log_backup) {
+        Err(err) if err.kind() != IOErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Marks a versioned database file. Files written before versioning are a
+/// bare msgpack payload, which can never start with these bytes.
+const MAGIC: &[u8; 4] = b"RGDB";
+/// Marks a versioned database file whose payload is compressed. The byte
+/// after it names the codec, so another can join [`CODEC_LZ77`] without
+/// a new magic or a migration.
+const COMPRESSED_MAGIC: &[u8; 4] = b"RGDZ";
+/// The codec byte of a payload [`compress`]ed with regia's own LZ77 codec.
+const CODEC_LZ77: u8 = 1;
+
+/// Upgrades a msgpack payload by one version. A migration decodes the old
+/// layout and re-encodes it in the next, so fields can be added, renamed or
+/// reshaped one step at a time.
+type Migration = fn(Vec<u8>) -> error::Result<Vec<u8>>;
+
+/// `MIGRATIONS[n]` upgrades a version `n` payload to version `n + 1`.
+const MIGRATIONS: [Migration; Database::VERSION as usize] = [from_unversioned];
+
+/// Unversioned files only ever gained trailing `#[serde(default)]` fields,
+/// which the version 1 layout already reads.
+fn from_unversioned(payload: Vec<u8>) -> error::Result<Vec<u8>> {
+    Ok(payload)
+}
+
+/// Splits a file into its schema version and payload, decompressing it
+/// if it is compressed.
+fn unwrap_envelope(buf: &[u8]) -> error::Result<(u32, Cow<'_, [u8]>)> {
+    let (rest, codec) = match (
+        buf.strip_prefix(&MAGIC[..]),
+        buf.strip_prefix(&COMPRESSED_MAGIC[..]),
+    ) {
+        (Some(rest), _) => (rest, None),
+        (_, Some([codec, rest @ ..])) => (rest, Some(*codec)),
+        (_, Some(_)) => return Err(RegiaError::corrupt("Truncated database header")),
+        _ => return Ok((0, Cow::Borrowed(buf))),
+    };
+    if rest.len() < 4 {
+        return Err(RegiaError::corrupt("Truncated database header"));
+    }
+    let (version, payload) = rest.split_at(4);
+    let version = u32::from_be_bytes([version[0], version[1], version[2], version[3]]);
+    match codec {
+        None => Ok((version, Cow::Borrowed(payload))),
+        Some(CODEC_LZ77) => Ok((version, Cow::Owned(compress::decompress(payload)?))),
+        Some(codec) => Err(RegiaError::corrupt(format!(
+            "Database is compressed with codec {}, which this regia can't read; upgrade regia",
+            codec
+        ))),
+    }
+}
+
+fn wrap_envelope(payload: &[u8], compressed: bool) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(MAGIC.len() + 5 + payload.len());
+    if compressed {
+        buf.extend_from_slice(COMPRESSED_MAGIC);
+        buf.push(CODEC_LZ77);
+    } else {
+        buf.extend_from_slice(MAGIC);
+    }
+    buf.extend_from_slice(&Database::VERSION.to_be_bytes());
+    match compressed {
+        true => buf.extend_from_slice(&compress::compress(payload)),
+        false => buf.extend_from_slice(payload),
+    }
+    buf
+}
+
+/// Whether a database file's payload is compressed.
+fn is_compressed(buf: &[u8]) -> bool {
+    buf.starts_with(COMPRESSED_MAGIC)
+}
+
+static COMPRESS: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether database files are compressed when they are next written
+/// whole, as `contents.compress` says. Files are read either way, and one
+/// in the other format is rewritten whole on its next save.
+pub fn set_compression(on: bool) {
+    COMPRESS.store(on, Ordering::Relaxed);
+}
+
+fn compressing() -> bool {
+    COMPRESS.load(Ordering::Relaxed)
+}
+
+/// Reads a database file's bytes, with the schema version it was written
+/// at.
+fn decode(buf: &[u8]) -> error::Result<(u32, Database)> {
+    let (version, payload) = unwrap_envelope(buf)?;
+    let payload = migrate(version, payload.into_owned())?;
+    Ok((version, Database::deserialize_msgpack(payload.as_slice())?))
+}
+
+/// Brings a payload written at `version` up to `Database::VERSION`.
+fn migrate(version: u32, mut payload: Vec<u8>) -> error::Result<Vec<u8>> {
+    if version > Database::VERSION {
+        return Err(RegiaError::corrupt(format!(
+            "Database is schema version {}, but this regia only reads up to version {}; upgrade regia",
+            version,
+            Database::VERSION
+        )));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        payload = migration(payload)?;
+    }
+    Ok(payload)
+}
+
+/// The name of the project kept directly in `Database::tasks`/`notes`.
+pub const ROOT_PROJECT: &str = "root";
+
+/// The database location from `contents.regia_db` in the config, or `.regia.db`.
+pub fn db_path(doc: &Config) -> &Path {
+    Path::new(doc.contents.regia_db.as_deref().unwrap_or(".regia.db"))
+}
+
+/// A project picked for this invocation with `--project`, which overrides
+/// the one saved by `regia project switch`.
+pub fn project_override(doc: &Config) -> Option<&str> {
+    doc.project.as_deref()
+}
+
+/// Loads the configured database with the project to work on active.
+pub fn open(doc: &Config) -> error::Result<Database> {
+    let mut db = Database::load(db_path(doc))?;
+    let project = match project_override(doc) {
+        Some(project) => Some(project.to_string()),
+        None => db.current_project.clone(),
+    };
+    db.activate(project.as_deref())?;
+    Ok(db)
+}
+
+/// The database [`hold`] keeps in memory, at rest.
+struct Held {
+    path: PathBuf,
+    db: Database,
+    /// Whether `db` changed since it was last written
+    dirty: bool,
+    /// Never written, see [`hold_dry`]
+    dry: bool,
+}
+
+static HELD: Mutex<Option<Held>> = Mutex::new(None);
+
+fn lock_held() -> MutexGuard<'static, Option<Held>> {
+    // A panic mid-update leaves nothing half-written worth refusing
+    HELD.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Keeps the database at `path` in memory, so that `Database::load` and
+/// `to_disk` on that path skip the file until [`release`]. This is for
+/// long-running callers such as `regia shell`, which should hold the
+/// database lock for as long.
+pub fn hold<P: AsRef<Path>>(path: P) -> error::Result<()> {
+    release()?;
+    let db = Database::load(path.as_ref())?;
+    *lock_held() = Some(Held {
+        path: path.as_ref().to_path_buf(),
+        db,
+        dirty: false,
+        dry: false,
+    });
+    Ok(())
+}
+
+/// Holds the database at `path` like [`hold`], but never writes it, for
+/// trying a command out: take what it would have saved with [`discard`].
+pub fn hold_dry<P: AsRef<Path>>(path: P) -> error::Result<()> {
+    hold(path)?;
+    if let Some(held) = lock_held().as_mut() {
+        held.dry = true;
+    }
+    Ok(())
+}
+
+/// Whether the database held is one from [`hold_dry`], that nothing
+/// beside it should be written for either.
+pub fn is_dry() -> bool {
+    lock_held().as_ref().is_some_and(|held| held.dry)
+}
+
+/// Stops holding the database without writing it, returning it as the
+/// changes since [`hold`] left it.
+pub fn discard() -> Option<Database> {
+    lock_held().take().map(|held| held.db)
+}
+
+/// Writes the held database to its file if it changed since it was read
+/// or last flushed.
+pub fn flush() -> error::Result<()> {
+    match lock_held().as_mut() {
+        Some(held) if held.dirty && !held.dry => {
+            save(&held.path, &held.db)?;
+            held.dirty = false;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Flushes the held database and goes back to using the file directly.
+pub fn release() -> error::Result<()> {
+    flush()?;
+    *lock_held() = None;
+    Ok(())
+}
+
+/// A database file as this process last read or wrote it, for the next
+/// save to log only what changed since.
+struct Saved {
+    db: Database,
+    /// The file's length and modification time
+    stamp: (u64, SystemTime),
+    /// The log's length, and how much of it held whole entries
+    log_len: u64,
+    log_valid: u64,
+    /// Whether the file is compressed
+    compressed: bool,
+}
+
+static SAVED: Mutex<BTreeMap<PathBuf, Saved>> = Mutex::new(BTreeMap::new());
+
+fn lock_saved() -> MutexGuard<'static, BTreeMap<PathBuf, Saved>> {
+    SAVED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn stamp(path: &Path) -> Result<(u64, SystemTime), IOError> {
+    let meta = fs::metadata(path)?;
+    Ok((meta.len(), meta.modified()?))
+}
+
+fn file_len(path: &Path) -> Result<u64, IOError> {
+    match fs::metadata(path) {
+        Ok(meta) => Ok(meta.len()),
+        Err(err) if err.kind() == IOErrorKind::NotFound => Ok(0),
+        Err(err) => Err(err),
+    }
+}
+
+/// Saves the at-rest `db` to `path`. What changed since this process read
+/// or wrote the file is appended to its log; the database is written whole
+/// instead when there is no such record, something else changed the files
+/// meanwhile, the file isn't compressed as [`set_compression`] asks, or the
+/// log would grow past half the database's size.
+fn save(path: &Path, db: &Database) -> error::Result<()> {
+    let log_path = wal::log_path(path);
+    let compressed = compressing();
+    let mut saved = lock_saved();
+    if let Some(last) = saved.get_mut(path) {
+        if stamp(path).ok() == Some(last.stamp)
+            && file_len(&log_path)? == last.log_len
+            && last.compressed == compressed
+        {
+            let deltas = wal::diff(&last.db, db);
+            if deltas.is_empty() {
+                return Ok(());
+            }
+            let entry = wal::encode(&deltas)?;
+            if last.log_valid + entry.len() as u64 <= last.stamp.0 / 2 {
+                rotate_backup_of_log(path)?;
+                let len = wal::append(&log_path, last.log_valid, &entry)?;
+                last.db = db.clone();
+                last.log_len = len;
+                last.log_valid = len;
+                return Ok(());
+            }
+        }
+    }
+
+    // Compact: the log is only dropped once the database holds all of it
+    rotate_backup(path)?;
+    write_to_disk(path, &wrap_envelope(&db.serialize_msgpack()?, compressed))?;
+    match fs::remove_file(&log_path) {
+        Err(err) if err.kind() != IOErrorKind::NotFound => return Err(err.into()),
+        _ => {}
+    }
+    saved.insert(
+        path.to_path_buf(),
+        Saved {
+            db: db.clone(),
+            stamp: stamp(path)?,
+            log_len: 0,
+            log_valid: 0,
+            compressed,
+        },
+    );
+    Ok(())
+}
+
+pub fn read_from_disk<P: AsRef<Path>>(path: P) -> IOResult<Vec<u8>> {
+    let file = File::open(path)?;
+    let mut stream = BufReader::new(file);
+    let mut data = Vec::new();
+    stream.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// How `Database::import` treats an incoming item whose UUID is taken.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Collision {
+    /// Keep the existing item and drop the incoming one
+    Skip,
+    /// Overwrite the existing item with the incoming one
+    Replace,
+    /// Keep both, giving the incoming item a fresh UUID
+    Rename,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub replaced: usize,
+    pub renamed: usize,
+    pub skipped: usize,
+}
+
+/// A named space of tasks and notes, kept apart from the root project.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct Project {
+    pub tasks: Tasks,
+    pub notes: Notes,
+    #[serde(default)]
+    pub archive: Tasks,
+    #[serde(default)]
+    pub trash: Trash,
+}
+
+impl Project {
+    pub fn named(name: &str) -> Self {
+        Self {
+            tasks: Tasks::named(name),
+            notes: Notes::named(name),
+            archive: Tasks::named(name),
+            trash: Trash::default(),
+        }
+    }
+}
+
+/// `tasks` and `notes` always hold the project being worked on. The root
+/// project lives there at rest; `activate` swaps a named project in and
+/// parks the root one in its place, and `to_disk` swaps back before saving.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct Database {
+    pub tasks: Tasks,
+    pub notes: Notes,
+    #[serde(default)]
+    pub projects: BTreeMap<String, Project>,
+    /// The project chosen with `regia project switch`
+    #[serde(default)]
+    pub current_project: Option<String>,
+    /// Finished tasks moved out of `tasks` by `task archive`
+    #[serde(default)]
+    pub archive: Tasks,
+    /// Tasks and notes removed with `rm`, until restored or purged
+    #[serde(default)]
+    pub trash: Trash,
+    /// Filters saved with `regia view save`, shared by every project
+    #[serde(default)]
+    pub views: BTreeMap<String, View>,
+    /// What happened to the tasks of every project, see [`history`]
+    #[serde(default)]
+    pub history: Vec<history::Event>,
+    /// Captured with `regia in` until triaged, shared by every project
+    #[serde(default)]
+    pub inbox: Vec<inbox::Item>,
+    /// When `regia review` last went through everything, shared by every
+    /// project
+    #[serde(default)]
+    pub reviewed: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    active: Option<String>,
+}
+
+impl Database {
+    /// The schema version `to_disk` writes. Bump it together with a new
+    /// entry in `MIGRATIONS` whenever the serialized layout changes.
+    pub const VERSION: u32 = 1;
+
+    pub fn serialize_msgpack(&self) -> error::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        match self.serialize(&mut rmp_serde::Serializer::new(&mut buf)) {
+            Ok(_) => Ok(buf),
+            Err(_) => Err(RegiaError::storage("Serialization failed")),
+        }
+    }
+
+    pub fn deserialize_msgpack(buf: &[u8]) -> error::Result<Database> {
+        let mut de = rmp_serde::Deserializer::new(buf);
+        match Database::deserialize(&mut de) {
+            Ok(tasks) => Ok(tasks),
+            Err(_) => Err(RegiaError::corrupt("Deserialization failed")),
+        }
+    }
+
+    pub fn serialize_json(&self) -> error::Result<String> {
+        match serde_json::to_string_pretty(self) {
+            Ok(json) => Ok(json),
+            Err(_) => Err(RegiaError::storage("Serialization failed")),
+        }
+    }
+
+    pub fn deserialize_json(buf: &str) -> error::Result<Database> {
+        match serde_json::from_str(buf) {
+            Ok(db) => Ok(db),
+            Err(err) => Err(RegiaError::Parse(format!(
+                "Deserialization failed: {}",
+                err
+            ))),
+        }
+    }
+
+    /// Merges the tasks and notes of `other` into this database, project by
+    /// project; call it with the root project active. With
+    /// `Collision::Rename`, references between imported tasks (dependencies,
+    /// parents) follow the renamed ids.
+    pub fn import(&mut self, other: Database, collision: Collision) -> ImportSummary {
+        let mut summary = ImportSummary::default();
+
+        let mut renamed: HashMap<Uuid, Uuid> = HashMap::new();
+        if collision == Collision::Rename {
+            for task in other.tasks.get_tasks() {
+                if self.tasks.get_task(&task.id).is_some() {
+                    renamed.insert(task.id, Uuid::new_v4());
+                }
+            }
+        }
+        let remap = |id: &Uuid| *renamed.get(id).unwrap_or(id);
+
+        for task in other.tasks.get_tasks() {
+            let mut task = task.clone();
+            task.depends = task.depends.iter().map(remap).collect();
+            task.parent = task.parent.as_ref().map(remap);
+            task.successor = task.successor.as_ref().map(remap);
+
+            if let Some(new_id) = renamed.get(&task.id) {
+                task.id = *new_id;
+                summary.renamed += 1;
+            } else if self.tasks.get_task(&task.id).is_some() {
+                if collision == Collision::Replace {
+                    self.tasks.remove(task.id);
+                    summary.replaced += 1;
+                } else {
+                    summary.skipped += 1;
+                    continue;
+                }
+            } else {
+                summary.added += 1;
+            }
+            self.tasks.add(task);
+        }
+
+        for note in other.notes.get_notes() {
+            let mut note = note.clone();
+            if self.notes.get_note(&note.id).is_some() {
+                match collision {
+                    Collision::Skip => {
+                        summary.skipped += 1;
+                        continue;
+                    }
+                    Collision::Replace => {
+                        self.notes.remove(note.id);
+                        summary.replaced += 1;
+                    }
+                    Collision::Rename => {
+                        note.id = Uuid::new_v4();
+                        summary.renamed += 1;
+                    }
+                }
+            } else {
+                summary.added += 1;
+            }
+            self.notes.add(note);
+        }
+
+        // Archived tasks are history, so they only ever merge by id
+        for task in other.archive.get_tasks() {
+            if self.archive.get_task(&task.id).is_some() {
+                if collision != Collision::Replace {
+                    summary.skipped += 1;
+                    continue;
+                }
+                self.archive.remove(task.id);
+                summary.replaced += 1;
+            } else {
+                summary.added += 1;
+            }
+            self.archive.add(task.clone());
+        }
+
+        for (name, project) in other.projects {
+            let existing = self
+                .projects
+                .remove(&name)
+                .unwrap_or_else(|| Project::named(&name));
+            let mut space = Database {
+                tasks: existing.tasks,
+                notes: existing.notes,
+                archive: existing.archive,
+                trash: existing.trash,
+                ..Database::default()
+            };
+            let incoming = Database {
+                tasks: project.tasks,
+                notes: project.notes,
+                archive: project.archive,
+                ..Database::default()
+            };
+            let part = space.import(incoming, collision);
+            summary.added += part.added;
+            summary.replaced += part.replaced;
+            summary.renamed += part.renamed;
+            summary.skipped += part.skipped;
+            self.projects.insert(
+                name,
+                Project {
+                    tasks: space.tasks,
+                    notes: space.notes,
+                    archive: space.archive,
+                    trash: space.trash,
+                },
+            );
+        }
+        history::merge(&mut self.history, other.history);
+        inbox::merge(&mut self.inbox, other.inbox);
+
+        summary
+    }
+
+    /// Reads the database at `path`, migrating older schema versions and
+    /// replaying the changes in its log.
+    pub fn from_disk<P: AsRef<Path>>(path: P) -> error::Result<Database> {
+        let path = path.as_ref();
+        let stamp = stamp(path)?;
+        let buf = read_from_disk(path)?;
+        let (version, mut db) = decode(&buf)?;
+
+        let log = wal::read(&wal::log_path(path))?;
+        for entry in log.entries {
+            wal::apply(&mut db, entry)?;
+        }
+        // A file in an older layout is left for the next save to rewrite
+        if version < Database::VERSION {
+            return Ok(db);
+        }
+        lock_saved().insert(
+            path.to_path_buf(),
+            Saved {
+                db: db.clone(),
+                stamp,
+                log_len: log.len,
+                log_valid: log.valid,
+                compressed: is_compressed(&buf),
+            },
+        );
+        Ok(db)
+    }
+
+    /// The database as its file holds it after a compaction: versioned,
+    /// with nothing left in a log, and not compressed whatever
+    /// [`set_compression`] says.
+    pub fn to_bytes(&self) -> error::Result<Vec<u8>> {
+        Ok(wrap_envelope(&self.serialize_msgpack()?, false))
+    }
+
+    /// Reads the bytes of a database file, migrating older schema versions
+    /// as `from_disk` does. Whatever a log beside the file held is not
+    /// part of them.
+    pub fn from_bytes(buf: &[u8]) -> error::Result<Database> {
+        decode(buf).map(|(_, db)| db)
+    }
+
+    /// Like `from_disk`, but a missing file yields an empty database. A
+    /// database kept in memory with [`hold`] is copied from there instead.
+    pub fn load<P: AsRef<Path>>(path: P) -> error::Result<Database> {
+        if let Some(held) = lock_held()
+            .as_ref()
+            .filter(|held| held.path == path.as_ref())
+        {
+            return Ok(held.db.clone());
+        }
+        match Database::from_disk(path) {
+            Err(err) if err.is_missing_file() => Ok(Database::default()),
+            result => result,
+        }
+    }
+
+    /// Saves to `path`, appending what changed since it was loaded to the
+    /// log beside it, see [`wal`], recording the [`history`] of tasks
+    /// completed or missed since the last save and resolving the [`links`]
+    /// between notes again. Now and then the database is compacted
+    /// into one file instead, written atomically with the previous one kept
+    /// as a backup. A database kept in memory with [`hold`] is only
+    /// replaced there, until [`flush`].
+    pub fn to_disk<P: AsRef<Path>>(&self, path: P) -> error::Result<()> {
+        if let Some(held) = lock_held()
+            .as_mut()
+            .filter(|held| held.path == path.as_ref())
+        {
+            held.db = self.at_rest()?.into_owned();
+            held.dirty = true;
+            return Ok(());
+        }
+        save(path.as_ref(), &*self.at_rest()?)
+    }
+
+    /// Saves to `path` like `to_disk`, but always writes the database whole
+    /// and drops its log, so that the file holds it exactly as it is now.
+    /// A database held with [`hold`] is written through at once.
+    pub fn compact<P: AsRef<Path>>(&self, path: P) -> error::Result<()> {
+        let path = path.as_ref();
+        let at_rest = self.at_rest()?;
+        let mut held = lock_held();
+        if let Some(held) = held.as_mut().filter(|held| held.path == path) {
+            held.db = at_rest.clone().into_owned();
+            // A dry run keeps it to show, like any other change
+            if held.dry {
+                held.dirty = true;
+                return Ok(());
+            }
+            held.dirty = false;
+        }
+        lock_saved().remove(path);
+        save(path, &at_rest)
+    }
+
+    /// The database as it is saved: with the root project active, the
+    /// [`history`] since the last save recorded and [`links`] resolved.
+    fn at_rest(&self) -> error::Result<Cow<'_, Database>> {
+        let events = history::pending(self);
+        if self.active.is_none() && events.is_empty() && !links::any_stale(self) {
+            return Ok(Cow::Borrowed(self));
+        }
+        let mut at_rest = self.clone();
+        at_rest.activate(None)?;
+        history::merge(&mut at_rest.history, events);
+        links::refresh_all(&mut at_rest);
+        Ok(Cow::Owned(at_rest))
+    }
+
+    /// The named project currently swapped into `tasks`/`notes`, if any.
+    pub fn active_project(&self) -> Option<&str> {
+        self.active.as_deref()
+    }
+
+    /// Makes `name` (or the root project for `None`) the one held in
+    /// `tasks`/`notes`.
+    pub fn activate(&mut self, name: Option<&str>) -> error::Result<()> {
+        let name = name.filter(|name| *name != ROOT_PROJECT);
+        if let Some(name) = name {
+            if !self.projects.contains_key(name) {
+                return Err(RegiaError::NotFound(format!("no project named: {}", name)));
+            }
+        }
+        if self.active.as_deref() == name {
+            return Ok(());
+        }
+
+        // Put the root project back before swapping the next one in
+        if let Some(active) = self.active.take() {
+            let parked = self.projects.get_mut(&active).unwrap();
+            mem::swap(&mut self.tasks, &mut parked.tasks);
+            mem::swap(&mut self.notes, &mut parked.notes);
+            mem::swap(&mut self.archive, &mut parked.archive);
+            mem::swap(&mut self.trash, &mut parked.trash);
+        }
+        if let Some(name) = name {
+            let parked = self.projects.get_mut(name).unwrap();
+            mem::swap(&mut self.tasks, &mut parked.tasks);
+            mem::swap(&mut self.notes, &mut parked.notes);
+            mem::swap(&mut self.archive, &mut parked.archive);
+            mem::swap(&mut self.trash, &mut parked.trash);
+            self.active = Some(name.to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::Task;
+
+    #[test]
+    fn import_collisions() {
+        let parent = Task::new(String::from("parent"), 0);
+        let mut child = Task::new(String::from("child"), 0);
+        child.add_dependency(&parent.id);
+
+        let mut db = Database::default();
+        db.tasks.add(parent.clone());
+
+        let mut incoming = Database::default();
+        incoming.tasks.add(parent.clone());
+        incoming.tasks.add(child.clone());
+        let json = incoming.serialize_json().unwrap();
+        let incoming = Database::deserialize_json(&json).unwrap();
+
+        let mut skipped = db.clone();
+        let summary = skipped.import(incoming.clone(), Collision::Skip);
+        assert_eq!((summary.added, summary.skipped), (1, 1));
+        assert_eq!(skipped.tasks.get_tasks().len(), 2);
+
+        let summary = db.import(incoming, Collision::Rename);
+        assert_eq!((summary.added, summary.renamed), (1, 1));
+        assert_eq!(db.tasks.get_tasks().len(), 3);
+
+        // The imported child follows its renamed parent
+        let child = db.tasks.get_task(&child.id).unwrap();
+        let dep = child.depends.iter().next().unwrap();
+        assert_ne!(dep, &parent.id);
+        assert_eq!(db.tasks.get_task(dep).unwrap().content, "parent");
+    }
+
+    #[test]
+    fn projects_swap_and_persist() {
+        let mut db = Database::default();
+        db.tasks.add(Task::new(String::from("root task"), 0));
+        db.projects
+            .insert(String::from("work"), Project::named("work"));
+
+        db.activate(Some("work")).unwrap();
+        assert!(db.tasks.get_tasks().is_empty());
+        db.tasks.add(Task::new(String::from("work task"), 0));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("regia.db");
+        db.to_disk(&path).unwrap();
+        let mut loaded = Database::from_disk(&path).unwrap();
+        assert_eq!(loaded.tasks.get_tasks()[0].content, "root task");
+
+        loaded.activate(Some("work")).unwrap();
+        assert_eq!(loaded.tasks.get_tasks()[0].content, "work task");
+        loaded.activate(Some(ROOT_PROJECT)).unwrap();
+        assert_eq!(loaded.tasks.get_tasks()[0].content, "root task");
+        assert!(loaded.activate(Some("missing")).is_err());
+    }
+
+    #[test]
+    fn writes_keep_a_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("regia.db");
+
+        let mut db = Database::default();
+        db.tasks.add(Task::new(String::from("first"), 0));
+        db.to_disk(&path).unwrap();
+        assert!(!backup_path(&path).exists());
+
+        // A change this size compacts the database rather than being logged
+        db.tasks.add(Task::new("second ".repeat(50), 0));
+        db.to_disk(&path).unwrap();
+        assert_eq!(
+            Database::from_disk(&path).unwrap().tasks.get_tasks().len(),
+            2
+        );
+        let backup = Database::from_disk(backup_path(&path)).unwrap();
+        assert_eq!(backup.tasks.get_tasks()[0].content, "first");
+
+        // Only the database and its backup are left; no stray temp files
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn saves_append_to_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("regia.db");
+        let log = wal::log_path(&path);
+
+        let mut db = Database::default();
+        for index in 0..20 {
+            db.tasks.add(Task::new(format!("task {}", index), 0));
+        }
+        db.to_disk(&path).unwrap();
+        let base = read_from_disk(&path).unwrap();
+
+        let mut db = Database::load(&path).unwrap();
+        let added = Task::new(String::from("added"), 0);
+        db.tasks.add(added.clone());
+        db.to_disk(&path).unwrap();
+        assert_eq!(read_from_disk(&path).unwrap(), base);
+        assert!(log.exists());
+        // The backup is the version before, log and all
+        let backup = Database::load(backup_path(&path)).unwrap();
+        assert_eq!(backup.tasks.get_tasks().len(), 20);
+        db.tasks.get_task_mut(&added.id).unwrap().priority = 2.into();
+        db.to_disk(&path).unwrap();
+        let backup = Database::load(backup_path(&path)).unwrap();
+        assert_eq!(backup.tasks.get_tasks().len(), 21);
+        assert_eq!(
+            backup.tasks.get_task(&added.id).unwrap().priority,
+            added.priority
+        );
+
+        // A cut-off save at the end of the log is ignored and written over
+        let mut file = fs::OpenOptions::new().append(true).open(&log).unwrap();
+        file.write_all(&[0, 0, 1]).unwrap();
+        let mut db = Database::load(&path).unwrap();
+        assert!(db.tasks.get_task(&added.id).is_some());
+        db.tasks.remove(added.id);
+        db.to_disk(&path).unwrap();
+        assert_eq!(Database::load(&path).unwrap().tasks.get_tasks().len(), 20);
+
+        // Once the log outgrows half the database it is folded in
+        let mut db = Database::load(&path).unwrap();
+        while log.exists() {
+            db.notes
+                .add(crate::note::Note::new("a longer note to fill the log"));
+            db.to_disk(&path).unwrap();
+        }
+        assert_eq!(Database::from_disk(&path).unwrap(), db);
+        assert_ne!(read_from_disk(&path).unwrap(), base);
+        let backup = Database::from_disk(backup_path(&path)).unwrap();
+        assert_eq!(
+            backup.notes.get_notes().len(),
+            db.notes.get_notes().len() - 1
+        );
+
+        // Compacting folds the log in whatever its size
+        db.notes.add(crate::note::Note::new("one more"));
+        db.to_disk(&path).unwrap();
+        assert!(log.exists());
+        db.compact(&path).unwrap();
+        assert!(!log.exists());
+        assert_eq!(Database::from_disk(&path).unwrap(), db);
+    }
+
+    #[test]
+    fn held_database_stays_in_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("regia.db");
+        Database::default().to_disk(&path).unwrap();
+
+        hold(&path).unwrap();
+        let mut db = Database::load(&path).unwrap();
+        db.tasks.add(Task::new(String::from("kept"), 0));
+        db.to_disk(&path).unwrap();
+        assert!(Database::from_disk(&path)
+            .unwrap()
+            .tasks
+            .get_tasks()
+            .is_empty());
+        assert_eq!(Database::load(&path).unwrap().tasks.get_tasks().len(), 1);
+
+        release().unwrap();
+        assert_eq!(
+            Database::from_disk(&path).unwrap().tasks.get_tasks()[0].content,
+            "kept"
+        );
+
+        // A dry run keeps its changes to itself
+        hold_dry(&path).unwrap();
+        assert!(is_dry());
+        let mut db = Database::load(&path).unwrap();
+        db.tasks.add(Task::new(String::from("tried"), 0));
+        db.to_disk(&path).unwrap();
+        flush().unwrap();
+        assert_eq!(discard().unwrap().tasks.get_tasks().len(), 2);
+        assert!(!is_dry());
+        assert_eq!(Database::load(&path).unwrap().tasks.get_tasks().len(), 1);
+    }
+
+    #[test]
+    fn versioned_envelope() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("regia.db");
+
+        let mut db = Database::default();
+        db.tasks.add(Task::new(String::from("old"), 0));
+
+        // Files from before versioning are read as version 0
+        write_to_disk(&path, &db.serialize_msgpack().unwrap()).unwrap();
+        assert_eq!(Database::from_disk(&path).unwrap(), db);
+
+        db.to_disk(&path).unwrap();
+        let buf = read_from_disk(&path).unwrap();
+        assert!(buf.starts_with(MAGIC));
+        assert_eq!(Database::from_disk(&path).unwrap(), db);
+
+        let mut newer = buf[..MAGIC.len()].to_vec();
+        newer.extend_from_slice(&(Database::VERSION + 1).to_be_bytes());
+        newer.extend_from_slice(&buf[MAGIC.len() + 4..]);
+        write_to_disk(&path, &newer).unwrap();
+        let err = Database::load(&path).unwrap_err();
+        assert!(err.to_string().contains("upgrade regia"));
+
+        // Only a missing file is an empty database; a broken one is an error
+        let missing = Database::load(dir.path().join("missing.db")).unwrap();
+        assert!(missing.tasks.get_tasks().is_empty());
+        write_to_disk(&path, b"RGDB").unwrap();
+        let err = Database::load(&path).unwrap_err();
+        assert_eq!(err.exit_code(), 1);
+        assert!(!err.is_missing_file());
+    }
+
+    #[test]
+    fn reads_stored_compressed_envelopes() {
+        let mut stored = b"RGDZ\x01\0\0\0\x01".to_vec();
+        stored.extend_from_slice(&compress::tests::STORED);
+        let (version, payload) = unwrap_envelope(&stored).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(&payload[..], b"regia regia regia regia");
+        assert_eq!(wrap_envelope(&payload, true)[..5], stored[..5]);
+
+        stored[4] = 2;
+        let err = unwrap_envelope(&stored).unwrap_err();
+        assert!(err.to_string().contains("codec 2"), "{}", err);
+        assert!(unwrap_envelope(b"RGDZ").is_err());
+    }
+
+    #[test]
+    fn compressed_files_read_and_migrate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("regia.db");
+        let mut db = Database::default();
+        for index in 0..50 {
+            db.notes.add(crate::note::Note::new(&format!(
+                "note {} with a long body",
+                index
+            )));
+        }
+        let plain = db.to_bytes().unwrap();
+        let packed = wrap_envelope(&db.serialize_msgpack().unwrap(), true);
+        assert!(packed.starts_with(COMPRESSED_MAGIC));
+        assert!(packed.len() < plain.len());
+
+        write_to_disk(&path, &packed).unwrap();
+        let mut db = Database::from_disk(&path).unwrap();
+        assert_eq!(Database::from_bytes(&plain).unwrap(), db);
+
+        // Saving with compression off rewrites the file whole, uncompressed,
+        // rather than logging the change beside a compressed file
+        db.tasks.add(Task::new(String::from("added"), 0));
+        db.to_disk(&path).unwrap();
+        assert!(!wal::log_path(&path).exists());
+        let buf = read_from_disk(&path).unwrap();
+        assert!(buf.starts_with(MAGIC));
+        assert_eq!(Database::from_disk(&path).unwrap(), db);
+
+        let mut cut = packed[..packed.len() - 5].to_vec();
+        write_to_disk(&path, &cut).unwrap();
+        assert!(Database::from_disk(&path).is_err());
+        cut.truncate(6);
+        assert!(Database::from_bytes(&cut).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+//! Line diffs between two texts, as `note diff` shows them.
+
+/// One line of a diff, from the old text, the new one or both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// The lines of `old` and `new` in order, with those they share kept as
+/// [`Line::Same`]: a longest common subsequence, so the fewest lines
+/// change.
+pub fn lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // shared[i][j] is how many lines old[i..] and new[j..] have in common
+    let mut shared = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            shared[i][j] = if old[i] == new[j] {
+                shared[i + 1][j + 1] + 1
+            } else {
+                shared[i + 1][j].max(shared[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut diff = Vec::with_capacity(old.len().max(new.len()));
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            diff.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if shared[i + 1][j] >= shared[i][j + 1] {
+            diff.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            diff.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    diff.extend(old[i..].iter().map(|line| Line::Removed(line)));
+    diff.extend(new[j..].iter().map(|line| Line::Added(line)));
+    diff
+}
+
+/// The `@@ -start,len +start,len @@` line opening a hunk, numbered from 1
+/// as diff does, from 0 for an empty side.
+fn hunk_header(old_start: usize, old_len: usize, new_start: usize, new_len: usize) -> String {
+    let side = |start: usize, len: usize| {
+        let start = if len == 0 { start } else { start + 1 };
+        format!("{},{}", start, len)
+    };
+    format!(
+        "@@ -{} +{} @@",
+        side(old_start, old_len),
+        side(new_start, new_len)
+    )
+}
+
+/// The hunks of a unified diff from `old` to `new`, each change with up to
+/// `context` unchanged lines around it, without the `---`/`+++` header.
+/// Empty when the texts have the same lines.
+pub fn unified(old: &str, new: &str, context: usize) -> Vec<String> {
+    let diff = lines(old, new);
+    let changed: Vec<usize> = diff
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Line::Same(_)))
+        .map(|(index, _)| index)
+        .collect();
+
+    // Changes closer than twice the context share a hunk
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    for index in changed {
+        let start = index.saturating_sub(context);
+        let end = (index + context + 1).min(diff.len());
+        match spans.last_mut() {
+            Some(span) if start <= span.1 => span.1 = end,
+            _ => spans.push((start, end)),
+        }
+    }
+
+    let mut out = Vec::new();
+    // Where each side is at the start of `diff[at]`
+    let (mut at, mut old_at, mut new_at) = (0, 0, 0);
+    for (start, end) in spans {
+        for line in &diff[at..start] {
+            match line {
+                Line::Same(_) => {
+                    old_at += 1;
+                    new_at += 1;
+                }
+                Line::Removed(_) => old_at += 1,
+                Line::Added(_) => new_at += 1,
+            }
+        }
+        let hunk = &diff[start..end];
+        let old_len = hunk
+            .iter()
+            .filter(|line| !matches!(line, Line::Added(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|line| !matches!(line, Line::Removed(_)))
+            .count();
+        out.push(hunk_header(old_at, old_len, new_at, new_len));
+        for line in hunk {
+            out.push(match line {
+                Line::Same(text) => format!(" {}", text),
+                Line::Removed(text) => format!("-{}", text),
+                Line::Added(text) => format!("+{}", text),
+            });
+        }
+        at = end;
+        old_at += old_len;
+        new_at += new_len;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified_hunks() {
+        let old = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten";
+        let new = "one\n2\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\neleven";
+        assert_eq!(
+            unified(old, new, 1),
+            [
+                "@@ -1,3 +1,3 @@",
+                " one",
+                "-two",
+                "+2",
+                " three",
+                "@@ -10,1 +10,2 @@",
+                " ten",
+                "+eleven",
+            ]
+        );
+        // Close changes share a hunk
+        assert_eq!(unified(old, new, 3)[0], "@@ -1,5 +1,5 @@");
+        assert_eq!(unified("", "new", 3), ["@@ -0,0 +1,1 @@", "+new"]);
+        assert!(unified(old, old, 3).is_empty());
+    }
+}
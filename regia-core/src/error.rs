@@ -0,0 +1,98 @@
+//! The error type shared by the library and the command line tool.
+
+use std::fmt;
+use std::io;
+
+use thiserror::Error;
+
+/// What went wrong, in enough detail to pick an exit code.
+#[derive(Debug, Error)]
+pub enum RegiaError {
+    /// An argument or a value in the database couldn't be understood
+    #[error("{0}")]
+    Parse(String),
+    /// Reading or writing a file failed, or it holds no valid database
+    #[error("{0}")]
+    Storage(#[from] io::Error),
+    /// No task, note, project or attachment matches what was asked for
+    #[error("{0}")]
+    NotFound(String),
+    /// The request clashes with the current state: an ambiguous match, a
+    /// held lock, something that already exists
+    #[error("{0}")]
+    Conflict(String),
+    /// The config file is missing, unreadable or has a bad setting
+    #[error("{0}")]
+    Config(String),
+    /// Not a failure but a no: a `--quiet` list with nothing in it, or a
+    /// check such as `task check-overdue` that found something. `regia`
+    /// exits without a message.
+    #[error("check failed")]
+    CheckFailed,
+}
+
+pub type Result<T> = std::result::Result<T, RegiaError>;
+
+impl RegiaError {
+    /// The status `regia` exits with: 1 for storage failures, then 2 to 5
+    /// for parse, not found, conflict and config errors, and 6 for a
+    /// failed check.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RegiaError::Storage(_) => 1,
+            RegiaError::Parse(_) => 2,
+            RegiaError::NotFound(_) => 3,
+            RegiaError::Conflict(_) => 4,
+            RegiaError::Config(_) => 5,
+            RegiaError::CheckFailed => 6,
+        }
+    }
+
+    /// A storage error with no `io::Error` behind it, such as a failed
+    /// external command.
+    pub fn storage<S: Into<String>>(message: S) -> Self {
+        RegiaError::Storage(io::Error::other(message.into()))
+    }
+
+    /// A storage error for a file that was read but holds nothing usable.
+    pub fn corrupt<S: Into<String>>(message: S) -> Self {
+        RegiaError::Storage(io::Error::new(io::ErrorKind::InvalidData, message.into()))
+    }
+
+    /// Prefixes the message with `what`, keeping the kind of error.
+    pub fn context<S: fmt::Display>(self, what: S) -> Self {
+        match self {
+            RegiaError::Parse(message) => RegiaError::Parse(format!("{}: {}", what, message)),
+            RegiaError::Storage(err) => {
+                RegiaError::Storage(io::Error::new(err.kind(), format!("{}: {}", what, err)))
+            }
+            RegiaError::NotFound(message) => RegiaError::NotFound(format!("{}: {}", what, message)),
+            RegiaError::Conflict(message) => RegiaError::Conflict(format!("{}: {}", what, message)),
+            RegiaError::Config(message) => RegiaError::Config(format!("{}: {}", what, message)),
+            RegiaError::CheckFailed => RegiaError::CheckFailed,
+        }
+    }
+
+    /// Whether this is a file that doesn't exist, as opposed to one that
+    /// couldn't be read.
+    pub fn is_missing_file(&self) -> bool {
+        matches!(self, RegiaError::Storage(err) if err.kind() == io::ErrorKind::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_keeps_the_kind() {
+        let err = RegiaError::NotFound(String::from("no task with id: 1")).context("task show");
+        assert_eq!(err.to_string(), "task show: no task with id: 1");
+        assert_eq!(err.exit_code(), 3);
+
+        let missing = io::Error::new(io::ErrorKind::NotFound, "gone");
+        let err = RegiaError::from(missing).context("can't attach a.txt");
+        assert!(err.is_missing_file());
+        assert_eq!(err.to_string(), "can't attach a.txt: gone");
+    }
+}
@@ -0,0 +1,407 @@
+//! Checks for `regia db fsck`: what a database file can hold that regia
+//! never writes, such as ids used twice, dependencies on tasks that are
+//! gone, notes attached to deleted tasks and vectors out of order, and
+//! [`repair`] to put them right.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::db::{Database, ROOT_PROJECT};
+use crate::error;
+use crate::index::{self, Irregularity};
+use crate::note::Notes;
+use crate::todo::{Task, Tasks};
+use crate::trash::Trash;
+
+/// Something wrong with a database.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Issue {
+    /// The project it is in, for those that belong to one
+    pub project: Option<String>,
+    /// The task or note it is about, if one
+    pub id: Option<Uuid>,
+    pub message: String,
+}
+
+impl Issue {
+    fn new(project: Option<&str>, id: Option<Uuid>, message: String) -> Self {
+        Self {
+            project: project.map(String::from),
+            id,
+            message,
+        }
+    }
+}
+
+/// Reads the database at `path` as `Database::from_disk` does, with what
+/// reading it had to sort or drop on the way. Those are put right in the
+/// database returned, but stay wrong in the file until it is rewritten.
+pub fn read(path: &Path) -> error::Result<(Database, Vec<Issue>)> {
+    let (db, found) = index::audit(|| Database::from_disk(path));
+    let issues = found
+        .into_iter()
+        .map(|irregularity| match irregularity {
+            Irregularity::Unsorted { kind } => {
+                Issue::new(None, None, format!("{}s are stored out of id order", kind))
+            }
+            Irregularity::Duplicate { kind, id } => Issue::new(
+                None,
+                Some(id),
+                format!(
+                    "{} id is stored more than once, only the first is kept",
+                    kind
+                ),
+            ),
+        })
+        .collect();
+    Ok((db?, issues))
+}
+
+/// The parts of one project the checks look at.
+struct Space<'a> {
+    name: Option<&'a str>,
+    tasks: &'a Tasks,
+    notes: &'a Notes,
+    archive: &'a Tasks,
+    trash: &'a Trash,
+}
+
+fn spaces(db: &Database) -> Vec<Space<'_>> {
+    let mut spaces = vec![Space {
+        name: None,
+        tasks: &db.tasks,
+        notes: &db.notes,
+        archive: &db.archive,
+        trash: &db.trash,
+    }];
+    for (name, project) in db.projects.iter() {
+        spaces.push(Space {
+            name: Some(name),
+            tasks: &project.tasks,
+            notes: &project.notes,
+            archive: &project.archive,
+            trash: &project.trash,
+        });
+    }
+    spaces
+}
+
+fn is_sorted_by<T, K: PartialOrd>(items: &[T], key: impl Fn(&T) -> K) -> bool {
+    items.windows(2).all(|pair| key(&pair[0]) <= key(&pair[1]))
+}
+
+impl Space<'_> {
+    /// Every task id the project knows, live, archived or trashed.
+    fn known(&self) -> HashSet<Uuid> {
+        self.tasks
+            .get_tasks()
+            .iter()
+            .chain(self.archive.get_tasks())
+            .map(|task| task.id)
+            .chain(self.trash.tasks.iter().map(|entry| entry.item.id))
+            .collect()
+    }
+
+    fn check(&self, issues: &mut Vec<Issue>) {
+        let mut issue =
+            |id: Option<Uuid>, message: String| issues.push(Issue::new(self.name, id, message));
+        let live: HashSet<Uuid> = self.tasks.get_tasks().iter().map(|task| task.id).collect();
+        for task in self.archive.get_tasks() {
+            if live.contains(&task.id) {
+                issue(
+                    Some(task.id),
+                    String::from("task is both live and archived"),
+                );
+            }
+        }
+        let mut trashed = HashSet::new();
+        for entry in self.trash.tasks.iter() {
+            let id = entry.item.id;
+            if live.contains(&id) || self.archive.get_task(&id).is_some() {
+                issue(Some(id), String::from("task is in the trash and out of it"));
+            } else if !trashed.insert(id) {
+                issue(
+                    Some(id),
+                    String::from("task is in the trash more than once"),
+                );
+            }
+        }
+        let mut trashed = HashSet::new();
+        for entry in self.trash.notes.iter() {
+            let id = entry.item.id;
+            if self.notes.get_note(&id).is_some() {
+                issue(Some(id), String::from("note is in the trash and out of it"));
+            } else if !trashed.insert(id) {
+                issue(
+                    Some(id),
+                    String::from("note is in the trash more than once"),
+                );
+            }
+        }
+
+        let known = self.known();
+        let tasks = self
+            .tasks
+            .get_tasks()
+            .iter()
+            .chain(self.archive.get_tasks());
+        for task in tasks {
+            let mut dangling: Vec<&Uuid> = task
+                .depends
+                .iter()
+                .filter(|dep| !known.contains(dep))
+                .collect();
+            dangling.sort();
+            for dep in dangling {
+                issue(
+                    Some(task.id),
+                    format!("task depends on missing task {}", dep),
+                );
+            }
+            if let Some(parent) = task.parent.filter(|parent| !known.contains(parent)) {
+                issue(
+                    Some(task.id),
+                    format!("task's parent {} is missing", parent),
+                );
+            }
+            if !is_sorted_by(&task.sessions, |session| session.start) {
+                issue(
+                    Some(task.id),
+                    String::from("task's sessions are out of order"),
+                );
+            }
+            if !is_sorted_by(&task.comments, |comment| comment.created) {
+                issue(
+                    Some(task.id),
+                    String::from("task's comments are out of order"),
+                );
+            }
+        }
+
+        for note in self.notes.get_notes() {
+            if let Some(task_id) = note.task_id {
+                if !live.contains(&task_id) && self.archive.get_task(&task_id).is_none() {
+                    issue(
+                        Some(note.id),
+                        format!("note is attached to deleted task {}", task_id),
+                    );
+                }
+            }
+            if !is_sorted_by(&note.revisions, |revision| revision.number) {
+                issue(
+                    Some(note.id),
+                    String::from("note's revisions are out of order"),
+                );
+            }
+        }
+
+        if !is_sorted_by(&self.trash.tasks, |entry| entry.deleted)
+            || !is_sorted_by(&self.trash.notes, |entry| entry.deleted)
+        {
+            issue(None, String::from("trash is out of order"));
+        }
+    }
+}
+
+/// Everything wrong with `db`, which should be at rest.
+pub fn check(db: &Database) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    for space in spaces(db) {
+        space.check(&mut issues);
+    }
+    if !is_sorted_by(&db.inbox, |item| item.created) {
+        issues.push(Issue::new(
+            None,
+            None,
+            String::from("inbox is out of order"),
+        ));
+    }
+    if let Some(current) = db.current_project.as_deref() {
+        if current != ROOT_PROJECT && !db.projects.contains_key(current) {
+            issues.push(Issue::new(
+                None,
+                None,
+                format!("current project {} doesn't exist", current),
+            ));
+        }
+    }
+    issues
+}
+
+/// Drops from `trash` what is also live or archived, and all but the
+/// first of anything in it twice.
+fn repair_trash(trash: &mut Trash, tasks: &Tasks, archive: &Tasks, notes: &Notes) {
+    let mut seen = HashSet::new();
+    trash.tasks.retain(|entry| {
+        let id = entry.item.id;
+        tasks.get_task(&id).is_none() && archive.get_task(&id).is_none() && seen.insert(id)
+    });
+    let mut seen = HashSet::new();
+    trash
+        .notes
+        .retain(|entry| notes.get_note(&entry.item.id).is_none() && seen.insert(entry.item.id));
+    trash.tasks.sort_by_key(|entry| entry.deleted);
+    trash.notes.sort_by_key(|entry| entry.deleted);
+}
+
+fn repair_task(task: &Task, known: &HashSet<Uuid>) -> Option<Task> {
+    let dangling = task.depends.iter().any(|dep| !known.contains(dep))
+        || task.parent.is_some_and(|parent| !known.contains(&parent));
+    let unsorted = !is_sorted_by(&task.sessions, |session| session.start)
+        || !is_sorted_by(&task.comments, |comment| comment.created);
+    if !dangling && !unsorted {
+        return None;
+    }
+    // Tasks compare by id, so the fixed copy can't be told apart with `!=`
+    let mut fixed = task.clone();
+    fixed.depends.retain(|dep| known.contains(dep));
+    fixed.parent = fixed.parent.filter(|parent| known.contains(parent));
+    fixed.sessions.sort_by_key(|session| session.start);
+    fixed.comments.sort_by_key(|comment| comment.created);
+    Some(fixed)
+}
+
+fn repair_tasks(tasks: &mut Tasks, known: &HashSet<Uuid>) {
+    let fixed: Vec<Task> = tasks
+        .get_tasks()
+        .iter()
+        .filter_map(|task| repair_task(task, known))
+        .collect();
+    for task in fixed {
+        tasks.add(task);
+    }
+}
+
+fn repair_space(tasks: &mut Tasks, notes: &mut Notes, archive: &mut Tasks, trash: &mut Trash) {
+    let live: Vec<Uuid> = tasks.get_tasks().iter().map(|task| task.id).collect();
+    for id in live {
+        archive.remove(id);
+    }
+    repair_trash(trash, tasks, archive, notes);
+
+    let known = Space {
+        name: None,
+        tasks,
+        notes,
+        archive,
+        trash,
+    }
+    .known();
+    repair_tasks(tasks, &known);
+    repair_tasks(archive, &known);
+
+    let ids: Vec<Uuid> = notes.get_notes().iter().map(|note| note.id).collect();
+    for id in ids {
+        if let Some(mut note) = notes.get_note_mut(&id) {
+            let attached = note.task_id;
+            if attached.is_some_and(|task_id| {
+                tasks.get_task(&task_id).is_none() && archive.get_task(&task_id).is_none()
+            }) {
+                note.task_id = None;
+            }
+            if !is_sorted_by(&note.revisions, |revision| revision.number) {
+                note.revisions.sort_by_key(|revision| revision.number);
+            }
+        }
+    }
+}
+
+/// Puts right in `db`, which should be at rest, everything [`check`]
+/// finds. A task both live and archived, or in the trash and out of it,
+/// keeps its live copy; dangling dependencies and parents are dropped;
+/// notes attached to deleted tasks are detached; and what is out of order
+/// is sorted.
+pub fn repair(db: &mut Database) {
+    repair_space(&mut db.tasks, &mut db.notes, &mut db.archive, &mut db.trash);
+    for project in db.projects.values_mut() {
+        repair_space(
+            &mut project.tasks,
+            &mut project.notes,
+            &mut project.archive,
+            &mut project.trash,
+        );
+    }
+    db.inbox.sort_by_key(|item| item.created);
+    let current = db.current_project.as_deref();
+    if current.is_some_and(|name| name != ROOT_PROJECT && !db.projects.contains_key(name)) {
+        db.current_project = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Project;
+    use crate::note::Note;
+    use chrono::Utc;
+
+    #[test]
+    fn finds_and_repairs_what_is_broken() {
+        let mut db = Database::default();
+        let kept = Task::new(String::from("water the plants"), 0);
+        let mut blocked = Task::new(String::from("repot the fern"), 0);
+        blocked.depends.insert(kept.id);
+        blocked.depends.insert(Uuid::new_v4());
+        db.tasks.add(kept.clone());
+        db.tasks.add(blocked.clone());
+        db.archive.add(kept.clone());
+        let mut note = Note::new("soil mix");
+        note.task_id = Some(Uuid::new_v4());
+        db.notes.add(note.clone());
+
+        let mut project = Project::named("garden");
+        let gone = Task::new(String::from("mow"), 0);
+        project.tasks.add(gone.clone());
+        let now = Utc::now();
+        project.trash.delete_task(&mut project.tasks, gone.id, now);
+        project.tasks.add(gone.clone());
+        db.projects.insert(String::from("garden"), project);
+        db.current_project = Some(String::from("shed"));
+
+        let issues = check(&db);
+        let messages: Vec<&str> = issues.iter().map(|issue| issue.message.as_str()).collect();
+        assert_eq!(messages.len(), 5, "{:?}", messages);
+        assert!(messages.contains(&"task is both live and archived"));
+        assert!(issues
+            .iter()
+            .any(|issue| issue.project.as_deref() == Some("garden") && issue.id == Some(gone.id)));
+
+        repair(&mut db);
+        assert_eq!(check(&db), []);
+        assert!(db.archive.get_tasks().is_empty());
+        assert_eq!(db.tasks.get_task(&blocked.id).unwrap().depends.len(), 1);
+        assert_eq!(db.notes.get_note(&note.id).unwrap().task_id, None);
+        assert!(db.projects["garden"].trash.is_empty());
+        assert!(db.projects["garden"].tasks.get_task(&gone.id).is_some());
+        assert_eq!(db.current_project, None);
+    }
+
+    #[test]
+    fn reading_reports_what_the_file_had_wrong() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("regia.db");
+        let mut db = Database::default();
+        db.tasks.add(Task::new(String::from("water the plants"), 0));
+        db.to_disk(&path).unwrap();
+        assert_eq!(read(&path).unwrap().1, []);
+
+        // The same task twice, as an older or hand-edited file could have it
+        let mut value: serde_json::Value =
+            serde_json::from_str(&db.serialize_json().unwrap()).unwrap();
+        let tasks = value["tasks"]["tasks"].as_array_mut().unwrap();
+        tasks.push(tasks[0].clone());
+        let doubled: Database = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(doubled, db);
+        let buf = rmp_serde::to_vec(&value).unwrap();
+        std::fs::write(&path, buf).unwrap();
+        let (read_back, issues) = read(&path).unwrap();
+        assert_eq!(read_back.tasks, db.tasks);
+        assert_eq!(issues.len(), 1, "{:?}", issues);
+        assert!(issues[0]
+            .message
+            .starts_with("task id is stored more than once"));
+    }
+}
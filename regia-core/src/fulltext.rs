@@ -0,0 +1,538 @@
+//! The index behind `regia search`: the stemmed words of every task and
+//! note and where they appear, kept beside the database as `<db>.search`.
+//! Each search first brings it up to date, reading again only the tasks
+//! and notes added or edited since the last one and dropping those
+//! removed, so saves pay nothing for it.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io::ErrorKind as IOErrorKind;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{self, Database};
+use crate::error::{self, RegiaError};
+
+/// Bump whenever stemming or the layout changes, so older indexes are
+/// built again rather than misread.
+const VERSION: u32 = 1;
+
+/// How quickly more occurrences of a word stop raising the score (BM25's
+/// k1), and how much a long text is marked down for its length (b).
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// The index of the database at `db_path` is kept in `<db>.search`.
+pub fn index_path(db_path: &Path) -> PathBuf {
+    crate::db::sibling_path(db_path, ".search")
+}
+
+fn is_vowel(byte: u8) -> bool {
+    b"aeiou".contains(&byte)
+}
+
+/// A light English stemmer, so "tasks", "tasked" and "tasking" all find
+/// "task". Only the commonest endings are taken off, and words that
+/// aren't ASCII are just lowercased.
+pub fn stem(word: &str) -> String {
+    let mut word = word.to_lowercase();
+    if !word.is_ascii() || word.len() <= 3 {
+        return word;
+    }
+    if word.ends_with("sses") || word.ends_with("ies") {
+        word.truncate(word.len() - 2);
+    } else if word.ends_with('s') && !word.ends_with("ss") && !word.ends_with("us") {
+        word.pop();
+    }
+    for suffix in ["ing", "ed"] {
+        if let Some(rest) = word.strip_suffix(suffix) {
+            if rest.len() >= 3 && rest.bytes().any(is_vowel) {
+                word.truncate(rest.len());
+                // "running" and "planned" come down to "run" and "plan"
+                let bytes = word.as_bytes();
+                let last = bytes[bytes.len() - 1];
+                if last == bytes[bytes.len() - 2] && !b"aeiouylsz".contains(&last) {
+                    word.pop();
+                }
+                break;
+            }
+        }
+    }
+    let bytes = word.as_bytes();
+    if word.len() > 3 && word.ends_with('y') && !is_vowel(bytes[bytes.len() - 2]) {
+        word.pop();
+        word.push('i');
+    }
+    if word.len() > 3 && word.ends_with('e') {
+        word.pop();
+    }
+    word
+}
+
+/// Where each word of `text` is, with its stem.
+pub fn terms(text: &str) -> Vec<(Range<usize>, String)> {
+    let mut found = Vec::new();
+    let mut start = None;
+    for (at, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(at),
+            (false, Some(from)) => {
+                found.push((from..at, stem(&text[from..at])));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(from) = start {
+        found.push((from..text.len(), stem(&text[from..])));
+    }
+    found
+}
+
+/// One part of a query, which a task or note must match to be found.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Clause {
+    Word(String),
+    /// Words starting with this, unstemmed
+    Prefix(String),
+    /// These words one after another
+    Phrase(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    clauses: Vec<Clause>,
+}
+
+impl Query {
+    /// Reads words, which must all appear, "quoted phrases", whose words
+    /// must appear together and in order, and prefixes such as `rep*`.
+    pub fn parse(input: &str) -> error::Result<Query> {
+        let mut clauses = Vec::new();
+        let mut rest = input;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                break;
+            }
+            if let Some(quoted) = rest.strip_prefix('"') {
+                let end = quoted.find('"').ok_or_else(|| {
+                    RegiaError::Parse(String::from("unclosed quote in search query"))
+                })?;
+                let words: Vec<String> = terms(&quoted[..end])
+                    .into_iter()
+                    .map(|(_, term)| term)
+                    .collect();
+                if !words.is_empty() {
+                    clauses.push(Clause::Phrase(words));
+                }
+                rest = &quoted[end + 1..];
+                continue;
+            }
+
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '"')
+                .unwrap_or(rest.len());
+            let (word, prefix) = match rest[..end].strip_suffix('*') {
+                Some(word) => (word, true),
+                None => (&rest[..end], false),
+            };
+            // Words joined by punctuation, like "e-mail", are each required
+            let mut words = terms(word);
+            let last = if prefix { words.pop() } else { None };
+            clauses.extend(words.into_iter().map(|(_, term)| Clause::Word(term)));
+            if let Some((range, _)) = last {
+                clauses.push(Clause::Prefix(word[range].to_lowercase()));
+            }
+            rest = &rest[end..];
+        }
+        if clauses.is_empty() {
+            return Err(RegiaError::Parse(String::from("nothing to search for")));
+        }
+        Ok(Query { clauses })
+    }
+
+    pub fn clauses(&self) -> &[Clause] {
+        &self.clauses
+    }
+
+    /// Whether the word whose stem is `term` is one the query looks for.
+    pub fn wants(&self, word: &str, term: &str) -> bool {
+        self.clauses.iter().any(|clause| match clause {
+            Clause::Word(wanted) => wanted == term,
+            Clause::Prefix(prefix) => word.to_lowercase().starts_with(prefix),
+            Clause::Phrase(words) => words.iter().any(|wanted| wanted == term),
+        })
+    }
+}
+
+/// What was indexed of one task or note.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kind {
+    Task,
+    Note,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Doc {
+    kind: Kind,
+    /// Of the text, to tell when it has been edited
+    hash: u64,
+    /// In words
+    length: u32,
+    /// Its distinct stems, to find its postings again on removal
+    terms: Vec<String>,
+}
+
+/// A task or note found by [`SearchIndex::search`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Found {
+    pub id: Uuid,
+    pub kind: Kind,
+    pub score: f64,
+}
+
+/// `DefaultHasher` isn't promised to stay the same across Rust releases;
+/// if it changes, everything is just indexed once more.
+fn hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchIndex {
+    version: u32,
+    docs: BTreeMap<Uuid, Doc>,
+    /// For each stem, the position of every word with it in each text
+    postings: BTreeMap<String, BTreeMap<Uuid, Vec<u32>>>,
+    /// In words, for the average length scores are weighed against
+    total_length: u64,
+}
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            docs: BTreeMap::new(),
+            postings: BTreeMap::new(),
+            total_length: 0,
+        }
+    }
+}
+
+impl SearchIndex {
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    /// Indexes `text` as the task or note `id`, replacing anything indexed
+    /// for it before.
+    pub fn insert(&mut self, id: Uuid, kind: Kind, text: &str) {
+        self.remove(&id);
+        let found = terms(text);
+        let mut positions: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+        for (position, (_, term)) in found.iter().enumerate() {
+            positions
+                .entry(term.clone())
+                .or_default()
+                .push(position as u32);
+        }
+        self.total_length += found.len() as u64;
+        self.docs.insert(
+            id,
+            Doc {
+                kind,
+                hash: hash(text),
+                length: found.len() as u32,
+                terms: positions.keys().cloned().collect(),
+            },
+        );
+        for (term, at) in positions {
+            self.postings.entry(term).or_default().insert(id, at);
+        }
+    }
+
+    pub fn remove(&mut self, id: &Uuid) -> bool {
+        let doc = match self.docs.remove(id) {
+            Some(doc) => doc,
+            None => return false,
+        };
+        self.total_length -= u64::from(doc.length);
+        for term in doc.terms {
+            if let Some(docs) = self.postings.get_mut(&term) {
+                docs.remove(id);
+                if docs.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+        true
+    }
+
+    /// Indexes the tasks and notes of `db` added or edited since the last
+    /// refresh and drops those removed. Returns whether anything changed.
+    pub fn refresh(&mut self, db: &Database) -> bool {
+        let texts = db
+            .tasks
+            .get_tasks()
+            .iter()
+            .map(|task| (task.id, Kind::Task, task.content.as_str()))
+            .chain(
+                db.notes
+                    .get_notes()
+                    .iter()
+                    .map(|note| (note.id, Kind::Note, note.content.as_str())),
+            );
+        let mut current = HashSet::new();
+        let mut changed = false;
+        for (id, kind, text) in texts {
+            current.insert(id);
+            let fresh = self
+                .docs
+                .get(&id)
+                .is_some_and(|doc| doc.kind == kind && doc.hash == hash(text));
+            if !fresh {
+                self.insert(id, kind, text);
+                changed = true;
+            }
+        }
+        let gone: Vec<Uuid> = self
+            .docs
+            .keys()
+            .filter(|id| !current.contains(id))
+            .copied()
+            .collect();
+        for id in gone {
+            changed |= self.remove(&id);
+        }
+        changed
+    }
+
+    /// The BM25 score of a document holding a word `count` times, when
+    /// `matching` documents hold it.
+    fn weight(&self, id: &Uuid, count: usize, matching: usize) -> f64 {
+        let total = self.docs.len() as f64;
+        let matching = matching as f64;
+        let rarity = (1.0 + (total - matching + 0.5) / (matching + 0.5)).ln();
+        let average = (self.total_length as f64 / total).max(1.0);
+        let length = f64::from(self.docs[id].length);
+        let count = count as f64;
+        rarity * count * (K1 + 1.0) / (count + K1 * (1.0 - B + B * length / average))
+    }
+
+    fn scored<I: Iterator<Item = (Uuid, usize)>>(&self, counts: I) -> HashMap<Uuid, f64> {
+        let counts: Vec<(Uuid, usize)> = counts.filter(|(_, count)| *count > 0).collect();
+        counts
+            .iter()
+            .map(|(id, count)| (*id, self.weight(id, *count, counts.len())))
+            .collect()
+    }
+
+    /// How many times each document holds `words` one after another.
+    fn phrase_counts(&self, words: &[String]) -> Vec<(Uuid, usize)> {
+        let lists: Option<Vec<&BTreeMap<Uuid, Vec<u32>>>> =
+            words.iter().map(|word| self.postings.get(word)).collect();
+        let (first, rest) = match lists.as_deref().and_then(<[_]>::split_first) {
+            Some(split) => split,
+            None => return Vec::new(),
+        };
+        first
+            .iter()
+            .map(|(id, starts)| {
+                let count = starts
+                    .iter()
+                    .filter(|start| {
+                        rest.iter().enumerate().all(|(offset, docs)| {
+                            let want = **start + offset as u32 + 1;
+                            docs.get(id)
+                                .is_some_and(|at| at.binary_search(&want).is_ok())
+                        })
+                    })
+                    .count();
+                (*id, count)
+            })
+            .collect()
+    }
+
+    fn clause_scores(&self, clause: &Clause) -> HashMap<Uuid, f64> {
+        let counts = |docs: &BTreeMap<Uuid, Vec<u32>>| {
+            docs.iter()
+                .map(|(id, at)| (*id, at.len()))
+                .collect::<Vec<_>>()
+        };
+        match clause {
+            Clause::Word(term) => match self.postings.get(term) {
+                Some(docs) => self.scored(counts(docs).into_iter()),
+                None => HashMap::new(),
+            },
+            Clause::Prefix(prefix) => {
+                let mut scores = HashMap::new();
+                let expanded = self
+                    .postings
+                    .range(prefix.clone()..)
+                    .take_while(|(term, _)| term.starts_with(prefix.as_str()));
+                for (_, docs) in expanded {
+                    for (id, score) in self.scored(counts(docs).into_iter()) {
+                        *scores.entry(id).or_insert(0.0) += score;
+                    }
+                }
+                scores
+            }
+            Clause::Phrase(words) => self.scored(self.phrase_counts(words).into_iter()),
+        }
+    }
+
+    /// The tasks and notes matching every clause of `query`, best first.
+    pub fn search(&self, query: &Query) -> Vec<Found> {
+        let mut scores: Option<HashMap<Uuid, f64>> = None;
+        for clause in &query.clauses {
+            let matched = self.clause_scores(clause);
+            scores = Some(match scores {
+                Some(previous) => previous
+                    .into_iter()
+                    .filter_map(|(id, score)| matched.get(&id).map(|more| (id, score + more)))
+                    .collect(),
+                None => matched,
+            });
+        }
+        let mut found: Vec<Found> = scores
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(id, score)| Found {
+                id,
+                kind: self.docs[&id].kind,
+                score,
+            })
+            .collect();
+        found.sort_by(|left, right| {
+            right
+                .score
+                .total_cmp(&left.score)
+                .then(left.id.cmp(&right.id))
+        });
+        found
+    }
+}
+
+/// The index for the database at `db_path`, brought up to date with `db`
+/// and written back if that changed it. A missing, damaged or outdated
+/// file is built again from scratch.
+pub fn open(db_path: &Path, db: &Database) -> error::Result<SearchIndex> {
+    let path = index_path(db_path);
+    let mut index = match db::read_from_disk(&path) {
+        Ok(buf) => rmp_serde::from_slice::<SearchIndex>(&buf)
+            .ok()
+            .filter(|index| index.version == VERSION)
+            .unwrap_or_default(),
+        Err(err) if err.kind() == IOErrorKind::NotFound => SearchIndex::default(),
+        Err(err) => {
+            return Err(RegiaError::from(err).context(format!("can't read {}", path.display())))
+        }
+    };
+    if index.refresh(db) {
+        let buf =
+            rmp_serde::to_vec(&index).map_err(|_| RegiaError::storage("Serialization failed"))?;
+        db::write_to_disk(&path, &buf).map_err(|err| {
+            RegiaError::from(err).context(format!("can't write {}", path.display()))
+        })?;
+    }
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::Note;
+    use crate::todo::Task;
+
+    #[test]
+    fn stems_common_endings() {
+        for (word, stemmed) in [
+            ("tasks", "task"),
+            ("tasking", "task"),
+            ("Tasked", "task"),
+            ("running", "run"),
+            ("planned", "plan"),
+            ("writes", "writ"),
+            ("writing", "writ"),
+            ("stories", "stori"),
+            ("story", "stori"),
+            ("classes", "class"),
+            ("status", "status"),
+            ("bus", "bus"),
+            ("Größe", "größe"),
+        ] {
+            assert_eq!(stem(word), stemmed, "{}", word);
+        }
+    }
+
+    #[test]
+    fn parses_words_phrases_and_prefixes() {
+        let query = Query::parse(r#"Meetings "weekly  report" rep* e-mail"#).unwrap();
+        assert_eq!(
+            query.clauses(),
+            [
+                Clause::Word(String::from("meet")),
+                Clause::Phrase(vec![String::from("weekli"), String::from("report")]),
+                Clause::Prefix(String::from("rep")),
+                Clause::Word(String::from("e")),
+                Clause::Word(String::from("mail")),
+            ]
+        );
+        assert!(Query::parse(r#"open "quote"#).is_err());
+        assert!(Query::parse(" -- ").is_err());
+    }
+
+    #[test]
+    fn ranks_and_follows_the_database() {
+        let mut db = Database::default();
+        let report = Task::new(String::from("Write the weekly report"), 0);
+        let reports = Task::new(
+            String::from("Reports, reports: file the report for the board"),
+            0,
+        );
+        let groceries = Task::new(String::from("Buy groceries"), 0);
+        let note = Note::new("The report was weekly, not monthly");
+        let (report_id, reports_id, note_id) = (report.id, reports.id, note.id);
+        db.tasks.add(report);
+        db.tasks.add(reports);
+        db.tasks.add(groceries);
+        db.notes.add(note);
+
+        let mut index = SearchIndex::default();
+        assert!(index.refresh(&db));
+        assert!(!index.refresh(&db), "nothing changed");
+        assert_eq!(index.len(), 4);
+
+        let ids = |index: &SearchIndex, query: &str| -> Vec<Uuid> {
+            let query = Query::parse(query).unwrap();
+            index.search(&query).iter().map(|found| found.id).collect()
+        };
+        assert_eq!(ids(&index, "report")[0], reports_id, "said most often");
+        assert_eq!(ids(&index, "reporting").len(), 3);
+        assert_eq!(ids(&index, r#""weekly report""#), [report_id]);
+        assert_eq!(ids(&index, "weekly report").len(), 2);
+        assert_eq!(ids(&index, "month*"), [note_id]);
+        assert!(ids(&index, "report groceries").is_empty());
+
+        db.tasks.get_task_mut(&report_id).unwrap().content = String::from("Call the bank");
+        db.notes.remove(note_id);
+        assert!(index.refresh(&db));
+        assert_eq!(index.len(), 3);
+        assert_eq!(ids(&index, "report"), [reports_id]);
+        assert_eq!(ids(&index, "bank"), [report_id]);
+        assert_eq!(
+            index.search(&Query::parse("bank").unwrap())[0].kind,
+            Kind::Task
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! Fuzzy matching, for picking tasks by a few characters of their content.
+
+/// Points for each matched character.
+const MATCH: i64 = 16;
+/// Extra points for a character right after the previous match.
+const CONSECUTIVE: i64 = 12;
+/// Extra points for a match at the start of a word.
+const WORD_START: i64 = 8;
+/// Points lost per skipped character, up to `MAX_GAP` per gap.
+const GAP: i64 = 1;
+const MAX_GAP: i64 = 8;
+
+fn is_word_start(text: &[char], at: usize) -> bool {
+    at == 0
+        || !text[at - 1].is_alphanumeric()
+        || (text[at - 1].is_lowercase() && text[at].is_uppercase())
+}
+
+fn gap_cost(skipped: usize) -> i64 {
+    (skipped as i64 * GAP).min(MAX_GAP)
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// How well `pattern` matches `text`, ignoring case: `None` unless all of
+/// its characters appear in `text` in order, and higher for runs of
+/// consecutive characters and matches at word starts. This takes the best
+/// of every way the pattern can line up.
+pub fn score(pattern: &str, text: &str) -> Option<i64> {
+    let pattern: Vec<char> = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(fold)
+        .collect();
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    let original: Vec<char> = text.chars().collect();
+    let text: Vec<char> = original.iter().map(|c| fold(*c)).collect();
+
+    // best[j]: the best score with the current pattern character at text[j]
+    let mut best: Vec<Option<i64>> = vec![None; text.len()];
+    for (i, wanted) in pattern.iter().enumerate() {
+        let mut next = vec![None; text.len()];
+        for j in (0..text.len()).filter(|j| text[*j] == *wanted) {
+            let word = if is_word_start(&original, j) {
+                WORD_START
+            } else {
+                0
+            };
+            let here = MATCH + word;
+            next[j] = if i == 0 {
+                Some(here - gap_cost(j))
+            } else {
+                (0..j)
+                    .filter_map(|k| {
+                        let bonus = if k + 1 == j {
+                            CONSECUTIVE
+                        } else {
+                            -gap_cost(j - k - 1)
+                        };
+                        best[k].map(|prev| prev + here + bonus)
+                    })
+                    .max()
+            };
+        }
+        best = next;
+    }
+    best.into_iter().flatten().max()
+}
+
+/// The `items` whose `key` matches `pattern`, best first. Ties keep the
+/// order they came in.
+pub fn rank<T>(
+    pattern: &str,
+    items: impl IntoIterator<Item = T>,
+    key: impl Fn(&T) -> &str,
+) -> Vec<T> {
+    let mut scored: Vec<(i64, T)> = items
+        .into_iter()
+        .filter_map(|item| score(pattern, key(&item)).map(|points| (points, item)))
+        .collect();
+    scored.sort_by_key(|(points, _)| std::cmp::Reverse(*points));
+    scored.into_iter().map(|(_, item)| item).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subsequences_match_ignoring_case() {
+        assert!(score("pyrnt", "Pay rent").is_some());
+        assert!(score("rent pay", "Pay rent").is_none());
+        assert!(score("xyz", "Pay rent").is_none());
+    }
+
+    #[test]
+    fn ranks_runs_and_word_starts_first() {
+        let tasks = ["prepare an elaborate note", "pay rent", "present"];
+        assert_eq!(
+            rank("rent", tasks, |task| task),
+            ["pay rent", "present", "prepare an elaborate note"]
+        );
+        assert_eq!(
+            rank("cb", ["scrub", "call bob"], |task| task),
+            ["call bob", "scrub"]
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! What happened to tasks over time, kept apart from the tasks themselves
+//! so that archiving, purging or editing them loses none of it.
+//!
+//! Events are never changed once recorded. [`db::Database::to_disk`]
+//! records them as it saves, for every task completed and every past
+//! instance of a repeated task left undone, so whatever changed a task
+//! needs do nothing about its history.
+//!
+//! [`db::Database::to_disk`]: crate::db::Database::to_disk
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::Database;
+use crate::todo::{RepeatType, Task, Tasks};
+use crate::trash::Trash;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Completed,
+    /// A repeated task whose next instance came due before it was done
+    Missed,
+}
+
+/// One thing that happened to a task, with what the task was at the time.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Event {
+    pub task: Uuid,
+    pub kind: Kind,
+    pub at: DateTime<Utc>,
+    /// Seconds from the task's creation to `at`
+    pub open: i64,
+    pub content: String,
+    /// The project the task was in, `None` for the root
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub repeat: Option<RepeatType>,
+    /// The series of a repeated task, see [`Task::series_id`]
+    #[serde(default)]
+    pub series: Option<Uuid>,
+}
+
+impl Event {
+    fn of(task: &Task, kind: Kind, at: DateTime<Utc>, project: Option<&str>) -> Self {
+        let mut tags: Vec<String> = task.tags.iter().cloned().collect();
+        tags.sort();
+        Self {
+            task: task.id,
+            kind,
+            at,
+            open: (at - task.created).num_seconds().max(0),
+            content: task.content.clone(),
+            project: project.map(String::from),
+            tags,
+            repeat: task.repeat.clone(),
+            series: task.repeat.as_ref().map(|_| task.series_id()),
+        }
+    }
+
+    /// A task has at most one event of each kind.
+    fn key(&self) -> (Uuid, Kind) {
+        (self.task, self.kind)
+    }
+}
+
+/// The event `task` is due, if it has one.
+fn event_for(task: &Task, project: Option<&str>) -> Option<Event> {
+    match task.completed {
+        Some(completed) => Some(Event::of(task, Kind::Completed, completed, project)),
+        None if task.repeat.is_some() && task.successor.is_some() => {
+            let passed = task.due.unwrap_or(task.created);
+            Some(Event::of(task, Kind::Missed, passed, project))
+        }
+        None => None,
+    }
+}
+
+fn space_events(
+    project: Option<&str>,
+    tasks: &Tasks,
+    archive: &Tasks,
+    trash: &Trash,
+    known: &HashSet<(Uuid, Kind)>,
+    events: &mut Vec<Event>,
+) {
+    let trashed = trash.tasks.iter().map(|trashed| &trashed.item);
+    for task in tasks
+        .get_tasks()
+        .iter()
+        .chain(archive.get_tasks())
+        .chain(trashed)
+    {
+        if let Some(event) = event_for(task, project) {
+            if !known.contains(&event.key()) {
+                events.push(event);
+            }
+        }
+    }
+}
+
+/// The events the tasks of every project in `db` call for that its
+/// history doesn't have yet, whichever project is active.
+pub fn pending(db: &Database) -> Vec<Event> {
+    let known: HashSet<(Uuid, Kind)> = db.history.iter().map(Event::key).collect();
+    let active = db.active_project();
+    let mut events = Vec::new();
+    space_events(
+        active,
+        &db.tasks,
+        &db.archive,
+        &db.trash,
+        &known,
+        &mut events,
+    );
+    for (name, project) in db.projects.iter() {
+        // The active project's place holds the root while it is swapped out
+        let name = if Some(name.as_str()) == active {
+            None
+        } else {
+            Some(name.as_str())
+        };
+        let (tasks, archive, trash) = (&project.tasks, &project.archive, &project.trash);
+        space_events(name, tasks, archive, trash, &known, &mut events);
+    }
+    events.sort_by_key(|event| event.at);
+    events
+}
+
+/// Adds the `events` not in `history` already.
+pub fn merge(history: &mut Vec<Event>, events: Vec<Event>) {
+    let mut known: HashSet<(Uuid, Kind)> = history.iter().map(Event::key).collect();
+    for event in events {
+        if known.insert(event.key()) {
+            history.push(event);
+        }
+    }
+}
+
+/// The history of the active project, what is yet to be recorded
+/// included, oldest first.
+pub fn of_active(db: &Database) -> Vec<Event> {
+    let active = db.active_project();
+    let mut events: Vec<Event> = db
+        .history
+        .iter()
+        .cloned()
+        .chain(pending(db))
+        .filter(|event| event.project.as_deref() == active)
+        .collect();
+    events.sort_by_key(|event| event.at);
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Project;
+    use chrono::Duration;
+
+    #[test]
+    fn events_are_recorded_once() {
+        let now = Utc::now();
+        let mut db = Database::default();
+        let mut done = Task::new(String::from("done"), 0);
+        done.created = now - Duration::hours(3);
+        done.completed = Some(now);
+        let mut missed = Task::new(String::from("standup"), 0);
+        missed.repeat = Some(RepeatType::Daily);
+        missed.due = Some(now - Duration::days(1));
+        missed.successor = Some(Uuid::new_v4());
+        db.tasks.add(done.clone());
+        db.archive.add(missed.clone());
+        db.tasks.add(Task::new(String::from("open"), 0));
+        let mut other = Project::named("work");
+        let mut elsewhere = Task::new(String::from("elsewhere"), 0);
+        elsewhere.complete();
+        other.tasks.add(elsewhere.clone());
+        db.projects.insert(String::from("work"), other);
+
+        let events = pending(&db);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].kind, Kind::Missed);
+        let completed = events.iter().find(|event| event.task == done.id).unwrap();
+        assert_eq!(completed.open, Duration::hours(3).num_seconds());
+        assert_eq!(
+            (completed.kind, completed.project.as_deref()),
+            (Kind::Completed, None)
+        );
+
+        merge(&mut db.history, events.clone());
+        merge(&mut db.history, events);
+        assert_eq!(db.history.len(), 3);
+        assert!(pending(&db).is_empty());
+
+        // Pruning a task keeps its history, and switching projects keeps
+        // telling their events apart
+        db.tasks.remove(done.id);
+        db.activate(Some("work")).unwrap();
+        assert!(pending(&db).is_empty());
+        let work = of_active(&db);
+        assert_eq!(work.len(), 1);
+        assert_eq!(work[0].task, elsewhere.id);
+    }
+}
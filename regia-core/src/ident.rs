@@ -0,0 +1,188 @@
+//! Naming tasks and notes on the command line. An id can be given whole,
+//! or as any prefix of it that only one item has, with or without its
+//! hyphens and in either case. The short ids listings print, see
+//! [`short`], are such prefixes.
+
+use std::fmt;
+
+use uuid::Uuid;
+
+use crate::error::RegiaError;
+use crate::index::Indexed;
+
+/// How many hex digits a short id has.
+pub const SHORT: usize = 8;
+
+/// How many of the candidates an ambiguous prefix fits its message names.
+const LISTED: usize = 5;
+
+/// `id` as listings abbreviate it.
+pub fn short(id: &Uuid) -> String {
+    id.to_simple().to_string()[..SHORT].to_string()
+}
+
+/// Why [`resolve`] found no single item.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unresolved {
+    /// No item has the id, or an id starting so
+    NotFound { kind: &'static str, given: String },
+    /// Several items have ids starting so, these
+    Ambiguous {
+        kind: &'static str,
+        given: String,
+        candidates: Vec<Uuid>,
+    },
+}
+
+impl fmt::Display for Unresolved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unresolved::NotFound { kind, given } if Uuid::parse_str(given).is_ok() => {
+                write!(f, "no {} with id: {}", kind, given)
+            }
+            Unresolved::NotFound { kind, given } => {
+                write!(f, "no {} id starts with: {}", kind, given)
+            }
+            Unresolved::Ambiguous {
+                kind,
+                given,
+                candidates,
+            } => {
+                let mut listed: Vec<String> = candidates
+                    .iter()
+                    .take(LISTED)
+                    .map(Uuid::to_string)
+                    .collect();
+                if candidates.len() > LISTED {
+                    listed.push(format!("{} more", candidates.len() - LISTED));
+                }
+                write!(
+                    f,
+                    "id prefix {} fits {} {}s, give more of it: {}",
+                    given,
+                    candidates.len(),
+                    kind,
+                    listed.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl From<Unresolved> for RegiaError {
+    fn from(unresolved: Unresolved) -> Self {
+        match unresolved {
+            Unresolved::NotFound { .. } => RegiaError::NotFound(unresolved.to_string()),
+            Unresolved::Ambiguous { .. } => RegiaError::Conflict(unresolved.to_string()),
+        }
+    }
+}
+
+/// `given` as the hex digits an id starts with, if it can be that.
+fn digits(given: &str) -> Option<String> {
+    let digits: String = given
+        .trim()
+        .chars()
+        .filter(|c| *c != '-')
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        None
+    } else {
+        Some(digits)
+    }
+}
+
+/// The ids of `ids` that `given` is or starts.
+pub fn matching(ids: impl IntoIterator<Item = Uuid>, given: &str) -> Vec<Uuid> {
+    match digits(given) {
+        Some(digits) => ids
+            .into_iter()
+            .filter(|id| id.to_simple().to_string().starts_with(&digits))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// The one id of `ids`, ids of `kind`, that `given` names.
+pub fn resolve_among(
+    ids: impl IntoIterator<Item = Uuid>,
+    kind: &'static str,
+    given: &str,
+) -> Result<Uuid, Unresolved> {
+    let not_found = || Unresolved::NotFound {
+        kind,
+        given: given.trim().to_string(),
+    };
+    if let Ok(id) = Uuid::parse_str(given.trim()) {
+        return ids
+            .into_iter()
+            .find(|candidate| *candidate == id)
+            .ok_or_else(not_found);
+    }
+    let mut found = matching(ids, given);
+    match found.len() {
+        0 => Err(not_found()),
+        1 => Ok(found[0]),
+        _ => {
+            found.sort();
+            Err(Unresolved::Ambiguous {
+                kind,
+                given: given.trim().to_string(),
+                candidates: found,
+            })
+        }
+    }
+}
+
+/// The one item of `items` that `given` names.
+pub fn resolve<T: Indexed>(items: &[T], given: &str) -> Result<Uuid, Unresolved> {
+    resolve_among(items.iter().map(Indexed::id), T::KIND, given)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::Task;
+
+    #[test]
+    fn resolves_ids_and_prefixes() {
+        let mut first = Task::new(String::from("first"), 0);
+        first.id = Uuid::parse_str("0a1b0000-0000-4000-8000-000000000000").unwrap();
+        let mut second = Task::new(String::from("second"), 0);
+        second.id = Uuid::parse_str("0a2c0000-0000-4000-8000-000000000000").unwrap();
+        let tasks = [first.clone(), second.clone()];
+
+        assert_eq!(resolve(&tasks, &first.id.to_string()), Ok(first.id));
+        assert_eq!(resolve(&tasks, "0A1"), Ok(first.id));
+        assert_eq!(resolve(&tasks, &short(&second.id)), Ok(second.id));
+        // Hyphens or not, as long as the digits line up
+        assert_eq!(resolve(&tasks, "0a2c00000000-4"), Ok(second.id));
+        assert_eq!(resolve(&tasks, "0a2c0000000040"), Ok(second.id));
+
+        let ambiguous = resolve(&tasks, "0a").unwrap_err();
+        assert_eq!(
+            ambiguous,
+            Unresolved::Ambiguous {
+                kind: "task",
+                given: String::from("0a"),
+                candidates: vec![first.id, second.id],
+            }
+        );
+        assert_eq!(
+            RegiaError::from(ambiguous).to_string(),
+            "id prefix 0a fits 2 tasks, give more of it: \
+             0a1b0000-0000-4000-8000-000000000000, 0a2c0000-0000-4000-8000-000000000000"
+        );
+
+        let missing = Uuid::parse_str("ff000000-0000-4000-8000-000000000000").unwrap();
+        assert!(matches!(
+            resolve(&tasks, &missing.to_string()),
+            Err(Unresolved::NotFound { .. })
+        ));
+        assert_eq!(
+            resolve(&tasks, "water").unwrap_err().to_string(),
+            "no task id starts with: water"
+        );
+    }
+}
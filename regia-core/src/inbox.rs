@@ -0,0 +1,34 @@
+//! Thoughts captured with `regia in`, waiting for `regia triage` to make
+//! tasks or notes of them. The inbox is shared by every project, since
+//! where a thought belongs is one of the things triage decides.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Item {
+    pub id: Uuid,
+    pub created: DateTime<Utc>,
+    pub content: String,
+}
+
+impl Item {
+    pub fn new(content: &str) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            created: Utc::now(),
+            content: content.to_string(),
+        }
+    }
+}
+
+/// Adds the items of `incoming` not in `inbox` already, oldest first.
+pub fn merge(inbox: &mut Vec<Item>, incoming: Vec<Item>) {
+    for item in incoming {
+        if !inbox.iter().any(|known| known.id == item.id) {
+            inbox.push(item);
+        }
+    }
+    inbox.sort_by_key(|item| item.created);
+}
@@ -0,0 +1,451 @@
+//! The lists behind `Tasks` and `Notes`, with lookups by id, tag and word
+//! that are built on load and kept up to date by every change.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// What a `List` needs to know about the items it indexes.
+pub trait Indexed {
+    /// What an item is called in messages, like `task`
+    const KIND: &'static str;
+    fn id(&self) -> Uuid;
+    fn tags(&self) -> &HashSet<String>;
+    /// The text whose words can be looked up
+    fn text(&self) -> &str;
+    /// The ids of the items this one waits on, looked up the other way
+    /// round by [`List::dependents`]
+    fn depends(&self) -> Vec<Uuid> {
+        Vec::new()
+    }
+}
+
+/// Sorts every array of plain strings, which is how the `HashSet` fields
+/// serialize, so equal items always produce the same JSON.
+pub fn canonical(value: Value) -> Value {
+    match value {
+        Value::Array(items) => {
+            let mut items: Vec<Value> = items.into_iter().map(canonical).collect();
+            if items.iter().all(Value::is_string) {
+                items.sort_by(|left, right| left.as_str().cmp(&right.as_str()));
+            }
+            Value::Array(items)
+        }
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key, canonical(value)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// Whether two items hold the same, as their `PartialEq` by id can't
+/// say. Their encodings are compared, and when those differ their
+/// [`canonical`] JSON, as a `HashSet` may encode in any order.
+pub fn same<T: Serialize>(left: &T, right: &T) -> bool {
+    let encode = |item: &T| {
+        let mut buf = Vec::new();
+        item.serialize(&mut rmp_serde::Serializer::new(&mut buf))
+            .ok()
+            .map(|_| buf)
+    };
+    if let (Some(left), Some(right)) = (encode(left), encode(right)) {
+        if left == right {
+            return true;
+        }
+    }
+    match (serde_json::to_value(left), serde_json::to_value(right)) {
+        (Ok(left), Ok(right)) => canonical(left) == canonical(right),
+        _ => false,
+    }
+}
+
+/// The lowercased words of `text`, split at anything not alphanumeric.
+pub fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+struct Index {
+    positions: HashMap<Uuid, usize>,
+    tags: HashMap<String, HashSet<Uuid>>,
+    /// Sorted, so words starting with a prefix are a range
+    words: BTreeMap<String, HashSet<Uuid>>,
+    /// The items waiting on each id
+    dependents: HashMap<Uuid, HashSet<Uuid>>,
+}
+
+impl Index {
+    fn add_terms<T: Indexed>(&mut self, item: &T) {
+        for tag in item.tags() {
+            self.tags.entry(tag.clone()).or_default().insert(item.id());
+        }
+        for word in words(item.text()) {
+            self.words.entry(word).or_default().insert(item.id());
+        }
+        for dep in item.depends() {
+            self.dependents.entry(dep).or_default().insert(item.id());
+        }
+    }
+
+    fn remove_terms<T: Indexed>(&mut self, item: &T) {
+        for tag in item.tags() {
+            if let Some(ids) = self.tags.get_mut(tag) {
+                ids.remove(&item.id());
+                if ids.is_empty() {
+                    self.tags.remove(tag);
+                }
+            }
+        }
+        for word in words(item.text()) {
+            if let Some(ids) = self.words.get_mut(&word) {
+                ids.remove(&item.id());
+                if ids.is_empty() {
+                    self.words.remove(&word);
+                }
+            }
+        }
+        for dep in item.depends() {
+            if let Some(ids) = self.dependents.get_mut(&dep) {
+                ids.remove(&item.id());
+                if ids.is_empty() {
+                    self.dependents.remove(&dep);
+                }
+            }
+        }
+    }
+}
+
+/// Something a `List` put right as it was read, see [`audit`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Irregularity {
+    /// The items weren't in id order
+    Unsorted { kind: &'static str },
+    /// More than one item had this id; the first was kept
+    Duplicate { kind: &'static str, id: Uuid },
+}
+
+thread_local! {
+    static AUDIT: RefCell<Option<Vec<Irregularity>>> = const { RefCell::new(None) };
+}
+
+/// Runs `read`, collecting what every `List` it deserializes had to sort
+/// or drop. Lists put these right either way; this is for telling that
+/// they had to.
+pub fn audit<R>(read: impl FnOnce() -> R) -> (R, Vec<Irregularity>) {
+    let outer = AUDIT.with(|audit| audit.replace(Some(Vec::new())));
+    let result = read();
+    let found = AUDIT.with(|audit| audit.replace(outer)).unwrap_or_default();
+    (result, found)
+}
+
+fn note_irregularities<T: Indexed>(items: &[T]) {
+    AUDIT.with(|audit| {
+        if let Some(found) = audit.borrow_mut().as_mut() {
+            if items.windows(2).any(|pair| pair[0].id() > pair[1].id()) {
+                found.push(Irregularity::Unsorted { kind: T::KIND });
+            }
+            let mut seen = HashSet::new();
+            for item in items {
+                if !seen.insert(item.id()) {
+                    found.push(Irregularity::Duplicate {
+                        kind: T::KIND,
+                        id: item.id(),
+                    });
+                }
+            }
+        }
+    });
+}
+
+/// Items kept sorted by id, one per id. Serializes as a plain sequence, so
+/// the index costs nothing on disk.
+#[derive(Clone)]
+pub struct List<T> {
+    items: Vec<T>,
+    index: Index,
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            index: Index::default(),
+        }
+    }
+}
+
+impl<T: Indexed> From<Vec<T>> for List<T> {
+    fn from(mut items: Vec<T>) -> Self {
+        note_irregularities(&items);
+        items.sort_by_key(|item| item.id());
+        items.dedup_by_key(|item| item.id());
+        let mut list = List {
+            items,
+            index: Index::default(),
+        };
+        list.reindex();
+        list
+    }
+}
+
+impl<T: Indexed> List<T> {
+    fn reindex(&mut self) {
+        let mut index = Index::default();
+        for (position, item) in self.items.iter().enumerate() {
+            index.positions.insert(item.id(), position);
+            index.add_terms(item);
+        }
+        self.index = index;
+    }
+
+    /// Every item, sorted by id.
+    pub fn items(&self) -> &Vec<T> {
+        &self.items
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<&T> {
+        self.index
+            .positions
+            .get(id)
+            .map(|position| &self.items[*position])
+    }
+
+    /// The item with `id`, to change in place. Its tags and words are
+    /// indexed again when the returned handle is dropped.
+    pub fn get_mut(&mut self, id: &Uuid) -> Option<ItemMut<'_, T>> {
+        let position = *self.index.positions.get(id)?;
+        self.index.remove_terms(&self.items[position]);
+        Some(ItemMut {
+            list: self,
+            position,
+            original: *id,
+        })
+    }
+
+    /// Adds `item` in id order, replacing any item with the same id.
+    pub fn insert(&mut self, item: T) {
+        let id = item.id();
+        if let Some(mut existing) = self.get_mut(&id) {
+            *existing = item;
+            return;
+        }
+        let position = self.items.partition_point(|probe| probe.id() < id);
+        self.index.add_terms(&item);
+        self.items.insert(position, item);
+        for (offset, item) in self.items[position..].iter().enumerate() {
+            self.index.positions.insert(item.id(), position + offset);
+        }
+    }
+
+    pub fn remove(&mut self, id: &Uuid) -> Option<T> {
+        let position = self.index.positions.remove(id)?;
+        let item = self.items.remove(position);
+        self.index.remove_terms(&item);
+        for (offset, item) in self.items[position..].iter().enumerate() {
+            self.index.positions.insert(item.id(), position + offset);
+        }
+        Some(item)
+    }
+
+    fn in_order(&self, ids: HashSet<Uuid>) -> Vec<&T> {
+        let mut positions: Vec<usize> = ids
+            .iter()
+            .filter_map(|id| self.index.positions.get(id).copied())
+            .collect();
+        positions.sort_unstable();
+        positions
+            .into_iter()
+            .map(|position| &self.items[position])
+            .collect()
+    }
+
+    /// Items carrying every tag in `wanted`, sorted by id.
+    pub fn tagged(&self, wanted: &HashSet<String>) -> Vec<&T> {
+        let mut sets: Vec<&HashSet<Uuid>> = Vec::new();
+        for tag in wanted {
+            match self.index.tags.get(tag) {
+                Some(ids) => sets.push(ids),
+                None => return Vec::new(),
+            }
+        }
+        sets.sort_by_key(|ids| ids.len());
+        let (smallest, rest) = match sets.split_first() {
+            Some(split) => split,
+            None => return self.items.iter().collect(),
+        };
+        let ids = smallest
+            .iter()
+            .filter(|id| rest.iter().all(|ids| ids.contains(id)))
+            .copied()
+            .collect();
+        self.in_order(ids)
+    }
+
+    /// Items that depend on `id`, sorted by id.
+    pub fn dependents(&self, id: &Uuid) -> Vec<&T> {
+        match self.index.dependents.get(id) {
+            Some(ids) => self.in_order(ids.clone()),
+            None => Vec::new(),
+        }
+    }
+
+    /// Items with, for every word of `query`, a word starting with it,
+    /// ignoring case, sorted by id.
+    pub fn with_words(&self, query: &str) -> Vec<&T> {
+        let mut matched: Option<HashSet<Uuid>> = None;
+        for prefix in words(query) {
+            let ids: HashSet<Uuid> = self
+                .index
+                .words
+                .range(prefix.clone()..)
+                .take_while(|(word, _)| word.starts_with(&prefix))
+                .flat_map(|(_, ids)| ids.iter().copied())
+                .collect();
+            matched = Some(match matched {
+                Some(previous) => previous.intersection(&ids).copied().collect(),
+                None => ids,
+            });
+        }
+        match matched {
+            Some(ids) => self.in_order(ids),
+            None => self.items.iter().collect(),
+        }
+    }
+}
+
+impl<T: PartialEq> PartialEq for List<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.items.fmt(f)
+    }
+}
+
+impl<T: Serialize> Serialize for List<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.items.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de> + Indexed> Deserialize<'de> for List<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<T>::deserialize(deserializer).map(List::from)
+    }
+}
+
+/// An item borrowed from a `List` to change, put back in the index when
+/// dropped.
+pub struct ItemMut<'a, T: Indexed> {
+    list: &'a mut List<T>,
+    position: usize,
+    /// Not `id`, which would hide the item's own field from this crate
+    original: Uuid,
+}
+
+impl<T: Indexed> Deref for ItemMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.list.items[self.position]
+    }
+}
+
+impl<T: Indexed> DerefMut for ItemMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.list.items[self.position]
+    }
+}
+
+impl<T: Indexed> Drop for ItemMut<'_, T> {
+    fn drop(&mut self) {
+        let list = &mut *self.list;
+        if list.items[self.position].id() == self.original {
+            list.index.add_terms(&list.items[self.position]);
+        } else {
+            // A new id moves the item, so sort and index everything again
+            let items = std::mem::take(&mut list.items);
+            *list = List::from(items);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::Task;
+
+    #[test]
+    fn same_ignores_set_order() {
+        let mut left = Task::new(String::from("tagged"), 0);
+        let mut right = left.clone();
+        for tag in ["a", "b", "c", "d", "e", "f"] {
+            left.tags.insert(String::from(tag));
+        }
+        for tag in ["f", "e", "d", "c", "b", "a"] {
+            right.tags.insert(String::from(tag));
+        }
+        assert!(same(&left, &right));
+        right.tags.remove("a");
+        assert!(!same(&left, &right));
+    }
+
+    fn tagged(content: &str, tags: &[&str]) -> Task {
+        let mut task = Task::new(String::from(content), 0);
+        task.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        task
+    }
+
+    #[test]
+    fn index_follows_changes() {
+        let report = tagged("Write the quarterly report", &["work"]);
+        let groceries = tagged("Buy groceries", &["home"]);
+        let review = tagged("Review the report draft", &["work", "urgent"]);
+        let mut list = List::from(vec![report.clone(), groceries.clone()]);
+        list.insert(review.clone());
+
+        let ids = |found: Vec<&Task>| found.iter().map(|task| task.id).collect::<Vec<_>>();
+        let work: HashSet<String> = vec![String::from("work")].into_iter().collect();
+        let mut expected = vec![report.id, review.id];
+        expected.sort();
+        assert_eq!(ids(list.tagged(&work)), expected);
+        assert_eq!(ids(list.with_words("REP")), expected);
+        assert_eq!(ids(list.with_words("rep draft")), vec![review.id]);
+        assert!(list.with_words("reports").is_empty());
+
+        list.get_mut(&report.id).unwrap().tags.clear();
+        assert_eq!(ids(list.tagged(&work)), vec![review.id]);
+        list.remove(&review.id);
+        assert!(list.tagged(&work).is_empty());
+        assert_eq!(list.get(&groceries.id).unwrap().content, "Buy groceries");
+
+        list.get_mut(&report.id)
+            .unwrap()
+            .depends
+            .insert(groceries.id);
+        assert_eq!(ids(list.dependents(&groceries.id)), vec![report.id]);
+        list.get_mut(&report.id).unwrap().depends.clear();
+        assert!(list.dependents(&groceries.id).is_empty());
+
+        // Changing an id moves the item to its new place
+        let moved = Uuid::nil();
+        list.get_mut(&groceries.id).unwrap().id = moved;
+        assert!(list.get(&groceries.id).is_none());
+        assert_eq!(list.items()[0].id, moved);
+        assert_eq!(ids(list.with_words("groceries")), vec![moved]);
+    }
+}
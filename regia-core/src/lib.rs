@@ -0,0 +1,55 @@
+//! The data model behind the `regia` command line tool, for anything else
+//! that wants to read or change the same database.
+//!
+//! A [`db::Database`] holds [`todo::Tasks`] and [`note::Notes`], split into
+//! projects. Open the configured one with [`db::open`], or any file with
+//! [`db::Database::load`], and save it back with [`db::Database::to_disk`].
+//! Writers should hold a [`lock::DbLock`] from [`lock::acquire`] across
+//! the whole load-change-save cycle, as the CLI does, and call
+//! [`schedule::tick`] to keep repeated tasks rolling. Everything that can
+//! fail returns an [`error::RegiaError`].
+//!
+//! ```no_run
+//! use regia_core::{dates, db, lock, schedule, todo};
+//!
+//! let path = std::path::Path::new("regia.db");
+//! let _lock = lock::acquire(path, "my tool", lock::Wait::Forever)?;
+//! let mut db = db::Database::load(path)?;
+//! schedule::tick(&mut db.tasks, chrono::Utc::now(), &dates::Local);
+//! db.tasks.add(todo::Task::new(String::from("water the plants"), 1));
+//! db.to_disk(path)?;
+//! # Ok::<(), regia_core::error::RegiaError>(())
+//! ```
+
+pub mod attach;
+pub mod backup;
+pub mod compress;
+pub mod conf;
+pub mod contexts;
+pub mod cron;
+pub mod dates;
+pub mod db;
+pub mod diff;
+pub mod error;
+pub mod fsck;
+pub mod fulltext;
+pub mod fuzzy;
+pub mod history;
+pub mod ident;
+pub mod inbox;
+pub mod index;
+pub mod links;
+pub mod lock;
+pub mod markdown;
+pub mod note;
+pub mod priority;
+pub mod query;
+pub mod schedule;
+pub mod tags;
+pub mod theme;
+pub mod todo;
+pub mod trash;
+pub mod urgency;
+pub mod view;
+pub mod wal;
+pub mod workflow;
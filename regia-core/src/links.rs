@@ -0,0 +1,163 @@
+//! `[[title]]` and `[[uuid]]` links between notes, and the backlinks they
+//! make.
+//!
+//! A link names a note of the same project by its title, its first line
+//! without any heading marks, or by its id. [`refresh`] resolves the links
+//! of every note into [`Note::links`] and [`db::Database::to_disk`] runs it
+//! as it saves, so a link to a note written later starts working then and
+//! one to a note renamed away stops.
+//!
+//! [`db::Database::to_disk`]: crate::db::Database::to_disk
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use uuid::Uuid;
+
+use crate::db::Database;
+use crate::note::{Note, Notes};
+
+/// What the `[[...]]` links in `text` name, in order, trimmed.
+pub fn targets(text: &str) -> Vec<&str> {
+    let mut found = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let end = match after.find("]]") {
+            Some(end) => end,
+            None => break,
+        };
+        let target = after[..end].trim();
+        if !target.is_empty() && !target.contains('\n') && !target.contains("[[") {
+            found.push(target);
+        }
+        rest = &after[end + 2..];
+    }
+    found
+}
+
+/// What links call `note`: its first line, without a markdown heading's
+/// `#`s.
+pub fn title(note: &Note) -> &str {
+    let first = note.content.lines().next().unwrap_or("");
+    first.trim_start_matches('#').trim()
+}
+
+/// The note `target` names in `notes`: the one with that id, else the
+/// oldest with that title, ignoring case.
+pub fn resolve(notes: &Notes, target: &str) -> Option<Uuid> {
+    if let Ok(id) = Uuid::parse_str(target) {
+        if notes.get_note(&id).is_some() {
+            return Some(id);
+        }
+    }
+    notes
+        .get_notes()
+        .iter()
+        .filter(|note| title(note).eq_ignore_ascii_case(target))
+        .min_by_key(|note| note.created)
+        .map(|note| note.id)
+}
+
+/// The notes `note` links to in `notes`, leaving itself out.
+fn resolved(notes: &Notes, note: &Note) -> BTreeSet<Uuid> {
+    targets(&note.content)
+        .into_iter()
+        .filter_map(|target| resolve(notes, target))
+        .filter(|id| *id != note.id)
+        .collect()
+}
+
+/// The notes whose `links` no longer match what they link to, with what
+/// they should be.
+fn stale(notes: &Notes) -> Vec<(Uuid, BTreeSet<Uuid>)> {
+    notes
+        .get_notes()
+        .iter()
+        .map(|note| (note.id, resolved(notes, note)))
+        .filter(|(id, links)| notes.get_note(id).is_some_and(|note| note.links != *links))
+        .collect()
+}
+
+/// Whether any note of any project in `db` has links to resolve again.
+pub fn any_stale(db: &Database) -> bool {
+    !stale(&db.notes).is_empty()
+        || db
+            .projects
+            .values()
+            .any(|project| !stale(&project.notes).is_empty())
+}
+
+/// Resolves the links of every note in `notes` again.
+pub fn refresh(notes: &mut Notes) {
+    for (id, links) in stale(notes) {
+        if let Some(mut note) = notes.get_note_mut(&id) {
+            note.links = links;
+        }
+    }
+}
+
+/// Resolves the links of every note of every project in `db` again.
+pub fn refresh_all(db: &mut Database) {
+    refresh(&mut db.notes);
+    for project in db.projects.values_mut() {
+        refresh(&mut project.notes);
+    }
+}
+
+/// The backlink index of `notes`: for each note linked to, the notes that
+/// link to it.
+pub fn backlinks(notes: &Notes) -> BTreeMap<Uuid, BTreeSet<Uuid>> {
+    let mut index: BTreeMap<Uuid, BTreeSet<Uuid>> = BTreeMap::new();
+    for note in notes.get_notes() {
+        for target in note.links.iter() {
+            index.entry(*target).or_default().insert(note.id);
+        }
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_resolve_to_titles_and_ids() {
+        assert_eq!(
+            targets("see [[Recipes]] and [[ a b ]], not [[]] or [[open"),
+            ["Recipes", "a b"]
+        );
+
+        let mut notes = Notes::default();
+        let recipes = Note::new("# Recipes\n\nPancakes");
+        let mut shopping = Note::new("Shopping\n\nfor [[recipes]]");
+        let later = Note::new(&format!(
+            "Later: [[{}]] [[nowhere]] [[Later:]]",
+            shopping.id
+        ));
+        shopping.content.push_str(&format!(" and [[{}]]", later.id));
+        for note in [&recipes, &shopping, &later] {
+            notes.add(note.clone());
+        }
+        assert_eq!(title(&recipes), "Recipes");
+
+        refresh(&mut notes);
+        let links = |notes: &Notes, id: &Uuid| notes.get_note(id).unwrap().links.clone();
+        assert_eq!(links(&notes, &shopping.id), [recipes.id, later.id].into());
+        assert_eq!(
+            links(&notes, &later.id),
+            [shopping.id].into(),
+            "not to itself"
+        );
+        assert!(stale(&notes).is_empty());
+
+        let index = backlinks(&notes);
+        assert_eq!(index[&recipes.id], [shopping.id].into());
+        assert_eq!(index[&shopping.id], [later.id].into());
+        assert!(!index.contains_key(&Uuid::nil()));
+
+        // Renaming the note linked to by title breaks the link
+        notes.get_note_mut(&recipes.id).unwrap().content = String::from("Cooking");
+        refresh(&mut notes);
+        assert_eq!(links(&notes, &shopping.id), [later.id].into());
+    }
+}
@@ -0,0 +1,144 @@
+//! Advisory locking that keeps concurrent writers from losing changes.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{self, RegiaError};
+use crate::theme::Themed;
+
+/// How long to wait for a busy database without `--wait` or `--no-wait`.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Wait {
+    /// Block until the lock is free
+    Forever,
+    /// Fail at once if another command holds the lock
+    Never,
+    /// Give up after this long
+    Timeout(Duration),
+}
+
+impl Wait {
+    pub fn from_flags(wait: bool, no_wait: bool) -> Self {
+        if wait {
+            Wait::Forever
+        } else if no_wait {
+            Wait::Never
+        } else {
+            Wait::Timeout(DEFAULT_TIMEOUT)
+        }
+    }
+}
+
+/// The lock lives next to the database, as `<db>.lock`.
+pub fn lock_path(db_path: &Path) -> PathBuf {
+    crate::db::sibling_path(db_path, ".lock")
+}
+
+/// An exclusive advisory lock on the database, held for one whole command
+/// so its read-modify-write can't interleave with another's. The lock file
+/// names the holding process while held and is emptied on release.
+pub struct DbLock {
+    file: File,
+}
+
+impl Drop for DbLock {
+    fn drop(&mut self) {
+        // The OS releases the lock itself when the file closes
+        let _ = self.file.set_len(0);
+    }
+}
+
+fn holder(file: &mut File) -> String {
+    let mut contents = String::new();
+    let _ = file.seek(SeekFrom::Start(0));
+    let _ = file.read_to_string(&mut contents);
+    contents.trim().to_string()
+}
+
+/// Takes the lock on the database at `db_path`, waiting as `wait` allows.
+pub fn acquire(db_path: &Path, command: &str, wait: Wait) -> error::Result<DbLock> {
+    let path = lock_path(db_path);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)?;
+
+    let started = Instant::now();
+    loop {
+        match file.try_lock() {
+            Ok(()) => break,
+            Err(TryLockError::WouldBlock) => {}
+            Err(TryLockError::Error(err)) => return Err(err.into()),
+        }
+        let give_up = match wait {
+            Wait::Forever => false,
+            Wait::Never => true,
+            Wait::Timeout(timeout) => started.elapsed() >= timeout,
+        };
+        if give_up {
+            return Err(RegiaError::Conflict(format!(
+                "Database is locked by another regia command ({}); retry, or pass --wait",
+                holder(&mut file)
+            )));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    // A holder that exits normally empties the file, so anything left here
+    // is from a command that crashed while holding the lock. The OS already
+    // dropped that lock; all that is left to recover is the stale record.
+    let stale = holder(&mut file);
+    if !stale.is_empty() {
+        eprintln!(
+            "{} Recovered a stale lock left by {}",
+            "warning:".warning(),
+            stale
+        );
+    }
+
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    write!(file, "pid {}: {}", std::process::id(), command)?;
+    file.flush()?;
+    Ok(DbLock { file })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exclusive_and_recovers_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("regia.db");
+
+        let held = acquire(&db_path, "task add", Wait::Never).unwrap();
+        let err = acquire(&db_path, "note add", Wait::Never).err().unwrap();
+        assert!(err.to_string().contains("task add"));
+        let err = acquire(&db_path, "note add", Wait::Timeout(POLL_INTERVAL))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("--wait"));
+
+        drop(held);
+        assert!(std::fs::read_to_string(lock_path(&db_path))
+            .unwrap()
+            .is_empty());
+
+        // A record left behind by a crashed holder doesn't block anyone
+        std::fs::write(lock_path(&db_path), "pid 1: task rm").unwrap();
+        let taken = acquire(&db_path, "task ls", Wait::Never).unwrap();
+        assert!(std::fs::read_to_string(lock_path(&db_path))
+            .unwrap()
+            .ends_with("task ls"));
+        drop(taken);
+    }
+}
@@ -0,0 +1,140 @@
+//! Terminal rendering for the bits of markdown notes are written in.
+
+use colored::*;
+
+use crate::theme::Themed;
+
+/// Renders `text` for the terminal: headings, emphasis, inline code, bullet
+/// and numbered lists, block quotes and fenced code blocks. Anything else
+/// passes through as written.
+pub fn render(text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut in_code = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            lines.push(format!("  {}", line.highlight()));
+            continue;
+        }
+        lines.push(render_line(line, trimmed));
+    }
+    lines
+}
+
+fn render_line(line: &str, trimmed: &str) -> String {
+    let indent = &line[..line.len() - trimmed.len()];
+
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+        let heading = trimmed[level..].trim();
+        return if level == 1 {
+            heading.bold().underline().to_string()
+        } else {
+            heading.bold().to_string()
+        };
+    }
+
+    for bullet in &["- ", "* ", "+ "] {
+        if let Some(item) = trimmed.strip_prefix(bullet) {
+            return format!("{}{} {}", indent, "•".accent(), inline(item));
+        }
+    }
+
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        if let Some(item) = trimmed[digits..].strip_prefix(". ") {
+            let number = format!("{}.", &trimmed[..digits]);
+            return format!("{}{} {}", indent, number.accent(), inline(item));
+        }
+    }
+
+    if let Some(quote) = trimmed.strip_prefix('>') {
+        return format!(
+            "{}{} {}",
+            indent,
+            "│".dimmed(),
+            inline(quote.trim_start()).dimmed()
+        );
+    }
+
+    format!("{}{}", indent, inline(trimmed))
+}
+
+/// The text between `open` at the start of `text` and the next `close`,
+/// with what follows it.
+fn delimited<'a>(text: &'a str, open: &str, close: &str) -> Option<(&'a str, &'a str)> {
+    let after = text.strip_prefix(open)?;
+    let end = after.find(close)?;
+    if end == 0 || after.starts_with(' ') {
+        return None;
+    }
+    Some((&after[..end], &after[end + close.len()..]))
+}
+
+/// Renders `**bold**`, `__bold__`, `*italic*`, `_italic_` and `` `code` ``.
+/// Underscores inside words, as in `snake_case`, are left alone.
+fn inline(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    let mut after_word = false;
+
+    while let Some(next) = rest.chars().next() {
+        let span = if let Some((code, tail)) = delimited(rest, "`", "`") {
+            Some((code.highlight(), tail))
+        } else if let Some((bold, tail)) =
+            delimited(rest, "**", "**").or_else(|| delimited(rest, "__", "__"))
+        {
+            Some((bold.bold(), tail))
+        } else if let Some((italic, tail)) = delimited(rest, "*", "*") {
+            Some((italic.italic(), tail))
+        } else if let Some((italic, tail)) = delimited(rest, "_", "_").filter(|_| !after_word) {
+            Some((italic.italic(), tail))
+        } else {
+            None
+        };
+
+        match span {
+            Some((styled, tail)) => {
+                out.push_str(&styled.to_string());
+                rest = tail;
+                after_word = false;
+            }
+            None => {
+                out.push(next);
+                rest = &rest[next.len_utf8()..];
+                after_word = next.is_alphanumeric();
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_blocks_and_spans() {
+        let text = "# Plan\n## Steps\n- buy **milk**\n2. call `bob`\n> quoted\n```\nlet x = 1;\n```\nsnake_case and _this_";
+        let lines = render(text);
+        assert_eq!(lines[0], "Plan".bold().underline().to_string());
+        assert_eq!(lines[1], "Steps".bold().to_string());
+        assert_eq!(lines[2], format!("{} buy {}", "•".cyan(), "milk".bold()));
+        assert_eq!(lines[3], format!("{} call {}", "2.".cyan(), "bob".yellow()));
+        assert!(lines[4].contains("quoted"));
+        assert_eq!(lines[5], format!("  {}", "let x = 1;".yellow()));
+        assert_eq!(lines[6], format!("snake_case and {}", "this".italic()));
+        assert_eq!(lines.len(), 7);
+    }
+
+    #[test]
+    fn leaves_plain_text_alone() {
+        assert_eq!(render("2 * 3 = 6, #5 ok"), ["2 * 3 = 6, #5 ok"]);
+        assert_eq!(render("**unclosed"), ["**unclosed"]);
+    }
+}
@@ -0,0 +1,315 @@
+//! Notes and the per-project note list.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::attach::Attachment;
+use crate::index::{Indexed, ItemMut, List};
+use crate::markdown;
+use crate::tags;
+
+/// A free-form note. Notes compare and sort by `id` alone.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Note {
+    pub id: Uuid,
+    pub created: DateTime<Utc>,
+    pub content: String,
+    #[serde(default)]
+    pub tags: HashSet<String>,
+    #[serde(default)]
+    pub modified: Option<DateTime<Utc>>,
+    /// The task this note is attached to
+    #[serde(default)]
+    pub task_id: Option<Uuid>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// What the note said before each change, oldest first and at most
+    /// `notes.revisions` of them
+    #[serde(default)]
+    pub revisions: Vec<Revision>,
+    /// The notes its `[[links]]` name, as of the last save, see [`links`]
+    ///
+    /// [`links`]: crate::links
+    #[serde(default)]
+    pub links: BTreeSet<Uuid>,
+    /// Listed ahead of the rest, for notes looked up all the time
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+/// An earlier content of a note.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Revision {
+    /// Counting from 1 for the note as first written, so numbers stay put
+    /// as the oldest revisions are dropped
+    pub number: usize,
+    pub content: String,
+    /// When the note came to say this
+    pub at: DateTime<Utc>,
+}
+
+impl Indexed for Note {
+    const KIND: &'static str = "note";
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn tags(&self) -> &HashSet<String> {
+        &self.tags
+    }
+
+    fn text(&self) -> &str {
+        &self.content
+    }
+}
+
+impl PartialOrd for Note {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.id.cmp(&other.id))
+    }
+}
+
+impl Note {
+    pub fn new(content: &str) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            created: Utc::now(),
+            content: content.to_string(),
+            tags: HashSet::new(),
+            modified: None,
+            task_id: None,
+            attachments: Vec::new(),
+            revisions: Vec::new(),
+            links: BTreeSet::new(),
+            pinned: false,
+        }
+    }
+
+    /// How `note ls` orders notes: pinned ones first, then newest first.
+    pub fn listing_order(&self, other: &Self) -> Ordering {
+        other
+            .pinned
+            .cmp(&self.pinned)
+            .then(other.created.cmp(&self.created))
+            .then(self.id.cmp(&other.id))
+    }
+
+    /// Changes the content, keeping what it said before as a revision and
+    /// at most `keep` revisions in all.
+    pub fn set_content(&mut self, content: &str, keep: usize) {
+        let previous = std::mem::replace(&mut self.content, content.to_string());
+        self.revisions.push(Revision {
+            number: self.revision(),
+            content: previous,
+            at: self.modified.unwrap_or(self.created),
+        });
+        let drop = self.revisions.len().saturating_sub(keep);
+        self.revisions.drain(..drop);
+        self.modified = Some(Utc::now());
+    }
+
+    /// The number of the current content, one past the last revision.
+    pub fn revision(&self) -> usize {
+        self.revisions.last().map_or(1, |last| last.number + 1)
+    }
+
+    /// What revision `number` said, the current content included, or
+    /// `None` if it was never kept or is no longer.
+    pub fn revision_content(&self, number: usize) -> Option<&str> {
+        if number == self.revision() {
+            return Some(&self.content);
+        }
+        self.revisions
+            .iter()
+            .find(|revision| revision.number == number)
+            .map(|revision| revision.content.as_str())
+    }
+
+    /// Formats the note with its content rendered as markdown, continuation
+    /// lines indented under the bullet.
+    pub fn fmt(&self) -> String {
+        let mut text = markdown::render(&self.content)
+            .iter()
+            .enumerate()
+            .map(|(index, line)| {
+                let bullet = if index == 0 { self.bullet() } else { " " };
+                format!("{} {}", bullet, line)
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        if text.is_empty() {
+            text.push_str(self.bullet());
+        }
+        self.with_tags(text)
+    }
+
+    /// Formats the note with its content exactly as written.
+    pub fn fmt_raw(&self) -> String {
+        self.with_tags(format!("{} {}", self.bullet(), self.content))
+    }
+
+    /// What a listed note starts with, with a pin for a pinned one.
+    fn bullet(&self) -> &'static str {
+        match self.pinned {
+            true => "* 📌",
+            false => "*",
+        }
+    }
+
+    /// `line` followed by the tags the content doesn't already show as
+    /// `#hashtags`.
+    fn with_tags(&self, line: String) -> String {
+        let written = tags::extract(&self.content);
+        let extra: HashSet<String> = self.tags.difference(&written).cloned().collect();
+        if extra.is_empty() {
+            line
+        } else {
+            format!("{} {}", line, tags::fmt_tags(&extra))
+        }
+    }
+}
+
+/// The first `limit` characters of `text`, with an ellipsis in place of
+/// the rest, or `None` when it is no longer than that.
+pub fn preview(text: &str, limit: usize) -> Option<String> {
+    let (cut, _) = text.char_indices().nth(limit)?;
+    let mut shortened = text[..cut].trim_end().to_string();
+    shortened.push('…');
+    Some(shortened)
+}
+
+/// The notes of one project, kept sorted by id for lookup.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Notes {
+    id: Uuid,
+    group_name: String,
+    notes: List<Note>,
+}
+
+impl Default for Notes {
+    fn default() -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            group_name: "root".to_string(),
+            notes: List::default(),
+        }
+    }
+}
+
+impl Notes {
+    pub fn named(group_name: &str) -> Self {
+        Self {
+            group_name: group_name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn get_notes(&self) -> &Vec<Note> {
+        self.notes.items()
+    }
+
+    pub fn get_note(&self, id: &Uuid) -> Option<&Note> {
+        self.notes.get(id)
+    }
+
+    pub fn get_note_mut(&mut self, id: &Uuid) -> Option<ItemMut<'_, Note>> {
+        self.notes.get_mut(id)
+    }
+
+    /// Inserts `note` in id order, replacing any note with the same id.
+    pub fn add(&mut self, note: Note) {
+        self.notes.insert(note);
+    }
+
+    /// Notes carrying every tag in `wanted`, looked up in the tag index.
+    pub fn tagged(&self, wanted: &HashSet<String>) -> Vec<&Note> {
+        self.notes.tagged(wanted)
+    }
+
+    /// Notes with a word starting with each word of `query`, looked up in
+    /// the word index.
+    pub fn with_words(&self, query: &str) -> Vec<&Note> {
+        self.notes.with_words(query)
+    }
+
+    /// Notes attached to the task `task_id`, oldest first.
+    pub fn for_task(&self, task_id: &Uuid) -> Vec<&Note> {
+        let mut attached: Vec<&Note> = self
+            .get_notes()
+            .iter()
+            .filter(|note| note.task_id.as_ref() == Some(task_id))
+            .collect();
+        attached.sort_by_key(|note| note.created);
+        attached
+    }
+
+    pub fn remove(&mut self, note_id: Uuid) {
+        self.notes.remove(&note_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn previews_cut_long_notes() {
+        assert_eq!(preview("short", 5), None);
+        assert_eq!(preview("a longer note", 9).as_deref(), Some("a longer…"));
+        assert_eq!(preview("naïve café", 4).as_deref(), Some("naïv…"));
+    }
+
+    #[test]
+    fn edits_keep_bounded_revisions() {
+        let mut note = Note::new("first");
+        assert_eq!(note.revision(), 1);
+        note.set_content("second", 2);
+        assert_eq!(note.revisions[0].at, note.created);
+        note.set_content("third", 2);
+        note.set_content("fourth", 2);
+        assert_eq!(note.revision(), 4);
+        let kept: Vec<usize> = note
+            .revisions
+            .iter()
+            .map(|revision| revision.number)
+            .collect();
+        assert_eq!(kept, [2, 3]);
+        assert_eq!(note.revision_content(2), Some("second"));
+        assert_eq!(note.revision_content(4), Some("fourth"));
+        assert_eq!(note.revision_content(1), None);
+    }
+
+    #[test]
+    fn shows_tags_once() {
+        crate::theme::disable();
+        let mut note = Note::new("#idea foo");
+        note.tags = tags::extract(&note.content);
+        assert_eq!(note.fmt_raw(), "* #idea foo");
+        note.tags.insert(String::from("work"));
+        assert_eq!(note.fmt_raw(), "* #idea foo #work");
+    }
+
+    #[test]
+    fn pinned_notes_list_first() {
+        let old = Note::new("old reference");
+        let pinned = Note {
+            created: old.created - chrono::Duration::days(1),
+            pinned: true,
+            ..Note::new("older still, but pinned")
+        };
+        let new = Note {
+            created: old.created + chrono::Duration::days(1),
+            ..Note::new("new")
+        };
+        let mut listed = [&old, &new, &pinned];
+        listed.sort_by(|left, right| left.listing_order(right));
+        let order: Vec<&str> = listed.iter().map(|note| note.content.as_str()).collect();
+        assert_eq!(order, ["older still, but pinned", "new", "old reference"]);
+        assert_eq!(pinned.fmt_raw(), "* 📌 older still, but pinned");
+    }
+}
@@ -0,0 +1,143 @@
+//! Task priorities: named levels, or any number on the same scale.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// How important a task is. The named levels stand for fixed weights on
+/// the numeric scale tasks have always used, which is also how priorities
+/// are stored, so a task given `--priority 6` is simply `High`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(from = "u32", into = "u32")]
+pub enum Priority {
+    #[default]
+    None,
+    Low,
+    Medium,
+    High,
+    Critical,
+    /// A number between or above the named levels
+    Other(u32),
+}
+
+impl Priority {
+    /// The named levels, least important first.
+    pub const LEVELS: [Priority; 4] = [
+        Priority::Low,
+        Priority::Medium,
+        Priority::High,
+        Priority::Critical,
+    ];
+
+    /// Where the priority sorts, higher first.
+    pub fn weight(self) -> u32 {
+        match self {
+            Priority::None => 0,
+            Priority::Low => 1,
+            Priority::Medium => 3,
+            Priority::High => 6,
+            Priority::Critical => 9,
+            Priority::Other(weight) => weight,
+        }
+    }
+
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            Priority::None => Some("none"),
+            Priority::Low => Some("low"),
+            Priority::Medium => Some("medium"),
+            Priority::High => Some("high"),
+            Priority::Critical => Some("critical"),
+            Priority::Other(_) => None,
+        }
+    }
+
+    /// Parses a level name or a number.
+    pub fn parse(text: &str) -> Result<Priority, String> {
+        let text = text.trim();
+        let lower = text.to_lowercase();
+        if let Some(level) = Priority::LEVELS
+            .iter()
+            .chain(&[Priority::None])
+            .find(|level| level.name() == Some(lower.as_str()))
+        {
+            return Ok(*level);
+        }
+        match text.parse::<u32>() {
+            Ok(weight) => Ok(Priority::from(weight)),
+            Err(_) => Err(format!(
+                "bad priority: {}, give low, medium, high, critical or a number",
+                text
+            )),
+        }
+    }
+}
+
+impl From<u32> for Priority {
+    fn from(weight: u32) -> Self {
+        [Priority::None]
+            .iter()
+            .chain(&Priority::LEVELS)
+            .find(|level| level.weight() == weight)
+            .copied()
+            .unwrap_or(Priority::Other(weight))
+    }
+}
+
+impl From<Priority> for u32 {
+    fn from(priority: Priority) -> Self {
+        priority.weight()
+    }
+}
+
+impl Ord for Priority {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.weight().cmp(&other.weight())
+    }
+}
+
+impl PartialOrd for Priority {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "{}", self.weight()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_numbers() {
+        assert_eq!(Priority::parse("High"), Ok(Priority::High));
+        assert_eq!(Priority::parse("6"), Ok(Priority::High));
+        assert_eq!(Priority::parse("4"), Ok(Priority::Other(4)));
+        assert!(Priority::parse("urgent")
+            .unwrap_err()
+            .contains("bad priority"));
+        assert!(Priority::parse("-1").is_err());
+
+        assert!(Priority::Medium < Priority::Other(4));
+        assert!(Priority::Other(4) < Priority::High);
+        assert!(Priority::Other(12) > Priority::Critical);
+        assert_eq!(Priority::Other(4).to_string(), "4");
+        assert_eq!(Priority::Critical.to_string(), "critical");
+    }
+
+    #[test]
+    fn stored_as_numbers() {
+        let bytes = rmp_serde::to_vec(&Priority::High).unwrap();
+        assert_eq!(bytes, rmp_serde::to_vec(&6u32).unwrap());
+        let back: Priority = rmp_serde::from_slice(&rmp_serde::to_vec(&4u32).unwrap()).unwrap();
+        assert_eq!(back, Priority::Other(4));
+    }
+}
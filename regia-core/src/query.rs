@@ -0,0 +1,627 @@
+//! A small language for picking tasks, for what the filter flags of
+//! `task ls` can't say:
+//!
+//! ```text
+//! priority > 2 and (tag = "work" or overdue) sort due limit 10
+//! ```
+//!
+//! Tests compare a field with a value, `FIELD OP VALUE`, or name a flag.
+//! They combine with `and`, `or`, `not` and parentheses, `and` binding
+//! tighter than `or`, and may be followed by `sort` with one of the
+//! `--sort` orders and `limit` with a count. Values with spaces in them
+//! are quoted.
+//!
+//! - `priority` takes a level name or number and `estimate` a span such as
+//!   `2h`, both compared with `= != < <= > >=`; the priority compared is
+//!   the one in effect once the `urgency` rules raise it, as `--sort` and
+//!   `--priority-min` go by
+//! - `due`, `created`, `modified`, `completed` and `scheduled` take dates
+//!   as `--due` reads them, `=` meaning the same day
+//! - `content`, `status` and `waiting`, who a task waits on, compare with
+//!   `=` and `!=`, ignoring case, and `~`, containing
+//! - `tag` and `context` say whether a task has one, `~` any containing
+//! - the flags are `open`, `done`, `overdue`, `blocked`, `snoozed`,
+//!   `waiting`, `repeating` and `scheduled`, starting later
+//!
+//! A task without the field at all, an undated one for `due` say, fails
+//! every comparison on it, `!=` included.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::contexts;
+use crate::dates::{self, Local};
+use crate::error::{self, RegiaError};
+use crate::priority::Priority;
+use crate::tags;
+use crate::todo::{SortBy, Task, Tasks};
+use crate::urgency::Urgency;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// Contains, for text
+    Has,
+}
+
+impl Op {
+    fn parse(symbol: &str) -> Option<Op> {
+        match symbol {
+            "=" | "==" => Some(Op::Eq),
+            "!=" => Some(Op::Ne),
+            "<" => Some(Op::Lt),
+            "<=" => Some(Op::Le),
+            ">" => Some(Op::Gt),
+            ">=" => Some(Op::Ge),
+            "~" => Some(Op::Has),
+            _ => None,
+        }
+    }
+
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+            Op::Has => false,
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Has => "~",
+        };
+        f.write_str(symbol)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateField {
+    Due,
+    Created,
+    Modified,
+    Completed,
+    Scheduled,
+}
+
+impl DateField {
+    fn of(self, task: &Task) -> Option<DateTime<Utc>> {
+        match self {
+            DateField::Due => task.due,
+            DateField::Created => Some(task.created),
+            DateField::Modified => Some(task.touched()),
+            DateField::Completed => task.completed,
+            DateField::Scheduled => task.scheduled,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    Content,
+    Status,
+    Waiting,
+}
+
+impl TextField {
+    fn of(self, task: &Task) -> Option<&str> {
+        match self {
+            TextField::Content => Some(&task.content),
+            TextField::Status => task.status.as_deref(),
+            TextField::Waiting => task.waiting_on.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flag {
+    Open,
+    Done,
+    Overdue,
+    Blocked,
+    Snoozed,
+    Waiting,
+    Repeating,
+    Scheduled,
+}
+
+impl Flag {
+    fn parse(word: &str) -> Option<Flag> {
+        match word {
+            "open" => Some(Flag::Open),
+            "done" => Some(Flag::Done),
+            "overdue" => Some(Flag::Overdue),
+            "blocked" => Some(Flag::Blocked),
+            "snoozed" => Some(Flag::Snoozed),
+            "waiting" => Some(Flag::Waiting),
+            "repeating" => Some(Flag::Repeating),
+            "scheduled" => Some(Flag::Scheduled),
+            _ => None,
+        }
+    }
+}
+
+/// One test of a filter, its value read when the filter was parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Test {
+    Priority(Op, Priority),
+    /// In seconds, as estimates are kept
+    Estimate(Op, i64),
+    Date(DateField, Op, DateTime<Utc>),
+    /// Lowercased
+    Text(TextField, Op, String),
+    Tag(Op, String),
+    Context(Op, String),
+    Flag(Flag),
+}
+
+/// Text matching ignoring case: `wanted` is lowercased already.
+fn text_holds(op: Op, text: &str, wanted: &str) -> bool {
+    let text = text.to_lowercase();
+    match op {
+        Op::Eq => text == wanted,
+        Op::Ne => text != wanted,
+        Op::Has => text.contains(wanted),
+        _ => false,
+    }
+}
+
+/// Whether a set of tags or contexts has `wanted`, or for `~` one
+/// containing it.
+fn set_holds<'a>(op: Op, mut items: impl Iterator<Item = &'a String>, wanted: &str) -> bool {
+    match op {
+        Op::Eq => items.any(|item| item == wanted),
+        Op::Ne => !items.any(|item| item == wanted),
+        Op::Has => items.any(|item| item.contains(wanted)),
+        _ => false,
+    }
+}
+
+impl Test {
+    fn matches(
+        &self,
+        task: &Task,
+        tasks: &Tasks,
+        urgency: Option<&Urgency>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        match self {
+            Test::Priority(op, priority) => {
+                let effective = match urgency {
+                    Some(urgency) => urgency.priority(task, now),
+                    None => task.priority,
+                };
+                op.holds(effective.cmp(priority))
+            }
+            Test::Estimate(op, secs) => task
+                .estimate
+                .is_some_and(|estimate| op.holds(estimate.cmp(secs))),
+            Test::Date(field, op, date) => field.of(task).is_some_and(|at| match op {
+                Op::Eq | Op::Ne => {
+                    let day = |at: DateTime<Utc>| at.with_timezone(&Local).date_naive();
+                    op.holds(day(at).cmp(&day(*date)))
+                }
+                _ => op.holds(at.cmp(date)),
+            }),
+            Test::Text(field, op, wanted) => field
+                .of(task)
+                .is_some_and(|text| text_holds(*op, text, wanted)),
+            Test::Tag(op, tag) => set_holds(*op, task.tags.iter(), tag),
+            Test::Context(op, context) => set_holds(*op, task.contexts.iter(), context),
+            Test::Flag(flag) => match flag {
+                Flag::Open => !task.is_done(),
+                Flag::Done => task.is_done(),
+                Flag::Overdue => !task.is_done() && task.due.is_some_and(|due| due < now),
+                Flag::Blocked => !task.is_done() && !tasks.open_dependencies(task).is_empty(),
+                Flag::Snoozed => task.is_snoozed(now),
+                Flag::Waiting => task.is_waiting(),
+                Flag::Repeating => task.repeat.is_some(),
+                Flag::Scheduled => task.is_scheduled_later(now),
+            },
+        }
+    }
+}
+
+/// Which tasks a query picks.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Test(Test),
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+impl Filter {
+    /// Whether `task`, one of `tasks`, is picked as of `now`, comparing
+    /// priorities as `urgency` raises them when there are rules.
+    pub fn matches(
+        &self,
+        task: &Task,
+        tasks: &Tasks,
+        urgency: Option<&Urgency>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let matches = |filter: &Filter| filter.matches(task, tasks, urgency, now);
+        match self {
+            Filter::Test(test) => test.matches(task, tasks, urgency, now),
+            Filter::Not(filter) => !matches(filter),
+            Filter::And(left, right) => matches(left) && matches(right),
+            Filter::Or(left, right) => matches(left) || matches(right),
+        }
+    }
+
+    /// Whether any test of the filter is `test`, so that the filter says
+    /// itself whether to list tasks left out by default, done or snoozed
+    /// ones say.
+    pub fn mentions(&self, test: impl Fn(&Test) -> bool + Copy) -> bool {
+        match self {
+            Filter::Test(own) => test(own),
+            Filter::Not(filter) => filter.mentions(test),
+            Filter::And(left, right) | Filter::Or(left, right) => {
+                left.mentions(test) || right.mentions(test)
+            }
+        }
+    }
+}
+
+/// A parsed query: the filter, if any, and how to order and cut what it
+/// picks.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Script {
+    pub filter: Option<Filter>,
+    pub sort: Option<SortBy>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(Op),
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => f.write_str(word),
+            Token::Quoted(text) => write!(f, "\"{}\"", text),
+            Token::Op(op) => write!(f, "{}", op),
+            Token::Open => f.write_str("("),
+            Token::Close => f.write_str(")"),
+        }
+    }
+}
+
+fn bad(message: impl fmt::Display) -> RegiaError {
+    RegiaError::Parse(format!("bad query, {}", message))
+}
+
+fn tokens(input: &str) -> error::Result<Vec<Token>> {
+    let is_op = |c: char| "=!<>~".contains(c);
+    let mut found = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        match c {
+            '(' => found.push(Token::Open),
+            ')' => found.push(Token::Close),
+            '"' => {
+                let mut text = String::new();
+                let mut closed = false;
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '"' => {
+                            closed = true;
+                            break;
+                        }
+                        '\\' => text.extend(chars.next().map(|(_, c)| c)),
+                        c => text.push(c),
+                    }
+                }
+                if !closed {
+                    return Err(bad("unclosed quote"));
+                }
+                found.push(Token::Quoted(text));
+            }
+            c if is_op(c) => {
+                let mut end = at + c.len_utf8();
+                while let Some((next, c)) = chars.peek().copied().filter(|(_, c)| is_op(*c)) {
+                    end = next + c.len_utf8();
+                    chars.next();
+                }
+                let symbol = &input[at..end];
+                let op = Op::parse(symbol)
+                    .ok_or_else(|| bad(format!("unknown operator: {}", symbol)))?;
+                found.push(Token::Op(op));
+            }
+            _ => {
+                let mut end = at + c.len_utf8();
+                while let Some((next, c)) = chars
+                    .peek()
+                    .copied()
+                    .filter(|(_, c)| !c.is_whitespace() && !is_op(*c) && !"()\"".contains(*c))
+                {
+                    end = next + c.len_utf8();
+                    chars.next();
+                }
+                found.push(Token::Word(input[at..end].to_string()));
+            }
+        }
+    }
+    Ok(found)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    at: usize,
+    now: DateTime<Utc>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.at)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.at).cloned();
+        self.at += 1;
+        token
+    }
+
+    /// Whether the next token is the word `keyword`, taking it if so.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.at += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn at_end_of_filter(&self) -> bool {
+        match self.peek() {
+            None | Some(Token::Close) => true,
+            Some(Token::Word(word)) => {
+                word.eq_ignore_ascii_case("sort") || word.eq_ignore_ascii_case("limit")
+            }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> error::Result<Filter> {
+        let mut filter = self.and()?;
+        while self.keyword("or") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> error::Result<Filter> {
+        let mut filter = self.unary()?;
+        while self.keyword("and") {
+            filter = Filter::And(Box::new(filter), Box::new(self.unary()?));
+        }
+        Ok(filter)
+    }
+
+    fn unary(&mut self) -> error::Result<Filter> {
+        if self.keyword("not") {
+            return Ok(Filter::Not(Box::new(self.unary()?)));
+        }
+        match self.next() {
+            Some(Token::Open) => {
+                let filter = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(filter),
+                    Some(token) => Err(bad(format!("expected ) before {}", token))),
+                    None => Err(bad("missing )")),
+                }
+            }
+            Some(Token::Word(word)) => self.test(&word.to_lowercase()),
+            Some(token) => Err(bad(format!("expected a field or flag before {}", token))),
+            None => Err(bad("expected a field or flag at the end")),
+        }
+    }
+
+    fn test(&mut self, name: &str) -> error::Result<Filter> {
+        let op = match self.peek() {
+            Some(Token::Op(op)) => *op,
+            _ => {
+                return match Flag::parse(name) {
+                    Some(flag) => Ok(Filter::Test(Test::Flag(flag))),
+                    None => Err(bad(format!("unknown flag: {}", name))),
+                }
+            }
+        };
+        self.at += 1;
+        let value = self.value(&format!("{} {}", name, op))?;
+        let ordered = || match op {
+            Op::Has => Err(bad(format!("{} can't be compared with ~", name))),
+            op => Ok(op),
+        };
+        let unordered = || match op {
+            Op::Eq | Op::Ne | Op::Has => Ok(op),
+            op => Err(bad(format!("{} can't be compared with {}", name, op))),
+        };
+        let date = |field| -> error::Result<Test> {
+            let date = dates::parse_relative(&value, self.now, &Local)
+                .ok_or_else(|| bad(format!("bad date: {}", value)))?;
+            Ok(Test::Date(field, ordered()?, date))
+        };
+        let text = |field| -> error::Result<Test> {
+            Ok(Test::Text(field, unordered()?, value.to_lowercase()))
+        };
+        let test = match name {
+            "priority" => Test::Priority(ordered()?, Priority::parse(&value).map_err(bad)?),
+            "estimate" => match dates::parse_duration(&value) {
+                Some(span) if span > Duration::zero() => {
+                    Test::Estimate(ordered()?, span.num_seconds())
+                }
+                _ => return Err(bad(format!("bad estimate: {}", value))),
+            },
+            "due" => date(DateField::Due)?,
+            "created" => date(DateField::Created)?,
+            "modified" => date(DateField::Modified)?,
+            "completed" => date(DateField::Completed)?,
+            "scheduled" => date(DateField::Scheduled)?,
+            "content" => text(TextField::Content)?,
+            "status" => text(TextField::Status)?,
+            "waiting" => text(TextField::Waiting)?,
+            "tag" => Test::Tag(unordered()?, tags::normalize(&value)),
+            "context" => Test::Context(unordered()?, contexts::normalize(&value)),
+            _ => return Err(bad(format!("unknown field: {}", name))),
+        };
+        Ok(Filter::Test(test))
+    }
+
+    fn value(&mut self, after: &str) -> error::Result<String> {
+        match self.next() {
+            Some(Token::Word(value)) | Some(Token::Quoted(value)) => Ok(value),
+            _ => Err(bad(format!("expected a value after {}", after))),
+        }
+    }
+}
+
+/// Parses `input`, reading relative dates in it as of `now`.
+pub fn parse(input: &str, now: DateTime<Utc>) -> error::Result<Script> {
+    let mut parser = Parser {
+        tokens: tokens(input)?,
+        at: 0,
+        now,
+    };
+    let mut script = Script::default();
+    if !parser.at_end_of_filter() {
+        script.filter = Some(parser.or()?);
+    }
+    loop {
+        if parser.keyword("sort") {
+            let order = parser.value("sort")?;
+            let sort =
+                SortBy::parse(&order).ok_or_else(|| bad(format!("bad sort order: {}", order)))?;
+            script.sort = Some(sort);
+        } else if parser.keyword("limit") {
+            let count = parser.value("limit")?;
+            let limit = count
+                .parse()
+                .map_err(|_| bad(format!("bad limit: {}", count)))?;
+            script.limit = Some(limit);
+        } else {
+            break;
+        }
+    }
+    match parser.next() {
+        None => Ok(script),
+        Some(token) => Err(bad(format!("unexpected {}", token))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parses_and_picks_tasks() {
+        let now = Utc.with_ymd_and_hms(2019, 10, 4, 12, 0, 0).unwrap();
+        let mut tasks = Tasks::default();
+        let mut report = Task::new(String::from("Write the report"), 6);
+        report.tags.insert(String::from("work"));
+        let mut late = Task::new(String::from("pay rent"), 1);
+        late.due = Some(now - Duration::days(1));
+        let mut shopping = Task::new(String::from("groceries"), 3);
+        shopping.contexts.insert(String::from("town"));
+        shopping.complete();
+        let ids = [report.id, late.id, shopping.id];
+        for task in [report, late, shopping] {
+            tasks.add(task);
+        }
+        let picked = |input: &str| -> Vec<bool> {
+            let filter = parse(input, now).unwrap().filter.unwrap();
+            ids.iter()
+                .map(|id| filter.matches(tasks.get_task(id).unwrap(), &tasks, None, now))
+                .collect()
+        };
+
+        assert_eq!(
+            picked("priority > 2 and (tag = \"work\" or overdue)"),
+            [true, false, false]
+        );
+
+        // With urgency rules, the overdue task's priority is the raised one
+        let urgency = Urgency {
+            due: Some(crate::urgency::Rule {
+                curve: crate::urgency::Curve::Linear,
+                window: Duration::days(7),
+                raise: 4,
+            }),
+            age: None,
+        };
+        let filter = parse("priority > 2", now).unwrap().filter.unwrap();
+        let raised: Vec<bool> = ids
+            .iter()
+            .map(|id| filter.matches(tasks.get_task(id).unwrap(), &tasks, Some(&urgency), now))
+            .collect();
+        assert_eq!(raised, [true, true, true]);
+        assert_eq!(
+            picked("priority>2 and tag=work or overdue"),
+            [true, true, false]
+        );
+        assert_eq!(
+            picked("not done and priority <= medium"),
+            [false, true, false]
+        );
+        assert_eq!(picked("content ~ REPORT"), [true, false, false]);
+        assert_eq!(picked("context = @town"), [false, false, true]);
+        // Undated tasks fail any comparison on their due date
+        assert_eq!(picked("due != today"), [false, true, false]);
+        assert_eq!(picked("tag != work"), [false, true, true]);
+
+        let script = parse("open sort due limit 5", now).unwrap();
+        assert_eq!(script.sort, Some(SortBy::Due));
+        assert_eq!(script.limit, Some(5));
+        assert_eq!(parse("sort priority", now).unwrap().filter, None);
+        let done = |test: &Test| *test == Test::Flag(Flag::Done);
+        assert!(parse("open or not done", now)
+            .unwrap()
+            .filter
+            .unwrap()
+            .mentions(done));
+
+        for input in [
+            "priority ~ 3",
+            "tag < work",
+            "colour = red",
+            "(open",
+            "open and",
+            "due > someday",
+            "content = \"unclosed",
+            "open limit many",
+            "open done",
+        ] {
+            assert!(parse(input, now).is_err(), "{}", input);
+        }
+    }
+}
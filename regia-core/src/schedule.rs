@@ -0,0 +1,522 @@
+//! Rolling repeated tasks forward to their next occurrence.
+//!
+//! Repeats keep to the clock of the timezone they are worked out in, so a
+//! task due daily at 9:00 stays at 9:00 when summer time starts or ends.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::cron::Cron;
+use crate::dates;
+use crate::history::{Event, Kind};
+use crate::todo::{RepeatType, Task, TaskType, Tasks};
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .unwrap()
+        .pred_opt()
+        .unwrap()
+        .day()
+}
+
+/// Moves `dt` forward by one month, clamping to the last day of shorter
+/// months so that a task due on the 31st lands on the 30th, 28th, etc.
+fn add_month(naive: NaiveDateTime) -> NaiveDateTime {
+    let (year, month) = if naive.month() == 12 {
+        (naive.year() + 1, 1)
+    } else {
+        (naive.year(), naive.month() + 1)
+    };
+    let day = naive.day().min(days_in_month(year, month));
+    let date = NaiveDate::from_ymd_opt(year, month, day).unwrap();
+    date.and_time(naive.time())
+}
+
+/// The last day of the month after `dt`'s, or of `dt`'s own month when
+/// that is still ahead, at the same time of day.
+fn next_month_end(naive: NaiveDateTime) -> NaiveDateTime {
+    let (mut year, mut month) = (naive.year(), naive.month());
+    if naive.day() == days_in_month(year, month) {
+        if month == 12 {
+            year += 1;
+            month = 1;
+        } else {
+            month += 1;
+        }
+    }
+    let date = NaiveDate::from_ymd_opt(year, month, days_in_month(year, month)).unwrap();
+    date.and_time(naive.time())
+}
+
+/// The last moment of the week after the one `naive` falls in, with weeks
+/// starting on `locale`'s first day.
+fn next_week_end(naive: NaiveDateTime, locale: &dates::Locale) -> NaiveDateTime {
+    let end = locale.week_of(naive.date()) + Duration::days(13);
+    end.and_hms_opt(23, 59, 59).unwrap()
+}
+
+/// The occurrence that follows `due` under `repeat` on the clocks of
+/// `zone`, or `None` for a cron expression that no longer parses or never
+/// fires.
+pub fn step<Tz: TimeZone>(
+    due: DateTime<Utc>,
+    repeat: &RepeatType,
+    zone: &Tz,
+) -> Option<DateTime<Utc>> {
+    let local = due.with_timezone(zone).naive_local();
+    let next = match repeat {
+        RepeatType::Daily => local + Duration::days(1),
+        RepeatType::Weekly => local + Duration::weeks(1),
+        RepeatType::Monthly => add_month(local),
+        RepeatType::EveryDays(days) => local + Duration::days(i64::from((*days).max(1))),
+        RepeatType::Weekdays(days) => (1..=7)
+            .map(|offset| local + Duration::days(offset))
+            .find(|next| days & (1 << next.weekday().num_days_from_monday()) != 0)?,
+        RepeatType::LastDayOfMonth => next_month_end(local),
+        RepeatType::Cron(expr) => return Cron::parse(expr)?.next_after(due, zone),
+    };
+    Some(dates::from_local(zone, &next))
+}
+
+/// When a task without a due date that was done at `done` is next due.
+/// A weekly one done this week is due by the end of next week, however
+/// weeks are counted here.
+fn step_undated<Tz: TimeZone>(
+    done: DateTime<Utc>,
+    repeat: &RepeatType,
+    zone: &Tz,
+) -> Option<DateTime<Utc>> {
+    if *repeat != RepeatType::Weekly {
+        return step(done, repeat, zone);
+    }
+    let local = done.with_timezone(zone).naive_local();
+    Some(dates::from_local(
+        zone,
+        &next_week_end(local, &dates::locale()),
+    ))
+}
+
+/// The first occurrence after `now`, counting from `due` in repeat steps.
+pub fn next_due<Tz: TimeZone>(
+    due: DateTime<Utc>,
+    repeat: &RepeatType,
+    now: DateTime<Utc>,
+    zone: &Tz,
+) -> Option<DateTime<Utc>> {
+    let mut next = step(due, repeat, zone)?;
+    while next <= now {
+        next = step(next, repeat, zone)?;
+    }
+    Some(next)
+}
+
+/// The next `count` occurrences after `due`.
+pub fn upcoming<Tz: TimeZone>(
+    due: DateTime<Utc>,
+    repeat: &RepeatType,
+    count: usize,
+    zone: &Tz,
+) -> Vec<DateTime<Utc>> {
+    let mut occurrences = Vec::new();
+    let mut next = due;
+    while occurrences.len() < count {
+        match step(next, repeat, zone) {
+            Some(after) => {
+                occurrences.push(after);
+                next = after;
+            }
+            None => break,
+        }
+    }
+    occurrences
+}
+
+/// The next `count` instances a task repeating under `repeat` would come
+/// back as once done at `now`: after its `due` date, or counting from `now`
+/// when it has none. Fewer when a cron expression stops firing.
+pub fn preview<Tz: TimeZone>(
+    due: Option<DateTime<Utc>>,
+    repeat: &RepeatType,
+    now: DateTime<Utc>,
+    count: usize,
+    zone: &Tz,
+) -> Vec<DateTime<Utc>> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let first = match due {
+        Some(due) => next_due(due, repeat, now, zone),
+        None => step_undated(now, repeat, zone),
+    };
+    let Some(first) = first else {
+        return Vec::new();
+    };
+    let mut occurrences = vec![first];
+    occurrences.extend(upcoming(first, repeat, count - 1, zone));
+    occurrences
+}
+
+/// Builds the next instance of a repeated task, or `None` when the task
+/// does not repeat or its current instance is still open and not yet due.
+pub fn next_occurrence<Tz: TimeZone>(task: &Task, now: DateTime<Utc>, zone: &Tz) -> Option<Task> {
+    let repeat = task.repeat.as_ref()?;
+    if task.successor.is_some() {
+        return None;
+    }
+
+    let due = match (task.due, task.completed) {
+        (Some(due), _) if task.is_done() || due < now => next_due(due, repeat, now, zone)?,
+        (None, Some(completed)) => step_undated(completed, repeat, zone)?,
+        _ => return None,
+    };
+
+    let mut next = Task::new_date(
+        task.content.clone(),
+        task.priority,
+        Some(due),
+        TaskType::Repeated,
+        Some(repeat.clone()),
+    );
+    next.depends = task.depends.clone();
+    next.tags = task.tags.clone();
+    next.contexts = task.contexts.clone();
+    // Keep the same lead time between starting and being due
+    if let (Some(scheduled), Some(old_due)) = (task.scheduled, task.due) {
+        next.scheduled = Some(due - (old_due - scheduled));
+    }
+    next.parent = task.parent;
+    next.series = Some(task.series_id());
+    next.remind = task.remind;
+    next.reminders = task.reminders.clone();
+    Some(next)
+}
+
+/// Spawns the next occurrence of every repeated task that was completed or
+/// has passed its due date, returning the ids of the new tasks. Callers
+/// other than tests will want [`dates::Local`] for `zone`.
+pub fn tick<Tz: TimeZone>(tasks: &mut Tasks, now: DateTime<Utc>, zone: &Tz) -> Vec<Uuid> {
+    let pending: Vec<(Uuid, Task)> = tasks
+        .get_tasks()
+        .iter()
+        .filter_map(|task| next_occurrence(task, now, zone).map(|next| (task.id, next)))
+        .collect();
+
+    let mut spawned = Vec::new();
+    for (parent, next) in pending {
+        if let Some(mut task) = tasks.get_task_mut(&parent) {
+            task.successor = Some(next.id);
+        }
+        spawned.push(next.id);
+        tasks.add(next);
+    }
+    spawned
+}
+
+/// How many instances of a repeated task in a row were done.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Streak {
+    /// Up to the latest instance that passed or was done
+    pub current: usize,
+    pub longest: usize,
+}
+
+/// The streak of each series in `history` whose repeats have streaks, see
+/// [`RepeatType::has_streaks`]. An instance missed breaks the streak even
+/// if it was done later.
+pub fn streaks(history: &[Event]) -> HashMap<Uuid, Streak> {
+    // Each instance once, when it was first done or missed
+    let mut instances: HashMap<Uuid, (Uuid, DateTime<Utc>, bool)> = HashMap::new();
+    for event in history {
+        let series = match (&event.series, &event.repeat) {
+            (Some(series), Some(repeat)) if repeat.has_streaks() => *series,
+            _ => continue,
+        };
+        let done = event.kind == Kind::Completed;
+        let instance = instances
+            .entry(event.task)
+            .or_insert((series, event.at, done));
+        instance.1 = instance.1.min(event.at);
+        instance.2 &= done;
+    }
+    let mut ordered: Vec<(Uuid, DateTime<Utc>, bool)> = instances.into_values().collect();
+    ordered.sort_by_key(|(_, at, _)| *at);
+
+    let mut streaks: HashMap<Uuid, Streak> = HashMap::new();
+    for (series, _, done) in ordered {
+        let streak = streaks.entry(series).or_default();
+        if done {
+            streak.current += 1;
+            streak.longest = streak.longest.max(streak.current);
+        } else {
+            streak.current = 0;
+        }
+    }
+    streaks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dates::tests::Berlin;
+
+    fn at(y: i32, m: u32, d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, 9, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn undated_weeks_end_on_the_locale() {
+        use chrono::Weekday;
+        // A Saturday
+        let done = at(2019, 10, 5).naive_utc();
+        let monday = dates::Locale::default();
+        let sunday = dates::Locale {
+            week_start: Weekday::Sun,
+            ..dates::Locale::default()
+        };
+        let end = |y, m, d| {
+            NaiveDate::from_ymd_opt(y, m, d)
+                .unwrap()
+                .and_hms_opt(23, 59, 59)
+        };
+        assert_eq!(Some(next_week_end(done, &monday)), end(2019, 10, 13));
+        assert_eq!(Some(next_week_end(done, &sunday)), end(2019, 10, 12));
+    }
+
+    #[test]
+    fn previews_start_after_now() {
+        let weekly = RepeatType::Weekly;
+        let now = at(2019, 10, 10);
+        assert_eq!(
+            preview(Some(at(2019, 9, 26)), &weekly, now, 3, &Utc),
+            vec![at(2019, 10, 17), at(2019, 10, 24), at(2019, 10, 31)]
+        );
+        assert_eq!(
+            preview(None, &RepeatType::Daily, now, 2, &Utc),
+            vec![at(2019, 10, 11), at(2019, 10, 12)]
+        );
+        assert!(preview(Some(now), &weekly, now, 0, &Utc).is_empty());
+    }
+
+    #[test]
+    fn monthly_clamps_to_month_end() {
+        let monthly = RepeatType::Monthly;
+        assert_eq!(step(at(2019, 1, 31), &monthly, &Utc), Some(at(2019, 2, 28)));
+        assert_eq!(step(at(2020, 1, 31), &monthly, &Utc), Some(at(2020, 2, 29)));
+        assert_eq!(
+            step(at(2019, 12, 15), &monthly, &Utc),
+            Some(at(2020, 1, 15))
+        );
+    }
+
+    #[test]
+    fn next_due_skips_past_occurrences() {
+        let due = at(2019, 10, 1);
+        let now = at(2019, 10, 3) + Duration::hours(1);
+        assert_eq!(
+            next_due(due, &RepeatType::Daily, now, &Utc),
+            Some(at(2019, 10, 4))
+        );
+        assert_eq!(
+            next_due(due, &RepeatType::Weekly, now, &Utc),
+            Some(at(2019, 10, 8))
+        );
+    }
+
+    #[test]
+    fn custom_rules() {
+        // 2019-10-04 is a Friday
+        let friday = at(2019, 10, 4);
+        let every = |rule: &str| RepeatType::parse(rule).unwrap();
+        assert_eq!(
+            step(friday, &every("every 3 days"), &Utc),
+            Some(at(2019, 10, 7))
+        );
+        assert_eq!(
+            upcoming(friday, &every("every mon,wed,fri"), 3, &Utc),
+            [at(2019, 10, 7), at(2019, 10, 9), at(2019, 10, 11)]
+        );
+        assert_eq!(
+            upcoming(at(2019, 1, 15), &every("last day of month"), 2, &Utc),
+            [at(2019, 1, 31), at(2019, 2, 28)]
+        );
+        assert_eq!(
+            step(friday, &every("cron 30 9 * * mon"), &Utc),
+            Some(at(2019, 10, 7) + Duration::minutes(30))
+        );
+        assert_eq!(
+            step(friday, &RepeatType::Cron(String::from("bad")), &Utc),
+            None
+        );
+    }
+
+    #[test]
+    fn rules_round_trip() {
+        for rule in [
+            "daily",
+            "every 3 days",
+            "every mon,wed,fri",
+            "last day of month",
+            "cron 0 9 * * 1-5",
+        ] {
+            assert_eq!(RepeatType::parse(rule).unwrap().to_string(), rule);
+        }
+        assert_eq!(
+            RepeatType::parse("every Monday Thursday"),
+            Some(RepeatType::Weekdays(0b1001))
+        );
+        assert_eq!(RepeatType::parse("every 0 days"), None);
+        assert_eq!(RepeatType::parse("every blursday"), None);
+        assert_eq!(RepeatType::parse("0 0 30 2 *"), None);
+    }
+
+    fn berlin(m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        dates::from_local(
+            &Berlin,
+            &NaiveDate::from_ymd_opt(2019, m, d)
+                .unwrap()
+                .and_hms_opt(h, min, 0)
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn repeats_keep_local_time_across_summer_time() {
+        // Summer time starts on 2019-03-31 and ends on 2019-10-27
+        let daily = RepeatType::Daily;
+        assert_eq!(
+            upcoming(berlin(3, 30, 9, 0), &daily, 2, &Berlin),
+            [berlin(3, 31, 9, 0), berlin(4, 1, 9, 0)]
+        );
+        assert_eq!(
+            berlin(3, 31, 9, 0) - berlin(3, 30, 9, 0),
+            Duration::hours(23)
+        );
+        assert_eq!(
+            step(berlin(10, 26, 9, 0), &daily, &Berlin),
+            Some(berlin(10, 27, 9, 0))
+        );
+        assert_eq!(berlin(10, 27, 9, 0), at(2019, 10, 27) - Duration::hours(1));
+        assert_eq!(
+            step(berlin(10, 20, 18, 30), &RepeatType::Weekly, &Berlin),
+            Some(berlin(10, 27, 18, 30))
+        );
+        assert_eq!(
+            step(berlin(3, 15, 8, 0), &RepeatType::Monthly, &Berlin),
+            Some(berlin(4, 15, 8, 0))
+        );
+        assert_eq!(
+            step(berlin(3, 31, 8, 0), &RepeatType::Weekdays(0b1), &Berlin),
+            Some(berlin(4, 1, 8, 0)),
+            "Monday"
+        );
+
+        // 02:30 doesn't happen on 2019-03-31, so that day's is an hour late
+        assert_eq!(
+            step(berlin(3, 30, 2, 30), &daily, &Berlin),
+            Some(at(2019, 3, 31) - Duration::hours(8) + Duration::minutes(30))
+        );
+        // and of the two on 2019-10-27, the first is taken
+        assert_eq!(
+            step(berlin(10, 26, 2, 30), &daily, &Berlin),
+            Some(at(2019, 10, 27) - Duration::hours(8) - Duration::minutes(30))
+        );
+
+        // Spawned tasks land on local nine o'clock too
+        let mut tasks = Tasks::default();
+        let task = Task::new_date(
+            String::from("stand-up"),
+            0,
+            Some(berlin(10, 26, 9, 0)),
+            TaskType::Repeated,
+            Some(RepeatType::Daily),
+        );
+        let id = task.id;
+        tasks.add(task);
+        tasks.get_task_mut(&id).unwrap().complete();
+        let spawned = tick(&mut tasks, berlin(10, 26, 10, 0), &Berlin);
+        assert_eq!(
+            tasks.get_task(&spawned[0]).unwrap().due,
+            Some(berlin(10, 27, 9, 0))
+        );
+    }
+
+    #[test]
+    fn tick_spawns_once() {
+        let mut tasks = Tasks::default();
+        let task = Task::new_date(
+            String::from("water plants"),
+            0,
+            Some(at(2019, 10, 1)),
+            TaskType::Repeated,
+            Some(RepeatType::Daily),
+        );
+        let id = task.id;
+        tasks.add(Task {
+            scheduled: Some(at(2019, 9, 30)),
+            ..task
+        });
+
+        // Not yet due and not done: nothing happens
+        assert!(tick(&mut tasks, at(2019, 9, 30), &Utc).is_empty());
+
+        tasks.get_task_mut(&id).unwrap().complete();
+        let spawned = tick(&mut tasks, at(2019, 10, 1) + Duration::hours(1), &Utc);
+        assert_eq!(spawned.len(), 1);
+        let next = tasks.get_task(&spawned[0]).unwrap();
+        assert_eq!(next.due, Some(at(2019, 10, 2)));
+        assert_eq!(next.scheduled, Some(at(2019, 10, 1)));
+        assert!(!next.is_done());
+
+        // The completed instance already has a successor
+        assert!(tick(&mut tasks, at(2019, 10, 1) + Duration::hours(2), &Utc).is_empty());
+    }
+
+    #[test]
+    fn streaks_run_until_missed() {
+        let mut db = crate::db::Database::default();
+        let first = Task::new_date(
+            String::from("stretch"),
+            0,
+            Some(at(2019, 10, 1)),
+            TaskType::Repeated,
+            Some(RepeatType::Daily),
+        );
+        db.tasks.add(first.clone());
+        // Done, done, missed, then done twice
+        for (day, done) in [(1, true), (2, true), (3, false), (4, true), (5, true)] {
+            let id = db
+                .tasks
+                .get_tasks()
+                .iter()
+                .find(|task| task.successor.is_none())
+                .unwrap()
+                .id;
+            if done {
+                db.tasks.get_task_mut(&id).unwrap().completed = Some(at(2019, 10, day));
+            }
+            tick(&mut db.tasks, at(2019, 10, day) + Duration::hours(1), &Utc);
+        }
+        let mut monthly = Task::new(String::from("rent"), 0);
+        monthly.repeat = Some(RepeatType::Monthly);
+        monthly.completed = Some(at(2019, 10, 1));
+        db.tasks.add(monthly);
+
+        let streaks = streaks(&crate::history::pending(&db));
+        assert_eq!(streaks.len(), 1);
+        assert_eq!(
+            streaks[&first.id],
+            Streak {
+                current: 2,
+                longest: 2
+            }
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! `#tag` labels shared by tasks and notes.
+
+use std::collections::HashSet;
+
+use crate::theme::Themed;
+
+/// Tags are stored without a leading `#` so `-t work` and `-t #work` agree.
+pub fn normalize(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_string()
+}
+
+pub fn from_args<'a, I: IntoIterator<Item = &'a str>>(args: I) -> HashSet<String> {
+    args.into_iter()
+        .map(normalize)
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// True when `tags` contains every tag in `wanted`.
+pub fn matches_all(tags: &HashSet<String>, wanted: &HashSet<String>) -> bool {
+    wanted.is_subset(tags)
+}
+
+pub fn fmt_tags(tags: &HashSet<String>) -> String {
+    let mut sorted: Vec<&String> = tags.iter().collect();
+    sorted.sort();
+    sorted
+        .iter()
+        .map(|tag| format!("#{}", tag).tag().to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// The `#tags` written into free text. A tag starts at a `#` that opens a
+/// word, so `C#`, URL fragments and markdown headings are left alone, and
+/// runs over letters, digits, `_`, `-` and `/`. Bare numbers such as
+/// `#42` are issue references rather than tags.
+pub fn extract(text: &str) -> HashSet<String> {
+    let mut found = HashSet::new();
+    let mut previous: Option<char> = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let opens_word = previous.is_none_or(|p| !(p.is_alphanumeric() || "#/&_".contains(p)));
+        previous = Some(c);
+        if c != '#' || !opens_word {
+            continue;
+        }
+        let mut end = start + 1;
+        while let Some(&(index, next)) = chars.peek() {
+            if !(next.is_alphanumeric() || "_-/".contains(next)) {
+                break;
+            }
+            end = index + next.len_utf8();
+            previous = Some(next);
+            chars.next();
+        }
+        let tag = text[start + 1..end].trim_end_matches(&['-', '/'][..]);
+        if !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit()) {
+            found.insert(tag.to_string());
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_hashtags() {
+        let text = "# Heading\nCall #alice about #work/q3, (#urgent) and #42.\n\
+                    Written in C# see http://x.org/a#anchor ## not-a-tag #trailing-";
+        let mut found: Vec<String> = extract(text).into_iter().collect();
+        found.sort();
+        assert_eq!(found, ["alice", "trailing", "urgent", "work/q3"]);
+        assert!(extract("no tags here").is_empty());
+    }
+}
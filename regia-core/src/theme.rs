@@ -0,0 +1,287 @@
+//! The colors output is shown in.
+//!
+//! Output doesn't name colors but what it is showing, an [`Element`], and
+//! the [`Theme`] in effect gives each its color: one of the built-in
+//! [`THEMES`] with the `theme` config section laid over it. Like the
+//! locale it holds for the whole process, see [`set_theme`]. Painting goes
+//! through `colored`, so `NO_COLOR`, `--no-color` and output that isn't a
+//! terminal turn it all off, see [`disable`].
+
+use std::sync::{Mutex, PoisonError};
+
+use colored::{Color, ColoredString, Colorize};
+
+use crate::priority::Priority;
+
+/// The built-in themes, the first the one used unless `theme.name` says.
+pub const THEMES: [&str; 3] = ["default", "solarized", "mono"];
+
+/// What a color is given to, each a key of the `theme` config section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Element {
+    /// Headings and the words that open a line of output
+    Header,
+    /// Markers and labels that should stand out a little
+    Accent,
+    Tag,
+    Context,
+    Overdue,
+    DueSoon,
+    /// Finished tasks, and whatever was added
+    Done,
+    /// Errors, and whatever was removed or went over
+    Error,
+    /// Warnings, and whatever changed or is blocked
+    Warning,
+    /// Words a search matched, and code
+    Highlight,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Element {
+    pub const ALL: [Element; 14] = [
+        Element::Header,
+        Element::Accent,
+        Element::Tag,
+        Element::Context,
+        Element::Overdue,
+        Element::DueSoon,
+        Element::Done,
+        Element::Error,
+        Element::Warning,
+        Element::Highlight,
+        Element::Low,
+        Element::Medium,
+        Element::High,
+        Element::Critical,
+    ];
+
+    /// Its key in the `theme` section.
+    pub fn key(self) -> &'static str {
+        match self {
+            Element::Header => "header",
+            Element::Accent => "accent",
+            Element::Tag => "tag",
+            Element::Context => "context",
+            Element::Overdue => "overdue",
+            Element::DueSoon => "due_soon",
+            Element::Done => "done",
+            Element::Error => "error",
+            Element::Warning => "warning",
+            Element::Highlight => "highlight",
+            Element::Low => "low",
+            Element::Medium => "medium",
+            Element::High => "high",
+            Element::Critical => "critical",
+        }
+    }
+
+    /// The level a task of `priority` is shown as: the highest it reaches.
+    pub fn of_priority(priority: Priority) -> Element {
+        match priority.weight() {
+            weight if weight >= Priority::Critical.weight() => Element::Critical,
+            weight if weight >= Priority::High.weight() => Element::High,
+            weight if weight >= Priority::Medium.weight() => Element::Medium,
+            _ => Element::Low,
+        }
+    }
+
+    fn index(self) -> usize {
+        Element::ALL
+            .iter()
+            .position(|element| *element == self)
+            .unwrap()
+    }
+}
+
+/// Reads a color name as `colored` knows them, like `red` or `bright
+/// blue`, or `none` for the terminal's own color. `None` when it is
+/// neither.
+pub fn parse_color(name: &str) -> Option<Option<Color>> {
+    match name.trim().to_lowercase().as_str() {
+        "none" | "default" => Some(None),
+        name => name.parse::<Color>().ok().map(Some),
+    }
+}
+
+/// A color for each [`Element`], or none to leave it as the terminal has.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    colors: [Option<Color>; 14],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            colors: [
+                Some(Color::Magenta),
+                Some(Color::Cyan),
+                Some(Color::Cyan),
+                Some(Color::Green),
+                Some(Color::Red),
+                Some(Color::Yellow),
+                Some(Color::Green),
+                Some(Color::Red),
+                Some(Color::Yellow),
+                Some(Color::Yellow),
+                None,
+                Some(Color::Yellow),
+                Some(Color::Red),
+                Some(Color::Magenta),
+            ],
+        }
+    }
+}
+
+impl Theme {
+    /// One of the built-in [`THEMES`].
+    pub fn named(name: &str) -> Option<Theme> {
+        match name.trim().to_lowercase().as_str() {
+            "default" => Some(Theme::default()),
+            // The accents of the solarized palette, as terminals set up
+            // for it map them onto the sixteen colors
+            "solarized" => Some(Theme {
+                colors: [
+                    Some(Color::Blue),
+                    Some(Color::Cyan),
+                    Some(Color::Cyan),
+                    Some(Color::BrightMagenta),
+                    Some(Color::Red),
+                    Some(Color::Yellow),
+                    Some(Color::Green),
+                    Some(Color::Red),
+                    Some(Color::BrightRed),
+                    Some(Color::Yellow),
+                    None,
+                    Some(Color::Yellow),
+                    Some(Color::BrightRed),
+                    Some(Color::Magenta),
+                ],
+            }),
+            // Bold and dim still tell things apart
+            "mono" => Some(Theme { colors: [None; 14] }),
+            _ => None,
+        }
+    }
+
+    pub fn color(&self, element: Element) -> Option<Color> {
+        self.colors[element.index()]
+    }
+
+    pub fn set(&mut self, element: Element, color: Option<Color>) {
+        self.colors[element.index()] = color;
+    }
+
+    /// `text` in the color of `element`.
+    pub fn paint<S: Colorize>(&self, text: S, element: Element) -> ColoredString {
+        in_color(text, self.color(element))
+    }
+}
+
+/// `text` in `color`, or as the terminal has it without one.
+pub fn in_color<S: Colorize>(text: S, color: Option<Color>) -> ColoredString {
+    match color {
+        Some(color) => text.color(color),
+        None => text.normal(),
+    }
+}
+
+static THEME: Mutex<Option<Theme>> = Mutex::new(None);
+
+/// Sets the colors output is shown in from here on.
+pub fn set_theme(theme: Theme) {
+    *THEME.lock().unwrap_or_else(PoisonError::into_inner) = Some(theme);
+}
+
+/// The theme [`set_theme`] set, or the default one.
+pub fn theme() -> Theme {
+    THEME
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .unwrap_or_default()
+}
+
+/// Turns color off for the rest of the process, bold and dim too.
+pub fn disable() {
+    colored::control::set_override(false);
+}
+
+/// Whether output is painted at all: not after [`disable`], with
+/// `NO_COLOR` set, or when stdout isn't a terminal.
+pub fn enabled() -> bool {
+    colored::control::SHOULD_COLORIZE.should_colorize()
+}
+
+/// Paints text by what it is, in the theme in effect, as
+/// [`colored::Colorize`] does by color.
+pub trait Themed: Sized {
+    fn themed(self, element: Element) -> ColoredString;
+
+    fn header(self) -> ColoredString {
+        self.themed(Element::Header)
+    }
+    fn accent(self) -> ColoredString {
+        self.themed(Element::Accent)
+    }
+    fn tag(self) -> ColoredString {
+        self.themed(Element::Tag)
+    }
+    fn context(self) -> ColoredString {
+        self.themed(Element::Context)
+    }
+    fn overdue(self) -> ColoredString {
+        self.themed(Element::Overdue)
+    }
+    fn due_soon(self) -> ColoredString {
+        self.themed(Element::DueSoon)
+    }
+    fn done(self) -> ColoredString {
+        self.themed(Element::Done)
+    }
+    fn error(self) -> ColoredString {
+        self.themed(Element::Error)
+    }
+    fn warning(self) -> ColoredString {
+        self.themed(Element::Warning)
+    }
+    fn highlight(self) -> ColoredString {
+        self.themed(Element::Highlight)
+    }
+}
+
+impl<S: Colorize> Themed for S {
+    fn themed(self, element: Element) -> ColoredString {
+        theme().paint(self, element)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn themes_color_elements() {
+        let theme = Theme::default();
+        assert_eq!(theme.color(Element::Header), Some(Color::Magenta));
+        assert_eq!(theme.color(Element::Low), None);
+        assert_eq!(Element::of_priority(Priority::Other(4)), Element::Medium);
+        assert_eq!(Element::of_priority(Priority::Other(12)), Element::Critical);
+
+        let mut mono = Theme::named("Mono").unwrap();
+        assert!(Element::ALL
+            .iter()
+            .all(|element| mono.color(*element).is_none()));
+        mono.set(Element::Overdue, Some(Color::Red));
+        assert_eq!(mono.color(Element::Overdue), Some(Color::Red));
+        assert!(THEMES.iter().all(|name| Theme::named(name).is_some()));
+        assert!(Theme::named("neon").is_none());
+
+        assert_eq!(parse_color("bright blue"), Some(Some(Color::BrightBlue)));
+        assert_eq!(parse_color("none"), Some(None));
+        assert_eq!(parse_color("mauve"), None);
+    }
+}
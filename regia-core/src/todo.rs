@@ -0,0 +1,1608 @@
+//! Tasks, their dependencies and the per-project task list.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::string::String;
+use std::vec::Vec;
+
+use chrono::{DateTime, Duration, Utc};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::attach::Attachment;
+use crate::contexts;
+use crate::dates;
+use crate::ident;
+use crate::index::{Indexed, ItemMut, List};
+use crate::priority::Priority;
+use crate::query::{self, DateField, Flag, Test};
+use crate::schedule::Streak;
+use crate::tags;
+use crate::theme::{self, Element, Themed};
+use crate::urgency::Urgency;
+
+/// Whether a task is due once or comes back on a schedule.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub enum TaskType {
+    Deadline,
+    Repeated,
+}
+
+/// How often a repeated task comes back. `schedule::step` works out when.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum RepeatType {
+    Daily,
+    Weekly,
+    Monthly,
+    /// Every this many days
+    EveryDays(u32),
+    /// On these weekdays, bit 0 being Monday
+    Weekdays(u8),
+    LastDayOfMonth,
+    /// A five-field cron expression, see `cron::Cron`
+    Cron(String),
+}
+
+const WEEKDAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+impl RepeatType {
+    /// Whether instances come often enough for a streak of them to mean
+    /// something: daily or weekly, give or take.
+    pub fn has_streaks(&self) -> bool {
+        match self {
+            RepeatType::Daily | RepeatType::Weekly | RepeatType::Weekdays(_) => true,
+            RepeatType::EveryDays(days) => *days <= 7,
+            _ => false,
+        }
+    }
+
+    /// Parses `daily`, `weekly`, `monthly`, `every 3 days`,
+    /// `every mon,wed,fri`, `every weekday`, `last day of month` and cron
+    /// expressions, bare or after `cron`.
+    pub fn parse(input: &str) -> Option<RepeatType> {
+        let input = input.trim().to_ascii_lowercase();
+        match input.as_str() {
+            "daily" | "every day" => return Some(RepeatType::Daily),
+            "weekly" | "every week" => return Some(RepeatType::Weekly),
+            "monthly" | "every month" => return Some(RepeatType::Monthly),
+            "last day of month" | "end of month" | "eom" => {
+                return Some(RepeatType::LastDayOfMonth)
+            }
+            "every weekday" => return Some(RepeatType::Weekdays(0b0011111)),
+            _ => {}
+        }
+
+        let expr = input.strip_prefix("cron ").unwrap_or(&input).trim();
+        if expr.split_whitespace().count() == 5 {
+            // An expression that never fires would never spawn anything
+            let cron = crate::cron::Cron::parse(expr)?;
+            cron.next_after(Utc::now(), &Utc)?;
+            return Some(RepeatType::Cron(
+                expr.split_whitespace().collect::<Vec<_>>().join(" "),
+            ));
+        }
+
+        let every = input.strip_prefix("every ")?.trim();
+        let count = every
+            .strip_suffix("days")
+            .or_else(|| every.strip_suffix("day"))
+            .or_else(|| every.strip_suffix('d'))
+            .and_then(|count| count.trim().parse::<u32>().ok());
+        if let Some(days) = count {
+            return Some(RepeatType::EveryDays(days)).filter(|_| days > 0);
+        }
+
+        let mut days = 0u8;
+        for name in every.split(|c: char| c == ',' || c == '/' || c.is_whitespace()) {
+            if name.is_empty() {
+                continue;
+            }
+            let index = WEEKDAY_NAMES.iter().position(|day| name.starts_with(day))?;
+            days |= 1 << index;
+        }
+        if days == 0 {
+            None
+        } else {
+            Some(RepeatType::Weekdays(days))
+        }
+    }
+}
+
+impl std::fmt::Display for RepeatType {
+    /// Writes the rule in a form `RepeatType::parse` reads back.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RepeatType::Daily => write!(f, "daily"),
+            RepeatType::Weekly => write!(f, "weekly"),
+            RepeatType::Monthly => write!(f, "monthly"),
+            RepeatType::EveryDays(days) => write!(f, "every {} days", days),
+            RepeatType::Weekdays(days) => {
+                let names: Vec<&str> = WEEKDAY_NAMES
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| days & (1 << index) != 0)
+                    .map(|(_, name)| *name)
+                    .collect();
+                write!(f, "every {}", names.join(","))
+            }
+            RepeatType::LastDayOfMonth => write!(f, "last day of month"),
+            RepeatType::Cron(expr) => write!(f, "cron {}", expr),
+        }
+    }
+}
+
+/// A stretch of time spent working on a task. `stop` is `None` while the
+/// clock is still running.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Session {
+    pub start: DateTime<Utc>,
+    pub stop: Option<DateTime<Utc>>,
+    /// A work phase `regia pomo` ran to the end
+    #[serde(default)]
+    pub pomodoro: bool,
+}
+
+impl Session {
+    /// How much of this session falls between `from` and `to`, counting a
+    /// running session up to `now`.
+    pub fn overlap(&self, from: DateTime<Utc>, to: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+        let start = self.start.max(from);
+        let stop = self.stop.unwrap_or(now).min(to);
+        if stop > start {
+            stop - start
+        } else {
+            Duration::zero()
+        }
+    }
+}
+
+/// One step in a task's checklist.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChecklistItem {
+    pub text: String,
+    pub done: bool,
+}
+
+/// A remark left on a task with `task comment`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub author: String,
+    pub created: DateTime<Utc>,
+    pub body: String,
+}
+
+/// The GitHub issue a task tracks.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Issue {
+    /// The repository as `owner/name`
+    pub repo: String,
+    pub number: u64,
+    pub url: String,
+}
+
+/// A single to-do. Tasks compare and sort by `id` alone.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Task {
+    pub id: Uuid,
+    pub priority: Priority,
+    pub created: DateTime<Utc>,
+    pub due: Option<DateTime<Utc>>,
+    pub content: String,
+    pub task_type: Option<TaskType>,
+    pub repeat: Option<RepeatType>,
+    pub depends: HashSet<Uuid>,
+    #[serde(default)]
+    pub completed: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub tags: HashSet<String>,
+    /// The next occurrence spawned from this instance of a repeated task
+    #[serde(default)]
+    pub successor: Option<Uuid>,
+    #[serde(default)]
+    pub parent: Option<Uuid>,
+    /// A single reminder offset, from before `reminders`; read both through
+    /// `reminder_offsets`
+    #[serde(default)]
+    pub remind: Option<i64>,
+    /// Time tracked with `task start`/`task stop`, oldest first
+    #[serde(default)]
+    pub sessions: Vec<Session>,
+    /// Kept out of default listings until then by `task snooze`
+    #[serde(default)]
+    pub hidden_until: Option<DateTime<Utc>>,
+    /// Files attached with `task attach`
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// Steps added with `task check add`, in order
+    #[serde(default)]
+    pub checklist: Vec<ChecklistItem>,
+    /// Seconds before `due` to send each reminder, overriding the daemon
+    /// default
+    #[serde(default)]
+    pub reminders: Vec<i64>,
+    /// When work is meant to start. Default listings leave the task out
+    /// until then and the agenda lists it on that day.
+    #[serde(default)]
+    pub scheduled: Option<DateTime<Utc>>,
+    /// Left with `task comment`, oldest first
+    #[serde(default)]
+    pub comments: Vec<Comment>,
+    /// Seconds of work expected, set with `--estimate`, to weigh against
+    /// `sessions`
+    #[serde(default)]
+    pub estimate: Option<i64>,
+    /// Where the task stands in `tasks.workflow`, set with `task move`;
+    /// read it through `Workflow::status_of`
+    #[serde(default)]
+    pub status: Option<String>,
+    /// The GitHub issue the task was imported from, see `task sync-github`
+    #[serde(default)]
+    pub issue: Option<Issue>,
+    /// The first instance of the repeated task this one continues, unless
+    /// it is that one
+    #[serde(default)]
+    pub series: Option<Uuid>,
+    /// Where the task can be done, see [`contexts`]
+    #[serde(default)]
+    pub contexts: HashSet<String>,
+    /// When a command last changed the task; read it through `touched`
+    #[serde(default)]
+    pub modified: Option<DateTime<Utc>>,
+    /// Who the task was handed to, set with `task wait`
+    #[serde(default)]
+    pub waiting_on: Option<String>,
+    /// When to chase `waiting_on` up if the task is still open
+    #[serde(default)]
+    pub waiting_until: Option<DateTime<Utc>>,
+}
+
+impl Task {
+    pub fn new(content: String, priority: impl Into<Priority>) -> Self {
+        Task {
+            id: Uuid::new_v4(),
+            priority: priority.into(),
+            created: Utc::now(),
+            due: None,
+            content,
+            task_type: None,
+            repeat: None,
+            depends: HashSet::new(),
+            completed: None,
+            tags: HashSet::new(),
+            successor: None,
+            parent: None,
+            remind: None,
+            sessions: Vec::new(),
+            hidden_until: None,
+            attachments: Vec::new(),
+            checklist: Vec::new(),
+            reminders: Vec::new(),
+            scheduled: None,
+            comments: Vec::new(),
+            estimate: None,
+            status: None,
+            issue: None,
+            series: None,
+            contexts: HashSet::new(),
+            modified: None,
+            waiting_on: None,
+            waiting_until: None,
+        }
+    }
+
+    pub fn new_date(
+        content: String,
+        priority: impl Into<Priority>,
+        due: Option<DateTime<Utc>>,
+        task_type: TaskType,
+        repeat: Option<RepeatType>,
+    ) -> Self {
+        Task {
+            id: Uuid::new_v4(),
+            priority: priority.into(),
+            created: Utc::now(),
+            due,
+            content,
+            task_type: Some(task_type),
+            repeat,
+            depends: HashSet::new(),
+            completed: None,
+            tags: HashSet::new(),
+            successor: None,
+            parent: None,
+            remind: None,
+            sessions: Vec::new(),
+            hidden_until: None,
+            attachments: Vec::new(),
+            checklist: Vec::new(),
+            reminders: Vec::new(),
+            scheduled: None,
+            comments: Vec::new(),
+            estimate: None,
+            status: None,
+            issue: None,
+            series: None,
+            contexts: HashSet::new(),
+            modified: None,
+            waiting_on: None,
+            waiting_until: None,
+        }
+    }
+
+    pub fn fmt(&self, priority_map: &[(u32, &str)]) -> String {
+        self.fmt_blocked(priority_map, &[])
+    }
+
+    /// Formats the task dimmed and followed by the content of `blockers`
+    /// when it is waiting on open dependencies.
+    pub fn fmt_blocked(&self, priority_map: &[(u32, &str)], blockers: &[&Task]) -> String {
+        Formatter::new(priority_map).task(self, blockers)
+    }
+
+    pub fn add_dependency(&mut self, task_id: &Uuid) {
+        self.depends.insert(*task_id);
+    }
+
+    /// Seconds before `due` to send each reminder, longest first. Empty
+    /// means the daemon default.
+    pub fn reminder_offsets(&self) -> Vec<i64> {
+        let mut offsets: Vec<i64> = self.reminders.iter().chain(&self.remind).cloned().collect();
+        offsets.sort_unstable_by(|a, b| b.cmp(a));
+        offsets.dedup();
+        offsets
+    }
+
+    /// How many checklist steps are done, out of how many, if it has any.
+    pub fn checklist_progress(&self) -> Option<(usize, usize)> {
+        if self.checklist.is_empty() {
+            return None;
+        }
+        let done = self.checklist.iter().filter(|item| item.done).count();
+        Some((done, self.checklist.len()))
+    }
+
+    /// Whether the task is snoozed past `now`.
+    pub fn is_snoozed(&self, now: DateTime<Utc>) -> bool {
+        self.hidden_until.is_some_and(|until| until > now)
+    }
+
+    /// Whether the task is open and handed to someone else to do.
+    pub fn is_waiting(&self) -> bool {
+        !self.is_done() && self.waiting_on.is_some()
+    }
+
+    /// Whether the task is still waiting on someone past the date set to
+    /// follow it up.
+    pub fn needs_follow_up(&self, now: DateTime<Utc>) -> bool {
+        self.is_waiting() && self.waiting_until.is_some_and(|until| until <= now)
+    }
+
+    /// Whether the task is open and not meant to be started before a
+    /// later date.
+    pub fn is_scheduled_later(&self, now: DateTime<Utc>) -> bool {
+        !self.is_done() && self.scheduled.is_some_and(|scheduled| scheduled > now)
+    }
+
+    /// When anything last happened to the task: a change, time tracked or
+    /// a comment, or else its creation. Tasks from before changes were
+    /// stamped go by the rest.
+    pub fn touched(&self) -> DateTime<Utc> {
+        let sessions = self
+            .sessions
+            .iter()
+            .map(|session| session.stop.unwrap_or(session.start));
+        let comments = self.comments.iter().map(|comment| comment.created);
+        [Some(self.created), self.modified, self.completed]
+            .iter()
+            .flatten()
+            .copied()
+            .chain(sessions)
+            .chain(comments)
+            .max()
+            .unwrap_or(self.created)
+    }
+
+    /// Whether the task is open and nothing has happened to it since
+    /// `cutoff`.
+    pub fn is_stale(&self, cutoff: DateTime<Utc>) -> bool {
+        !self.is_done() && !self.is_running() && self.touched() < cutoff
+    }
+
+    /// Whether a tracked session is still open.
+    pub fn is_running(&self) -> bool {
+        self.sessions
+            .last()
+            .is_some_and(|session| session.stop.is_none())
+    }
+
+    /// Opens a tracked session at `now`, unless one is already running.
+    pub fn start(&mut self, now: DateTime<Utc>) -> bool {
+        if self.is_running() {
+            return false;
+        }
+        self.sessions.push(Session {
+            start: now,
+            stop: None,
+            pomodoro: false,
+        });
+        true
+    }
+
+    /// Adds a finished pomodoro from `start` to `stop`, ahead of the
+    /// session still running if there is one.
+    pub fn log_pomodoro(&mut self, start: DateTime<Utc>, stop: DateTime<Utc>) {
+        let at = if self.is_running() {
+            self.sessions.len() - 1
+        } else {
+            self.sessions.len()
+        };
+        let session = Session {
+            start,
+            stop: Some(stop),
+            pomodoro: true,
+        };
+        self.sessions.insert(at, session);
+    }
+
+    /// Closes the running session at `now`, returning how long it lasted.
+    pub fn stop(&mut self, now: DateTime<Utc>) -> Option<Duration> {
+        let session = self
+            .sessions
+            .last_mut()
+            .filter(|session| session.stop.is_none())?;
+        session.stop = Some(now.max(session.start));
+        Some(session.stop.unwrap() - session.start)
+    }
+
+    /// The time tracked between `from` and `to`.
+    pub fn tracked_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Duration {
+        self.sessions
+            .iter()
+            .fold(Duration::zero(), |total, session| {
+                total + session.overlap(from, to, now)
+            })
+    }
+
+    /// All the time tracked on this task, up to `now`.
+    pub fn tracked(&self, now: DateTime<Utc>) -> Duration {
+        let since = self.sessions.first().map_or(now, |session| session.start);
+        self.tracked_between(since, now, now)
+    }
+
+    /// What the instances of a repeated task have in common, this one's own
+    /// id for the first of them.
+    pub fn series_id(&self) -> Uuid {
+        self.series.unwrap_or(self.id)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.completed.is_some()
+    }
+
+    /// A new task for doing this one again: the same content, priority,
+    /// tags, contexts, estimate, reminders and parent, and its checklist
+    /// with nothing ticked. Given `due` the copy is due then, its start
+    /// moved along by as much, else it keeps this one's dates. How the
+    /// task went, its sessions, comments and completion, stays behind, as
+    /// does any repeat.
+    pub fn duplicate(&self, due: Option<DateTime<Utc>>) -> Task {
+        let mut copy = Task::new(self.content.clone(), self.priority);
+        copy.tags = self.tags.clone();
+        copy.contexts = self.contexts.clone();
+        copy.estimate = self.estimate;
+        copy.remind = self.remind;
+        copy.reminders = self.reminders.clone();
+        copy.parent = self.parent;
+        copy.checklist = self
+            .checklist
+            .iter()
+            .map(|item| ChecklistItem {
+                text: item.text.clone(),
+                done: false,
+            })
+            .collect();
+        copy.due = self.due;
+        copy.scheduled = self.scheduled;
+        if let Some(due) = due {
+            if let (Some(was), Some(scheduled)) = (self.due, self.scheduled) {
+                copy.scheduled = Some(scheduled + (due - was));
+            }
+            copy.due = Some(due);
+        }
+        if copy.due.is_some() {
+            copy.task_type = Some(TaskType::Deadline);
+        }
+        copy
+    }
+
+    /// Marks the task done as of now.
+    pub fn complete(&mut self) {
+        if self.completed.is_none() {
+            self.completed = Some(Utc::now());
+        }
+    }
+
+    /// The order every listing falls back on where what it sorts by ties:
+    /// most important first, then soonest due with undated tasks last,
+    /// then newest, then by id so that no two tasks ever tie.
+    pub fn listing_order(&self, other: &Task) -> Ordering {
+        other
+            .priority
+            .cmp(&self.priority)
+            .then_with(|| match (self.due, other.due) {
+                (Some(ours), Some(theirs)) => ours.cmp(&theirs),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            })
+            .then_with(|| other.created.cmp(&self.created))
+            .then_with(|| self.id.cmp(&other.id))
+    }
+}
+
+impl Indexed for Task {
+    const KIND: &'static str = "task";
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn tags(&self) -> &HashSet<String> {
+        &self.tags
+    }
+
+    fn text(&self) -> &str {
+        &self.content
+    }
+
+    fn depends(&self) -> Vec<Uuid> {
+        self.depends.iter().copied().collect()
+    }
+}
+
+impl PartialEq for Task {
+    fn eq(&self, other: &Task) -> bool {
+        self.id == other.id
+    }
+}
+
+impl PartialOrd for Task {
+    fn partial_cmp(&self, other: &Task) -> Option<Ordering> {
+        Some(self.id.cmp(&other.id))
+    }
+}
+
+/// Arranges `list` depth first so every task follows its parent, pairing
+/// each with its depth. Tasks whose parent is not in `list` are roots, and
+/// siblings keep their relative order from `list`.
+pub fn tree_order<'a>(list: &[&'a Task]) -> Vec<(usize, &'a Task)> {
+    let listed: HashSet<Uuid> = list.iter().map(|task| task.id).collect();
+    let is_root = |task: &Task| match task.parent {
+        Some(parent) => !listed.contains(&parent) || parent == task.id,
+        None => true,
+    };
+
+    let mut ordered = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stack: Vec<(usize, &'a Task)> = list
+        .iter()
+        .rev()
+        .filter(|task| is_root(task))
+        .map(|task| (0, *task))
+        .collect();
+    while let Some((depth, task)) = stack.pop() {
+        if !seen.insert(task.id) {
+            continue;
+        }
+        ordered.push((depth, task));
+        stack.extend(
+            list.iter()
+                .rev()
+                .filter(|child| child.parent == Some(task.id) && child.id != task.id)
+                .map(|child| (depth + 1, *child)),
+        );
+    }
+    ordered
+}
+
+/// Renders tasks for the terminal. Build one per listing so every line
+/// agrees on what "now" is.
+pub struct Formatter<'a> {
+    pub priority_map: &'a [(u32, &'a str)],
+    pub now: DateTime<Utc>,
+    /// Open tasks due within this long are highlighted as due soon
+    pub due_soon: Duration,
+    /// The streaks shown after repeated tasks, by series
+    pub streaks: Option<&'a HashMap<Uuid, Streak>>,
+    /// Open tasks untouched for this long show how long
+    pub stale_after: Option<Duration>,
+    /// Color tasks by the priorities they have in effect under these rules
+    pub urgency: Option<Urgency>,
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new(priority_map: &'a [(u32, &'a str)]) -> Self {
+        Self {
+            priority_map,
+            now: Utc::now(),
+            due_soon: Duration::days(1),
+            streaks: None,
+            stale_after: None,
+            urgency: None,
+        }
+    }
+
+    /// "due in 2d" or "overdue 3h" for an open task with a due date, in
+    /// the theme's colors once overdue and while due soon.
+    pub fn due(&self, task: &Task) -> Option<String> {
+        let due = task.due.filter(|_| !task.is_done())?;
+        let until = due - self.now;
+        Some(if until < Duration::zero() {
+            format!("overdue {}", dates::fmt_duration(until))
+                .overdue()
+                .to_string()
+        } else if until <= self.due_soon {
+            format!("due in {}", dates::fmt_duration(until))
+                .due_soon()
+                .to_string()
+        } else {
+            format!("due in {}", dates::fmt_duration(until))
+        })
+    }
+
+    /// The color of the task's priority, as in effect under `urgency`: from
+    /// the config's `colors` when it sets any, otherwise the theme's for its
+    /// level.
+    pub fn color(&self, task: &Task) -> Option<Color> {
+        let priority = match &self.urgency {
+            Some(urgency) => urgency.priority(task, self.now),
+            None => task.priority,
+        };
+        if self.priority_map.is_empty() {
+            return theme::theme().color(Element::of_priority(priority));
+        }
+        self.priority_map
+            .iter()
+            .find(|(pri, _)| priority.weight() < *pri)
+            .and_then(|(_, col)| theme::parse_color(col).flatten())
+    }
+
+    /// The task's line: content colored by priority and its due date, its
+    /// repeat and streak, then tags and, dimmed, what it is waiting on.
+    pub fn task(&self, task: &Task, blockers: &[&Task]) -> String {
+        let bullet = if task.is_done() { "x" } else { "*" };
+        let line = format!("{} {}", bullet, task.content);
+        let mut line = theme::in_color(line.as_str(), self.color(task));
+        if !blockers.is_empty() {
+            line = line.dimmed();
+        }
+
+        let mut out = line.to_string();
+        if let Some((done, total)) = task.checklist_progress() {
+            out = format!("{} {}", out, format!("[{}/{}]", done, total).dimmed());
+        }
+        if let Some(due) = self.due(task) {
+            out = format!("{} ({})", out, due);
+        }
+        if let Some(scheduled) = task.scheduled.filter(|_| task.is_scheduled_later(self.now)) {
+            let until = dates::fmt_duration(scheduled - self.now);
+            out = format!("{} {}", out, format!("(starts in {})", until).dimmed());
+        }
+        if let Some(repeat) = task.repeat.as_ref().filter(|_| !task.is_done()) {
+            out = format!("{} {}", out, format!("↻ {}", repeat).dimmed());
+            let streak = self
+                .streaks
+                .and_then(|streaks| streaks.get(&task.series_id()));
+            if let Some(streak) = streak.filter(|streak| streak.current > 0) {
+                out = format!("{} 🔥 {}", out, streak.current);
+            }
+        }
+        if self
+            .stale_after
+            .is_some_and(|after| task.is_stale(self.now - after))
+        {
+            let age = format!(
+                "(untouched {})",
+                dates::fmt_duration(self.now - task.touched())
+            );
+            out = format!("{} {}", out, age.warning());
+        }
+        if let Some(who) = task.waiting_on.as_ref().filter(|_| task.is_waiting()) {
+            let waiting = match task.waiting_until {
+                Some(until) if until <= self.now => format!(
+                    "(waiting on {}, follow up {} ago)",
+                    who,
+                    dates::fmt_duration(self.now - until)
+                )
+                .warning(),
+                Some(until) => format!(
+                    "(waiting on {}, follow up in {})",
+                    who,
+                    dates::fmt_duration(until - self.now)
+                )
+                .dimmed(),
+                None => format!("(waiting on {})", who).dimmed(),
+            };
+            out = format!("{} {}", out, waiting);
+        }
+        if !task.tags.is_empty() {
+            out = format!("{} {}", out, tags::fmt_tags(&task.tags));
+        }
+        if !task.contexts.is_empty() {
+            out = format!("{} {}", out, contexts::fmt_contexts(&task.contexts));
+        }
+        if !blockers.is_empty() {
+            let names: Vec<&str> = blockers.iter().map(|dep| dep.content.as_str()).collect();
+            let blocked = format!("(blocked by: {})", names.join(", "));
+            out = format!("{} {}", out, blocked.dimmed());
+        }
+        out
+    }
+}
+
+/// The order `Tasks::query` returns tasks in.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SortBy {
+    /// Newest day first, most important first within a day
+    #[default]
+    Day,
+    /// Soonest due first, undated tasks last
+    Due,
+    /// Most important first
+    Priority,
+    /// Newest first
+    Created,
+}
+
+impl SortBy {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "day" => Some(SortBy::Day),
+            "due" => Some(SortBy::Due),
+            "priority" => Some(SortBy::Priority),
+            "created" => Some(SortBy::Created),
+            _ => None,
+        }
+    }
+}
+
+/// How many items a page holds unless `--per-page` says.
+pub const PER_PAGE: usize = 25;
+
+/// One page of a long listing, the first numbered 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Page {
+    pub number: usize,
+    pub size: usize,
+}
+
+impl Page {
+    /// How many pages `len` items fill, one even when there are none.
+    pub fn count(&self, len: usize) -> usize {
+        len.div_ceil(self.size).max(1)
+    }
+
+    /// Whether items come after this page of `len`.
+    pub fn has_more(&self, len: usize) -> bool {
+        self.number * self.size < len
+    }
+}
+
+/// Keeps a listing to one [`Page`] of it.
+pub trait Paginate: Iterator + Sized {
+    /// The items on `page`, or all of them without one.
+    fn paginate(self, page: Option<Page>) -> std::iter::Take<std::iter::Skip<Self>> {
+        let (skip, take) = match page {
+            Some(page) => (page.number.saturating_sub(1) * page.size, page.size),
+            None => (0, usize::MAX),
+        };
+        self.skip(skip).take(take)
+    }
+}
+
+impl<I: Iterator> Paginate for I {}
+
+/// Which tasks to list and how. Every filter that is set must match; the
+/// default selects all open tasks.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    /// Also select completed tasks
+    pub include_done: bool,
+    /// Select only tasks carrying all of these tags
+    pub tags: HashSet<String>,
+    /// Select only tasks that fit this context, see [`contexts::fits`]
+    pub context: Option<String>,
+    pub due_before: Option<DateTime<Utc>>,
+    pub due_after: Option<DateTime<Utc>>,
+    pub priority_min: Option<Priority>,
+    /// Select only open tasks whose due date has passed
+    pub overdue: bool,
+    /// Select only open tasks waiting on open dependencies
+    pub blocked: bool,
+    /// Select only snoozed tasks, which are otherwise left out
+    pub snoozed: bool,
+    /// Also select tasks scheduled to start later, which are otherwise
+    /// left out
+    pub upcoming: bool,
+    pub sort: SortBy,
+    /// Keep at most this many tasks after sorting
+    pub limit: Option<usize>,
+    /// Select only open tasks left untouched since then, see
+    /// [`Task::is_stale`]
+    pub stale_before: Option<DateTime<Utc>>,
+    /// Select only tasks waiting on someone, see [`Task::is_waiting`]
+    pub waiting: bool,
+    /// Select only tasks this picks, see [`query`]. When it tests for done,
+    /// snoozed or scheduled tasks, it decides about those itself.
+    pub filter: Option<query::Filter>,
+    /// Sort, and select by `priority_min`, by the priorities tasks have in
+    /// effect under these rules rather than as set
+    pub urgency: Option<Urgency>,
+}
+
+/// The tasks of one project, kept sorted by id for lookup.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Tasks {
+    id: Uuid,
+    group_name: String,
+    tasks: List<Task>,
+}
+
+impl Default for Tasks {
+    fn default() -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            group_name: "root".to_string(),
+            tasks: List::default(),
+        }
+    }
+}
+
+impl Tasks {
+    pub fn named(group_name: &str) -> Self {
+        Self {
+            group_name: group_name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn get_tasks(&self) -> &Vec<Task> {
+        self.tasks.items()
+    }
+
+    pub fn get_task(&self, id: &Uuid) -> Option<&Task> {
+        self.tasks.get(id)
+    }
+
+    /// Tasks whose id starts with `prefix`, as [`ident::matching`] reads it.
+    pub fn find_by_prefix(&self, prefix: &str) -> Vec<&Task> {
+        let ids = ident::matching(self.get_tasks().iter().map(|task| task.id), prefix);
+        ids.iter().filter_map(|id| self.get_task(id)).collect()
+    }
+
+    pub fn get_task_mut(&mut self, id: &Uuid) -> Option<ItemMut<'_, Task>> {
+        self.tasks.get_mut(id)
+    }
+
+    /// Inserts `task` in id order, replacing any task with the same id.
+    pub fn add(&mut self, task: Task) {
+        self.tasks.insert(task);
+    }
+
+    /// Tasks carrying every tag in `wanted`, looked up in the tag index.
+    pub fn tagged(&self, wanted: &HashSet<String>) -> Vec<&Task> {
+        self.tasks.tagged(wanted)
+    }
+
+    /// Tasks with a word starting with each word of `query`, looked up in
+    /// the word index.
+    pub fn with_words(&self, query: &str) -> Vec<&Task> {
+        self.tasks.with_words(query)
+    }
+
+    /// Dependencies of `task` that exist and are not yet done.
+    pub fn open_dependencies(&self, task: &Task) -> Vec<&Task> {
+        let mut open: Vec<&Task> = task
+            .depends
+            .iter()
+            .filter_map(|dep| self.get_task(dep))
+            .filter(|dep| !dep.is_done())
+            .collect();
+        open.sort_by_key(|dep| dep.created);
+        open
+    }
+
+    /// Tasks that depend on the task `id`, looked up in the dependency
+    /// index.
+    pub fn dependents(&self, id: &Uuid) -> Vec<&Task> {
+        self.tasks.dependents(id)
+    }
+
+    /// Open tasks that waited on any of the tasks `done` and, with those
+    /// done, wait on nothing anymore, highest priority first.
+    pub fn unblocked_by(&self, done: &[Uuid]) -> Vec<&Task> {
+        let mut unblocked: Vec<&Task> = Vec::new();
+        for id in done {
+            for task in self.dependents(id) {
+                if !task.is_done()
+                    && self.open_dependencies(task).is_empty()
+                    && !unblocked.iter().any(|seen| seen.id == task.id)
+                {
+                    unblocked.push(task);
+                }
+            }
+        }
+        unblocked.sort_by(|left, right| left.listing_order(right));
+        unblocked
+    }
+
+    /// Every dependency reachable from `task`, depth first, paired with its
+    /// depth below `task`. Each task appears once even if shared.
+    pub fn dependency_tree<'a>(&'a self, task: &Task) -> Vec<(usize, &'a Task)> {
+        let mut tree = Vec::new();
+        let mut seen = HashSet::new();
+        seen.insert(task.id);
+        let mut stack: Vec<(usize, &Task)> = self
+            .newest_dependencies(task)
+            .into_iter()
+            .map(|dep| (0, dep))
+            .collect();
+        while let Some((depth, dep)) = stack.pop() {
+            if !seen.insert(dep.id) {
+                continue;
+            }
+            tree.push((depth, dep));
+            stack.extend(
+                self.newest_dependencies(dep)
+                    .into_iter()
+                    .map(|next| (depth + 1, next)),
+            );
+        }
+        tree
+    }
+
+    /// Dependencies of `task` ordered newest first, so popping them off a
+    /// stack visits the oldest first.
+    fn newest_dependencies<'a>(&'a self, task: &Task) -> Vec<&'a Task> {
+        let mut deps: Vec<&Task> = task
+            .depends
+            .iter()
+            .filter_map(|dep| self.get_task(dep))
+            .collect();
+        deps.sort_by_key(|dep| std::cmp::Reverse(dep.created));
+        deps
+    }
+
+    /// True when making `task_id` depend on `dep_id` would close a loop,
+    /// i.e. `task_id` is already reachable from `dep_id`.
+    pub fn would_cycle(&self, task_id: &Uuid, dep_id: &Uuid) -> bool {
+        let mut seen = HashSet::new();
+        let mut stack = vec![*dep_id];
+        while let Some(id) = stack.pop() {
+            if id == *task_id {
+                return true;
+            }
+            if !seen.insert(id) {
+                continue;
+            }
+            if let Some(task) = self.get_task(&id) {
+                stack.extend(task.depends.iter());
+            }
+        }
+        false
+    }
+
+    /// Tasks with a tracked session still running.
+    pub fn running(&self) -> Vec<&Task> {
+        self.get_tasks()
+            .iter()
+            .filter(|task| task.is_running())
+            .collect()
+    }
+
+    /// Runs `query` over the tasks: filters, then sorts, then limits.
+    pub fn query(&self, query: &Query, now: DateTime<Utc>) -> Vec<&Task> {
+        let mentions = |wanted: fn(&Test) -> bool| {
+            query
+                .filter
+                .as_ref()
+                .is_some_and(|filter| filter.mentions(wanted))
+        };
+        let done_decided = mentions(|test| {
+            matches!(
+                test,
+                Test::Flag(Flag::Open | Flag::Done) | Test::Date(DateField::Completed, ..)
+            )
+        });
+        let snoozed_decided = mentions(|test| *test == Test::Flag(Flag::Snoozed));
+        let later_decided = mentions(|test| *test == Test::Flag(Flag::Scheduled));
+        let mut selected: Vec<&Task> =
+            self.tasks
+                .tagged(&query.tags)
+                .into_iter()
+                .filter(|task| query.include_done || done_decided || !task.is_done())
+                .filter(|task| {
+                    query
+                        .context
+                        .as_deref()
+                        .is_none_or(|wanted| contexts::fits(&task.contexts, wanted))
+                })
+                .filter(|task| match query.due_before {
+                    Some(before) => task.due.is_some_and(|due| due < before),
+                    None => true,
+                })
+                .filter(|task| match query.due_after {
+                    Some(after) => task.due.is_some_and(|due| due > after),
+                    None => true,
+                })
+                .filter(|task| {
+                    query.priority_min.is_none_or(|min| match &query.urgency {
+                        Some(urgency) => urgency.priority(task, now) >= min,
+                        None => task.priority >= min,
+                    })
+                })
+                .filter(|task| {
+                    !query.overdue || (!task.is_done() && task.due.is_some_and(|due| due < now))
+                })
+                .filter(|task| {
+                    !query.blocked || (!task.is_done() && !self.open_dependencies(task).is_empty())
+                })
+                .filter(|task| snoozed_decided || task.is_snoozed(now) == query.snoozed)
+                .filter(|task| query.upcoming || later_decided || !task.is_scheduled_later(now))
+                .filter(|task| {
+                    query
+                        .stale_before
+                        .is_none_or(|cutoff| task.is_stale(cutoff))
+                })
+                .filter(|task| !query.waiting || task.is_waiting())
+                .filter(|task| {
+                    query.filter.as_ref().is_none_or(|filter| {
+                        filter.matches(task, self, query.urgency.as_ref(), now)
+                    })
+                })
+                .collect();
+
+        let order = |left: &Task, right: &Task| match &query.urgency {
+            Some(urgency) => urgency.listing_order(left, right, now),
+            None => left.listing_order(right),
+        };
+        match query.sort {
+            SortBy::Day => selected.sort_by(|left, right| {
+                right
+                    .created
+                    .date_naive()
+                    .cmp(&left.created.date_naive())
+                    .then_with(|| order(left, right))
+            }),
+            SortBy::Due => selected.sort_by(|left, right| {
+                left.due
+                    .is_none()
+                    .cmp(&right.due.is_none())
+                    .then(left.due.cmp(&right.due))
+                    .then_with(|| order(left, right))
+            }),
+            SortBy::Priority => selected.sort_by(|left, right| order(left, right)),
+            SortBy::Created => selected.sort_by(|left, right| {
+                right
+                    .created
+                    .cmp(&left.created)
+                    .then_with(|| order(left, right))
+            }),
+        }
+
+        if let Some(limit) = query.limit {
+            selected.truncate(limit);
+        }
+        selected
+    }
+
+    pub fn remove(&mut self, task_id: Uuid) {
+        self.tasks.remove(&task_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use chrono::TimeZone;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn add_and_remove_task() {
+        let task = Task::new(String::from("test task"), 0);
+        let mut tasks = Tasks::default();
+        tasks.add(task.clone());
+        assert_eq!(&vec![task.clone()], tasks.get_tasks());
+        tasks.remove(task.id);
+        assert_eq!(&Vec::<Task>::new(), tasks.get_tasks());
+    }
+
+    #[test]
+    fn ties_break_the_same_way_and_pages_split_listings() {
+        let now = Utc::now();
+        let mut tasks = Tasks::default();
+        for content in ["a", "b", "c", "d"] {
+            let mut task = Task::new(String::from(content), 2);
+            task.created = now;
+            tasks.add(task);
+        }
+        let mut urgent = Task::new(String::from("urgent"), 2);
+        urgent.created = now;
+        urgent.due = Some(now + chrono::Duration::hours(1));
+        tasks.add(urgent);
+
+        let mut ids: Vec<Uuid> = tasks.get_tasks().iter().map(|task| task.id).collect();
+        ids.sort();
+        for sort in [SortBy::Created, SortBy::Day, SortBy::Priority] {
+            let query = Query {
+                sort,
+                ..Query::default()
+            };
+            let listed: Vec<Uuid> = tasks
+                .query(&query, now)
+                .iter()
+                .map(|task| task.id)
+                .collect();
+            assert_eq!(tasks.get_task(&listed[0]).unwrap().content, "urgent");
+            let mut rest = listed[1..].to_vec();
+            rest.sort();
+            assert_eq!(listed[1..], rest[..]);
+        }
+
+        let page = Page { number: 2, size: 2 };
+        assert_eq!((1..=5).paginate(Some(page)).collect::<Vec<_>>(), [3, 4]);
+        assert_eq!((1..=5).paginate(None).count(), 5);
+        assert_eq!(page.count(5), 3);
+        assert!(page.has_more(5) && !page.has_more(4));
+        assert_eq!(page.count(0), 1);
+    }
+
+    #[test]
+    fn query_filters_sorts_and_limits() {
+        let now = Utc::now();
+        let hour = chrono::Duration::hours(1);
+        let overdue = Task::new_date(
+            String::from("overdue"),
+            1,
+            Some(now - hour),
+            TaskType::Deadline,
+            None,
+        );
+        let soon = Task::new_date(
+            String::from("soon"),
+            5,
+            Some(now + hour),
+            TaskType::Deadline,
+            None,
+        );
+        let mut blocked = Task::new(String::from("blocked"), 9);
+        blocked.add_dependency(&soon.id);
+        let mut done = Task::new(String::from("done"), 9);
+        done.complete();
+
+        let mut tasks = Tasks::default();
+        for task in [&overdue, &soon, &blocked, &done] {
+            tasks.add(task.clone());
+        }
+        let names = |query: &Query| -> Vec<String> {
+            tasks
+                .query(query, now)
+                .iter()
+                .map(|task| task.content.clone())
+                .collect()
+        };
+
+        let by_due = Query {
+            sort: SortBy::Due,
+            ..Query::default()
+        };
+        assert_eq!(names(&by_due), ["overdue", "soon", "blocked"]);
+        let by_priority = Query {
+            sort: SortBy::Priority,
+            include_done: true,
+            limit: Some(2),
+            ..Query::default()
+        };
+        assert_eq!(names(&by_priority).len(), 2);
+        assert!(!names(&by_priority).contains(&String::from("soon")));
+
+        let overdue_only = Query {
+            overdue: true,
+            ..Query::default()
+        };
+        assert_eq!(names(&overdue_only), ["overdue"]);
+        let blocked_only = Query {
+            blocked: true,
+            ..Query::default()
+        };
+        assert_eq!(names(&blocked_only), ["blocked"]);
+        let window = Query {
+            due_after: Some(now),
+            priority_min: Some(Priority::from(2)),
+            ..Query::default()
+        };
+        assert_eq!(names(&window), ["soon"]);
+        let before = Query {
+            due_before: Some(now),
+            include_done: true,
+            ..Query::default()
+        };
+        assert_eq!(names(&before), ["overdue"]);
+    }
+
+    #[test]
+    fn contexts_narrow_a_query() {
+        let now = Utc::now();
+        let mut home = Task::new(String::from("home"), 0);
+        home.contexts = contexts::from_args(vec!["@home"]);
+        let mut errands = Task::new(String::from("errands"), 0);
+        errands.contexts = contexts::from_args(vec!["errands", "@phone"]);
+        let mut tasks = Tasks::default();
+        tasks.add(home);
+        tasks.add(errands);
+        tasks.add(Task::new(String::from("anywhere"), 0));
+
+        let names = |context: &str| -> Vec<String> {
+            let query = Query {
+                context: Some(String::from(context)),
+                sort: SortBy::Priority,
+                ..Query::default()
+            };
+            let mut names: Vec<String> = tasks
+                .query(&query, now)
+                .iter()
+                .map(|task| task.content.clone())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names("home"), ["anywhere", "home"]);
+        assert_eq!(names("phone"), ["anywhere", "errands"]);
+        assert_eq!(names("office"), ["anywhere"]);
+    }
+
+    #[test]
+    fn scheduled_tasks_wait_for_their_start() {
+        let now = Utc::now();
+        let day = chrono::Duration::days(1);
+        let mut later = Task::new(String::from("later"), 0);
+        later.scheduled = Some(now + day);
+        let mut started = Task::new(String::from("started"), 0);
+        started.scheduled = Some(now - day);
+        let mut tasks = Tasks::default();
+        tasks.add(later);
+        tasks.add(started);
+
+        let names = |query: &Query| -> Vec<String> {
+            let mut names: Vec<String> = tasks
+                .query(query, now)
+                .iter()
+                .map(|task| task.content.clone())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&Query::default()), ["started"]);
+        let upcoming = Query {
+            upcoming: true,
+            ..Query::default()
+        };
+        assert_eq!(names(&upcoming), ["later", "started"]);
+    }
+
+    #[test]
+    fn snoozed_tasks_stay_hidden_until_due() {
+        let now = Utc::now();
+        let hour = chrono::Duration::hours(1);
+        let mut snoozed = Task::new(String::from("snoozed"), 0);
+        snoozed.hidden_until = Some(now + hour);
+        let mut woken = Task::new(String::from("woken"), 0);
+        woken.hidden_until = Some(now - hour);
+        let mut tasks = Tasks::default();
+        tasks.add(snoozed);
+        tasks.add(woken);
+
+        let names = |query: &Query| -> Vec<String> {
+            tasks
+                .query(query, now)
+                .iter()
+                .map(|task| task.content.clone())
+                .collect()
+        };
+        assert_eq!(names(&Query::default()), ["woken"]);
+        let snoozed_only = Query {
+            snoozed: true,
+            ..Query::default()
+        };
+        assert_eq!(names(&snoozed_only), ["snoozed"]);
+    }
+
+    #[test]
+    fn tracks_sessions() {
+        let start = Utc::now() - Duration::hours(3);
+        let mut task = Task::new(String::from("tracked"), 0);
+        assert!(task.start(start));
+        assert!(!task.start(start));
+        assert!(task.is_running());
+        assert_eq!(
+            task.stop(start + Duration::hours(1)),
+            Some(Duration::hours(1))
+        );
+        assert_eq!(task.stop(start), None);
+
+        task.start(start + Duration::hours(2));
+        let now = start + Duration::minutes(150);
+        assert_eq!(task.tracked(now), Duration::minutes(90));
+        // Only the part of a session inside the window counts
+        assert_eq!(
+            task.tracked_between(
+                start + Duration::minutes(30),
+                start + Duration::hours(2),
+                now
+            ),
+            Duration::minutes(30)
+        );
+    }
+
+    #[test]
+    fn checklist_progress() {
+        let mut task = Task::new(String::from("pack"), 0);
+        assert_eq!(task.checklist_progress(), None);
+        for (text, done) in [("socks", true), ("charger", false)] {
+            task.checklist.push(ChecklistItem {
+                text: String::from(text),
+                done,
+            });
+        }
+        assert_eq!(task.checklist_progress(), Some((1, 2)));
+        let line = Formatter::new(&[]).task(&task, &[]);
+        assert!(line.contains("[1/2]"));
+    }
+
+    #[test]
+    fn formats_relative_due() {
+        let now = Utc::now();
+        let due = |offset| {
+            Task::new_date(
+                String::from("t"),
+                0,
+                Some(now + offset),
+                TaskType::Deadline,
+                None,
+            )
+        };
+        let formatter = Formatter {
+            priority_map: &[],
+            now,
+            due_soon: Duration::days(1),
+            streaks: None,
+            stale_after: None,
+            urgency: None,
+        };
+
+        assert_eq!(
+            formatter.due(&due(Duration::hours(-3))),
+            Some("overdue 3h".red().to_string())
+        );
+        assert_eq!(
+            formatter.due(&due(Duration::hours(5))),
+            Some("due in 5h".yellow().to_string())
+        );
+        assert_eq!(
+            formatter.due(&due(Duration::days(2))),
+            Some(String::from("due in 2d"))
+        );
+        let mut done = due(Duration::hours(-3));
+        done.complete();
+        assert_eq!(formatter.due(&done), None);
+        assert_eq!(formatter.due(&Task::new(String::from("t"), 0)), None);
+    }
+
+    #[test]
+    fn untouched_tasks_go_stale() {
+        let now = Utc::now();
+        let mut task = Task::new(String::from("file the taxes"), 0);
+        task.created = now - Duration::days(40);
+        let cutoff = now - Duration::days(30);
+        assert!(task.is_stale(cutoff));
+        let formatter = Formatter {
+            stale_after: Some(Duration::days(30)),
+            ..Formatter::new(&[])
+        };
+        assert!(formatter.task(&task, &[]).contains("untouched 40d"));
+
+        task.comments.push(Comment {
+            author: String::from("me"),
+            created: now - Duration::days(2),
+            body: String::from("found the forms"),
+        });
+        assert_eq!(task.touched(), now - Duration::days(2));
+        assert!(!task.is_stale(cutoff));
+        task.comments.clear();
+        task.modified = Some(now - Duration::days(1));
+        assert!(!task.is_stale(cutoff));
+
+        let mut tasks = Tasks::default();
+        task.modified = None;
+        tasks.add(task);
+        tasks.add(Task::new(String::from("fresh"), 0));
+        let query = Query {
+            stale_before: Some(cutoff),
+            ..Query::default()
+        };
+        assert_eq!(tasks.query(&query, now).len(), 1);
+    }
+
+    #[test]
+    fn find_by_id_prefix() {
+        let mut tasks = Tasks::default();
+        let mut first = Task::new(String::from("first"), 0);
+        first.id = Uuid::parse_str("0a1b0000-0000-4000-8000-000000000000").unwrap();
+        let mut second = Task::new(String::from("second"), 0);
+        second.id = Uuid::parse_str("0a2c0000-0000-4000-8000-000000000000").unwrap();
+        tasks.add(first.clone());
+        tasks.add(second.clone());
+
+        assert_eq!(tasks.find_by_prefix("0A1"), vec![&first]);
+        assert_eq!(tasks.find_by_prefix("0a").len(), 2);
+        assert!(tasks.find_by_prefix("ff").is_empty());
+    }
+
+    #[test]
+    fn dependency_blocking_and_cycles() {
+        let first = Task::new(String::from("first"), 0);
+        let mut second = Task::new(String::from("second"), 0);
+        second.add_dependency(&first.id);
+        let mut third = Task::new(String::from("third"), 0);
+        third.add_dependency(&second.id);
+
+        let mut fourth = Task::new(String::from("fourth"), 0);
+        fourth.add_dependency(&first.id);
+        fourth.add_dependency(&third.id);
+
+        let mut tasks = Tasks::default();
+        tasks.add(first.clone());
+        tasks.add(second.clone());
+        tasks.add(third.clone());
+        tasks.add(fourth.clone());
+
+        assert_eq!(tasks.open_dependencies(&second), vec![&first]);
+        let mut dependents = vec![second.id, fourth.id];
+        dependents.sort();
+        let ids = |found: Vec<&Task>| found.iter().map(|task| task.id).collect::<Vec<_>>();
+        assert_eq!(ids(tasks.dependents(&first.id)), dependents);
+        assert!(tasks.open_dependencies(&first).is_empty());
+        assert!(tasks.would_cycle(&first.id, &third.id));
+        assert!(tasks.would_cycle(&first.id, &first.id));
+        assert!(!tasks.would_cycle(&third.id, &first.id));
+
+        tasks.get_task_mut(&first.id).unwrap().complete();
+        // The fourth still waits on the third
+        assert_eq!(ids(tasks.unblocked_by(&[first.id])), vec![second.id]);
+        let second = tasks.get_task(&second.id).unwrap();
+        assert!(tasks.open_dependencies(second).is_empty());
+    }
+
+    #[test]
+    fn tree_order_nests_children() {
+        let root = Task::new(String::from("project"), 0);
+        let mut child = Task::new(String::from("step"), 0);
+        child.parent = Some(root.id);
+        let mut grandchild = Task::new(String::from("detail"), 0);
+        grandchild.parent = Some(child.id);
+        let other = Task::new(String::from("other"), 0);
+
+        let list = vec![&grandchild, &other, &child, &root];
+        let ordered: Vec<(usize, &str)> = tree_order(&list)
+            .into_iter()
+            .map(|(depth, task)| (depth, task.content.as_str()))
+            .collect();
+        assert_eq!(
+            ordered,
+            vec![(0, "other"), (0, "project"), (1, "step"), (2, "detail")]
+        );
+
+        // Without its parent listed a child is shown at the top level
+        let ordered = tree_order(&[&child, &other]);
+        assert_eq!(ordered[0], (0, &child));
+    }
+
+    #[test]
+    fn duplicates_leave_the_history_behind() {
+        let now = Utc.with_ymd_and_hms(2019, 10, 4, 9, 0, 0).unwrap();
+        let mut task = Task::new_date(
+            String::from("file taxes"),
+            6,
+            Some(now),
+            TaskType::Repeated,
+            Some(RepeatType::Daily),
+        );
+        task.scheduled = Some(now - Duration::days(2));
+        task.estimate = Some(3600);
+        task.tags.insert(String::from("admin"));
+        task.checklist.push(ChecklistItem {
+            text: String::from("receipts"),
+            done: true,
+        });
+        task.start(now - Duration::hours(1));
+        task.complete();
+
+        let copy = task.duplicate(None);
+        assert_ne!(copy.id, task.id);
+        assert_eq!(
+            (copy.priority, copy.due, copy.estimate),
+            (task.priority, task.due, Some(3600))
+        );
+        assert_eq!(copy.tags, task.tags);
+        assert!(!copy.is_done() && copy.sessions.is_empty() && copy.repeat.is_none());
+        assert!(matches!(copy.task_type, Some(TaskType::Deadline)));
+        assert!(!copy.checklist[0].done);
+
+        let later = task.duplicate(Some(now + Duration::days(7)));
+        assert_eq!(later.due, Some(now + Duration::days(7)));
+        assert_eq!(later.scheduled, Some(now + Duration::days(5)));
+    }
+
+    #[test]
+    fn complete_task() {
+        let task = Task::new(String::from("test task"), 0);
+        let mut tasks = Tasks::default();
+        tasks.add(task.clone());
+        assert!(!task.is_done());
+
+        let mut stored = tasks.get_task_mut(&task.id).unwrap();
+        stored.complete();
+        let first = stored.completed;
+        assert!(first.is_some());
+
+        // Completing twice keeps the original timestamp
+        stored.complete();
+        assert_eq!(first, stored.completed);
+    }
+
+    #[test]
+    fn to_from_disk() {
+        let mut task = Task::new(String::from("test task"), 0);
+        let subtask = Task::new_date(
+            String::from("subtask"),
+            0,
+            Some(Utc::now()),
+            TaskType::Repeated,
+            Some(RepeatType::Weekly),
+        );
+
+        task.add_dependency(&subtask.id);
+
+        let mut db = Database::default();
+        {
+            let tasks = &mut db.tasks;
+            tasks.add(task.clone());
+            tasks.add(subtask.clone());
+        }
+
+        let file = NamedTempFile::new().unwrap();
+        db.to_disk(file.path()).unwrap();
+
+        let from_disk_db = Database::from_disk(file.path()).unwrap();
+        assert_eq!(db.tasks, from_disk_db.tasks);
+
+        for disk_task in from_disk_db.tasks.get_tasks().iter() {
+            match disk_task.id {
+                id if id == task.id => {
+                    assert_eq!(&task, disk_task);
+                }
+                id if id == subtask.id => assert_eq!(&subtask, disk_task),
+                _ => {
+                    unreachable!();
+                }
+            }
+        }
+    }
+}
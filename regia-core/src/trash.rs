@@ -0,0 +1,124 @@
+//! Removed tasks and notes, kept for a while so a deletion can be taken
+//! back with `regia trash restore`.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::note::{Note, Notes};
+use crate::todo::{Task, Tasks};
+
+/// An item together with when it was deleted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Trashed<T> {
+    pub item: T,
+    pub deleted: DateTime<Utc>,
+}
+
+/// The removed tasks and notes of one project, oldest deletion first.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct Trash {
+    pub tasks: Vec<Trashed<Task>>,
+    pub notes: Vec<Trashed<Note>>,
+}
+
+impl Trash {
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty() && self.notes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len() + self.notes.len()
+    }
+
+    /// Moves the task `id` out of `tasks` into the trash.
+    pub fn delete_task(&mut self, tasks: &mut Tasks, id: Uuid, now: DateTime<Utc>) -> bool {
+        match tasks.get_task(&id).cloned() {
+            Some(item) => {
+                tasks.remove(id);
+                self.tasks.push(Trashed { item, deleted: now });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the note `id` out of `notes` into the trash.
+    pub fn delete_note(&mut self, notes: &mut Notes, id: Uuid, now: DateTime<Utc>) -> bool {
+        match notes.get_note(&id).cloned() {
+            Some(item) => {
+                notes.remove(id);
+                self.notes.push(Trashed { item, deleted: now });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Puts the trashed task or note `id` back. Fails when nothing in the
+    /// trash has that id, or a live item already does.
+    pub fn restore(
+        &mut self,
+        id: Uuid,
+        tasks: &mut Tasks,
+        notes: &mut Notes,
+    ) -> Result<(), String> {
+        if let Some(index) = self.tasks.iter().position(|entry| entry.item.id == id) {
+            if tasks.get_task(&id).is_some() {
+                return Err(format!("a task with id {} already exists", id));
+            }
+            tasks.add(self.tasks.remove(index).item);
+            return Ok(());
+        }
+        if let Some(index) = self.notes.iter().position(|entry| entry.item.id == id) {
+            if notes.get_note(&id).is_some() {
+                return Err(format!("a note with id {} already exists", id));
+            }
+            notes.add(self.notes.remove(index).item);
+            return Ok(());
+        }
+        Err(format!("nothing in the trash with id {}", id))
+    }
+
+    /// Drops everything deleted before `cutoff`, returning how many items
+    /// went.
+    pub fn purge(&mut self, cutoff: DateTime<Utc>) -> usize {
+        let before = self.len();
+        self.tasks.retain(|entry| entry.deleted >= cutoff);
+        self.notes.retain(|entry| entry.deleted >= cutoff);
+        before - self.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn delete_restore_and_purge() {
+        let now = Utc::now();
+        let mut tasks = Tasks::default();
+        let mut notes = Notes::default();
+        let task = Task::new(String::from("old"), 0);
+        let task_id = task.id;
+        tasks.add(task);
+        let note = Note::new("kept");
+        let note_id = note.id;
+        notes.add(note);
+
+        let mut trash = Trash::default();
+        assert!(trash.delete_task(&mut tasks, task_id, now - Duration::days(40)));
+        assert!(trash.delete_note(&mut notes, note_id, now));
+        assert!(!trash.delete_note(&mut notes, note_id, now));
+        assert!(tasks.get_task(&task_id).is_none());
+        assert_eq!(trash.len(), 2);
+
+        assert!(trash.restore(note_id, &mut tasks, &mut notes).is_ok());
+        assert!(notes.get_note(&note_id).is_some());
+        assert!(trash.restore(note_id, &mut tasks, &mut notes).is_err());
+
+        assert_eq!(trash.purge(now - Duration::days(30)), 1);
+        assert!(trash.is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+//! Urgency: the priority a task has in effect once its due date draws near
+//! or it has been open a while, as the `urgency` config section asks. A
+//! rule raises the priority by up to its `raise` points over a window,
+//! following its curve: for the due date the window leads up to it, and an
+//! overdue task gets the whole raise; for age it runs from the task's
+//! creation. The stored priority never changes, so turning the rules off
+//! puts everything back as it was.
+
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::priority::Priority;
+use crate::todo::Task;
+
+/// How steeply the exponential and logarithmic curves bend: halfway
+/// through the window one has a fifth of the raise, the other over three
+/// quarters.
+const STEEPNESS: f64 = 16.0;
+
+/// How a raise builds up over its window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Curve {
+    /// Evenly
+    Linear,
+    /// Slowly at first, then quickly toward the end
+    Exponential,
+    /// Quickly at first, then leveling off
+    Logarithmic,
+}
+
+impl Curve {
+    pub const NAMES: [&'static str; 3] = ["linear", "exponential", "logarithmic"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            "linear" => Some(Curve::Linear),
+            "exponential" => Some(Curve::Exponential),
+            "logarithmic" => Some(Curve::Logarithmic),
+            _ => None,
+        }
+    }
+
+    /// How much of the raise is due `progress` of the way through the
+    /// window, both from 0 to 1.
+    pub fn at(self, progress: f64) -> f64 {
+        let progress = progress.clamp(0.0, 1.0);
+        match self {
+            Curve::Linear => progress,
+            Curve::Exponential => (STEEPNESS.powf(progress) - 1.0) / (STEEPNESS - 1.0),
+            Curve::Logarithmic => (1.0 + (STEEPNESS - 1.0) * progress).log(STEEPNESS),
+        }
+    }
+}
+
+/// One way a task's priority rises.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rule {
+    pub curve: Curve,
+    pub window: Duration,
+    /// The most points it adds to the priority's weight
+    pub raise: u32,
+}
+
+impl Rule {
+    fn points(&self, progress: f64) -> f64 {
+        self.raise as f64 * self.curve.at(progress)
+    }
+}
+
+/// The rules in effect; with neither, priorities stay as set.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Urgency {
+    /// Raises the priority as the due date approaches
+    pub due: Option<Rule>,
+    /// Raises the priority as the task ages
+    pub age: Option<Rule>,
+}
+
+/// How much of `window` `span` covers, from 0 to 1.
+fn progress(window: Duration, span: Duration) -> f64 {
+    if window <= Duration::zero() {
+        return 1.0;
+    }
+    (span.num_seconds() as f64 / window.num_seconds() as f64).clamp(0.0, 1.0)
+}
+
+impl Urgency {
+    /// The points the rules add to an open task's priority as of `now`.
+    pub fn raise(&self, task: &Task, now: DateTime<Utc>) -> u32 {
+        if task.is_done() {
+            return 0;
+        }
+        let due = match (self.due, task.due) {
+            (Some(rule), Some(due)) => {
+                let left = (due - now).max(Duration::zero());
+                rule.points(1.0 - progress(rule.window, left))
+            }
+            _ => 0.0,
+        };
+        let age = match self.age {
+            Some(rule) => rule.points(progress(rule.window, now - task.created)),
+            None => 0.0,
+        };
+        (due + age).floor() as u32
+    }
+
+    /// The priority `task` has in effect as of `now`.
+    pub fn priority(&self, task: &Task, now: DateTime<Utc>) -> Priority {
+        match self.raise(task, now) {
+            0 => task.priority,
+            raise => Priority::from(task.priority.weight() + raise),
+        }
+    }
+
+    /// [`Task::listing_order`] with the priorities in effect as of `now`.
+    pub fn listing_order(&self, left: &Task, right: &Task, now: DateTime<Utc>) -> Ordering {
+        self.priority(right, now)
+            .cmp(&self.priority(left, now))
+            .then_with(|| left.listing_order(right))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves_run_from_none_to_all() {
+        for name in Curve::NAMES {
+            let curve = Curve::parse(name).unwrap();
+            assert_eq!(curve.at(-1.0), 0.0, "{}", name);
+            assert!((curve.at(1.0) - 1.0).abs() < 1e-9, "{}", name);
+        }
+        assert_eq!(Curve::Linear.at(0.5), 0.5);
+        assert!(Curve::Exponential.at(0.5) < 0.25);
+        assert!(Curve::Logarithmic.at(0.5) > 0.75);
+        assert_eq!(Curve::parse("sigmoid"), None);
+    }
+
+    #[test]
+    fn raises_priority_near_due_and_with_age() {
+        let now = Utc::now();
+        let urgency = Urgency {
+            due: Some(Rule {
+                curve: Curve::Linear,
+                window: Duration::days(10),
+                raise: 6,
+            }),
+            age: Some(Rule {
+                curve: Curve::Linear,
+                window: Duration::days(30),
+                raise: 2,
+            }),
+        };
+        let mut task = Task::new(String::from("file taxes"), Priority::Low);
+        task.created = now;
+        assert_eq!(urgency.priority(&task, now), Priority::Low);
+
+        task.due = Some(now + Duration::days(5));
+        assert_eq!(urgency.raise(&task, now), 3);
+        task.due = Some(now - Duration::days(1));
+        assert_eq!(urgency.priority(&task, now), Priority::Other(7));
+        task.created = now - Duration::days(90);
+        assert_eq!(urgency.priority(&task, now), Priority::Critical);
+
+        let calm = Task::new(String::from("tidy desk"), Priority::High);
+        assert_eq!(
+            urgency.listing_order(&task, &calm, now),
+            Ordering::Less,
+            "the overdue task overtakes"
+        );
+        assert_eq!(calm.listing_order(&task), Ordering::Less);
+
+        task.complete();
+        assert_eq!(urgency.priority(&task, now), Priority::Low);
+    }
+}
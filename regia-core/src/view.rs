@@ -0,0 +1,166 @@
+//! Saved `task ls` filters, run again with `regia view NAME`.
+
+use serde::{Deserialize, Serialize};
+
+/// The filter flags of `task ls`, kept as they were typed so that dates
+/// like `friday` are read again each time the view is used.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct View {
+    pub tags: Vec<String>,
+    pub due_before: Option<String>,
+    pub due_after: Option<String>,
+    pub priority_min: Option<String>,
+    pub all: bool,
+    pub overdue: bool,
+    pub blocked: bool,
+    pub snoozed: bool,
+    pub sort: Option<String>,
+    pub limit: Option<String>,
+    #[serde(default)]
+    pub upcoming: bool,
+    /// Kept without its `@`
+    #[serde(default)]
+    pub context: Option<String>,
+    /// Ignore the sticky context set with `regia context set`
+    #[serde(default)]
+    pub any_context: bool,
+    /// Only tasks untouched for `tasks.stale_after`
+    #[serde(default)]
+    pub stale: bool,
+    /// Which page of the list to show, see [`crate::todo::Page`]
+    #[serde(default)]
+    pub page: Option<String>,
+    #[serde(default)]
+    pub per_page: Option<String>,
+    /// Only tasks waiting on someone, see `task wait`
+    #[serde(default)]
+    pub waiting: bool,
+    /// Only tasks this picks, in the language of [`crate::query`]
+    #[serde(default)]
+    pub query: Option<String>,
+}
+
+impl View {
+    /// This view narrowed by the flags in `extra`: tags and switches
+    /// add up, and any value `extra` sets replaces this one's.
+    pub fn with(&self, extra: &View) -> View {
+        let mut tags = self.tags.clone();
+        for tag in extra.tags.iter() {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        let or = |ours: &Option<String>, theirs: &Option<String>| theirs.clone().or(ours.clone());
+        View {
+            tags,
+            due_before: or(&self.due_before, &extra.due_before),
+            due_after: or(&self.due_after, &extra.due_after),
+            priority_min: or(&self.priority_min, &extra.priority_min),
+            all: self.all || extra.all,
+            overdue: self.overdue || extra.overdue,
+            blocked: self.blocked || extra.blocked,
+            snoozed: self.snoozed || extra.snoozed,
+            sort: or(&self.sort, &extra.sort),
+            limit: or(&self.limit, &extra.limit),
+            upcoming: self.upcoming || extra.upcoming,
+            context: or(&self.context, &extra.context),
+            any_context: self.any_context || extra.any_context,
+            stale: self.stale || extra.stale,
+            page: or(&self.page, &extra.page),
+            per_page: or(&self.per_page, &extra.per_page),
+            waiting: self.waiting || extra.waiting,
+            query: or(&self.query, &extra.query),
+        }
+    }
+
+    /// The flags that recreate the view, for `view ls`.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for tag in self.tags.iter() {
+            args.push(format!("--tag {}", tag));
+        }
+        if let Some(context) = &self.context {
+            args.push(format!("--context @{}", context));
+        }
+        let values = [
+            ("--due-before", &self.due_before),
+            ("--due-after", &self.due_after),
+            ("--priority-min", &self.priority_min),
+            ("--sort", &self.sort),
+            ("--limit", &self.limit),
+            ("--page", &self.page),
+            ("--per-page", &self.per_page),
+        ];
+        for (flag, value) in values.iter() {
+            if let Some(value) = value {
+                args.push(format!("{} {}", flag, value));
+            }
+        }
+        let switches = [
+            ("--all", self.all),
+            ("--overdue", self.overdue),
+            ("--blocked", self.blocked),
+            ("--snoozed", self.snoozed),
+            ("--upcoming", self.upcoming),
+            ("--any-context", self.any_context),
+            ("--stale", self.stale),
+            ("--waiting", self.waiting),
+        ];
+        for (flag, set) in switches.iter() {
+            if *set {
+                args.push(flag.to_string());
+            }
+        }
+        if let Some(query) = &self.query {
+            args.push(format!("--where '{}'", query.replace('\'', "'\\''")));
+        }
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_narrow_a_view() {
+        let saved = View {
+            tags: vec![String::from("work")],
+            priority_min: Some(String::from("3")),
+            due_before: Some(String::from("friday")),
+            ..View::default()
+        };
+        let extra = View {
+            tags: vec![String::from("work"), String::from("home")],
+            priority_min: Some(String::from("high")),
+            blocked: true,
+            ..View::default()
+        };
+        let view = saved.with(&extra);
+        assert_eq!(view.tags, ["work", "home"]);
+        assert_eq!(view.priority_min.as_deref(), Some("high"));
+        assert_eq!(view.due_before.as_deref(), Some("friday"));
+        assert!(view.blocked && !view.overdue);
+        assert_eq!(
+            saved.args(),
+            ["--tag work", "--due-before friday", "--priority-min 3"]
+        );
+
+        let anywhere = View {
+            any_context: true,
+            ..View::default()
+        };
+        let home = View {
+            context: Some(String::from("home")),
+            ..View::default()
+        };
+        assert_eq!(anywhere.with(&home).context.as_deref(), Some("home"));
+        assert_eq!(home.args(), ["--context @home"]);
+
+        let picked = View {
+            query: Some(String::from("content ~ don't")),
+            ..View::default()
+        };
+        assert_eq!(picked.args(), [r#"--where 'content ~ don'\''t'"#]);
+    }
+}
@@ -0,0 +1,396 @@
+//! The write-ahead log beside a database: what each save changed, appended
+//! as a small delta instead of rewriting the whole file.
+//!
+//! Every delta sets an item to its new value or removes it, so replaying
+//! a log over a database that already has some of it leaves the same
+//! result. That makes compaction safe at any point: write the database
+//! whole, then drop the log.
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind as IOErrorKind, Read, Result as IOResult, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{Database, Project};
+use crate::error::{self, RegiaError};
+use crate::history::{self, Event};
+use crate::inbox::Item;
+use crate::index::same;
+use crate::note::{Note, Notes};
+use crate::todo::{Task, Tasks};
+use crate::trash::Trash;
+use crate::view::View;
+
+/// Marks a log file, followed by the schema version its entries are in.
+const MAGIC: &[u8; 4] = b"RGWL";
+const HEADER_LEN: u64 = 8;
+
+/// The log lives next to the database, as `<db>.wal`.
+pub fn log_path(db_path: &Path) -> PathBuf {
+    crate::db::sibling_path(db_path, ".wal")
+}
+
+/// One change to a database at rest. `project` is `None` for the root.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) enum Delta {
+    /// A task added or changed, in the archive when `archived`
+    Task {
+        project: Option<String>,
+        archived: bool,
+        task: Box<Task>,
+    },
+    TaskGone {
+        project: Option<String>,
+        archived: bool,
+        id: Uuid,
+    },
+    /// A note added or changed
+    Note {
+        project: Option<String>,
+        note: Box<Note>,
+    },
+    NoteGone {
+        project: Option<String>,
+        id: Uuid,
+    },
+    /// The trash as a whole
+    Trash {
+        project: Option<String>,
+        trash: Box<Trash>,
+    },
+    /// A project created, with everything in it at the time
+    Project {
+        name: String,
+        project: Box<Project>,
+    },
+    ProjectGone {
+        name: String,
+    },
+    /// The project chosen with `regia project switch`
+    Current(Option<String>),
+    Views(BTreeMap<String, View>),
+    /// Events added to the history, which only ever grows
+    History(Vec<Event>),
+    /// The inbox as a whole
+    Inbox(Vec<Item>),
+    Reviewed(Option<DateTime<Utc>>),
+}
+
+/// Walks two lists sorted by id for the items of `after` that are new or
+/// differ, and the ids only in `before`.
+fn compare<'a, T: Serialize>(
+    before: &[T],
+    after: &'a [T],
+    id: fn(&T) -> Uuid,
+) -> (Vec<&'a T>, Vec<Uuid>) {
+    let mut changed = Vec::new();
+    let mut gone = Vec::new();
+    let mut old = before.iter().peekable();
+    for item in after {
+        while let Some(previous) = old.next_if(|previous| id(previous) < id(item)) {
+            gone.push(id(previous));
+        }
+        match old.next_if(|previous| id(previous) == id(item)) {
+            Some(previous) if same(previous, item) => {}
+            _ => changed.push(item),
+        }
+    }
+    gone.extend(old.map(id));
+    (changed, gone)
+}
+
+/// The lists of one project, root or named.
+struct Space<'a> {
+    tasks: &'a Tasks,
+    notes: &'a Notes,
+    archive: &'a Tasks,
+    trash: &'a Trash,
+}
+
+impl<'a> Space<'a> {
+    fn root(db: &'a Database) -> Self {
+        Space {
+            tasks: &db.tasks,
+            notes: &db.notes,
+            archive: &db.archive,
+            trash: &db.trash,
+        }
+    }
+
+    fn of(project: &'a Project) -> Self {
+        Space {
+            tasks: &project.tasks,
+            notes: &project.notes,
+            archive: &project.archive,
+            trash: &project.trash,
+        }
+    }
+}
+
+fn diff_space(project: Option<&String>, before: Space, after: Space, deltas: &mut Vec<Delta>) {
+    for (archived, before, after) in [
+        (false, before.tasks, after.tasks),
+        (true, before.archive, after.archive),
+    ] {
+        let (changed, gone) = compare(before.get_tasks(), after.get_tasks(), |task| task.id);
+        deltas.extend(changed.into_iter().map(|task| Delta::Task {
+            project: project.cloned(),
+            archived,
+            task: Box::new(task.clone()),
+        }));
+        deltas.extend(gone.into_iter().map(|id| Delta::TaskGone {
+            project: project.cloned(),
+            archived,
+            id,
+        }));
+    }
+    let (changed, gone) = compare(before.notes.get_notes(), after.notes.get_notes(), |note| {
+        note.id
+    });
+    deltas.extend(changed.into_iter().map(|note| Delta::Note {
+        project: project.cloned(),
+        note: Box::new(note.clone()),
+    }));
+    deltas.extend(gone.into_iter().map(|id| Delta::NoteGone {
+        project: project.cloned(),
+        id,
+    }));
+    if !same(before.trash, after.trash) {
+        deltas.push(Delta::Trash {
+            project: project.cloned(),
+            trash: Box::new(after.trash.clone()),
+        });
+    }
+}
+
+/// What turns `before` into `after`, both at rest.
+pub(crate) fn diff(before: &Database, after: &Database) -> Vec<Delta> {
+    let mut deltas = Vec::new();
+    diff_space(None, Space::root(before), Space::root(after), &mut deltas);
+    for (name, project) in after.projects.iter() {
+        match before.projects.get(name) {
+            Some(old) => diff_space(Some(name), Space::of(old), Space::of(project), &mut deltas),
+            None => deltas.push(Delta::Project {
+                name: name.clone(),
+                project: Box::new(project.clone()),
+            }),
+        }
+    }
+    for name in before.projects.keys() {
+        if !after.projects.contains_key(name) {
+            deltas.push(Delta::ProjectGone { name: name.clone() });
+        }
+    }
+    if before.current_project != after.current_project {
+        deltas.push(Delta::Current(after.current_project.clone()));
+    }
+    if before.views != after.views {
+        deltas.push(Delta::Views(after.views.clone()));
+    }
+    if before.history != after.history {
+        let mut added = before.history.clone();
+        history::merge(&mut added, after.history.clone());
+        deltas.push(Delta::History(added.split_off(before.history.len())));
+    }
+    if before.inbox != after.inbox {
+        deltas.push(Delta::Inbox(after.inbox.clone()));
+    }
+    if before.reviewed != after.reviewed {
+        deltas.push(Delta::Reviewed(after.reviewed));
+    }
+    deltas
+}
+
+fn project_mut<'a>(db: &'a mut Database, name: &str) -> error::Result<&'a mut Project> {
+    db.projects.get_mut(name).ok_or_else(|| {
+        RegiaError::corrupt(format!("Database log names a missing project: {}", name))
+    })
+}
+
+fn tasks_mut<'a>(
+    db: &'a mut Database,
+    project: &Option<String>,
+    archived: bool,
+) -> error::Result<&'a mut Tasks> {
+    Ok(match (project, archived) {
+        (None, false) => &mut db.tasks,
+        (None, true) => &mut db.archive,
+        (Some(name), false) => &mut project_mut(db, name)?.tasks,
+        (Some(name), true) => &mut project_mut(db, name)?.archive,
+    })
+}
+
+fn notes_mut<'a>(db: &'a mut Database, project: &Option<String>) -> error::Result<&'a mut Notes> {
+    Ok(match project {
+        None => &mut db.notes,
+        Some(name) => &mut project_mut(db, name)?.notes,
+    })
+}
+
+/// Replays `deltas` on `db`, which must be at rest.
+pub(crate) fn apply(db: &mut Database, deltas: Vec<Delta>) -> error::Result<()> {
+    for delta in deltas {
+        match delta {
+            Delta::Task {
+                project,
+                archived,
+                task,
+            } => tasks_mut(db, &project, archived)?.add(*task),
+            Delta::TaskGone {
+                project,
+                archived,
+                id,
+            } => tasks_mut(db, &project, archived)?.remove(id),
+            Delta::Note { project, note } => notes_mut(db, &project)?.add(*note),
+            Delta::NoteGone { project, id } => notes_mut(db, &project)?.remove(id),
+            Delta::Trash { project, trash } => match project {
+                None => db.trash = *trash,
+                Some(name) => project_mut(db, &name)?.trash = *trash,
+            },
+            Delta::Project { name, project } => {
+                db.projects.insert(name, *project);
+            }
+            Delta::ProjectGone { name } => {
+                db.projects.remove(&name);
+            }
+            Delta::Current(current) => db.current_project = current,
+            Delta::Views(views) => db.views = views,
+            Delta::History(events) => history::merge(&mut db.history, events),
+            Delta::Inbox(inbox) => db.inbox = inbox,
+            Delta::Reviewed(at) => db.reviewed = at,
+        }
+    }
+    Ok(())
+}
+
+/// The entries of a log, in order.
+pub(crate) struct Log {
+    pub(crate) entries: Vec<Vec<Delta>>,
+    /// The file's length when read
+    pub(crate) len: u64,
+    /// How much of the file holds whole entries. Anything after is a save
+    /// that was cut off, to be written over by the next one.
+    pub(crate) valid: u64,
+}
+
+/// Reads the log at `path`; a missing one is empty.
+pub(crate) fn read(path: &Path) -> error::Result<Log> {
+    let mut buf = Vec::new();
+    match File::open(path) {
+        Ok(mut file) => file.read_to_end(&mut buf)?,
+        Err(err) if err.kind() == IOErrorKind::NotFound => 0,
+        Err(err) => return Err(err.into()),
+    };
+    let mut log = Log {
+        entries: Vec::new(),
+        len: buf.len() as u64,
+        valid: 0,
+    };
+    if buf.len() < HEADER_LEN as usize {
+        return Ok(log);
+    }
+    let (header, mut rest) = buf.split_at(HEADER_LEN as usize);
+    if !header.starts_with(MAGIC) {
+        return Err(RegiaError::corrupt("Database log has a bad header"));
+    }
+    let version = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    if version > Database::VERSION {
+        return Err(RegiaError::corrupt(format!(
+            "Database log is schema version {}, but this regia only reads up to version {}; upgrade regia",
+            version,
+            Database::VERSION
+        )));
+    }
+    log.valid = HEADER_LEN;
+    while rest.len() >= 4 {
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let entry = match rest.get(4..4 + len) {
+            Some(entry) => entry,
+            None => break,
+        };
+        let deltas = Vec::<Delta>::deserialize(&mut rmp_serde::Deserializer::new(entry))
+            .map_err(|_| RegiaError::corrupt("Database log entry is unreadable"))?;
+        log.entries.push(deltas);
+        log.valid += 4 + len as u64;
+        rest = &rest[4 + len..];
+    }
+    Ok(log)
+}
+
+/// Encodes `deltas` as one log entry.
+pub(crate) fn encode(deltas: &[Delta]) -> error::Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    deltas
+        .serialize(&mut rmp_serde::Serializer::new(&mut payload))
+        .map_err(|_| RegiaError::storage("Serialization failed"))?;
+    let mut entry = Vec::with_capacity(4 + payload.len());
+    entry.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    entry.extend_from_slice(&payload);
+    Ok(entry)
+}
+
+/// Writes `entry` after the first `valid` bytes of the log at `path` and
+/// syncs it, returning the log's new length.
+pub(crate) fn append(path: &Path, valid: u64, entry: &[u8]) -> IOResult<u64> {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    // The log holds what the database does, so it's kept as private
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    file.set_len(valid)?;
+    let mut len = valid;
+    if valid == 0 {
+        file.write_all(MAGIC)?;
+        file.write_all(&Database::VERSION.to_be_bytes())?;
+        len = HEADER_LEN;
+    }
+    file.write_all(entry)?;
+    file.sync_data()?;
+    Ok(len + entry.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaying_a_diff_is_idempotent() {
+        let mut before = Database::default();
+        let kept = Task::new(String::from("kept"), 0);
+        let dropped = Task::new(String::from("dropped"), 0);
+        before.tasks.add(kept.clone());
+        before.tasks.add(dropped.clone());
+        before
+            .projects
+            .insert(String::from("old"), Project::named("old"));
+
+        let mut after = before.clone();
+        after.tasks.get_task_mut(&kept.id).unwrap().content = String::from("edited");
+        after.tasks.remove(dropped.id);
+        after.archive.add(dropped);
+        after.notes.add(Note::new("a note"));
+        after.projects.remove("old");
+        let mut work = Project::named("work");
+        work.tasks.add(Task::new(String::from("work task"), 0));
+        after.projects.insert(String::from("work"), work);
+        after.current_project = Some(String::from("work"));
+        after.reviewed = Some(Utc::now());
+
+        let deltas = diff(&before, &after);
+        assert_eq!(deltas.len(), 8);
+        assert!(diff(&after, &after).is_empty());
+
+        let mut replayed = before.clone();
+        apply(&mut replayed, deltas.clone()).unwrap();
+        assert!(same(&replayed, &after));
+
+        // Replaying over a database that already has the changes is harmless
+        apply(&mut replayed, deltas).unwrap();
+        assert!(same(&replayed, &after));
+    }
+}
@@ -0,0 +1,166 @@
+//! The statuses a task moves through on `regia board`, from
+//! `tasks.workflow`.
+
+use crate::error::{self, RegiaError};
+use crate::todo::Task;
+
+/// The workflow when `tasks.workflow` isn't set.
+pub const WORKFLOW: &str = "Backlog, Todo, In Progress, Review, Done";
+
+/// Statuses in order. A new task starts in the first one. The last one
+/// means done: moving a task there completes it, and a completed task is
+/// in it whatever status it was last given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workflow {
+    statuses: Vec<String>,
+}
+
+impl Workflow {
+    /// Reads a comma-separated list of two or more distinct statuses.
+    pub fn parse(input: &str) -> Result<Workflow, String> {
+        let statuses: Vec<String> = input
+            .split(',')
+            .map(str::trim)
+            .filter(|status| !status.is_empty())
+            .map(String::from)
+            .collect();
+        if statuses.len() < 2 {
+            return Err(format!(
+                "a workflow needs at least two statuses, the last meaning done: {}",
+                input
+            ));
+        }
+        for (index, status) in statuses.iter().enumerate() {
+            if statuses[..index]
+                .iter()
+                .any(|other| other.eq_ignore_ascii_case(status))
+            {
+                return Err(format!("workflow lists {} twice", status));
+            }
+        }
+        Ok(Workflow { statuses })
+    }
+
+    pub fn statuses(&self) -> &[String] {
+        &self.statuses
+    }
+
+    /// The last status, which means done.
+    pub fn done(&self) -> &str {
+        self.statuses.last().unwrap()
+    }
+
+    /// The status `task` is in. One it was given that the workflow no
+    /// longer has counts as the first.
+    pub fn status_of(&self, task: &Task) -> &str {
+        if task.is_done() {
+            return self.done();
+        }
+        let open = &self.statuses[..self.statuses.len() - 1];
+        task.status
+            .as_ref()
+            .and_then(|given| {
+                open.iter()
+                    .find(|status| status.eq_ignore_ascii_case(given))
+            })
+            .unwrap_or(&self.statuses[0])
+    }
+
+    /// The status `name` means, ignoring case, spaces and dashes; the
+    /// start of it or of one of its words will do.
+    pub fn find(&self, name: &str) -> error::Result<&str> {
+        let wanted = squash(name);
+        if let Some(status) = self.statuses.iter().find(|status| squash(status) == wanted) {
+            return Ok(status);
+        }
+        let found: Vec<&String> = self
+            .statuses
+            .iter()
+            .filter(|status| {
+                squash(status).starts_with(&wanted)
+                    || status
+                        .split_whitespace()
+                        .any(|word| squash(word).starts_with(&wanted))
+            })
+            .collect();
+        match found.as_slice() {
+            [status] => Ok(status),
+            [] => Err(RegiaError::NotFound(format!(
+                "no status {} in the workflow: {}",
+                name.trim(),
+                self.statuses.join(", ")
+            ))),
+            _ => Err(RegiaError::Conflict(format!(
+                "{} could be any of: {}",
+                name.trim(),
+                found
+                    .iter()
+                    .map(|status| status.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ))),
+        }
+    }
+
+    /// Puts `task` in `status`, completing it on the last status and
+    /// reopening it when it moves back from there.
+    pub fn move_task(&self, task: &mut Task, status: &str) {
+        if status == self.done() {
+            task.complete();
+        } else {
+            task.completed = None;
+        }
+        task.status = Some(status.to_string());
+    }
+}
+
+/// `text` lowercased with only its letters and digits, so "In Progress"
+/// and "in-progress" compare equal.
+fn squash(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        Workflow::parse(WORKFLOW).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tasks_move_through_the_workflow() {
+        let workflow = Workflow::default();
+        let mut task = Task::new(String::from("ship it"), 0);
+        assert_eq!(workflow.status_of(&task), "Backlog");
+
+        assert_eq!(workflow.find("in-progress").unwrap(), "In Progress");
+        let status = workflow.find("prog").unwrap().to_string();
+        workflow.move_task(&mut task, &status);
+        assert_eq!(workflow.status_of(&task), "In Progress");
+
+        workflow.move_task(&mut task, "Done");
+        assert!(task.is_done());
+        workflow.move_task(&mut task, "Review");
+        assert!(!task.is_done());
+        assert_eq!(workflow.status_of(&task), "Review");
+
+        // A status dropped from the workflow falls back to the first
+        let narrower = Workflow::parse("todo, doing, done").unwrap();
+        assert_eq!(narrower.status_of(&task), "todo");
+        task.complete();
+        assert_eq!(narrower.status_of(&task), "done");
+
+        assert_eq!(workflow.find("DONE").unwrap(), "Done");
+        assert_eq!(workflow.find("r").unwrap(), "Review");
+        assert_eq!(workflow.find("b").unwrap(), "Backlog");
+        assert_eq!(workflow.find("x").unwrap_err().exit_code(), 3);
+        assert!(Workflow::parse("done").is_err());
+        assert!(Workflow::parse("todo, Todo, done").is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, Utc};
+use clap::ArgMatches;
+use colored::*;
+use serde::Serialize;
+
+use crate::output::{self, Output};
+use regia_core::conf::{self, Config};
+use regia_core::contexts;
+use regia_core::dates;
+use regia_core::db;
+use regia_core::error::{self, RegiaError};
+use regia_core::schedule;
+use regia_core::theme::Themed;
+use regia_core::todo::{self, Task, Tasks};
+
+/// How many days ahead repeated tasks are projected by default.
+const DEFAULT_DAYS: i64 = 14;
+
+/// The most future occurrences one repeated task shows, so that a rule
+/// firing every few minutes doesn't drown the rest.
+const MAX_PROJECTED: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Bucket {
+    /// Due today or already overdue
+    Today,
+    Tomorrow,
+    /// Due within the next week
+    ThisWeek,
+    Later,
+    NoDate,
+}
+
+impl Bucket {
+    const ALL: [Bucket; 5] = [
+        Bucket::Today,
+        Bucket::Tomorrow,
+        Bucket::ThisWeek,
+        Bucket::Later,
+        Bucket::NoDate,
+    ];
+
+    /// How records name the bucket.
+    fn name(self) -> &'static str {
+        match self {
+            Bucket::Today => "today",
+            Bucket::Tomorrow => "tomorrow",
+            Bucket::ThisWeek => "this_week",
+            Bucket::Later => "later",
+            Bucket::NoDate => "no_date",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Bucket::Today => "Today",
+            Bucket::Tomorrow => "Tomorrow",
+            Bucket::ThisWeek => "This Week",
+            Bucket::Later => "Later",
+            Bucket::NoDate => "No date",
+        }
+    }
+
+    /// The bucket of a task due on local `day`.
+    fn of(day: Option<NaiveDate>, today: NaiveDate) -> Bucket {
+        let day = match day {
+            Some(day) => day,
+            None => return Bucket::NoDate,
+        };
+        match (day - today).num_days() {
+            days if days <= 0 => Bucket::Today,
+            1 => Bucket::Tomorrow,
+            days if days < 7 => Bucket::ThisWeek,
+            _ => Bucket::Later,
+        }
+    }
+}
+
+/// A task on the agenda: either itself, or a future occurrence of a
+/// repeated task that hasn't been spawned yet.
+pub struct Entry<'a> {
+    pub task: &'a Task,
+    /// The day it is listed under: when it is scheduled to start if it
+    /// is, otherwise when it is due
+    pub date: Option<DateTime<Utc>>,
+    pub projected: bool,
+}
+
+/// The open, unsnoozed tasks in date order, undated ones last, with the
+/// occurrences of repeated tasks up to `until` projected after them.
+/// Scheduled tasks go by their start rather than their due date.
+pub fn entries(tasks: &Tasks, now: DateTime<Utc>, until: DateTime<Utc>) -> Vec<Entry<'_>> {
+    let mut entries = Vec::new();
+    for task in tasks.get_tasks() {
+        if task.is_done() || task.is_snoozed(now) {
+            continue;
+        }
+        entries.push(Entry {
+            task,
+            date: task.scheduled.or(task.due),
+            projected: false,
+        });
+        let (due, repeat) = match (task.due, &task.repeat) {
+            (Some(due), Some(repeat)) if task.successor.is_none() => (due, repeat),
+            _ => continue,
+        };
+        // Occurrences start as far ahead of their due date as this one
+        let lead = task
+            .scheduled
+            .map(|scheduled| due - scheduled)
+            .unwrap_or_else(Duration::zero);
+        for occurrence in schedule::upcoming(due, repeat, MAX_PROJECTED, &Local)
+            .into_iter()
+            .map(|occurrence| occurrence - lead)
+            .take_while(|occurrence| *occurrence <= until)
+        {
+            entries.push(Entry {
+                task,
+                date: Some(occurrence),
+                projected: true,
+            });
+        }
+    }
+    entries.sort_by(|left, right| {
+        (left.date.is_none(), left.date)
+            .cmp(&(right.date.is_none(), right.date))
+            .then_with(|| left.task.listing_order(right.task))
+    });
+    entries
+}
+
+#[derive(Serialize)]
+struct AgendaRecord {
+    bucket: Bucket,
+    /// The day the entry is listed under, see `Entry::date`
+    date: Option<DateTime<Utc>>,
+    projected: bool,
+    task: output::TaskRecord,
+}
+
+impl output::Record for AgendaRecord {
+    fn plain(&self) -> String {
+        output::fields(&[
+            self.bucket.name().to_string(),
+            output::fmt_time(&self.date),
+            if self.projected { "projected" } else { "" }.to_string(),
+            self.task.id.to_string(),
+            self.task.content.clone(),
+        ])
+    }
+}
+
+/// The day `at` falls on by local clocks.
+pub fn local_day(at: DateTime<Utc>) -> NaiveDate {
+    at.with_timezone(&Local).date_naive()
+}
+
+fn count(n: usize) -> String {
+    format!("({})", n).dimmed().to_string()
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db = db::open(doc)?;
+    let days = match matches.value_of("days") {
+        Some(input) => match input.parse::<i64>() {
+            Ok(days) if days >= 0 => days,
+            _ => return Err(RegiaError::Parse(format!("bad day count: {}", input))),
+        },
+        None => DEFAULT_DAYS,
+    };
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let formatter = todo::Formatter {
+        due_soon: conf::due_soon(doc)?,
+        ..todo::Formatter::new(&priority_map)
+    };
+    let now = formatter.now;
+    let today = local_day(now);
+    let midnight = (today + Duration::days(days + 1)).and_time(NaiveTime::MIN);
+    let until = dates::from_local(&Local, &midnight);
+    let given = matches.value_of("context");
+    let context = crate::contexts::wanted(given, matches.is_present("any-context"), doc)?;
+    let mut entries = entries(&db.tasks, now, until);
+    if let Some(context) = context.as_deref() {
+        entries.retain(|entry| contexts::fits(&entry.task.contexts, context));
+    }
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        let records: Vec<AgendaRecord> = entries
+            .iter()
+            .map(|entry| AgendaRecord {
+                bucket: Bucket::of(entry.date.map(local_day), today),
+                date: entry.date,
+                projected: entry.projected,
+                task: output::TaskRecord::new(entry.task, &db.tasks, now),
+            })
+            .collect();
+        return output::print(format, &records);
+    }
+
+    if entries.is_empty() {
+        println!("Nothing on the agenda");
+    }
+    for line in render(&entries, &db.tasks, today, &formatter) {
+        println!("{}", line);
+    }
+    crate::contexts::print_sticky(given, context.as_deref());
+    Ok(())
+}
+
+/// The agenda as `regia agenda` prints it, a line at a time: `entries`
+/// under their buckets and days, as seen on local `today`.
+pub fn render(
+    entries: &[Entry],
+    tasks: &Tasks,
+    today: NaiveDate,
+    formatter: &todo::Formatter,
+) -> Vec<String> {
+    let locale = dates::locale();
+    let mut lines = Vec::new();
+    for bucket in Bucket::ALL.iter() {
+        let in_bucket: Vec<&Entry> = entries
+            .iter()
+            .filter(|entry| Bucket::of(entry.date.map(local_day), today) == *bucket)
+            .collect();
+        if in_bucket.is_empty() {
+            continue;
+        }
+        lines.push(format!(
+            "{} {}",
+            bucket.title().bold().underline(),
+            count(in_bucket.len())
+        ));
+
+        let mut day_start = 0;
+        while day_start < in_bucket.len() {
+            let day = in_bucket[day_start].date.map(local_day);
+            let day_len = in_bucket[day_start..]
+                .iter()
+                .take_while(|entry| entry.date.map(local_day) == day)
+                .count();
+            // Overdue tasks share Today's bucket, under their own dates
+            if let Some(day) = day {
+                lines.push(format!(
+                    "  {} {}",
+                    format!("{} {}", day.format("%a"), locale.day(day)).accent(),
+                    count(day_len)
+                ));
+            }
+            for entry in &in_bucket[day_start..day_start + day_len] {
+                let line = if entry.projected {
+                    format!(
+                        "↻ {} {}",
+                        entry.task.content,
+                        format!(
+                            "({})",
+                            locale.time(entry.date.unwrap().with_timezone(&Local).time())
+                        )
+                        .dimmed()
+                    )
+                    .dimmed()
+                    .to_string()
+                } else {
+                    formatter.task(entry.task, &tasks.open_dependencies(entry.task))
+                };
+                lines.push(format!("    {}", line));
+            }
+            day_start += day_len;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use regia_core::todo::RepeatType;
+
+    #[test]
+    fn buckets_and_projects_repeats() {
+        let now = Utc.with_ymd_and_hms(2019, 10, 4, 9, 0, 0).unwrap();
+        let today = now.date_naive();
+        assert_eq!(
+            Bucket::of(Some((now - Duration::days(3)).date_naive()), today),
+            Bucket::Today
+        );
+        assert_eq!(
+            Bucket::of(Some((now + Duration::hours(16)).date_naive()), today),
+            Bucket::Tomorrow
+        );
+        assert_eq!(
+            Bucket::of(Some((now + Duration::days(6)).date_naive()), today),
+            Bucket::ThisWeek
+        );
+        assert_eq!(
+            Bucket::of(Some((now + Duration::days(7)).date_naive()), today),
+            Bucket::Later
+        );
+        assert_eq!(Bucket::of(None, today), Bucket::NoDate);
+
+        let mut tasks = Tasks::default();
+        let mut daily = Task::new(String::from("standup"), 0);
+        daily.due = Some(now);
+        daily.repeat = Some(RepeatType::Daily);
+        tasks.add(daily);
+        tasks.add(Task::new(String::from("someday"), 0));
+        let mut planned = Task::new(String::from("planned"), 0);
+        planned.scheduled = Some(now + Duration::hours(36));
+        planned.due = Some(now + Duration::days(10));
+        tasks.add(planned);
+
+        let listed = entries(&tasks, now, now + Duration::days(3));
+        let shown: Vec<(&str, bool)> = listed
+            .iter()
+            .map(|entry| (entry.task.content.as_str(), entry.projected))
+            .collect();
+        assert_eq!(
+            shown,
+            [
+                ("standup", false),
+                ("standup", true),
+                ("planned", false),
+                ("standup", true),
+                ("standup", true),
+                ("someday", false)
+            ]
+        );
+    }
+}
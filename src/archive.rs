@@ -0,0 +1,137 @@
+use chrono::{DateTime, Duration, Utc};
+use clap::ArgMatches;
+use colored::*;
+use uuid::Uuid;
+
+use regia_core::conf::{self, Config};
+use regia_core::dates;
+use regia_core::db::Database;
+use regia_core::error::{self, RegiaError};
+use regia_core::theme::Themed;
+use regia_core::todo::{Task, Tasks};
+
+/// How old a finished task must be for `task archive --prune` when neither
+/// `--older-than` nor `archive.after` says otherwise.
+const DEFAULT_AGE: i64 = 30;
+
+/// Reads `archive.after` from the config. When set, every task command
+/// archives tasks that have been finished for longer than it.
+pub fn auto_age(doc: &Config) -> error::Result<Option<Duration>> {
+    conf::duration("archive.after", &doc.archive.after)
+}
+
+/// Whether `task` was finished before `cutoff`. Besides completed tasks
+/// this covers instances of a repeated task that were skipped: they stay
+/// open, but a successor has replaced them and their due date is past.
+pub fn is_stale(task: &Task, cutoff: DateTime<Utc>) -> bool {
+    match task.completed {
+        Some(completed) => completed < cutoff,
+        None => task.successor.is_some() && task.due.is_some_and(|due| due < cutoff),
+    }
+}
+
+/// Moves every task finished before `cutoff` from `tasks` into `archive`.
+pub fn prune(tasks: &mut Tasks, archive: &mut Tasks, cutoff: DateTime<Utc>) -> Vec<Uuid> {
+    let stale: Vec<Uuid> = tasks
+        .get_tasks()
+        .iter()
+        .filter(|task| is_stale(task, cutoff))
+        .map(|task| task.id)
+        .collect();
+    for id in stale.iter() {
+        if let Some(task) = tasks.get_task(id).cloned() {
+            tasks.remove(*id);
+            archive.add(task);
+        }
+    }
+    stale
+}
+
+/// Lists the archive, newest first, after pruning when asked to. Returns
+/// whether anything moved.
+pub fn handle_task_archive(
+    matches: &ArgMatches,
+    db: &mut Database,
+    doc: &Config,
+) -> error::Result<bool> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+
+    let mut moved = Vec::new();
+    if matches.is_present("prune") {
+        let age = match matches.value_of("older-than") {
+            Some(value) => match dates::parse_duration(value) {
+                Some(age) => age,
+                None => {
+                    return Err(RegiaError::Parse(format!(
+                        "bad --older-than duration: {}",
+                        value
+                    )))
+                }
+            },
+            None => auto_age(doc)?.unwrap_or_else(|| Duration::days(DEFAULT_AGE)),
+        };
+        moved = prune(&mut db.tasks, &mut db.archive, Utc::now() - age);
+        println!(
+            "{} {} task{}",
+            "Archived".header(),
+            moved.len(),
+            if moved.len() == 1 { "" } else { "s" }
+        );
+    }
+
+    let mut archived: Vec<&Task> = db.archive.get_tasks().iter().collect();
+    archived.sort_by_key(|task| std::cmp::Reverse(task.completed.or(task.due)));
+    let locale = dates::locale();
+    let width = locale.day(Utc::now().date_naive()).chars().count();
+    for task in archived {
+        let finished = match task.completed.or(task.due) {
+            Some(at) => locale.day(at.date_naive()),
+            None => "-".repeat(width),
+        };
+        println!("{} {}", finished.dimmed(), task.fmt(&priority_map));
+    }
+    Ok(!moved.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use regia_core::todo::{RepeatType, TaskType};
+
+    #[test]
+    fn prunes_finished_and_skipped() {
+        let now = Utc.with_ymd_and_hms(2019, 10, 30, 12, 0, 0).unwrap();
+        let cutoff = now - Duration::days(7);
+
+        let mut old_done = Task::new(String::from("old done"), 0);
+        old_done.completed = Some(now - Duration::days(10));
+        let mut recent_done = Task::new(String::from("recent done"), 0);
+        recent_done.completed = Some(now - Duration::days(1));
+        let open = Task::new(String::from("open"), 0);
+        let mut skipped = Task::new_date(
+            String::from("standup"),
+            0,
+            Some(now - Duration::days(14)),
+            TaskType::Repeated,
+            Some(RepeatType::Weekly),
+        );
+        skipped.successor = Some(Uuid::new_v4());
+
+        let mut tasks = Tasks::default();
+        for task in [&old_done, &recent_done, &open, &skipped] {
+            tasks.add(task.clone());
+        }
+        let mut archive = Tasks::default();
+
+        let mut moved = prune(&mut tasks, &mut archive, cutoff);
+        moved.sort();
+        let mut expected = vec![old_done.id, skipped.id];
+        expected.sort();
+        assert_eq!(moved, expected);
+        assert_eq!(tasks.get_tasks().len(), 2);
+        assert!(archive.get_task(&old_done.id).is_some());
+        assert!(prune(&mut tasks, &mut archive, cutoff).is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use clap::ArgMatches;
+use colored::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::output::{self, Output};
+use regia_core::attach::{Attachment, Store};
+use regia_core::conf::Config;
+use regia_core::db::{self, Database};
+use regia_core::error::{self, RegiaError};
+use regia_core::theme::Themed;
+
+/// Copies the file at `path` into the attachment store and records it in
+/// `attachments`, once per name and contents.
+pub fn attach_file(
+    doc: &Config,
+    path: &str,
+    attachments: &mut Vec<Attachment>,
+) -> error::Result<()> {
+    let store = Store::for_db(db::db_path(doc));
+    let attachment = store
+        .add(Path::new(path))
+        .map_err(|err| err.context(format!("can't attach {}", path)))?;
+    let short = attachment.short_hash().dimmed();
+    if attachments
+        .iter()
+        .any(|known| known.hash == attachment.hash && known.filename == attachment.filename)
+    {
+        println!("Already attached {} {}", attachment.filename, short);
+        return Ok(());
+    }
+    println!("{} {} {}", "Attached".header(), attachment.filename, short);
+    attachments.push(attachment);
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct AttachmentRecord {
+    #[serde(flatten)]
+    attachment: Attachment,
+    /// `task` or `note`
+    kind: &'static str,
+    /// The task or note it is attached to
+    id: Uuid,
+    content: String,
+}
+
+impl output::Record for AttachmentRecord {
+    fn plain(&self) -> String {
+        output::fields(&[
+            self.attachment.hash.clone(),
+            self.attachment.filename.clone(),
+            self.kind.to_string(),
+            self.id.to_string(),
+            self.content.replace('\n', " "),
+        ])
+    }
+}
+
+/// Every attachment in the active project, oldest first.
+fn records(db: &Database) -> Vec<AttachmentRecord> {
+    let record = |attachment: &Attachment, kind, id, content: &str| AttachmentRecord {
+        attachment: attachment.clone(),
+        kind,
+        id,
+        content: content.to_string(),
+    };
+    let mut records: Vec<AttachmentRecord> = db
+        .tasks
+        .get_tasks()
+        .iter()
+        .flat_map(|task| {
+            task.attachments
+                .iter()
+                .map(move |attachment| record(attachment, "task", task.id, &task.content))
+        })
+        .chain(db.notes.get_notes().iter().flat_map(|note| {
+            note.attachments
+                .iter()
+                .map(move |attachment| record(attachment, "note", note.id, &note.content))
+        }))
+        .collect();
+    records.sort_by_key(|record| record.attachment.added);
+    records
+}
+
+fn print_record(record: &AttachmentRecord) {
+    println!(
+        "{} {} {} {}",
+        record.attachment.short_hash().dimmed(),
+        record.attachment.filename,
+        record.kind.accent(),
+        record.content.lines().next().unwrap_or("")
+    );
+}
+
+fn handle_attach_list(db: &Database, doc: &Config) -> error::Result<()> {
+    let records = records(db);
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        return output::print(format, &records);
+    }
+    if records.is_empty() {
+        println!("Nothing is attached");
+    }
+    for record in records.iter() {
+        print_record(record);
+    }
+    Ok(())
+}
+
+/// The program that opens a file with its default application.
+fn system_opener() -> Command {
+    if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    }
+}
+
+/// Copies a stored attachment out under its own filename, so that the
+/// system handler can tell what kind of file it is. It goes in a fresh
+/// directory only this user can get at, which is left for the handler,
+/// under the last part of the name only, whatever the database says.
+fn checkout(store: &Store, attachment: &Attachment) -> error::Result<PathBuf> {
+    let source = store.path(attachment)?;
+    let name = attachment.file_name().ok_or_else(|| {
+        RegiaError::corrupt(format!(
+            "attachment has a bad filename: {}",
+            attachment.filename
+        ))
+    })?;
+    let mut builder = tempfile::Builder::new();
+    builder.prefix("regia-attachment-");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(std::fs::Permissions::from_mode(0o700));
+    }
+    let dir = builder.tempdir()?.keep();
+    let path = dir.join(name);
+    std::fs::copy(source, &path)?;
+    Ok(path)
+}
+
+/// Opens the attachment named by hash prefix or filename.
+fn handle_attach_open(matches: &ArgMatches, db: &Database, doc: &Config) -> error::Result<()> {
+    let wanted = matches.value_of("attachment").unwrap();
+    let mut found: Vec<AttachmentRecord> = records(db)
+        .into_iter()
+        .filter(|record| record.attachment.matches(wanted))
+        .collect();
+    found.sort_by(|a, b| a.attachment.hash.cmp(&b.attachment.hash));
+    found.dedup_by(|a, b| a.attachment.hash == b.attachment.hash);
+    let record = match found.len() {
+        0 => {
+            return Err(RegiaError::NotFound(format!(
+                "no attachment matches: {}",
+                wanted
+            )))
+        }
+        1 => &found[0],
+        _ => {
+            for record in found.iter() {
+                print_record(record);
+            }
+            return Err(RegiaError::Conflict(format!(
+                "{} matches several attachments, give more of the hash",
+                wanted
+            )));
+        }
+    };
+
+    let store = Store::for_db(db::db_path(doc));
+    let attachment = &record.attachment;
+    let path = checkout(&store, attachment)
+        .map_err(|err| err.context(format!("can't read {}", attachment.filename)))?;
+    let status = system_opener().arg(&path).status()?;
+    if !status.success() {
+        return Err(RegiaError::storage(format!(
+            "opening {} failed with {}",
+            path.display(),
+            status
+        )));
+    }
+    Ok(())
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db = db::open(doc)?;
+    if matches.subcommand_matches("ls").is_some() {
+        handle_attach_list(&db, doc)
+    } else if let Some(matches) = matches.subcommand_matches("open") {
+        handle_attach_open(matches, &db, doc)
+    } else {
+        unreachable!();
+    }
+}
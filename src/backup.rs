@@ -0,0 +1,166 @@
+//! `regia backup` and `regia restore`: compressed snapshots of the whole
+//! database, kept to `backup.keep` of them, see [`regia_core::backup`].
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use clap::ArgMatches;
+use colored::*;
+use serde::Serialize;
+
+use crate::journal;
+use crate::output::{self, Output};
+use crate::prompt;
+use regia_core::backup;
+use regia_core::conf::{self, Config};
+use regia_core::db::{self, Database};
+use regia_core::error::{self, RegiaError};
+use regia_core::theme::Themed;
+
+/// The directory from `--to`, else `backup.dir`, else `<db>.backups`.
+fn dir(matches: &ArgMatches, doc: &Config) -> PathBuf {
+    match matches.value_of("to").or(doc.backup.dir.as_deref()) {
+        Some(dir) => PathBuf::from(dir),
+        None => backup::default_dir(db::db_path(doc)),
+    }
+}
+
+#[derive(Serialize)]
+struct BackupRecord {
+    path: String,
+    bytes: u64,
+}
+
+impl BackupRecord {
+    fn new(path: &Path) -> error::Result<Self> {
+        Ok(Self {
+            path: path.display().to_string(),
+            bytes: fs::metadata(path)?.len(),
+        })
+    }
+}
+
+impl output::Record for BackupRecord {
+    fn plain(&self) -> String {
+        output::fields(&[self.path.clone(), self.bytes.to_string()])
+    }
+}
+
+/// `bytes` in B, KiB or MiB.
+pub fn fmt_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+fn handle_list(dir: &Path, doc: &Config) -> error::Result<()> {
+    let records = backup::list(dir, db::db_path(doc))?
+        .iter()
+        .map(|path| BackupRecord::new(path))
+        .collect::<error::Result<Vec<BackupRecord>>>()?;
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        return output::print(format, &records);
+    }
+    if records.is_empty() {
+        println!("No backups in {}", dir.display());
+    }
+    for record in records.iter() {
+        println!(
+            "{} {}",
+            record.path,
+            format!("({})", fmt_bytes(record.bytes)).dimmed()
+        );
+    }
+    Ok(())
+}
+
+/// `regia backup`: writes a backup of the database, then deletes the
+/// oldest beyond `backup.keep`. `--list` lists them instead.
+pub fn handle_backup(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let dir = dir(matches, doc);
+    if matches.is_present("list") {
+        return handle_list(&dir, doc);
+    }
+
+    let db_path = db::db_path(doc);
+    let db = Database::load(db_path)?;
+    let path = backup::write(&dir, db_path, &db, Utc::now())?;
+    let removed = backup::rotate(&dir, db_path, conf::backup_keep(doc))?;
+    let record = BackupRecord::new(&path)?;
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        return output::print_one(format, &record);
+    }
+    println!(
+        "{} {} {}",
+        "Backed up to".header(),
+        record.path,
+        format!("({})", fmt_bytes(record.bytes)).dimmed()
+    );
+    if !removed.is_empty() {
+        let plural = if removed.len() == 1 { "" } else { "s" };
+        println!(
+            "{}",
+            format!("Deleted {} older backup{}", removed.len(), plural).dimmed()
+        );
+    }
+    Ok(())
+}
+
+/// How many tasks and notes `db` holds in all its projects.
+pub fn counts(db: &Database) -> (usize, usize) {
+    db.projects.values().fold(
+        (db.tasks.get_tasks().len(), db.notes.get_notes().len()),
+        |(tasks, notes), project| {
+            (
+                tasks + project.tasks.get_tasks().len(),
+                notes + project.notes.get_notes().len(),
+            )
+        },
+    )
+}
+
+/// `regia restore FILE`: checks the backup in `FILE` and puts it in
+/// place of the database, after backing up the database as it was.
+pub fn handle_restore(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let file = matches.value_of("file").unwrap();
+    let restored = fs::read(file)
+        .map_err(RegiaError::from)
+        .and_then(|buf| backup::decode(&buf))
+        .map_err(|err| err.context(format!("can't restore {}", file)))?;
+    let (tasks, notes) = counts(&restored);
+    println!(
+        "{} {} {}",
+        "Checked".header(),
+        file,
+        format!(
+            "({} tasks, {} notes, {} projects)",
+            tasks,
+            notes,
+            restored.projects.len()
+        )
+        .dimmed()
+    );
+    if !prompt::confirm("Restore it over the database?")? {
+        return Ok(());
+    }
+
+    let db_path = db::db_path(doc);
+    let current = Database::load(db_path)?;
+    // A dry run writes nothing, not even this
+    if !db::is_dry() {
+        let kept = backup::write(&dir(matches, doc), db_path, &current, Utc::now())?;
+        println!(
+            "{}",
+            format!("Backed up the database as it was to {}", kept.display()).dimmed()
+        );
+    }
+    journal::record_as_is(db_path, "restore", &current, &restored)?;
+    restored.to_disk(db_path)?;
+    println!("{} {}", "Restored".header(), file);
+    Ok(())
+}
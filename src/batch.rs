@@ -0,0 +1,385 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::editor;
+use crate::prompt;
+use crate::taskmaster;
+use regia_core::error::{self, RegiaError};
+use regia_core::note::Notes;
+use regia_core::priority::Priority;
+use regia_core::tags;
+use regia_core::theme::Themed;
+use regia_core::todo::{self, Task, Tasks};
+use regia_core::trash::Trash;
+use regia_core::workflow::Workflow;
+
+const HEADER: &str = "\
+# Edit the tasks below, then save and quit to apply the changes.
+#
+# Change a field to change it on the task, or leave it out to clear it.
+# Delete an entry to remove its task, and add one without an id to add a
+# task. Entries take content, status, priority, due, scheduled, estimate,
+# repeats, tags and depends.
+#
+# Saving the file unchanged, or with no entries at all, changes nothing.
+";
+
+/// A task as `task edit --batch` writes it out and reads it back. Dates
+/// are local time, in a form `dates::parse` takes.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<Uuid>,
+    content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    due: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scheduled: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    estimate: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    repeats: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    depends: Vec<Uuid>,
+}
+
+fn fmt_local(at: DateTime<Utc>) -> String {
+    at.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// `seconds` in the largest unit that holds it whole, as `parse_duration`
+/// reads it back.
+fn fmt_span(seconds: i64) -> String {
+    let minutes = seconds / 60;
+    if minutes % (24 * 60) == 0 {
+        format!("{}d", minutes / (24 * 60))
+    } else if minutes % 60 == 0 {
+        format!("{}h", minutes / 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+impl Entry {
+    fn of(task: &Task, workflow: &Workflow) -> Entry {
+        let mut tags: Vec<String> = task.tags.iter().cloned().collect();
+        tags.sort();
+        let mut depends: Vec<Uuid> = task.depends.iter().copied().collect();
+        depends.sort();
+        Entry {
+            id: Some(task.id),
+            content: task.content.clone(),
+            status: Some(workflow.status_of(task).to_string()),
+            priority: Some(task.priority)
+                .filter(|priority| *priority != Priority::None)
+                .map(|priority| priority.to_string()),
+            due: task.due.map(fmt_local),
+            scheduled: task.scheduled.map(fmt_local),
+            estimate: task.estimate.map(fmt_span),
+            repeats: task.repeat.as_ref().map(ToString::to_string),
+            tags,
+            depends,
+        }
+    }
+
+    /// Makes `task` say what this entry does wherever it differs from
+    /// `old`, what the task was written out as.
+    fn update(
+        &self,
+        old: &Entry,
+        task: &mut Task,
+        tasks: &Tasks,
+        workflow: &Workflow,
+    ) -> error::Result<()> {
+        if self.content != old.content {
+            if self.content.trim().is_empty() {
+                return Err(RegiaError::Parse(String::from("content can't be empty")));
+            }
+            task.content = self.content.clone();
+        }
+        if self.priority != old.priority {
+            task.priority = match &self.priority {
+                Some(priority) => taskmaster::parse_priority(priority)?,
+                None => Priority::None,
+            };
+        }
+        if self.due != old.due {
+            task.due = self.due.as_deref().map(taskmaster::parse_due).transpose()?;
+        }
+        if self.scheduled != old.scheduled {
+            task.scheduled = self
+                .scheduled
+                .as_deref()
+                .map(taskmaster::parse_due)
+                .transpose()?;
+        }
+        if self.estimate != old.estimate {
+            task.estimate = self
+                .estimate
+                .as_deref()
+                .map(taskmaster::parse_estimate)
+                .transpose()?;
+        }
+        if self.repeats != old.repeats {
+            task.repeat = self
+                .repeats
+                .as_deref()
+                .map(taskmaster::parse_repeat)
+                .transpose()?;
+        }
+        if self.due != old.due || self.repeats != old.repeats {
+            task.task_type = if task.repeat.is_some() {
+                Some(todo::TaskType::Repeated)
+            } else if task.due.is_some() {
+                Some(todo::TaskType::Deadline)
+            } else {
+                None
+            };
+        }
+        if self.tags != old.tags {
+            task.tags = tags::from_args(self.tags.iter().map(String::as_str));
+        }
+        if self.depends != old.depends {
+            let depends: HashSet<Uuid> = self.depends.iter().copied().collect();
+            for dep in depends.difference(&task.depends) {
+                if *dep == task.id || tasks.get_task(dep).is_none() {
+                    return Err(RegiaError::NotFound(format!("no task with id: {}", dep)));
+                }
+                if tasks.would_cycle(&task.id, dep) {
+                    return Err(RegiaError::Conflict(format!(
+                        "depending on {} would create a dependency cycle",
+                        dep
+                    )));
+                }
+            }
+            task.depends = depends;
+        }
+        if self.status != old.status {
+            let status = match &self.status {
+                Some(status) => workflow.find(status)?,
+                None => &workflow.statuses()[0],
+            };
+            workflow.move_task(task, status);
+        }
+        Ok(())
+    }
+}
+
+/// What an edited batch file asks for, checked in full before any of it
+/// is applied.
+#[derive(Debug, Default)]
+struct Plan {
+    added: Vec<Task>,
+    updated: Vec<Task>,
+    removed: Vec<Uuid>,
+}
+
+impl Plan {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Reads back the edited `text` of a file that was written out as
+/// `written`, working out what changes on which task.
+fn plan(text: &str, written: &[Entry], tasks: &Tasks, workflow: &Workflow) -> error::Result<Plan> {
+    let mut plan = Plan::default();
+    let blank = text
+        .lines()
+        .map(str::trim)
+        .all(|line| line.is_empty() || line.starts_with('#'));
+    if blank {
+        return Ok(plan);
+    }
+    let entries: Vec<Entry> = match serde_yaml::from_str::<Option<Vec<Entry>>>(text) {
+        Ok(entries) => entries.unwrap_or_default(),
+        Err(err) => return Err(RegiaError::Parse(format!("bad batch file: {}", err))),
+    };
+    if entries.is_empty() {
+        return Ok(plan);
+    }
+
+    let old: HashMap<Uuid, &Entry> = written
+        .iter()
+        .filter_map(|entry| entry.id.map(|id| (id, entry)))
+        .collect();
+    let mut seen = HashSet::new();
+    for entry in entries.iter() {
+        let (old_entry, mut task) = match entry.id {
+            Some(id) => {
+                let old_entry = match old.get(&id) {
+                    Some(old_entry) => *old_entry,
+                    None => {
+                        return Err(RegiaError::NotFound(format!(
+                            "{} isn't one of the tasks being edited",
+                            id
+                        )))
+                    }
+                };
+                if !seen.insert(id) {
+                    return Err(RegiaError::Conflict(format!("{} is listed twice", id)));
+                }
+                if entry == old_entry {
+                    continue;
+                }
+                (old_entry.clone(), tasks.get_task(&id).unwrap().clone())
+            }
+            None => (Entry::default(), Task::new(String::new(), Priority::None)),
+        };
+        entry
+            .update(&old_entry, &mut task, tasks, workflow)
+            .map_err(|err| err.context(entry.content.trim()))?;
+        if entry.id.is_some() {
+            plan.updated.push(task);
+        } else {
+            plan.added.push(task);
+        }
+    }
+    plan.removed = written
+        .iter()
+        .filter_map(|entry| entry.id)
+        .filter(|id| !seen.contains(id))
+        .collect();
+    Ok(plan)
+}
+
+/// Writes the tasks `ids` out for the user to edit in `$EDITOR`, then
+/// applies what changed. A file that doesn't read back can be edited
+/// again. Removed tasks go to the trash and their notes are kept
+/// unattached.
+pub fn edit(
+    ids: &[Uuid],
+    tasks: &mut Tasks,
+    notes: &mut Notes,
+    trash: &mut Trash,
+    workflow: &Workflow,
+    priority_map: &[(u32, &str)],
+) -> error::Result<()> {
+    let written: Vec<Entry> = ids
+        .iter()
+        .filter_map(|id| tasks.get_task(id))
+        .map(|task| Entry::of(task, workflow))
+        .collect();
+    let body = match serde_yaml::to_string(&written) {
+        Ok(body) => body,
+        Err(err) => return Err(RegiaError::storage(err.to_string())),
+    };
+    let mut text = format!("{}\n{}", HEADER, body);
+
+    let plan = loop {
+        text = editor::edit_text(&text, ".yml")?;
+        match plan(&text, &written, tasks, workflow) {
+            Ok(plan) => break plan,
+            Err(err) => {
+                println!("{}", err.to_string().error());
+                if !prompt::confirm("Edit again?")? {
+                    return Err(RegiaError::Parse(String::from("batch file not applied")));
+                }
+            }
+        }
+    };
+    if plan.is_empty() {
+        println!("No changes");
+        return Ok(());
+    }
+
+    for task in plan.added {
+        println!("{} {}", "Added".header(), task.fmt(priority_map));
+        tasks.add(task);
+    }
+    for task in plan.updated {
+        println!("{} {}", "Updated".header(), task.fmt(priority_map));
+        tasks.add(task);
+    }
+    let now = Utc::now();
+    for id in plan.removed {
+        if let Some(task) = tasks.get_task(&id) {
+            println!("{} {}", "Removed".header(), task.fmt(priority_map));
+        }
+        trash.delete_task(tasks, id, now);
+        let attached: Vec<Uuid> = notes.for_task(&id).iter().map(|note| note.id).collect();
+        for note_id in attached {
+            if let Some(mut note) = notes.get_note_mut(&note_id) {
+                note.task_id = None;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_files_round_trip_into_changes() {
+        let workflow = Workflow::default();
+        let mut tasks = Tasks::default();
+        let mut report = Task::new(String::from("Write the report"), 3);
+        report.estimate = Some(90 * 60);
+        report.tags.insert(String::from("work"));
+        let groceries = Task::new(String::from("Buy groceries"), 0);
+        let old_task = Task::new(String::from("Old task"), 0);
+        let ids = [report.id, groceries.id, old_task.id];
+        for task in [report, groceries, old_task] {
+            tasks.add(task);
+        }
+        let written: Vec<Entry> = ids
+            .iter()
+            .map(|id| Entry::of(tasks.get_task(id).unwrap(), &workflow))
+            .collect();
+        let text = serde_yaml::to_string(&written).unwrap();
+        assert!(text.contains("estimate: 90m"), "{}", text);
+
+        // Unchanged, the file asks for nothing
+        let unchanged = format!("{}\n{}", HEADER, text);
+        assert!(plan(&unchanged, &written, &tasks, &workflow)
+            .unwrap()
+            .is_empty());
+        assert!(plan(HEADER, &written, &tasks, &workflow)
+            .unwrap()
+            .is_empty());
+
+        let edited = format!(
+            "- id: {}\n  content: Write the report\n  priority: 5\n  estimate: 90m\n  \
+             status: review\n  tags: [work, urgent]\n\
+             - id: {}\n  content: Buy groceries\n  status: Backlog\n\
+             - content: Call the bank\n  due: 2026-05-01 09:00\n",
+            ids[0], ids[1]
+        );
+        let changes = plan(&edited, &written, &tasks, &workflow).unwrap();
+        assert_eq!(changes.updated.len(), 1);
+        let updated = &changes.updated[0];
+        assert_eq!(updated.priority.weight(), 5);
+        assert_eq!(updated.estimate, Some(90 * 60));
+        assert_eq!(workflow.status_of(updated), "Review");
+        assert_eq!(updated.tags.len(), 2);
+        assert_eq!(changes.added.len(), 1);
+        assert_eq!(changes.added[0].content, "Call the bank");
+        assert!(changes.added[0].due.is_some());
+        assert_eq!(changes.removed, vec![ids[2]]);
+
+        let stranger = format!("- id: {}\n  content: Who?\n", Uuid::new_v4());
+        let err = plan(&stranger, &written, &tasks, &workflow).unwrap_err();
+        assert_eq!(err.exit_code(), 3);
+        let bad = format!(
+            "- id: {}\n  content: Buy groceries\n  due: someday\n",
+            ids[1]
+        );
+        let err = plan(&bad, &written, &tasks, &workflow).unwrap_err();
+        assert!(err.to_string().starts_with("Buy groceries: "), "{}", err);
+        assert!(plan("- colour: red\n", &written, &tasks, &workflow).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Duration, Utc};
+use clap::ArgMatches;
+use colored::*;
+use ratatui::crossterm::terminal;
+use serde::Serialize;
+
+use crate::output::{self, Output};
+use regia_core::conf::{self, Config};
+use regia_core::db;
+use regia_core::error;
+use regia_core::tags;
+use regia_core::theme;
+use regia_core::todo::{self, Task, Tasks};
+use regia_core::workflow::Workflow;
+
+/// The done column only shows tasks completed this recently, unless
+/// `--all` is given.
+const DONE_DAYS: i64 = 7;
+/// Columns are never drawn narrower than this, however many there are.
+const MIN_WIDTH: usize = 12;
+const GAP: &str = "  ";
+
+/// A status of the workflow and the tasks in it, most important first.
+pub struct Column<'a> {
+    pub status: &'a str,
+    pub tasks: Vec<&'a Task>,
+}
+
+/// The tasks carrying every tag in `wanted` sorted into the statuses of
+/// `workflow`, leaving out those completed before `done_since`.
+pub fn columns<'a>(
+    workflow: &'a Workflow,
+    tasks: &'a Tasks,
+    wanted: &HashSet<String>,
+    done_since: Option<DateTime<Utc>>,
+) -> Vec<Column<'a>> {
+    let mut columns: Vec<Column> = workflow
+        .statuses()
+        .iter()
+        .map(|status| Column {
+            status,
+            tasks: Vec::new(),
+        })
+        .collect();
+    for task in tasks.tagged(wanted) {
+        let recent = match (task.completed, done_since) {
+            (Some(completed), Some(since)) => completed >= since,
+            _ => true,
+        };
+        if !recent {
+            continue;
+        }
+        let status = workflow.status_of(task);
+        if let Some(column) = columns.iter_mut().find(|column| column.status == status) {
+            column.tasks.push(task);
+        }
+    }
+    for column in columns.iter_mut() {
+        column
+            .tasks
+            .sort_by(|left, right| left.listing_order(right));
+    }
+    columns
+}
+
+/// `text` cut to `width` characters, ending in an ellipsis when cut, then
+/// padded out to `width`.
+fn fit(text: &str, width: usize) -> String {
+    let cut: String = if text.chars().count() > width {
+        let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+        cut.push('…');
+        cut
+    } else {
+        text.to_string()
+    };
+    format!("{:width$}", cut, width = width)
+}
+
+#[derive(Serialize)]
+struct BoardCard {
+    status: String,
+    #[serde(flatten)]
+    task: output::TaskRecord,
+}
+
+impl output::Record for BoardCard {
+    fn plain(&self) -> String {
+        format!("{}\t{}", self.status, self.task.plain())
+    }
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db = db::open(doc)?;
+    let workflow = conf::workflow(doc)?;
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let formatter = todo::Formatter {
+        due_soon: conf::due_soon(doc)?,
+        ..todo::Formatter::new(&priority_map)
+    };
+    let wanted = tags::from_args(matches.values_of("tag").into_iter().flatten());
+    let done_since = if matches.is_present("all") {
+        None
+    } else {
+        Some(formatter.now - Duration::days(DONE_DAYS))
+    };
+    let columns = columns(&workflow, &db.tasks, &wanted, done_since);
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        let mut records = Vec::new();
+        for column in columns.iter() {
+            for task in column.tasks.iter() {
+                records.push(BoardCard {
+                    status: column.status.to_string(),
+                    task: output::TaskRecord::new(task, &db.tasks, formatter.now),
+                });
+            }
+        }
+        return output::print(format, &records);
+    }
+
+    let (term_width, _) = terminal::size().unwrap_or((80, 24));
+    let gaps = GAP.len() * (columns.len() - 1);
+    let width = ((term_width as usize).saturating_sub(gaps) / columns.len()).max(MIN_WIDTH);
+
+    let headers: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            let header = format!("{} ({})", column.status, column.tasks.len());
+            fit(&header, width).bold().to_string()
+        })
+        .collect();
+    println!("{}", headers.join(GAP));
+    let rules: Vec<String> = columns.iter().map(|_| "─".repeat(width)).collect();
+    println!("{}", rules.join(GAP).dimmed());
+
+    let rows = columns
+        .iter()
+        .map(|column| column.tasks.len())
+        .max()
+        .unwrap_or(0);
+    for row in 0..rows {
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|column| match column.tasks.get(row) {
+                Some(task) => {
+                    let card = fit(&task.content, width);
+                    let card = theme::in_color(card.as_str(), formatter.color(task));
+                    if task.is_done() || !db.tasks.open_dependencies(task).is_empty() {
+                        card.dimmed().to_string()
+                    } else {
+                        card.to_string()
+                    }
+                }
+                None => " ".repeat(width),
+            })
+            .collect();
+        println!("{}", cells.join(GAP).trim_end());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tasks_sort_into_columns() {
+        let now = Utc::now();
+        let workflow = Workflow::default();
+        let mut tasks = Tasks::default();
+
+        let backlog = Task::new(String::from("someday"), 0);
+        let mut doing = Task::new(String::from("doing"), 0);
+        workflow.move_task(&mut doing, "In Progress");
+        let mut urgent = Task::new(String::from("urgent"), 4);
+        workflow.move_task(&mut urgent, "In Progress");
+        let mut shipped = Task::new(String::from("shipped"), 0);
+        workflow.move_task(&mut shipped, "Done");
+        let mut long_done = Task::new(String::from("long done"), 0);
+        long_done.completed = Some(now - Duration::days(30));
+        for task in [backlog, doing, urgent, shipped, long_done] {
+            tasks.add(task);
+        }
+
+        let everything = HashSet::new();
+        let recent = Some(now - Duration::days(DONE_DAYS));
+        let names = |columns: &[Column]| -> Vec<Vec<String>> {
+            columns
+                .iter()
+                .map(|column| {
+                    column
+                        .tasks
+                        .iter()
+                        .map(|task| task.content.clone())
+                        .collect()
+                })
+                .collect()
+        };
+        let board = columns(&workflow, &tasks, &everything, recent);
+        assert_eq!(board.len(), 5);
+        assert_eq!(
+            names(&board),
+            vec![
+                vec!["someday"],
+                vec![],
+                vec!["urgent", "doing"],
+                vec![],
+                vec!["shipped"],
+            ]
+        );
+        let board = columns(&workflow, &tasks, &everything, None);
+        assert_eq!(board[4].tasks.len(), 2);
+
+        assert_eq!(fit("a long task", 6), "a lon…");
+        assert_eq!(fit("short", 7), "short  ");
+    }
+}
@@ -0,0 +1,429 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, SubsecRound, Utc};
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::http;
+use crate::ics::{self, RemoteTodo};
+use crate::journal;
+use regia_core::conf::{CaldavSettings, Config};
+use regia_core::db::{self, ROOT_PROJECT};
+use regia_core::error::{self, RegiaError};
+use regia_core::theme::Themed;
+use regia_core::todo::{Task, Tasks};
+
+/// The due date and completion of a task, which is all that syncs.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Synced {
+    pub due: Option<DateTime<Utc>>,
+    pub done: bool,
+}
+
+impl Synced {
+    fn of(task: &Task) -> Self {
+        Self {
+            // iCalendar keeps whole seconds
+            due: task.due.map(|due| due.trunc_subsecs(0)),
+            done: task.is_done(),
+        }
+    }
+
+    fn of_remote(todo: &RemoteTodo) -> Self {
+        Self {
+            due: todo.due,
+            done: todo.done,
+        }
+    }
+}
+
+/// What both sides agreed on after the last sync, per project and task.
+/// Without it a change on one side couldn't be told from one on the other.
+type State = BTreeMap<String, BTreeMap<Uuid, Synced>>;
+
+fn state_path(db_path: &Path) -> PathBuf {
+    db::sibling_path(db_path, ".caldav.json")
+}
+
+fn read_state(path: &Path) -> error::Result<State> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|err| RegiaError::corrupt(format!("{}: {}", path.display(), err))),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(State::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn write_state(path: &Path, state: &State) -> error::Result<()> {
+    let json = serde_json::to_string_pretty(state)
+        .map_err(|err| RegiaError::storage(format!("Serialization failed: {}", err)))?;
+    Ok(db::write_to_disk(path, json.as_bytes())?)
+}
+
+/// The tasks that belong on the calendar: those with a due date, less the
+/// finished instances of repeated tasks that already spawned the next.
+fn candidates(tasks: &Tasks) -> BTreeMap<Uuid, &Task> {
+    tasks
+        .get_tasks()
+        .iter()
+        .filter(|task| task.due.is_some() && task.successor.is_none())
+        .map(|task| (task.id, task))
+        .collect()
+}
+
+/// One side's value of a field in a three-way merge: a side that kept the
+/// base takes the other's change, and when both changed ours wins.
+fn pick<T: PartialEq + Copy>(base: Option<T>, ours: T, theirs: T) -> T {
+    match base {
+        Some(base) if ours == base => theirs,
+        _ => ours,
+    }
+}
+
+/// What a sync does on each side.
+#[derive(Debug, Default, PartialEq)]
+pub struct Plan {
+    /// Tasks to write to the calendar, after `pull` is applied
+    pub push: Vec<Uuid>,
+    /// Local tasks to update from the calendar
+    pub pull: Vec<(Uuid, Synced)>,
+    /// Calendar items whose task was deleted or lost its due date
+    pub delete: Vec<Uuid>,
+    /// The agreed state once the plan has run
+    pub state: BTreeMap<Uuid, Synced>,
+}
+
+/// Works out a sync from the local tasks, the calendar's items by task id
+/// and the state of the last sync. Calendar items regia never synced are
+/// left alone, as they may belong to another project or client.
+pub fn plan(
+    tasks: &Tasks,
+    remote: &BTreeMap<Uuid, RemoteTodo>,
+    base: &BTreeMap<Uuid, Synced>,
+) -> Plan {
+    let local = candidates(tasks);
+    let ids: BTreeSet<Uuid> = local
+        .keys()
+        .chain(remote.keys())
+        .chain(base.keys())
+        .cloned()
+        .collect();
+
+    let mut plan = Plan::default();
+    for id in ids {
+        let base = base.get(&id).copied();
+        match (local.get(&id), remote.get(&id)) {
+            (Some(task), Some(todo)) => {
+                let ours = Synced::of(task);
+                let theirs = Synced::of_remote(todo);
+                let merged = Synced {
+                    due: pick(base.map(|base| base.due), ours.due, theirs.due),
+                    done: pick(base.map(|base| base.done), ours.done, theirs.done),
+                };
+                if merged != ours {
+                    plan.pull.push((id, merged));
+                }
+                if merged != theirs || todo.summary.as_deref() != Some(task.content.as_str()) {
+                    plan.push.push(id);
+                }
+                plan.state.insert(id, merged);
+            }
+            // New here, or removed from the calendar: it's still a task
+            (Some(task), None) => {
+                plan.push.push(id);
+                plan.state.insert(id, Synced::of(task));
+            }
+            (None, Some(_)) if base.is_some() => plan.delete.push(id),
+            _ => {}
+        }
+    }
+    plan
+}
+
+/// The `calendar-data` of every item in a WebDAV multistatus response.
+fn calendar_data(xml: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find("calendar-data") {
+        // Skip to the end of the opening tag, whatever its namespace prefix
+        let after = &rest[start..];
+        let open_end = match after.find('>') {
+            Some(end) => end + 1,
+            None => break,
+        };
+        if after[..open_end].ends_with("/>") {
+            rest = &after[open_end..];
+            continue;
+        }
+        let body = &after[open_end..];
+        let close = match body.find("</") {
+            Some(close) => close,
+            None => break,
+        };
+        let text = body[..close]
+            .trim()
+            .trim_start_matches("<![CDATA[")
+            .trim_end_matches("]]>");
+        found.push(
+            text.replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&#13;", "\r")
+                .replace("&#10;", "\n")
+                .replace("&amp;", "&"),
+        );
+        rest = match body[close..].find('>') {
+            Some(end) => &body[close + end + 1..],
+            None => "",
+        };
+    }
+    found
+}
+
+const QUERY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><c:calendar-data/></d:prop>
+  <c:filter>
+    <c:comp-filter name="VCALENDAR"><c:comp-filter name="VTODO"/></c:comp-filter>
+  </c:filter>
+</c:calendar-query>
+"#;
+
+/// Talks to the calendar collection through `curl`, the way `regia sync`
+/// leaves git to `git`.
+struct Client<'a> {
+    settings: &'a CaldavSettings,
+    url: String,
+}
+
+impl<'a> Client<'a> {
+    fn new(settings: &'a CaldavSettings) -> error::Result<Self> {
+        let url = match &settings.url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => {
+                return Err(RegiaError::Config(String::from(
+                    "set sync.caldav.url to the calendar to sync with",
+                )))
+            }
+        };
+        Ok(Self { settings, url })
+    }
+
+    fn resource(&self, id: &Uuid) -> String {
+        format!("{}/{}.ics", self.url, id)
+    }
+
+    /// Sends a request through [`http::request`], with the credentials
+    /// from the config, and returns the status and body of the response.
+    fn request(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &[&str],
+        body: Option<&str>,
+    ) -> error::Result<(u32, String)> {
+        let mut config = Vec::new();
+        if let Some(user) = &self.settings.user {
+            let password = self.settings.password.as_deref().unwrap_or_default();
+            config.push(http::config_line("user", &format!("{}:{}", user, password)));
+        }
+        http::request(method, url, headers, &config, body)
+    }
+
+    fn check(method: &str, url: &str, (status, body): (u32, String)) -> error::Result<String> {
+        match status {
+            200..=299 => Ok(body),
+            _ => Err(RegiaError::storage(format!(
+                "{} {} answered {}",
+                method, url, status
+            ))),
+        }
+    }
+
+    /// The calendar's to-dos that regia put there, by task id.
+    fn fetch(&self) -> error::Result<BTreeMap<Uuid, RemoteTodo>> {
+        let headers = ["Depth: 1", "Content-Type: application/xml; charset=utf-8"];
+        let response = self.request("REPORT", &self.url, &headers, Some(QUERY))?;
+        let xml = Client::check("REPORT", &self.url, response)?;
+        let mut todos = BTreeMap::new();
+        for data in calendar_data(&xml) {
+            for todo in ics::parse_todos(&data) {
+                let id = todo.uid.strip_suffix("@regia").map(Uuid::parse_str);
+                if let Some(Ok(id)) = id {
+                    todos.insert(id, todo);
+                }
+            }
+        }
+        Ok(todos)
+    }
+
+    fn put(&self, task: &Task, now: DateTime<Utc>) -> error::Result<()> {
+        let url = self.resource(&task.id);
+        let headers = ["Content-Type: text/calendar; charset=utf-8"];
+        let response = self.request("PUT", &url, &headers, Some(&ics::todo_calendar(task, now)))?;
+        Client::check("PUT", &url, response).map(|_| ())
+    }
+
+    fn delete(&self, id: &Uuid) -> error::Result<()> {
+        let url = self.resource(id);
+        match self.request("DELETE", &url, &[], None)? {
+            (404, _) => Ok(()),
+            response => Client::check("DELETE", &url, response).map(|_| ()),
+        }
+    }
+}
+
+/// Handles `regia sync caldav`: pushes the project's dated tasks to
+/// `sync.caldav.url` as to-dos and pulls back due dates and completions
+/// changed there.
+pub fn handle_it(_matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let client = Client::new(&doc.sync.caldav)?;
+    let db_path = db::db_path(doc);
+    let mut db = db::open(doc)?;
+    let before = db.clone();
+    let project = db.active_project().unwrap_or(ROOT_PROJECT).to_string();
+    let state_path = state_path(db_path);
+    let mut state = read_state(&state_path)?;
+
+    let remote = client.fetch()?;
+    let now = Utc::now();
+    let plan = plan(
+        &db.tasks,
+        &remote,
+        state.get(&project).unwrap_or(&BTreeMap::new()),
+    );
+
+    for (id, synced) in plan.pull.iter() {
+        let mut task = db.tasks.get_task_mut(id).unwrap();
+        task.due = synced.due;
+        match (synced.done, task.is_done()) {
+            (true, false) => task.complete(),
+            (false, true) => task.completed = None,
+            _ => {}
+        }
+    }
+    for id in plan.push.iter() {
+        client.put(db.tasks.get_task(id).unwrap(), now)?;
+    }
+    for id in plan.delete.iter() {
+        client.delete(id)?;
+    }
+
+    if !plan.pull.is_empty() {
+        journal::record(db_path, "sync caldav", &before, &mut db)?;
+        db.to_disk(db_path)?;
+    }
+    state.insert(project, plan.state);
+    write_state(&state_path, &state)?;
+    println!(
+        "{} {}, pulled {}, removed {} on {}",
+        "Pushed".header(),
+        plan.push.len(),
+        plan.pull.len(),
+        plan.delete.len(),
+        client.url
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use regia_core::todo::TaskType;
+
+    fn dated(content: &str, due: DateTime<Utc>) -> Task {
+        Task::new_date(
+            String::from(content),
+            0,
+            Some(due),
+            TaskType::Deadline,
+            None,
+        )
+    }
+
+    fn remote(task: &Task, due: DateTime<Utc>, done: bool) -> RemoteTodo {
+        RemoteTodo {
+            uid: ics::uid(task),
+            summary: Some(task.content.clone()),
+            due: Some(due),
+            done,
+        }
+    }
+
+    #[test]
+    fn merges_due_dates_and_completion() {
+        let due = Utc.with_ymd_and_hms(2019, 10, 4, 17, 0, 0).unwrap();
+        let later = due + Duration::days(1);
+        let mut tasks = Tasks::default();
+        let fresh = dated("fresh", due);
+        let moved_here = dated("moved here", later);
+        let done_there = dated("done there", due);
+        let both = dated("both", later);
+        let deleted = Uuid::new_v4();
+        let foreign = dated("someone else's", due);
+        for task in [&fresh, &moved_here, &done_there, &both] {
+            tasks.add((*task).clone());
+        }
+        tasks.add(Task::new(String::from("undated"), 0));
+
+        let was = Synced {
+            due: Some(due),
+            done: false,
+        };
+        let base: BTreeMap<Uuid, Synced> = [moved_here.id, done_there.id, both.id, deleted]
+            .iter()
+            .map(|id| (*id, was))
+            .collect();
+        let mut calendar = BTreeMap::new();
+        calendar.insert(moved_here.id, remote(&moved_here, due, false));
+        calendar.insert(done_there.id, remote(&done_there, due, true));
+        calendar.insert(both.id, remote(&both, due + Duration::days(2), false));
+        calendar.insert(deleted, remote(&foreign, due, false));
+        calendar.insert(foreign.id, remote(&foreign, due, false));
+
+        let plan = plan(&tasks, &calendar, &base);
+        let mut push = plan.push.clone();
+        push.sort();
+        let mut expected = vec![fresh.id, moved_here.id, both.id];
+        expected.sort();
+        assert_eq!(push, expected);
+        assert_eq!(
+            plan.pull,
+            [(
+                done_there.id,
+                Synced {
+                    due: Some(due),
+                    done: true
+                }
+            )]
+        );
+        assert_eq!(plan.delete, [deleted]);
+        // Both changed the due date, and ours wins
+        assert_eq!(plan.state[&both.id].due, Some(later));
+        assert!(!plan.state.contains_key(&foreign.id));
+    }
+
+    #[test]
+    fn reads_multistatus() {
+        let xml = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">
+  <d:response><d:propstat><d:prop>
+    <cal:calendar-data>BEGIN:VCALENDAR&#13;
+BEGIN:VTODO&#13;
+UID:a&amp;b&#13;
+END:VTODO&#13;
+END:VCALENDAR&#13;
+</cal:calendar-data>
+  </d:prop></d:propstat></d:response>
+  <d:response><d:propstat><d:prop><cal:calendar-data/></d:prop></d:propstat></d:response>
+</d:multistatus>"#;
+        let data = calendar_data(xml);
+        assert_eq!(data.len(), 1);
+        assert_eq!(ics::parse_todos(&data[0])[0].uid, "a&b");
+    }
+}
@@ -0,0 +1,106 @@
+//! Reading the system clipboard, for `note add --clipboard`. regia asks
+//! whichever of the usual paste tools is installed: `pbpaste` on macOS,
+//! `wl-paste` under Wayland, `xclip` or `xsel` under X11, and PowerShell's
+//! `Get-Clipboard` on Windows and WSL.
+//!
+//! No clipboard crate such as arboard is available to this build, so the
+//! tools are run as commands; that also spares regia linking against X11
+//! and Wayland for the one command that wants the clipboard.
+
+use std::io::ErrorKind;
+use std::process::Command;
+
+use regia_core::error::{self, RegiaError};
+
+/// The paste tools to try, in order, with the arguments that make each
+/// print the clipboard's text, on macOS or not and in a Wayland session or
+/// not.
+fn pasters(macos: bool, wayland: bool) -> Vec<(&'static str, &'static [&'static str])> {
+    let mut pasters: Vec<(&str, &[&str])> = Vec::new();
+    if macos {
+        pasters.push(("pbpaste", &[]));
+    }
+    // A Wayland session usually runs X11 programs too, but its own
+    // clipboard is the one the user copied to
+    if wayland {
+        pasters.push(("wl-paste", &["--no-newline"]));
+    }
+    pasters.push(("xclip", &["-selection", "clipboard", "-out"]));
+    pasters.push(("xsel", &["--clipboard", "--output"]));
+    pasters.push((
+        "powershell.exe",
+        &["-NoProfile", "-Command", "Get-Clipboard"],
+    ));
+    pasters
+}
+
+/// The text on the clipboard, failing when no paste tool is installed or
+/// the clipboard holds nothing.
+pub fn paste() -> error::Result<String> {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    for (program, args) in pasters(cfg!(target_os = "macos"), wayland) {
+        let output = match Command::new(program).args(args).output() {
+            Ok(output) => output,
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+        if !output.status.success() {
+            return Err(RegiaError::storage(format!(
+                "{} exited with {}: {}",
+                program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        return text(output.stdout);
+    }
+    Err(RegiaError::NotFound(String::from(
+        "no clipboard tool found, install wl-clipboard, xclip or xsel",
+    )))
+}
+
+/// What a paste tool printed, as the clipboard's text.
+fn text(stdout: Vec<u8>) -> error::Result<String> {
+    let text = String::from_utf8(stdout)
+        .map_err(|_| RegiaError::Parse(String::from("the clipboard doesn't hold text")))?;
+    // PowerShell ends what it prints with CRLF
+    let text = text.trim_end_matches(['\r', '\n']).to_string();
+    if text.trim().is_empty() {
+        return Err(RegiaError::NotFound(String::from("the clipboard is empty")));
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn programs(macos: bool, wayland: bool) -> Vec<&'static str> {
+        pasters(macos, wayland)
+            .into_iter()
+            .map(|(program, _)| program)
+            .collect()
+    }
+
+    #[test]
+    fn tries_the_sessions_own_clipboard_first() {
+        assert_eq!(programs(false, false), ["xclip", "xsel", "powershell.exe"]);
+        assert_eq!(
+            programs(false, true),
+            ["wl-paste", "xclip", "xsel", "powershell.exe"]
+        );
+        assert_eq!(programs(true, false)[0], "pbpaste");
+    }
+
+    #[test]
+    fn trims_only_the_trailing_newline() {
+        let pasted = text(b"  first\r\nsecond\r\n".to_vec()).unwrap();
+        assert_eq!(pasted, "  first\r\nsecond");
+        assert_eq!(text(b"line\n\n".to_vec()).unwrap(), "line");
+        assert!(matches!(
+            text(b" \r\n".to_vec()),
+            Err(RegiaError::NotFound(_))
+        ));
+        assert!(matches!(text(vec![0xff, 0xfe]), Err(RegiaError::Parse(_))));
+    }
+}
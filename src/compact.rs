@@ -0,0 +1,63 @@
+//! `regia db compact`: writes the database whole, folding in its log and
+//! compressing it or not as `contents.compress` says, for when that
+//! setting changes and the file shouldn't wait for its next compaction.
+
+use std::fs;
+use std::io::ErrorKind as IOErrorKind;
+use std::path::Path;
+
+use clap::ArgMatches;
+use colored::*;
+
+use crate::backup::fmt_bytes;
+use regia_core::conf::{self, Config};
+use regia_core::db::{self, Database};
+use regia_core::error;
+use regia_core::theme::Themed;
+use regia_core::wal;
+
+/// How much the database and its log take up.
+fn size(db_path: &Path) -> error::Result<u64> {
+    let mut total = 0;
+    for path in [db_path.to_path_buf(), wal::log_path(db_path)] {
+        match fs::metadata(path) {
+            Ok(meta) => total += meta.len(),
+            Err(err) if err.kind() == IOErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(total)
+}
+
+pub fn handle_it(_matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    db::flush()?;
+    let before = size(db_path)?;
+    let how = if conf::compress(doc) {
+        "compressed"
+    } else {
+        "uncompressed"
+    };
+    if db::is_dry() {
+        println!(
+            "Would compact {} {}",
+            db_path.display(),
+            format!("({}, {})", fmt_bytes(before), how).dimmed()
+        );
+        return Ok(());
+    }
+    Database::load(db_path)?.compact(db_path)?;
+    println!(
+        "{} {} {}",
+        "Compacted".header(),
+        db_path.display(),
+        format!(
+            "({} → {}, {})",
+            fmt_bytes(before),
+            fmt_bytes(size(db_path)?),
+            how
+        )
+        .dimmed()
+    );
+    Ok(())
+}
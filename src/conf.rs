@@ -1,2 +0,0 @@
-use std::collections::HashMap;
-pub type Config = HashMap<String, HashMap<String, String>>;
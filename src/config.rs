@@ -0,0 +1,151 @@
+use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use clap::ArgMatches;
+use serde_json::Value;
+
+use regia_core::conf::{self, Config};
+use regia_core::error::{self, RegiaError};
+use regia_core::theme::Themed;
+
+/// Parses YAML config text: empty text is an empty config, and unknown
+/// keys or bad values are errors that name `path`.
+pub fn parse(path: &Path, text: &str) -> error::Result<Config> {
+    let error = |err: String| RegiaError::Config(format!("{}: {}", path.display(), err));
+    if text.trim().is_empty() {
+        return Ok(Config::default());
+    }
+    let doc: Config = serde_yaml::from_str(text).map_err(|err| error(err.to_string()))?;
+    doc.check().map_err(error)?;
+    Ok(doc)
+}
+
+/// Reads the config file at `path`. A missing file is an empty config
+/// unless `required`.
+pub fn read(path: &Path, required: bool) -> error::Result<Config> {
+    match read_to_string(path) {
+        Ok(text) => parse(path, &text),
+        Err(err) if err.kind() == ErrorKind::NotFound && !required => Ok(Config::default()),
+        Err(err) => Err(RegiaError::Config(format!("{}: {}", path.display(), err))),
+    }
+}
+
+/// The config in effect: the file at `path` with the nearest project
+/// directory's `.regia.yml` laid over it.
+pub fn load(path: &Path, required: bool) -> error::Result<Config> {
+    let mut doc = read(path, required)?;
+    if let Some(dir) = conf::discover(&std::env::current_dir()?) {
+        let local = read(&dir.join(conf::LOCAL_CONFIG), false)?;
+        for section in conf::overlay(&mut doc, &dir, local) {
+            eprintln!(
+                "{} {} sets {}, which only the global config may, ignoring it",
+                "warning:".warning(),
+                dir.join(conf::LOCAL_CONFIG).display(),
+                section
+            );
+        }
+    }
+    Ok(doc)
+}
+
+/// Drops empty sections, which only clutter the YAML.
+fn pruned(mut value: Value) -> Value {
+    if let Some(sections) = value.as_object_mut() {
+        sections.retain(|_, section| section.as_object().is_none_or(|values| !values.is_empty()));
+    }
+    value
+}
+
+fn to_yaml(doc: &Config) -> error::Result<String> {
+    serde_yaml::to_string(&pruned(doc.to_value()))
+        .map_err(|err| RegiaError::storage(format!("Serialization failed: {}", err)))
+}
+
+fn write(path: &Path, doc: &Config) -> error::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    Ok(std::fs::write(path, to_yaml(doc)?)?)
+}
+
+fn print_value(value: &Value) {
+    match value {
+        Value::String(text) => println!("{}", text),
+        Value::Object(_) => print!("{}", serde_yaml::to_string(value).unwrap_or_default()),
+        other => println!("{}", other),
+    }
+}
+
+fn handle_config_get(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let key = matches.value_of("key").unwrap();
+    let value = match doc.get(key).map_err(RegiaError::Config)? {
+        Some(value) => Some(value),
+        None => Config::defaults().get(key).map_err(RegiaError::Config)?,
+    };
+    if let Some(value) = value {
+        print_value(&value);
+    }
+    Ok(())
+}
+
+fn handle_config_set(matches: &ArgMatches, path: &Path, dry: bool) -> error::Result<()> {
+    let key = matches.value_of("key").unwrap();
+    let value = matches.value_of("value").unwrap();
+    let mut doc = read(path, false)?;
+    doc.set(key, value).map_err(RegiaError::Config)?;
+    if !dry {
+        write(path, &doc)?;
+    }
+    println!(
+        "{} {} to {} in {}",
+        if dry { "Would set" } else { "Set" }.header(),
+        key,
+        value,
+        path.display()
+    );
+    Ok(())
+}
+
+fn handle_config_init(matches: &ArgMatches, path: &Path, dry: bool) -> error::Result<()> {
+    if path.exists() && !matches.is_present("force") {
+        return Err(RegiaError::Conflict(format!(
+            "{} already exists, pass --force to replace it",
+            path.display()
+        )));
+    }
+    if !dry {
+        write(path, &Config::defaults())?;
+    }
+    let wrote = if dry { "Would write" } else { "Wrote" };
+    println!("{} {}", wrote.header(), path.display());
+    Ok(())
+}
+
+/// Handles `regia config`. It reads and writes the file at `path` itself,
+/// so that `init` works when that file is missing or broken. A `dry` run
+/// checks what `set` and `init` would write but leaves the file be.
+pub fn handle_it(
+    matches: &ArgMatches,
+    path: &Path,
+    required: bool,
+    dry: bool,
+) -> error::Result<()> {
+    if let Some(matches) = matches.subcommand_matches("show") {
+        let doc = if matches.is_present("defaults") {
+            Config::defaults()
+        } else {
+            load(path, required)?
+        };
+        print!("{}", to_yaml(&doc)?);
+        Ok(())
+    } else if let Some(matches) = matches.subcommand_matches("get") {
+        handle_config_get(matches, &load(path, required)?)
+    } else if let Some(matches) = matches.subcommand_matches("set") {
+        handle_config_set(matches, path, dry)
+    } else if let Some(matches) = matches.subcommand_matches("init") {
+        handle_config_init(matches, path, dry)
+    } else {
+        unreachable!();
+    }
+}
@@ -0,0 +1,150 @@
+//! `regia context`: the contexts tasks can be done in, and the sticky one
+//! `task ls`, `next` and `agenda` keep to until it is cleared.
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+use colored::*;
+use serde::{Deserialize, Serialize};
+
+use crate::output::{self, Output};
+use regia_core::conf::Config;
+use regia_core::contexts;
+use regia_core::db;
+use regia_core::error::{self, RegiaError};
+use regia_core::theme::Themed;
+
+/// Kept beside the database rather than in it, since where you are isn't
+/// something to sync, undo or share between projects.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct Sticky {
+    /// Without its `@`
+    pub context: Option<String>,
+}
+
+fn sticky_path(db_path: &Path) -> PathBuf {
+    db::sibling_path(db_path, ".context.json")
+}
+
+fn read_sticky(path: &Path) -> error::Result<Sticky> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|err| RegiaError::corrupt(format!("{}: {}", path.display(), err))),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Sticky::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn write_sticky(path: &Path, sticky: &Sticky) -> error::Result<()> {
+    if db::is_dry() {
+        return Ok(());
+    }
+    let json = serde_json::to_string_pretty(sticky)
+        .map_err(|err| RegiaError::storage(format!("Serialization failed: {}", err)))?;
+    Ok(db::write_to_disk(path, json.as_bytes())?)
+}
+
+/// The context a listing keeps to: the one `given` with `--context`, else
+/// the sticky one unless `any` says to ignore it.
+pub fn wanted(given: Option<&str>, any: bool, doc: &Config) -> error::Result<Option<String>> {
+    match given {
+        Some(given) => Ok(Some(contexts::normalize(given))),
+        None if any => Ok(None),
+        None => Ok(read_sticky(&sticky_path(db::db_path(doc)))?.context),
+    }
+}
+
+/// Says under a text listing that the sticky context narrowed it, which
+/// is easy to forget having set.
+pub fn print_sticky(given: Option<&str>, wanted: Option<&str>) {
+    if let (None, Some(context)) = (given, wanted) {
+        let hint = format!("(only tasks for @{}, see regia context clear)", context);
+        println!("{}", hint.dimmed());
+    }
+}
+
+#[derive(Serialize)]
+struct ContextRecord {
+    context: String,
+    tasks: usize,
+}
+
+impl output::Record for ContextRecord {
+    fn plain(&self) -> String {
+        output::fields(&[self.context.clone(), self.tasks.to_string()])
+    }
+}
+
+fn handle_ls(doc: &Config) -> error::Result<()> {
+    let db = db::open(doc)?;
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for task in db.tasks.get_tasks().iter().filter(|task| !task.is_done()) {
+        for context in task.contexts.iter() {
+            *counts.entry(context).or_default() += 1;
+        }
+    }
+    let records: Vec<ContextRecord> = counts
+        .into_iter()
+        .map(|(context, tasks)| ContextRecord {
+            context: context.to_string(),
+            tasks,
+        })
+        .collect();
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        return output::print(format, &records);
+    }
+    if records.is_empty() {
+        println!("No open task has a context");
+    }
+    let sticky = read_sticky(&sticky_path(db::db_path(doc)))?.context;
+    for record in records {
+        let mark = if sticky.as_deref() == Some(record.context.as_str()) {
+            "*"
+        } else {
+            " "
+        };
+        println!(
+            "{} {} {}",
+            mark,
+            format!("@{}", record.context).context(),
+            format!("({})", record.tasks).dimmed()
+        );
+    }
+    Ok(())
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let path = sticky_path(db::db_path(doc));
+    if let Some(matches) = matches.subcommand_matches("set") {
+        let context = contexts::normalize(matches.value_of("context").unwrap());
+        if context.is_empty() {
+            return Err(RegiaError::Parse(String::from("the context has no name")));
+        }
+        println!("{} @{}", "Working in".header(), context.bold());
+        write_sticky(
+            &path,
+            &Sticky {
+                context: Some(context),
+            },
+        )
+    } else if matches.subcommand_matches("clear").is_some() {
+        if read_sticky(&path)?.context.is_some() {
+            println!("{}", "Cleared the context".header());
+        }
+        write_sticky(&path, &Sticky::default())
+    } else if matches.subcommand_matches("show").is_some() {
+        match read_sticky(&path)?.context {
+            Some(context) => println!("@{}", context),
+            None => println!("No context set"),
+        }
+        Ok(())
+    } else if matches.subcommand_matches("ls").is_some() {
+        handle_ls(doc)
+    } else {
+        unreachable!();
+    }
+}
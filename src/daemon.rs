@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use chrono::{DateTime, Duration, Local, Utc};
+use clap::ArgMatches;
+use notify_rust::Notification;
+use uuid::Uuid;
+
+use crate::hooks::{self, Event};
+use crate::notify::{self, Notifier};
+use regia_core::conf::{self, Config};
+use regia_core::db;
+use regia_core::error::{self, RegiaError};
+use regia_core::todo::Task;
+
+/// When each reminder for `task` should fire, earliest first, if it has a
+/// due date.
+pub fn reminder_times(task: &Task, default_offset: Duration) -> Vec<DateTime<Utc>> {
+    let due = match task.due {
+        Some(due) => due,
+        None => return Vec::new(),
+    };
+    let offsets = task.reminder_offsets();
+    if offsets.is_empty() {
+        return vec![due - default_offset];
+    }
+    offsets
+        .iter()
+        .map(|seconds| due - Duration::seconds(*seconds))
+        .collect()
+}
+
+/// The reminders already sent, by task and the time each was due to fire.
+/// A reminder moves with its task's due date, so a changed date fires again.
+pub type Fired = BTreeMap<Uuid, BTreeSet<DateTime<Utc>>>;
+
+/// Kept beside the database, since the daemon never writes the database
+/// itself.
+fn fired_path(db_path: &Path) -> PathBuf {
+    db::sibling_path(db_path, ".reminders.json")
+}
+
+fn read_fired(path: &Path) -> error::Result<Fired> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|err| RegiaError::corrupt(format!("{}: {}", path.display(), err))),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Fired::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn write_fired(path: &Path, fired: &Fired) -> error::Result<()> {
+    let json = serde_json::to_string_pretty(fired)
+        .map_err(|err| RegiaError::storage(format!("Serialization failed: {}", err)))?;
+    Ok(db::write_to_disk(path, json.as_bytes())?)
+}
+
+/// Open tasks with a reminder that came due by `now`, before the deadline
+/// itself, and hasn't fired yet, each with the reminder times it reached.
+/// Those are all recorded in `fired`, so that a daemon that was stopped
+/// for a while sends one notification per task rather than a burst.
+pub fn due_reminders<'a>(
+    tasks: &'a [Task],
+    default_offset: Duration,
+    fired: &mut Fired,
+    now: DateTime<Utc>,
+) -> Vec<&'a Task> {
+    let mut firing = Vec::new();
+    for task in tasks.iter().filter(|task| !task.is_done()) {
+        let due = match task.due {
+            Some(due) if now <= due => due,
+            _ => continue,
+        };
+        let sent = fired.entry(task.id).or_default();
+        let mut reached = false;
+        for time in reminder_times(task, default_offset) {
+            if time <= now && time <= due && sent.insert(time) {
+                reached = true;
+            }
+        }
+        if reached {
+            firing.push(task);
+        }
+    }
+    fired.retain(|_, sent| !sent.is_empty());
+    firing
+}
+
+/// Forgets reminders of tasks that are gone, done or past due.
+fn prune(fired: &mut Fired, tasks: &[Task], now: DateTime<Utc>) {
+    let live: BTreeSet<Uuid> = tasks
+        .iter()
+        .filter(|task| !task.is_done() && task.due.is_some_and(|due| now <= due))
+        .map(|task| task.id)
+        .collect();
+    fired.retain(|id, _| live.contains(id));
+}
+
+fn notify(task: &Task, now: DateTime<Utc>) {
+    let left = task.due.unwrap() - now;
+    let body = format!("{}\ndue in {} min", task.content, left.num_minutes().max(0));
+    if let Err(err) = Notification::new()
+        .appname("regia")
+        .summary("Task due soon")
+        .body(&body)
+        .show()
+    {
+        eprintln!("regia: could not show notification: {}", err);
+    }
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let default_offset =
+        conf::duration("daemon.remind", &doc.daemon.remind)?.unwrap_or(Duration::minutes(15));
+    let interval =
+        conf::duration("daemon.interval", &doc.daemon.interval)?.unwrap_or(Duration::minutes(1));
+    let once = matches.is_present("once");
+
+    let fired_path = fired_path(db::db_path(doc));
+    let mut fired = read_fired(&fired_path)?;
+    let notifier = Notifier::new(doc);
+    let alerts = notifier
+        .as_ref()
+        .filter(|_| doc.notify.overdue != Some(false));
+    let agenda_at = match (&notifier, &doc.notify.agenda_at) {
+        (Some(_), Some(at)) => Some(conf::agenda_at(at).map_err(RegiaError::Config)?),
+        _ => None,
+    };
+    let posted_path = notify::posted_path(db::db_path(doc));
+    let mut posted = notify::read_posted(&posted_path)?;
+    let overdue_path = hooks::overdue_path(db::db_path(doc));
+    let watch_overdue = doc.hooks.task_overdue.is_some() || alerts.is_some();
+    let mut reported = if watch_overdue {
+        hooks::read_overdue(&overdue_path)?
+    } else {
+        None
+    };
+    loop {
+        let db = db::open(doc)?;
+        let now = Utc::now();
+        let before = fired.clone();
+        prune(&mut fired, db.tasks.get_tasks(), now);
+        for task in due_reminders(db.tasks.get_tasks(), default_offset, &mut fired, now) {
+            notify(task, now);
+        }
+        if fired != before {
+            write_fired(&fired_path, &fired)?;
+        }
+
+        if watch_overdue {
+            // The first check only notes what is already overdue, rather
+            // than reporting a backlog of old deadlines at once
+            let first = reported.is_none();
+            let seen = reported.get_or_insert_with(hooks::Overdue::new);
+            let before = seen.clone();
+            let overdue = hooks::newly_overdue(db.tasks.get_tasks(), seen, now);
+            if !first && !overdue.is_empty() {
+                for task in overdue.iter() {
+                    let project = db.active_project();
+                    hooks::run(
+                        doc,
+                        Event::TaskOverdue,
+                        &db.tasks,
+                        Some(task),
+                        None,
+                        project,
+                    );
+                }
+                if let Some(alerts) = alerts {
+                    if let Err(err) = alerts.post(&notify::overdue_message(&overdue)) {
+                        eprintln!("regia: could not post overdue tasks: {}", err);
+                    }
+                }
+            }
+            if first || *seen != before {
+                hooks::write_overdue(&overdue_path, seen)?;
+            }
+        }
+
+        if let (Some(notifier), Some(at)) = (&notifier, agenda_at) {
+            let local = now.with_timezone(&Local).naive_local();
+            if notify::agenda_due(at, posted.agenda, local) {
+                match notifier.post(&notify::agenda_message(&db, doc, now)?) {
+                    Ok(()) => {
+                        posted.agenda = Some(local.date());
+                        notify::write_posted(&posted_path, &posted)?;
+                    }
+                    Err(err) => eprintln!("regia: could not post the agenda: {}", err),
+                }
+            }
+        }
+
+        if once {
+            return Ok(());
+        }
+        thread::sleep(
+            interval
+                .to_std()
+                .unwrap_or(std::time::Duration::from_secs(60)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regia_core::todo::TaskType;
+
+    #[test]
+    fn reminder_windows() {
+        let now = Utc::now();
+        let soon = Task::new_date(
+            String::from("soon"),
+            0,
+            Some(now + Duration::minutes(10)),
+            TaskType::Deadline,
+            None,
+        );
+        let mut early = Task::new_date(
+            String::from("early"),
+            0,
+            Some(now + Duration::hours(2)),
+            TaskType::Deadline,
+            None,
+        );
+        early.remind = Some(Duration::hours(3).num_seconds());
+        let mut twice = Task::new_date(
+            String::from("twice"),
+            0,
+            Some(now + Duration::minutes(30)),
+            TaskType::Deadline,
+            None,
+        );
+        twice.reminders = vec![
+            Duration::hours(1).num_seconds(),
+            Duration::minutes(10).num_seconds(),
+        ];
+        let later = Task::new_date(
+            String::from("later"),
+            0,
+            Some(now + Duration::hours(2)),
+            TaskType::Deadline,
+            None,
+        );
+        let past = Task::new_date(
+            String::from("past"),
+            0,
+            Some(now - Duration::minutes(1)),
+            TaskType::Deadline,
+            None,
+        );
+
+        let mut tasks = vec![soon, early, later, past, twice];
+        let mut fired = Fired::new();
+        let firing = |tasks: &[Task], fired: &mut Fired, now| -> Vec<String> {
+            due_reminders(tasks, Duration::minutes(15), fired, now)
+                .iter()
+                .map(|task| task.content.clone())
+                .collect()
+        };
+        assert_eq!(firing(&tasks, &mut fired, now), ["soon", "early", "twice"]);
+        // Each reminder fires once, and the next offset fires in its turn
+        assert!(firing(&tasks, &mut fired, now).is_empty());
+        let later_on = now + Duration::minutes(21);
+        assert_eq!(firing(&tasks, &mut fired, later_on), ["twice"]);
+
+        // Moving the due date sets the reminder up again
+        tasks[0].due = Some(now + Duration::minutes(5));
+        assert_eq!(firing(&tasks, &mut fired, now), ["soon"]);
+        prune(&mut fired, &tasks, later_on);
+        assert!(!fired.contains_key(&tasks[0].id));
+    }
+}
@@ -0,0 +1,142 @@
+//! `regia db ls`: the databases named in the `databases` config, which
+//! `--db` picks between, and what each holds.
+
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use clap::ArgMatches;
+use colored::*;
+use serde::Serialize;
+
+use crate::backup;
+use crate::output::{self, Output};
+use regia_core::conf::Config;
+use regia_core::dates;
+use regia_core::db::{self, Database};
+use regia_core::error;
+use regia_core::theme::Themed;
+use regia_core::wal;
+
+#[derive(Serialize)]
+struct DatabaseRecord {
+    /// Its name in `databases`, if it has one
+    name: Option<String>,
+    path: String,
+    /// Whether it is the one this command would use
+    current: bool,
+    /// Absent when there is no such file
+    tasks: Option<usize>,
+    notes: Option<usize>,
+    /// When it or its log was last written
+    modified: Option<DateTime<Utc>>,
+    /// Why it couldn't be read, if it couldn't
+    error: Option<String>,
+}
+
+impl output::Record for DatabaseRecord {
+    fn plain(&self) -> String {
+        let count = |count: Option<usize>| count.map(|count| count.to_string());
+        output::fields(&[
+            self.name.clone().unwrap_or_default(),
+            self.path.clone(),
+            if self.current { "*" } else { "" }.to_string(),
+            count(self.tasks).unwrap_or_default(),
+            count(self.notes).unwrap_or_default(),
+            self.modified.map(|at| at.to_rfc3339()).unwrap_or_default(),
+        ])
+    }
+}
+
+/// When the database at `path` or its log last changed.
+fn modified(path: &Path) -> Option<DateTime<Utc>> {
+    [path.to_path_buf(), wal::log_path(path)]
+        .iter()
+        .filter_map(|path| fs::metadata(path).and_then(|meta| meta.modified()).ok())
+        .max()
+        .map(DateTime::<Utc>::from)
+}
+
+impl DatabaseRecord {
+    fn new(name: Option<&str>, path: &str, current: &Path) -> Self {
+        let mut record = Self {
+            name: name.map(String::from),
+            path: path.to_string(),
+            current: Path::new(path) == current,
+            tasks: None,
+            notes: None,
+            modified: None,
+            error: None,
+        };
+        match Database::from_disk(path) {
+            Ok(db) => {
+                let (tasks, notes) = backup::counts(&db);
+                record.tasks = Some(tasks);
+                record.notes = Some(notes);
+                record.modified = modified(Path::new(path));
+            }
+            Err(err) if err.is_missing_file() => {}
+            Err(err) => record.error = Some(err.to_string()),
+        }
+        record
+    }
+
+    fn summary(&self, now: DateTime<Utc>) -> String {
+        if let Some(error) = &self.error {
+            return format!("can't read: {}", error);
+        }
+        let (Some(tasks), Some(notes)) = (self.tasks, self.notes) else {
+            return String::from("not created yet");
+        };
+        let mut summary = format!(
+            "{} task{}, {} note{}",
+            tasks,
+            if tasks == 1 { "" } else { "s" },
+            notes,
+            if notes == 1 { "" } else { "s" }
+        );
+        if let Some(at) = self.modified {
+            summary.push_str(&format!(", changed {} ago", dates::fmt_duration(now - at)));
+        }
+        summary
+    }
+}
+
+pub fn handle_it(_matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    // So that the database in use is counted as it stands
+    db::flush()?;
+    let current = db::db_path(doc);
+    let mut records: Vec<DatabaseRecord> = doc
+        .databases
+        .iter()
+        .map(|(name, path)| DatabaseRecord::new(Some(name), path, current))
+        .collect();
+    if !records.iter().any(|record| record.current) {
+        let path = current.to_string_lossy();
+        records.insert(0, DatabaseRecord::new(None, &path, current));
+    }
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        return output::print(format, &records);
+    }
+    let now = Utc::now();
+    for record in records.iter() {
+        let marker = if record.current { "*" } else { " " };
+        let label = match &record.name {
+            Some(name) => format!("{} {}", name, record.path.dimmed()),
+            None => record.path.clone(),
+        };
+        println!(
+            "{} {} ({})",
+            marker.header(),
+            if record.current {
+                label.bold().to_string()
+            } else {
+                label
+            },
+            record.summary(now)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,189 @@
+//! `regia journal`: one note a day, started from a template.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ArgMatches;
+use colored::*;
+
+use crate::editor;
+use crate::journal;
+use crate::output::{self, Output};
+
+use regia_core::conf::{self, Config};
+use regia_core::dates;
+use regia_core::db;
+use regia_core::error;
+use regia_core::note::{Note, Notes};
+use regia_core::tags;
+use regia_core::theme::Themed;
+use regia_core::todo::Tasks;
+
+fn entry_tag(doc: &Config) -> String {
+    tags::normalize(doc.journal.tag.as_deref().unwrap_or("journal"))
+}
+
+/// Fills the placeholders of `template` for the day of `now`.
+pub fn fill(template: &str, tasks: &Tasks, now: DateTime<Utc>) -> String {
+    let open: Vec<_> = tasks
+        .get_tasks()
+        .iter()
+        .filter(|task| !task.is_done())
+        .collect();
+    let overdue = open
+        .iter()
+        .filter(|task| task.due.is_some_and(|due| due < now))
+        .count();
+    let due_today = open
+        .iter()
+        .filter(|task| {
+            task.due
+                .is_some_and(|due| due.date_naive() == now.date_naive())
+        })
+        .count();
+    template
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{weekday}", &now.format("%A").to_string())
+        .replace("{open}", &open.len().to_string())
+        .replace("{overdue}", &overdue.to_string())
+        .replace("{due_today}", &due_today.to_string())
+}
+
+/// The journal entries in `notes`, oldest first.
+fn entries<'a>(notes: &'a Notes, tag: &str) -> Vec<&'a Note> {
+    let mut entries: Vec<&Note> = notes
+        .get_notes()
+        .iter()
+        .filter(|note| note.tags.contains(tag))
+        .collect();
+    entries.sort_by_key(|note| note.created);
+    entries
+}
+
+/// The entry written on `day`, if there is one.
+fn entry_on<'a>(notes: &'a Notes, tag: &str, day: NaiveDate) -> Option<&'a Note> {
+    entries(notes, tag)
+        .into_iter()
+        .find(|note| note.created.date_naive() == day)
+}
+
+fn handle_journal_list(db: &db::Database, doc: &Config) -> error::Result<()> {
+    let entries = entries(&db.notes, &entry_tag(doc));
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        let records: Vec<output::NoteRecord> = entries
+            .iter()
+            .map(|note| output::NoteRecord::new(note))
+            .collect();
+        return output::print(format, &records);
+    }
+
+    if entries.is_empty() {
+        println!("No journal entries");
+    }
+    for note in entries {
+        println!(
+            "{}",
+            format!(
+                "{} {}",
+                note.created.format("%a"),
+                dates::locale().day(note.created.date_naive())
+            )
+            .header()
+            .bold()
+        );
+        println!("{}", note.fmt());
+    }
+    Ok(())
+}
+
+/// Starts today's entry if there is none yet, then adds `text` to it or
+/// opens it in the editor.
+fn handle_journal_write(
+    matches: &ArgMatches,
+    db: &mut db::Database,
+    doc: &Config,
+) -> error::Result<()> {
+    let tag = entry_tag(doc);
+    let now = Utc::now();
+    let id = match entry_on(&db.notes, &tag, now.date_naive()) {
+        Some(note) => note.id,
+        None => {
+            let template = doc
+                .journal
+                .template
+                .as_deref()
+                .unwrap_or(conf::JOURNAL_TEMPLATE);
+            let mut note = Note::new(&fill(template, &db.tasks, now));
+            note.tags.insert(tag.clone());
+            let id = note.id;
+            db.notes.add(note);
+            println!("{}", "Started today's entry".header());
+            id
+        }
+    };
+    let mut note = db.notes.get_note_mut(&id).unwrap();
+
+    let text: Vec<&str> = matches.values_of("text").into_iter().flatten().collect();
+    let edited = if matches.is_present("edit") {
+        let edited = editor::edit_text(&note.content, ".md")?;
+        Some(edited.trim_end_matches('\n').to_string())
+    } else if !text.is_empty() {
+        Some(format!("{}\n\n{}", note.content, text.join(" ")))
+    } else {
+        None
+    };
+    if let Some(edited) = edited.filter(|edited| *edited != note.content) {
+        note.set_content(&edited, conf::note_revisions(doc));
+        note.tags.extend(tags::extract(&edited));
+        println!("{}", "Added to today's entry".header());
+    }
+    println!("{}", note.fmt());
+    Ok(())
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let mut db = db::open(doc)?;
+    if matches.subcommand_matches("ls").is_some() {
+        return handle_journal_list(&db, doc);
+    }
+
+    let before = db.clone();
+    handle_journal_write(matches, &mut db, doc)?;
+    if db == before {
+        return Ok(());
+    }
+    journal::record(db_path, "journal", &before, &mut db)?;
+    db.to_disk(db_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use regia_core::todo::Task;
+
+    #[test]
+    fn fills_the_template() {
+        let now = Utc.with_ymd_and_hms(2019, 10, 2, 12, 0, 0).unwrap();
+        let mut tasks = Tasks::default();
+        let mut late = Task::new(String::from("late"), 0);
+        late.due = Some(now - Duration::days(1));
+        let mut today = Task::new(String::from("today"), 0);
+        today.due = Some(now + Duration::hours(2));
+        let mut done = Task::new(String::from("done"), 0);
+        done.completed = Some(now);
+        for task in [late, today, done] {
+            tasks.add(task);
+        }
+
+        assert_eq!(
+            fill(conf::JOURNAL_TEMPLATE, &tasks, now),
+            "# Wednesday 2019-10-02\n\n2 open tasks, 1 overdue"
+        );
+        assert_eq!(
+            fill("{due_today} due, {unknown}", &tasks, now),
+            "1 due, {unknown}"
+        );
+    }
+}
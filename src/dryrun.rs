@@ -0,0 +1,284 @@
+//! `--dry-run`: runs a command against a database held in memory, see
+//! [`db::hold_dry`], and says what it would have saved.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use colored::*;
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use regia_core::db::{self, Database, Project, ROOT_PROJECT};
+use regia_core::error::{self, RegiaError};
+use regia_core::note::Note;
+use regia_core::theme::Themed;
+use regia_core::todo::Task;
+
+/// Commands a dry run can't keep from having effects beyond the database.
+pub const REFUSED: [&str; 5] = ["shell", "daemon", "sync", "notify", "backup"];
+
+/// The longest a field value is shown before it is cut short.
+const VALUE_WIDTH: usize = 60;
+
+fn fmt_value(value: &Value) -> String {
+    let text = match value {
+        Value::Null => String::from("none"),
+        Value::String(text) => text.replace('\n', " "),
+        other => other.to_string(),
+    };
+    match text.char_indices().nth(VALUE_WIDTH) {
+        Some((cut, _)) => format!("{}…", &text[..cut]),
+        None => text,
+    }
+}
+
+/// The fields that differ between two versions of an item, as
+/// `name: old → new`.
+fn changed_fields<T: Serialize>(before: &T, after: &T) -> Vec<String> {
+    let (before, after) = match (serde_json::to_value(before), serde_json::to_value(after)) {
+        (Ok(Value::Object(before)), Ok(Value::Object(after))) => (before, after),
+        _ => return Vec::new(),
+    };
+    after
+        .iter()
+        .filter_map(|(name, new)| {
+            let old = before.get(name).unwrap_or(&Value::Null);
+            (old != new).then(|| format!("{}: {} → {}", name, fmt_value(old), fmt_value(new)))
+        })
+        .collect()
+}
+
+/// The lines for the items of one kind that were added, then changed,
+/// then removed, each with what names it.
+fn compare<'a, T: Serialize + 'a>(
+    kind: &str,
+    before: impl Iterator<Item = &'a T>,
+    after: impl Iterator<Item = &'a T>,
+    key: fn(&T) -> (Uuid, &str),
+    lines: &mut Vec<String>,
+) {
+    let mut old: BTreeMap<Uuid, &T> = before.map(|item| (key(item).0, item)).collect();
+    let mut changed = Vec::new();
+    for item in after {
+        let (id, name) = key(item);
+        match old.remove(&id) {
+            None => lines.push(format!("+ {} {}", kind, name)),
+            Some(previous) => {
+                let fields = changed_fields(previous, item);
+                if !fields.is_empty() {
+                    changed.push(format!("~ {} {}", kind, name));
+                    changed.extend(fields.iter().map(|field| format!("    {}", field)));
+                }
+            }
+        }
+    }
+    lines.extend(changed);
+    for item in old.values() {
+        lines.push(format!("- {} {}", kind, key(item).1));
+    }
+}
+
+fn task_key(task: &Task) -> (Uuid, &str) {
+    (task.id, &task.content)
+}
+
+fn note_key(note: &Note) -> (Uuid, &str) {
+    (note.id, note.content.lines().next().unwrap_or(""))
+}
+
+/// What changed between two versions of one project.
+fn space_lines(before: &Project, after: &Project) -> Vec<String> {
+    let mut lines = Vec::new();
+    compare(
+        "task",
+        before.tasks.get_tasks().iter(),
+        after.tasks.get_tasks().iter(),
+        task_key,
+        &mut lines,
+    );
+    compare(
+        "note",
+        before.notes.get_notes().iter(),
+        after.notes.get_notes().iter(),
+        note_key,
+        &mut lines,
+    );
+    compare(
+        "archived task",
+        before.archive.get_tasks().iter(),
+        after.archive.get_tasks().iter(),
+        task_key,
+        &mut lines,
+    );
+    compare(
+        "trashed task",
+        before.trash.tasks.iter().map(|trashed| &trashed.item),
+        after.trash.tasks.iter().map(|trashed| &trashed.item),
+        task_key,
+        &mut lines,
+    );
+    compare(
+        "trashed note",
+        before.trash.notes.iter().map(|trashed| &trashed.item),
+        after.trash.notes.iter().map(|trashed| &trashed.item),
+        note_key,
+        &mut lines,
+    );
+    lines
+}
+
+fn root_of(db: &Database) -> Project {
+    Project {
+        tasks: db.tasks.clone(),
+        notes: db.notes.clone(),
+        archive: db.archive.clone(),
+        trash: db.trash.clone(),
+    }
+}
+
+/// Everything a command changed from `before` to `after`, both at rest,
+/// a line at a time under the project it was in: `+` for what is new,
+/// `~` for what changed, with the fields that did, and `-` for what is
+/// gone. What the save itself records, like the history, is left out.
+pub fn changes(before: &Database, after: &Database) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut spaces = vec![(ROOT_PROJECT, root_of(before), root_of(after))];
+    let empty = Project::default();
+    for (name, project) in after.projects.iter() {
+        let old = before.projects.get(name).unwrap_or(&empty);
+        if !before.projects.contains_key(name) {
+            lines.push(format!("+ project {}", name));
+        }
+        spaces.push((name.as_str(), old.clone(), project.clone()));
+    }
+    for name in before.projects.keys() {
+        if !after.projects.contains_key(name) {
+            lines.push(format!("- project {}", name));
+        }
+    }
+    for (name, before, after) in spaces.iter() {
+        let changed = space_lines(before, after);
+        if !changed.is_empty() {
+            lines.push(name.to_string());
+            lines.extend(changed.iter().map(|line| format!("  {}", line)));
+        }
+    }
+
+    if before.current_project != after.current_project {
+        let current = |db: &Database| {
+            db.current_project
+                .clone()
+                .unwrap_or_else(|| String::from(ROOT_PROJECT))
+        };
+        lines.push(format!(
+            "~ current project: {} → {}",
+            current(before),
+            current(after)
+        ));
+    }
+    let mut views = Vec::new();
+    for (name, view) in after.views.iter() {
+        match before.views.get(name) {
+            None => views.push(format!("+ view {}", name)),
+            Some(old) if old != view => views.push(format!("~ view {}", name)),
+            _ => {}
+        }
+    }
+    for name in before.views.keys() {
+        if !after.views.contains_key(name) {
+            views.push(format!("- view {}", name));
+        }
+    }
+    lines.extend(views);
+    let ids = |db: &Database| -> BTreeSet<Uuid> { db.inbox.iter().map(|item| item.id).collect() };
+    let (old_ids, new_ids) = (ids(before), ids(after));
+    for item in after
+        .inbox
+        .iter()
+        .filter(|item| !old_ids.contains(&item.id))
+    {
+        lines.push(format!("+ inbox {}", item.content));
+    }
+    for item in before
+        .inbox
+        .iter()
+        .filter(|item| !new_ids.contains(&item.id))
+    {
+        lines.push(format!("- inbox {}", item.content));
+    }
+    lines
+}
+
+/// Fails for the commands in [`REFUSED`].
+pub fn check(command: Option<&str>) -> error::Result<()> {
+    match command.filter(|command| REFUSED.contains(command)) {
+        Some(command) => Err(RegiaError::Parse(format!(
+            "--dry-run can't be used with {}",
+            command
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Prints what the command run since [`db::hold_dry`] would have saved,
+/// against `before`, and lets go of it.
+pub fn report(before: &Database) {
+    let after = match db::discard() {
+        Some(after) => after,
+        None => return,
+    };
+    let lines = changes(before, &after);
+    if lines.is_empty() {
+        eprintln!("{}", "Dry run: nothing would change".dimmed());
+        return;
+    }
+    eprintln!("{}", "Dry run, nothing saved. This would change:".header());
+    for line in lines {
+        let body = line.trim_start();
+        let indent = &line[..line.len() - body.len()];
+        let line = match body.split_at(body.find(' ').unwrap_or(0)) {
+            ("+", rest) => format!("{}{}{}", indent, "+".done(), rest),
+            ("~", rest) => format!("{}{}{}", indent, "~".warning(), rest),
+            ("-", rest) => format!("{}{}{}", indent, "-".error(), rest),
+            _ if indent.len() > 2 => line.dimmed().to_string(),
+            _ => line.bold().to_string(),
+        };
+        eprintln!("{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_what_would_change() {
+        let mut before = Database::default();
+        let mut kept = Task::new(String::from("kept"), 0);
+        before.tasks.add(kept.clone());
+        let gone = Task::new(String::from("gone"), 0);
+        before.tasks.add(gone.clone());
+
+        let mut after = before.clone();
+        after.tasks.remove(gone.id);
+        after.tasks.add(Task::new(String::from("new"), 0));
+        kept.content = String::from("kept, renamed");
+        after.tasks.add(kept);
+        after
+            .projects
+            .insert(String::from("work"), Project::named("work"));
+
+        assert_eq!(
+            changes(&before, &after),
+            [
+                "+ project work",
+                "root",
+                "  + task new",
+                "  ~ task kept, renamed",
+                "      content: kept → kept, renamed",
+                "  - task gone",
+            ]
+        );
+        assert!(changes(&before, &before).is_empty());
+    }
+}
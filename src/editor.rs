@@ -0,0 +1,42 @@
+use std::fs::read_to_string;
+use std::io::Write;
+use std::process::Command;
+
+use regia_core::error::{self, RegiaError};
+use tempfile::Builder;
+
+/// The user's editor from `$VISUAL` or `$EDITOR`, falling back to `vi`.
+/// The variable may carry arguments, e.g. `code --wait`.
+fn editor_command() -> Vec<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+    let parts: Vec<String> = editor.split_whitespace().map(String::from).collect();
+    if parts.is_empty() {
+        vec![String::from("vi")]
+    } else {
+        parts
+    }
+}
+
+/// Opens `initial` in the user's editor and returns the saved text. The
+/// temp file gets `suffix` (e.g. `.md`) so editors can pick a syntax mode.
+pub fn edit_text(initial: &str, suffix: &str) -> error::Result<String> {
+    let mut file = Builder::new().prefix("regia-").suffix(suffix).tempfile()?;
+    file.write_all(initial.as_bytes())?;
+    file.flush()?;
+
+    let command = editor_command();
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .arg(file.path())
+        .status()?;
+    if !status.success() {
+        return Err(RegiaError::storage(format!(
+            "editor {} exited with {}",
+            command[0], status
+        )));
+    }
+
+    Ok(read_to_string(file.path())?)
+}
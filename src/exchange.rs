@@ -0,0 +1,108 @@
+use std::fs::read_to_string;
+
+use chrono::Utc;
+use clap::ArgMatches;
+use colored::*;
+
+use crate::github;
+use crate::ics;
+use crate::journal;
+use crate::todotxt;
+use regia_core::conf::Config;
+use regia_core::db;
+use regia_core::error::{self, RegiaError};
+use regia_core::theme::Themed;
+
+pub fn handle_export(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let output = match matches.value_of("format").unwrap_or("json") {
+        // JSON is a full dump of every project
+        "json" => db::Database::load(db::db_path(doc))?.serialize_json()?,
+        "ics" => ics::to_ics(&db::open(doc)?.tasks, Utc::now()),
+        "todotxt" => todotxt::to_todotxt(&db::Database::load(db::db_path(doc))?)?,
+        other => return Err(RegiaError::Parse(format!("bad export format: {}", other))),
+    };
+
+    if let Some(out) = matches.value_of("out") {
+        Ok(db::write_to_disk(out, output.as_bytes())?)
+    } else {
+        println!("{}", output);
+        Ok(())
+    }
+}
+
+pub fn handle_import(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let file = matches.value_of("file").unwrap();
+    match matches.value_of("from") {
+        Some("github") => return github::handle_import(file, doc),
+        Some("todotxt") => return handle_import_todotxt(file, doc),
+        _ => {}
+    }
+    let collision = match matches.value_of("on-conflict").unwrap_or("skip") {
+        "skip" => db::Collision::Skip,
+        "replace" => db::Collision::Replace,
+        "rename" => db::Collision::Rename,
+        other => return Err(RegiaError::Parse(format!("bad conflict mode: {}", other))),
+    };
+    let incoming = db::Database::deserialize_json(&read_to_string(file)?)?;
+
+    let db_path = db::db_path(doc);
+    let mut db = db::Database::load(db_path)?;
+    let before = db.clone();
+    let summary = db.import(incoming, collision);
+
+    println!(
+        "Imported {} new, {} replaced, {} renamed, {} skipped",
+        format!("{}", summary.added).header(),
+        format!("{}", summary.replaced).header(),
+        format!("{}", summary.renamed).header(),
+        format!("{}", summary.skipped).header(),
+    );
+    journal::record_as_is(db_path, "import", &before, &db)?;
+    db.to_disk(db_path)
+}
+
+/// Adds the tasks of a todo.txt file to the projects their `+project`
+/// names, creating those, or else to the active one. A task already there
+/// with the same text is skipped, so importing the same file twice adds
+/// nothing. Each project changed gets its own journal entry to undo.
+fn handle_import_todotxt(file: &str, doc: &Config) -> error::Result<()> {
+    let items = todotxt::parse(&read_to_string(file)?, Utc::now())?;
+
+    let db_path = db::db_path(doc);
+    let mut db = db::open(doc)?;
+    let active = db.active_project().map(String::from);
+    let (mut added, mut skipped) = (0, 0);
+    for (project, tasks) in todotxt::by_project(items) {
+        let project = project.or_else(|| active.clone());
+        if let Some(name) = project.as_deref() {
+            if name != db::ROOT_PROJECT && !db.projects.contains_key(name) {
+                db.projects
+                    .insert(name.to_string(), db::Project::named(name));
+                println!("{} {}", "Created project".header(), name.bold());
+            }
+        }
+        db.activate(project.as_deref())?;
+        let before = db.clone();
+        for task in tasks {
+            let known = db
+                .tasks
+                .get_tasks()
+                .iter()
+                .any(|old| old.content == task.content);
+            if known {
+                skipped += 1;
+            } else {
+                db.tasks.add(task);
+                added += 1;
+            }
+        }
+        journal::record_as_is(db_path, "import", &before, &db)?;
+    }
+
+    println!(
+        "Imported {} new, {} skipped",
+        format!("{}", added).header(),
+        format!("{}", skipped).header(),
+    );
+    db.to_disk(db_path)
+}
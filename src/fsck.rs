@@ -0,0 +1,95 @@
+//! `regia db fsck`: checks the database file for what regia never writes,
+//! and with `--repair` puts it right, see [`regia_core::fsck`].
+
+use clap::ArgMatches;
+use colored::*;
+
+use crate::journal;
+use crate::output::{self, Output};
+use regia_core::conf::Config;
+use regia_core::db;
+use regia_core::error::{self, RegiaError};
+use regia_core::fsck::{self, Issue};
+use regia_core::theme::Themed;
+
+impl output::Record for Issue {
+    fn plain(&self) -> String {
+        output::fields(&[
+            self.project.clone().unwrap_or_default(),
+            self.id.map(|id| id.to_string()).unwrap_or_default(),
+            self.message.clone(),
+        ])
+    }
+}
+
+fn print_issues(issues: &[Issue]) {
+    for issue in issues {
+        let mut place = Vec::new();
+        if let Some(project) = &issue.project {
+            place.push(project.clone());
+        }
+        if let Some(id) = issue.id {
+            place.push(id.to_string());
+        }
+        let place = if place.is_empty() {
+            String::new()
+        } else {
+            format!("{} ", place.join(" ").dimmed())
+        };
+        println!("{} {}{}", "!".error().bold(), place, issue.message);
+    }
+}
+
+/// `regia db fsck [--repair]`. Fails quietly, as `task check-overdue`
+/// does, when the database has problems left.
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    // A database kept in memory is checked as it would be written
+    db::flush()?;
+    let (mut db, mut issues) = fsck::read(db_path)?;
+    issues.extend(fsck::check(&db));
+
+    let repair = matches.is_present("repair") && !issues.is_empty();
+    let left = if repair {
+        let before = db.clone();
+        fsck::repair(&mut db);
+        journal::record_as_is(db_path, "db fsck", &before, &db)?;
+        db.compact(db_path)?;
+        fsck::check(&db)
+    } else {
+        issues.clone()
+    };
+
+    let format = Output::from_config(doc);
+    match format {
+        Output::Text => {
+            print_issues(&issues);
+            if issues.is_empty() {
+                println!("{} {}", "No problems in".header(), db_path.display());
+            } else if repair {
+                let fixed = issues.len().saturating_sub(left.len());
+                let plural = if fixed == 1 { "" } else { "s" };
+                println!(
+                    "{} {} problem{} in {}",
+                    "Repaired".header(),
+                    fixed,
+                    plural,
+                    db_path.display()
+                );
+                if !left.is_empty() {
+                    println!("{}", "These are left:".dimmed());
+                    print_issues(&left);
+                }
+            } else {
+                println!("{}", "Run regia db fsck --repair to fix these".dimmed());
+            }
+        }
+        Output::Quiet => {}
+        _ => output::print(format, &issues)?,
+    }
+    if left.is_empty() {
+        Ok(())
+    } else {
+        Err(RegiaError::CheckFailed)
+    }
+}
@@ -0,0 +1,297 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::http;
+use crate::journal;
+use regia_core::conf::{self, Config};
+use regia_core::db;
+use regia_core::error::{self, RegiaError};
+use regia_core::priority::Priority;
+use regia_core::tags;
+use regia_core::theme::Themed;
+use regia_core::todo::{self, Issue, Task, Tasks};
+
+/// Where the API is unless `$GITHUB_API_URL` says otherwise, as it does
+/// for GitHub Enterprise.
+const API: &str = "https://api.github.com";
+/// Issues fetched per request, the most the API gives.
+const PAGE: usize = 100;
+/// The most pages one import reads.
+const MAX_PAGES: usize = 20;
+
+#[derive(Deserialize, Debug, Clone)]
+struct Label {
+    name: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct Milestone {
+    #[serde(default)]
+    due_on: Option<DateTime<Utc>>,
+}
+
+/// An issue as the API returns it, with only what regia uses.
+#[derive(Deserialize, Debug, Clone)]
+struct RemoteIssue {
+    number: u64,
+    title: String,
+    html_url: String,
+    state: String,
+    #[serde(default)]
+    labels: Vec<Label>,
+    #[serde(default)]
+    milestone: Option<Milestone>,
+    /// Set on pull requests, which the issues API lists too
+    #[serde(default)]
+    pull_request: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct User {
+    login: String,
+}
+
+/// Checks `repo` is given as `owner/name`.
+fn parse_repo(repo: &str) -> error::Result<&str> {
+    match repo.split_once('/') {
+        Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {
+            Ok(repo)
+        }
+        _ => Err(RegiaError::Parse(format!(
+            "give the repository as owner/name: {}",
+            repo
+        ))),
+    }
+}
+
+struct Client {
+    api: String,
+    token: String,
+}
+
+impl Client {
+    fn new(doc: &Config) -> error::Result<Self> {
+        let token = match doc
+            .github
+            .token
+            .clone()
+            .or_else(|| std::env::var("GITHUB_TOKEN").ok())
+        {
+            Some(token) if !token.trim().is_empty() => token,
+            _ => {
+                return Err(RegiaError::Config(String::from(
+                    "set github.token or $GITHUB_TOKEN to a GitHub access token",
+                )))
+            }
+        };
+        let api = std::env::var("GITHUB_API_URL").unwrap_or_else(|_| String::from(API));
+        Ok(Self {
+            api: api.trim_end_matches('/').to_string(),
+            token,
+        })
+    }
+
+    /// The JSON at `path` under the API.
+    fn get<T: for<'de> Deserialize<'de>>(&self, path: &str) -> error::Result<T> {
+        let url = format!("{}{}", self.api, path);
+        let headers = [
+            "Accept: application/vnd.github+json",
+            "X-GitHub-Api-Version: 2022-11-28",
+            "User-Agent: regia",
+        ];
+        let auth = [http::config_line(
+            "header",
+            &format!("Authorization: Bearer {}", self.token),
+        )];
+        let body = match http::request("GET", &url, &headers, &auth, None)? {
+            (200..=299, body) => body,
+            (404, _) => return Err(RegiaError::NotFound(format!("GitHub has no {}", path))),
+            (401, _) | (403, _) => {
+                return Err(RegiaError::Config(format!(
+                    "GitHub refused the token for {}",
+                    path
+                )))
+            }
+            (status, _) => {
+                return Err(RegiaError::storage(format!(
+                    "GET {} answered {}",
+                    path, status
+                )))
+            }
+        };
+        serde_json::from_str(&body)
+            .map_err(|err| RegiaError::corrupt(format!("GitHub answered {}: {}", path, err)))
+    }
+
+    /// The open issues of `repo` assigned to `login`, pull requests left
+    /// out.
+    fn assigned(&self, repo: &str, login: &str) -> error::Result<Vec<RemoteIssue>> {
+        let mut issues = Vec::new();
+        for page in 1..=MAX_PAGES {
+            let path = format!(
+                "/repos/{}/issues?state=open&assignee={}&per_page={}&page={}",
+                repo, login, PAGE, page
+            );
+            let found: Vec<RemoteIssue> = self.get(&path)?;
+            let last = found.len() < PAGE;
+            issues.extend(
+                found
+                    .into_iter()
+                    .filter(|issue| issue.pull_request.is_none()),
+            );
+            if last {
+                break;
+            }
+        }
+        Ok(issues)
+    }
+}
+
+/// A new task for `issue` of `repo`: its title, its labels as tags and its
+/// milestone's due date.
+fn task_for(repo: &str, issue: &RemoteIssue) -> Task {
+    let due = issue
+        .milestone
+        .as_ref()
+        .and_then(|milestone| milestone.due_on);
+    let mut task = match due {
+        Some(due) => Task::new_date(
+            issue.title.clone(),
+            Priority::None,
+            Some(due),
+            todo::TaskType::Deadline,
+            None,
+        ),
+        None => Task::new(issue.title.clone(), Priority::None),
+    };
+    // A tag is one word, and labels like `good first issue` aren't
+    let labels: Vec<String> = issue
+        .labels
+        .iter()
+        .map(|label| label.name.split_whitespace().collect::<Vec<_>>().join("-"))
+        .collect();
+    task.tags = tags::from_args(labels.iter().map(String::as_str));
+    task.issue = Some(Issue {
+        repo: repo.to_string(),
+        number: issue.number,
+        url: issue.html_url.clone(),
+    });
+    task
+}
+
+/// Adds a task for each of `issues` that no task tracks yet, returning the
+/// ones added.
+fn add_issues(tasks: &mut Tasks, repo: &str, issues: &[RemoteIssue]) -> Vec<Task> {
+    let mut added = Vec::new();
+    for issue in issues {
+        let tracked = tasks.get_tasks().iter().any(|task| {
+            task.issue
+                .as_ref()
+                .is_some_and(|known| known.repo == repo && known.number == issue.number)
+        });
+        if !tracked {
+            let task = task_for(repo, issue);
+            tasks.add(task.clone());
+            added.push(task);
+        }
+    }
+    added
+}
+
+/// `regia import --from github OWNER/NAME`
+pub fn handle_import(repo: &str, doc: &Config) -> error::Result<()> {
+    let repo = parse_repo(repo)?;
+    let client = Client::new(doc)?;
+    let login = match &doc.github.user {
+        Some(user) => user.clone(),
+        None => client.get::<User>("/user")?.login,
+    };
+    let issues = client.assigned(repo, &login)?;
+
+    let db_path = db::db_path(doc);
+    let mut db = db::open(doc)?;
+    let before = db.clone();
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let added = add_issues(&mut db.tasks, repo, &issues);
+    for task in added.iter() {
+        println!("{} {}", "Added".header(), task.fmt(&priority_map));
+    }
+    println!(
+        "Imported {} new, {} already tracked from {}",
+        format!("{}", added.len()).header(),
+        format!("{}", issues.len() - added.len()).header(),
+        repo
+    );
+    journal::record(db_path, "import", &before, &mut db)?;
+    db.to_disk(db_path)
+}
+
+/// `task sync-github`: completes the open tasks whose issue was closed,
+/// returning how many.
+pub fn sync(tasks: &mut Tasks, doc: &Config, priority_map: &[(u32, &str)]) -> error::Result<usize> {
+    let linked: Vec<(uuid::Uuid, Issue)> = tasks
+        .get_tasks()
+        .iter()
+        .filter(|task| !task.is_done())
+        .filter_map(|task| task.issue.clone().map(|issue| (task.id, issue)))
+        .collect();
+    if linked.is_empty() {
+        println!("No open tasks track a GitHub issue");
+        return Ok(0);
+    }
+
+    let client = Client::new(doc)?;
+    let mut closed = 0;
+    for (id, issue) in linked {
+        let path = format!("/repos/{}/issues/{}", issue.repo, issue.number);
+        let remote: RemoteIssue = client.get(&path)?;
+        if remote.state == "closed" {
+            let mut task = tasks.get_task_mut(&id).unwrap();
+            task.complete();
+            println!(
+                "{} {} ({}#{} closed)",
+                "Completed".header(),
+                task.fmt(priority_map),
+                issue.repo,
+                issue.number
+            );
+            closed += 1;
+        }
+    }
+    if closed == 0 {
+        println!("Every tracked issue is still open");
+    }
+    Ok(closed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn issues_become_tasks_once() {
+        let json = r#"[
+            {"number": 12, "title": "Crash on empty config", "html_url": "https://github.com/o/r/issues/12",
+             "state": "open", "labels": [{"name": "bug"}, {"name": "good first issue"}], "milestone": {"due_on": "2026-11-01T07:00:00Z"}},
+            {"number": 13, "title": "Add a flag", "html_url": "https://github.com/o/r/pull/13",
+             "state": "open", "pull_request": {"url": "x"}}
+        ]"#;
+        let issues: Vec<RemoteIssue> = serde_json::from_str(json).unwrap();
+        let task = task_for("o/r", &issues[0]);
+        assert_eq!(task.content, "Crash on empty config");
+        assert!(task.tags.contains("bug") && task.tags.contains("good-first-issue"));
+        assert!(task.due.is_some());
+        assert_eq!(task.issue.as_ref().unwrap().number, 12);
+
+        let mut tasks = Tasks::default();
+        assert_eq!(add_issues(&mut tasks, "o/r", &issues[..1]).len(), 1);
+        assert!(add_issues(&mut tasks, "o/r", &issues[..1]).is_empty());
+        // The same number in another repository is another issue
+        assert_eq!(add_issues(&mut tasks, "o/other", &issues[..1]).len(), 1);
+
+        assert!(parse_repo("o/r").is_ok());
+        assert!(parse_repo("o").is_err());
+        assert!(parse_repo("o/r/x").is_err());
+    }
+}
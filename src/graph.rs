@@ -0,0 +1,197 @@
+//! `task graph`: the dependencies between open tasks as a Graphviz or
+//! Mermaid diagram.
+
+use chrono::{DateTime, Utc};
+use clap::ArgMatches;
+use colored::Color;
+
+use regia_core::conf::{self, Config};
+use regia_core::error;
+use regia_core::todo::{self, Task, Tasks};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Dot,
+    Mermaid,
+}
+
+/// A light fill for each terminal color a priority can be shown in, so
+/// labels stay readable.
+fn fill(color: Option<Color>) -> &'static str {
+    match color {
+        Some(Color::Magenta | Color::BrightMagenta) => "#f3d1f4",
+        Some(Color::Red | Color::BrightRed) => "#f9d0d0",
+        Some(Color::Yellow | Color::BrightYellow) => "#fff2b3",
+        Some(Color::Green | Color::BrightGreen) => "#d4f2d2",
+        Some(Color::Blue | Color::BrightBlue) => "#d3e0fa",
+        Some(Color::Cyan | Color::BrightCyan) => "#cff3f3",
+        Some(Color::Black | Color::BrightBlack) => "#d9d9d9",
+        _ => "#ffffff",
+    }
+}
+
+/// What the diagram needs to know about each open task.
+struct Node<'a> {
+    task: &'a Task,
+    /// The task's content, marked when overdue
+    label: String,
+    fill: &'static str,
+    blocked: bool,
+    overdue: bool,
+}
+
+fn node_id(task: &Task) -> String {
+    format!("t{}", &task.id.to_simple().to_string()[..8])
+}
+
+fn nodes<'a>(tasks: &'a Tasks, formatter: &todo::Formatter, now: DateTime<Utc>) -> Vec<Node<'a>> {
+    let mut open: Vec<&Task> = tasks
+        .get_tasks()
+        .iter()
+        .filter(|task| !task.is_done())
+        .collect();
+    open.sort_by_key(|task| task.created);
+    open.into_iter()
+        .map(|task| {
+            let overdue = task.due.is_some_and(|due| due < now);
+            Node {
+                task,
+                label: if overdue {
+                    format!("{} (overdue)", task.content)
+                } else {
+                    task.content.clone()
+                },
+                fill: fill(formatter.color(task)),
+                blocked: !tasks.open_dependencies(task).is_empty(),
+                overdue,
+            }
+        })
+        .collect()
+}
+
+/// Edges from each open dependency to the open task waiting on it.
+fn edges(tasks: &Tasks, nodes: &[Node]) -> Vec<(String, String)> {
+    let mut edges = Vec::new();
+    for node in nodes {
+        for dep in tasks.open_dependencies(node.task) {
+            edges.push((node_id(dep), node_id(node.task)));
+        }
+    }
+    edges
+}
+
+fn dot(tasks: &Tasks, nodes: &[Node]) -> String {
+    let mut lines = vec![
+        String::from("digraph tasks {"),
+        String::from("  rankdir=LR;"),
+        String::from("  node [shape=box, style=\"rounded,filled\", fontname=\"sans-serif\"];"),
+    ];
+    for node in nodes {
+        let mut style = String::from("rounded,filled");
+        if node.blocked {
+            style.push_str(",dashed");
+        }
+        let border = if node.overdue {
+            ", color=\"#cc0000\", penwidth=2"
+        } else {
+            ""
+        };
+        let label = node.label.replace('\\', "\\\\").replace('"', "\\\"");
+        lines.push(format!(
+            "  {} [label=\"{}\", fillcolor=\"{}\", style=\"{}\"{}];",
+            node_id(node.task),
+            label,
+            node.fill,
+            style,
+            border
+        ));
+    }
+    for (from, to) in edges(tasks, nodes) {
+        lines.push(format!("  {} -> {};", from, to));
+    }
+    lines.push(String::from("}"));
+    lines.join("\n")
+}
+
+fn mermaid(tasks: &Tasks, nodes: &[Node]) -> String {
+    let mut lines = vec![String::from("graph LR")];
+    for node in nodes {
+        let label = node.label.replace('"', "#quot;");
+        lines.push(format!("  {}[\"{}\"]", node_id(node.task), label));
+    }
+    for (from, to) in edges(tasks, nodes) {
+        lines.push(format!("  {} --> {}", from, to));
+    }
+    for node in nodes {
+        let mut style = format!("fill:{}", node.fill);
+        if node.overdue {
+            style.push_str(",stroke:#cc0000,stroke-width:2px");
+        }
+        if node.blocked {
+            style.push_str(",stroke-dasharray:5 5");
+        }
+        lines.push(format!("  style {} {}", node_id(node.task), style));
+    }
+    lines.join("\n")
+}
+
+/// Renders the open tasks of `tasks` and what each waits on. Blocked
+/// tasks get dashed borders and overdue ones a red border.
+pub fn render(tasks: &Tasks, format: Format, formatter: &todo::Formatter) -> String {
+    let nodes = nodes(tasks, formatter, formatter.now);
+    match format {
+        Format::Dot => dot(tasks, &nodes),
+        Format::Mermaid => mermaid(tasks, &nodes),
+    }
+}
+
+pub fn handle_task_graph(matches: &ArgMatches, tasks: &Tasks, doc: &Config) -> error::Result<()> {
+    let format = match matches.value_of("format") {
+        Some("mermaid") => Format::Mermaid,
+        _ => Format::Dot,
+    };
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let formatter = todo::Formatter::new(&priority_map);
+    println!("{}", render(tasks, format, &formatter));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn draws_open_dependencies() {
+        let mut tasks = Tasks::default();
+        let mut design = Task::new(String::from("design \"v2\""), 9);
+        design.due = Some(Utc::now() - Duration::days(1));
+        let mut build = Task::new(String::from("build"), 0);
+        build.add_dependency(&design.id);
+        let mut done = Task::new(String::from("done"), 0);
+        done.completed = Some(Utc::now());
+        build.add_dependency(&done.id);
+        let (design_id, build_id) = (node_id(&design), node_id(&build));
+        for task in [design, build, done] {
+            tasks.add(task);
+        }
+        let formatter = todo::Formatter::new(&[]);
+
+        let dot = render(&tasks, Format::Dot, &formatter);
+        assert!(dot.contains(&format!("{} -> {};", design_id, build_id)));
+        assert!(dot.contains("label=\"design \\\"v2\\\" (overdue)\""));
+        assert!(dot.contains("fillcolor=\"#f3d1f4\""));
+        assert!(dot.contains("style=\"rounded,filled,dashed\""));
+        assert!(!dot.contains("done"));
+
+        let mermaid = render(&tasks, Format::Mermaid, &formatter);
+        assert!(mermaid.starts_with("graph LR"));
+        assert!(mermaid.contains(&format!("  {} --> {}", design_id, build_id)));
+        assert!(mermaid.contains("design #quot;v2#quot; (overdue)"));
+        assert!(mermaid.contains(&format!(
+            "style {} fill:#ffffff,stroke-dasharray:5 5",
+            build_id
+        )));
+    }
+}
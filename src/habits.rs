@@ -0,0 +1,370 @@
+//! `regia habit`: repeated tasks kept for the doing rather than the done,
+//! tagged `habit`, with a calendar of the last weeks and their streaks.
+//! Each habit is an ordinary repeated task underneath, so `task ls`,
+//! `task edit` and `task rm` work on it too, and what was done or missed
+//! comes from the task history.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use clap::ArgMatches;
+use colored::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::journal;
+use crate::output::{self, Output};
+use crate::taskmaster;
+use regia_core::conf::Config;
+use regia_core::dates;
+use regia_core::db;
+use regia_core::error::{self, RegiaError};
+use regia_core::history::{self, Event, Kind};
+use regia_core::ident;
+use regia_core::priority::Priority;
+use regia_core::schedule::{self, Streak};
+use regia_core::theme::Themed;
+use regia_core::todo::{self, Task, Tasks};
+
+/// The tag that makes a repeated task a habit.
+pub const HABIT_TAG: &str = "habit";
+/// How many weeks `habit ls` shows without `--weeks`.
+const DEFAULT_WEEKS: i64 = 12;
+
+/// What became of a habit on one day.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mark {
+    Done,
+    Missed,
+}
+
+/// The current instance of every habit, by name.
+fn habits(tasks: &Tasks) -> Vec<&Task> {
+    let mut habits: Vec<&Task> = tasks
+        .get_tasks()
+        .iter()
+        .filter(|task| task.tags.contains(HABIT_TAG) && task.repeat.is_some())
+        .filter(|task| task.successor.is_none())
+        .collect();
+    habits.sort_by_key(|task| (task.content.to_lowercase(), task.id));
+    habits
+}
+
+/// The days of `events` on which the habit `series` was done or missed,
+/// on the clocks of `zone`. A day with both counts as done.
+pub fn marks<Tz: TimeZone>(events: &[Event], series: Uuid, zone: &Tz) -> BTreeMap<NaiveDate, Mark> {
+    let mut marks = BTreeMap::new();
+    for event in events.iter().filter(|event| event.series == Some(series)) {
+        let day = event.at.with_timezone(zone).date_naive();
+        match event.kind {
+            Kind::Completed => {
+                marks.insert(day, Mark::Done);
+            }
+            Kind::Missed => {
+                marks.entry(day).or_insert(Mark::Missed);
+            }
+        }
+    }
+    marks
+}
+
+/// The first instance of a habit added at `now`: due by the end of its
+/// first day, today when the schedule has it today.
+fn first_due(repeat: &todo::RepeatType, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let yesterday = now.with_timezone(&dates::Local).date_naive() - Duration::days(1);
+    let end = yesterday.and_time(NaiveTime::from_hms_opt(23, 59, 59).unwrap());
+    schedule::next_due(
+        dates::from_local(&dates::Local, &end),
+        repeat,
+        now,
+        &dates::Local,
+    )
+}
+
+fn handle_habit_add(matches: &ArgMatches, tasks: &mut Tasks) -> error::Result<()> {
+    let content = matches
+        .values_of("content")
+        .unwrap()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let schedule = matches.value_of("schedule").unwrap_or("daily");
+    let repeat = taskmaster::parse_repeat(schedule)?;
+    if !repeat.has_streaks() {
+        return Err(RegiaError::Parse(format!(
+            "a habit repeats daily or weekly, give or take: {}",
+            schedule
+        )));
+    }
+    let now = Utc::now();
+    let due = first_due(&repeat, now)
+        .ok_or_else(|| RegiaError::Parse(format!("{} never comes round", schedule)))?;
+
+    let mut task = Task::new_date(
+        content,
+        Priority::None,
+        Some(due),
+        todo::TaskType::Repeated,
+        Some(repeat),
+    );
+    task.tags.insert(String::from(HABIT_TAG));
+    println!(
+        "{} {} {}, first due {}",
+        "Added habit".header(),
+        task.content,
+        format!("↻ {}", task.repeat.as_ref().unwrap()).dimmed(),
+        taskmaster::fmt_date(&due)
+    );
+    tasks.add(task);
+    Ok(())
+}
+
+/// The habit `given` names, by id, id prefix or words of it.
+fn resolve(tasks: &Tasks, given: &str) -> error::Result<Uuid> {
+    let habits = habits(tasks);
+    let ids: Vec<Uuid> = habits.iter().map(|task| task.id).collect();
+    if !ident::matching(ids.iter().copied(), given).is_empty() {
+        return Ok(ident::resolve_among(ids, "habit", given)?);
+    }
+    let wanted = given.to_lowercase();
+    let found: Vec<&&Task> = habits
+        .iter()
+        .filter(|task| task.content.to_lowercase().contains(&wanted))
+        .collect();
+    match found.len() {
+        0 => Err(RegiaError::NotFound(format!("no habit matches: {}", given))),
+        1 => Ok(found[0].id),
+        _ => {
+            for task in found.iter() {
+                println!("  {}", task.content);
+            }
+            Err(RegiaError::Conflict(format!(
+                "{} fits {} habits, give more of it",
+                given,
+                found.len()
+            )))
+        }
+    }
+}
+
+/// Ticks the habit off for now, unless it already was until its next
+/// instance comes round.
+fn handle_habit_done(matches: &ArgMatches, tasks: &mut Tasks) -> error::Result<()> {
+    let id = resolve(tasks, matches.value_of("habit").unwrap())?;
+    let now = Utc::now();
+    let early = tasks
+        .get_tasks()
+        .iter()
+        .find(|task| task.successor == Some(id))
+        .filter(|previous| previous.is_done() && previous.due.is_some_and(|due| due > now));
+    let task = tasks.get_task(&id).unwrap();
+    if early.is_some() {
+        let next = task.due.map(|due| taskmaster::fmt_date(&due));
+        return Err(RegiaError::Conflict(format!(
+            "{} is already done, next due {}",
+            task.content,
+            next.unwrap_or_default()
+        )));
+    }
+    tasks.get_task_mut(&id).unwrap().complete();
+    schedule::tick(tasks, now, &dates::Local);
+    let task = tasks.get_task(&id).unwrap();
+    println!("{} {}", "Done".done(), task.content);
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct HabitRecord {
+    series: Uuid,
+    id: Uuid,
+    content: String,
+    repeat: String,
+    due: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    streak: Streak,
+    /// Every day the habit was done or missed
+    days: BTreeMap<NaiveDate, Mark>,
+}
+
+impl output::Record for HabitRecord {
+    fn plain(&self) -> String {
+        let mut lines = vec![output::fields(&[
+            String::from("habit"),
+            self.series.to_string(),
+            self.streak.current.to_string(),
+            self.streak.longest.to_string(),
+            self.repeat.clone(),
+            self.content.clone(),
+        ])];
+        for (day, mark) in self.days.iter() {
+            let mark = match mark {
+                Mark::Done => "done",
+                Mark::Missed => "missed",
+            };
+            lines.push(output::fields(&[
+                String::from("day"),
+                self.series.to_string(),
+                day.to_string(),
+                mark.to_string(),
+            ]));
+        }
+        lines.join("\n")
+    }
+}
+
+/// A grid of the `weeks` weeks up to `today`, a column each and a row for
+/// every day of the week.
+fn print_grid(days: &BTreeMap<NaiveDate, Mark>, today: NaiveDate, weeks: i64, due_today: bool) {
+    let first = dates::locale().week_of(today) - Duration::weeks(weeks - 1);
+    for row in 0..7 {
+        let mut line = format!("  {} ", (first + Duration::days(row)).format("%a"))
+            .dimmed()
+            .to_string();
+        for column in 0..weeks {
+            let day = first + Duration::weeks(column) + Duration::days(row);
+            let cell = match days.get(&day) {
+                _ if day > today => " ".normal(),
+                Some(Mark::Done) => "■".done(),
+                Some(Mark::Missed) => "■".error(),
+                None if day == today && due_today => "□".accent(),
+                None => "·".dimmed(),
+            };
+            line.push_str(&format!("{} ", cell));
+        }
+        println!("{}", line.trim_end());
+    }
+}
+
+fn handle_habit_ls(matches: &ArgMatches, db: &db::Database, doc: &Config) -> error::Result<()> {
+    let weeks = match matches.value_of("weeks") {
+        Some(input) => match input.parse::<i64>() {
+            Ok(weeks) if weeks > 0 => weeks,
+            _ => return Err(RegiaError::Parse(format!("bad week count: {}", input))),
+        },
+        None => DEFAULT_WEEKS,
+    };
+    let events = history::of_active(db);
+    let streaks = schedule::streaks(&events);
+    let records: Vec<HabitRecord> = habits(&db.tasks)
+        .into_iter()
+        .map(|task| HabitRecord {
+            series: task.series_id(),
+            id: task.id,
+            content: task.content.clone(),
+            repeat: task.repeat.as_ref().unwrap().to_string(),
+            due: task.due,
+            streak: streaks.get(&task.series_id()).copied().unwrap_or_default(),
+            days: marks(&events, task.series_id(), &dates::Local),
+        })
+        .collect();
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        return output::print(format, &records);
+    }
+    if records.is_empty() {
+        println!("No habits yet, add one with regia habit add");
+        return Ok(());
+    }
+    let now = Utc::now();
+    let today = now.with_timezone(&dates::Local).date_naive();
+    let since = today - Duration::weeks(weeks);
+    for record in records {
+        let done = record
+            .days
+            .iter()
+            .filter(|(day, mark)| **day > since && **mark == Mark::Done)
+            .count();
+        let current = format!("🔥 {}", record.streak.current);
+        let current = if record.streak.current > 0 {
+            current.warning()
+        } else {
+            current.dimmed()
+        };
+        println!(
+            "{} {} {} {}",
+            record.content.bold(),
+            format!("↻ {}", record.repeat).dimmed(),
+            current,
+            format!(
+                "best {}, done {} time{} in {} week{}",
+                record.streak.longest,
+                done,
+                if done == 1 { "" } else { "s" },
+                weeks,
+                if weeks == 1 { "" } else { "s" }
+            )
+            .dimmed()
+        );
+        let due_today = record
+            .due
+            .is_some_and(|due| due.with_timezone(&dates::Local).date_naive() == today);
+        print_grid(&record.days, today, weeks, due_today);
+    }
+    Ok(())
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let mut db = db::open(doc)?;
+    let before = db.clone();
+    schedule::tick(&mut db.tasks, Utc::now(), &dates::Local);
+
+    let command = match matches.subcommand() {
+        ("add", Some(matches)) => {
+            handle_habit_add(matches, &mut db.tasks)?;
+            "habit add"
+        }
+        ("done", Some(matches)) => {
+            handle_habit_done(matches, &mut db.tasks)?;
+            "habit done"
+        }
+        ("ls", Some(matches)) => return handle_habit_ls(matches, &db, doc),
+        _ => return handle_habit_ls(&ArgMatches::default(), &db, doc),
+    };
+    journal::record(db_path, command, &before, &mut db)?;
+    db.to_disk(db_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_days_done_and_missed() {
+        let at = |day: u32, hour: u32| Utc.with_ymd_and_hms(2019, 10, day, hour, 0, 0).unwrap();
+        let mut habit = Task::new(String::from("meditate"), 0);
+        habit.repeat = Some(todo::RepeatType::Daily);
+        let other = Uuid::new_v4();
+        let event = |kind: Kind, at: DateTime<Utc>, series: Uuid| Event {
+            task: Uuid::new_v4(),
+            kind,
+            at,
+            open: 0,
+            content: habit.content.clone(),
+            project: None,
+            tags: Vec::new(),
+            repeat: habit.repeat.clone(),
+            series: Some(series),
+        };
+        let series = habit.series_id();
+        let events = [
+            event(Kind::Completed, at(1, 8), series),
+            event(Kind::Missed, at(2, 23), series),
+            // Missed first, then done late the same day
+            event(Kind::Missed, at(3, 9), series),
+            event(Kind::Completed, at(3, 20), series),
+            event(Kind::Completed, at(4, 8), other),
+        ];
+        let day = |day: u32| NaiveDate::from_ymd_opt(2019, 10, day).unwrap();
+        let marks: Vec<(NaiveDate, Mark)> = marks(&events, series, &Utc).into_iter().collect();
+        assert_eq!(
+            marks,
+            [
+                (day(1), Mark::Done),
+                (day(2), Mark::Missed),
+                (day(3), Mark::Done),
+            ]
+        );
+    }
+}
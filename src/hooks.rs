@@ -0,0 +1,341 @@
+use std::collections::BTreeMap;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::output;
+use regia_core::conf::Config;
+use regia_core::db::{self, Database};
+use regia_core::error::{self, RegiaError};
+use regia_core::note::Note;
+use regia_core::todo::{Task, Tasks};
+
+/// What `hooks` setting an event runs, and the name it goes by in the
+/// payload and `$REGIA_EVENT`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    TaskAdded,
+    TaskCompleted,
+    TaskOverdue,
+    /// A task left with nothing to wait on by another being completed
+    TaskUnblocked,
+    NoteAdded,
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::TaskAdded => "task-added",
+            Event::TaskCompleted => "task-completed",
+            Event::TaskOverdue => "task-overdue",
+            Event::TaskUnblocked => "task-unblocked",
+            Event::NoteAdded => "note-added",
+        }
+    }
+
+    fn hook(self, doc: &Config) -> Option<&str> {
+        let hook = match self {
+            Event::TaskAdded => &doc.hooks.task_added,
+            Event::TaskCompleted => &doc.hooks.task_completed,
+            Event::TaskOverdue => &doc.hooks.task_overdue,
+            Event::TaskUnblocked => &doc.hooks.task_unblocked,
+            Event::NoteAdded => &doc.hooks.note_added,
+        };
+        hook.as_deref()
+    }
+}
+
+/// What a hook gets: the event and the task or note it happened to.
+#[derive(Serialize)]
+struct Payload<'a> {
+    event: &'static str,
+    at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    task: Option<output::TaskRecord>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<output::NoteRecord>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project: Option<&'a str>,
+}
+
+/// The item an event is about, by id.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Item {
+    Task(Uuid),
+    Note(Uuid),
+}
+
+/// The task and note events between `before` and `after`, tasks first.
+/// Switching projects isn't anything happening, nor is anything when the
+/// two are of different projects.
+pub fn events(before: &Database, after: &Database) -> Vec<(Event, Item)> {
+    let mut events = Vec::new();
+    if before.active_project() != after.active_project() {
+        return events;
+    }
+    let mut completed = Vec::new();
+    for task in after.tasks.get_tasks() {
+        match before.tasks.get_task(&task.id) {
+            None => events.push((Event::TaskAdded, Item::Task(task.id))),
+            Some(old) if task.is_done() && !old.is_done() => {
+                events.push((Event::TaskCompleted, Item::Task(task.id)));
+                completed.push(task.id);
+            }
+            _ => {}
+        }
+    }
+    for task in after.tasks.unblocked_by(&completed) {
+        events.push((Event::TaskUnblocked, Item::Task(task.id)));
+    }
+    for note in after.notes.get_notes() {
+        if before.notes.get_note(&note.id).is_none() {
+            events.push((Event::NoteAdded, Item::Note(note.id)));
+        }
+    }
+    events
+}
+
+/// The database as it is before a command, when a hook might want to hear
+/// what the command does to it.
+pub fn snapshot(doc: &Config) -> Option<Database> {
+    let hooks = &doc.hooks;
+    if hooks.task_added.is_none()
+        && hooks.task_completed.is_none()
+        && hooks.task_unblocked.is_none()
+        && hooks.note_added.is_none()
+    {
+        return None;
+    }
+    db::open(doc).ok()
+}
+
+/// Runs the hooks for whatever changed since `before`. Call it once the
+/// command is done and the database lock is released, so that hooks can
+/// run regia themselves.
+pub fn fire(doc: &Config, before: &Database) {
+    let after = match db::open(doc) {
+        Ok(after) => after,
+        Err(err) => {
+            eprintln!("regia: could not run hooks: {}", err);
+            return;
+        }
+    };
+    for (event, item) in events(before, &after) {
+        let (task, note) = match item {
+            Item::Task(id) => (after.tasks.get_task(&id), None),
+            Item::Note(id) => (None, after.notes.get_note(&id)),
+        };
+        run(doc, event, &after.tasks, task, note, after.active_project());
+    }
+}
+
+/// Sends `event` about `task` or `note` to its hook, if one is set. A hook
+/// failing only gets a warning; the command it followed still succeeded.
+pub fn run(
+    doc: &Config,
+    event: Event,
+    tasks: &Tasks,
+    task: Option<&Task>,
+    note: Option<&Note>,
+    project: Option<&str>,
+) {
+    let hook = match event.hook(doc) {
+        Some(hook) => hook,
+        None => return,
+    };
+    let now = Utc::now();
+    let payload = Payload {
+        event: event.name(),
+        at: now,
+        task: task.map(|task| output::TaskRecord::new(task, tasks, now)),
+        note: note.map(output::NoteRecord::new),
+        project,
+    };
+    let result = serde_json::to_vec(&payload)
+        .map_err(|err| RegiaError::storage(err.to_string()))
+        .and_then(|body| deliver(hook, event.name(), &body));
+    if let Err(err) = result {
+        eprintln!("regia: {} hook failed: {}", event.name(), err);
+    }
+}
+
+fn is_url(hook: &str) -> bool {
+    hook.starts_with("http://") || hook.starts_with("https://")
+}
+
+/// POSTs `body` to the URL `hook` through `curl`, or pipes it to the shell
+/// command `hook`, waiting for either to finish.
+fn deliver(hook: &str, event: &str, body: &[u8]) -> error::Result<()> {
+    let mut command = if is_url(hook) {
+        let mut command = Command::new("curl");
+        command
+            .args(["--silent", "--show-error", "--fail", "--max-time", "10"])
+            .args(["--request", "POST"])
+            .args(["--header", "Content-Type: application/json"])
+            .args(["--header", &format!("X-Regia-Event: {}", event)])
+            .args(["--data-binary", "@-"])
+            .arg(hook)
+            .stdout(Stdio::null());
+        command
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", hook]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", hook]);
+        command
+    };
+    let mut child = command
+        .env("REGIA_EVENT", event)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| RegiaError::from(err).context(format!("can't run {}", hook)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that doesn't read its input is fine
+        match stdin.write_all(body) {
+            Err(err) if err.kind() != ErrorKind::BrokenPipe => return Err(err.into()),
+            _ => {}
+        }
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(RegiaError::storage(format!(
+            "{} exited with {}",
+            hook, status
+        )));
+    }
+    Ok(())
+}
+
+/// The due date each overdue task was last reported with, so a task is
+/// reported once per deadline it misses.
+pub type Overdue = BTreeMap<Uuid, DateTime<Utc>>;
+
+/// Kept beside the database, like the daemon's reminders.
+pub fn overdue_path(db_path: &Path) -> PathBuf {
+    db::sibling_path(db_path, ".overdue.json")
+}
+
+/// The overdue tasks reported so far, or `None` when nothing has been yet.
+pub fn read_overdue(path: &Path) -> error::Result<Option<Overdue>> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|err| RegiaError::corrupt(format!("{}: {}", path.display(), err))),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub fn write_overdue(path: &Path, overdue: &Overdue) -> error::Result<()> {
+    let json = serde_json::to_string_pretty(overdue)
+        .map_err(|err| RegiaError::storage(format!("Serialization failed: {}", err)))?;
+    Ok(db::write_to_disk(path, json.as_bytes())?)
+}
+
+/// Open tasks whose due date passed by `now` and that haven't been
+/// reported with that due date, all recorded in `reported`. Tasks done or
+/// no longer overdue are forgotten, so missing a new deadline reports
+/// again.
+pub fn newly_overdue<'a>(
+    tasks: &'a [Task],
+    reported: &mut Overdue,
+    now: DateTime<Utc>,
+) -> Vec<&'a Task> {
+    let mut overdue = Vec::new();
+    let mut still = Overdue::new();
+    for task in tasks.iter().filter(|task| !task.is_done()) {
+        let due = match task.due {
+            Some(due) if due < now => due,
+            _ => continue,
+        };
+        if reported.get(&task.id) != Some(&due) {
+            overdue.push(task);
+        }
+        still.insert(task.id, due);
+    }
+    *reported = still;
+    overdue
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn changes_become_events() {
+        let mut before = Database::default();
+        let open = Task::new(String::from("open"), 0);
+        let finishing = Task::new(String::from("finishing"), 0);
+        let mut waiting = Task::new(String::from("waiting"), 0);
+        waiting.add_dependency(&finishing.id);
+        let mut finished = Task::new(String::from("finished"), 0);
+        finished.complete();
+        for task in [
+            open.clone(),
+            finishing.clone(),
+            finished.clone(),
+            waiting.clone(),
+        ] {
+            before.tasks.add(task);
+        }
+
+        let mut after = before.clone();
+        after.tasks.get_task_mut(&finishing.id).unwrap().complete();
+        after.tasks.get_task_mut(&finished.id).unwrap().content = String::from("renamed");
+        let added = Task::new(String::from("added"), 0);
+        after.tasks.add(added.clone());
+        let note = Note::new("a note");
+        after.notes.add(note.clone());
+        let mut events = events(&before, &after);
+        events.sort_by_key(|(event, _)| event.name());
+        assert_eq!(
+            events,
+            vec![
+                (Event::NoteAdded, Item::Note(note.id)),
+                (Event::TaskAdded, Item::Task(added.id)),
+                (Event::TaskCompleted, Item::Task(finishing.id)),
+                (Event::TaskUnblocked, Item::Task(waiting.id)),
+            ]
+        );
+    }
+
+    #[test]
+    fn overdue_tasks_report_once_per_deadline() {
+        let now = Utc::now();
+        let mut late = Task::new(String::from("late"), 0);
+        late.due = Some(now - Duration::hours(1));
+        let mut later = Task::new(String::from("later"), 0);
+        later.due = Some(now + Duration::hours(1));
+        let mut tasks = vec![late, later];
+        let mut reported = Overdue::new();
+
+        let ids = |found: Vec<&Task>| found.iter().map(|task| task.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(newly_overdue(&tasks, &mut reported, now)),
+            vec![tasks[0].id]
+        );
+        assert!(newly_overdue(&tasks, &mut reported, now).is_empty());
+
+        let two_hours = now + Duration::hours(2);
+        assert_eq!(
+            ids(newly_overdue(&tasks, &mut reported, two_hours)),
+            vec![tasks[1].id]
+        );
+        // Put off and missed again, it reports again
+        tasks[0].due = Some(now + Duration::minutes(90));
+        assert_eq!(
+            ids(newly_overdue(&tasks, &mut reported, two_hours)),
+            vec![tasks[0].id]
+        );
+        tasks[0].complete();
+        newly_overdue(&tasks, &mut reported, two_hours);
+        assert_eq!(reported.len(), 1);
+    }
+}
@@ -0,0 +1,63 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use regia_core::error::{self, RegiaError};
+
+/// A line for the curl config file `request` takes, quoted for it.
+pub fn config_line(option: &str, value: &str) -> String {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("{} = \"{}\"", option, value)
+}
+
+/// Sends a request through `curl` and returns the status and body of the
+/// response. The URL and the `config` lines go in a curl config file
+/// rather than on the command line, where other users could read them, so
+/// credentials belong there too.
+pub fn request(
+    method: &str,
+    url: &str,
+    headers: &[&str],
+    config: &[String],
+    body: Option<&str>,
+) -> error::Result<(u32, String)> {
+    let mut command = Command::new("curl");
+    command
+        .args(["--silent", "--show-error", "--location", "--max-time", "30"])
+        .args(["--request", method])
+        .args(["--write-out", "\n%{http_code}"]);
+    for header in headers {
+        command.args(["--header", header]);
+    }
+    if body.is_some() {
+        command.args(["--data-binary", "@-"]);
+    }
+    let mut lines = vec![config_line("url", url)];
+    lines.extend(config.iter().cloned());
+    let file = tempfile::NamedTempFile::new()?;
+    std::fs::write(file.path(), format!("{}\n", lines.join("\n")))?;
+    let mut child = command
+        .arg("--config")
+        .arg(file.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| RegiaError::from(err).context("can't run curl"))?;
+    let mut stdin = child.stdin.take().unwrap();
+    if let Some(body) = body {
+        stdin.write_all(body.as_bytes())?;
+    }
+    drop(stdin);
+    let output = child.wait_with_output()?;
+    drop(file);
+    if !output.status.success() {
+        return Err(RegiaError::storage(format!(
+            "{} failed: {}",
+            method,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let (body, status) = text.rsplit_once('\n').unwrap_or(("", &text));
+    Ok((status.trim().parse().unwrap_or(0), body.to_string()))
+}
@@ -0,0 +1,339 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Utc};
+
+use regia_core::schedule;
+use regia_core::todo::{RepeatType, Task, TaskType, Tasks};
+
+fn fmt_stamp(dt: &DateTime<Utc>) -> String {
+    dt.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes TEXT values per RFC 5545 section 3.3.11.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line to 75 octets, continuing with a leading space.
+fn fold(line: &str, out: &mut String) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// How many occurrences of a cron rule are listed, since RRULE can't
+/// express one.
+const CRON_OCCURRENCES: usize = 52;
+
+/// The RRULE describing `repeat`, or for cron rules an RDATE list of the
+/// occurrences after `due`.
+pub fn recurrence(due: &DateTime<Utc>, repeat: &RepeatType) -> String {
+    const BYDAY: [&str; 7] = ["MO", "TU", "WE", "TH", "FR", "SA", "SU"];
+    match repeat {
+        RepeatType::Daily => String::from("RRULE:FREQ=DAILY"),
+        RepeatType::Weekly => String::from("RRULE:FREQ=WEEKLY"),
+        RepeatType::Monthly => String::from("RRULE:FREQ=MONTHLY"),
+        RepeatType::EveryDays(days) => format!("RRULE:FREQ=DAILY;INTERVAL={}", days),
+        RepeatType::Weekdays(days) => {
+            let byday: Vec<&str> = BYDAY
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| days & (1 << index) != 0)
+                .map(|(_, day)| *day)
+                .collect();
+            format!("RRULE:FREQ=WEEKLY;BYDAY={}", byday.join(","))
+        }
+        RepeatType::LastDayOfMonth => String::from("RRULE:FREQ=MONTHLY;BYMONTHDAY=-1"),
+        RepeatType::Cron(_) => {
+            let dates: Vec<String> = schedule::upcoming(*due, repeat, CRON_OCCURRENCES, &Local)
+                .iter()
+                .map(fmt_stamp)
+                .collect();
+            format!("RDATE:{}", dates.join(","))
+        }
+    }
+}
+
+/// The component for `task`: a recurring event when given its `repeat`,
+/// and otherwise a to-do with a due date.
+fn component(
+    task: &Task,
+    due: &DateTime<Utc>,
+    repeat: Option<&RepeatType>,
+    now: &DateTime<Utc>,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    let kind = if repeat.is_some() { "VEVENT" } else { "VTODO" };
+    lines.push(format!("BEGIN:{}", kind));
+    lines.push(format!("UID:{}", uid(task)));
+    lines.push(format!("DTSTAMP:{}", fmt_stamp(now)));
+    lines.push(format!("CREATED:{}", fmt_stamp(&task.created)));
+    lines.push(format!("SUMMARY:{}", escape(&task.content)));
+    if let Some(repeat) = repeat {
+        lines.push(format!("DTSTART:{}", fmt_stamp(due)));
+        lines.push(format!("DTEND:{}", fmt_stamp(due)));
+        lines.push(recurrence(due, repeat));
+    } else {
+        if let Some(scheduled) = task.scheduled.filter(|scheduled| scheduled <= due) {
+            lines.push(format!("DTSTART:{}", fmt_stamp(&scheduled)));
+        }
+        lines.push(format!("DUE:{}", fmt_stamp(due)));
+        match task.completed {
+            Some(completed) => {
+                lines.push(String::from("STATUS:COMPLETED"));
+                lines.push(format!("COMPLETED:{}", fmt_stamp(&completed)));
+            }
+            None => lines.push(String::from("STATUS:NEEDS-ACTION")),
+        }
+    }
+    if task.priority.weight() > 0 {
+        // iCalendar priorities run 1 (highest) to 9 (lowest)
+        let priority = 10u32.saturating_sub(task.priority.weight().min(9));
+        lines.push(format!("PRIORITY:{}", priority));
+    }
+    if !task.tags.is_empty() {
+        let mut tags: Vec<String> = task.tags.iter().map(|tag| escape(tag)).collect();
+        tags.sort();
+        lines.push(format!("CATEGORIES:{}", tags.join(",")));
+    }
+    lines.push(format!("END:{}", kind));
+    lines
+}
+
+/// Renders every task with a due date as an iCalendar document. Of a
+/// repeated task only the newest open instance is kept, since its RRULE
+/// already describes the rest of the series.
+pub fn to_ics(tasks: &Tasks, now: DateTime<Utc>) -> String {
+    let mut components = Vec::new();
+    let mut dated: Vec<&Task> = tasks
+        .get_tasks()
+        .iter()
+        .filter(|task| task.due.is_some())
+        .filter(|task| task.repeat.is_none() || (task.successor.is_none() && !task.is_done()))
+        .collect();
+    dated.sort_by_key(|task| task.due);
+    for task in dated {
+        // Repeated tasks become recurring events so calendars lay out the
+        // whole series; one-off deadlines are to-dos with a due date.
+        let repeat = match task.task_type {
+            Some(TaskType::Repeated) => task.repeat.as_ref(),
+            _ => None,
+        };
+        components.extend(component(task, task.due.as_ref().unwrap(), repeat, &now));
+    }
+    calendar(components)
+}
+
+/// A calendar holding `task` alone as a to-do, the way each resource in a
+/// CalDAV collection holds one item. Repeats are left out, since regia
+/// spawns the next occurrence itself.
+pub fn todo_calendar(task: &Task, now: DateTime<Utc>) -> String {
+    calendar(component(task, task.due.as_ref().unwrap(), None, &now))
+}
+
+fn calendar(components: Vec<String>) -> String {
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        String::from("PRODID:-//regia//regia//EN"),
+        String::from("CALSCALE:GREGORIAN"),
+    ];
+    lines.extend(components);
+    lines.push(String::from("END:VCALENDAR"));
+
+    let mut out = String::new();
+    for line in lines {
+        fold(&line, &mut out);
+    }
+    out
+}
+
+/// The UID a task is exported under.
+pub fn uid(task: &Task) -> String {
+    format!("{}@regia", task.id)
+}
+
+/// The parts of a VTODO read back from a calendar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteTodo {
+    pub uid: String,
+    pub summary: Option<String>,
+    pub due: Option<DateTime<Utc>>,
+    pub done: bool,
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+/// Reads a DATE-TIME or DATE value. Times with a TZID or none at all are
+/// taken as UTC, which is what regia itself writes.
+fn parse_stamp(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim_end_matches('Z');
+    if let Ok(stamp) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        return Some(stamp.and_utc());
+    }
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .ok()
+        .map(|day| day.and_hms_opt(0, 0, 0).unwrap().and_utc())
+}
+
+/// Every VTODO in an iCalendar document.
+pub fn parse_todos(text: &str) -> Vec<RemoteTodo> {
+    // Unfold continuation lines first
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut todos = Vec::new();
+    let mut current: Option<RemoteTodo> = None;
+    for line in lines {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name, value),
+            None => continue,
+        };
+        // Parameters such as `;TZID=...` follow the property name
+        let name = name.split(';').next().unwrap_or(name).to_ascii_uppercase();
+        match (name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if value == "VTODO" => {
+                current = Some(RemoteTodo {
+                    uid: String::new(),
+                    summary: None,
+                    due: None,
+                    done: false,
+                })
+            }
+            ("END", Some(_)) if value == "VTODO" => todos.extend(current.take()),
+            ("UID", Some(todo)) => todo.uid = value.to_string(),
+            ("SUMMARY", Some(todo)) => todo.summary = Some(unescape(value)),
+            ("DUE", Some(todo)) => todo.due = parse_stamp(value),
+            ("STATUS", Some(todo)) => todo.done = value.eq_ignore_ascii_case("COMPLETED"),
+            ("COMPLETED", Some(todo)) => todo.done = true,
+            _ => {}
+        }
+    }
+    todos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn renders_todos_and_recurring_events() {
+        let due = Utc.with_ymd_and_hms(2019, 10, 4, 17, 0, 0).unwrap();
+        let mut tasks = Tasks::default();
+        tasks.add(Task::new_date(
+            String::from("file taxes; all of them"),
+            0,
+            Some(due),
+            TaskType::Deadline,
+            None,
+        ));
+        tasks.add(Task::new_date(
+            String::from("standup"),
+            0,
+            Some(due),
+            TaskType::Repeated,
+            Some(RepeatType::Weekly),
+        ));
+        tasks.add(Task::new(String::from("undated"), 0));
+
+        let ics = to_ics(&tasks, due);
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.contains("BEGIN:VTODO\r\n"));
+        assert!(ics.contains("SUMMARY:file taxes\\; all of them\r\n"));
+        assert!(ics.contains("DUE:20191004T170000Z\r\n"));
+        assert!(ics.contains("BEGIN:VEVENT\r\n"));
+        assert!(ics.contains("RRULE:FREQ=WEEKLY\r\n"));
+        assert!(!ics.contains("undated"));
+    }
+
+    #[test]
+    fn custom_recurrences() {
+        // A Friday
+        let due = Utc.with_ymd_and_hms(2019, 10, 4, 9, 0, 0).unwrap();
+        let rule = |text: &str| recurrence(&due, &RepeatType::parse(text).unwrap());
+        assert_eq!(rule("every 3 days"), "RRULE:FREQ=DAILY;INTERVAL=3");
+        assert_eq!(rule("every mon,fri"), "RRULE:FREQ=WEEKLY;BYDAY=MO,FR");
+        assert_eq!(
+            rule("last day of month"),
+            "RRULE:FREQ=MONTHLY;BYMONTHDAY=-1"
+        );
+        let rdate = rule("0 9 * * mon");
+        assert!(rdate.starts_with("RDATE:20191007T090000Z,20191014T090000Z,"));
+        assert_eq!(rdate.split(',').count(), CRON_OCCURRENCES);
+    }
+
+    #[test]
+    fn reads_back_todos() {
+        let due = Utc.with_ymd_and_hms(2019, 10, 4, 17, 0, 0).unwrap();
+        let mut task = Task::new_date(
+            String::from("file taxes; all of them, really"),
+            0,
+            Some(due),
+            TaskType::Repeated,
+            Some(RepeatType::Weekly),
+        );
+        task.complete();
+        let ics = todo_calendar(&task, due);
+        assert!(ics.contains("BEGIN:VTODO\r\n"));
+        assert!(!ics.contains("RRULE"));
+        assert_eq!(
+            parse_todos(&ics),
+            [RemoteTodo {
+                uid: uid(&task),
+                summary: Some(task.content.clone()),
+                due: Some(due),
+                done: true,
+            }]
+        );
+
+        let other = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:abc\r\nDUE;VALUE=DATE:2019100\r\n 5\r\nSTATUS:NEEDS-ACTION\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+        let todos = parse_todos(other);
+        assert_eq!(todos[0].uid, "abc");
+        assert_eq!(
+            todos[0].due,
+            Some(Utc.with_ymd_and_hms(2019, 10, 5, 0, 0, 0).unwrap())
+        );
+        assert!(!todos[0].done);
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let mut out = String::new();
+        fold(&"x".repeat(100), &mut out);
+        let lines: Vec<&str> = out.split("\r\n").collect();
+        assert_eq!(lines[0].len(), 75);
+        assert_eq!(lines[1], format!(" {}", "x".repeat(25)));
+    }
+}
@@ -0,0 +1,238 @@
+//! `regia in` and `regia triage`: get a thought down in as few keystrokes
+//! as possible, and decide what it is later.
+
+use std::io::{self, BufRead, Write};
+
+use chrono::{DateTime, Utc};
+use clap::ArgMatches;
+use colored::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::journal;
+use crate::output::{self, Output};
+use regia_core::conf::Config;
+use regia_core::dates;
+use regia_core::db;
+use regia_core::error;
+use regia_core::inbox::Item;
+use regia_core::note::Note;
+use regia_core::priority::Priority;
+use regia_core::tags;
+use regia_core::theme::Themed;
+use regia_core::todo::Task;
+
+#[derive(Serialize)]
+struct ItemRecord {
+    id: Uuid,
+    created: DateTime<Utc>,
+    content: String,
+}
+
+impl output::Record for ItemRecord {
+    fn plain(&self) -> String {
+        output::fields(&[
+            self.id.to_string(),
+            self.created.to_rfc3339(),
+            self.content.clone(),
+        ])
+    }
+}
+
+fn age(item: &Item, now: DateTime<Utc>) -> String {
+    format!("({} ago)", dates::fmt_duration(now - item.created))
+        .dimmed()
+        .to_string()
+}
+
+fn handle_list(db: &db::Database, doc: &Config) -> error::Result<()> {
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        let records: Vec<ItemRecord> = db
+            .inbox
+            .iter()
+            .map(|item| ItemRecord {
+                id: item.id,
+                created: item.created,
+                content: item.content.clone(),
+            })
+            .collect();
+        return output::print(format, &records);
+    }
+    if db.inbox.is_empty() {
+        println!("The inbox is empty");
+    }
+    let now = Utc::now();
+    for item in db.inbox.iter() {
+        println!("* {} {}", item.content, age(item, now));
+    }
+    Ok(())
+}
+
+/// `regia in TEXT`: captures `TEXT` in the inbox, or lists the inbox when
+/// there is none.
+pub fn handle_in(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let mut db = db::open(doc)?;
+    let words: Vec<&str> = matches.values_of("text").into_iter().flatten().collect();
+    let content = words.join(" ");
+    if content.trim().is_empty() {
+        return handle_list(&db, doc);
+    }
+
+    let before = db.clone();
+    db.inbox.push(Item::new(content.trim()));
+    println!(
+        "{} {}",
+        "Captured".header(),
+        format!("({} in the inbox)", db.inbox.len()).dimmed()
+    );
+    journal::record(db_path, "in", &before, &mut db)?;
+    db.to_disk(db_path)
+}
+
+/// What to make of one inbox item.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Choice {
+    Task,
+    Note,
+    Discard,
+    Skip,
+    Quit,
+}
+
+impl Choice {
+    fn parse(input: &str) -> Option<Choice> {
+        match input.trim().to_lowercase().as_str() {
+            "t" | "task" => Some(Choice::Task),
+            "n" | "note" => Some(Choice::Note),
+            "d" | "discard" => Some(Choice::Discard),
+            "s" | "skip" | "" => Some(Choice::Skip),
+            "q" | "quit" => Some(Choice::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// Asks what to make of an item until it gets an answer; the end of
+/// input quits.
+fn ask(lines: &mut impl Iterator<Item = io::Result<String>>) -> error::Result<Choice> {
+    loop {
+        print!(
+            "{} [{}]ask, [{}]ote, [{}]iscard, [{}]kip or [{}]uit? ",
+            "Make it a".header(),
+            "t".bold(),
+            "n".bold(),
+            "d".bold(),
+            "s".bold(),
+            "q".bold()
+        );
+        io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => {
+                println!();
+                return Ok(Choice::Quit);
+            }
+        };
+        match Choice::parse(&line) {
+            Some(choice) => return Ok(choice),
+            None => println!("Didn't understand {}, please type t, n, d, s or q", line),
+        }
+    }
+}
+
+/// What a triage made of the inbox.
+#[derive(Debug, Default)]
+pub struct Triaged {
+    pub tasks: usize,
+    pub notes: usize,
+    pub discarded: usize,
+    /// Whether it was stopped before the last item
+    pub quit: bool,
+}
+
+/// Walks the inbox of `db` oldest first, asking on `lines` what to make of
+/// each item: a task or a note of the current project, or nothing. Skipped
+/// items stay for next time.
+pub fn triage(
+    db: &mut db::Database,
+    lines: &mut impl Iterator<Item = io::Result<String>>,
+) -> error::Result<Triaged> {
+    let now = Utc::now();
+    let items = std::mem::take(&mut db.inbox);
+    let total = items.len();
+    let mut triaged = Triaged::default();
+    let mut left = Vec::new();
+    let mut items = items.into_iter().enumerate();
+    for (index, item) in items.by_ref() {
+        println!(
+            "{} {} {}",
+            format!("[{}/{}]", index + 1, total).dimmed(),
+            item.content.bold(),
+            age(&item, now)
+        );
+        match ask(lines)? {
+            Choice::Task => {
+                db.tasks.add(Task::new(item.content, Priority::None));
+                triaged.tasks += 1;
+            }
+            Choice::Note => {
+                let mut note = Note::new(&item.content);
+                note.tags = tags::extract(&item.content);
+                db.notes.add(note);
+                triaged.notes += 1;
+            }
+            Choice::Discard => triaged.discarded += 1,
+            Choice::Skip => left.push(item),
+            Choice::Quit => {
+                left.push(item);
+                triaged.quit = true;
+                break;
+            }
+        }
+    }
+    left.extend(items.map(|(_, item)| item));
+    db.inbox = left;
+    Ok(triaged)
+}
+
+/// `regia triage`: see [`triage`].
+pub fn handle_triage(_matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let mut db = db::open(doc)?;
+    if db.inbox.is_empty() {
+        println!("The inbox is empty");
+        return Ok(());
+    }
+    let before = db.clone();
+    let stdin = io::stdin();
+    let triaged = triage(&mut db, &mut stdin.lock().lines())?;
+
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    println!(
+        "{} {} task{} and {} note{}, discarded {}, {} left in the inbox",
+        "Made".header(),
+        triaged.tasks,
+        plural(triaged.tasks),
+        triaged.notes,
+        plural(triaged.notes),
+        triaged.discarded,
+        db.inbox.len()
+    );
+    journal::record(db_path, "triage", &before, &mut db)?;
+    db.to_disk(db_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_choices() {
+        assert_eq!(Choice::parse("T"), Some(Choice::Task));
+        assert_eq!(Choice::parse(" note\n"), Some(Choice::Note));
+        assert_eq!(Choice::parse(""), Some(Choice::Skip));
+        assert_eq!(Choice::parse("x"), None);
+    }
+}
@@ -0,0 +1,584 @@
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{Cursor, ErrorKind as IOErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use clap::ArgMatches;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use regia_core::conf::Config;
+use regia_core::dates;
+use regia_core::db::{self, Database, Project};
+use regia_core::error::{self, RegiaError};
+use regia_core::inbox;
+use regia_core::index::same;
+use regia_core::note::Note;
+use regia_core::theme::Themed;
+use regia_core::todo::{Task, Tasks};
+use regia_core::trash::Trash;
+
+/// One item as it was before and after an operation. `None` on either side
+/// means the item did not exist, so adds have no `before` and removals no
+/// `after`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Change {
+    Task {
+        before: Option<Box<Task>>,
+        after: Option<Box<Task>>,
+    },
+    Note {
+        before: Option<Box<Note>>,
+        after: Option<Box<Note>>,
+    },
+    /// A task in the archive rather than the live list
+    Archived {
+        before: Option<Box<Task>>,
+        after: Option<Box<Task>>,
+    },
+    /// The trash as a whole
+    Trash {
+        before: Box<Trash>,
+        after: Box<Trash>,
+    },
+    /// The inbox as a whole, which every project shares
+    Inbox {
+        before: Vec<inbox::Item>,
+        after: Vec<inbox::Item>,
+    },
+    /// A named project as it is parked while another is active, or the
+    /// root one's when that project is
+    Project {
+        name: String,
+        before: Option<Box<Project>>,
+        after: Option<Box<Project>>,
+    },
+    /// The project chosen with `regia project switch`
+    Current {
+        before: Option<String>,
+        after: Option<String>,
+    },
+}
+
+impl Change {
+    fn inverted(&self) -> Change {
+        match self {
+            Change::Task { before, after } => Change::Task {
+                before: after.clone(),
+                after: before.clone(),
+            },
+            Change::Note { before, after } => Change::Note {
+                before: after.clone(),
+                after: before.clone(),
+            },
+            Change::Archived { before, after } => Change::Archived {
+                before: after.clone(),
+                after: before.clone(),
+            },
+            Change::Trash { before, after } => Change::Trash {
+                before: after.clone(),
+                after: before.clone(),
+            },
+            Change::Inbox { before, after } => Change::Inbox {
+                before: after.clone(),
+                after: before.clone(),
+            },
+            Change::Project {
+                name,
+                before,
+                after,
+            } => Change::Project {
+                name: name.clone(),
+                before: after.clone(),
+                after: before.clone(),
+            },
+            Change::Current { before, after } => Change::Current {
+                before: after.clone(),
+                after: before.clone(),
+            },
+        }
+    }
+
+    /// Moves `db` from the `before` side of this change to the `after` side.
+    fn apply(&self, db: &mut Database) {
+        match self {
+            Change::Task { before, after } => {
+                if let Some(task) = before.as_ref().or(after.as_ref()) {
+                    db.tasks.remove(task.id);
+                }
+                if let Some(task) = after {
+                    db.tasks.add((**task).clone());
+                }
+            }
+            Change::Note { before, after } => {
+                if let Some(note) = before.as_ref().or(after.as_ref()) {
+                    db.notes.remove(note.id);
+                }
+                if let Some(note) = after {
+                    db.notes.add((**note).clone());
+                }
+            }
+            Change::Archived { before, after } => {
+                if let Some(task) = before.as_ref().or(after.as_ref()) {
+                    db.archive.remove(task.id);
+                }
+                if let Some(task) = after {
+                    db.archive.add((**task).clone());
+                }
+            }
+            Change::Trash { after, .. } => db.trash = (**after).clone(),
+            Change::Inbox { after, .. } => db.inbox = after.clone(),
+            Change::Project { name, after, .. } => match after {
+                Some(project) => {
+                    db.projects.insert(name.clone(), (**project).clone());
+                }
+                None => {
+                    db.projects.remove(name);
+                }
+            },
+            Change::Current { after, .. } => db.current_project = after.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum EntryKind {
+    /// A command changed the database
+    Apply(Vec<Change>),
+    /// The `Apply` entry with this id was reverted
+    Undo(Uuid),
+    /// The `Apply` entry with this id was re-applied after an undo
+    Redo(Uuid),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    pub(crate) id: Uuid,
+    pub(crate) at: DateTime<Utc>,
+    pub(crate) command: String,
+    pub(crate) kind: EntryKind,
+    /// The named project the changes were made in, `None` for the root
+    #[serde(default)]
+    pub(crate) project: Option<String>,
+}
+
+/// The journal lives next to the database, as `<db>.journal`.
+pub fn journal_path(db_path: &Path) -> PathBuf {
+    db::sibling_path(db_path, ".journal")
+}
+
+type TaskChange = fn(Option<Box<Task>>, Option<Box<Task>>) -> Change;
+
+fn diff_tasks(before: &Tasks, after: &Tasks, change: TaskChange, changes: &mut Vec<Change>) {
+    let old_tasks: HashMap<Uuid, &Task> = before
+        .get_tasks()
+        .iter()
+        .map(|task| (task.id, task))
+        .collect();
+    for task in after.get_tasks() {
+        match old_tasks.get(&task.id) {
+            Some(old) if same(*old, task) => {}
+            old => changes.push(change(
+                old.map(|old| Box::new((*old).clone())),
+                Some(Box::new(task.clone())),
+            )),
+        }
+    }
+    for task in before.get_tasks() {
+        if after.get_task(&task.id).is_none() {
+            changes.push(change(Some(Box::new(task.clone())), None));
+        }
+    }
+}
+
+/// Every task, note, archived task and project that differs between
+/// `before` and `after`, and the trash, inbox and current project when
+/// they do.
+pub fn diff(before: &Database, after: &Database) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_tasks(
+        &before.tasks,
+        &after.tasks,
+        |before, after| Change::Task { before, after },
+        &mut changes,
+    );
+
+    let old_notes: HashMap<Uuid, &Note> = before
+        .notes
+        .get_notes()
+        .iter()
+        .map(|note| (note.id, note))
+        .collect();
+    for note in after.notes.get_notes() {
+        match old_notes.get(&note.id) {
+            Some(old) if same(*old, note) => {}
+            old => changes.push(Change::Note {
+                before: old.map(|old| Box::new((*old).clone())),
+                after: Some(Box::new(note.clone())),
+            }),
+        }
+    }
+    for note in before.notes.get_notes() {
+        if after.notes.get_note(&note.id).is_none() {
+            changes.push(Change::Note {
+                before: Some(Box::new(note.clone())),
+                after: None,
+            });
+        }
+    }
+
+    diff_tasks(
+        &before.archive,
+        &after.archive,
+        |before, after| Change::Archived { before, after },
+        &mut changes,
+    );
+
+    if !same(&before.trash, &after.trash) {
+        changes.push(Change::Trash {
+            before: Box::new(before.trash.clone()),
+            after: Box::new(after.trash.clone()),
+        });
+    }
+    if before.inbox != after.inbox {
+        changes.push(Change::Inbox {
+            before: before.inbox.clone(),
+            after: after.inbox.clone(),
+        });
+    }
+
+    for (name, project) in after.projects.iter() {
+        match before.projects.get(name) {
+            Some(old) if same(old, project) => {}
+            old => changes.push(Change::Project {
+                name: name.clone(),
+                before: old.map(|old| Box::new(old.clone())),
+                after: Some(Box::new(project.clone())),
+            }),
+        }
+    }
+    for (name, project) in before.projects.iter() {
+        if !after.projects.contains_key(name) {
+            changes.push(Change::Project {
+                name: name.clone(),
+                before: Some(Box::new(project.clone())),
+                after: None,
+            });
+        }
+    }
+    if before.current_project != after.current_project {
+        changes.push(Change::Current {
+            before: before.current_project.clone(),
+            after: after.current_project.clone(),
+        });
+    }
+    changes
+}
+
+fn append(db_path: &Path, entry: &Entry) -> error::Result<()> {
+    // A dry run leaves nothing to undo
+    if db::is_dry() {
+        return Ok(());
+    }
+    let mut buf = Vec::new();
+    if entry
+        .serialize(&mut rmp_serde::Serializer::new(&mut buf))
+        .is_err()
+    {
+        return Err(RegiaError::storage("Serialization failed"));
+    }
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    // Entries hold whole tasks and notes, so the journal is as private as
+    // the database's log
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(journal_path(db_path))?;
+    Ok(file.write_all(&buf)?)
+}
+
+pub fn read_entries(db_path: &Path) -> error::Result<Vec<Entry>> {
+    let buf = match db::read_from_disk(journal_path(db_path)) {
+        Ok(buf) => buf,
+        Err(err) if err.kind() == IOErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let len = buf.len() as u64;
+    let mut cursor = Cursor::new(buf);
+    let mut entries = Vec::new();
+    while cursor.position() < len {
+        let mut de = rmp_serde::Deserializer::new(&mut cursor);
+        match Entry::deserialize(&mut de) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => return Err(RegiaError::corrupt("Journal deserialization failed")),
+        }
+    }
+    Ok(entries)
+}
+
+/// Marks the tasks of `after` that differ from those of `before` as
+/// changed `at`, which is what staleness goes by.
+fn touch(before: &Tasks, after: &mut Tasks, at: DateTime<Utc>) {
+    let changed: Vec<Uuid> = after
+        .get_tasks()
+        .iter()
+        .filter(|task| match before.get_task(&task.id) {
+            Some(old) => !same(old, *task),
+            None => true,
+        })
+        .map(|task| task.id)
+        .collect();
+    for id in changed {
+        if let Some(mut task) = after.get_task_mut(&id) {
+            task.modified = Some(at);
+        }
+    }
+}
+
+/// Appends whatever `command` changed between `before` and `after`,
+/// stamping the tasks it changed. Commands that changed nothing leave no
+/// entry.
+pub fn record(
+    db_path: &Path,
+    command: &str,
+    before: &Database,
+    after: &mut Database,
+) -> error::Result<()> {
+    touch(&before.tasks, &mut after.tasks, Utc::now());
+    record_as_is(db_path, command, before, after)
+}
+
+/// [`record`] without stamping, for commands that bring back changes made
+/// earlier or elsewhere, which carry their own.
+pub fn record_as_is(
+    db_path: &Path,
+    command: &str,
+    before: &Database,
+    after: &Database,
+) -> error::Result<()> {
+    let changes = diff(before, after);
+    if changes.is_empty() {
+        return Ok(());
+    }
+    append(
+        db_path,
+        &Entry {
+            id: Uuid::new_v4(),
+            at: Utc::now(),
+            command: command.to_string(),
+            kind: EntryKind::Apply(changes),
+            project: after.active_project().map(String::from),
+        },
+    )
+}
+
+/// Replays the journal into the entries that can currently be undone and
+/// redone, most recent last.
+fn stacks(entries: &[Entry]) -> (Vec<&Entry>, Vec<&Entry>) {
+    let applied: HashMap<Uuid, &Entry> = entries
+        .iter()
+        .filter(|entry| matches!(entry.kind, EntryKind::Apply(_)))
+        .map(|entry| (entry.id, entry))
+        .collect();
+
+    let mut undo = Vec::new();
+    let mut redo = Vec::new();
+    for entry in entries {
+        match &entry.kind {
+            EntryKind::Apply(_) => {
+                undo.push(entry);
+                redo.clear();
+            }
+            EntryKind::Undo(target) => {
+                if let Some(found) = applied.get(target) {
+                    undo.retain(|done: &&Entry| done.id != *target);
+                    redo.push(*found);
+                }
+            }
+            EntryKind::Redo(target) => {
+                if let Some(found) = applied.get(target) {
+                    redo.retain(|undone: &&Entry| undone.id != *target);
+                    undo.push(*found);
+                }
+            }
+        }
+    }
+    (undo, redo)
+}
+
+fn changes_of(entry: &Entry) -> &[Change] {
+    match &entry.kind {
+        EntryKind::Apply(changes) => changes,
+        _ => &[],
+    }
+}
+
+fn summarize(verb: &str, entry: &Entry) {
+    println!(
+        "{} {} ({} change{}, {} UTC)",
+        verb.header(),
+        entry.command.bold(),
+        changes_of(entry).len(),
+        if changes_of(entry).len() == 1 {
+            ""
+        } else {
+            "s"
+        },
+        dates::locale().datetime(entry.at.naive_utc())
+    );
+}
+
+pub fn handle_undo(_matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let entries = read_entries(db_path)?;
+    let (undo, _) = stacks(&entries);
+    let target = match undo.last() {
+        Some(target) => *target,
+        None => {
+            println!("Nothing to undo");
+            return Ok(());
+        }
+    };
+
+    let mut db = Database::load(db_path)?;
+    db.activate(target.project.as_deref())?;
+    for change in changes_of(target).iter().rev() {
+        change.inverted().apply(&mut db);
+    }
+    db.to_disk(db_path)?;
+    append(
+        db_path,
+        &Entry {
+            id: Uuid::new_v4(),
+            at: Utc::now(),
+            command: String::from("undo"),
+            kind: EntryKind::Undo(target.id),
+            project: target.project.clone(),
+        },
+    )?;
+    summarize("Undid", target);
+    Ok(())
+}
+
+pub fn handle_redo(_matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let entries = read_entries(db_path)?;
+    let (_, redo) = stacks(&entries);
+    let target = match redo.last() {
+        Some(target) => *target,
+        None => {
+            println!("Nothing to redo");
+            return Ok(());
+        }
+    };
+
+    let mut db = Database::load(db_path)?;
+    db.activate(target.project.as_deref())?;
+    for change in changes_of(target) {
+        change.apply(&mut db);
+    }
+    db.to_disk(db_path)?;
+    append(
+        db_path,
+        &Entry {
+            id: Uuid::new_v4(),
+            at: Utc::now(),
+            command: String::from("redo"),
+            kind: EntryKind::Redo(target.id),
+            project: target.project.clone(),
+        },
+    )?;
+    summarize("Redid", target);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_and_revert() {
+        let mut before = Database::default();
+        let kept = Task::new(String::from("kept"), 0);
+        let removed = Task::new(String::from("removed"), 0);
+        before.tasks.add(kept.clone());
+        before.tasks.add(removed.clone());
+
+        let mut after = before.clone();
+        after.tasks.remove(removed.id);
+        after.tasks.get_task_mut(&kept.id).unwrap().priority = 3.into();
+        after.notes.add(Note::new("new note"));
+
+        let changes = diff(&before, &after);
+        assert_eq!(changes.len(), 3);
+
+        let mut db = after.clone();
+        for change in changes.iter().rev() {
+            change.inverted().apply(&mut db);
+        }
+        assert!(diff(&before, &db).is_empty());
+
+        for change in changes.iter() {
+            change.apply(&mut db);
+        }
+        assert!(diff(&after, &db).is_empty());
+    }
+
+    #[test]
+    fn reverts_projects() {
+        let mut before = Database::default();
+        before
+            .projects
+            .insert(String::from("old"), Project::named("old"));
+
+        // As a restore might leave it
+        let mut after = before.clone();
+        after.projects.remove("old");
+        let mut work = Project::named("work");
+        work.tasks.add(Task::new(String::from("ship it"), 0));
+        after.projects.insert(String::from("work"), work);
+        after.current_project = Some(String::from("work"));
+
+        let changes = diff(&before, &after);
+        assert_eq!(changes.len(), 3);
+        let mut db = after.clone();
+        for change in changes.iter().rev() {
+            change.inverted().apply(&mut db);
+        }
+        assert_eq!(db, before);
+    }
+
+    #[test]
+    fn undo_redo_stacks() {
+        let entry = |kind| Entry {
+            id: Uuid::new_v4(),
+            at: Utc::now(),
+            command: String::from("test"),
+            kind,
+            project: None,
+        };
+        let first = entry(EntryKind::Apply(vec![]));
+        let second = entry(EntryKind::Apply(vec![]));
+        let mut entries = vec![first.clone(), second.clone()];
+
+        entries.push(entry(EntryKind::Undo(second.id)));
+        let (undo, redo) = stacks(&entries);
+        assert_eq!(undo.last().unwrap().id, first.id);
+        assert_eq!(redo.last().unwrap().id, second.id);
+
+        entries.push(entry(EntryKind::Redo(second.id)));
+        let (undo, redo) = stacks(&entries);
+        assert_eq!(undo.last().unwrap().id, second.id);
+        assert!(redo.is_empty());
+
+        // A new operation after an undo drops the redo history
+        entries.push(entry(EntryKind::Undo(second.id)));
+        entries.push(entry(EntryKind::Apply(vec![])));
+        let (undo, redo) = stacks(&entries);
+        assert_eq!(undo.len(), 2);
+        assert!(redo.is_empty());
+    }
+}
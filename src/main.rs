@@ -1,15 +1,61 @@
-use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
 
-use clap::{App, AppSettings, Arg, SubCommand};
-use serde_yaml;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use colored::*;
+use regia_core::conf::{self, Config};
+use regia_core::error::{self, RegiaError};
+use regia_core::theme::Themed;
+use regia_core::{db, lock, theme};
 
-mod conf;
-mod db;
-mod note;
+mod agenda;
+mod archive;
+mod attach;
+mod backup;
+mod batch;
+mod board;
+mod caldav;
+mod clipboard;
+mod compact;
+mod config;
+mod contexts;
+mod daemon;
+mod databases;
+mod diary;
+mod dryrun;
+mod editor;
+mod exchange;
+mod fsck;
+mod github;
+mod graph;
+mod habits;
+mod hooks;
+mod http;
+mod ics;
+mod inbox;
+mod journal;
+mod next;
 mod notetaker;
+mod notify;
+mod output;
+mod pager;
+mod plan;
+mod plugins;
+mod pomo;
+mod projects;
+mod prompt;
+mod report;
+mod review;
+mod search;
+mod selector;
+mod shell;
+mod streaks;
+mod sync;
+mod tags;
 mod taskmaster;
-mod todo;
+mod todotxt;
+mod trash;
+mod ui;
+mod views;
 
 fn expand_tilde<P: AsRef<Path>>(path_user_input: P) -> Option<PathBuf> {
     let p = path_user_input.as_ref();
@@ -33,28 +79,389 @@ fn expand_tilde<P: AsRef<Path>>(path_user_input: P) -> Option<PathBuf> {
     }
 }
 
-fn main() -> std::io::Result<()> {
-    let app = App::new("regia")
+/// The flags choosing the context `task ls`, `view`, `next` and `agenda`
+/// keep to.
+fn context_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("context")
+            .long("context")
+            .takes_value(true)
+            .value_name("@CONTEXT")
+            .help("Only tasks for this context or none, instead of the one set"),
+        Arg::with_name("any-context")
+            .long("any-context")
+            .conflicts_with("context")
+            .help("Ignore the context set with regia context set"),
+    ]
+}
+
+/// The filter flags shared by `task ls`, `view` and `view save`.
+fn filter_args() -> Vec<Arg<'static, 'static>> {
+    let mut args = vec![
+        Arg::with_name("all")
+            .short("a")
+            .long("all")
+            .help("Include completed tasks"),
+        Arg::with_name("tag")
+            .short("t")
+            .long("tag")
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true)
+            .value_name("TAG"),
+        Arg::with_name("due-before")
+            .long("due-before")
+            .takes_value(true)
+            .value_name("DATE"),
+        Arg::with_name("due-after")
+            .long("due-after")
+            .takes_value(true)
+            .value_name("DATE"),
+        Arg::with_name("priority-min")
+            .long("priority-min")
+            .takes_value(true)
+            .value_name("PRIORITY"),
+        Arg::with_name("overdue")
+            .long("overdue")
+            .help("Only open tasks past their due date"),
+        Arg::with_name("blocked")
+            .long("blocked")
+            .help("Only open tasks waiting on dependencies"),
+        Arg::with_name("snoozed")
+            .long("snoozed")
+            .help("Only snoozed tasks, which are otherwise hidden"),
+        Arg::with_name("upcoming")
+            .long("upcoming")
+            .help("Include tasks scheduled to start later"),
+        Arg::with_name("stale")
+            .long("stale")
+            .help("Only open tasks untouched for tasks.stale_after"),
+        Arg::with_name("waiting")
+            .long("waiting")
+            .help("Only tasks waiting on someone, see task wait"),
+        Arg::with_name("where")
+            .long("where")
+            .takes_value(true)
+            .value_name("QUERY")
+            .help("Only tasks this query picks, see regia query"),
+        // No default, so a view's own order holds unless one is given
+        Arg::with_name("sort")
+            .long("sort")
+            .takes_value(true)
+            .possible_values(&["day", "due", "priority", "created"])
+            .help("Order by day (the default), due, priority or created"),
+        Arg::with_name("limit")
+            .long("limit")
+            .takes_value(true)
+            .value_name("N"),
+    ];
+    args.extend(page_args());
+    args.extend(context_args());
+    args
+}
+
+/// `--page` and `--per-page`, for listings that may run long.
+fn page_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("page")
+            .long("page")
+            .takes_value(true)
+            .value_name("N")
+            .help("Show this page of the list, counting from 1"),
+        Arg::with_name("per-page")
+            .long("per-page")
+            .takes_value(true)
+            .value_name("N")
+            .help("How many to show on a page [default: 25]"),
+    ]
+}
+
+/// What `note ls` and `note pinned` take.
+fn note_list_args() -> Vec<Arg<'static, 'static>> {
+    let mut args = vec![
+        Arg::with_name("tag")
+            .short("t")
+            .long("tag")
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true)
+            .value_name("TAG"),
+        Arg::with_name("raw")
+            .long("raw")
+            .help("Print notes as written instead of rendering markdown"),
+        Arg::with_name("full")
+            .long("full")
+            .help("Print long notes whole instead of cutting them short"),
+    ];
+    args.extend(page_args());
+    args
+}
+
+/// Runs the handler for the subcommand in `matches`. Each handler opens
+/// the database itself, once, the way it needs it.
+fn dispatch(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    if let Some(matches) = matches.subcommand_matches("task") {
+        taskmaster::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("note") {
+        notetaker::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("in") {
+        inbox::handle_in(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("triage") {
+        inbox::handle_triage(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("review") {
+        review::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("agenda") {
+        agenda::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("next") {
+        next::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("plan") {
+        plan::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("pomo") {
+        pomo::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("context") {
+        contexts::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("streaks") {
+        streaks::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("habit") {
+        habits::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("board") {
+        board::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("attach") {
+        attach::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("trash") {
+        trash::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("search") {
+        search::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("export") {
+        exchange::handle_export(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("import") {
+        exchange::handle_import(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("project") {
+        projects::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("daemon") {
+        daemon::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("notify") {
+        notify::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("undo") {
+        journal::handle_undo(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("redo") {
+        journal::handle_redo(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("ui") {
+        ui::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("tick") {
+        taskmaster::handle_tick(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("report") {
+        report::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("sync") {
+        sync::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("tag") {
+        tags::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("journal") {
+        diary::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("view") {
+        views::handle_it(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("query") {
+        taskmaster::handle_query(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("backup") {
+        backup::handle_backup(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("restore") {
+        backup::handle_restore(matches, doc)
+    } else if let Some(matches) = matches.subcommand_matches("db") {
+        match matches.subcommand() {
+            ("fsck", Some(matches)) => fsck::handle_it(matches, doc),
+            ("compact", Some(matches)) => compact::handle_it(matches, doc),
+            ("ls", Some(matches)) => databases::handle_it(matches, doc),
+            _ => unreachable!(),
+        }
+    } else if let Some(matches) = matches.subcommand_matches("shell") {
+        shell::handle_it(matches, doc)
+    } else {
+        unreachable!();
+    }
+}
+
+/// The names of `app`'s own subcommands; anything else runs as a plugin.
+const COMMANDS: [&str; 36] = [
+    "agenda", "attach", "backup", "board", "config", "context", "daemon", "db", "export", "habit",
+    "import", "in", "journal", "next", "note", "notify", "plan", "pomo", "project", "query",
+    "redo", "report", "restore", "review", "search", "shell", "streaks", "sync", "tag", "task",
+    "tick", "trash", "triage", "ui", "undo", "view",
+];
+
+/// The command line `regia` takes, also parsed line by line by `regia
+/// shell`.
+fn app() -> App<'static, 'static> {
+    App::new("regia")
         .version("0.1")
         .about("The solution to your problems")
         .author("Teague Lasser")
         .setting(AppSettings::SubcommandRequired)
+        .setting(AppSettings::AllowExternalSubcommands)
         .arg(
             Arg::with_name("config")
                 .long("config")
                 .takes_value(true)
                 .value_name("FILE"),
         )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .global(true)
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&output::Output::NAMES)
+                .help("Print records as json, yaml or plain tab-separated lines"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .global(true)
+                .conflicts_with("output")
+                .help("Print no list, only exit with 6 when it would be empty"),
+        )
+        .arg(
+            Arg::with_name("no-color")
+                .long("no-color")
+                .global(true)
+                .help("Print without colors, as NO_COLOR does"),
+        )
+        .arg(
+            Arg::with_name("db")
+                .long("db")
+                .global(true)
+                .takes_value(true)
+                .value_name("NAME|PATH")
+                .help("Use the database of this name in the databases config, or at this path"),
+        )
+        .arg(
+            Arg::with_name("project")
+                .long("project")
+                .global(true)
+                .takes_value(true)
+                .value_name("NAME")
+                .help("Work in this project instead of the current one"),
+        )
+        .arg(
+            Arg::with_name("wait")
+                .long("wait")
+                .global(true)
+                .conflicts_with("no-wait")
+                .help("Wait for another regia command to release the database"),
+        )
+        .arg(
+            Arg::with_name("no-wait")
+                .long("no-wait")
+                .global(true)
+                .help("Fail at once if another regia command holds the database"),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .global(true)
+                .help("Run the command without saving anything, printing what it would change"),
+        )
+        .args(&prompt::args())
         .subcommand(
             SubCommand::with_name("note")
                 .setting(AppSettings::SubcommandRequired)
-                .subcommand(SubCommand::with_name("ls"))
                 .subcommand(
-                    SubCommand::with_name("add").arg(
-                        Arg::with_name("content")
-                            .value_name("STRING")
-                            .required(true),
-                    ),
+                    SubCommand::with_name("ls")
+                        .about("List notes, pinned ones first and then newest first")
+                        .args(&note_list_args()),
+                )
+                .subcommand(
+                    SubCommand::with_name("pinned")
+                        .about("List only the pinned notes")
+                        .args(&note_list_args()),
+                )
+                .subcommand(
+                    SubCommand::with_name("pin")
+                        .about("Pin a note to the top of note ls")
+                        .arg(
+                            Arg::with_name("id")
+                                .long("id")
+                                .takes_value(true)
+                                .value_name("UUID"),
+                        )
+                        .arg(
+                            Arg::with_name("search")
+                                .required_unless("id")
+                                .value_name("ID|STRING"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("unpin")
+                        .about("Unpin a pinned note")
+                        .arg(
+                            Arg::with_name("id")
+                                .long("id")
+                                .takes_value(true)
+                                .value_name("UUID"),
+                        )
+                        .arg(
+                            Arg::with_name("search")
+                                .required_unless("id")
+                                .value_name("ID|STRING"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Show a whole note and its details through $PAGER")
+                        .arg(
+                            Arg::with_name("id")
+                                .long("id")
+                                .takes_value(true)
+                                .value_name("UUID"),
+                        )
+                        .arg(
+                            Arg::with_name("search")
+                                .required_unless("id")
+                                .value_name("ID|STRING"),
+                        )
+                        .arg(
+                            Arg::with_name("raw")
+                                .long("raw")
+                                .help("Print the note as written instead of rendering markdown"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .arg(
+                            Arg::with_name("tag")
+                                .short("t")
+                                .long("tag")
+                                .multiple(true)
+                                .number_of_values(1)
+                                .takes_value(true)
+                                .value_name("TAG"),
+                        )
+                        .arg(
+                            Arg::with_name("task")
+                                .long("task")
+                                .takes_value(true)
+                                .value_name("UUID")
+                                .help("Attach the note to the task with this id or id prefix"),
+                        )
+                        .arg(
+                            Arg::with_name("clipboard")
+                                .long("clipboard")
+                                .conflicts_with("content")
+                                .help("Take the note from the system clipboard"),
+                        )
+                        .arg(
+                            Arg::with_name("append")
+                                .long("append")
+                                .takes_value(true)
+                                .value_name("ID|STRING")
+                                .conflicts_with("task")
+                                .help("Add to the end of this note instead of making a new one"),
+                        )
+                        .arg(
+                            Arg::with_name("content")
+                                .value_name("STRING")
+                                .required_unless("clipboard"),
+                        ),
                 )
                 .subcommand(
                     SubCommand::with_name("rm")
@@ -66,16 +473,327 @@ fn main() -> std::io::Result<()> {
                         )
                         .arg(
                             Arg::with_name("search")
-                                .required(true)
+                                .required_unless("id")
                                 .value_name("STRING")
                                 .min_values(1),
                         ),
+                )
+                .subcommand(
+                    SubCommand::with_name("edit")
+                        .about("Edit a note in $EDITOR")
+                        .arg(
+                            Arg::with_name("id")
+                                .long("id")
+                                .takes_value(true)
+                                .value_name("UUID"),
+                        )
+                        .arg(
+                            Arg::with_name("search")
+                                .required_unless("id")
+                                .value_name("ID|STRING"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("links")
+                        .about("List the notes a note links to with [[title]] and those linking to it")
+                        .arg(
+                            Arg::with_name("search")
+                                .required(true)
+                                .value_name("ID|STRING"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("history")
+                        .about("List the revisions kept of a note, newest first")
+                        .arg(
+                            Arg::with_name("search")
+                                .required(true)
+                                .value_name("ID|STRING"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("diff")
+                        .about("Show what changed in a note since a revision")
+                        .arg(
+                            Arg::with_name("search")
+                                .required(true)
+                                .value_name("ID|STRING"),
+                        )
+                        .arg(
+                            Arg::with_name("revision")
+                                .value_name("REV")
+                                .help("The revision to compare with [default: the one before]"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("revert")
+                        .about("Put back what a note said at a revision")
+                        .arg(
+                            Arg::with_name("search")
+                                .required(true)
+                                .value_name("ID|STRING"),
+                        )
+                        .arg(Arg::with_name("revision").value_name("REV").required(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("attach")
+                        .about("Attach a copy of a file to a note")
+                        .arg(
+                            Arg::with_name("search")
+                                .value_name("ID|STRING")
+                                .required(true)
+                                .help("Note id or search text"),
+                        )
+                        .arg(Arg::with_name("path").value_name("PATH").required(true)),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("config")
+                .about("Inspect and change the config file")
+                .setting(AppSettings::SubcommandRequired)
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Print the config in effect, project settings included")
+                        .arg(
+                            Arg::with_name("defaults")
+                                .long("defaults")
+                                .help("Print the built-in defaults instead"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("get")
+                        .about("Print one setting, or its default when unset")
+                        .arg(
+                            Arg::with_name("key")
+                                .value_name("SECTION.KEY")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("set")
+                        .about("Change one setting in the config file")
+                        .arg(
+                            Arg::with_name("key")
+                                .value_name("SECTION.KEY")
+                                .required(true),
+                        )
+                        .arg(Arg::with_name("value").value_name("VALUE").required(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("init")
+                        .about("Write a config file with every default spelled out")
+                        .arg(
+                            Arg::with_name("force")
+                                .long("force")
+                                .help("Replace an existing config file"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("in")
+                .about("Capture a thought in the inbox to triage later, or list the inbox")
+                .arg(
+                    Arg::with_name("text")
+                        .value_name("TEXT")
+                        .min_values(1)
+                        .help("What to capture, quoted or not"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("triage")
+                .about("Make each inbox item a task or a note, or discard it"),
+        )
+        .subcommand(
+            SubCommand::with_name("review")
+                .about("Go through overdue and stale tasks, the inbox and the week ahead"),
+        )
+        .subcommand(
+            SubCommand::with_name("agenda")
+                .about("List open tasks by day, with upcoming repeats")
+                .arg(
+                    Arg::with_name("days")
+                        .long("days")
+                        .takes_value(true)
+                        .value_name("N")
+                        .help("How many days ahead to project repeated tasks [default: 14]"),
+                )
+                .args(&context_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("next")
+                .about("Suggest what to work on, weighing priority, due dates, blocking and age")
+                .arg(
+                    Arg::with_name("limit")
+                        .long("limit")
+                        .takes_value(true)
+                        .value_name("N")
+                        .help("How many tasks to suggest [default: next.count or 5]"),
+                )
+                .args(&context_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("plan")
+                .about("Add up the estimated work on each coming day against tasks.capacity")
+                .arg(
+                    Arg::with_name("days")
+                        .long("days")
+                        .takes_value(true)
+                        .value_name("N")
+                        .help("How many days to plan, today first [default: 7]"),
+                )
+                .args(&context_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("context")
+                .about("Keep task ls, next and agenda to what can be done where you are")
+                .setting(AppSettings::SubcommandRequired)
+                .subcommand(
+                    SubCommand::with_name("set")
+                        .about("Keep to tasks for this context, or none, until cleared")
+                        .arg(
+                            Arg::with_name("context")
+                                .value_name("@CONTEXT")
+                                .required(true),
+                        ),
+                )
+                .subcommand(SubCommand::with_name("clear").about("Show tasks for every context again"))
+                .subcommand(SubCommand::with_name("show").about("Print the context set, if any"))
+                .subcommand(
+                    SubCommand::with_name("ls").about("Count the open tasks for each context"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("pomo")
+                .about("Work on a task in pomodoros, logging each as tracked time")
+                .arg(
+                    Arg::with_name("id")
+                        .long("id")
+                        .takes_value(true)
+                        .value_name("UUID"),
+                )
+                .arg(
+                    Arg::with_name("search")
+                        .required_unless("id")
+                        .value_name("STRING")
+                        .min_values(1),
+                )
+                .arg(
+                    Arg::with_name("work")
+                        .long("work")
+                        .takes_value(true)
+                        .value_name("DURATION")
+                        .default_value("25m"),
+                )
+                .arg(
+                    Arg::with_name("break")
+                        .long("break")
+                        .takes_value(true)
+                        .value_name("DURATION")
+                        .default_value("5m"),
+                )
+                .arg(
+                    Arg::with_name("rounds")
+                        .long("rounds")
+                        .takes_value(true)
+                        .value_name("N")
+                        .default_value("4")
+                        .help("How many pomodoros to run"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("streaks")
+                .about("Show how many times in a row each daily or weekly task was done"),
+        )
+        .subcommand(
+            SubCommand::with_name("habit")
+                .about("Keep habits, repeated tasks with a calendar of what was done")
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .about("Start a habit")
+                        .arg(
+                            Arg::with_name("schedule")
+                                .long("schedule")
+                                .takes_value(true)
+                                .value_name("REPEAT")
+                                .help("How often, like daily or every mon,wed,fri; daily if not given"),
+                        )
+                        .arg(
+                            Arg::with_name("content")
+                                .value_name("HABIT")
+                                .required(true)
+                                .min_values(1),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("done")
+                        .about("Tick a habit off for today")
+                        .arg(
+                            Arg::with_name("habit")
+                                .value_name("HABIT")
+                                .required(true)
+                                .help("Habit id, id prefix or words of it"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("ls")
+                        .about("Show each habit's last weeks and streaks, the default")
+                        .arg(
+                            Arg::with_name("weeks")
+                                .long("weeks")
+                                .takes_value(true)
+                                .value_name("N")
+                                .help("How many weeks to show, 12 if not given"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("board")
+                .about("Show tasks in a column for each status of the tasks.workflow")
+                .arg(
+                    Arg::with_name("all")
+                        .long("all")
+                        .help("Show every done task, not just those of the last week"),
+                )
+                .arg(
+                    Arg::with_name("tag")
+                        .short("t")
+                        .long("tag")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true)
+                        .value_name("TAG"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("attach")
+                .about("List and open files attached to tasks and notes")
+                .setting(AppSettings::SubcommandRequired)
+                .subcommand(SubCommand::with_name("ls").about("List attached files"))
+                .subcommand(
+                    SubCommand::with_name("open")
+                        .about("Open an attached file with its default application")
+                        .arg(
+                            Arg::with_name("attachment")
+                                .value_name("HASH|NAME")
+                                .required(true)
+                                .help("Hash prefix or filename of the attachment"),
+                        ),
                 ),
         )
         .subcommand(
             SubCommand::with_name("task")
                 .setting(AppSettings::SubcommandRequired)
-                .subcommand(SubCommand::with_name("ls"))
+                .subcommand(
+                    SubCommand::with_name("ls")
+                        .args(&filter_args())
+                        .arg(
+                            Arg::with_name("view")
+                                .long("view")
+                                .takes_value(true)
+                                .value_name("NAME")
+                                .help("Start from a view saved with regia view save"),
+                        ),
+                )
                 .subcommand(
                     SubCommand::with_name("add")
                         .arg(
@@ -85,12 +803,27 @@ fn main() -> std::io::Result<()> {
                                 .takes_value(true)
                                 .value_name("DATE"),
                         )
+                        .arg(
+                            Arg::with_name("scheduled")
+                                .long("scheduled")
+                                .takes_value(true)
+                                .value_name("DATE")
+                                .help("When to start, hiding the task from task ls until then"),
+                        )
+                        .arg(
+                            Arg::with_name("estimate")
+                                .long("estimate")
+                                .takes_value(true)
+                                .value_name("DURATION")
+                                .help("How long the task should take, e.g. 2h or 30m"),
+                        )
                         .arg(
                             Arg::with_name("priority")
                                 .short("p")
                                 .long("priority")
                                 .takes_value(true)
-                                .value_name("INT"),
+                                .value_name("LEVEL")
+                                .help("low, medium, high, critical or a number"),
                         )
                         .arg(
                             Arg::with_name("repeats")
@@ -99,6 +832,15 @@ fn main() -> std::io::Result<()> {
                                 .takes_value(true)
                                 .value_name("PERIOD"),
                         )
+                        .arg(
+                            Arg::with_name("remind")
+                                .long("remind")
+                                .multiple(true)
+                                .number_of_values(1)
+                                .takes_value(true)
+                                .value_name("DURATION")
+                                .help("How long before the due date to send a reminder; repeat for more"),
+                        )
                         .arg(
                             Arg::with_name("depends")
                                 .short("l")
@@ -107,48 +849,891 @@ fn main() -> std::io::Result<()> {
                                 .takes_value(true)
                                 .value_name("ID"),
                         )
+                        .arg(
+                            Arg::with_name("parent")
+                                .long("parent")
+                                .takes_value(true)
+                                .value_name("ID")
+                                .help("Add as a subtask of another task"),
+                        )
+                        .arg(
+                            Arg::with_name("tag")
+                                .short("t")
+                                .long("tag")
+                                .multiple(true)
+                                .number_of_values(1)
+                                .takes_value(true)
+                                .value_name("TAG"),
+                        )
+                        .arg(
+                            Arg::with_name("context")
+                                .long("context")
+                                .multiple(true)
+                                .number_of_values(1)
+                                .takes_value(true)
+                                .value_name("@CONTEXT")
+                                .help("Where the task can be done, like @home; repeat for more"),
+                        )
+                        .arg(
+                            Arg::with_name("stdin")
+                                .long("stdin")
+                                .conflicts_with("content")
+                                .help("Add one task per line of stdin"),
+                        )
                         .arg(
                             Arg::with_name("content")
                                 .value_name("STRING")
-                                .required(true),
+                                .required_unless("stdin"),
                         ),
                 )
                 .subcommand(
                     SubCommand::with_name("rm")
+                        .arg(
+                            Arg::with_name("id")
+                                .long("id")
+                                .takes_value(true)
+                                .value_name("UUID")
+                                .help("Remove the task with this id or id prefix, without asking"),
+                        )
+                        .arg(
+                            Arg::with_name("cascade")
+                                .long("cascade")
+                                .conflicts_with("orphan")
+                                .help("Also delete notes attached to the removed tasks"),
+                        )
+                        .arg(
+                            Arg::with_name("orphan")
+                                .long("orphan")
+                                .help("Keep attached notes, detached from the removed tasks"),
+                        )
+                        .args(&selector::args())
+                        .arg(
+                            Arg::with_name("search")
+                                .required_unless_one(&selector::or(&["id"]))
+                                .value_name("STRING")
+                                .min_values(1)
+                                .help("Words the task must all match"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("edit")
+                        .arg(
+                            Arg::with_name("id")
+                                .long("id")
+                                .takes_value(true)
+                                .value_name("UUID"),
+                        )
+                        .arg(
+                            Arg::with_name("content")
+                                .short("c")
+                                .long("content")
+                                .takes_value(true)
+                                .value_name("STRING"),
+                        )
+                        .arg(
+                            Arg::with_name("due date")
+                                .short("d")
+                                .long("due")
+                                .takes_value(true)
+                                .value_name("DATE"),
+                        )
+                        .arg(
+                            Arg::with_name("scheduled")
+                                .long("scheduled")
+                                .takes_value(true)
+                                .value_name("DATE")
+                                .help("When to start, hiding the task from task ls until then"),
+                        )
+                        .arg(
+                            Arg::with_name("estimate")
+                                .long("estimate")
+                                .takes_value(true)
+                                .value_name("DURATION")
+                                .help("How long the task should take, e.g. 2h or 30m"),
+                        )
+                        .arg(
+                            Arg::with_name("priority")
+                                .short("p")
+                                .long("priority")
+                                .takes_value(true)
+                                .value_name("LEVEL")
+                                .help("low, medium, high, critical or a number"),
+                        )
+                        .arg(
+                            Arg::with_name("repeats")
+                                .short("r")
+                                .long("repeats")
+                                .takes_value(true)
+                                .value_name("PERIOD"),
+                        )
+                        .arg(
+                            Arg::with_name("remind")
+                                .long("remind")
+                                .multiple(true)
+                                .number_of_values(1)
+                                .takes_value(true)
+                                .value_name("DURATION")
+                                .help("How long before the due date to send a reminder; repeat for more"),
+                        )
+                        .arg(
+                            Arg::with_name("depends")
+                                .short("l")
+                                .long("depends")
+                                .multiple(true)
+                                .number_of_values(1)
+                                .takes_value(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::with_name("undepends")
+                                .long("undepends")
+                                .multiple(true)
+                                .number_of_values(1)
+                                .takes_value(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::with_name("batch")
+                                .long("batch")
+                                .conflicts_with_all(&[
+                                    "id",
+                                    "content",
+                                    "due date",
+                                    "scheduled",
+                                    "estimate",
+                                    "priority",
+                                    "repeats",
+                                    "remind",
+                                    "depends",
+                                    "undepends",
+                                ])
+                                .help("Edit every open task found, or all of them, as one file in $EDITOR"),
+                        )
+                        .args(&selector::args())
+                        .arg(
+                            Arg::with_name("search")
+                                .required_unless_one(&selector::or(&["id", "batch"]))
+                                .value_name("STRING")
+                                .min_values(1)
+                                .help("Words the task must all match"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Show every detail of one task")
+                        .arg(
+                            Arg::with_name("id")
+                                .long("id")
+                                .takes_value(true)
+                                .value_name("UUID"),
+                        )
+                        .arg(
+                            Arg::with_name("search")
+                                .required_unless("id")
+                                .value_name("ID|STRING"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("preview")
+                        .about("List the dates a repeated task will come back on")
                         .arg(
                             Arg::with_name("id")
                                 .long("id")
                                 .takes_value(true)
                                 .value_name("UUID"),
                         )
+                        .arg(
+                            Arg::with_name("count")
+                                .short("n")
+                                .long("count")
+                                .takes_value(true)
+                                .value_name("N")
+                                .default_value("10")
+                                .help("How many occurrences to list"),
+                        )
+                        .arg(
+                            Arg::with_name("repeats")
+                                .short("r")
+                                .long("repeats")
+                                .takes_value(true)
+                                .value_name("PERIOD")
+                                .help("Try this rule instead, on the task or without one"),
+                        )
+                        .arg(
+                            Arg::with_name("due")
+                                .short("d")
+                                .long("due")
+                                .takes_value(true)
+                                .value_name("DATE")
+                                .help("Count from this due date rather than the task's"),
+                        )
                         .arg(
                             Arg::with_name("search")
+                                .required_unless_one(&["id", "repeats"])
+                                .value_name("ID|STRING"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("done")
+                        .arg(
+                            Arg::with_name("force")
+                                .short("f")
+                                .long("force")
+                                .help("Complete even if dependencies are still open"),
+                        )
+                        .arg(
+                            Arg::with_name("id")
+                                .long("id")
+                                .takes_value(true)
+                                .value_name("UUID"),
+                        )
+                        .arg(
+                            Arg::with_name("stdin")
+                                .long("stdin")
+                                .conflicts_with_all(&["id", "search"])
+                                .help("Complete the task ids or id prefixes on each line of stdin"),
+                        )
+                        .args(&selector::args())
+                        .arg(
+                            Arg::with_name("search")
+                                .required_unless_one(&selector::or(&["id", "stdin"]))
+                                .value_name("STRING")
+                                .min_values(1)
+                                .help("Words the task must all match"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("attach")
+                        .about("Attach a copy of a file to a task")
+                        .arg(
+                            Arg::with_name("task")
+                                .value_name("TASK")
+                                .required(true)
+                                .help("Task id, id prefix or search text"),
+                        )
+                        .arg(Arg::with_name("path").value_name("PATH").required(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("move")
+                        .about("Move a task to another status of the tasks.workflow")
+                        .arg(
+                            Arg::with_name("force")
+                                .short("f")
+                                .long("force")
+                                .help("Move to the last status even if dependencies are still open"),
+                        )
+                        .arg(
+                            Arg::with_name("task")
+                                .value_name("TASK")
+                                .required(true)
+                                .help("Task id, id prefix or search text"),
+                        )
+                        .arg(
+                            Arg::with_name("status")
+                                .value_name("STATUS")
+                                .required(true)
+                                .help("A status of the workflow, or the start of one"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("comment")
+                        .about("Leave a dated comment on a task, shown by task show")
+                        .arg(
+                            Arg::with_name("task")
+                                .value_name("TASK")
+                                .required(true)
+                                .help("Task id, id prefix or search text"),
+                        )
+                        .arg(
+                            Arg::with_name("text")
+                                .value_name("TEXT")
+                                .required(true)
+                                .min_values(1),
+                        ),
+                )
+                .subcommand(SubCommand::with_name("check-overdue").about(
+                    "Print nothing, and exit with 6 if any open task is overdue",
+                ))
+                .subcommand(SubCommand::with_name("sync-github").about(
+                    "Complete the tasks whose GitHub issue has been closed",
+                ))
+                .subcommand(
+                    SubCommand::with_name("check")
+                        .about("Keep a checklist of steps on a task")
+                        .setting(AppSettings::SubcommandRequired)
+                        .subcommand(
+                            SubCommand::with_name("add")
+                                .about("Add a step to the end of the checklist")
+                                .arg(
+                                    Arg::with_name("task")
+                                        .value_name("TASK")
+                                        .required(true)
+                                        .help("Task id, id prefix or search text"),
+                                )
+                                .arg(Arg::with_name("step").value_name("STEP").required(true)),
+                        )
+                        .subcommand(
+                            SubCommand::with_name("toggle")
+                                .about("Tick a step off, or back on")
+                                .arg(
+                                    Arg::with_name("task")
+                                        .value_name("TASK")
+                                        .required(true)
+                                        .help("Task id, id prefix or search text"),
+                                )
+                                .arg(
+                                    Arg::with_name("step")
+                                        .value_name("N|TEXT")
+                                        .required(true)
+                                        .help("Step number, from 1, or text from the step"),
+                                ),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("clone")
+                        .about("Add a copy of a task to do again, without its history")
+                        .arg(
+                            Arg::with_name("task")
+                                .value_name("TASK")
+                                .required(true)
+                                .help("Task id, id prefix or search text"),
+                        )
+                        .arg(
+                            Arg::with_name("due")
+                                .long("due")
+                                .takes_value(true)
+                                .value_name("DATE")
+                                .help("Make the copy due then, moving its start date along"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("wait")
+                        .about("Mark a task as handed to someone, to follow up on")
+                        .arg(
+                            Arg::with_name("clear")
+                                .long("clear")
+                                .help("Stop waiting, the task is back with you"),
+                        )
+                        .arg(
+                            Arg::with_name("task")
+                                .value_name("TASK")
+                                .required(true)
+                                .help("Task id, id prefix or search text"),
+                        )
+                        .arg(
+                            Arg::with_name("on")
+                                .long("on")
+                                .takes_value(true)
+                                .value_name("WHO")
+                                .required_unless("clear")
+                                .conflicts_with("clear")
+                                .help("Who the task waits on"),
+                        )
+                        .arg(
+                            Arg::with_name("until")
+                                .long("until")
+                                .takes_value(true)
+                                .value_name("DATE")
+                                .conflicts_with("clear")
+                                .help("When to follow up if it is still waiting"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("snooze")
+                        .about("Hide a task from task ls until a date or for a while")
+                        .arg(
+                            Arg::with_name("clear")
+                                .long("clear")
+                                .help("Show the task again now"),
+                        )
+                        .arg(
+                            Arg::with_name("task")
+                                .value_name("TASK")
                                 .required(true)
+                                .help("Task id, id prefix or search text"),
+                        )
+                        .arg(
+                            Arg::with_name("until")
+                                .value_name("DURATION|DATE")
+                                .required_unless("clear")
+                                .conflicts_with("clear"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("start")
+                        .about("Start tracking time on a task")
+                        .arg(
+                            Arg::with_name("id")
+                                .long("id")
+                                .takes_value(true)
+                                .value_name("UUID"),
+                        )
+                        .arg(
+                            Arg::with_name("search")
+                                .required_unless("id")
                                 .value_name("STRING")
                                 .min_values(1),
                         ),
+                )
+                .subcommand(
+                    SubCommand::with_name("stop")
+                        .about("Stop tracking time, on every running task by default")
+                        .arg(
+                            Arg::with_name("id")
+                                .long("id")
+                                .takes_value(true)
+                                .value_name("UUID"),
+                        )
+                        .arg(Arg::with_name("search").value_name("STRING").min_values(1)),
+                )
+                .subcommand(
+                    SubCommand::with_name("graph")
+                        .about("Print the dependencies between open tasks as a diagram")
+                        .arg(
+                            Arg::with_name("format")
+                                .long("format")
+                                .takes_value(true)
+                                .possible_values(&["dot", "mermaid"])
+                                .default_value("dot")
+                                .help("Graphviz dot or Mermaid flowchart source"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("time")
+                        .about(
+                            "Show time tracked on a task, or this week's totals by project and tag",
+                        )
+                        .arg(
+                            Arg::with_name("id")
+                                .long("id")
+                                .takes_value(true)
+                                .value_name("UUID"),
+                        )
+                        .arg(Arg::with_name("search").value_name("STRING").min_values(1)),
+                )
+                .subcommand(
+                    SubCommand::with_name("archive")
+                        .about("List archived tasks")
+                        .arg(
+                            Arg::with_name("prune")
+                                .long("prune")
+                                .help("First archive tasks finished a while ago"),
+                        )
+                        .arg(
+                            Arg::with_name("older-than")
+                                .long("older-than")
+                                .takes_value(true)
+                                .value_name("DURATION")
+                                .requires("prune")
+                                .help("How long ago, overriding archive.after (default 30d)"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Dump the database in a portable format")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["json", "ics", "todotxt"])
+                        .default_value("json"),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .short("o")
+                        .long("out")
+                        .takes_value(true)
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Merge an exported database into this one")
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .takes_value(true)
+                        .possible_values(&["json", "todotxt", "github"])
+                        .default_value("json")
+                        .help("Read an export, a todo.txt file, or the open issues of a GitHub repository assigned to you"),
+                )
+                .arg(
+                    Arg::with_name("on-conflict")
+                        .long("on-conflict")
+                        .takes_value(true)
+                        .possible_values(&["skip", "replace", "rename"])
+                        .default_value("skip")
+                        .help("What to do with items whose id already exists"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE|OWNER/NAME")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("project")
+                .about("Keep tasks and notes in separate projects")
+                .setting(AppSettings::SubcommandRequired)
+                .subcommand(SubCommand::with_name("ls"))
+                .subcommand(
+                    SubCommand::with_name("create")
+                        .arg(Arg::with_name("name").value_name("NAME").required(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("rm")
+                        .arg(
+                            Arg::with_name("force")
+                                .short("f")
+                                .long("force")
+                                .help("Remove the project even if it is not empty"),
+                        )
+                        .arg(Arg::with_name("name").value_name("NAME").required(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("switch")
+                        .arg(Arg::with_name("name").value_name("NAME").required(true)),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("tag")
+                .about("See which tags tasks and notes use")
+                .setting(AppSettings::SubcommandRequired)
+                .subcommand(
+                    SubCommand::with_name("ls").about("Count the tasks and notes under each tag"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("journal")
+                .about("Write in today's journal entry, started from journal.template")
+                .arg(
+                    Arg::with_name("edit")
+                        .short("e")
+                        .long("edit")
+                        .conflicts_with("text")
+                        .help("Open today's entry in $EDITOR"),
+                )
+                .arg(
+                    Arg::with_name("text")
+                        .value_name("TEXT")
+                        .min_values(1)
+                        .help("Add a paragraph to today's entry"),
+                )
+                .subcommand(SubCommand::with_name("ls").about("List journal entries, oldest first")),
+        )
+        .subcommand(
+            SubCommand::with_name("view")
+                .about("Save task filters under a name and list them again")
+                .setting(AppSettings::SubcommandsNegateReqs)
+                .args(&filter_args())
+                .arg(
+                    Arg::with_name("name")
+                        .value_name("NAME")
+                        .required(true)
+                        .help("The view to list, narrowed by any filters given"),
+                )
+                .subcommand(
+                    SubCommand::with_name("save")
+                        .about("Save the given filters as a view, replacing any of that name")
+                        .args(&filter_args())
+                        .arg(Arg::with_name("name").value_name("NAME").required(true)),
+                )
+                .subcommand(SubCommand::with_name("ls").about("List saved views"))
+                .subcommand(
+                    SubCommand::with_name("rm")
+                        .arg(Arg::with_name("name").value_name("NAME").required(true)),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("query")
+                .about("List the tasks a query picks, e.g. 'priority > 2 and overdue sort due'")
+                .arg(
+                    Arg::with_name("query")
+                        .required(true)
+                        .value_name("QUERY")
+                        .help("Tests such as tag = work or due < friday, joined by and, or and not"),
+                )
+                .args(&page_args())
+                .args(&context_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("report")
+                .about("Summarize open, overdue and completed work")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["table", "json"])
+                        .default_value("table"),
+                )
+                .arg(
+                    Arg::with_name("where")
+                        .long("where")
+                        .takes_value(true)
+                        .value_name("QUERY")
+                        .help("Count only the tasks this query picks, see regia query"),
+                )
+                .subcommand(
+                    SubCommand::with_name("burndown")
+                        .about("Chart tasks created and completed per day")
+                        .arg(
+                            Arg::with_name("weeks")
+                                .long("weeks")
+                                .takes_value(true)
+                                .value_name("N")
+                                .help("How many weeks back to chart, 2 by default"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sync")
+                .about("Commit the database to its git repository, then pull and push sync.remote")
+                .subcommand(SubCommand::with_name("caldav").about(
+                    "Push dated tasks to the sync.caldav calendar as to-dos and pull back changes",
+                )),
+        )
+        .subcommand(
+            SubCommand::with_name("daemon")
+                .about("Send desktop reminders for tasks that are due soon")
+                .arg(
+                    Arg::with_name("once")
+                        .long("once")
+                        .help("Check reminders once and exit instead of polling"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("notify")
+                .about("Post to the Slack or Matrix room set under notify")
+                .setting(AppSettings::SubcommandRequired)
+                .subcommand(SubCommand::with_name("agenda").about("Post today's agenda"))
+                .subcommand(SubCommand::with_name("overdue").about("Post the tasks that are overdue")),
+        )
+        .subcommand(SubCommand::with_name("undo").about("Revert the last change"))
+        .subcommand(SubCommand::with_name("redo").about("Re-apply the last undone change"))
+        .subcommand(
+            SubCommand::with_name("trash")
+                .about("Review and restore removed tasks and notes")
+                .setting(AppSettings::SubcommandRequired)
+                .subcommand(SubCommand::with_name("ls").about("List removed tasks and notes"))
+                .subcommand(
+                    SubCommand::with_name("restore")
+                        .about("Put removed items back")
+                        .arg(
+                            Arg::with_name("item")
+                                .value_name("ID|TEXT")
+                                .required(true)
+                                .multiple(true)
+                                .help("Id, id prefix or content of each item to restore"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("empty").about("Delete everything in the trash for good"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("backup")
+                .about("Write a compressed backup of the database, keeping backup.keep of them")
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .help("Back up into this directory instead of backup.dir"),
+                )
+                .arg(
+                    Arg::with_name("list")
+                        .long("list")
+                        .help("List the backups instead of writing one"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("restore")
+                .about("Check a backup and put it in place of the database")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("A file regia backup wrote"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("db")
+                .about("Look after the database file itself")
+                .setting(AppSettings::SubcommandRequired)
+                .subcommand(
+                    SubCommand::with_name("fsck")
+                        .about("Check the database for broken references and disorder")
+                        .arg(
+                            Arg::with_name("repair")
+                                .long("repair")
+                                .help("Fix what is found, keeping the fix undoable"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("ls")
+                        .about("List the databases in the config, with what they hold"),
+                )
+                .subcommand(SubCommand::with_name("compact").about(
+                    "Write the database whole, compressed or not as contents.compress says",
+                )),
+        )
+        .subcommand(SubCommand::with_name("ui").about("Open the interactive dashboard"))
+        .subcommand(
+            SubCommand::with_name("shell")
+                .about("Run many commands on the database kept in memory, with history and completion"),
+        )
+        .subcommand(
+            SubCommand::with_name("tick").about("Spawn the next occurrence of repeated tasks"),
+        )
+        .subcommand(
+            SubCommand::with_name("search")
+                .about("Search tasks and notes, best matches first")
+                .arg(
+                    Arg::with_name("regex")
+                        .short("e")
+                        .long("regex")
+                        .help("Treat the query as a regular expression"),
+                )
+                .arg(
+                    Arg::with_name("query")
+                        .value_name("QUERY")
+                        .required(true)
+                        .min_values(1)
+                        .help("Words to find in any form, \"a phrase\" or a prefix*"),
                 ),
-        );
-    let matches = app.get_matches();
+        )
+}
 
-    let conf_string = if matches.is_present("config") {
-        let conf_arg = matches.value_of("config").unwrap();
-        let conf_path = expand_tilde(conf_arg);
-        read_to_string(conf_path.unwrap())?
-    } else {
-        let default_conf = expand_tilde("~/.config/regia/default.yml").unwrap();
-        match read_to_string(default_conf) {
-            Ok(contents) => contents,
-            Err(_) => String::new(),
+/// `doc` with the project and output format `matches` picks for this one
+/// command, keeping those of `doc` where it picks none.
+fn for_command(doc: &Config, matches: &ArgMatches) -> Config {
+    let mut doc = doc.clone();
+    if let Some(db) = matches.value_of("db") {
+        doc.contents.regia_db = Some(conf::database(&doc, db).to_string());
+    }
+    if let Some(project) = matches.value_of("project") {
+        doc.project = Some(project.to_string());
+    }
+    if matches.is_present("quiet") {
+        doc.output = Some(String::from("quiet"));
+    } else if let Some(output) = matches.value_of("output") {
+        doc.output = Some(output.to_string());
+    }
+    doc
+}
+
+/// `task add` or `undo`: the subcommand and action `matches` runs, as the
+/// lock, journal and sync commits name it.
+fn command_name(matches: &ArgMatches) -> String {
+    match matches.subcommand() {
+        (command, Some(sub)) => match sub.subcommand_name() {
+            Some(action) => format!("{} {}", command, action),
+            None => command.to_string(),
+        },
+        (command, None) => command.to_string(),
+    }
+}
+
+/// Parses the command line and runs it, holding the database lock for
+/// the duration.
+fn run() -> error::Result<()> {
+    let matches = app().get_matches();
+
+    let config_path = match matches.value_of("config") {
+        Some(conf_arg) => expand_tilde(conf_arg).unwrap(),
+        None => expand_tilde("~/.config/regia/default.yml").unwrap(),
+    };
+    if matches.is_present("no-color") {
+        theme::disable();
+    }
+    // `is_present` would also see the `config` subcommand
+    let required = matches.value_of("config").is_some();
+    let dry = matches.is_present("dry-run");
+    if dry {
+        dryrun::check(matches.subcommand_name())?;
+    }
+    if let Some(config) = matches.subcommand_matches("config") {
+        return config::handle_it(config, &config_path, required, dry);
+    }
+
+    let doc = for_command(&config::load(&config_path, required)?, &matches);
+    conf::apply_timezone(&doc);
+    conf::apply_locale(&doc);
+    conf::apply_theme(&doc);
+    db::set_compression(conf::compress(&doc));
+    let command = command_name(&matches);
+    prompt::set_mode(prompt::Mode::from_args(&matches));
+    if let Some((name, args)) = plugins::requested(&matches) {
+        if dry {
+            return Err(RegiaError::Parse(format!(
+                "--dry-run can't be used with plugin {}",
+                name
+            )));
+        }
+        let status = plugins::run(name, &args, &doc, &config_path)?;
+        if !status.success() {
+            std::process::exit(status.code().unwrap_or(1));
+        }
+        return Ok(());
+    }
+    if let ("task", Some(task)) = matches.subcommand() {
+        if let (_, Some(action)) = task.subcommand() {
+            if action.is_present("stdin") {
+                taskmaster::stdin_lines()?;
+            }
+        }
+    }
+
+    // The daemon only reads, and atomic writes keep its reads consistent,
+    // so it doesn't hold the lock between checks. Pomodoros take it for
+    // each write instead of for as long as they run.
+    let lock = match matches.subcommand_name() {
+        Some("daemon") | Some("pomo") => None,
+        _ => {
+            let wait =
+                lock::Wait::from_flags(matches.is_present("wait"), matches.is_present("no-wait"));
+            Some(lock::acquire(db::db_path(&doc), &command, wait)?)
         }
     };
-    let doc: conf::Config = serde_yaml::from_str(&conf_string).unwrap();
+    // What sync pulls in already fired its hooks where it was made
+    let before = match matches.subcommand_name() {
+        Some("sync") | Some("daemon") => None,
+        _ => hooks::snapshot(&doc),
+    };
 
-    if let Some(ref matches) = matches.subcommand_matches("task") {
-        taskmaster::handle_it(matches, &doc)
-    } else if let Some(ref matches) = matches.subcommand_matches("note") {
-        notetaker::handle_it(matches, &doc)
-    } else {
-        unreachable!();
+    if dry {
+        let db_path = db::db_path(&doc);
+        db::hold_dry(db_path)?;
+        let original = db::Database::load(db_path)?;
+        let result = dispatch(&matches, &doc);
+        if result.is_ok() {
+            dryrun::report(&original);
+        }
+        db::discard();
+        return result;
+    }
+
+    let result = dispatch(&matches, &doc);
+
+    match matches.subcommand_name() {
+        Some("sync") | Some("daemon") => {}
+        _ if result.is_ok() => sync::autocommit(&doc, &command),
+        _ => {}
+    }
+    drop(lock);
+    if let (Ok(()), Some(before)) = (&result, before) {
+        hooks::fire(&doc, &before);
+    }
+    result
+}
+
+fn main() {
+    // Run to completion first so the lock is released before exiting
+    if let Err(err) = run() {
+        if !matches!(err, RegiaError::CheckFailed) {
+            eprintln!("{} {}", "error:".error().bold(), err);
+        }
+        std::process::exit(err.exit_code());
     }
 }
@@ -0,0 +1,249 @@
+use chrono::{DateTime, Duration, Utc};
+use clap::ArgMatches;
+use colored::*;
+use serde::Serialize;
+
+use crate::output::{self, Output};
+use regia_core::conf::{self, Config};
+use regia_core::contexts;
+use regia_core::db;
+use regia_core::error::{self, RegiaError};
+use regia_core::todo::{self, Task, Tasks};
+use regia_core::urgency::Urgency;
+
+/// How many tasks `regia next` suggests when neither `--limit` nor
+/// `next.count` says otherwise.
+const DEFAULT_COUNT: usize = 5;
+
+/// Due dates further off than this don't add to a task's score.
+const DUE_HORIZON_DAYS: f64 = 14.0;
+/// Overdue tasks keep gaining for this long.
+const OVERDUE_CAP_DAYS: f64 = 7.0;
+/// Tasks stop gaining for age after this long.
+const AGE_CAP_DAYS: f64 = 30.0;
+
+/// How much each consideration counts, from the `next` config section.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weights {
+    /// Per priority level
+    pub priority: f64,
+    /// For a task due now, scaling down to nothing at two weeks out and up
+    /// to double a week overdue
+    pub due: f64,
+    /// For a task a month old or older
+    pub age: f64,
+    /// Taken off tasks still waiting on a dependency
+    pub blocked: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            priority: 1.0,
+            due: 4.0,
+            age: 1.0,
+            blocked: 10.0,
+        }
+    }
+}
+
+pub fn weights(doc: &Config) -> Weights {
+    let defaults = Weights::default();
+    Weights {
+        priority: doc.next.priority.unwrap_or(defaults.priority),
+        due: doc.next.due.unwrap_or(defaults.due),
+        age: doc.next.age.unwrap_or(defaults.age),
+        blocked: doc.next.blocked.unwrap_or(defaults.blocked),
+    }
+}
+
+fn days(span: Duration) -> f64 {
+    span.num_seconds() as f64 / 86_400.0
+}
+
+/// How urgent the due date makes a task, from 0 for none or far off
+/// through 1 for due now to 2 for a week or more overdue.
+fn due_pressure(task: &Task, now: DateTime<Utc>) -> f64 {
+    match task.due {
+        Some(due) if due <= now => 1.0 + days(now - due).min(OVERDUE_CAP_DAYS) / OVERDUE_CAP_DAYS,
+        Some(due) => (1.0 - days(due - now) / DUE_HORIZON_DAYS).max(0.0),
+        None => 0.0,
+    }
+}
+
+/// The value of working on `task` now; higher is better. With `urgency`
+/// the priority counts as in effect now rather than as set.
+pub fn score(
+    task: &Task,
+    blocked: bool,
+    weights: &Weights,
+    urgency: Option<&Urgency>,
+    now: DateTime<Utc>,
+) -> f64 {
+    let age = days(now - task.created).clamp(0.0, AGE_CAP_DAYS) / AGE_CAP_DAYS;
+    let priority = match urgency {
+        Some(urgency) => urgency.priority(task, now),
+        None => task.priority,
+    };
+    let mut score = weights.priority * priority.weight() as f64
+        + weights.due * due_pressure(task, now)
+        + weights.age * age;
+    if blocked {
+        score -= weights.blocked;
+    }
+    score
+}
+
+/// The `count` open, unsnoozed tasks with the best scores, best first,
+/// leaving out those not meant to be started yet, those waiting on someone
+/// else, and those not fit for `context`.
+pub fn pick<'a>(
+    tasks: &'a Tasks,
+    weights: &Weights,
+    urgency: Option<&Urgency>,
+    now: DateTime<Utc>,
+    count: usize,
+    context: Option<&str>,
+) -> Vec<(&'a Task, f64)> {
+    let mut scored: Vec<(&Task, f64)> = tasks
+        .get_tasks()
+        .iter()
+        .filter(|task| {
+            !task.is_done()
+                && !task.is_snoozed(now)
+                && !task.is_scheduled_later(now)
+                && !task.is_waiting()
+        })
+        .filter(|task| context.is_none_or(|context| contexts::fits(&task.contexts, context)))
+        .map(|task| {
+            let blocked = !tasks.open_dependencies(task).is_empty();
+            (task, score(task, blocked, weights, urgency, now))
+        })
+        .collect();
+    scored.sort_by(|(left_task, left), (right_task, right)| {
+        right
+            .total_cmp(left)
+            .then_with(|| left_task.listing_order(right_task))
+    });
+    scored.truncate(count);
+    scored
+}
+
+#[derive(Serialize)]
+struct NextRecord {
+    score: f64,
+    #[serde(flatten)]
+    task: output::TaskRecord,
+}
+
+impl output::Record for NextRecord {
+    fn plain(&self) -> String {
+        format!("{:.2}\t{}", self.score, self.task.plain())
+    }
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db = db::open(doc)?;
+    let weights = weights(doc);
+    let urgency = conf::urgency(doc)?;
+    let count = match matches.value_of("limit") {
+        Some(input) => input
+            .parse()
+            .map_err(|_| RegiaError::Parse(format!("bad limit: {}", input)))?,
+        None => doc.next.count.unwrap_or(DEFAULT_COUNT),
+    };
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let formatter = todo::Formatter {
+        due_soon: conf::due_soon(doc)?,
+        urgency,
+        ..todo::Formatter::new(&priority_map)
+    };
+    let given = matches.value_of("context");
+    let context = crate::contexts::wanted(given, matches.is_present("any-context"), doc)?;
+    let picked = pick(
+        &db.tasks,
+        &weights,
+        urgency.as_ref(),
+        formatter.now,
+        count,
+        context.as_deref(),
+    );
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        let records: Vec<NextRecord> = picked
+            .iter()
+            .map(|(task, score)| NextRecord {
+                score: *score,
+                task: output::TaskRecord::new(task, &db.tasks, formatter.now),
+            })
+            .collect();
+        return output::print(format, &records);
+    }
+
+    if picked.is_empty() {
+        println!("Nothing to do");
+    }
+    for (rank, (task, score)) in picked.iter().enumerate() {
+        let blockers = db.tasks.open_dependencies(task);
+        println!(
+            "{}. {} {}",
+            rank + 1,
+            formatter.task(task, &blockers),
+            format!("[{:.1}]", score).dimmed()
+        );
+    }
+    crate::contexts::print_sticky(given, context.as_deref());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_priority_due_age_and_blocking() {
+        let now = Utc::now();
+        let weights = Weights::default();
+        let mut tasks = Tasks::default();
+
+        let mut overdue = Task::new(String::from("overdue"), 0);
+        overdue.due = Some(now - Duration::days(7));
+        let mut important = Task::new(String::from("important"), 3);
+        important.due = Some(now + Duration::days(30));
+        let mut old = Task::new(String::from("old"), 1);
+        old.created = now - Duration::days(60);
+        let mut blocked = Task::new(String::from("blocked"), 5);
+        blocked.add_dependency(&old.id);
+        let mut done = Task::new(String::from("done"), 9);
+        done.completed = Some(now);
+        let mut delegated = Task::new(String::from("delegated"), 9);
+        delegated.waiting_on = Some(String::from("Alice"));
+        for task in [overdue, important, old, blocked, done, delegated] {
+            tasks.add(task);
+        }
+
+        let order: Vec<&str> = pick(&tasks, &weights, None, now, 10, None)
+            .iter()
+            .map(|(task, _)| task.content.as_str())
+            .collect();
+        assert_eq!(order, ["overdue", "important", "old", "blocked"]);
+        assert_eq!(
+            pick(&tasks, &weights, None, now, 2, None)[0].1,
+            weights.due * 2.0,
+            "a week overdue counts double"
+        );
+
+        let priority_first = Weights {
+            priority: 3.0,
+            ..weights
+        };
+        assert_eq!(
+            pick(&tasks, &priority_first, None, now, 1, None)[0]
+                .0
+                .content,
+            "important"
+        );
+    }
+}
@@ -1,20 +1,64 @@
-use std::io::{self, BufRead, ErrorKind as IOErrorKind};
-use std::path::Path;
-
+use chrono::{DateTime, Utc};
 use clap::ArgMatches;
 use colored::*;
+use serde::Serialize;
+use uuid::Uuid;
 
-use crate::conf::Config;
-use crate::db;
-use crate::note;
+use crate::attach;
+use crate::clipboard;
+use crate::editor;
+use crate::journal;
+use crate::output::{self, Output};
+use crate::pager;
+use crate::prompt;
+use crate::taskmaster;
+use crate::trash;
+use regia_core::conf::{self, Config};
+use regia_core::db;
+use regia_core::diff;
+use regia_core::error::{self, RegiaError};
+use regia_core::ident::{self, Unresolved};
+use regia_core::links;
+use regia_core::markdown;
+use regia_core::note;
+use regia_core::tags;
+use regia_core::theme::Themed;
+use regia_core::todo;
+use regia_core::trash::Trash;
 
 fn handle_note_add(
     matches: &ArgMatches,
     notes: &mut note::Notes,
-    _doc: &Config,
-) -> std::io::Result<()> {
-    let content = matches.value_of("content").unwrap();
-    let note = note::Note::new(content);
+    tasks: &todo::Tasks,
+    doc: &Config,
+) -> error::Result<()> {
+    let content = if matches.is_present("clipboard") {
+        clipboard::paste()?
+    } else {
+        matches.value_of("content").unwrap().to_string()
+    };
+    let mut added = tags::extract(&content);
+    if let Some(tag_args) = matches.values_of("tag") {
+        added.extend(tags::from_args(tag_args));
+    }
+
+    if let Some(search) = matches.value_of("append") {
+        let id = resolve(notes, search)?;
+        let mut note = notes.get_note_mut(&id).unwrap();
+        let appended = format!("{}\n\n{}", note.content, content);
+        note.set_content(&appended, conf::note_revisions(doc));
+        note.tags.extend(added);
+        println!("{} {}", "Appended to".header(), note.fmt());
+        return Ok(());
+    }
+
+    let mut note = note::Note::new(&content);
+    note.tags = added;
+    if let Some(id_str) = matches.value_of("task") {
+        let colors = conf::priority_colors(doc)?;
+        let priority_map = conf::as_priority_map(&colors);
+        note.task_id = Some(taskmaster::resolve_id(tasks, id_str, &priority_map)?);
+    }
     notes.add(note);
     Ok(())
 }
@@ -22,14 +66,23 @@ fn handle_note_add(
 fn handle_note_rm(
     matches: &ArgMatches,
     notes: &mut note::Notes,
+    trash: &mut Trash,
     _doc: &Config,
-) -> std::io::Result<()> {
+) -> error::Result<()> {
+    // An exact id leaves no doubt about what goes, so it skips the prompt
+    if let Some(id_str) = matches.value_of("id") {
+        let id = resolve_id(notes, id_str)?;
+        trash.delete_note(notes, id, Utc::now());
+        println!("Removed note {}", id);
+        return Ok(());
+    }
+
     let search = matches.value_of("search").unwrap();
     let mut delete_me = Vec::new();
 
     for note in notes.get_notes() {
         if note.content.contains(search) {
-            delete_me.push(note.id.clone());
+            delete_me.push(note.id);
         }
     }
 
@@ -38,7 +91,7 @@ fn handle_note_rm(
     if delete_len > 0 {
         println!(
             "Found {} note{} that match{}:",
-            format!("{}", delete_len).magenta(),
+            format!("{}", delete_len).header(),
             if delete_len > 1 { "s" } else { "" },
             if delete_len > 1 { "" } else { "es" }
         );
@@ -46,75 +99,515 @@ fn handle_note_rm(
             let note = notes.get_note(id).unwrap();
             println!("{}", note.fmt());
         }
-        println!("{} [{}/{}]", "Complete?".magenta(), "y".bold(), "N".bold());
-        let stdin = io::stdin();
-        let mut stdin_iter = stdin.lock().lines();
-        loop {
-            let next_line = stdin_iter.next().unwrap().unwrap();
-            if next_line.to_lowercase() == "y" {
-                break;
-            } else if next_line == "" || next_line.to_lowercase() == "n" {
-                return Ok(());
-            } else {
-                println!("Didn't understand {} please type y or n", next_line);
-            }
+        if !prompt::confirm("Complete?")? {
+            return Ok(());
         }
 
+        let now = Utc::now();
         for id in delete_me {
-            notes.remove(id);
+            trash.delete_note(notes, id, now);
         }
     }
 
     Ok(())
 }
 
-fn handle_note_list(notes: &note::Notes, _doc: &Config) -> std::io::Result<()> {
-    let mut notes_list = notes.get_notes().clone();
-    notes_list.sort_by_key(|k| k.created);
-    for note in notes_list.iter().rev() {
+/// Picks a single note from `--id`, or from the `search` argument, which
+/// may itself be an id or id prefix. An ambiguous search lists the
+/// candidates.
+fn select_one(matches: &ArgMatches, notes: &note::Notes) -> error::Result<Uuid> {
+    if let Some(id_str) = matches.value_of("id") {
+        return resolve_id(notes, id_str);
+    }
+
+    resolve(notes, matches.value_of("search").unwrap())
+}
+
+/// The note whose id is or starts with `id_str`. The notes an ambiguous
+/// prefix fits are listed before the error.
+fn resolve_id(notes: &note::Notes, id_str: &str) -> error::Result<Uuid> {
+    ident::resolve(notes.get_notes(), id_str).map_err(|unresolved| {
+        if let Unresolved::Ambiguous { candidates, .. } = &unresolved {
+            let found: Vec<&note::Note> = candidates
+                .iter()
+                .filter_map(|id| notes.get_note(id))
+                .collect();
+            print_found(&found);
+        }
+        unresolved.into()
+    })
+}
+
+fn print_found(found: &[&note::Note]) {
+    println!(
+        "Found {} notes that match:",
+        format!("{}", found.len()).header()
+    );
+    for note in found {
         println!("{}", note.fmt());
     }
+}
+
+/// The note `search` names: its id or an id prefix only it has, or words
+/// only its content has.
+fn resolve(notes: &note::Notes, search: &str) -> error::Result<Uuid> {
+    if !ident::matching(notes.get_notes().iter().map(|note| note.id), search).is_empty() {
+        return resolve_id(notes, search);
+    }
+
+    let found: Vec<&note::Note> = notes
+        .get_notes()
+        .iter()
+        .filter(|note| note.content.contains(search))
+        .collect();
+    match found.len() {
+        0 => Err(RegiaError::NotFound(format!("no note matches: {}", search))),
+        1 => Ok(found[0].id),
+        _ => {
+            print_found(&found);
+            Err(RegiaError::Conflict(String::from(
+                "search is ambiguous, narrow it or use --id",
+            )))
+        }
+    }
+}
+
+fn handle_note_attach(
+    matches: &ArgMatches,
+    notes: &mut note::Notes,
+    doc: &Config,
+) -> error::Result<()> {
+    let id = select_one(matches, notes)?;
+    let mut note = notes.get_note_mut(&id).unwrap();
+    attach::attach_file(
+        doc,
+        matches.value_of("path").unwrap(),
+        &mut note.attachments,
+    )
+}
+
+fn handle_note_edit(
+    matches: &ArgMatches,
+    notes: &mut note::Notes,
+    doc: &Config,
+) -> error::Result<()> {
+    let id = select_one(matches, notes)?;
+    let mut note = match notes.get_note_mut(&id) {
+        Some(note) => note,
+        None => return Err(RegiaError::NotFound(format!("no note with id: {}", id))),
+    };
+
+    let edited = editor::edit_text(&note.content, ".md")?;
+    let edited = edited.trim_end_matches('\n');
+    if edited.trim().is_empty() {
+        println!("Note is empty, leaving it unchanged");
+    } else if edited == note.content {
+        println!("No changes");
+    } else {
+        note.set_content(edited, conf::note_revisions(doc));
+        note.tags.extend(tags::extract(edited));
+        println!("{} {}", "Updated".header(), note.fmt());
+    }
     Ok(())
 }
 
-pub fn handle_it(matches: &ArgMatches, doc: &Config) -> std::io::Result<()> {
-    let db_default = Path::new(".regia.db");
-    let db_path = match doc.get("contents") {
-        Some(content) => match content.get("regia_db") {
-            Some(content) => Path::new(content),
-            None => db_default,
+fn parse_revision(input: &str) -> error::Result<usize> {
+    match input.parse() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(RegiaError::Parse(format!("bad revision: {}", input))),
+    }
+}
+
+/// What revision `number` of `note` said, failing when it isn't kept.
+fn revision_of(note: &note::Note, number: usize) -> error::Result<&str> {
+    note.revision_content(number).ok_or_else(|| {
+        RegiaError::NotFound(format!(
+            "no revision {} of this note, see note history",
+            number
+        ))
+    })
+}
+
+#[derive(Serialize)]
+struct RevisionRecord {
+    number: usize,
+    at: DateTime<Utc>,
+    current: bool,
+    content: String,
+}
+
+impl output::Record for RevisionRecord {
+    fn plain(&self) -> String {
+        output::fields(&[
+            self.number.to_string(),
+            self.at.to_rfc3339(),
+            if self.current { "current" } else { "" }.to_string(),
+            self.content.clone(),
+        ])
+    }
+}
+
+/// The revisions of a note kept, newest first, the current one included.
+fn handle_note_history(
+    matches: &ArgMatches,
+    notes: &note::Notes,
+    doc: &Config,
+) -> error::Result<()> {
+    let id = select_one(matches, notes)?;
+    let note = notes.get_note(&id).unwrap();
+    let mut records = vec![RevisionRecord {
+        number: note.revision(),
+        at: note.modified.unwrap_or(note.created),
+        current: true,
+        content: note.content.clone(),
+    }];
+    records.extend(note.revisions.iter().rev().map(|revision| RevisionRecord {
+        number: revision.number,
+        at: revision.at,
+        current: false,
+        content: revision.content.clone(),
+    }));
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        return output::print(format, &records);
+    }
+    let limit = conf::note_preview(doc);
+    for record in records {
+        let first = record.content.lines().next().unwrap_or("");
+        let first = note::preview(first, limit).unwrap_or_else(|| first.to_string());
+        let current = if record.current { " (current)" } else { "" };
+        println!(
+            "{} {}{} {}",
+            format!("{:>3}", record.number).bold(),
+            taskmaster::fmt_date(&record.at).dimmed(),
+            current.header(),
+            first
+        );
+    }
+    Ok(())
+}
+
+/// A unified diff from a revision, the one before the current by default,
+/// to the note as it is now.
+fn handle_note_diff(matches: &ArgMatches, notes: &note::Notes) -> error::Result<()> {
+    let id = select_one(matches, notes)?;
+    let note = notes.get_note(&id).unwrap();
+    let number = match matches.value_of("revision") {
+        Some(input) => parse_revision(input)?,
+        None => match note.revisions.last() {
+            Some(revision) => revision.number,
+            None => {
+                println!("No earlier revisions of this note");
+                return Ok(());
+            }
         },
-        None => db_default,
     };
+    let old = revision_of(note, number)?;
+    let hunks = diff::unified(old, &note.content, 3);
+    if hunks.is_empty() {
+        println!("No changes");
+        return Ok(());
+    }
+
+    println!("{}", format!("--- revision {}", number).bold());
+    println!(
+        "{}",
+        format!("+++ revision {} (current)", note.revision()).bold()
+    );
+    for line in hunks {
+        let line = if line.starts_with("@@") {
+            line.accent()
+        } else if line.starts_with('-') {
+            line.error()
+        } else if line.starts_with('+') {
+            line.done()
+        } else {
+            line.normal()
+        };
+        println!("{}", line);
+    }
+    Ok(())
+}
 
-    let db = match db::Database::from_disk(db_path) {
-        Ok(db) => db,
-        Err(err) => {
-            if err.kind() == IOErrorKind::Other {
-                return Err(err);
-            } else {
-                db::Database::default()
+/// Puts back what a revision said, as a new revision, so the revert can
+/// itself be reverted.
+fn handle_note_revert(
+    matches: &ArgMatches,
+    notes: &mut note::Notes,
+    doc: &Config,
+) -> error::Result<()> {
+    let id = select_one(matches, notes)?;
+    let number = parse_revision(matches.value_of("revision").unwrap())?;
+    let mut note = notes.get_note_mut(&id).unwrap();
+    let content = revision_of(&note, number)?.to_string();
+    if content == note.content {
+        println!("No changes");
+        return Ok(());
+    }
+    note.set_content(&content, conf::note_revisions(doc));
+    note.tags.extend(tags::extract(&content));
+    println!(
+        "{} {} {}",
+        "Reverted".header(),
+        format!("to revision {}", number).dimmed(),
+        note.fmt()
+    );
+    Ok(())
+}
+
+/// Pins or unpins a note, so that it does or doesn't list first.
+fn handle_note_pin(
+    matches: &ArgMatches,
+    notes: &mut note::Notes,
+    pin: bool,
+) -> error::Result<bool> {
+    let id = select_one(matches, notes)?;
+    let mut note = notes.get_note_mut(&id).unwrap();
+    if note.pinned == pin {
+        let state = if pin { "Already pinned" } else { "Not pinned" };
+        println!("{} {}", state.dimmed(), note.fmt());
+        return Ok(false);
+    }
+    note.pinned = pin;
+    let done = if pin { "Pinned" } else { "Unpinned" };
+    println!("{} {}", done.header(), note.fmt());
+    Ok(true)
+}
+
+/// Lists notes, pinned ones first, or with `pinned_only` nothing else.
+fn handle_note_list(
+    matches: &ArgMatches,
+    notes: &note::Notes,
+    doc: &Config,
+    pinned_only: bool,
+) -> error::Result<()> {
+    let wanted_tags = tags::from_args(matches.values_of("tag").into_iter().flatten());
+    let mut notes_list = notes.tagged(&wanted_tags);
+    if pinned_only {
+        notes_list.retain(|note| note.pinned);
+    }
+    notes_list.sort_by(|left, right| left.listing_order(right));
+    let page = taskmaster::parse_page(matches.value_of("page"), matches.value_of("per-page"))?;
+    let total = notes_list.len();
+    let notes_list = taskmaster::page_of(notes_list, page)?;
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        let records: Vec<output::NoteRecord> = notes_list
+            .iter()
+            .map(|note| output::NoteRecord::new(note))
+            .collect();
+        return output::print(format, &records);
+    }
+
+    let raw = matches.is_present("raw");
+    let limit = conf::note_preview(doc);
+    for note in notes_list.iter() {
+        let shortened;
+        let note = match note::preview(&note.content, limit) {
+            Some(content) if !matches.is_present("full") => {
+                shortened = note::Note {
+                    content,
+                    ..(*note).clone()
+                };
+                &shortened
             }
+            _ => *note,
+        };
+        if raw {
+            println!("{}", note.fmt_raw());
+        } else {
+            println!("{}", note.fmt());
         }
+    }
+    taskmaster::print_more(page, total);
+    Ok(())
+}
+
+fn handle_note_show(
+    matches: &ArgMatches,
+    notes: &note::Notes,
+    tasks: &todo::Tasks,
+    doc: &Config,
+) -> error::Result<()> {
+    let id = select_one(matches, notes)?;
+    let note = match notes.get_note(&id) {
+        Some(note) => note,
+        None => return Err(RegiaError::NotFound(format!("no note with id: {}", id))),
     };
 
-    let mut notes = db.notes;
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        return output::print_one(format, &output::NoteRecord::new(note));
+    }
 
-    if let Some(ref matches) = matches.subcommand_matches("add") {
-        handle_note_add(matches, &mut notes, doc)?;
-        let new_db = db::Database {
-            tasks: db.tasks,
-            notes,
+    let mut lines = vec![
+        taskmaster::field_line("id", &note.id.to_string()),
+        taskmaster::field_line("created", &taskmaster::fmt_date(&note.created)),
+    ];
+    if let Some(modified) = &note.modified {
+        lines.push(taskmaster::field_line(
+            "modified",
+            &taskmaster::fmt_date(modified),
+        ));
+    }
+    if !note.tags.is_empty() {
+        lines.push(taskmaster::field_line("tags", &tags::fmt_tags(&note.tags)));
+    }
+    if let Some(task_id) = &note.task_id {
+        let task = match tasks.get_task(task_id) {
+            Some(task) => task.content.clone(),
+            None => task_id.to_string(),
         };
-        new_db.to_disk(db_path)
-    } else if let Some(ref matches) = matches.subcommand_matches("rm") {
-        handle_note_rm(matches, &mut notes, doc)?;
-        let new_db = db::Database {
-            tasks: db.tasks,
-            notes,
+        lines.push(taskmaster::field_line("task", &task));
+    }
+    for linked in note.links.iter().filter_map(|id| notes.get_note(id)) {
+        lines.push(taskmaster::field_line("links to", links::title(linked)));
+    }
+    let backlinks = links::backlinks(notes);
+    for linking in backlinks
+        .get(&note.id)
+        .into_iter()
+        .flatten()
+        .filter_map(|id| notes.get_note(id))
+    {
+        lines.push(taskmaster::field_line("linked by", links::title(linking)));
+    }
+    for attachment in note.attachments.iter() {
+        lines.push(taskmaster::field_line(
+            "attached",
+            &format!("{} {}", attachment.short_hash(), attachment.filename),
+        ));
+    }
+    lines.push(String::new());
+    if matches.is_present("raw") {
+        lines.push(note.content.clone());
+    } else {
+        lines.extend(markdown::render(&note.content));
+    }
+    pager::page(&lines.join("\n"))
+}
+
+#[derive(Serialize)]
+struct LinkRecord {
+    /// `to`, `from` or `broken`, for a link that names no note
+    direction: &'static str,
+    id: Option<Uuid>,
+    title: String,
+}
+
+impl output::Record for LinkRecord {
+    fn plain(&self) -> String {
+        output::fields(&[
+            self.direction.to_string(),
+            self.id.map(|id| id.to_string()).unwrap_or_default(),
+            self.title.clone(),
+        ])
+    }
+}
+
+/// The notes a note links to, those linking to it, and its links that
+/// name no note.
+fn handle_note_links(matches: &ArgMatches, notes: &note::Notes, doc: &Config) -> error::Result<()> {
+    let id = select_one(matches, notes)?;
+    let note = notes.get_note(&id).unwrap();
+    let record = |direction, linked: &note::Note| LinkRecord {
+        direction,
+        id: Some(linked.id),
+        title: links::title(linked).to_string(),
+    };
+    let mut records: Vec<LinkRecord> = note
+        .links
+        .iter()
+        .filter_map(|id| notes.get_note(id))
+        .map(|linked| record("to", linked))
+        .collect();
+    let backlinks = links::backlinks(notes);
+    records.extend(
+        backlinks
+            .get(&note.id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| notes.get_note(id))
+            .map(|linking| record("from", linking)),
+    );
+    records.extend(
+        links::targets(&note.content)
+            .into_iter()
+            .filter(|target| links::resolve(notes, target).is_none())
+            .map(|target| LinkRecord {
+                direction: "broken",
+                id: None,
+                title: target.to_string(),
+            }),
+    );
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        return output::print(format, &records);
+    }
+    if records.is_empty() {
+        println!("No links to or from this note");
+    }
+    for record in records {
+        let arrow = match record.direction {
+            "to" => "→".done(),
+            "from" => "←".accent(),
+            _ => "✗".error(),
+        };
+        let title = if record.direction == "broken" {
+            format!("[[{}]] {}", record.title, "(no such note)".dimmed())
+        } else {
+            record.title
         };
-        new_db.to_disk(db_path)
+        println!("{} {}", arrow, title);
+    }
+    Ok(())
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let mut db = db::open(doc)?;
+    let before = db.clone();
+    trash::auto_purge(&mut db, doc, Utc::now())?;
+    let notes = &mut db.notes;
+
+    if let Some(matches) = matches.subcommand_matches("add") {
+        handle_note_add(matches, notes, &db.tasks, doc)?;
+    } else if let Some(matches) = matches.subcommand_matches("rm") {
+        handle_note_rm(matches, notes, &mut db.trash, doc)?;
+    } else if let Some(matches) = matches.subcommand_matches("edit") {
+        handle_note_edit(matches, notes, doc)?;
+    } else if let Some(matches) = matches.subcommand_matches("attach") {
+        handle_note_attach(matches, notes, doc)?;
+    } else if let Some(matches) = matches.subcommand_matches("ls") {
+        return handle_note_list(matches, notes, doc, false);
+    } else if let Some(matches) = matches.subcommand_matches("pinned") {
+        return handle_note_list(matches, notes, doc, true);
+    } else if let Some(matches) = matches.subcommand_matches("pin") {
+        if !handle_note_pin(matches, notes, true)? {
+            return Ok(());
+        }
+    } else if let Some(matches) = matches.subcommand_matches("unpin") {
+        if !handle_note_pin(matches, notes, false)? {
+            return Ok(());
+        }
+    } else if let Some(matches) = matches.subcommand_matches("show") {
+        return handle_note_show(matches, notes, &db.tasks, doc);
+    } else if let Some(matches) = matches.subcommand_matches("links") {
+        return handle_note_links(matches, notes, doc);
+    } else if let Some(matches) = matches.subcommand_matches("history") {
+        return handle_note_history(matches, notes, doc);
+    } else if let Some(matches) = matches.subcommand_matches("diff") {
+        return handle_note_diff(matches, notes);
+    } else if let Some(matches) = matches.subcommand_matches("revert") {
+        handle_note_revert(matches, notes, doc)?;
     } else {
-        handle_note_list(&notes, doc)
+        unreachable!();
     }
+
+    let command = format!("note {}", matches.subcommand_name().unwrap());
+    journal::record(db_path, &command, &before, &mut db)?;
+    db.to_disk(db_path)
 }
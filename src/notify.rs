@@ -0,0 +1,264 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::agenda;
+use crate::http;
+use crate::taskmaster;
+use regia_core::conf::{self, Config, MatrixSettings, NotifySettings};
+use regia_core::dates;
+use regia_core::db::{self, Database};
+use regia_core::error::{self, RegiaError};
+use regia_core::theme::Themed;
+use regia_core::todo::{self, Task};
+
+/// Posts to the Slack webhook and Matrix room of `notify`.
+pub struct Notifier<'a> {
+    settings: &'a NotifySettings,
+}
+
+impl<'a> Notifier<'a> {
+    /// The notifier `doc` sets up, if it names anywhere to post.
+    pub fn new(doc: &'a Config) -> Option<Self> {
+        let settings = &doc.notify;
+        if settings.slack.is_none() && settings.matrix.is_empty() {
+            return None;
+        }
+        Some(Self { settings })
+    }
+
+    /// Posts `text` everywhere configured, trying each even when one fails.
+    pub fn post(&self, text: &str) -> error::Result<()> {
+        let mut failed = None;
+        if let Some(url) = &self.settings.slack {
+            let body = json!({ "text": text }).to_string();
+            if let Err(err) = send("POST", url, &body, None) {
+                failed = Some(err.context("slack"));
+            }
+        }
+        if !self.settings.matrix.is_empty() {
+            if let Err(err) = post_matrix(&self.settings.matrix, text) {
+                failed = Some(err.context("matrix"));
+            }
+        }
+        match failed {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `segment` with everything but unreserved characters percent-encoded, so
+/// a room id can go in a URL path.
+fn encode(segment: &str) -> String {
+    let mut encoded = String::new();
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn post_matrix(matrix: &MatrixSettings, text: &str) -> error::Result<()> {
+    let (homeserver, room, token) = match (&matrix.homeserver, &matrix.room, &matrix.token) {
+        (Some(homeserver), Some(room), Some(token)) => (homeserver, room, token),
+        _ => {
+            return Err(RegiaError::Config(String::from(
+                "notify.matrix needs all of homeserver, room and token",
+            )))
+        }
+    };
+    let url = format!(
+        "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+        homeserver.trim_end_matches('/'),
+        encode(room),
+        Uuid::new_v4()
+    );
+    let body = json!({ "msgtype": "m.text", "body": text }).to_string();
+    send("PUT", &url, &body, Some(&format!("Bearer {}", token)))
+}
+
+/// Sends `body` as JSON to `url`, with the `Authorization` header if any.
+fn send(method: &str, url: &str, body: &str, authorization: Option<&str>) -> error::Result<()> {
+    let config: Vec<String> = authorization
+        .map(|value| http::config_line("header", &format!("Authorization: {}", value)))
+        .into_iter()
+        .collect();
+    let headers = ["Content-Type: application/json"];
+    match http::request(method, url, &headers, &config, Some(body))? {
+        (200..=299, _) => Ok(()),
+        (status, _) => Err(RegiaError::storage(format!(
+            "{} answered {}",
+            method, status
+        ))),
+    }
+}
+
+/// `text` without the terminal color codes `colored` put in it.
+fn plain(text: &str) -> String {
+    let mut plain = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // An escape runs up to the letter that ends it, like `m`
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+/// Today's agenda, what is overdue included, as `regia agenda` lays it
+/// out.
+pub fn agenda_message(db: &Database, doc: &Config, now: DateTime<Utc>) -> error::Result<String> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let formatter = todo::Formatter {
+        now,
+        due_soon: conf::due_soon(doc)?,
+        ..todo::Formatter::new(&priority_map)
+    };
+    let today = agenda::local_day(now);
+    let midnight = (today + Duration::days(1)).and_time(NaiveTime::MIN);
+    let until = dates::from_local(&Local, &midnight);
+    // Only what falls today, not the undated tasks or those further out
+    let entries: Vec<agenda::Entry> = agenda::entries(&db.tasks, now, until)
+        .into_iter()
+        .filter(|entry| entry.date.is_some_and(|date| date < until))
+        .collect();
+
+    let mut lines = vec![format!("Agenda for {}", today.format("%a %Y-%m-%d"))];
+    if entries.is_empty() {
+        lines.push(String::from("Nothing on the agenda"));
+    }
+    lines.extend(agenda::render(&entries, &db.tasks, today, &formatter));
+    Ok(plain(&lines.join("\n")))
+}
+
+/// An alert listing the overdue `tasks`.
+pub fn overdue_message(tasks: &[&Task]) -> String {
+    let mut lines = vec![match tasks.len() {
+        1 => String::from("A task is overdue:"),
+        many => format!("{} tasks are overdue:", many),
+    }];
+    for task in tasks {
+        let due = task.due.map(|due| taskmaster::fmt_date(&due));
+        lines.push(format!(
+            "* {} (due {})",
+            task.content,
+            due.unwrap_or_default()
+        ));
+    }
+    lines.join("\n")
+}
+
+/// What the daemon has posted, kept beside the database.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct Posted {
+    /// The last day whose agenda went out
+    pub agenda: Option<NaiveDate>,
+}
+
+pub fn posted_path(db_path: &Path) -> PathBuf {
+    db::sibling_path(db_path, ".notify.json")
+}
+
+pub fn read_posted(path: &Path) -> error::Result<Posted> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|err| RegiaError::corrupt(format!("{}: {}", path.display(), err))),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Posted::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub fn write_posted(path: &Path, posted: &Posted) -> error::Result<()> {
+    let json = serde_json::to_string_pretty(posted)
+        .map_err(|err| RegiaError::storage(format!("Serialization failed: {}", err)))?;
+    Ok(db::write_to_disk(path, json.as_bytes())?)
+}
+
+/// Whether the agenda for the day of local `now` is due to go out: it is
+/// past `at` and none went out yet today. A daemon started later in the
+/// day still posts it.
+pub fn agenda_due(at: NaiveTime, last: Option<NaiveDate>, now: NaiveDateTime) -> bool {
+    last != Some(now.date()) && now.time() >= at
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let notifier = match Notifier::new(doc) {
+        Some(notifier) => notifier,
+        None => {
+            return Err(RegiaError::Config(String::from(
+                "set notify.slack or notify.matrix to say where to post",
+            )))
+        }
+    };
+    let db = db::open(doc)?;
+    let now = Utc::now();
+
+    if matches.subcommand_matches("agenda").is_some() {
+        notifier.post(&agenda_message(&db, doc, now)?)?;
+        println!("{} today's agenda", "Posted".header());
+    } else if matches.subcommand_matches("overdue").is_some() {
+        let overdue: Vec<&Task> = db
+            .tasks
+            .get_tasks()
+            .iter()
+            .filter(|task| !task.is_done() && task.due.is_some_and(|due| due < now))
+            .collect();
+        if overdue.is_empty() {
+            println!("Nothing is overdue");
+            return Ok(());
+        }
+        notifier.post(&overdue_message(&overdue))?;
+        println!(
+            "{} {} overdue task{}",
+            "Posted".header(),
+            overdue.len(),
+            if overdue.len() == 1 { "" } else { "s" }
+        );
+    } else {
+        unreachable!();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use colored::*;
+
+    #[test]
+    fn messages_and_agenda_times() {
+        assert_eq!(encode("!room:example.com"), "%21room%3Aexample.com");
+        let colored = format!("{} {}", "Today".bold().underline(), "(1)".dimmed());
+        assert_eq!(plain(&colored), "Today (1)");
+
+        let mut task = Task::new(String::from("File taxes"), 0);
+        task.due = Some(Utc::now());
+        assert!(overdue_message(&[&task]).starts_with("A task is overdue:\n* File taxes (due "));
+
+        let eight = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+        let day = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        let at = |hour| day.and_hms_opt(hour, 0, 0).unwrap();
+        assert!(!agenda_due(eight, None, at(7)));
+        assert!(agenda_due(eight, None, at(8)));
+        assert!(agenda_due(eight, day.pred_opt(), at(15)));
+        assert!(!agenda_due(eight, Some(day), at(15)));
+    }
+}
@@ -0,0 +1,299 @@
+//! How list, show and report commands present what they find: colored text
+//! for people by default, or with `--output` records other tools can read.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use regia_core::attach::Attachment;
+use regia_core::conf::Config;
+use regia_core::error::{self, RegiaError};
+use regia_core::note;
+use regia_core::todo;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+    Text,
+    Json,
+    Yaml,
+    /// One tab-separated, uncolored line per record
+    Plain,
+    /// Nothing at all, from `--quiet`: lists answer through the exit
+    /// status instead, failing when they come up empty
+    Quiet,
+}
+
+impl Output {
+    /// The formats `--output` accepts.
+    pub const NAMES: [&'static str; 3] = ["json", "yaml", "plain"];
+
+    pub fn parse(name: &str) -> Option<Output> {
+        match name {
+            "json" => Some(Output::Json),
+            "yaml" => Some(Output::Yaml),
+            "plain" => Some(Output::Plain),
+            "quiet" => Some(Output::Quiet),
+            _ => None,
+        }
+    }
+
+    /// The format picked with `--output` or `--quiet`, which main keeps in
+    /// the config.
+    pub fn from_config(doc: &Config) -> Output {
+        doc.output
+            .as_deref()
+            .and_then(Output::parse)
+            .unwrap_or(Output::Text)
+    }
+}
+
+/// Something a command can print for `--output`: serialized as is for
+/// JSON and YAML, and as `plain` otherwise.
+pub trait Record: Serialize {
+    fn plain(&self) -> String;
+}
+
+fn serialize<T: Serialize + ?Sized>(output: Output, value: &T) -> error::Result<String> {
+    let text = match output {
+        Output::Yaml => serde_yaml::to_string(value).map_err(|err| err.to_string()),
+        _ => serde_json::to_string_pretty(value).map_err(|err| err.to_string()),
+    };
+    text.map_err(|err| RegiaError::storage(format!("Serialization failed: {}", err)))
+}
+
+/// Prints `records` as a JSON array, a YAML sequence or plain lines.
+pub fn print<R: Record>(output: Output, records: &[R]) -> error::Result<()> {
+    match output {
+        Output::Json | Output::Yaml => println!("{}", serialize(output, records)?.trim_end()),
+        Output::Text | Output::Plain => {
+            for record in records {
+                println!("{}", record.plain());
+            }
+        }
+        Output::Quiet if records.is_empty() => return Err(RegiaError::CheckFailed),
+        Output::Quiet => {}
+    }
+    Ok(())
+}
+
+/// Prints a single record, as an object rather than a one-item list.
+pub fn print_one<R: Record>(output: Output, record: &R) -> error::Result<()> {
+    match output {
+        Output::Json | Output::Yaml => println!("{}", serialize(output, record)?.trim_end()),
+        Output::Text | Output::Plain => println!("{}", record.plain()),
+        Output::Quiet => {}
+    }
+    Ok(())
+}
+
+/// Joins plain fields with tabs, writing absent ones as `-`.
+pub fn fields(values: &[String]) -> String {
+    values
+        .iter()
+        .map(|value| {
+            if value.is_empty() {
+                "-"
+            } else {
+                value.as_str()
+            }
+        })
+        .collect::<Vec<&str>>()
+        .join("\t")
+}
+
+pub fn fmt_time(time: &Option<DateTime<Utc>>) -> String {
+    time.map(|time| time.to_rfc3339()).unwrap_or_default()
+}
+
+fn sorted_tags(tags: &std::collections::HashSet<String>) -> Vec<String> {
+    let mut tags: Vec<String> = tags.iter().cloned().collect();
+    tags.sort();
+    tags
+}
+
+#[derive(Serialize)]
+pub struct TaskRecord {
+    pub id: Uuid,
+    pub content: String,
+    /// `open`, `blocked` or `done`
+    pub status: &'static str,
+    pub priority: u32,
+    pub created: DateTime<Utc>,
+    pub due: Option<DateTime<Utc>>,
+    pub scheduled: Option<DateTime<Utc>>,
+    pub completed: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    pub contexts: Vec<String>,
+    pub parent: Option<Uuid>,
+    pub depends: Vec<Uuid>,
+    /// The dependencies still open
+    pub blocked_by: Vec<Uuid>,
+    pub tracked_secs: i64,
+    pub estimate_secs: Option<i64>,
+    /// How long before `due` each reminder fires
+    pub reminder_secs: Vec<i64>,
+    pub attachments: Vec<Attachment>,
+    pub checklist: Vec<todo::ChecklistItem>,
+    /// The GitHub issue the task was imported from
+    pub issue: Option<todo::Issue>,
+    /// When anything last happened to it, which is what makes it stale
+    pub touched: DateTime<Utc>,
+    /// Who it was handed to with `task wait`, and when to follow up
+    pub waiting_on: Option<String>,
+    pub waiting_until: Option<DateTime<Utc>>,
+}
+
+impl TaskRecord {
+    pub fn new(task: &todo::Task, tasks: &todo::Tasks, now: DateTime<Utc>) -> Self {
+        let blocked_by: Vec<Uuid> = if task.is_done() {
+            Vec::new()
+        } else {
+            tasks
+                .open_dependencies(task)
+                .iter()
+                .map(|dep| dep.id)
+                .collect()
+        };
+        let mut depends: Vec<Uuid> = task.depends.iter().cloned().collect();
+        depends.sort();
+        Self {
+            id: task.id,
+            content: task.content.clone(),
+            status: if task.is_done() {
+                "done"
+            } else if !blocked_by.is_empty() {
+                "blocked"
+            } else {
+                "open"
+            },
+            priority: task.priority.weight(),
+            created: task.created,
+            due: task.due,
+            scheduled: task.scheduled,
+            completed: task.completed,
+            tags: sorted_tags(&task.tags),
+            contexts: sorted_tags(&task.contexts),
+            parent: task.parent,
+            depends,
+            blocked_by,
+            tracked_secs: task.tracked(now).num_seconds(),
+            estimate_secs: task.estimate,
+            reminder_secs: task.reminder_offsets(),
+            attachments: task.attachments.clone(),
+            checklist: task.checklist.clone(),
+            issue: task.issue.clone(),
+            touched: task.touched(),
+            waiting_on: task.waiting_on.clone(),
+            waiting_until: task.waiting_until,
+        }
+    }
+}
+
+impl Record for TaskRecord {
+    fn plain(&self) -> String {
+        fields(&[
+            self.id.to_string(),
+            self.status.to_string(),
+            self.priority.to_string(),
+            fmt_time(&self.due),
+            self.content.clone(),
+            self.tags.join(","),
+        ])
+    }
+}
+
+#[derive(Serialize)]
+pub struct NoteRecord {
+    pub id: Uuid,
+    pub content: String,
+    pub created: DateTime<Utc>,
+    pub tags: Vec<String>,
+    pub task_id: Option<Uuid>,
+    pub attachments: Vec<Attachment>,
+    /// The notes its `[[links]]` name
+    pub links: Vec<Uuid>,
+    pub pinned: bool,
+}
+
+impl NoteRecord {
+    pub fn new(note: &note::Note) -> Self {
+        Self {
+            id: note.id,
+            content: note.content.clone(),
+            created: note.created,
+            tags: sorted_tags(&note.tags),
+            task_id: note.task_id,
+            attachments: note.attachments.clone(),
+            links: note.links.iter().cloned().collect(),
+            pinned: note.pinned,
+        }
+    }
+}
+
+impl Record for NoteRecord {
+    fn plain(&self) -> String {
+        fields(&[
+            self.id.to_string(),
+            self.created.to_rfc3339(),
+            self.content.replace('\n', " "),
+            self.tags.join(","),
+        ])
+    }
+}
+
+/// A task with the notes attached to it, for `task show`.
+#[derive(Serialize)]
+pub struct TaskDetail {
+    #[serde(flatten)]
+    pub task: TaskRecord,
+    /// The status of the `tasks.workflow` the task is in
+    pub workflow: String,
+    pub notes: Vec<NoteRecord>,
+    pub comments: Vec<todo::Comment>,
+}
+
+impl Record for TaskDetail {
+    fn plain(&self) -> String {
+        let mut lines = vec![self.task.plain()];
+        lines.extend(self.notes.iter().map(|note| note.plain()));
+        lines.extend(self.comments.iter().map(|comment| {
+            fields(&[
+                comment.created.to_rfc3339(),
+                comment.author.clone(),
+                comment.body.replace('\n', " "),
+            ])
+        }));
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_task_lines() {
+        let mut tasks = todo::Tasks::default();
+        let mut dep = todo::Task::new(String::from("dep"), 0);
+        let dep_id = dep.id;
+        dep.tags.insert(String::from("b"));
+        dep.tags.insert(String::from("a"));
+        tasks.add(dep);
+        let mut task = todo::Task::new(String::from("main"), 3);
+        task.add_dependency(&dep_id);
+        let task_id = task.id;
+        tasks.add(task);
+
+        let now = Utc::now();
+        let dep = TaskRecord::new(tasks.get_task(&dep_id).unwrap(), &tasks, now);
+        assert_eq!(dep.plain(), format!("{}\topen\t0\t-\tdep\ta,b", dep_id));
+        let task = TaskRecord::new(tasks.get_task(&task_id).unwrap(), &tasks, now);
+        assert_eq!(task.status, "blocked");
+        assert_eq!(task.blocked_by, vec![dep_id]);
+
+        let json = serialize(Output::Json, &task).unwrap();
+        assert!(json.contains("\"status\": \"blocked\""));
+        let yaml = serialize(Output::Yaml, &task).unwrap();
+        assert!(yaml.contains("status: blocked"));
+    }
+}
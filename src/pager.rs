@@ -0,0 +1,60 @@
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+use regia_core::error::{self, RegiaError};
+
+/// The user's pager from `$PAGER`, falling back to `less`. The variable may
+/// carry arguments, e.g. `less -S`; set but empty means no pager at all.
+fn pager_command() -> Option<Vec<String>> {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| String::from("less"));
+    let parts: Vec<String> = pager.split_whitespace().map(String::from).collect();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts)
+    }
+}
+
+/// Shows `text` through the user's pager, or prints it when stdout isn't
+/// a terminal or there is no pager to run.
+pub fn page(text: &str) -> error::Result<()> {
+    let command = match pager_command() {
+        Some(command) if io::stdout().is_terminal() => command,
+        _ => {
+            println!("{}", text);
+            return Ok(());
+        }
+    };
+
+    let mut pager = Command::new(&command[0]);
+    pager.args(&command[1..]).stdin(Stdio::piped());
+    if std::env::var_os("LESS").is_none() {
+        // Quit when it fits on one screen, keep colors and leave the text
+        // on the terminal afterwards
+        pager.env("LESS", "FRX");
+    }
+    let mut child = match pager.spawn() {
+        Ok(child) => child,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            println!("{}", text);
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager closing early, say on `q`, is not an error
+        match writeln!(stdin, "{}", text) {
+            Err(err) if err.kind() != io::ErrorKind::BrokenPipe => return Err(err.into()),
+            _ => {}
+        }
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(RegiaError::storage(format!(
+            "pager {} exited with {}",
+            command[0], status
+        )));
+    }
+    Ok(())
+}
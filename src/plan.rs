@@ -0,0 +1,337 @@
+//! `regia plan`: the estimated work on each of the coming days, against
+//! `tasks.capacity`, and which tasks to put off where a day holds too much.
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, Utc};
+use clap::ArgMatches;
+use colored::*;
+use serde::Serialize;
+
+use crate::agenda::{self, Entry};
+use crate::output::{self, Output};
+use regia_core::conf::{self, Config};
+use regia_core::contexts;
+use regia_core::dates;
+use regia_core::db;
+use regia_core::error::{self, RegiaError};
+use regia_core::theme::Themed;
+use regia_core::todo::{self, Task};
+
+/// How many days, today first, `regia plan` looks at by default.
+const DEFAULT_DAYS: i64 = 7;
+
+/// The work a task asks of one day.
+pub struct Load<'a> {
+    pub task: &'a Task,
+    /// What is left of its estimate, or all of it for an occurrence that
+    /// hasn't been spawned yet
+    pub effort: Duration,
+    pub projected: bool,
+    /// Whether to put it off, and to which day of the plan has room for it;
+    /// `Some(None)` when none does
+    pub defer: Option<Option<NaiveDate>>,
+}
+
+pub struct Day<'a> {
+    pub date: NaiveDate,
+    /// Highest priority first
+    pub loads: Vec<Load<'a>>,
+}
+
+impl Day<'_> {
+    pub fn effort(&self) -> Duration {
+        self.loads
+            .iter()
+            .fold(Duration::zero(), |total, load| total + load.effort)
+    }
+
+    /// The effort of what isn't put off.
+    fn kept(&self) -> Duration {
+        self.loads
+            .iter()
+            .filter(|load| load.defer.is_none())
+            .fold(Duration::zero(), |total, load| total + load.effort)
+    }
+}
+
+/// What is left to do of `entry`'s task, when it has an estimate.
+fn effort(entry: &Entry, now: DateTime<Utc>) -> Option<Duration> {
+    let estimate = Duration::seconds(entry.task.estimate?);
+    if entry.projected {
+        return Some(estimate);
+    }
+    let left = estimate - entry.task.tracked(now);
+    if left > Duration::zero() {
+        Some(left)
+    } else {
+        None
+    }
+}
+
+/// The `days` days from local `today` with the estimated work of `entries`
+/// on them, overdue work on today. Where a day holds more than `capacity`
+/// its lower priority tasks are put off, each to the first later day with
+/// room left, keeping at least the most important.
+pub fn plan<'a>(
+    entries: &[Entry<'a>],
+    today: NaiveDate,
+    days: i64,
+    capacity: Duration,
+    now: DateTime<Utc>,
+) -> Vec<Day<'a>> {
+    let mut plan: Vec<Day> = (0..days)
+        .map(|offset| Day {
+            date: today + Duration::days(offset),
+            loads: Vec::new(),
+        })
+        .collect();
+    for entry in entries {
+        let (Some(date), Some(effort)) = (entry.date, effort(entry, now)) else {
+            continue;
+        };
+        let offset = (agenda::local_day(date) - today).num_days().max(0);
+        if let Some(day) = plan.get_mut(offset as usize) {
+            day.loads.push(Load {
+                task: entry.task,
+                effort,
+                projected: entry.projected,
+                defer: None,
+            });
+        }
+    }
+
+    let mut deferred = Vec::new();
+    for (index, day) in plan.iter_mut().enumerate() {
+        day.loads
+            .sort_by(|left, right| left.task.listing_order(right.task));
+        let mut kept = Duration::zero();
+        for (at, load) in day.loads.iter_mut().enumerate() {
+            if at > 0 && kept + load.effort > capacity {
+                load.defer = Some(None);
+                deferred.push((index, at));
+            } else {
+                kept += load.effort;
+            }
+        }
+    }
+    // What each day takes on from earlier ones, after its own work
+    let mut taken: Vec<Duration> = plan.iter().map(Day::kept).collect();
+    for (index, at) in deferred {
+        let effort = plan[index].loads[at].effort;
+        let to = (index + 1..plan.len()).find(|later| taken[*later] + effort <= capacity);
+        if let Some(later) = to {
+            taken[later] += effort;
+            plan[index].loads[at].defer = Some(Some(plan[later].date));
+        }
+    }
+    plan
+}
+
+#[derive(Serialize)]
+struct LoadRecord {
+    effort_secs: i64,
+    projected: bool,
+    defer: bool,
+    /// The day to put it off to, absent when no day of the plan has room
+    defer_to: Option<NaiveDate>,
+    task: output::TaskRecord,
+}
+
+#[derive(Serialize)]
+struct DayRecord {
+    date: NaiveDate,
+    effort_secs: i64,
+    capacity_secs: i64,
+    over: bool,
+    tasks: Vec<LoadRecord>,
+}
+
+impl output::Record for DayRecord {
+    fn plain(&self) -> String {
+        let mut lines = vec![output::fields(&[
+            String::from("day"),
+            self.date.to_string(),
+            self.effort_secs.to_string(),
+            self.capacity_secs.to_string(),
+        ])];
+        for load in self.tasks.iter() {
+            let defer = match (load.defer, load.defer_to) {
+                (true, Some(date)) => date.to_string(),
+                (true, None) => String::from("later"),
+                (false, _) => String::new(),
+            };
+            lines.push(output::fields(&[
+                String::from("task"),
+                self.date.to_string(),
+                load.effort_secs.to_string(),
+                defer,
+                load.task.id.to_string(),
+                load.task.content.clone(),
+            ]));
+        }
+        lines.join("\n")
+    }
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db = db::open(doc)?;
+    let days = match matches.value_of("days") {
+        Some(input) => match input.parse::<i64>() {
+            Ok(days) if days > 0 => days,
+            _ => return Err(RegiaError::Parse(format!("bad day count: {}", input))),
+        },
+        None => DEFAULT_DAYS,
+    };
+    let capacity = conf::capacity(doc)?;
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let formatter = todo::Formatter {
+        due_soon: conf::due_soon(doc)?,
+        ..todo::Formatter::new(&priority_map)
+    };
+    let now = formatter.now;
+    let today = agenda::local_day(now);
+    let midnight = (today + Duration::days(days)).and_time(NaiveTime::MIN);
+    let until = dates::from_local(&Local, &midnight);
+    let given = matches.value_of("context");
+    let context = crate::contexts::wanted(given, matches.is_present("any-context"), doc)?;
+    let mut entries = agenda::entries(&db.tasks, now, until);
+    entries.retain(|entry| entry.date.is_some_and(|date| date < until));
+    if let Some(context) = context.as_deref() {
+        entries.retain(|entry| contexts::fits(&entry.task.contexts, context));
+    }
+    let plan = plan(&entries, today, days, capacity, now);
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        let records: Vec<DayRecord> = plan
+            .iter()
+            .map(|day| DayRecord {
+                date: day.date,
+                effort_secs: day.effort().num_seconds(),
+                capacity_secs: capacity.num_seconds(),
+                over: day.effort() > capacity,
+                tasks: day
+                    .loads
+                    .iter()
+                    .map(|load| LoadRecord {
+                        effort_secs: load.effort.num_seconds(),
+                        projected: load.projected,
+                        defer: load.defer.is_some(),
+                        defer_to: load.defer.flatten(),
+                        task: output::TaskRecord::new(load.task, &db.tasks, now),
+                    })
+                    .collect(),
+            })
+            .collect();
+        return output::print(format, &records);
+    }
+
+    let locale = dates::locale();
+    for day in plan.iter() {
+        let date = format!("{} {}", day.date.format("%a"), locale.day(day.date));
+        let effort = day.effort();
+        let load = format!(
+            "{} of {}",
+            dates::fmt_duration(effort),
+            dates::fmt_duration(capacity)
+        );
+        let load = if day.loads.is_empty() {
+            "nothing estimated".dimmed()
+        } else if effort > capacity {
+            format!(
+                "{}, over by {}",
+                load,
+                dates::fmt_duration(effort - capacity)
+            )
+            .error()
+        } else {
+            load.dimmed()
+        };
+        println!("{} {}", date.accent(), load);
+        for load in day.loads.iter() {
+            let line = if load.projected {
+                format!("↻ {}", load.task.content).dimmed().to_string()
+            } else {
+                formatter.task(load.task, &db.tasks.open_dependencies(load.task))
+            };
+            let effort = format!("({})", dates::fmt_duration(load.effort)).dimmed();
+            let defer = match load.defer {
+                Some(Some(to)) => format!(" defer to {} {}", to.format("%a"), locale.day(to))
+                    .warning()
+                    .to_string(),
+                Some(None) => " defer past these days".warning().to_string(),
+                None => String::new(),
+            };
+            println!("    {} {}{}", line, effort, defer);
+        }
+    }
+    let unestimated = entries
+        .iter()
+        .filter(|entry| !entry.projected && entry.task.estimate.is_none())
+        .count();
+    if unestimated > 0 {
+        let plural = if unestimated == 1 { " has" } else { "s have" };
+        let note = format!(
+            "{} task{} no estimate, see task edit --estimate",
+            unestimated, plural
+        );
+        println!("{}", note.dimmed());
+    }
+    crate::contexts::print_sticky(given, context.as_deref());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn defers_lower_priorities_to_days_with_room() {
+        let now = Utc.with_ymd_and_hms(2019, 10, 4, 9, 0, 0).unwrap();
+        let today = now.date_naive();
+        let hours = |hours: i64| Some(Duration::hours(hours).num_seconds());
+        let mut tasks = Vec::new();
+        for (content, priority, estimate, days) in [
+            ("urgent", 9, 4, 0),
+            ("minor", 1, 3, -2),
+            ("whenever", 0, 2, 0),
+            ("tomorrow", 5, 4, 1),
+            ("unestimated", 5, 0, 1),
+        ] {
+            let mut task = Task::new(String::from(content), priority);
+            task.estimate = hours(estimate).filter(|secs| *secs > 0);
+            task.due = Some(now + Duration::days(days));
+            tasks.push(task);
+        }
+        let entries: Vec<Entry> = tasks
+            .iter()
+            .map(|task| Entry {
+                task,
+                date: task.due,
+                projected: false,
+            })
+            .collect();
+
+        let plan = plan(&entries, today, 3, Duration::hours(6), now);
+        assert_eq!(plan.len(), 3);
+        let shown = |day: &Day| -> Vec<(String, Option<Option<NaiveDate>>)> {
+            day.loads
+                .iter()
+                .map(|load| (load.task.content.clone(), load.defer))
+                .collect()
+        };
+        let later = |days: i64| Some(Some(today + Duration::days(days)));
+        assert_eq!(
+            shown(&plan[0]),
+            [
+                (String::from("urgent"), None),
+                (String::from("minor"), later(2)),
+                (String::from("whenever"), None),
+            ]
+        );
+        assert_eq!(plan[0].effort(), Duration::hours(9));
+        assert_eq!(shown(&plan[1]), [(String::from("tomorrow"), None)]);
+        assert!(plan[2].loads.is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+//! Subcommands from outside regia, as git has them: `regia foo` runs the
+//! first `regia-foo` on PATH with the arguments that follow, for anything
+//! regia itself has no command of that name for. The plugin learns where
+//! things are from the environment:
+//!
+//! - `REGIA_BIN`: the `regia` that ran it, to call back into
+//! - `REGIA_CONFIG`: the config file
+//! - `REGIA_DB`: the database, after `--db`
+//! - `REGIA_PROJECT` and `REGIA_OUTPUT`, the project and output format
+//!   in effect, when there are any
+//!
+//! regia holds no lock while a plugin runs, so a plugin can run regia
+//! commands, and goes by their lock like any other.
+
+use std::ffi::OsString;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+use clap::ArgMatches;
+
+use regia_core::conf::Config;
+use regia_core::db;
+use regia_core::error::{self, RegiaError};
+
+/// What a plugin's executable is called before its subcommand's name.
+pub const PREFIX: &str = "regia-";
+
+/// The name and arguments of the plugin `matches` asks for, if its
+/// subcommand isn't one of regia's own.
+pub fn requested<'a>(matches: &'a ArgMatches) -> Option<(&'a str, Vec<OsString>)> {
+    let (name, sub) = matches.subcommand();
+    if name.is_empty() || crate::COMMANDS.contains(&name) {
+        return None;
+    }
+    // clap keeps the arguments of an external subcommand under ""
+    let args = sub
+        .and_then(|sub| sub.values_of_os(""))
+        .map(|args| args.map(OsString::from).collect())
+        .unwrap_or_default();
+    Some((name, args))
+}
+
+/// Runs the plugin `name` with `args` and waits for it.
+pub fn run(
+    name: &str,
+    args: &[OsString],
+    doc: &Config,
+    config_path: &Path,
+) -> error::Result<ExitStatus> {
+    let program = format!("{}{}", PREFIX, name);
+    let mut command = Command::new(&program);
+    command
+        .args(args)
+        .env("REGIA_CONFIG", config_path)
+        .env("REGIA_DB", db::db_path(doc));
+    if let Ok(exe) = std::env::current_exe() {
+        command.env("REGIA_BIN", exe);
+    }
+    if let Some(project) = &doc.project {
+        command.env("REGIA_PROJECT", project);
+    }
+    if let Some(output) = &doc.output {
+        command.env("REGIA_OUTPUT", output);
+    }
+    match command.status() {
+        Ok(status) => Ok(status),
+        Err(err) if err.kind() == ErrorKind::NotFound => Err(RegiaError::Parse(format!(
+            "no command {}, and no {} on PATH to run for it",
+            name, program
+        ))),
+        Err(err) => Err(RegiaError::from(err).context(format!("can't run {}", program))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_plugins_from_commands() {
+        let matches = crate::app().get_matches_from(["regia", "agenda"]);
+        assert_eq!(requested(&matches), None);
+        let matches = crate::app().get_matches_from(["regia", "lint", "--fix", "notes"]);
+        assert_eq!(
+            requested(&matches),
+            Some((
+                "lint",
+                vec![OsString::from("--fix"), OsString::from("notes")]
+            ))
+        );
+        let matches = crate::app().get_matches_from(["regia", "lint"]);
+        assert_eq!(requested(&matches), Some(("lint", vec![])));
+    }
+
+    #[test]
+    fn commands_match_the_app() {
+        let mut help = Vec::new();
+        crate::app().write_help(&mut help).unwrap();
+        let help = String::from_utf8(help).unwrap();
+        let listed: Vec<&str> = help
+            .lines()
+            .skip_while(|line| !line.starts_with("SUBCOMMANDS:"))
+            .skip(1)
+            .filter(|line| line.starts_with("    ") && !line.starts_with("     "))
+            .filter_map(|line| line.split_whitespace().next())
+            .filter(|name| *name != "help")
+            .collect();
+        let mut commands = crate::COMMANDS.to_vec();
+        commands.sort_unstable();
+        assert_eq!(listed, commands);
+    }
+}
@@ -0,0 +1,201 @@
+//! `regia pomo`: pomodoros on a task, work and breaks counted down in the
+//! terminal. Each work phase that runs to the end is logged on the task as
+//! a tracked session.
+//!
+//! Like the daemon it can run for a long while, so it takes the database
+//! lock only for each write instead of throughout.
+
+use std::io::{self, IsTerminal, Write};
+use std::thread;
+
+use chrono::{DateTime, Duration, Utc};
+use clap::ArgMatches;
+use colored::*;
+use notify_rust::Notification;
+use uuid::Uuid;
+
+use crate::journal;
+use crate::taskmaster;
+use regia_core::conf::{self, Config};
+use regia_core::dates;
+use regia_core::db;
+use regia_core::error::{self, RegiaError};
+use regia_core::lock;
+use regia_core::theme::Themed;
+
+fn duration(flag: &str, input: &str) -> error::Result<Duration> {
+    match dates::parse_duration(input) {
+        Some(span) if span > Duration::zero() => Ok(span),
+        _ => Err(RegiaError::Parse(format!("bad --{}: {}", flag, input))),
+    }
+}
+
+/// `secs` as `mm:ss`, or `h:mm:ss` past the hour.
+fn clock(secs: i64) -> String {
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+fn notify(summary: &str, body: &str) {
+    if let Err(err) = Notification::new()
+        .appname("regia")
+        .summary(summary)
+        .body(body)
+        .show()
+    {
+        eprintln!("regia: could not show notification: {}", err);
+    }
+}
+
+/// Counts `length` down on one line, rewritten every second when stdout
+/// is a terminal.
+fn countdown(label: &str, length: Duration, content: &str) -> error::Result<()> {
+    let end = Utc::now() + length;
+    let live = io::stdout().is_terminal();
+    if !live {
+        println!("{} {} {}", label, clock(length.num_seconds()), content);
+    }
+    loop {
+        let left = end - Utc::now();
+        if left <= Duration::zero() {
+            break;
+        }
+        if live {
+            // Round up, so the clock reads 00:01 for the last second
+            let secs = (left.num_milliseconds() + 999) / 1000;
+            print!(
+                "\r\x1b[K{} {} {}",
+                label,
+                clock(secs).bold(),
+                content.dimmed()
+            );
+            io::stdout().flush()?;
+        }
+        let step = left.min(Duration::seconds(1));
+        thread::sleep(step.to_std().unwrap_or_default());
+    }
+    if live {
+        print!("\r\x1b[K");
+        io::stdout().flush()?;
+    }
+    Ok(())
+}
+
+/// Saves a finished pomodoro on task `id`, under the database lock.
+fn log(doc: &Config, id: Uuid, start: DateTime<Utc>, stop: DateTime<Utc>) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let _lock = lock::acquire(db_path, "pomo", lock::Wait::Forever)?;
+    let mut db = db::open(doc)?;
+    let before = db.clone();
+    match db.tasks.get_task_mut(&id) {
+        Some(mut task) => task.log_pomodoro(start, stop),
+        None => {
+            return Err(RegiaError::NotFound(String::from(
+                "the task went away before its pomodoro was logged",
+            )))
+        }
+    }
+    journal::record(db_path, "pomo", &before, &mut db)?;
+    db.to_disk(db_path)
+}
+
+/// Stops any clock `task start` left running, since a pomodoro is the
+/// one running now.
+fn stop_clocks(doc: &Config, now: DateTime<Utc>) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let _lock = lock::acquire(db_path, "pomo", lock::Wait::Forever)?;
+    let mut db = db::open(doc)?;
+    let running: Vec<Uuid> = db.tasks.running().iter().map(|task| task.id).collect();
+    if running.is_empty() {
+        return Ok(());
+    }
+    let before = db.clone();
+    for id in running {
+        let mut task = db.tasks.get_task_mut(&id).unwrap();
+        if let Some(spent) = task.stop(now) {
+            println!(
+                "{} {} after {}",
+                "Stopped".header(),
+                task.content,
+                dates::fmt_duration(spent)
+            );
+        }
+    }
+    journal::record(db_path, "pomo", &before, &mut db)?;
+    db.to_disk(db_path)
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let work = duration("work", matches.value_of("work").unwrap())?;
+    let rest = duration("break", matches.value_of("break").unwrap())?;
+    let rounds: usize = match matches.value_of("rounds").unwrap().parse() {
+        Ok(rounds) if rounds > 0 => rounds,
+        _ => {
+            return Err(RegiaError::Parse(format!(
+                "bad --rounds: {}",
+                matches.value_of("rounds").unwrap()
+            )))
+        }
+    };
+
+    let (id, content) = {
+        let db = db::open(doc)?;
+        let colors = conf::priority_colors(doc)?;
+        let priority_map = conf::as_priority_map(&colors);
+        let id = taskmaster::select_one(matches, &db.tasks, &priority_map)?;
+        let task = db.tasks.get_task(&id).unwrap();
+        if task.is_done() {
+            return Err(RegiaError::Conflict(format!(
+                "already done: {}",
+                task.content
+            )));
+        }
+        (id, task.content.clone())
+    };
+    stop_clocks(doc, Utc::now())?;
+
+    for round in 1..=rounds {
+        let start = Utc::now();
+        let label = format!("🍅 {}/{}", round, rounds);
+        countdown(&label, work, &content)?;
+        log(doc, id, start, Utc::now())?;
+        println!(
+            "{} pomodoro {}/{} on {}",
+            "Finished".header(),
+            round,
+            rounds,
+            content
+        );
+        print!("\x07");
+        if round == rounds {
+            notify("Pomodoros done", &format!("{} on {}", rounds, content));
+            break;
+        }
+        notify(
+            "Pomodoro done",
+            &format!("Take {} off {}", dates::fmt_duration(rest), content),
+        );
+        countdown("☕ break", rest, &content)?;
+        print!("\x07");
+        notify("Break over", &format!("Back to {}", content));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_faces() {
+        assert_eq!(clock(25 * 60), "25:00");
+        assert_eq!(clock(61), "01:01");
+        assert_eq!(clock(3600 + 5), "1:00:05");
+        assert!(duration("work", "25m").is_ok());
+        assert!(duration("work", "0m").is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use clap::ArgMatches;
+use colored::*;
+use serde::Serialize;
+
+use crate::journal;
+use crate::output::{self, Output};
+
+use regia_core::conf::Config;
+use regia_core::db::{self, Project, ROOT_PROJECT};
+use regia_core::error::{self, RegiaError};
+use regia_core::theme::Themed;
+
+#[derive(Serialize)]
+struct ProjectRecord {
+    name: String,
+    current: bool,
+    open_tasks: usize,
+    notes: usize,
+}
+
+impl output::Record for ProjectRecord {
+    fn plain(&self) -> String {
+        output::fields(&[
+            self.name.clone(),
+            if self.current { "*" } else { "" }.to_string(),
+            self.open_tasks.to_string(),
+            self.notes.to_string(),
+        ])
+    }
+}
+
+fn handle_project_list(db: &db::Database, doc: &Config) -> error::Result<()> {
+    let current = db.current_project.as_deref().unwrap_or(ROOT_PROJECT);
+    let mut rows = vec![(ROOT_PROJECT, &db.tasks, &db.notes)];
+    for (name, project) in db.projects.iter() {
+        rows.push((name.as_str(), &project.tasks, &project.notes));
+    }
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        let records: Vec<ProjectRecord> = rows
+            .iter()
+            .map(|(name, tasks, notes)| ProjectRecord {
+                name: name.to_string(),
+                current: *name == current,
+                open_tasks: tasks
+                    .get_tasks()
+                    .iter()
+                    .filter(|task| !task.is_done())
+                    .count(),
+                notes: notes.get_notes().len(),
+            })
+            .collect();
+        return output::print(format, &records);
+    }
+
+    for (name, tasks, notes) in rows {
+        let open = tasks
+            .get_tasks()
+            .iter()
+            .filter(|task| !task.is_done())
+            .count();
+        let marker = if name == current { "*" } else { " " };
+        println!(
+            "{} {} ({} open task{}, {} note{})",
+            marker.header(),
+            if name == current {
+                name.bold().to_string()
+            } else {
+                name.to_string()
+            },
+            open,
+            if open == 1 { "" } else { "s" },
+            notes.get_notes().len(),
+            if notes.get_notes().len() == 1 {
+                ""
+            } else {
+                "s"
+            },
+        );
+    }
+    Ok(())
+}
+
+fn handle_project_create(matches: &ArgMatches, db: &mut db::Database) -> error::Result<()> {
+    let name = matches.value_of("name").unwrap().trim();
+    if name.is_empty() || name == ROOT_PROJECT || db.projects.contains_key(name) {
+        return Err(RegiaError::Conflict(format!(
+            "project already exists: {}",
+            name
+        )));
+    }
+    db.projects.insert(name.to_string(), Project::named(name));
+    println!("{} {}", "Created project".header(), name.bold());
+    Ok(())
+}
+
+fn handle_project_rm(matches: &ArgMatches, db: &mut db::Database) -> error::Result<()> {
+    let name = matches.value_of("name").unwrap();
+    if name == ROOT_PROJECT {
+        return Err(RegiaError::Conflict(String::from(
+            "the root project cannot be removed",
+        )));
+    }
+    let project = match db.projects.get(name) {
+        Some(project) => project,
+        None => return Err(RegiaError::NotFound(format!("no project named: {}", name))),
+    };
+    let empty = project.tasks.get_tasks().is_empty() && project.notes.get_notes().is_empty();
+    if !empty && !matches.is_present("force") {
+        return Err(RegiaError::Conflict(format!(
+            "project {} still has tasks or notes, use --force to remove it anyway",
+            name
+        )));
+    }
+
+    db.projects.remove(name);
+    if db.current_project.as_deref() == Some(name) {
+        db.current_project = None;
+    }
+    println!("{} {}", "Removed project".header(), name.bold());
+    Ok(())
+}
+
+fn handle_project_switch(matches: &ArgMatches, db: &mut db::Database) -> error::Result<()> {
+    let name = matches.value_of("name").unwrap();
+    if name == ROOT_PROJECT {
+        db.current_project = None;
+    } else if db.projects.contains_key(name) {
+        db.current_project = Some(name.to_string());
+    } else {
+        return Err(RegiaError::NotFound(format!("no project named: {}", name)));
+    }
+    println!("{} {}", "Switched to project".header(), name.bold());
+    Ok(())
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    // Projects are managed on the database at rest, with the root active
+    let mut db = db::Database::load(db_path)?;
+    let before = db.clone();
+
+    if let Some(matches) = matches.subcommand_matches("create") {
+        handle_project_create(matches, &mut db)?;
+    } else if let Some(matches) = matches.subcommand_matches("rm") {
+        handle_project_rm(matches, &mut db)?;
+    } else if let Some(matches) = matches.subcommand_matches("switch") {
+        handle_project_switch(matches, &mut db)?;
+    } else if matches.subcommand_matches("ls").is_some() {
+        return handle_project_list(&db, doc);
+    } else {
+        unreachable!();
+    }
+
+    let command = format!("project {}", matches.subcommand_name().unwrap());
+    journal::record_as_is(db_path, &command, &before, &db)?;
+    db.to_disk(db_path)
+}
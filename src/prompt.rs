@@ -0,0 +1,109 @@
+//! Asking before acting, for commands that may run without anyone to ask:
+//! `--yes` answers every confirmation, and `--no-input`, or a stdin that
+//! isn't a terminal, makes a question an error instead of a wait.
+
+use std::io::{self, BufRead, IsTerminal};
+use std::sync::{Mutex, PoisonError};
+
+use clap::{Arg, ArgMatches};
+use colored::*;
+
+use regia_core::error::{self, RegiaError};
+use regia_core::theme::Themed;
+
+/// How the command being run may ask questions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Ask when there is a terminal to ask on
+    Ask,
+    /// Take yes for an answer without asking
+    Yes,
+    /// Never ask
+    NoInput,
+}
+
+impl Mode {
+    pub fn from_args(matches: &ArgMatches) -> Self {
+        if matches.is_present("yes") {
+            Mode::Yes
+        } else if matches.is_present("no-input") {
+            Mode::NoInput
+        } else {
+            Mode::Ask
+        }
+    }
+}
+
+static MODE: Mutex<Mode> = Mutex::new(Mode::Ask);
+
+fn mode() -> Mode {
+    *MODE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Sets how the commands run from here on may ask, as each command line
+/// says.
+pub fn set_mode(mode: Mode) {
+    *MODE.lock().unwrap_or_else(PoisonError::into_inner) = mode;
+}
+
+/// The global `--yes` and `--no-input` flags.
+pub fn args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("yes")
+            .short("y")
+            .long("yes")
+            .global(true)
+            .conflicts_with("no-input")
+            .help("Answer yes to every confirmation instead of asking"),
+        Arg::with_name("no-input")
+            .long("no-input")
+            .global(true)
+            .help("Fail instead of asking anything, as when stdin isn't a terminal"),
+    ]
+}
+
+/// Whether `--yes` answers for the user.
+pub fn assumed_yes() -> bool {
+    mode() == Mode::Yes
+}
+
+/// Fails unless `question` can be put to someone: not with `--no-input`,
+/// and only on a terminal.
+pub fn require(question: &str) -> error::Result<()> {
+    let why = match mode() {
+        Mode::NoInput => "with --no-input",
+        Mode::Ask if !io::stdin().is_terminal() => "without a terminal",
+        _ => return Ok(()),
+    };
+    Err(RegiaError::Conflict(format!(
+        "can't ask \"{}\" {}, pass --yes to answer yes",
+        question.trim_end(),
+        why
+    )))
+}
+
+/// Asks `question` and waits for y or n; anything else but an empty
+/// line, which means no, asks again. With `--yes` the answer is yes.
+pub fn confirm(question: &str) -> error::Result<bool> {
+    if assumed_yes() {
+        println!("{} {}", question.header(), "yes".dimmed());
+        return Ok(true);
+    }
+    require(question)?;
+    println!("{} [{}/{}]", question.header(), "y".bold(), "N".bold());
+    let stdin = io::stdin();
+    let mut stdin_iter = stdin.lock().lines();
+    loop {
+        let next_line = match stdin_iter.next() {
+            Some(line) => line?,
+            None => return Ok(false),
+        };
+        if next_line.to_lowercase() == "y" {
+            return Ok(true);
+        } else if next_line.is_empty() || next_line.to_lowercase() == "n" {
+            return Ok(false);
+        } else {
+            println!("Didn't understand {} please type y or n", next_line);
+        }
+    }
+}
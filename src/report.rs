@@ -0,0 +1,794 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use clap::ArgMatches;
+use colored::*;
+
+use crate::output::{self, Output};
+use regia_core::conf::{self, Config};
+use regia_core::dates;
+use regia_core::db::{self, Database};
+use regia_core::error::{self, RegiaError};
+use regia_core::history;
+use regia_core::query::{self, Filter};
+use regia_core::theme::Themed;
+use regia_core::todo::Task;
+use regia_core::trash::Trash;
+use regia_core::urgency::Urgency;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// How many tags `busiest_tags` keeps.
+const TOP_TAGS: usize = 5;
+/// How far back `report burndown` looks without `--weeks`.
+const DEFAULT_WEEKS: i64 = 2;
+/// The width of the longest velocity bar.
+const BAR_WIDTH: usize = 30;
+/// How many stale tasks the weekly report lists.
+const STALE_SHOWN: usize = 5;
+/// Sparkline levels, lowest first.
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// Statistics over the active project. "This week" starts on the
+/// `dates.week_start` day.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Report {
+    pub since: DateTime<Utc>,
+    pub open: usize,
+    pub overdue: usize,
+    pub completed_this_week: usize,
+    /// Mean time from creation to completion over every completed task
+    pub average_completion_secs: Option<i64>,
+    pub notes_created_this_week: usize,
+    /// Tags on the most tasks and notes created or completed this week
+    pub busiest_tags: Vec<TagCount>,
+    /// Completed tasks with an estimate and tracked time
+    pub estimated: usize,
+    /// Of those, how many took longer than estimated
+    pub over_estimate: usize,
+    pub under_estimate: usize,
+    /// The time tracked on them over the time estimated
+    pub estimate_ratio: Option<f64>,
+    /// Pomodoros finished each day of the week so far
+    pub pomodoros: Vec<PomodoroDay>,
+    /// Open tasks untouched for `tasks.stale_after`, longest untouched
+    /// first
+    pub stale: Vec<StaleTask>,
+    /// When `regia review` was last seen through
+    pub reviewed: Option<DateTime<Utc>>,
+    /// Tasks still waiting on someone past their follow-up date, longest
+    /// past first
+    pub follow_up: Vec<FollowUp>,
+}
+
+/// A task handed off with `task wait` that is due a follow-up.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FollowUp {
+    pub id: Uuid,
+    pub content: String,
+    pub waiting_on: String,
+    /// How long past the follow-up date it is
+    pub late_secs: i64,
+}
+
+/// A task the weekly report suggests closing or re-scoping.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct StaleTask {
+    pub id: Uuid,
+    pub content: String,
+    pub untouched_secs: i64,
+    /// `re-scope` when work on it started and stalled, else `close`
+    pub suggest: &'static str,
+}
+
+impl StaleTask {
+    fn new(task: &Task, now: DateTime<Utc>) -> Self {
+        let started = !task.sessions.is_empty()
+            || !task.comments.is_empty()
+            || task.checklist.iter().any(|item| item.done);
+        Self {
+            id: task.id,
+            content: task.content.clone(),
+            untouched_secs: (now - task.touched()).num_seconds(),
+            suggest: if started { "re-scope" } else { "close" },
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct PomodoroDay {
+    pub date: NaiveDate,
+    pub count: usize,
+}
+
+/// The weekly report as of `now`, listing tasks as stale once untouched
+/// for `stale_after`.
+pub fn build(db: &Database, now: DateTime<Utc>, stale_after: Option<Duration>) -> Report {
+    let since = dates::week_start(now, &dates::locale());
+    let this_week = |at: &DateTime<Utc>| *at >= since && *at <= now;
+
+    // Archived tasks are finished work too
+    let tasks: Vec<&Task> = db
+        .tasks
+        .get_tasks()
+        .iter()
+        .chain(db.archive.get_tasks())
+        .collect();
+    let open: Vec<&&Task> = tasks.iter().filter(|task| !task.is_done()).collect();
+
+    // Completions are counted from the history, which keeps those of
+    // tasks since purged
+    let completions: Vec<history::Event> = history::of_active(db)
+        .into_iter()
+        .filter(|event| event.kind == history::Kind::Completed)
+        .collect();
+    let average_completion_secs = if completions.is_empty() {
+        None
+    } else {
+        let total: i64 = completions.iter().map(|event| event.open).sum();
+        Some(total / completions.len() as i64)
+    };
+
+    let mut tag_counts: HashMap<&String, usize> = HashMap::new();
+    let active_tasks = tasks
+        .iter()
+        .filter(|task| this_week(&task.created) || task.completed.as_ref().is_some_and(this_week))
+        .map(|task| &task.tags);
+    let new_notes = db
+        .notes
+        .get_notes()
+        .iter()
+        .filter(|note| this_week(&note.created))
+        .map(|note| &note.tags);
+    for tags in active_tasks.chain(new_notes) {
+        for tag in tags {
+            *tag_counts.entry(tag).or_insert(0) += 1;
+        }
+    }
+    let mut busiest_tags: Vec<TagCount> = tag_counts
+        .into_iter()
+        .map(|(tag, count)| TagCount {
+            tag: tag.clone(),
+            count,
+        })
+        .collect();
+    busiest_tags.sort_by(|left, right| right.count.cmp(&left.count).then(left.tag.cmp(&right.tag)));
+    busiest_tags.truncate(TOP_TAGS);
+
+    let estimated: Vec<(Duration, Duration)> = tasks
+        .iter()
+        .filter(|task| task.is_done())
+        .filter_map(|task| Some((Duration::seconds(task.estimate?), task.tracked(now))))
+        .filter(|(_, tracked)| *tracked > Duration::zero())
+        .collect();
+    let total_estimate: i64 = estimated
+        .iter()
+        .map(|(estimate, _)| estimate.num_seconds())
+        .sum();
+    let total_tracked: i64 = estimated
+        .iter()
+        .map(|(_, tracked)| tracked.num_seconds())
+        .sum();
+
+    let mut pomodoros: Vec<PomodoroDay> = Vec::new();
+    let mut day = since.date_naive();
+    while day <= now.date_naive() {
+        pomodoros.push(PomodoroDay {
+            date: day,
+            count: 0,
+        });
+        day = day.succ_opt().unwrap();
+    }
+    let finished = tasks
+        .iter()
+        .flat_map(|task| task.sessions.iter())
+        .filter(|session| session.pomodoro)
+        .filter_map(|session| session.stop.filter(this_week));
+    for stop in finished {
+        if let Some(day) = pomodoros
+            .iter_mut()
+            .find(|day| day.date == stop.date_naive())
+        {
+            day.count += 1;
+        }
+    }
+
+    let mut stale: Vec<&&Task> = match stale_after {
+        Some(after) => open
+            .iter()
+            .copied()
+            .filter(|task| task.is_stale(now - after))
+            .collect(),
+        None => Vec::new(),
+    };
+    stale.sort_by_key(|task| task.touched());
+    let mut follow_up: Vec<FollowUp> = open
+        .iter()
+        .filter(|task| task.needs_follow_up(now))
+        .map(|task| FollowUp {
+            id: task.id,
+            content: task.content.clone(),
+            waiting_on: task.waiting_on.clone().unwrap_or_default(),
+            late_secs: task
+                .waiting_until
+                .map_or(0, |until| (now - until).num_seconds()),
+        })
+        .collect();
+    follow_up.sort_by_key(|task| std::cmp::Reverse(task.late_secs));
+
+    Report {
+        since,
+        open: open.len(),
+        overdue: open
+            .iter()
+            .filter(|task| task.due.is_some_and(|due| due < now))
+            .count(),
+        completed_this_week: completions
+            .iter()
+            .filter(|event| this_week(&event.at))
+            .count(),
+        average_completion_secs,
+        notes_created_this_week: db
+            .notes
+            .get_notes()
+            .iter()
+            .filter(|note| this_week(&note.created))
+            .count(),
+        busiest_tags,
+        estimated: estimated.len(),
+        over_estimate: estimated
+            .iter()
+            .filter(|(estimate, tracked)| tracked > estimate)
+            .count(),
+        under_estimate: estimated
+            .iter()
+            .filter(|(estimate, tracked)| tracked < estimate)
+            .count(),
+        estimate_ratio: if total_estimate > 0 {
+            Some(total_tracked as f64 / total_estimate as f64)
+        } else {
+            None
+        },
+        pomodoros,
+        stale: stale.iter().map(|task| StaleTask::new(task, now)).collect(),
+        reviewed: db.reviewed,
+        follow_up,
+    }
+}
+
+fn row(label: &str, value: String) {
+    println!("  {:<24} {}", label, value);
+}
+
+fn print_table(report: &Report) {
+    println!(
+        "{} {}",
+        "Week of".bold(),
+        dates::locale().day(report.since.date_naive()).header()
+    );
+    row("open", report.open.to_string());
+    let overdue = report.overdue.to_string();
+    row(
+        "overdue",
+        if report.overdue > 0 {
+            overdue.overdue().to_string()
+        } else {
+            overdue
+        },
+    );
+    row(
+        "completed this week",
+        report.completed_this_week.to_string().done().to_string(),
+    );
+    row(
+        "avg time to complete",
+        match report.average_completion_secs {
+            Some(secs) => dates::fmt_duration(Duration::seconds(secs)),
+            None => String::from("-"),
+        },
+    );
+    row(
+        "notes this week",
+        report.notes_created_this_week.to_string(),
+    );
+    row(
+        "last reviewed",
+        match report.reviewed {
+            Some(at) => format!("{} ago", dates::fmt_duration(Utc::now() - at)),
+            None => String::from("never, see regia review"),
+        },
+    );
+    if let Some(ratio) = report.estimate_ratio {
+        row(
+            "estimated and done",
+            format!(
+                "{} ({} over, {} under)",
+                report.estimated, report.over_estimate, report.under_estimate
+            ),
+        );
+        let ratio = format!("{:.0}%", ratio * 100.0);
+        row(
+            "tracked of estimate",
+            if report.over_estimate > report.under_estimate {
+                ratio.error().to_string()
+            } else {
+                ratio
+            },
+        );
+    }
+    let pomodoros: usize = report.pomodoros.iter().map(|day| day.count).sum();
+    if pomodoros > 0 {
+        let days: Vec<String> = report
+            .pomodoros
+            .iter()
+            .map(|day| format!("{} {}", day.date.format("%a"), day.count))
+            .collect();
+        row(
+            "pomodoros",
+            format!("{} ({})", pomodoros, days.join(", ").dimmed()),
+        );
+    }
+
+    if !report.busiest_tags.is_empty() {
+        println!("{}", "Busiest tags".bold());
+        for count in report.busiest_tags.iter() {
+            // Pad before coloring so the escape codes don't skew the column
+            let label = format!("{:<24}", format!("#{}", count.tag));
+            println!("  {} {}", label.accent(), count.count);
+        }
+    }
+
+    if !report.stale.is_empty() {
+        println!("{}", "Stale, worth closing or re-scoping".bold());
+        for task in report.stale.iter().take(STALE_SHOWN) {
+            let untouched = dates::fmt_duration(Duration::seconds(task.untouched_secs));
+            println!(
+                "  * {} {} {}",
+                task.content,
+                format!("(untouched {})", untouched).warning(),
+                task.suggest.dimmed()
+            );
+        }
+        if report.stale.len() > STALE_SHOWN {
+            let more = format!(
+                "  and {} more, see task ls --stale",
+                report.stale.len() - STALE_SHOWN
+            );
+            println!("{}", more.dimmed());
+        }
+    }
+
+    if !report.follow_up.is_empty() {
+        println!("{}", "Waiting, time to follow up".bold());
+        for task in report.follow_up.iter() {
+            let late = dates::fmt_duration(Duration::seconds(task.late_secs));
+            println!(
+                "  * {} {}",
+                task.content,
+                format!("(on {}, due {} ago)", task.waiting_on, late).warning()
+            );
+        }
+    }
+}
+
+impl output::Record for Report {
+    fn plain(&self) -> String {
+        let average = self
+            .average_completion_secs
+            .map(|secs| secs.to_string())
+            .unwrap_or_default();
+        let mut lines = vec![
+            output::fields(&[String::from("since"), self.since.to_rfc3339()]),
+            output::fields(&[String::from("open"), self.open.to_string()]),
+            output::fields(&[String::from("overdue"), self.overdue.to_string()]),
+            output::fields(&[
+                String::from("completed_this_week"),
+                self.completed_this_week.to_string(),
+            ]),
+            output::fields(&[String::from("average_completion_secs"), average]),
+            output::fields(&[
+                String::from("notes_created_this_week"),
+                self.notes_created_this_week.to_string(),
+            ]),
+            output::fields(&[String::from("reviewed"), output::fmt_time(&self.reviewed)]),
+        ];
+        lines.push(output::fields(&[
+            String::from("estimated"),
+            self.estimated.to_string(),
+            self.over_estimate.to_string(),
+            self.under_estimate.to_string(),
+            self.estimate_ratio
+                .map(|ratio| format!("{:.3}", ratio))
+                .unwrap_or_default(),
+        ]));
+        for count in self.busiest_tags.iter() {
+            lines.push(output::fields(&[
+                String::from("tag"),
+                count.tag.clone(),
+                count.count.to_string(),
+            ]));
+        }
+        for day in self.pomodoros.iter() {
+            lines.push(output::fields(&[
+                String::from("pomodoros"),
+                day.date.to_string(),
+                day.count.to_string(),
+            ]));
+        }
+        for task in self.stale.iter() {
+            lines.push(output::fields(&[
+                String::from("stale"),
+                task.id.to_string(),
+                task.untouched_secs.to_string(),
+                task.suggest.to_string(),
+                task.content.clone(),
+            ]));
+        }
+        for task in self.follow_up.iter() {
+            lines.push(output::fields(&[
+                String::from("follow_up"),
+                task.id.to_string(),
+                task.late_secs.to_string(),
+                task.waiting_on.clone(),
+                task.content.clone(),
+            ]));
+        }
+        lines.join("\n")
+    }
+}
+
+/// One day of the burndown, by UTC date like the weekly report.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Day {
+    pub date: NaiveDate,
+    pub created: usize,
+    pub completed: usize,
+    /// Tasks still open at the end of the day
+    pub open: usize,
+}
+
+impl output::Record for Day {
+    fn plain(&self) -> String {
+        output::fields(&[
+            self.date.to_string(),
+            self.created.to_string(),
+            self.completed.to_string(),
+            self.open.to_string(),
+        ])
+    }
+}
+
+/// Tasks created and completed on each of the `days` days up to and
+/// including today, with the backlog left at the end of each.
+pub fn burndown(db: &Database, now: DateTime<Utc>, days: i64) -> Vec<Day> {
+    let tasks: Vec<&Task> = db
+        .tasks
+        .get_tasks()
+        .iter()
+        .chain(db.archive.get_tasks())
+        .collect();
+    let today = now.date_naive();
+    (0..days)
+        .rev()
+        .map(|back| {
+            let date = today - Duration::days(back);
+            let on_day = |at: &DateTime<Utc>| at.date_naive() == date;
+            let by_end = |at: &DateTime<Utc>| at.date_naive() <= date;
+            Day {
+                date,
+                created: tasks.iter().filter(|task| on_day(&task.created)).count(),
+                completed: tasks
+                    .iter()
+                    .filter(|task| task.completed.as_ref().is_some_and(on_day))
+                    .count(),
+                open: tasks
+                    .iter()
+                    .filter(|task| by_end(&task.created))
+                    .filter(|task| !task.completed.as_ref().is_some_and(by_end))
+                    .count(),
+            }
+        })
+        .collect()
+}
+
+/// A bar of spark characters scaled so the largest value fills a cell;
+/// days with nothing stay blank.
+fn sparkline<I: Iterator<Item = usize> + Clone>(values: I) -> String {
+    let max = values.clone().max().unwrap_or(0);
+    values
+        .map(|value| match value {
+            0 => ' ',
+            value => SPARKS[(value * SPARKS.len()).div_ceil(max) - 1],
+        })
+        .collect()
+}
+
+fn bar(value: usize, max: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+    "█".repeat((value * BAR_WIDTH).div_ceil(max))
+}
+
+fn print_burndown(days: &[Day]) {
+    let (first, last) = match (days.first(), days.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return,
+    };
+    println!(
+        "{} {} to {}",
+        "Burndown".bold(),
+        first.date.to_string().header(),
+        last.date.to_string().header()
+    );
+    let created: usize = days.iter().map(|day| day.created).sum();
+    let completed: usize = days.iter().map(|day| day.completed).sum();
+    let start = (first.open + first.completed).saturating_sub(first.created);
+    let change = last.open as i64 - start as i64;
+    println!(
+        "  {:<10} {} {}",
+        "created",
+        sparkline(days.iter().map(|day| day.created)).error(),
+        created
+    );
+    println!(
+        "  {:<10} {} {}",
+        "completed",
+        sparkline(days.iter().map(|day| day.completed)).done(),
+        completed
+    );
+    let trend = format!("{:+}", change);
+    println!(
+        "  {:<10} {} {} -> {} ({})",
+        "open",
+        sparkline(days.iter().map(|day| day.open)).accent(),
+        start,
+        last.open,
+        if change > 0 {
+            trend.error().to_string()
+        } else {
+            trend.done().to_string()
+        }
+    );
+
+    // Velocity in whole weeks back from today, so the latest is complete
+    let mut weeks: Vec<&[Day]> = days.rchunks(7).collect();
+    weeks.reverse();
+    let week_totals: Vec<(NaiveDate, usize, usize)> = weeks
+        .iter()
+        .map(|week| {
+            (
+                week[0].date,
+                week.iter().map(|day| day.created).sum(),
+                week.iter().map(|day| day.completed).sum(),
+            )
+        })
+        .collect();
+    let largest = week_totals
+        .iter()
+        .map(|(_, created, completed)| *created.max(completed))
+        .max()
+        .unwrap_or(0);
+    println!("{}", "Velocity".bold());
+    for (start, created, completed) in week_totals {
+        println!(
+            "  {}  {:>4} created   {}",
+            start,
+            created,
+            bar(created, largest).error()
+        );
+        println!(
+            "  {:<10}  {:>4} completed {}",
+            "",
+            completed,
+            bar(completed, largest).done()
+        );
+    }
+}
+
+fn handle_burndown(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db = db::open(doc)?;
+    let weeks = match matches.value_of("weeks") {
+        Some(input) => match input.parse() {
+            Ok(weeks) if weeks > 0 => weeks,
+            _ => return Err(RegiaError::Parse(format!("bad number of weeks: {}", input))),
+        },
+        None => DEFAULT_WEEKS,
+    };
+    let days = burndown(&db, Utc::now(), weeks * 7);
+
+    match Output::from_config(doc) {
+        Output::Text => print_burndown(&days),
+        format => output::print(format, &days)?,
+    }
+    Ok(())
+}
+
+/// Leaves only the tasks `filter` picks in `db`, and the history of
+/// those, for a report on them alone. Notes are left as they are.
+fn narrow(db: &mut Database, filter: &Filter, urgency: Option<&Urgency>, now: DateTime<Utc>) {
+    let picked: HashSet<Uuid> = db
+        .tasks
+        .get_tasks()
+        .iter()
+        .chain(db.archive.get_tasks())
+        .filter(|task| filter.matches(task, &db.tasks, urgency, now))
+        .map(|task| task.id)
+        .collect();
+    for tasks in [&mut db.tasks, &mut db.archive] {
+        let left: Vec<Uuid> = tasks
+            .get_tasks()
+            .iter()
+            .map(|task| task.id)
+            .filter(|id| !picked.contains(id))
+            .collect();
+        for id in left {
+            tasks.remove(id);
+        }
+    }
+    // Trashed tasks would otherwise still count as completed
+    db.trash = Trash::default();
+    db.history.retain(|event| picked.contains(&event.task));
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    if let Some(matches) = matches.subcommand_matches("burndown") {
+        return handle_burndown(matches, doc);
+    }
+    let mut db = db::open(doc)?;
+    let now = Utc::now();
+    if let Some(input) = matches.value_of("where") {
+        let script = query::parse(input, now)?;
+        if script.sort.is_some() || script.limit.is_some() {
+            return Err(RegiaError::Parse(String::from(
+                "report --where takes no sort or limit",
+            )));
+        }
+        if let Some(filter) = &script.filter {
+            narrow(&mut db, filter, conf::urgency(doc)?.as_ref(), now);
+        }
+    }
+    let report = build(&db, now, conf::stale_after(doc)?);
+
+    // `--format json` predates the global `--output`
+    let format = match (Output::from_config(doc), matches.value_of("format")) {
+        (Output::Text, Some("json")) => Output::Json,
+        (format, _) => format,
+    };
+    match format {
+        Output::Text => print_table(&report),
+        format => output::print_one(format, &report)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use regia_core::note::Note;
+    use regia_core::todo::{Session, TaskType};
+
+    #[test]
+    fn weekly_statistics() {
+        // A Wednesday
+        let now = Utc.with_ymd_and_hms(2019, 10, 2, 12, 0, 0).unwrap();
+        let mut db = Database::default();
+
+        let mut done = Task::new(String::from("done"), 0);
+        done.created = now - Duration::hours(4);
+        done.completed = Some(now - Duration::hours(2));
+        done.tags.insert(String::from("work"));
+        done.estimate = Some(Duration::hours(1).num_seconds());
+        done.sessions.push(Session {
+            start: now - Duration::hours(4),
+            stop: Some(now - Duration::hours(2)),
+            pomodoro: false,
+        });
+        done.log_pomodoro(now - Duration::days(1), now - Duration::days(1));
+        let mut last_week = Task::new(String::from("last week"), 0);
+        last_week.created = now - Duration::days(10);
+        last_week.completed = Some(now - Duration::days(9));
+        let mut late = Task::new_date(
+            String::from("late"),
+            0,
+            Some(now - Duration::hours(1)),
+            TaskType::Deadline,
+            None,
+        );
+        late.waiting_on = Some(String::from("Bob"));
+        late.waiting_until = Some(now - Duration::days(2));
+        db.tasks.add(done);
+        db.tasks.add(late);
+        db.archive.add(last_week);
+        let mut note = Note::new("tagged");
+        note.created = now - Duration::hours(1);
+        note.tags.insert(String::from("work"));
+        db.notes.add(note);
+
+        let mut forgotten = Task::new(String::from("forgotten"), 0);
+        forgotten.created = now - Duration::days(40);
+        db.tasks.add(forgotten);
+
+        let report = build(&db, now, Some(Duration::days(30)));
+        assert_eq!(report.stale.len(), 1);
+        assert_eq!(report.stale[0].content, "forgotten");
+        assert_eq!(report.stale[0].suggest, "close");
+        assert!(build(&db, now, None).stale.is_empty());
+        db.tasks.remove(report.stale[0].id);
+
+        let report = build(&db, now, Some(Duration::days(30)));
+        assert_eq!(report.open, 1);
+        assert_eq!(report.overdue, 1);
+        assert_eq!(report.follow_up.len(), 1);
+        assert_eq!(report.follow_up[0].waiting_on, "Bob");
+        assert_eq!(
+            report.follow_up[0].late_secs,
+            Duration::days(2).num_seconds()
+        );
+        assert_eq!(report.completed_this_week, 1);
+        assert_eq!(
+            report.average_completion_secs,
+            Some(Duration::hours(13).num_seconds())
+        );
+        assert_eq!(report.notes_created_this_week, 1);
+        assert_eq!(
+            report.busiest_tags,
+            vec![TagCount {
+                tag: String::from("work"),
+                count: 2
+            }]
+        );
+        assert_eq!(
+            (
+                report.estimated,
+                report.over_estimate,
+                report.under_estimate
+            ),
+            (1, 1, 0)
+        );
+        assert_eq!(report.estimate_ratio, Some(2.0));
+        let counts: Vec<usize> = report.pomodoros.iter().map(|day| day.count).collect();
+        assert_eq!(counts, vec![0, 1, 0]);
+
+        // Purged tasks stay counted through the history
+        db.history = history::pending(&db);
+        let done = db.history.iter().find(|event| event.content == "done");
+        db.tasks.remove(done.unwrap().task);
+        assert_eq!(build(&db, now, None).completed_this_week, 1);
+    }
+
+    #[test]
+    fn daily_burndown() {
+        let now = Utc.with_ymd_and_hms(2019, 10, 2, 12, 0, 0).unwrap();
+        let mut db = Database::default();
+
+        let mut old = Task::new(String::from("old"), 0);
+        old.created = now - Duration::days(10);
+        let mut quick = Task::new(String::from("quick"), 0);
+        quick.created = now - Duration::days(2);
+        quick.completed = Some(now - Duration::days(1));
+        let mut archived = Task::new(String::from("archived"), 0);
+        archived.created = now - Duration::days(2);
+        archived.completed = Some(now - Duration::days(2));
+        let mut today = Task::new(String::from("today"), 0);
+        today.created = now;
+        db.tasks.add(old);
+        db.tasks.add(quick);
+        db.tasks.add(today);
+        db.archive.add(archived);
+
+        let counts: Vec<(usize, usize, usize)> = burndown(&db, now, 3)
+            .iter()
+            .map(|day| (day.created, day.completed, day.open))
+            .collect();
+        assert_eq!(counts, [(2, 1, 2), (0, 1, 1), (1, 0, 2)]);
+        assert_eq!(sparkline([0, 1, 8, 4].iter().copied()), " ▁█▄");
+    }
+}
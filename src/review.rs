@@ -0,0 +1,327 @@
+//! `regia review`: a weekly review in the manner of GTD, a step at a time.
+//! Overdue tasks come first, each rescheduled, completed or dropped, then
+//! tasks gone stale, then the inbox, and last what falls due in the coming
+//! week. A review seen through to the end is remembered, and `regia
+//! report` says when that was.
+
+use std::io::{self, BufRead, Write};
+
+use chrono::{DateTime, Duration, Utc};
+use clap::ArgMatches;
+use colored::*;
+use uuid::Uuid;
+
+use crate::inbox;
+use crate::journal;
+use crate::taskmaster;
+use regia_core::conf::{self, Config};
+use regia_core::dates;
+use regia_core::db::{self, Database};
+use regia_core::error;
+use regia_core::theme::Themed;
+use regia_core::todo::{self, Task};
+
+/// How far ahead the last step lists deadlines.
+const UPCOMING_DAYS: i64 = 7;
+
+/// What to do with a task under review.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Choice {
+    Reschedule,
+    Complete,
+    Drop,
+    /// Leave it be but count it as looked at, so that it isn't stale
+    Keep,
+    Skip,
+    Quit,
+}
+
+impl Choice {
+    fn word(self) -> &'static str {
+        match self {
+            Choice::Reschedule => "reschedule",
+            Choice::Complete => "complete",
+            Choice::Drop => "drop",
+            Choice::Keep => "keep",
+            Choice::Skip => "skip",
+            Choice::Quit => "quit",
+        }
+    }
+
+    /// The one of `offered` that `input` names, whole or by its first
+    /// letter. An empty line skips.
+    fn parse(input: &str, offered: &[Choice]) -> Option<Choice> {
+        let input = input.trim().to_lowercase();
+        let input = if input.is_empty() { "skip" } else { &input };
+        offered
+            .iter()
+            .copied()
+            .find(|choice| choice.word() == input || choice.word()[..1] == *input)
+    }
+}
+
+type Lines<'a> = dyn Iterator<Item = io::Result<String>> + 'a;
+
+/// Asks which of `offered` to do until it gets an answer; the end of
+/// input quits.
+fn ask(lines: &mut Lines, offered: &[Choice]) -> error::Result<Choice> {
+    let options: Vec<String> = offered
+        .iter()
+        .map(|choice| {
+            let word = choice.word();
+            format!("[{}]{}", word[..1].bold(), &word[1..])
+        })
+        .collect();
+    let (last, rest) = options.split_last().unwrap();
+    loop {
+        print!("{} or {}? ", rest.join(", "), last);
+        io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => {
+                println!();
+                return Ok(Choice::Quit);
+            }
+        };
+        match Choice::parse(&line, offered) {
+            Some(choice) => return Ok(choice),
+            None => {
+                let letters: Vec<&str> = offered.iter().map(|choice| &choice.word()[..1]).collect();
+                println!(
+                    "Didn't understand {}, please type {}",
+                    line,
+                    letters.join(", ")
+                );
+            }
+        }
+    }
+}
+
+/// Asks for a new due date until one parses; an empty line or the end of
+/// input leaves the task as it was.
+fn new_due(lines: &mut Lines) -> error::Result<Option<DateTime<Utc>>> {
+    loop {
+        print!("{} ", "Due:".header());
+        io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(None),
+        };
+        if line.trim().is_empty() {
+            return Ok(None);
+        }
+        match taskmaster::parse_due(line.trim()) {
+            Ok(due) => return Ok(Some(due)),
+            Err(err) => println!("{}, try e.g. tomorrow or friday 9am", err),
+        }
+    }
+}
+
+/// What the review did, for the summary at the end.
+#[derive(Debug, Default)]
+struct Tally {
+    rescheduled: usize,
+    completed: usize,
+    dropped: usize,
+    kept: usize,
+}
+
+fn heading(title: &str, count: usize) {
+    println!(
+        "{} {}",
+        title.bold().underline(),
+        format!("({})", count).dimmed()
+    );
+}
+
+/// Puts each of the tasks `ids` up for `offered`, applying the answers to
+/// `db` as they come. Returns whether the review was quit.
+fn walk(
+    db: &mut Database,
+    ids: &[Uuid],
+    offered: &[Choice],
+    lines: &mut Lines,
+    formatter: &todo::Formatter,
+    tally: &mut Tally,
+) -> error::Result<bool> {
+    let now = formatter.now;
+    for (index, id) in ids.iter().enumerate() {
+        let task = db.tasks.get_task(id).unwrap();
+        println!(
+            "{} {}",
+            format!("[{}/{}]", index + 1, ids.len()).dimmed(),
+            formatter.task(task, &db.tasks.open_dependencies(task))
+        );
+        match ask(lines, offered)? {
+            Choice::Reschedule => {
+                if let Some(due) = new_due(lines)? {
+                    db.tasks.get_task_mut(id).unwrap().due = Some(due);
+                    tally.rescheduled += 1;
+                }
+            }
+            Choice::Complete => {
+                db.tasks.get_task_mut(id).unwrap().complete();
+                taskmaster::print_unblocked(&db.tasks, &[*id], formatter.priority_map);
+                tally.completed += 1;
+            }
+            Choice::Drop => {
+                db.trash.delete_task(&mut db.tasks, *id, now);
+                tally.dropped += 1;
+            }
+            Choice::Keep => {
+                db.tasks.get_task_mut(id).unwrap().modified = Some(now);
+                tally.kept += 1;
+            }
+            Choice::Skip => {}
+            Choice::Quit => return Ok(true),
+        }
+    }
+    Ok(false)
+}
+
+/// The ids of `tasks` in the order `key` puts them.
+fn ids_by<K: Ord>(tasks: Vec<&Task>, key: impl Fn(&Task) -> K) -> Vec<Uuid> {
+    let mut tasks = tasks;
+    tasks.sort_by_key(|task| key(task));
+    tasks.iter().map(|task| task.id).collect()
+}
+
+pub fn handle_it(_matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let mut db = db::open(doc)?;
+    let before = db.clone();
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let formatter = todo::Formatter {
+        due_soon: conf::due_soon(doc)?,
+        stale_after: conf::stale_after(doc)?,
+        ..todo::Formatter::new(&priority_map)
+    };
+    let now = formatter.now;
+    match db.reviewed {
+        Some(at) => println!(
+            "{}",
+            format!("Last reviewed {} ago", dates::fmt_duration(now - at)).dimmed()
+        ),
+        None => println!("{}", "Not reviewed before".dimmed()),
+    }
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut tally = Tally::default();
+    let open: Vec<&Task> = db
+        .tasks
+        .get_tasks()
+        .iter()
+        .filter(|task| !task.is_done())
+        .collect();
+    let is_overdue = |task: &Task| task.due.is_some_and(|due| due < now);
+    let overdue = ids_by(
+        open.iter()
+            .copied()
+            .filter(|task| is_overdue(task))
+            .collect(),
+        |task| task.due,
+    );
+    let stale = match formatter.stale_after {
+        Some(after) => ids_by(
+            open.iter()
+                .copied()
+                .filter(|task| !is_overdue(task) && task.is_stale(now - after))
+                .collect(),
+            Task::touched,
+        ),
+        None => Vec::new(),
+    };
+
+    let mut quit = false;
+    if !overdue.is_empty() {
+        heading("Overdue", overdue.len());
+        let offered = [
+            Choice::Reschedule,
+            Choice::Complete,
+            Choice::Drop,
+            Choice::Skip,
+            Choice::Quit,
+        ];
+        quit = walk(
+            &mut db, &overdue, &offered, &mut lines, &formatter, &mut tally,
+        )?;
+    }
+    if !quit && !stale.is_empty() {
+        heading("Stale", stale.len());
+        let offered = [
+            Choice::Keep,
+            Choice::Complete,
+            Choice::Drop,
+            Choice::Skip,
+            Choice::Quit,
+        ];
+        quit = walk(
+            &mut db, &stale, &offered, &mut lines, &formatter, &mut tally,
+        )?;
+    }
+    let mut triaged = inbox::Triaged::default();
+    if !quit && !db.inbox.is_empty() {
+        heading("Inbox", db.inbox.len());
+        triaged = inbox::triage(&mut db, &mut lines)?;
+        quit = triaged.quit;
+    }
+    if !quit {
+        let until = now + Duration::days(UPCOMING_DAYS);
+        let mut upcoming: Vec<&Task> = db
+            .tasks
+            .get_tasks()
+            .iter()
+            .filter(|task| {
+                !task.is_done() && task.due.is_some_and(|due| due >= now && due <= until)
+            })
+            .collect();
+        upcoming.sort_by(|left, right| {
+            left.due
+                .cmp(&right.due)
+                .then_with(|| left.listing_order(right))
+        });
+        if !upcoming.is_empty() {
+            heading("Due in the coming week", upcoming.len());
+            for task in upcoming {
+                println!(
+                    "{}",
+                    formatter.task(task, &db.tasks.open_dependencies(task))
+                );
+            }
+        }
+        db.reviewed = Some(now);
+    }
+
+    let mut summary = format!(
+        "{} rescheduled, {} completed, {} dropped, {} kept",
+        tally.rescheduled, tally.completed, tally.dropped, tally.kept
+    );
+    let sorted = triaged.tasks + triaged.notes + triaged.discarded;
+    if sorted > 0 {
+        summary.push_str(&format!(", {} from the inbox sorted", sorted));
+    }
+    let done = if quit { "Stopped" } else { "Reviewed" };
+    println!("{} {}", done.header(), summary);
+    if quit {
+        println!("{}", "Run regia review again to finish".dimmed());
+    }
+    journal::record(db_path, "review", &before, &mut db)?;
+    db.to_disk(db_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_only_offered_choices() {
+        let offered = [Choice::Reschedule, Choice::Drop, Choice::Skip, Choice::Quit];
+        assert_eq!(Choice::parse("R", &offered), Some(Choice::Reschedule));
+        assert_eq!(Choice::parse(" drop\n", &offered), Some(Choice::Drop));
+        assert_eq!(Choice::parse("", &offered), Some(Choice::Skip));
+        assert_eq!(Choice::parse("k", &offered), None);
+        assert_eq!(Choice::parse("keep", &[Choice::Keep]), Some(Choice::Keep));
+    }
+}
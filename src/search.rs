@@ -0,0 +1,226 @@
+use clap::ArgMatches;
+use colored::*;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::output::{self, Output};
+
+use regia_core::conf::Config;
+use regia_core::db::{self, Database};
+use regia_core::error::{self, RegiaError};
+use regia_core::fulltext::{self, Kind, Query, SearchIndex};
+use regia_core::note::Note;
+use regia_core::theme::Themed;
+use regia_core::todo::Task;
+
+/// Builds a case-insensitive matcher. Plain queries are escaped so they match
+/// as substrings; `regex` mode hands the query to the regex engine as is.
+pub fn build_matcher(query: &str, regex: bool) -> error::Result<Regex> {
+    let pattern = if regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    match RegexBuilder::new(&pattern).case_insensitive(true).build() {
+        Ok(re) => Ok(re),
+        Err(err) => Err(RegiaError::Parse(format!("bad search regex: {}", err))),
+    }
+}
+
+/// Returns `text` with every match of `re` highlighted.
+pub fn highlight(re: &Regex, text: &str) -> String {
+    let mut out = String::new();
+    let mut last = 0;
+    for found in re.find_iter(text) {
+        out.push_str(&text[last..found.start()]);
+        out.push_str(&found.as_str().highlight().bold().to_string());
+        last = found.end();
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/// Returns `text` with every word `query` looks for highlighted.
+pub fn highlight_terms(query: &Query, text: &str) -> String {
+    let mut out = String::new();
+    let mut last = 0;
+    for (range, term) in fulltext::terms(text) {
+        if query.wants(&text[range.clone()], &term) {
+            out.push_str(&text[last..range.start]);
+            out.push_str(&text[range.clone()].highlight().bold().to_string());
+            last = range.end;
+        }
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+#[derive(Serialize)]
+struct Hit {
+    /// `task` or `note`
+    kind: &'static str,
+    id: Uuid,
+    content: String,
+    /// How well it matched, higher first; regex searches don't rank
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<f64>,
+}
+
+impl output::Record for Hit {
+    fn plain(&self) -> String {
+        output::fields(&[
+            self.kind.to_string(),
+            self.id.to_string(),
+            self.content.replace('\n', " "),
+        ])
+    }
+}
+
+fn print_group(name: &str, lines: &[String]) {
+    if lines.is_empty() {
+        return;
+    }
+    println!("{} ({})", name.bold(), format!("{}", lines.len()).header());
+    for line in lines {
+        println!("{}", line);
+    }
+}
+
+/// Tasks and notes found, best or newest first, with their scores when
+/// ranked.
+type Found<'a> = (Vec<(&'a Task, Option<f64>)>, Vec<(&'a Note, Option<f64>)>);
+
+fn regex_search<'a>(db: &'a Database, re: &Regex) -> Found<'a> {
+    let mut tasks: Vec<&Task> = db
+        .tasks
+        .get_tasks()
+        .iter()
+        .filter(|task| re.is_match(&task.content))
+        .collect();
+    tasks.sort_by(|left, right| {
+        right
+            .created
+            .cmp(&left.created)
+            .then_with(|| left.listing_order(right))
+    });
+    let mut notes: Vec<&Note> = db
+        .notes
+        .get_notes()
+        .iter()
+        .filter(|note| re.is_match(&note.content))
+        .collect();
+    notes.sort_by_key(|note| std::cmp::Reverse(note.created));
+    (
+        tasks.into_iter().map(|task| (task, None)).collect(),
+        notes.into_iter().map(|note| (note, None)).collect(),
+    )
+}
+
+fn indexed_search<'a>(db: &'a Database, index: &SearchIndex, query: &Query) -> Found<'a> {
+    let (mut tasks, mut notes) = (Vec::new(), Vec::new());
+    for found in index.search(query) {
+        let score = Some(found.score);
+        match found.kind {
+            Kind::Task => tasks.extend(db.tasks.get_task(&found.id).map(|task| (task, score))),
+            Kind::Note => notes.extend(db.notes.get_note(&found.id).map(|note| (note, score))),
+        }
+    }
+    (tasks, notes)
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let query = matches
+        .values_of("query")
+        .into_iter()
+        .flatten()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    let db = db::open(doc)?;
+
+    let mark: Box<dyn Fn(&str) -> String>;
+    let (tasks, notes) = if matches.is_present("regex") {
+        let re = build_matcher(&query, true)?;
+        let found = regex_search(&db, &re);
+        mark = Box::new(move |text| highlight(&re, text));
+        found
+    } else {
+        let parsed = Query::parse(&query)?;
+        let index = fulltext::open(db::db_path(doc), &db)?;
+        let found = indexed_search(&db, &index, &parsed);
+        mark = Box::new(move |text| highlight_terms(&parsed, text));
+        found
+    };
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        let mut records: Vec<Hit> = tasks
+            .iter()
+            .map(|(task, score)| Hit {
+                kind: "task",
+                id: task.id,
+                content: task.content.clone(),
+                score: *score,
+            })
+            .collect();
+        records.extend(notes.iter().map(|(note, score)| Hit {
+            kind: "note",
+            id: note.id,
+            content: note.content.clone(),
+            score: *score,
+        }));
+        return output::print(format, &records);
+    }
+
+    if tasks.is_empty() && notes.is_empty() {
+        println!("No matches for {}", query.bold());
+        return Ok(());
+    }
+
+    let task_lines: Vec<String> = tasks
+        .iter()
+        .map(|(task, _)| {
+            let bullet = if task.is_done() { "x" } else { "*" };
+            format!("{} {}", bullet, mark(&task.content))
+        })
+        .collect();
+    let note_lines: Vec<String> = notes
+        .iter()
+        .map(|(note, _)| format!("* {}", mark(&note.content)))
+        .collect();
+    print_group("Tasks", &task_lines);
+    print_group("Notes", &note_lines);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substring_is_case_insensitive_and_escaped() {
+        let re = build_matcher("a.B", false).unwrap();
+        assert!(re.is_match("xA.bx"));
+        assert!(!re.is_match("aXb"));
+    }
+
+    #[test]
+    fn regex_mode() {
+        let re = build_matcher("^fix (bug|typo)$", true).unwrap();
+        assert!(re.is_match("Fix Typo"));
+        assert!(!re.is_match("fix bugs"));
+        assert!(build_matcher("(", true).is_err());
+    }
+
+    #[test]
+    fn highlights_the_words_looked_for() {
+        let query = Query::parse("report plan*").unwrap();
+        let marked = highlight_terms(&query, "Reports on planning, not the plot");
+        let expected = format!(
+            "{} on {}, not the plot",
+            "Reports".yellow().bold(),
+            "planning".yellow().bold()
+        );
+        assert_eq!(marked, expected);
+    }
+}
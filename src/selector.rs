@@ -0,0 +1,193 @@
+//! Which tasks `task rm`, `task done` and `task edit` act on: those matching
+//! every search term given, narrowed by `--regex`, `--tag` and
+//! `--older-than`.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Duration, Utc};
+use clap::{Arg, ArgMatches};
+use regex::Regex;
+use uuid::Uuid;
+
+use crate::search;
+use regia_core::dates;
+use regia_core::error::{self, RegiaError};
+use regia_core::fuzzy;
+use regia_core::tags;
+use regia_core::todo::Task;
+
+/// The selector flags, for the commands that pick tasks to act on.
+pub fn args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("regex")
+            .long("regex")
+            .takes_value(true)
+            .value_name("PATTERN")
+            .help("Only tasks whose content matches this regex, ignoring case"),
+        Arg::with_name("tag")
+            .short("t")
+            .long("tag")
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true)
+            .value_name("TAG")
+            .help("Only tasks with this tag; repeat for more"),
+        Arg::with_name("older-than")
+            .long("older-than")
+            .takes_value(true)
+            .value_name("AGE")
+            .help("Only tasks created longer ago than this, e.g. 30d or 2w"),
+    ]
+}
+
+/// `others` and the selector flags, for the `search` of a command that
+/// selectors alone make enough.
+pub fn or(others: &[&'static str]) -> Vec<&'static str> {
+    let mut names = others.to_vec();
+    names.extend(["regex", "tag", "older-than"]);
+    names
+}
+
+#[derive(Debug, Default)]
+pub struct Selector {
+    /// The search terms, each of which a task must match
+    pub terms: Vec<String>,
+    pub regex: Option<Regex>,
+    pub tags: HashSet<String>,
+    pub older_than: Option<Duration>,
+}
+
+impl Selector {
+    /// A selector for the single search `text`.
+    pub fn search(text: &str) -> Self {
+        Self {
+            terms: vec![text.to_string()],
+            ..Self::default()
+        }
+    }
+
+    /// The selector the `search` values and selector flags of a command
+    /// make.
+    pub fn from_args(matches: &ArgMatches) -> error::Result<Self> {
+        let terms = matches
+            .values_of("search")
+            .into_iter()
+            .flatten()
+            .map(str::trim)
+            .filter(|term| !term.is_empty())
+            .map(String::from)
+            .collect();
+        let regex = match matches.value_of("regex") {
+            Some(pattern) => Some(search::build_matcher(pattern, true)?),
+            None => None,
+        };
+        let older_than = match matches.value_of("older-than") {
+            Some(age) => match dates::parse_duration(age) {
+                Some(age) if age > Duration::zero() => Some(age),
+                _ => return Err(RegiaError::Parse(format!("bad age: {}", age))),
+            },
+            None => None,
+        };
+        Ok(Self {
+            terms,
+            regex,
+            tags: tags::from_args(matches.values_of("tag").into_iter().flatten()),
+            older_than,
+        })
+    }
+
+    /// The search terms as one string, as a message can show them.
+    pub fn query(&self) -> String {
+        self.terms.join(" ")
+    }
+
+    /// Whether `task` passes the flags, leaving the search terms aside.
+    fn admits(&self, task: &Task, now: DateTime<Utc>) -> bool {
+        self.regex
+            .as_ref()
+            .is_none_or(|re| re.is_match(&task.content))
+            && tags::matches_all(&task.tags, &self.tags)
+            && self.older_than.is_none_or(|age| now - task.created > age)
+    }
+
+    /// The tasks of `tasks` this could mean, best guess first. A single task
+    /// containing every term verbatim is the one meant; otherwise every task
+    /// that each term fuzzily matches is a candidate. With no terms, every
+    /// task the flags let through is, in order.
+    pub fn candidates<'a>(
+        &self,
+        tasks: impl Iterator<Item = &'a Task>,
+        now: DateTime<Utc>,
+    ) -> Vec<Uuid> {
+        let pool: Vec<&Task> = tasks.filter(|task| self.admits(task, now)).collect();
+        if self.terms.is_empty() {
+            return pool.iter().map(|task| task.id).collect();
+        }
+
+        let lower: Vec<String> = self.terms.iter().map(|term| term.to_lowercase()).collect();
+        let verbatim: Vec<&&Task> = pool
+            .iter()
+            .filter(|task| {
+                let content = task.content.to_lowercase();
+                lower.iter().all(|term| content.contains(term.as_str()))
+            })
+            .collect();
+        if verbatim.len() == 1 {
+            return vec![verbatim[0].id];
+        }
+
+        let mut scored: Vec<(i64, Uuid)> = pool
+            .iter()
+            .filter_map(|task| {
+                self.terms
+                    .iter()
+                    .map(|term| fuzzy::score(term, &task.content))
+                    .sum::<Option<i64>>()
+                    .map(|points| (points, task.id))
+            })
+            .collect();
+        scored.sort_by_key(|(points, _)| std::cmp::Reverse(*points));
+        scored.into_iter().map(|(_, id)| id).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_term_and_flag_must_match() {
+        let now = Utc::now();
+        let mut old = Task::new(String::from("water the plants"), 0);
+        old.created = now - Duration::days(40);
+        let mut tagged = Task::new(String::from("water the lawn"), 0);
+        tagged.tags.insert(String::from("garden"));
+        let other = Task::new(String::from("pay the rent"), 0);
+        let tasks = [old.clone(), tagged.clone(), other.clone()];
+        let pick = |selector: &Selector| selector.candidates(tasks.iter(), now);
+
+        let terms = |terms: &[&str]| Selector {
+            terms: terms.iter().map(|term| term.to_string()).collect(),
+            ..Selector::default()
+        };
+        assert_eq!(pick(&terms(&["water", "lawn"])), [tagged.id]);
+        assert_eq!(pick(&terms(&["the"])).len(), 3);
+        assert!(pick(&terms(&["rent", "lawn"])).is_empty());
+
+        let selector = Selector {
+            regex: Some(search::build_matcher("^water", true).unwrap()),
+            ..Selector::default()
+        };
+        assert_eq!(pick(&selector), [old.id, tagged.id]);
+        let selector = Selector {
+            tags: tags::from_args(vec!["#garden"]),
+            ..Selector::default()
+        };
+        assert_eq!(pick(&selector), [tagged.id]);
+        let selector = Selector {
+            older_than: Some(Duration::days(30)),
+            ..terms(&["water"])
+        };
+        assert_eq!(pick(&selector), [old.id]);
+    }
+}
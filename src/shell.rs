@@ -0,0 +1,492 @@
+//! `regia shell`: many commands against one loaded database.
+//!
+//! The database stays in memory through `db::hold` and goes back to disk
+//! when the shell exits, and the lock is held all the while. Lines are
+//! parsed with the same clap tree as the command line, which also drives
+//! tab completion.
+
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+
+use clap::{App, ArgMatches, ErrorKind};
+use colored::*;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::{cursor, queue, terminal};
+
+use regia_core::conf::Config;
+use regia_core::db;
+use regia_core::error::{self, RegiaError};
+use regia_core::theme::Themed;
+
+/// How many lines the history file keeps.
+const HISTORY_LEN: usize = 1000;
+
+/// Commands that make no sense from inside the shell.
+const REFUSED: [&str; 4] = ["shell", "daemon", "pomo", "config"];
+
+/// Words the shell understands itself.
+const BUILTINS: [&str; 2] = ["exit", "help"];
+
+fn history_path(db_path: &Path) -> PathBuf {
+    db::sibling_path(db_path, ".history")
+}
+
+/// Splits a line into words the way a shell would: whitespace apart,
+/// except inside single or double quotes or after a backslash.
+pub fn split(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some('"'), '\\') | (None, '\\') => match chars.next() {
+                Some(escaped) => word.get_or_insert_with(String::new).push(escaped),
+                None => return Err(String::from("line ends in a backslash")),
+            },
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(String::from("unclosed quote"));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// The names completion offers, read off the clap tree.
+pub struct Completions {
+    name: String,
+    subcommands: Vec<Completions>,
+    /// Long flags and options, with their dashes
+    flags: Vec<String>,
+}
+
+impl Completions {
+    pub fn of(app: &App) -> Completions {
+        // clap 2 only exposes its tree through these undocumented fields
+        let flags = app.p.flags.iter().filter_map(|flag| flag.s.long);
+        let options = app.p.opts.iter().filter_map(|opt| opt.s.long);
+        Completions {
+            name: app.get_name().to_string(),
+            subcommands: app.p.subcommands.iter().map(Completions::of).collect(),
+            flags: flags
+                .chain(options)
+                .map(|long| format!("--{}", long))
+                .collect(),
+        }
+    }
+
+    /// Where the word being typed at the end of `line` starts, and the
+    /// words it could be completed to.
+    pub fn complete(&self, line: &str) -> (usize, Vec<String>) {
+        let start = line.rfind(char::is_whitespace).map_or(0, |space| space + 1);
+        let partial = &line[start..];
+        let mut command = self;
+        for word in line[..start].split_whitespace() {
+            if let Some(sub) = command.subcommands.iter().find(|sub| sub.name == word) {
+                command = sub;
+            }
+        }
+
+        let mut found: Vec<String> = if partial.starts_with('-') {
+            // The root's flags are global ones
+            command
+                .flags
+                .iter()
+                .chain(&self.flags)
+                .filter(|flag| flag.starts_with(partial))
+                .cloned()
+                .collect()
+        } else {
+            let builtins = BUILTINS
+                .iter()
+                .filter(|_| start == 0)
+                .map(|name| name.to_string());
+            command
+                .subcommands
+                .iter()
+                .map(|sub| sub.name.clone())
+                .filter(|name| name != "help" && !(start == 0 && REFUSED.contains(&name.as_str())))
+                .chain(builtins)
+                .filter(|name| name.starts_with(partial))
+                .collect()
+        };
+        found.sort();
+        found.dedup();
+        (start, found)
+    }
+}
+
+fn common_prefix(words: &[String]) -> String {
+    let mut prefix: Vec<char> = words[0].chars().collect();
+    for word in &words[1..] {
+        let same = prefix
+            .iter()
+            .zip(word.chars())
+            .take_while(|(left, right)| **left == *right)
+            .count();
+        prefix.truncate(same);
+    }
+    prefix.into_iter().collect()
+}
+
+/// Leaves raw mode however reading a line ends.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Reads lines with editing, history and completion from a terminal, or
+/// plainly from anything else.
+struct Editor {
+    history: Vec<String>,
+    completions: Completions,
+    interactive: bool,
+}
+
+impl Editor {
+    fn redraw(
+        &self,
+        out: &mut impl Write,
+        prompt: &str,
+        line: &[char],
+        at: usize,
+    ) -> io::Result<()> {
+        let text: String = line.iter().collect();
+        queue!(
+            out,
+            cursor::MoveToColumn(0),
+            terminal::Clear(terminal::ClearType::CurrentLine)
+        )?;
+        write!(out, "{}{}", prompt.header().bold(), text)?;
+        let column = prompt.chars().count() + at;
+        queue!(out, cursor::MoveToColumn(column as u16))?;
+        out.flush()
+    }
+
+    /// Completes the word before the cursor, or lists the choices when
+    /// there is more than one and nothing more in common.
+    fn tab(&self, out: &mut impl Write, line: &mut Vec<char>, at: &mut usize) -> io::Result<()> {
+        let before: String = line[..*at].iter().collect();
+        let (start, found) = self.completions.complete(&before);
+        if found.is_empty() {
+            return Ok(());
+        }
+        let typed = before[start..].chars().count();
+        let prefix = common_prefix(&found);
+        let mut insert: Vec<char> = prefix.chars().skip(typed).collect();
+        if found.len() == 1 {
+            insert.push(' ');
+        } else if insert.is_empty() {
+            write!(out, "\r\n{}\r\n", found.join("  "))?;
+        }
+        for c in insert {
+            line.insert(*at, c);
+            *at += 1;
+        }
+        Ok(())
+    }
+
+    /// The next line, or `None` at the end of input or on Ctrl-D.
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        if !self.interactive {
+            let mut line = String::new();
+            return Ok(match io::stdin().lock().read_line(&mut line)? {
+                0 => None,
+                _ => Some(line.trim_end_matches(&['\r', '\n'][..]).to_string()),
+            });
+        }
+
+        let mut out = io::stdout();
+        let _raw = RawMode::enable()?;
+        let mut line: Vec<char> = Vec::new();
+        let mut at = 0;
+        // Where in the history Up and Down have got to
+        let mut recalled = self.history.len();
+        self.redraw(&mut out, prompt, &line, at)?;
+        loop {
+            let key = match event::read()? {
+                Event::Key(
+                    key @ KeyEvent {
+                        kind: KeyEventKind::Press,
+                        ..
+                    },
+                ) => key,
+                _ => continue,
+            };
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Enter => {
+                    write!(out, "\r\n")?;
+                    return Ok(Some(line.into_iter().collect()));
+                }
+                KeyCode::Char('d') if ctrl && line.is_empty() => {
+                    write!(out, "\r\n")?;
+                    return Ok(None);
+                }
+                KeyCode::Char('c') if ctrl => {
+                    write!(out, "^C\r\n")?;
+                    line.clear();
+                    at = 0;
+                }
+                KeyCode::Char('a') if ctrl => at = 0,
+                KeyCode::Char('e') if ctrl => at = line.len(),
+                KeyCode::Char('u') if ctrl => {
+                    line.drain(..at);
+                    at = 0;
+                }
+                KeyCode::Char('w') if ctrl => {
+                    let mut start = at;
+                    while start > 0 && line[start - 1] == ' ' {
+                        start -= 1;
+                    }
+                    while start > 0 && line[start - 1] != ' ' {
+                        start -= 1;
+                    }
+                    line.drain(start..at);
+                    at = start;
+                }
+                KeyCode::Char(c) if !ctrl => {
+                    line.insert(at, c);
+                    at += 1;
+                }
+                KeyCode::Backspace if at > 0 => {
+                    at -= 1;
+                    line.remove(at);
+                }
+                KeyCode::Delete if at < line.len() => {
+                    line.remove(at);
+                }
+                KeyCode::Left if at > 0 => at -= 1,
+                KeyCode::Right if at < line.len() => at += 1,
+                KeyCode::Home => at = 0,
+                KeyCode::End => at = line.len(),
+                KeyCode::Up if recalled > 0 => {
+                    recalled -= 1;
+                    line = self.history[recalled].chars().collect();
+                    at = line.len();
+                }
+                KeyCode::Down if recalled < self.history.len() => {
+                    recalled += 1;
+                    line = match self.history.get(recalled) {
+                        Some(entry) => entry.chars().collect(),
+                        None => Vec::new(),
+                    };
+                    at = line.len();
+                }
+                KeyCode::Tab => self.tab(&mut out, &mut line, &mut at)?,
+                _ => {}
+            }
+            self.redraw(&mut out, prompt, &line, at)?;
+        }
+    }
+
+    fn remember(&mut self, line: &str) {
+        if self.history.last().map(String::as_str) != Some(line) {
+            self.history.push(line.to_string());
+        }
+    }
+}
+
+fn read_history(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .map(|text| text.lines().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn write_history(path: &Path, history: &[String]) -> error::Result<()> {
+    let kept = &history[history.len().saturating_sub(HISTORY_LEN)..];
+    let mut text = kept.join("\n");
+    text.push('\n');
+    Ok(fs::write(path, text)?)
+}
+
+/// Runs one line of the shell, as `regia` would run it from the command
+/// line.
+fn run_line(words: Vec<String>, doc: &Config) -> error::Result<()> {
+    let args = iter::once(String::from("regia")).chain(words);
+    let matches = match crate::app().get_matches_from_safe(args) {
+        Ok(matches) => matches,
+        // Help and version are errors to clap too
+        Err(err)
+            if matches!(
+                err.kind,
+                ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed
+            ) =>
+        {
+            println!("{}", err.message);
+            return Ok(());
+        }
+        Err(err) => {
+            eprintln!("{}", err.message);
+            return Ok(());
+        }
+    };
+    refuse(&matches)?;
+    crate::prompt::set_mode(crate::prompt::Mode::from_args(&matches));
+    crate::dispatch(&matches, &crate::for_command(doc, &matches))
+}
+
+fn refuse(matches: &ArgMatches) -> error::Result<()> {
+    if let Some((name, _)) = crate::plugins::requested(matches) {
+        return Err(RegiaError::Parse(format!(
+            "run plugin {} outside the shell",
+            name
+        )));
+    }
+    if let Some(command) = matches
+        .subcommand_name()
+        .filter(|name| REFUSED.contains(name))
+    {
+        return Err(RegiaError::Parse(format!(
+            "run {} outside the shell",
+            command
+        )));
+    }
+    if matches.value_of("config").is_some() {
+        return Err(RegiaError::Parse(String::from(
+            "the shell keeps the config it started with",
+        )));
+    }
+    if matches.value_of("db").is_some() {
+        return Err(RegiaError::Parse(String::from(
+            "the shell keeps the database it started with, pass --db to regia shell",
+        )));
+    }
+    let reads_stdin = matches
+        .subcommand()
+        .1
+        .and_then(|command| command.subcommand().1)
+        .is_some_and(|action| action.is_present("stdin"));
+    if reads_stdin {
+        return Err(RegiaError::Parse(String::from(
+            "--stdin would read the shell's own input",
+        )));
+    }
+    Ok(())
+}
+
+pub fn handle_it(_matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let history_path = history_path(db_path);
+    db::hold(db_path)?;
+
+    let mut editor = Editor {
+        history: read_history(&history_path),
+        completions: Completions::of(&crate::app()),
+        interactive: io::stdin().is_terminal() && io::stdout().is_terminal(),
+    };
+    if editor.interactive {
+        println!(
+            "{}",
+            "Type a command as you would after regia, or exit".dimmed()
+        );
+    }
+    let result = loop {
+        let line = match editor.read_line("regia> ") {
+            Ok(Some(line)) => line,
+            Ok(None) => break Ok(()),
+            Err(err) => break Err(RegiaError::from(err)),
+        };
+        let words = match split(&line) {
+            Ok(words) if words.is_empty() => continue,
+            Ok(words) => words,
+            Err(err) => {
+                eprintln!("{} {}", "error:".error().bold(), err);
+                continue;
+            }
+        };
+        editor.remember(line.trim());
+        let result = match words[0].as_str() {
+            "exit" | "quit" => break Ok(()),
+            "help" => run_line(vec![String::from("help")], doc),
+            _ => run_line(words, doc),
+        };
+        // Saved line by line, so that a closed terminal or a panic loses
+        // at most the line it happened on
+        match result.and_then(|()| db::flush()) {
+            Err(RegiaError::CheckFailed) => {}
+            Err(err) => eprintln!("{} {}", "error:".error().bold(), err),
+            Ok(()) => {}
+        }
+    };
+
+    db::release()?;
+    if editor.interactive {
+        if let Err(err) = write_history(&history_path, &editor.history) {
+            eprintln!(
+                "{} can't save the shell history to {}: {}",
+                "warning:".warning(),
+                history_path.display(),
+                err
+            );
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, SubCommand};
+
+    #[test]
+    fn splits_like_a_shell() {
+        assert_eq!(
+            split(r#"task add "buy milk" -t 'home life' a\ b"#).unwrap(),
+            ["task", "add", "buy milk", "-t", "home life", "a b"]
+        );
+        assert_eq!(split(r#"note add "" x"#).unwrap(), ["note", "add", "", "x"]);
+        assert!(split("task add \"open").is_err());
+    }
+
+    #[test]
+    fn completes_from_the_command_tree() {
+        let app = App::new("regia")
+            .arg(Arg::with_name("output").long("output").takes_value(true))
+            .subcommand(
+                SubCommand::with_name("task")
+                    .subcommand(
+                        SubCommand::with_name("add")
+                            .arg(Arg::with_name("due").long("due").takes_value(true)),
+                    )
+                    .subcommand(SubCommand::with_name("archive")),
+            )
+            .subcommand(SubCommand::with_name("tag"))
+            .subcommand(SubCommand::with_name("shell"));
+        let completions = Completions::of(&app);
+
+        assert_eq!(
+            completions.complete("ta"),
+            (0, vec![String::from("tag"), String::from("task")])
+        );
+        assert_eq!(completions.complete("e"), (0, vec![String::from("exit")]));
+        assert_eq!(completions.complete("sh").1, Vec::<String>::new());
+        assert_eq!(completions.complete("task a").1, ["add", "archive"]);
+        assert_eq!(completions.complete("task add --").1, ["--due", "--output"]);
+        assert_eq!(
+            common_prefix(&[String::from("add"), String::from("archive")]),
+            "a"
+        );
+    }
+}
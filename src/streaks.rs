@@ -0,0 +1,96 @@
+use chrono::Utc;
+use clap::ArgMatches;
+use colored::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::output::{self, Output};
+use regia_core::conf::Config;
+use regia_core::dates;
+use regia_core::db;
+use regia_core::error;
+use regia_core::history;
+use regia_core::schedule::{self, Streak};
+use regia_core::theme::Themed;
+use regia_core::todo::Task;
+
+#[derive(Serialize)]
+struct StreakRecord {
+    series: Uuid,
+    content: String,
+    repeat: String,
+    #[serde(flatten)]
+    streak: Streak,
+}
+
+impl output::Record for StreakRecord {
+    fn plain(&self) -> String {
+        output::fields(&[
+            self.series.to_string(),
+            self.streak.current.to_string(),
+            self.streak.longest.to_string(),
+            self.repeat.clone(),
+            self.content.clone(),
+        ])
+    }
+}
+
+/// `regia streaks`: the streak of every repeated task still repeating,
+/// longest running first.
+pub fn handle_it(_matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let mut db = db::open(doc)?;
+    // As `task ls` would see them, without saving anything
+    schedule::tick(&mut db.tasks, Utc::now(), &dates::Local);
+    let streaks = schedule::streaks(&history::of_active(&db));
+
+    let open: Vec<&Task> = db
+        .tasks
+        .get_tasks()
+        .iter()
+        .filter(|task| !task.is_done() && task.successor.is_none())
+        .filter(|task| {
+            task.repeat
+                .as_ref()
+                .is_some_and(|repeat| repeat.has_streaks())
+        })
+        .collect();
+    let mut records: Vec<StreakRecord> = open
+        .iter()
+        .map(|task| StreakRecord {
+            series: task.series_id(),
+            content: task.content.clone(),
+            repeat: task.repeat.as_ref().unwrap().to_string(),
+            streak: streaks.get(&task.series_id()).copied().unwrap_or_default(),
+        })
+        .collect();
+    records.sort_by(|left, right| {
+        (right.streak.current, right.streak.longest)
+            .cmp(&(left.streak.current, left.streak.longest))
+            .then(left.content.cmp(&right.content))
+    });
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        return output::print(format, &records);
+    }
+    if records.is_empty() {
+        println!("No daily or weekly tasks to keep a streak of");
+        return Ok(());
+    }
+    for record in records {
+        let current = format!("🔥 {:<3}", record.streak.current);
+        let current = if record.streak.current > 0 {
+            current.warning().to_string()
+        } else {
+            current.dimmed().to_string()
+        };
+        println!(
+            "{} {} {} {}",
+            current,
+            format!("best {:<3}", record.streak.longest).dimmed(),
+            record.content,
+            format!("↻ {}", record.repeat).dimmed()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,444 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use clap::ArgMatches;
+use regia_core::conf::Config;
+use regia_core::db::{self, Database, Project};
+use regia_core::error::{self, RegiaError};
+use regia_core::index::{canonical, same};
+use regia_core::note::{Note, Notes};
+use regia_core::theme::Themed;
+use regia_core::todo::{Task, Tasks};
+use regia_core::trash::{Trash, Trashed};
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::caldav;
+use crate::journal;
+
+/// The database is kept in the sync repository as pretty JSON, one field
+/// per line, so the history stays readable with plain git tools.
+const SNAPSHOT: &str = "regia.json";
+
+/// The working copy from `sync.repo`, or `<db>.sync` beside the database.
+fn repo_path(doc: &Config) -> PathBuf {
+    match &doc.sync.repo {
+        Some(repo) => PathBuf::from(repo),
+        None => db::sibling_path(db::db_path(doc), ".sync"),
+    }
+}
+
+fn branch(doc: &Config) -> &str {
+    doc.sync.branch.as_deref().unwrap_or("main")
+}
+
+fn run_git(repo: &Path, args: &[&str]) -> error::Result<std::process::Output> {
+    Ok(Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()?)
+}
+
+fn git(repo: &Path, args: &[&str]) -> error::Result<String> {
+    let output = run_git(repo, args)?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(RegiaError::storage(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Runs a git query whose answer is its exit status.
+fn git_check(repo: &Path, args: &[&str]) -> error::Result<bool> {
+    Ok(run_git(repo, args)?.status.success())
+}
+
+fn to_canonical<T: Serialize>(item: &T) -> error::Result<Value> {
+    match serde_json::to_value(item) {
+        Ok(value) => Ok(canonical(value)),
+        Err(_) => Err(RegiaError::storage("Serialization failed")),
+    }
+}
+
+/// Picks one side of a three-way merge of a single item. A side that left
+/// the item as it was in `base` takes the other side's version. When both
+/// changed it differently ours wins, and an edit always beats a removal.
+fn resolve<T: Serialize + Clone>(
+    base: Option<&T>,
+    ours: Option<&T>,
+    theirs: Option<&T>,
+    conflicts: &mut usize,
+) -> Option<T> {
+    if same(&ours, &theirs) || same(&base, &theirs) {
+        ours.cloned()
+    } else if same(&base, &ours) {
+        theirs.cloned()
+    } else {
+        *conflicts += 1;
+        ours.or(theirs).cloned()
+    }
+}
+
+fn merge_tasks(base: &Tasks, ours: &Tasks, theirs: &Tasks, conflicts: &mut usize) -> Tasks {
+    let ids: BTreeSet<Uuid> = [base, ours, theirs]
+        .iter()
+        .flat_map(|tasks| tasks.get_tasks().iter().map(|task| task.id))
+        .collect();
+
+    let mut merged = ours.clone();
+    for id in ids {
+        let picked: Option<Task> = resolve(
+            base.get_task(&id),
+            ours.get_task(&id),
+            theirs.get_task(&id),
+            conflicts,
+        );
+        if !same(&picked.as_ref(), &ours.get_task(&id)) {
+            merged.remove(id);
+            if let Some(task) = picked {
+                merged.add(task);
+            }
+        }
+    }
+    merged
+}
+
+fn merge_notes(base: &Notes, ours: &Notes, theirs: &Notes, conflicts: &mut usize) -> Notes {
+    let ids: BTreeSet<Uuid> = [base, ours, theirs]
+        .iter()
+        .flat_map(|notes| notes.get_notes().iter().map(|note| note.id))
+        .collect();
+
+    let mut merged = ours.clone();
+    for id in ids {
+        let picked: Option<Note> = resolve(
+            base.get_note(&id),
+            ours.get_note(&id),
+            theirs.get_note(&id),
+            conflicts,
+        );
+        if !same(&picked.as_ref(), &ours.get_note(&id)) {
+            merged.remove(id);
+            if let Some(note) = picked {
+                merged.add(note);
+            }
+        }
+    }
+    merged
+}
+
+fn merge_trashed<T: Serialize + Clone>(
+    base: &[Trashed<T>],
+    ours: &[Trashed<T>],
+    theirs: &[Trashed<T>],
+    id: fn(&T) -> Uuid,
+    conflicts: &mut usize,
+) -> Vec<Trashed<T>> {
+    let find = |entries: &'_ [Trashed<T>], wanted: Uuid| {
+        entries
+            .iter()
+            .find(|entry| id(&entry.item) == wanted)
+            .cloned()
+    };
+    let ids: BTreeSet<Uuid> = [base, ours, theirs]
+        .iter()
+        .flat_map(|entries| entries.iter().map(|entry| id(&entry.item)))
+        .collect();
+
+    let mut merged: Vec<Trashed<T>> = ids
+        .into_iter()
+        .filter_map(|wanted| {
+            resolve(
+                find(base, wanted).as_ref(),
+                find(ours, wanted).as_ref(),
+                find(theirs, wanted).as_ref(),
+                conflicts,
+            )
+        })
+        .collect();
+    merged.sort_by_key(|entry| entry.deleted);
+    merged
+}
+
+fn merge_trash(base: &Trash, ours: &Trash, theirs: &Trash, conflicts: &mut usize) -> Trash {
+    Trash {
+        tasks: merge_trashed(
+            &base.tasks,
+            &ours.tasks,
+            &theirs.tasks,
+            |task| task.id,
+            conflicts,
+        ),
+        notes: merge_trashed(
+            &base.notes,
+            &ours.notes,
+            &theirs.notes,
+            |note| note.id,
+            conflicts,
+        ),
+    }
+}
+
+fn merge_project(
+    base: &Project,
+    ours: &Project,
+    theirs: &Project,
+    conflicts: &mut usize,
+) -> Project {
+    Project {
+        tasks: merge_tasks(&base.tasks, &ours.tasks, &theirs.tasks, conflicts),
+        notes: merge_notes(&base.notes, &ours.notes, &theirs.notes, conflicts),
+        archive: merge_tasks(&base.archive, &ours.archive, &theirs.archive, conflicts),
+        trash: merge_trash(&base.trash, &ours.trash, &theirs.trash, conflicts),
+    }
+}
+
+/// Merges two databases that both descend from `base`, item by item on
+/// their UUIDs. Returns the result and how many items both sides changed.
+pub fn merge(base: &Database, ours: &Database, theirs: &Database) -> (Database, usize) {
+    let mut conflicts = 0;
+    let mut merged = ours.clone();
+
+    let root = |db: &Database| Project {
+        tasks: db.tasks.clone(),
+        notes: db.notes.clone(),
+        archive: db.archive.clone(),
+        trash: db.trash.clone(),
+    };
+    let merged_root = merge_project(&root(base), &root(ours), &root(theirs), &mut conflicts);
+    merged.tasks = merged_root.tasks;
+    merged.notes = merged_root.notes;
+    merged.archive = merged_root.archive;
+    merged.trash = merged_root.trash;
+    merged.reviewed = ours.reviewed.max(theirs.reviewed);
+
+    let names: BTreeSet<&String> = ours.projects.keys().chain(theirs.projects.keys()).collect();
+    for name in names {
+        let empty = Project::named(name);
+        let project = merge_project(
+            base.projects.get(name).unwrap_or(&empty),
+            ours.projects.get(name).unwrap_or(&empty),
+            theirs.projects.get(name).unwrap_or(&empty),
+            &mut conflicts,
+        );
+        // A project removed on one side stays removed once nothing in it
+        // was changed on the other
+        let removed = !ours.projects.contains_key(name) || !theirs.projects.contains_key(name);
+        let emptied = project.tasks.get_tasks().is_empty()
+            && project.notes.get_notes().is_empty()
+            && project.archive.get_tasks().is_empty()
+            && project.trash.is_empty();
+        if removed && emptied && base.projects.contains_key(name) {
+            merged.projects.remove(name);
+        } else {
+            merged.projects.insert(name.clone(), project);
+        }
+    }
+
+    (merged, conflicts)
+}
+
+fn read_snapshot(repo: &Path, rev: &str) -> error::Result<Database> {
+    let json = git(repo, &["show", &format!("{}:{}", rev, SNAPSHOT)])?;
+    Database::deserialize_json(&json)
+}
+
+fn write_snapshot(repo: &Path, db: &Database) -> error::Result<()> {
+    let value = to_canonical(db)?;
+    let mut json = match serde_json::to_string_pretty(&value) {
+        Ok(json) => json,
+        Err(_) => return Err(RegiaError::storage("Serialization failed")),
+    };
+    json.push('\n');
+    Ok(fs::write(repo.join(SNAPSHOT), json)?)
+}
+
+/// Writes `db` into the repository and commits it, unless nothing changed.
+fn commit_snapshot(repo: &Path, db: &Database, message: &str) -> error::Result<bool> {
+    write_snapshot(repo, db)?;
+    git(repo, &["add", SNAPSHOT])?;
+    if git(repo, &["status", "--porcelain", "--", SNAPSHOT])?.is_empty() {
+        return Ok(false);
+    }
+    git(repo, &["commit", "-q", "-m", message])?;
+    Ok(true)
+}
+
+fn ensure_repo(repo: &Path, doc: &Config) -> error::Result<()> {
+    if !repo.join(".git").exists() {
+        fs::create_dir_all(repo)?;
+        git(repo, &["init", "-q"])?;
+        let head = format!("refs/heads/{}", branch(doc));
+        git(repo, &["symbolic-ref", "HEAD", &head])?;
+    }
+    if let Some(remote) = doc.sync.remote.as_deref() {
+        match git(repo, &["remote", "get-url", "origin"]) {
+            Ok(url) if url == remote => {}
+            Ok(_) => {
+                git(repo, &["remote", "set-url", "origin", remote])?;
+            }
+            Err(_) => {
+                git(repo, &["remote", "add", "origin", remote])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Commits the database after a command changed it, once `regia sync` has
+/// set up the repository. Set `sync.auto: false` to only commit on sync.
+/// Failures are reported without failing the command that ran.
+pub fn autocommit(doc: &Config, command: &str) {
+    let repo = repo_path(doc);
+    if !repo.join(".git").exists() || doc.sync.auto == Some(false) {
+        return;
+    }
+    let result = Database::load(db::db_path(doc))
+        .and_then(|db| commit_snapshot(&repo, &db, &format!("regia {}", command)));
+    if let Err(err) = result {
+        eprintln!("{} sync commit failed: {}", "warning:".warning(), err);
+    }
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    if let Some(matches) = matches.subcommand_matches("caldav") {
+        return caldav::handle_it(matches, doc);
+    }
+    let db_path = db::db_path(doc);
+    let repo = repo_path(doc);
+    ensure_repo(&repo, doc)?;
+
+    let ours = Database::load(db_path)?;
+    commit_snapshot(&repo, &ours, "regia sync")?;
+
+    if doc.sync.remote.is_none() {
+        println!(
+            "Committed to {}; set sync.remote to push and pull",
+            repo.display()
+        );
+        return Ok(());
+    }
+
+    let branch = branch(doc);
+    let remote_head = format!("refs/heads/{}", branch);
+    let on_remote = !git(&repo, &["ls-remote", "--heads", "origin", &remote_head])?.is_empty();
+    if on_remote {
+        git(&repo, &["fetch", "-q", "origin", branch])?;
+        if git_check(
+            &repo,
+            &["merge-base", "--is-ancestor", "FETCH_HEAD", "HEAD"],
+        )? {
+            println!("Already up to date");
+        } else if git_check(
+            &repo,
+            &["merge-base", "--is-ancestor", "HEAD", "FETCH_HEAD"],
+        )? {
+            git(&repo, &["merge", "-q", "--ff-only", "FETCH_HEAD"])?;
+            let theirs = read_snapshot(&repo, "HEAD")?;
+            journal::record_as_is(db_path, "sync", &ours, &theirs)?;
+            theirs.to_disk(db_path)?;
+            println!("{} remote changes", "Pulled".header());
+        } else {
+            let base = match git(&repo, &["merge-base", "HEAD", "FETCH_HEAD"]) {
+                Ok(base) => read_snapshot(&repo, &base)?,
+                // Two databases that started syncing separately
+                Err(_) => Database::default(),
+            };
+            let theirs = read_snapshot(&repo, "FETCH_HEAD")?;
+            let (merged, conflicts) = merge(&base, &ours, &theirs);
+
+            git(
+                &repo,
+                &[
+                    "merge",
+                    "-q",
+                    "--no-commit",
+                    "--no-ff",
+                    "--allow-unrelated-histories",
+                    "-s",
+                    "ours",
+                    "FETCH_HEAD",
+                ],
+            )?;
+            write_snapshot(&repo, &merged)?;
+            git(&repo, &["add", SNAPSHOT])?;
+            git(&repo, &["commit", "-q", "-m", "regia sync: merge"])?;
+            journal::record_as_is(db_path, "sync", &ours, &merged)?;
+            merged.to_disk(db_path)?;
+
+            println!("{} remote changes", "Merged".header());
+            if conflicts > 0 {
+                println!(
+                    "{} item{} changed on both sides, kept the local version",
+                    format!("{}", conflicts).warning(),
+                    if conflicts == 1 { "" } else { "s" }
+                );
+            }
+        }
+    }
+
+    git(
+        &repo,
+        &["push", "-q", "origin", &format!("HEAD:{}", remote_head)],
+    )?;
+    println!("{} to {}", "Pushed".header(), branch);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn three_way_merge_by_id() {
+        let mut base = Database::default();
+        let kept = Task::new(String::from("kept"), 0);
+        let edited = Task::new(String::from("edited"), 0);
+        let removed = Task::new(String::from("removed"), 0);
+        let contested = Task::new(String::from("contested"), 0);
+        for task in [&kept, &edited, &removed, &contested] {
+            base.tasks.add(task.clone());
+        }
+
+        let mut ours = base.clone();
+        ours.tasks.get_task_mut(&edited.id).unwrap().priority = 5.into();
+        ours.tasks.get_task_mut(&contested.id).unwrap().content = String::from("ours");
+        ours.notes.add(Note::new("our note"));
+
+        let mut theirs = base.clone();
+        theirs.tasks.remove(removed.id);
+        theirs.tasks.get_task_mut(&contested.id).unwrap().content = String::from("theirs");
+        let added = Task::new(String::from("added"), 0);
+        theirs.tasks.add(added.clone());
+        theirs
+            .projects
+            .insert(String::from("work"), Project::named("work"));
+
+        let (merged, conflicts) = merge(&base, &ours, &theirs);
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            merged.tasks.get_task(&edited.id).unwrap().priority.weight(),
+            5
+        );
+        assert!(merged.tasks.get_task(&removed.id).is_none());
+        assert!(merged.tasks.get_task(&added.id).is_some());
+        assert_eq!(
+            merged.tasks.get_task(&contested.id).unwrap().content,
+            "ours"
+        );
+        assert_eq!(merged.notes.get_notes().len(), 1);
+        assert!(merged.projects.contains_key("work"));
+
+        // Removing an untouched project on one side removes it
+        let (merged, _) = merge(&theirs, &theirs, &base);
+        assert!(!merged.projects.contains_key("work"));
+    }
+}
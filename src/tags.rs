@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use clap::ArgMatches;
+use serde::Serialize;
+
+use crate::output::{self, Output};
+
+use regia_core::conf::Config;
+use regia_core::db;
+use regia_core::error;
+use regia_core::theme::Themed;
+
+#[derive(Serialize)]
+struct TagRecord {
+    tag: String,
+    tasks: usize,
+    notes: usize,
+}
+
+impl output::Record for TagRecord {
+    fn plain(&self) -> String {
+        output::fields(&[
+            self.tag.clone(),
+            self.tasks.to_string(),
+            self.notes.to_string(),
+        ])
+    }
+}
+
+/// How many tasks and notes carry each tag, most used first.
+fn usage(db: &db::Database) -> Vec<TagRecord> {
+    let mut counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for task in db.tasks.get_tasks() {
+        for tag in task.tags.iter() {
+            counts.entry(tag).or_default().0 += 1;
+        }
+    }
+    for note in db.notes.get_notes() {
+        for tag in note.tags.iter() {
+            counts.entry(tag).or_default().1 += 1;
+        }
+    }
+    let mut records: Vec<TagRecord> = counts
+        .into_iter()
+        .map(|(tag, (tasks, notes))| TagRecord {
+            tag: tag.to_string(),
+            tasks,
+            notes,
+        })
+        .collect();
+    // The map already sorted them by name, which the stable sort keeps for ties
+    records.sort_by_key(|record| std::cmp::Reverse(record.tasks + record.notes));
+    records
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+fn handle_tag_list(doc: &Config) -> error::Result<()> {
+    let db = db::open(doc)?;
+    let records = usage(&db);
+
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        return output::print(format, &records);
+    }
+
+    if records.is_empty() {
+        println!("No tags");
+    }
+    let width = records
+        .iter()
+        .map(|record| record.tag.chars().count())
+        .max()
+        .unwrap_or(0);
+    for record in records.iter() {
+        println!(
+            "{}  {}, {}",
+            format!("#{:<width$}", record.tag, width = width).tag(),
+            plural(record.tasks, "task"),
+            plural(record.notes, "note"),
+        );
+    }
+    Ok(())
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    if matches.subcommand_matches("ls").is_some() {
+        handle_tag_list(doc)
+    } else {
+        unreachable!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use regia_core::note::Note;
+    use regia_core::todo::Task;
+
+    #[test]
+    fn counts_tasks_and_notes_per_tag() {
+        let mut db = db::Database::default();
+        for (content, tags) in [("a", vec!["work"]), ("b", vec!["work", "home"])] {
+            let mut task = Task::new(String::from(content), 0);
+            task.tags = tags.into_iter().map(String::from).collect();
+            db.tasks.add(task);
+        }
+        let mut note = Note::new("ideas for #home and #garden");
+        note.tags = regia_core::tags::extract(&note.content);
+        db.notes.add(note);
+
+        let counts: Vec<(String, usize, usize)> = usage(&db)
+            .into_iter()
+            .map(|record| (record.tag, record.tasks, record.notes))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                (String::from("home"), 1, 1),
+                (String::from("work"), 2, 0),
+                (String::from("garden"), 0, 1),
+            ]
+        );
+    }
+}
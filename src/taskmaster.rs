@@ -1,191 +1,1728 @@
-use std::io::{self, BufRead, ErrorKind as IOErrorKind};
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead};
+use std::sync::OnceLock;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Local, Utc};
 use clap::ArgMatches;
 use colored::*;
+use serde::Serialize;
 use uuid::Uuid;
 
-use crate::conf::Config;
-use crate::db;
-use crate::todo;
+use crate::archive;
+use crate::attach;
+use crate::batch;
+use crate::github;
+use crate::graph;
+use crate::journal;
+use crate::output::{self, Output};
+use crate::prompt;
+use crate::selector::Selector;
+use crate::trash;
+use regia_core::conf::{self, Config};
+use regia_core::contexts;
+use regia_core::dates;
+use regia_core::db;
+use regia_core::error::{self, RegiaError};
+use regia_core::history;
+use regia_core::ident::{self, Unresolved};
+use regia_core::note;
+use regia_core::priority::Priority;
+use regia_core::query;
+use regia_core::schedule;
+use regia_core::tags;
+use regia_core::theme::Themed;
+use regia_core::todo::{self, Paginate};
+use regia_core::trash::Trash;
+use regia_core::view::View;
 
+pub fn parse_priority(priority_str: &str) -> error::Result<Priority> {
+    Priority::parse(priority_str).map_err(RegiaError::Parse)
+}
+
+pub fn parse_repeat(repeat_str: &str) -> error::Result<todo::RepeatType> {
+    match todo::RepeatType::parse(repeat_str) {
+        Some(repeat) => Ok(repeat),
+        None => Err(RegiaError::Parse(format!(
+            "bad repeats string: {}",
+            repeat_str
+        ))),
+    }
+}
+
+pub fn parse_due(due_date: &str) -> error::Result<DateTime<Utc>> {
+    match dates::parse(due_date) {
+        Some(dt) => Ok(dt),
+        None => Err(RegiaError::Parse(format!(
+            "bad datetime string: {}",
+            due_date
+        ))),
+    }
+}
+
+fn parse_remind(remind_str: &str) -> error::Result<i64> {
+    match dates::parse_duration(remind_str) {
+        Some(offset) => Ok(offset.num_seconds()),
+        None => Err(RegiaError::Parse(format!(
+            "bad reminder offset: {}",
+            remind_str
+        ))),
+    }
+}
+
+pub fn parse_estimate(estimate_str: &str) -> error::Result<i64> {
+    match dates::parse_duration(estimate_str) {
+        Some(estimate) if estimate > Duration::zero() => Ok(estimate.num_seconds()),
+        _ => Err(RegiaError::Parse(format!("bad estimate: {}", estimate_str))),
+    }
+}
+
+/// The page `--page` and `--per-page` ask for, when either is given.
+pub fn parse_page(page: Option<&str>, per_page: Option<&str>) -> error::Result<Option<todo::Page>> {
+    if page.is_none() && per_page.is_none() {
+        return Ok(None);
+    }
+    let count = |input: Option<&str>, default: usize, what: &str| match input {
+        Some(input) => match input.parse::<usize>() {
+            Ok(count) if count > 0 => Ok(count),
+            _ => Err(RegiaError::Parse(format!("bad {}: {}", what, input))),
+        },
+        None => Ok(default),
+    };
+    Ok(Some(todo::Page {
+        number: count(page, 1, "page")?,
+        size: count(per_page, todo::PER_PAGE, "page size")?,
+    }))
+}
+
+/// `page` of `list`, all of it without one. A page past the end of a list
+/// that has any is an error rather than empty.
+pub fn page_of<T>(list: Vec<T>, page: Option<todo::Page>) -> error::Result<Vec<T>> {
+    if let Some(page) = page {
+        if !list.is_empty() && page.number > page.count(list.len()) {
+            return Err(RegiaError::NotFound(format!(
+                "no page {}, there are {}",
+                page.number,
+                page.count(list.len())
+            )));
+        }
+    }
+    Ok(list.into_iter().paginate(page).collect())
+}
+
+/// Says how to get the next page, when `page` of `total` items isn't the
+/// last.
+pub fn print_more(page: Option<todo::Page>, total: usize) {
+    if let Some(page) = page.filter(|page| page.has_more(total)) {
+        let more = format!(
+            "Page {} of {}, see --page {} for more",
+            page.number,
+            page.count(total),
+            page.number + 1
+        );
+        println!("{}", more.dimmed());
+    }
+}
+
+/// Every `--remind` offset, longest first.
+fn parse_reminders<'a>(remind_strs: impl Iterator<Item = &'a str>) -> error::Result<Vec<i64>> {
+    let mut offsets = remind_strs
+        .map(parse_remind)
+        .collect::<error::Result<Vec<i64>>>()?;
+    offsets.sort_unstable_by(|a, b| b.cmp(a));
+    offsets.dedup();
+    Ok(offsets)
+}
+
+/// Resolves an `--id` value, which may be a full UUID or any unambiguous
+/// prefix of one, to the id of an existing task. The tasks an ambiguous
+/// prefix fits are listed before the error.
+pub(crate) fn resolve_id(
+    tasks: &todo::Tasks,
+    id_str: &str,
+    priority_map: &[(u32, &str)],
+) -> error::Result<Uuid> {
+    ident::resolve(tasks.get_tasks(), id_str).map_err(|unresolved| {
+        if let Unresolved::Ambiguous { candidates, .. } = &unresolved {
+            print_matches(tasks, candidates, priority_map);
+        }
+        unresolved.into()
+    })
+}
+
+static STDIN_LINES: OnceLock<Vec<String>> = OnceLock::new();
+
+/// The non-blank lines of stdin, trimmed, for the `--stdin` bulk forms.
+/// Read once; main reads them before taking the database lock so another
+/// regia command feeding the pipe isn't left waiting on it.
+pub fn stdin_lines() -> error::Result<&'static [String]> {
+    if let Some(lines) = STDIN_LINES.get() {
+        return Ok(lines);
+    }
+    let mut lines = Vec::new();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() {
+            lines.push(String::from(line));
+        }
+    }
+    Ok(STDIN_LINES.get_or_init(|| lines))
+}
+
+/// Adds the task described by `matches`, or with `--stdin` one task per
+/// line of stdin sharing the rest of the arguments.
 fn handle_task_add(
     matches: &ArgMatches,
     tasks: &mut todo::Tasks,
     _doc: &Config,
-) -> std::io::Result<()> {
+) -> error::Result<()> {
+    let contents = if matches.is_present("stdin") {
+        stdin_lines()?.to_vec()
+    } else {
+        vec![String::from(matches.value_of("content").unwrap())]
+    };
+
+    for content in contents {
+        let task = build_task(matches, content, tasks)?;
+        tasks.add(task);
+    }
+
+    Ok(())
+}
+
+fn build_task(
+    matches: &ArgMatches,
+    content: String,
+    tasks: &todo::Tasks,
+) -> error::Result<todo::Task> {
     // Go through all the ArgMatches for this function
-    // due, priority, repeats, depends, content
+    // due, priority, repeats, depends
     let priority = if let Some(priority_str) = matches.value_of("priority") {
-        priority_str.parse::<u32>().unwrap()
+        parse_priority(priority_str)?
     } else {
-        0
+        Priority::None
     };
 
     let mut task_type = None;
     let repeat: Option<todo::RepeatType> = if let Some(repeat_str) = matches.value_of("repeats") {
         task_type = Some(todo::TaskType::Repeated);
-        match repeat_str.to_ascii_lowercase().as_ref() {
-            "daily" => Some(todo::RepeatType::Daily),
-            "weekly" => Some(todo::RepeatType::Weekly),
-            "monthly" => Some(todo::RepeatType::Monthly),
-            _ => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "bad repeats string",
-                ))
-            }
-        }
+        Some(parse_repeat(repeat_str)?)
     } else {
         None
     };
 
-    let content = matches.value_of("content").unwrap();
-
     let datetime: Option<DateTime<Utc>> = if let Some(due_date) = matches.value_of("due date") {
         if task_type.is_none() {
             task_type = Some(todo::TaskType::Deadline);
         }
-        match DateTime::parse_from_rfc2822(due_date) {
-            Ok(dt) => Some(dt.with_timezone(&Utc)),
-            Err(_) => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "bad datetime string",
-                ));
-            }
-        }
+        Some(parse_due(due_date)?)
     } else {
         None
     };
 
     // Build the input from the matches
     let mut task = if let Some(task_type) = task_type {
-        todo::Task::new_date(String::from(content), priority, datetime, task_type, repeat)
+        todo::Task::new_date(content, priority, datetime, task_type, repeat)
     } else {
-        todo::Task::new(String::from(content), priority)
+        todo::Task::new(content, priority)
     };
 
+    if let Some(scheduled) = matches.value_of("scheduled") {
+        task.scheduled = Some(parse_due(scheduled)?);
+    }
+    if let Some(estimate) = matches.value_of("estimate") {
+        task.estimate = Some(parse_estimate(estimate)?);
+    }
+
+    if let Some(tag_args) = matches.values_of("tag") {
+        task.tags = tags::from_args(tag_args);
+    }
+    if let Some(context_args) = matches.values_of("context") {
+        task.contexts = contexts::from_args(context_args);
+    }
+
+    if let Some(remind_strs) = matches.values_of("remind") {
+        task.reminders = parse_reminders(remind_strs)?;
+    }
+
+    if let Some(parent_str) = matches.value_of("parent") {
+        task.parent = Some(ident::resolve(tasks.get_tasks(), parent_str)?);
+    }
+
     if let Some(deps) = matches.values_of("depends") {
         for dep in deps {
-            let uuid = match Uuid::parse_str(dep) {
-                Ok(ok) => ok,
-                Err(_) => {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("bad depends uuid: {}", dep),
-                    ));
-                }
-            };
-            task.add_dependency(&uuid);
+            task.add_dependency(&ident::resolve(tasks.get_tasks(), dep)?);
+        }
+    }
+
+    Ok(task)
+}
+
+/// What to say when `selector` finds nothing.
+fn no_match(selector: &Selector) -> String {
+    match selector.query() {
+        search if search.is_empty() => String::from("no task matches the selectors"),
+        search => format!("no task matches: {}", search),
+    }
+}
+
+/// How many candidates `choose` offers before asking for a narrower search.
+const MENU_LEN: usize = 20;
+
+/// Lists `ids` numbered and asks which are meant: one of them, or with
+/// `many` any of them or `a` for all. An empty answer picks nothing. `a`,
+/// like `--yes` with `many`, picks every one, even those not shown.
+fn choose(
+    tasks: &todo::Tasks,
+    ids: &[Uuid],
+    priority_map: &[(u32, &str)],
+    many: bool,
+) -> error::Result<Vec<Uuid>> {
+    let shown = &ids[..ids.len().min(MENU_LEN)];
+    println!(
+        "Found {} tasks that match:",
+        format!("{}", ids.len()).header()
+    );
+    for (index, id) in shown.iter().enumerate() {
+        let task = tasks.get_task(id).unwrap();
+        println!("{:>3}) {}", index + 1, task.fmt(priority_map));
+    }
+    if ids.len() > shown.len() {
+        println!(
+            "     ...and {} more, narrow the search to see them",
+            ids.len() - shown.len()
+        );
+    }
+
+    if many && prompt::assumed_yes() {
+        println!("{} {}", "Which?".header(), "all of them".dimmed());
+        return Ok(ids.to_vec());
+    }
+    prompt::require("Which?")?;
+    let prompt = if many {
+        format!(
+            "Which? [1-{}, a list like 1,3 or a for all {}]",
+            shown.len(),
+            ids.len()
+        )
+    } else {
+        format!("Which? [1-{}]", shown.len())
+    };
+    println!("{}", prompt.header());
+    let stdin = io::stdin();
+    let mut stdin_iter = stdin.lock().lines();
+    loop {
+        let answer = match stdin_iter.next() {
+            Some(line) => line?,
+            None => return Ok(Vec::new()),
+        };
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(Vec::new());
+        }
+        if many && answer.eq_ignore_ascii_case("a") {
+            return Ok(ids.to_vec());
         }
+        let picked: Option<Vec<Uuid>> = answer
+            .split(',')
+            .map(|pick| match pick.trim().parse::<usize>() {
+                Ok(index) if index >= 1 && index <= shown.len() => Some(shown[index - 1]),
+                _ => None,
+            })
+            .collect();
+        match picked {
+            Some(picked) if many || picked.len() == 1 => return Ok(picked),
+            _ => println!("Didn't understand {}, {}", answer, prompt),
+        }
+    }
+}
+
+/// Picks a single task from `--id`, or from the `search` argument, which
+/// may itself be an id or id prefix, and the selector flags. When the
+/// search could mean several tasks they are offered to choose from.
+pub fn select_one(
+    matches: &ArgMatches,
+    tasks: &todo::Tasks,
+    priority_map: &[(u32, &str)],
+) -> error::Result<Uuid> {
+    if let Some(id_str) = matches.value_of("id") {
+        return resolve_id(tasks, id_str, priority_map);
+    }
+
+    search_one(tasks, &Selector::from_args(matches)?, priority_map)
+}
+
+/// Picks the single task `selector` means, or whose id or id prefix its
+/// search is.
+fn search_one(
+    tasks: &todo::Tasks,
+    selector: &Selector,
+    priority_map: &[(u32, &str)],
+) -> error::Result<Uuid> {
+    let search = selector.query();
+    if Uuid::parse_str(&search).is_ok() || !tasks.find_by_prefix(&search).is_empty() {
+        return resolve_id(tasks, &search, priority_map);
     }
 
-    // Add it to Tasks
-    tasks.add(task);
+    let found = selector.candidates(tasks.get_tasks().iter(), Utc::now());
+    match found.len() {
+        0 => Err(RegiaError::NotFound(no_match(selector))),
+        1 => Ok(found[0]),
+        _ => match choose(tasks, &found, priority_map, false)?.first() {
+            Some(id) => Ok(*id),
+            None => Err(RegiaError::NotFound(String::from(
+                "no task picked, narrow the search or use --id",
+            ))),
+        },
+    }
+}
+
+/// Picks the task a single `TASK` argument names: an id or id prefix if it
+/// is one, and search text otherwise.
+fn resolve_task(
+    tasks: &todo::Tasks,
+    task_str: &str,
+    priority_map: &[(u32, &str)],
+) -> error::Result<Uuid> {
+    search_one(tasks, &Selector::search(task_str), priority_map)
+}
+
+/// `dt` on local clocks, with the offset so it can't be mistaken for UTC.
+pub fn fmt_date(dt: &DateTime<Utc>) -> String {
+    let local = dt.with_timezone(&Local);
+    format!(
+        "{} {}",
+        dates::locale().datetime(local.naive_local()),
+        local.format("%:z")
+    )
+}
+
+/// One labelled line of `task show` or `note show`.
+pub fn field_line(label: &str, value: &str) -> String {
+    format!("  {} {}", format!("{:<10}", label).bold(), value)
+}
+
+fn show_field(label: &str, value: &str) {
+    println!("{}", field_line(label, value));
+}
+
+/// One upcoming date of `task preview`.
+#[derive(Serialize)]
+struct OccurrenceRecord {
+    due: DateTime<Utc>,
+}
+
+impl output::Record for OccurrenceRecord {
+    fn plain(&self) -> String {
+        self.due.to_rfc3339()
+    }
+}
 
-    // Handle any pruning of data
+/// The dates a repeated task, or a rule given with `--repeats`, will come
+/// back on, so that a rule can be checked before it is trusted.
+fn handle_task_preview(
+    matches: &ArgMatches,
+    tasks: &todo::Tasks,
+    doc: &Config,
+) -> error::Result<()> {
+    let count = matches.value_of("count").unwrap();
+    let count = match count.parse::<usize>() {
+        Ok(count) if count > 0 => count,
+        _ => return Err(RegiaError::Parse(format!("bad count: {}", count))),
+    };
+    let task = if matches.is_present("id") || matches.is_present("search") {
+        let colors = conf::priority_colors(doc)?;
+        let priority_map = conf::as_priority_map(&colors);
+        let id = select_one(matches, tasks, &priority_map)?;
+        tasks.get_task(&id)
+    } else {
+        None
+    };
+    let repeat = match (matches.value_of("repeats"), task) {
+        (Some(repeat_str), _) => parse_repeat(repeat_str)?,
+        (None, Some(task)) => match &task.repeat {
+            Some(repeat) => repeat.clone(),
+            None => {
+                return Err(RegiaError::Conflict(format!(
+                    "{} doesn't repeat, try a rule with --repeats",
+                    task.content
+                )))
+            }
+        },
+        (None, None) => unreachable!(),
+    };
+    let due = match matches.value_of("due") {
+        Some(due_str) => Some(parse_due(due_str)?),
+        None => task.and_then(|task| task.due),
+    };
 
+    let occurrences = schedule::preview(due, &repeat, Utc::now(), count, &Local);
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        let records: Vec<OccurrenceRecord> = occurrences
+            .iter()
+            .map(|due| OccurrenceRecord { due: *due })
+            .collect();
+        return output::print(format, &records);
+    }
+    match task {
+        Some(task) => println!("{} {}", task.content.bold(), repeat.to_string().dimmed()),
+        None => println!("{}", repeat.to_string().bold()),
+    }
+    let locale = dates::locale();
+    for due in occurrences.iter() {
+        let local = due.with_timezone(&Local).date_naive();
+        println!("  {} {}", local.format("%a"), locale.day(local));
+    }
+    if occurrences.len() < count {
+        println!("{}", "  and never again".dimmed());
+    }
     Ok(())
 }
 
-fn handle_task_rm(
+fn handle_task_show(
     matches: &ArgMatches,
-    tasks: &mut todo::Tasks,
-    _doc: &Config,
-) -> std::io::Result<()> {
-    let search = matches.value_of("search").unwrap();
-    let mut delete_me = Vec::new();
+    tasks: &todo::Tasks,
+    notes: &note::Notes,
+    doc: &Config,
+) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let id = select_one(matches, tasks, &priority_map)?;
+    let task = match tasks.get_task(&id) {
+        Some(task) => task,
+        None => return Err(RegiaError::NotFound(format!("no task with id: {}", id))),
+    };
 
-    for task in tasks.get_tasks() {
-        if task.content.contains(search) {
-            delete_me.push(task.id.clone());
+    let workflow = conf::workflow(doc)?;
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        let detail = output::TaskDetail {
+            task: output::TaskRecord::new(task, tasks, Utc::now()),
+            workflow: workflow.status_of(task).to_string(),
+            notes: notes
+                .for_task(&task.id)
+                .into_iter()
+                .map(output::NoteRecord::new)
+                .collect(),
+            comments: task.comments.clone(),
+        };
+        return output::print_one(format, &detail);
+    }
+
+    println!("{}", task.content.bold());
+    show_field("id", &task.id.to_string());
+    let status = match task.completed {
+        Some(completed) => format!("{} {}", "done".done(), fmt_date(&completed)),
+        None if !tasks.open_dependencies(task).is_empty() => {
+            format!("{}, {}", "blocked".warning(), workflow.status_of(task))
+        }
+        None => format!("open, {}", workflow.status_of(task)),
+    };
+    show_field("status", &status);
+    show_field("created", &fmt_date(&task.created));
+    if let Some(due) = task.due {
+        let due_str = fmt_date(&due);
+        if due < Utc::now() && !task.is_done() {
+            show_field("due", &due_str.overdue().to_string());
+        } else {
+            show_field("due", &due_str);
+        }
+    }
+    if let Some(scheduled) = task.scheduled {
+        show_field("scheduled", &fmt_date(&scheduled));
+    }
+    show_field("priority", &task.priority.to_string());
+    if let Some(estimate) = task.estimate {
+        let estimate = Duration::seconds(estimate);
+        let tracked = task.tracked(Utc::now());
+        let spent = format!("{} tracked", dates::fmt_duration(tracked));
+        let spent = if tracked > estimate {
+            spent.error().to_string()
+        } else {
+            spent
+        };
+        show_field(
+            "estimate",
+            &format!("{}, {}", dates::fmt_duration(estimate), spent),
+        );
+    }
+    if let Some(until) = task.hidden_until.filter(|_| task.is_snoozed(Utc::now())) {
+        show_field("snoozed", &format!("until {}", fmt_date(&until)));
+    }
+    if let Some(who) = task.waiting_on.as_ref().filter(|_| task.is_waiting()) {
+        match task.waiting_until {
+            Some(until) => show_field(
+                "waiting",
+                &format!("on {}, follow up {}", who, fmt_date(&until)),
+            ),
+            None => show_field("waiting", &format!("on {}", who)),
         }
     }
+    if let Some(repeat) = &task.repeat {
+        let next = match task.due.filter(|_| task.successor.is_none()) {
+            Some(due) => schedule::step(due, repeat, &Local),
+            None => None,
+        };
+        match next {
+            Some(next) => show_field("repeats", &format!("{}, then {}", repeat, fmt_date(&next))),
+            None => show_field("repeats", &repeat.to_string()),
+        }
+    }
+    let reminders = task.reminder_offsets();
+    if !reminders.is_empty() {
+        let offsets: Vec<String> = reminders
+            .iter()
+            .map(|seconds| dates::fmt_duration(chrono::Duration::seconds(*seconds)))
+            .collect();
+        show_field("remind", &format!("{} before due", offsets.join(", ")));
+    }
+    if !task.tags.is_empty() {
+        show_field("tags", &tags::fmt_tags(&task.tags));
+    }
+    if !task.contexts.is_empty() {
+        show_field("contexts", &contexts::fmt_contexts(&task.contexts));
+    }
+    if let Some(parent) = task.parent.and_then(|parent| tasks.get_task(&parent)) {
+        show_field("parent", &parent.content);
+    }
+    if let Some(issue) = &task.issue {
+        show_field(
+            "issue",
+            &format!("{}#{} {}", issue.repo, issue.number, issue.url.dimmed()),
+        );
+    }
 
-    let delete_len = delete_me.len();
+    let deps = tasks.dependency_tree(task);
+    if !deps.is_empty() {
+        println!("  {}", "depends".bold());
+        for (depth, dep) in deps {
+            println!("  {}{}", "  ".repeat(depth + 1), dep.fmt(&priority_map));
+        }
+    }
 
-    if delete_len > 0 {
+    if let Some((done, total)) = task.checklist_progress() {
         println!(
-            "Found {} task{} that match{}:",
-            format!("{}", delete_len).magenta(),
-            if delete_len > 1 { "s" } else { "" },
-            if delete_len > 1 { "" } else { "es" }
+            "  {} {}",
+            "checklist".bold(),
+            format!("{}/{}", done, total).dimmed()
         );
+        for (index, item) in task.checklist.iter().enumerate() {
+            let mark = if item.done { "[x]" } else { "[ ]" };
+            println!(
+                "    {} {} {}",
+                format!("{}.", index + 1).dimmed(),
+                mark,
+                item.text
+            );
+        }
+    }
+
+    if !task.attachments.is_empty() {
+        println!("  {}", "attachments".bold());
+        for attachment in task.attachments.iter() {
+            println!(
+                "    {} {}",
+                attachment.short_hash().dimmed(),
+                attachment.filename
+            );
+        }
+    }
+
+    if !task.comments.is_empty() {
+        println!("  {}", "comments".bold());
+        let mut comments: Vec<&todo::Comment> = task.comments.iter().collect();
+        comments.sort_by_key(|comment| comment.created);
+        for comment in comments {
+            println!(
+                "    {} {}",
+                fmt_date(&comment.created).dimmed(),
+                comment.author.bold()
+            );
+            for line in comment.body.lines() {
+                println!("      {}", line);
+            }
+        }
+    }
+
+    let attached = notes.for_task(&task.id);
+    if !attached.is_empty() {
+        println!("  {}", "notes".bold());
+        for note in attached {
+            println!("    {}", note.fmt());
+        }
+    }
+    Ok(())
+}
+
+fn handle_task_edit(
+    matches: &ArgMatches,
+    tasks: &mut todo::Tasks,
+    doc: &Config,
+) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let id = select_one(matches, tasks, &priority_map)?;
+
+    let mut depends = Vec::new();
+    if let Some(deps) = matches.values_of("depends") {
+        for dep in deps {
+            let dep = resolve_id(tasks, dep, &priority_map)?;
+            if tasks.would_cycle(&id, &dep) {
+                return Err(RegiaError::Conflict(format!(
+                    "depending on {} would create a dependency cycle",
+                    dep
+                )));
+            }
+            depends.push(dep);
+        }
+    }
+    let mut undepends = Vec::new();
+    if let Some(deps) = matches.values_of("undepends") {
+        // Only what the task depends on can be taken off it
+        let current = tasks.get_task(&id).unwrap().depends.clone();
+        for dep in deps {
+            undepends.push(ident::resolve_among(
+                current.iter().copied(),
+                "dependency",
+                dep,
+            )?);
+        }
+    }
+
+    let mut task = match tasks.get_task_mut(&id) {
+        Some(task) => task,
+        None => return Err(RegiaError::NotFound(format!("no task with id: {}", id))),
+    };
+
+    if let Some(content) = matches.value_of("content") {
+        task.content = String::from(content);
+    }
+    if let Some(priority_str) = matches.value_of("priority") {
+        task.priority = parse_priority(priority_str)?;
+    }
+    if let Some(repeat_str) = matches.value_of("repeats") {
+        task.repeat = Some(parse_repeat(repeat_str)?);
+        task.task_type = Some(todo::TaskType::Repeated);
+    }
+    if let Some(due_date) = matches.value_of("due date") {
+        task.due = Some(parse_due(due_date)?);
+        if task.task_type.is_none() {
+            task.task_type = Some(todo::TaskType::Deadline);
+        }
+    }
+    if let Some(scheduled) = matches.value_of("scheduled") {
+        task.scheduled = Some(parse_due(scheduled)?);
+    }
+    if let Some(estimate) = matches.value_of("estimate") {
+        task.estimate = Some(parse_estimate(estimate)?);
+    }
+    if let Some(remind_strs) = matches.values_of("remind") {
+        task.reminders = parse_reminders(remind_strs)?;
+        task.remind = None;
+    }
+    for dep in depends {
+        task.add_dependency(&dep);
+    }
+    for dep in undepends {
+        task.depends.remove(&dep);
+    }
+
+    println!("{} {}", "Updated".header(), task.fmt(&priority_map));
+    Ok(())
+}
+
+/// Opens the open tasks carrying every `--tag` that the search, if any,
+/// finds in `$EDITOR` to change, add to and remove from all at once.
+fn handle_task_batch(
+    matches: &ArgMatches,
+    tasks: &mut todo::Tasks,
+    notes: &mut note::Notes,
+    trash: &mut Trash,
+    doc: &Config,
+) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let workflow = conf::workflow(doc)?;
+    let open = tasks.get_tasks().iter().filter(|task| !task.is_done());
+    let mut ids = Selector::from_args(matches)?.candidates(open, Utc::now());
+    ids.sort_by_key(|id| tasks.get_task(id).unwrap().created);
+    batch::edit(&ids, tasks, notes, trash, &workflow, &priority_map)
+}
+
+fn print_matches(tasks: &todo::Tasks, ids: &[Uuid], priority_map: &[(u32, &str)]) {
+    let len = ids.len();
+    println!(
+        "Found {} task{} that match{}:",
+        format!("{}", len).header(),
+        if len > 1 { "s" } else { "" },
+        if len > 1 { "" } else { "es" }
+    );
+    for id in ids.iter() {
+        let task = tasks.get_task(id).unwrap();
+        println!("{}", task.fmt(priority_map));
+    }
+}
+
+/// Deletes or detaches the notes attached to the removed tasks `ids`, as
+/// `--cascade`/`--orphan` say or else as the user answers.
+fn drop_attached_notes(
+    matches: &ArgMatches,
+    notes: &mut note::Notes,
+    trash: &mut Trash,
+    ids: &[Uuid],
+) -> error::Result<()> {
+    let attached: Vec<Uuid> = ids
+        .iter()
+        .flat_map(|id| notes.for_task(id))
+        .map(|note| note.id)
+        .collect();
+    if attached.is_empty() {
+        return Ok(());
+    }
+
+    let cascade = if matches.is_present("cascade") {
+        true
+    } else if matches.is_present("orphan") {
+        false
+    } else {
+        for id in attached.iter() {
+            println!("{}", notes.get_note(id).unwrap().fmt());
+        }
+        let plural = if attached.len() == 1 { "" } else { "s" };
+        prompt::confirm(&format!(
+            "Delete {} attached note{} too? Otherwise they are kept unattached.",
+            attached.len(),
+            plural
+        ))?
+    };
+    let now = Utc::now();
+    for id in attached {
+        if cascade {
+            trash.delete_note(notes, id, now);
+        } else if let Some(mut note) = notes.get_note_mut(&id) {
+            note.task_id = None;
+        }
+    }
+    Ok(())
+}
+
+fn handle_task_rm(
+    matches: &ArgMatches,
+    tasks: &mut todo::Tasks,
+    notes: &mut note::Notes,
+    trash: &mut Trash,
+    doc: &Config,
+) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    // An exact id leaves no doubt about what goes, so it skips the prompt
+    if let Some(id_str) = matches.value_of("id") {
+        let id = resolve_id(tasks, id_str, &priority_map)?;
+        trash.delete_task(tasks, id, Utc::now());
+        println!("Removed task {}", id);
+        return drop_attached_notes(matches, notes, trash, &[id]);
+    }
+
+    let selector = Selector::from_args(matches)?;
+    let mut delete_me = selector.candidates(tasks.get_tasks().iter(), Utc::now());
+    if delete_me.is_empty() {
+        println!("{}", no_match(&selector));
+    } else if delete_me.len() > 1 {
+        delete_me = choose(tasks, &delete_me, &priority_map, true)?;
+    } else {
+        print_matches(tasks, &delete_me, &priority_map);
+    }
+
+    if !delete_me.is_empty() {
+        let count = delete_me.len();
+        let question = format!(
+            "Remove {} task{}?",
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+        if !prompt::confirm(&question)? {
+            return Ok(());
+        }
+        let now = Utc::now();
         for id in delete_me.iter() {
-            let task = tasks.get_task(id).unwrap();
-            println!("{}", task.fmt(&[]));
-        }
-        println!("{} [{}/{}]", "Complete?".magenta(), "y".bold(), "N".bold());
-        let stdin = io::stdin();
-        let mut stdin_iter = stdin.lock().lines();
-        loop {
-            let next_line = stdin_iter.next().unwrap().unwrap();
-            if next_line.to_lowercase() == "y" {
-                break;
-            } else if next_line == "" || next_line.to_lowercase() == "n" {
+            trash.delete_task(tasks, *id, now);
+        }
+        drop_attached_notes(matches, notes, trash, &delete_me)?;
+    }
+
+    Ok(())
+}
+
+fn handle_task_done(
+    matches: &ArgMatches,
+    tasks: &mut todo::Tasks,
+    doc: &Config,
+) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let mut finish_me = Vec::new();
+
+    if matches.is_present("stdin") {
+        for id_str in stdin_lines()? {
+            let id = resolve_id(tasks, id_str, &priority_map)?;
+            if !tasks.get_task(&id).unwrap().is_done() && !finish_me.contains(&id) {
+                finish_me.push(id);
+            }
+        }
+    } else if let Some(id_str) = matches.value_of("id") {
+        let id = resolve_id(tasks, id_str, &priority_map)?;
+        if tasks.get_task(&id).unwrap().is_done() {
+            println!("Task {} is already done", id);
+        } else {
+            finish_me.push(id);
+        }
+    } else {
+        let selector = Selector::from_args(matches)?;
+        let open = tasks.get_tasks().iter().filter(|task| !task.is_done());
+        finish_me = selector.candidates(open, Utc::now());
+
+        if finish_me.is_empty() {
+            println!("{}", no_match(&selector));
+        } else if finish_me.len() > 1 {
+            finish_me = choose(tasks, &finish_me, &priority_map, true)?;
+        } else if !finish_me.is_empty() {
+            print_matches(tasks, &finish_me, &priority_map);
+            if !prompt::confirm("Mark done?")? {
                 return Ok(());
-            } else {
-                println!("Didn't understand {} please type y or n", next_line);
             }
         }
+    }
+
+    if !matches.is_present("force") {
+        for id in finish_me.iter() {
+            let task = tasks.get_task(id).unwrap();
+            let blockers: Vec<&todo::Task> = tasks
+                .open_dependencies(task)
+                .into_iter()
+                .filter(|dep| !finish_me.contains(&dep.id))
+                .collect();
+            if !blockers.is_empty() {
+                println!("{}", task.fmt_blocked(&priority_map, &blockers));
+                return Err(RegiaError::Conflict(String::from(
+                    "task has open dependencies, finish them first or use --force",
+                )));
+            }
+        }
+    }
 
-        for id in delete_me {
-            tasks.remove(id);
+    for id in finish_me.iter() {
+        if let Some(mut task) = tasks.get_task_mut(id) {
+            task.complete();
         }
     }
+    print_unblocked(tasks, &finish_me, &priority_map);
 
     Ok(())
 }
 
-fn handle_task_list(tasks: &todo::Tasks, _doc: &Config) -> std::io::Result<()> {
-    let mut tasks_list = tasks.get_tasks().clone();
-    tasks_list.sort_by_key(|k| k.created);
-    for task in tasks_list.iter().rev() {
-        println!("{}", task.fmt(&[]));
+/// Lists what finishing the tasks `done` left with nothing to wait on.
+pub fn print_unblocked(tasks: &todo::Tasks, done: &[Uuid], priority_map: &[(u32, &str)]) {
+    let unblocked = tasks.unblocked_by(done);
+    if unblocked.is_empty() {
+        return;
     }
-    Ok(())
+    println!("{}", "Now unblocked:".header());
+    for task in unblocked {
+        println!("  {}", task.fmt(priority_map));
+    }
+}
+
+/// The filter flags given to `task ls`, `view` or `view save`.
+pub fn view_from_args(matches: &ArgMatches) -> View {
+    let value = |name: &str| matches.value_of(name).map(String::from);
+    View {
+        tags: matches
+            .values_of("tag")
+            .into_iter()
+            .flatten()
+            .map(tags::normalize)
+            .filter(|tag| !tag.is_empty())
+            .collect(),
+        due_before: value("due-before"),
+        due_after: value("due-after"),
+        priority_min: value("priority-min"),
+        all: matches.is_present("all"),
+        overdue: matches.is_present("overdue"),
+        blocked: matches.is_present("blocked"),
+        snoozed: matches.is_present("snoozed"),
+        sort: value("sort"),
+        limit: value("limit"),
+        upcoming: matches.is_present("upcoming"),
+        stale: matches.is_present("stale"),
+        page: value("page"),
+        per_page: value("per-page"),
+        waiting: matches.is_present("waiting"),
+        query: value("where"),
+        context: matches
+            .value_of("context")
+            .map(contexts::normalize)
+            .filter(|context| !context.is_empty()),
+        any_context: matches.is_present("any-context"),
+    }
+}
+
+/// Reads the flags of a view as of now, failing on any that don't parse.
+pub fn query_from_view(view: &View) -> error::Result<todo::Query> {
+    let parse_date = |input: &Option<String>| match input {
+        Some(input) => parse_due(input).map(Some),
+        None => Ok(None),
+    };
+    let script = match &view.query {
+        Some(input) => query::parse(input, Utc::now())?,
+        None => query::Script::default(),
+    };
+    Ok(todo::Query {
+        include_done: view.all,
+        tags: tags::from_args(view.tags.iter().map(String::as_str)),
+        context: view.context.clone(),
+        due_before: parse_date(&view.due_before)?,
+        due_after: parse_date(&view.due_after)?,
+        priority_min: match &view.priority_min {
+            Some(input) => Some(parse_priority(input)?),
+            None => None,
+        },
+        overdue: view.overdue,
+        blocked: view.blocked,
+        snoozed: view.snoozed,
+        upcoming: view.upcoming,
+        sort: match view.sort.as_deref() {
+            Some(input) => match todo::SortBy::parse(input) {
+                Some(sort) => sort,
+                None => return Err(RegiaError::Parse(format!("bad sort order: {}", input))),
+            },
+            None => script.sort.unwrap_or_default(),
+        },
+        limit: match &view.limit {
+            Some(input) => match input.parse() {
+                Ok(limit) => Some(limit),
+                Err(_) => return Err(RegiaError::Parse(format!("bad limit: {}", input))),
+            },
+            None => script.limit,
+        },
+        // Set by `list_tasks`, which knows `tasks.stale_after`
+        stale_before: None,
+        waiting: view.waiting,
+        filter: script.filter,
+        // Set by `list_tasks` too, from the `urgency` section
+        urgency: None,
+    })
 }
 
-pub fn handle_it(matches: &ArgMatches, doc: &Config) -> std::io::Result<()> {
-    let db_default = Path::new(".regia.db");
-    let db_path = match doc.get("contents") {
-        Some(content) => match content.get("regia_db") {
-            Some(content) => Path::new(content),
-            None => db_default,
+/// The filter `task ls` or `view` was asked for: the flags given, on top
+/// of the saved view they name, if any.
+pub fn requested_view(
+    matches: &ArgMatches,
+    name: Option<&str>,
+    views: &BTreeMap<String, View>,
+) -> error::Result<View> {
+    let flags = view_from_args(matches);
+    match name {
+        Some(name) => match views.get(name) {
+            Some(saved) => Ok(saved.with(&flags)),
+            None => Err(RegiaError::NotFound(format!("no view named: {}", name))),
         },
-        None => db_default,
+        None => Ok(flags),
+    }
+}
+
+fn handle_task_list(matches: &ArgMatches, db: &db::Database, doc: &Config) -> error::Result<()> {
+    let view = requested_view(matches, matches.value_of("view"), &db.views)?;
+    list_tasks(&view, db, doc)
+}
+
+/// `regia query`: `task ls` with nothing but a query.
+pub fn handle_query(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db = db::open(doc)?;
+    let view = View {
+        query: matches.value_of("query").map(String::from),
+        ..view_from_args(matches)
     };
+    list_tasks(&view, &db, doc)
+}
 
-    let db = match db::Database::from_disk(db_path) {
-        Ok(db) => db,
-        Err(err) => {
-            if err.kind() == IOErrorKind::Other {
-                return Err(err);
-            } else {
-                db::Database::default()
+/// Prints the tasks `view` selects, as `task ls` does.
+pub fn list_tasks(view: &View, db: &db::Database, doc: &Config) -> error::Result<()> {
+    let tasks = &db.tasks;
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let mut query = query_from_view(view)?;
+    query.context = crate::contexts::wanted(view.context.as_deref(), view.any_context, doc)?;
+    query.urgency = conf::urgency(doc)?;
+    let streaks = schedule::streaks(&history::of_active(db));
+    let stale_after = conf::stale_after(doc)?;
+    let formatter = todo::Formatter {
+        due_soon: conf::due_soon(doc)?,
+        streaks: Some(&streaks),
+        stale_after,
+        urgency: query.urgency,
+        ..todo::Formatter::new(&priority_map)
+    };
+    if view.stale {
+        match stale_after {
+            Some(after) => query.stale_before = Some(formatter.now - after),
+            None => {
+                return Err(RegiaError::Config(String::from(
+                    "tasks.stale_after is never, so no task goes stale",
+                )))
             }
         }
-    };
+    }
+    let page = parse_page(view.page.as_deref(), view.per_page.as_deref())?;
+    let tasks_list = tasks.query(&query, formatter.now);
+    let total = tasks_list.len();
+    let tasks_list = page_of(tasks_list, page)?;
 
-    let mut tasks = db.tasks;
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        let records: Vec<output::TaskRecord> = tasks_list
+            .iter()
+            .map(|task| output::TaskRecord::new(task, tasks, formatter.now))
+            .collect();
+        return output::print(format, &records);
+    }
 
-    if let Some(ref matches) = matches.subcommand_matches("add") {
-        handle_task_add(matches, &mut tasks, doc)?;
-        let new_db = db::Database {
-            tasks,
-            notes: db.notes,
+    for (depth, task) in todo::tree_order(&tasks_list) {
+        let blockers = if task.is_done() {
+            Vec::new()
+        } else {
+            tasks.open_dependencies(task)
         };
-        new_db.to_disk(db_path)
-    } else if let Some(ref matches) = matches.subcommand_matches("rm") {
-        handle_task_rm(matches, &mut tasks, doc)?;
-        let new_db = db::Database {
-            tasks,
-            notes: db.notes,
+        println!("{}{}", "  ".repeat(depth), formatter.task(task, &blockers));
+    }
+    print_more(page, total);
+    if !view.waiting {
+        print_follow_ups(tasks, formatter.now);
+    }
+    crate::contexts::print_sticky(view.context.as_deref(), query.context.as_deref());
+    Ok(())
+}
+
+/// Nags about tasks still waiting on someone past their follow-up date.
+fn print_follow_ups(tasks: &todo::Tasks, now: DateTime<Utc>) {
+    let due = tasks
+        .get_tasks()
+        .iter()
+        .filter(|task| task.needs_follow_up(now))
+        .count();
+    if due > 0 {
+        let plural = if due == 1 { "" } else { "s" };
+        let nag = format!(
+            "{} task{} waiting past the follow-up date, see task ls --waiting",
+            due, plural
+        );
+        println!("{}", nag.warning());
+    }
+}
+
+/// Fails silently when any open task is overdue, for shell prompts and
+/// status bars.
+fn handle_task_check_overdue(tasks: &todo::Tasks) -> error::Result<()> {
+    let query = todo::Query {
+        overdue: true,
+        ..todo::Query::default()
+    };
+    if tasks.query(&query, Utc::now()).is_empty() {
+        Ok(())
+    } else {
+        Err(RegiaError::CheckFailed)
+    }
+}
+
+fn handle_task_start(
+    matches: &ArgMatches,
+    tasks: &mut todo::Tasks,
+    doc: &Config,
+) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let id = select_one(matches, tasks, &priority_map)?;
+    let now = Utc::now();
+
+    // One clock at a time: starting a task stops whatever else is running
+    let running: Vec<Uuid> = tasks
+        .running()
+        .iter()
+        .map(|task| task.id)
+        .filter(|running| *running != id)
+        .collect();
+    for other in running {
+        let mut task = tasks.get_task_mut(&other).unwrap();
+        if let Some(spent) = task.stop(now) {
+            println!(
+                "{} {} after {}",
+                "Stopped".header(),
+                task.content,
+                dates::fmt_duration(spent)
+            );
+        }
+    }
+
+    let mut task = tasks.get_task_mut(&id).unwrap();
+    if task.start(now) {
+        println!("{} {}", "Started".header(), task.fmt(&priority_map));
+    } else {
+        println!("Already tracking {}", task.content);
+    }
+    Ok(())
+}
+
+/// Adds a copy of a task to do again, due on `--due` if given.
+fn handle_task_clone(
+    matches: &ArgMatches,
+    tasks: &mut todo::Tasks,
+    doc: &Config,
+) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let id = resolve_task(tasks, matches.value_of("task").unwrap(), &priority_map)?;
+    let due = match matches.value_of("due") {
+        Some(due) => Some(parse_due(due)?),
+        None => None,
+    };
+    let copy = tasks.get_task(&id).unwrap().duplicate(due);
+    println!("{} {}", "Cloned".header(), copy.fmt(&priority_map));
+    tasks.add(copy);
+    Ok(())
+}
+
+/// Marks a task as waiting on someone, to follow up on by `--until`, or
+/// with `--clear` takes it back.
+fn handle_task_wait(
+    matches: &ArgMatches,
+    tasks: &mut todo::Tasks,
+    doc: &Config,
+) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let id = resolve_task(tasks, matches.value_of("task").unwrap(), &priority_map)?;
+
+    let mut task = tasks.get_task_mut(&id).unwrap();
+    if matches.is_present("clear") {
+        task.waiting_on = None;
+        task.waiting_until = None;
+        println!(
+            "{} {}",
+            "No longer waiting".header(),
+            task.fmt(&priority_map)
+        );
+        return Ok(());
+    }
+    if task.is_done() {
+        return Err(RegiaError::Conflict(String::from(
+            "task is done, there is nothing to wait for",
+        )));
+    }
+
+    let who = matches.value_of("on").unwrap().trim();
+    if who.is_empty() {
+        return Err(RegiaError::Parse(String::from("say who the task waits on")));
+    }
+    task.waiting_on = Some(String::from(who));
+    task.waiting_until = match matches.value_of("until") {
+        Some(until) => Some(parse_due(until)?),
+        None => None,
+    };
+    let until = match task.waiting_until {
+        Some(until) => format!(", following up {}", fmt_date(&until)),
+        None => String::new(),
+    };
+    println!(
+        "{} {} on {}{}",
+        "Waiting".header(),
+        task.content,
+        who,
+        until
+    );
+    Ok(())
+}
+
+/// Hides a task from default listings until a date or for a while, or
+/// with `--clear` brings it back.
+fn handle_task_snooze(
+    matches: &ArgMatches,
+    tasks: &mut todo::Tasks,
+    doc: &Config,
+) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let id = resolve_task(tasks, matches.value_of("task").unwrap(), &priority_map)?;
+
+    let mut task = tasks.get_task_mut(&id).unwrap();
+    if matches.is_present("clear") {
+        task.hidden_until = None;
+        println!("{} {}", "Unsnoozed".header(), task.fmt(&priority_map));
+        return Ok(());
+    }
+
+    let until = parse_due(matches.value_of("until").unwrap())?;
+    if until <= Utc::now() {
+        return Err(RegiaError::Parse(String::from(
+            "snooze time is already past",
+        )));
+    }
+    task.hidden_until = Some(until);
+    println!(
+        "{} {} until {}",
+        "Snoozed".header(),
+        task.fmt(&priority_map),
+        fmt_date(&until)
+    );
+    Ok(())
+}
+
+fn handle_task_attach(
+    matches: &ArgMatches,
+    tasks: &mut todo::Tasks,
+    doc: &Config,
+) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let id = resolve_task(tasks, matches.value_of("task").unwrap(), &priority_map)?;
+    let mut task = tasks.get_task_mut(&id).unwrap();
+    attach::attach_file(
+        doc,
+        matches.value_of("path").unwrap(),
+        &mut task.attachments,
+    )
+}
+
+fn handle_task_comment(
+    matches: &ArgMatches,
+    tasks: &mut todo::Tasks,
+    doc: &Config,
+) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let id = resolve_task(tasks, matches.value_of("task").unwrap(), &priority_map)?;
+    let body = matches
+        .values_of("text")
+        .into_iter()
+        .flatten()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if body.trim().is_empty() {
+        return Err(RegiaError::Parse(String::from("comment is empty")));
+    }
+
+    let mut task = tasks.get_task_mut(&id).unwrap();
+    task.comments.push(todo::Comment {
+        author: conf::author(doc),
+        created: Utc::now(),
+        body,
+    });
+    println!("{} on {}", "Commented".header(), task.fmt(&priority_map));
+    Ok(())
+}
+
+fn handle_task_move(
+    matches: &ArgMatches,
+    tasks: &mut todo::Tasks,
+    doc: &Config,
+) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let workflow = conf::workflow(doc)?;
+    let status = workflow.find(matches.value_of("status").unwrap())?;
+    let id = resolve_task(tasks, matches.value_of("task").unwrap(), &priority_map)?;
+
+    let task = tasks.get_task(&id).unwrap();
+    if status == workflow.done() && !task.is_done() && !matches.is_present("force") {
+        let blockers = tasks.open_dependencies(task);
+        if !blockers.is_empty() {
+            println!("{}", task.fmt_blocked(&priority_map, &blockers));
+            return Err(RegiaError::Conflict(String::from(
+                "task has open dependencies, finish them first or use --force",
+            )));
+        }
+    }
+
+    let mut task = tasks.get_task_mut(&id).unwrap();
+    workflow.move_task(&mut task, status);
+    println!(
+        "{} {} to {}",
+        "Moved".header(),
+        task.fmt(&priority_map),
+        status.bold()
+    );
+    Ok(())
+}
+
+/// The index of the checklist step `wanted` names: a number from 1, or
+/// text found in exactly one step.
+fn select_step(task: &todo::Task, wanted: &str) -> error::Result<usize> {
+    if let Ok(number) = wanted.parse::<usize>() {
+        return match number {
+            number if (1..=task.checklist.len()).contains(&number) => Ok(number - 1),
+            _ => Err(RegiaError::NotFound(format!(
+                "no step {}, the checklist has {}",
+                number,
+                task.checklist.len()
+            ))),
         };
-        new_db.to_disk(db_path)
+    }
+    let lower = wanted.to_lowercase();
+    let found: Vec<usize> = task
+        .checklist
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.text.to_lowercase().contains(&lower))
+        .map(|(index, _)| index)
+        .collect();
+    match found.len() {
+        0 => Err(RegiaError::NotFound(format!("no step matches: {}", wanted))),
+        1 => Ok(found[0]),
+        _ => Err(RegiaError::Conflict(format!(
+            "{} matches several steps, give its number",
+            wanted
+        ))),
+    }
+}
+
+fn handle_task_check(
+    matches: &ArgMatches,
+    tasks: &mut todo::Tasks,
+    doc: &Config,
+) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let (action, matches) = match matches.subcommand() {
+        (action, Some(matches)) => (action, matches),
+        _ => unreachable!(),
+    };
+    let id = resolve_task(tasks, matches.value_of("task").unwrap(), &priority_map)?;
+    let mut task = tasks.get_task_mut(&id).unwrap();
+    let step = matches.value_of("step").unwrap();
+
+    if action == "add" {
+        task.checklist.push(todo::ChecklistItem {
+            text: step.to_string(),
+            done: false,
+        });
+        println!(
+            "{} step {} to {}",
+            "Added".header(),
+            task.checklist.len(),
+            task.fmt(&priority_map)
+        );
+    } else {
+        let index = select_step(&task, step)?;
+        let item = &mut task.checklist[index];
+        item.done = !item.done;
+        let verb = if item.done { "Checked" } else { "Unchecked" };
+        println!("{} {}", verb.header(), item.text);
+    }
+    Ok(())
+}
+
+fn handle_task_stop(
+    matches: &ArgMatches,
+    tasks: &mut todo::Tasks,
+    doc: &Config,
+) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let ids: Vec<Uuid> = if matches.is_present("id") || matches.is_present("search") {
+        vec![select_one(matches, tasks, &priority_map)?]
+    } else {
+        tasks.running().iter().map(|task| task.id).collect()
+    };
+
+    let now = Utc::now();
+    let mut stopped = false;
+    for id in ids {
+        let mut task = tasks.get_task_mut(&id).unwrap();
+        if let Some(spent) = task.stop(now) {
+            println!(
+                "{} {} after {}",
+                "Stopped".header(),
+                task.content,
+                dates::fmt_duration(spent)
+            );
+            stopped = true;
+        }
+    }
+    if !stopped {
+        println!("Nothing is being tracked");
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct SessionRecord {
+    start: DateTime<Utc>,
+    stop: Option<DateTime<Utc>>,
+    secs: i64,
+    pomodoro: bool,
+}
+
+impl output::Record for SessionRecord {
+    fn plain(&self) -> String {
+        output::fields(&[
+            self.start.to_rfc3339(),
+            output::fmt_time(&self.stop),
+            self.secs.to_string(),
+            self.pomodoro.to_string(),
+        ])
+    }
+}
+
+/// One row of the weekly `task time` summary.
+#[derive(Serialize)]
+struct TotalRecord {
+    /// `project` or `tag`
+    by: &'static str,
+    name: String,
+    secs: i64,
+}
+
+impl output::Record for TotalRecord {
+    fn plain(&self) -> String {
+        output::fields(&[
+            self.by.to_string(),
+            self.name.clone(),
+            self.secs.to_string(),
+        ])
+    }
+}
+
+fn print_totals(title: &str, totals: &BTreeMap<String, chrono::Duration>) {
+    println!("{}", title.bold());
+    let mut rows: Vec<(&String, &chrono::Duration)> = totals.iter().collect();
+    rows.sort_by(|left, right| right.1.cmp(left.1));
+    for (name, spent) in rows {
+        println!("  {:<24} {}", name, dates::fmt_duration(*spent));
+    }
+}
+
+/// Tracked time for one task, or without one a summary of this week's
+/// tracked time by project and by tag.
+fn handle_task_time(matches: &ArgMatches, db: &db::Database, doc: &Config) -> error::Result<()> {
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+    let now = Utc::now();
+    let format = Output::from_config(doc);
+
+    if matches.is_present("id") || matches.is_present("search") {
+        let id = select_one(matches, &db.tasks, &priority_map)?;
+        let task = db.tasks.get_task(&id).unwrap();
+        if format != Output::Text {
+            let records: Vec<SessionRecord> = task
+                .sessions
+                .iter()
+                .map(|session| SessionRecord {
+                    start: session.start,
+                    stop: session.stop,
+                    secs: session.overlap(session.start, now, now).num_seconds(),
+                    pomodoro: session.pomodoro,
+                })
+                .collect();
+            return output::print(format, &records);
+        }
+        println!("{}", task.fmt(&priority_map));
+        for session in task.sessions.iter() {
+            let stop = match session.stop {
+                Some(stop) => fmt_date(&stop),
+                None => "running".done().to_string(),
+            };
+            let spent = session.overlap(session.start, now, now);
+            println!(
+                "  {} - {}  {}{}",
+                fmt_date(&session.start),
+                stop,
+                dates::fmt_duration(spent),
+                if session.pomodoro { " 🍅" } else { "" }
+            );
+        }
+        println!(
+            "  {} {}",
+            "total".bold(),
+            dates::fmt_duration(task.tracked(now))
+        );
+        return Ok(());
+    }
+
+    let from = dates::week_start(now, &dates::locale());
+    let mut at_rest = db.clone();
+    at_rest.activate(None)?;
+    let mut spaces = vec![(db::ROOT_PROJECT, &at_rest.tasks, &at_rest.archive)];
+    for (name, project) in at_rest.projects.iter() {
+        spaces.push((name.as_str(), &project.tasks, &project.archive));
+    }
+
+    let mut by_project = BTreeMap::new();
+    let mut by_tag = BTreeMap::new();
+    for (name, tasks, archive) in spaces {
+        for task in tasks.get_tasks().iter().chain(archive.get_tasks()) {
+            let spent = task.tracked_between(from, now, now);
+            if spent.is_zero() {
+                continue;
+            }
+            *by_project
+                .entry(name.to_string())
+                .or_insert_with(chrono::Duration::zero) += spent;
+            if task.tags.is_empty() {
+                *by_tag
+                    .entry(String::from("(untagged)"))
+                    .or_insert_with(chrono::Duration::zero) += spent;
+            }
+            for tag in task.tags.iter() {
+                *by_tag
+                    .entry(format!("#{}", tag))
+                    .or_insert_with(chrono::Duration::zero) += spent;
+            }
+        }
+    }
+
+    if format != Output::Text {
+        let mut records = Vec::new();
+        for (by, totals) in [("project", &by_project), ("tag", &by_tag)] {
+            for (name, spent) in totals {
+                records.push(TotalRecord {
+                    by,
+                    name: name.clone(),
+                    secs: spent.num_seconds(),
+                });
+            }
+        }
+        return output::print(format, &records);
+    }
+
+    if by_project.is_empty() {
+        println!(
+            "Nothing tracked since {}",
+            dates::locale().day(from.date_naive())
+        );
+        return Ok(());
+    }
+    println!(
+        "Tracked since {}",
+        dates::locale().day(from.date_naive()).header()
+    );
+    print_totals("By project", &by_project);
+    print_totals("By tag", &by_tag);
+    Ok(())
+}
+
+pub fn handle_tick(_matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let mut db = db::open(doc)?;
+
+    let colors = conf::priority_colors(doc)?;
+    let priority_map = conf::as_priority_map(&colors);
+
+    let spawned = schedule::tick(&mut db.tasks, Utc::now(), &Local);
+    if spawned.is_empty() {
+        println!("Nothing to schedule");
+        return Ok(());
+    }
+
+    println!(
+        "Scheduled {} repeated task{}:",
+        format!("{}", spawned.len()).header(),
+        if spawned.len() > 1 { "s" } else { "" }
+    );
+    for id in spawned.iter() {
+        println!("{}", db.tasks.get_task(id).unwrap().fmt(&priority_map));
+    }
+    db.to_disk(db_path)
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let mut db = db::open(doc)?;
+    let before = db.clone();
+
+    let now = Utc::now();
+    let spawned = schedule::tick(&mut db.tasks, now, &Local);
+    let pruned = match archive::auto_age(doc)? {
+        Some(age) => archive::prune(&mut db.tasks, &mut db.archive, now - age),
+        None => Vec::new(),
+    };
+    let purged = trash::auto_purge(&mut db, doc, now)?;
+    let tasks = &mut db.tasks;
+
+    let changed = if let Some(matches) = matches.subcommand_matches("add") {
+        handle_task_add(matches, tasks, doc)?;
+        true
+    } else if let Some(matches) = matches.subcommand_matches("rm") {
+        handle_task_rm(matches, tasks, &mut db.notes, &mut db.trash, doc)?;
+        true
+    } else if let Some(matches) = matches.subcommand_matches("done") {
+        handle_task_done(matches, tasks, doc)?;
+        true
+    } else if let Some(matches) = matches.subcommand_matches("edit") {
+        if matches.is_present("batch") {
+            handle_task_batch(matches, tasks, &mut db.notes, &mut db.trash, doc)?;
+        } else {
+            handle_task_edit(matches, tasks, doc)?;
+        }
+        true
+    } else if let Some(matches) = matches.subcommand_matches("show") {
+        handle_task_show(matches, tasks, &db.notes, doc)?;
+        false
+    } else if let Some(matches) = matches.subcommand_matches("preview") {
+        handle_task_preview(matches, tasks, doc)?;
+        false
+    } else if let Some(matches) = matches.subcommand_matches("ls") {
+        handle_task_list(matches, &db, doc)?;
+        false
+    } else if matches.subcommand_matches("check-overdue").is_some() {
+        handle_task_check_overdue(tasks)?;
+        false
+    } else if let Some(matches) = matches.subcommand_matches("start") {
+        handle_task_start(matches, tasks, doc)?;
+        true
+    } else if let Some(matches) = matches.subcommand_matches("wait") {
+        handle_task_wait(matches, tasks, doc)?;
+        true
+    } else if let Some(matches) = matches.subcommand_matches("clone") {
+        handle_task_clone(matches, tasks, doc)?;
+        true
+    } else if let Some(matches) = matches.subcommand_matches("snooze") {
+        handle_task_snooze(matches, tasks, doc)?;
+        true
+    } else if let Some(matches) = matches.subcommand_matches("attach") {
+        handle_task_attach(matches, tasks, doc)?;
+        true
+    } else if let Some(matches) = matches.subcommand_matches("check") {
+        handle_task_check(matches, tasks, doc)?;
+        true
+    } else if let Some(matches) = matches.subcommand_matches("comment") {
+        handle_task_comment(matches, tasks, doc)?;
+        true
+    } else if let Some(matches) = matches.subcommand_matches("move") {
+        handle_task_move(matches, tasks, doc)?;
+        true
+    } else if let Some(matches) = matches.subcommand_matches("stop") {
+        handle_task_stop(matches, tasks, doc)?;
+        true
+    } else if let Some(matches) = matches.subcommand_matches("graph") {
+        graph::handle_task_graph(matches, tasks, doc)?;
+        false
+    } else if let Some(matches) = matches.subcommand_matches("time") {
+        handle_task_time(matches, &db, doc)?;
+        false
+    } else if let Some(matches) = matches.subcommand_matches("archive") {
+        archive::handle_task_archive(matches, &mut db, doc)?
+    } else if matches.subcommand_matches("sync-github").is_some() {
+        let colors = conf::priority_colors(doc)?;
+        let priority_map = conf::as_priority_map(&colors);
+        github::sync(tasks, doc, &priority_map)? > 0
+    } else {
+        unreachable!();
+    };
+
+    if changed || !spawned.is_empty() || !pruned.is_empty() || purged > 0 {
+        let command = format!("task {}", matches.subcommand_name().unwrap());
+        journal::record(db_path, &command, &before, &mut db)?;
+        db.to_disk(db_path)
     } else {
-        handle_task_list(&tasks, doc)
+        Ok(())
     }
 }
@@ -0,0 +1,264 @@
+//! The [todo.txt](https://github.com/todotxt/todo.txt) format: one task a
+//! line, like `(A) 2026-10-01 Call the bank +house @phone due:2026-10-20`.
+//!
+//! Priority letters stand for regia's named levels, `+project` for the
+//! project a task is in and `@context` for its contexts. Tags, which
+//! todo.txt has none of, are written as `#tag`. Anything else regia keeps
+//! of a task, like its repeats or dependencies, has no place there.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
+
+use regia_core::dates;
+use regia_core::db::Database;
+use regia_core::error::{self, RegiaError};
+use regia_core::priority::Priority;
+use regia_core::todo::{Task, TaskType};
+
+const DATE: &str = "%Y-%m-%d";
+
+/// The letter for `priority`, the named level it reaches.
+fn letter(priority: Priority) -> Option<char> {
+    match priority.weight() {
+        0 => None,
+        weight if weight >= Priority::Critical.weight() => Some('A'),
+        weight if weight >= Priority::High.weight() => Some('B'),
+        weight if weight >= Priority::Medium.weight() => Some('C'),
+        _ => Some('D'),
+    }
+}
+
+/// The level for a priority letter; `D` and everything after it is low.
+fn from_letter(letter: char) -> Priority {
+    match letter {
+        'A' => Priority::Critical,
+        'B' => Priority::High,
+        'C' => Priority::Medium,
+        _ => Priority::Low,
+    }
+}
+
+fn local_date<Tz: TimeZone>(at: &DateTime<Utc>, zone: &Tz) -> String {
+    at.with_timezone(zone).date_naive().format(DATE).to_string()
+}
+
+/// The line for `task` of `project`, `None` being the root project.
+/// Completed tasks keep their priority as `pri:`, as todo.sh does.
+pub fn line<Tz: TimeZone>(task: &Task, project: Option<&str>, zone: &Tz) -> String {
+    let mut words = Vec::new();
+    let priority = letter(task.priority);
+    match task.completed {
+        Some(completed) => {
+            words.push(String::from("x"));
+            words.push(local_date(&completed, zone));
+        }
+        None => words.extend(priority.map(|letter| format!("({})", letter))),
+    }
+    words.push(local_date(&task.created, zone));
+    words.extend(task.content.split_whitespace().map(String::from));
+    words.extend(project.map(|project| format!("+{}", project)));
+    let mut contexts: Vec<&String> = task.contexts.iter().collect();
+    contexts.sort();
+    words.extend(contexts.iter().map(|context| format!("@{}", context)));
+    let mut tags: Vec<&String> = task.tags.iter().collect();
+    tags.sort();
+    words.extend(tags.iter().map(|tag| format!("#{}", tag)));
+    if let Some(due) = task.due {
+        words.push(format!("due:{}", local_date(&due, zone)));
+    }
+    if task.completed.is_some() {
+        words.extend(priority.map(|letter| format!("pri:{}", letter)));
+    }
+    words.join(" ")
+}
+
+/// Every task of every project in `db`, oldest first within each.
+pub fn to_todotxt(db: &Database) -> error::Result<String> {
+    let mut at_rest = db.clone();
+    at_rest.activate(None)?;
+    let mut spaces = vec![(None, &at_rest.tasks)];
+    for (name, project) in at_rest.projects.iter() {
+        spaces.push((Some(name.as_str()), &project.tasks));
+    }
+
+    let mut lines = Vec::new();
+    for (project, tasks) in spaces {
+        let mut tasks: Vec<&Task> = tasks.get_tasks().iter().collect();
+        tasks.sort_by_key(|task| task.created);
+        lines.extend(tasks.iter().map(|task| line(task, project, &dates::Local)));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// A task read from a line, and the project its first `+project` names.
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub task: Task,
+    pub project: Option<String>,
+}
+
+fn parse_date(word: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(word, DATE).ok()
+}
+
+fn start_of<Tz: TimeZone>(date: NaiveDate, zone: &Tz) -> DateTime<Utc> {
+    dates::from_local(zone, &date.and_time(NaiveTime::MIN))
+}
+
+/// Parses one line, `None` for a blank one. Further `+project`s past the
+/// first become tags, since a task is in one project, as do `#tag`s other
+/// than bare numbers.
+pub fn parse_line<Tz: TimeZone>(
+    line: &str,
+    now: DateTime<Utc>,
+    zone: &Tz,
+) -> error::Result<Option<Item>> {
+    let mut words = line.split_whitespace().peekable();
+    if words.peek().is_none() {
+        return Ok(None);
+    }
+
+    let mut completed = None;
+    let mut priority = Priority::None;
+    if words.peek() == Some(&"x") {
+        words.next();
+        completed = Some(now);
+        if let Some(date) = words.peek().and_then(|word| parse_date(word)) {
+            words.next();
+            completed = Some(start_of(date, zone));
+        }
+    } else if let Some(word) = words.peek() {
+        let bytes = word.as_bytes();
+        if bytes.len() == 3 && bytes[0] == b'(' && bytes[1].is_ascii_uppercase() && bytes[2] == b')'
+        {
+            priority = from_letter(bytes[1] as char);
+            words.next();
+        }
+    }
+    let mut created = None;
+    if let Some(date) = words.peek().and_then(|word| parse_date(word)) {
+        words.next();
+        created = Some(start_of(date, zone));
+    }
+
+    let mut content = Vec::new();
+    let mut project = None;
+    let mut tags = Vec::new();
+    let mut contexts = Vec::new();
+    let mut due = None;
+    for word in words {
+        if let Some(name) = word.strip_prefix('+').filter(|name| !name.is_empty()) {
+            if project.is_none() {
+                project = Some(name.to_string());
+            } else {
+                tags.push(name);
+            }
+        } else if let Some(context) = word.strip_prefix('@').filter(|name| !name.is_empty()) {
+            contexts.push(context);
+        } else if let Some(tag) = word
+            .strip_prefix('#')
+            .filter(|tag| !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit()))
+        {
+            tags.push(tag);
+        } else if let Some(value) = word.strip_prefix("due:") {
+            due = match parse_date(value) {
+                Some(_) => dates::parse_relative(value, now, zone),
+                None => None,
+            };
+            if due.is_none() {
+                return Err(RegiaError::Parse(format!("bad due date: {}", value)));
+            }
+        } else if let Some(value) = word.strip_prefix("pri:").filter(|_| completed.is_some()) {
+            match value.chars().next() {
+                Some(letter) if value.len() == 1 && letter.is_ascii_uppercase() => {
+                    priority = from_letter(letter)
+                }
+                _ => return Err(RegiaError::Parse(format!("bad priority: {}", value))),
+            }
+        } else {
+            content.push(word);
+        }
+    }
+    let content = content.join(" ");
+    if content.is_empty() {
+        return Err(RegiaError::Parse(String::from("the task says nothing")));
+    }
+
+    let mut task = match due {
+        Some(due) => Task::new_date(content, priority, Some(due), TaskType::Deadline, None),
+        None => Task::new(content, priority),
+    };
+    task.tags = regia_core::tags::from_args(tags);
+    task.contexts = regia_core::contexts::from_args(contexts);
+    if let Some(created) = created {
+        task.created = created;
+    }
+    task.completed = completed;
+    Ok(Some(Item { task, project }))
+}
+
+/// Parses a whole file, naming the line that fails.
+pub fn parse(text: &str, now: DateTime<Utc>) -> error::Result<Vec<Item>> {
+    let mut items = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        match parse_line(line, now, &dates::Local) {
+            Ok(item) => items.extend(item),
+            Err(err) => return Err(err.context(format!("line {}", number + 1))),
+        }
+    }
+    Ok(items)
+}
+
+/// `items` by the project each goes to, `None` for whichever is active.
+pub fn by_project(items: Vec<Item>) -> BTreeMap<Option<String>, Vec<Task>> {
+    let mut projects: BTreeMap<Option<String>, Vec<Task>> = BTreeMap::new();
+    for item in items {
+        projects.entry(item.project).or_default().push(item.task);
+    }
+    projects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_round_trip() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 14, 12, 0, 0).unwrap();
+        let item = parse_line(
+            "(B) 2026-10-01 Call the bank about #42 +house +money @phone #admin due:2026-10-20",
+            now,
+            &Utc,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(item.project.as_deref(), Some("house"));
+        let task = &item.task;
+        assert_eq!(task.content, "Call the bank about #42");
+        assert_eq!(task.priority, Priority::High);
+        assert!(task.tags.contains("admin") && task.tags.contains("money"));
+        assert!(task.contexts.contains("phone") && task.tags.len() == 2);
+        assert_eq!(local_date(&task.created, &Utc), "2026-10-01");
+        assert_eq!(
+            line(task, Some("house"), &Utc),
+            "(B) 2026-10-01 Call the bank about #42 +house @phone #admin #money due:2026-10-20"
+        );
+
+        let done = parse_line("x 2026-10-12 2026-10-02 Renew passport pri:A", now, &Utc)
+            .unwrap()
+            .unwrap();
+        assert!(done.task.is_done());
+        assert_eq!(done.task.priority, Priority::Critical);
+        assert_eq!(
+            line(&done.task, None, &Utc),
+            "x 2026-10-12 2026-10-02 Renew passport pri:A"
+        );
+
+        // Lowercase letters aren't a priority, and a blank line is nothing
+        let plain = parse_line("(a) sort out", now, &Utc).unwrap().unwrap();
+        assert_eq!(plain.task.content, "(a) sort out");
+        assert!(parse_line("   ", now, &Utc).unwrap().is_none());
+        assert!(parse_line("Pay rent due:friday", now, &Utc).is_err());
+    }
+}
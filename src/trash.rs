@@ -0,0 +1,180 @@
+use chrono::{DateTime, Duration, Utc};
+use clap::ArgMatches;
+use colored::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::journal;
+use crate::output::{self, Output};
+use regia_core::conf::{self, Config};
+use regia_core::dates;
+use regia_core::db::{self, Database};
+use regia_core::error::{self, RegiaError};
+use regia_core::ident;
+use regia_core::theme::Themed;
+
+/// How long removed items stay in the trash when `trash.after` isn't set.
+const DEFAULT_AGE: i64 = 30;
+
+/// Reads `trash.after` from the config: how long removed items are kept
+/// before commands that change the database purge them. `never` keeps
+/// them until `trash empty`.
+pub fn auto_age(doc: &Config) -> error::Result<Option<Duration>> {
+    match doc.trash.after.as_deref() {
+        Some(value) if value.trim() == "never" => Ok(None),
+        _ => Ok(Some(
+            conf::duration("trash.after", &doc.trash.after)?.unwrap_or(Duration::days(DEFAULT_AGE)),
+        )),
+    }
+}
+
+/// Purges the active project's trash of items older than `trash.after`,
+/// returning how many went.
+pub fn auto_purge(db: &mut Database, doc: &Config, now: DateTime<Utc>) -> error::Result<usize> {
+    Ok(match auto_age(doc)? {
+        Some(age) => db.trash.purge(now - age),
+        None => 0,
+    })
+}
+
+#[derive(Serialize)]
+struct TrashRecord {
+    /// `task` or `note`
+    kind: &'static str,
+    id: Uuid,
+    content: String,
+    deleted: DateTime<Utc>,
+}
+
+impl output::Record for TrashRecord {
+    fn plain(&self) -> String {
+        output::fields(&[
+            self.kind.to_string(),
+            self.id.to_string(),
+            self.deleted.to_rfc3339(),
+            self.content.replace('\n', " "),
+        ])
+    }
+}
+
+/// Everything in the trash, newest deletion first.
+fn records(db: &Database) -> Vec<TrashRecord> {
+    let mut records: Vec<TrashRecord> = db
+        .trash
+        .tasks
+        .iter()
+        .map(|entry| TrashRecord {
+            kind: "task",
+            id: entry.item.id,
+            content: entry.item.content.clone(),
+            deleted: entry.deleted,
+        })
+        .chain(db.trash.notes.iter().map(|entry| TrashRecord {
+            kind: "note",
+            id: entry.item.id,
+            content: entry.item.content.clone(),
+            deleted: entry.deleted,
+        }))
+        .collect();
+    records.sort_by_key(|record| std::cmp::Reverse(record.deleted));
+    records
+}
+
+fn print_record(record: &TrashRecord, now: DateTime<Utc>) {
+    let first_line = record.content.lines().next().unwrap_or("");
+    println!(
+        "{} {} {} {}",
+        ident::short(&record.id).dimmed(),
+        record.kind.accent(),
+        first_line,
+        format!(
+            "(deleted {} ago)",
+            dates::fmt_duration(now - record.deleted)
+        )
+        .dimmed()
+    );
+}
+
+fn handle_trash_list(db: &Database, doc: &Config) -> error::Result<()> {
+    let records = records(db);
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        return output::print(format, &records);
+    }
+    if records.is_empty() {
+        println!("The trash is empty");
+    }
+    let now = Utc::now();
+    for record in records.iter() {
+        print_record(record, now);
+    }
+    Ok(())
+}
+
+/// Restores the item each argument names, by id, id prefix or content.
+fn handle_trash_restore(matches: &ArgMatches, db: &mut Database) -> error::Result<()> {
+    let now = Utc::now();
+    for wanted in matches.values_of("item").unwrap() {
+        let lower = wanted.to_lowercase();
+        let found: Vec<TrashRecord> = records(db)
+            .into_iter()
+            .filter(|record| {
+                !ident::matching([record.id], wanted).is_empty()
+                    || record.content.to_lowercase().contains(&lower)
+            })
+            .collect();
+        let record = match found.len() {
+            0 => {
+                return Err(RegiaError::NotFound(format!(
+                    "nothing in the trash matches: {}",
+                    wanted
+                )))
+            }
+            1 => &found[0],
+            _ => {
+                for record in found.iter() {
+                    print_record(record, now);
+                }
+                return Err(RegiaError::Conflict(format!(
+                    "{} matches several items, give more of the id",
+                    wanted
+                )));
+            }
+        };
+        db.trash
+            .restore(record.id, &mut db.tasks, &mut db.notes)
+            .map_err(RegiaError::Conflict)?;
+        println!("{} {} {}", "Restored".header(), record.kind, record.content);
+    }
+    Ok(())
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let mut db = db::open(doc)?;
+    let before = db.clone();
+    let purged = auto_purge(&mut db, doc, Utc::now())?;
+
+    if matches.subcommand_matches("ls").is_some() {
+        handle_trash_list(&db, doc)?;
+    } else if let Some(matches) = matches.subcommand_matches("restore") {
+        handle_trash_restore(matches, &mut db)?;
+    } else if matches.subcommand_matches("empty").is_some() {
+        let emptied = db.trash.len();
+        db.trash = Default::default();
+        println!(
+            "Emptied the trash of {} item{}",
+            emptied,
+            if emptied == 1 { "" } else { "s" }
+        );
+    } else {
+        unreachable!();
+    }
+
+    if matches.subcommand_matches("ls").is_some() && purged == 0 {
+        return Ok(());
+    }
+    let command = format!("trash {}", matches.subcommand_name().unwrap());
+    journal::record(db_path, &command, &before, &mut db)?;
+    db.to_disk(db_path)
+}
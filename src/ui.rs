@@ -0,0 +1,400 @@
+use chrono::{Local, Utc};
+use clap::ArgMatches;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use uuid::Uuid;
+
+use regia_core::conf::Config;
+use regia_core::db;
+use regia_core::error;
+use regia_core::note;
+use regia_core::schedule;
+use regia_core::theme::{self, Element};
+use regia_core::todo;
+use regia_core::trash::Trash;
+
+/// The style of `element` in the theme in effect, plain when colors are off.
+fn themed(element: Element) -> Style {
+    let color = match theme::theme().color(element) {
+        Some(color) if theme::enabled() => color,
+        _ => return Style::default(),
+    };
+    let color = match color {
+        colored::Color::Black => Color::Black,
+        colored::Color::Red => Color::Red,
+        colored::Color::Green => Color::Green,
+        colored::Color::Yellow => Color::Yellow,
+        colored::Color::Blue => Color::Blue,
+        colored::Color::Magenta => Color::Magenta,
+        colored::Color::Cyan => Color::Cyan,
+        colored::Color::White => Color::Gray,
+        colored::Color::BrightBlack => Color::DarkGray,
+        colored::Color::BrightRed => Color::LightRed,
+        colored::Color::BrightGreen => Color::LightGreen,
+        colored::Color::BrightYellow => Color::LightYellow,
+        colored::Color::BrightBlue => Color::LightBlue,
+        colored::Color::BrightMagenta => Color::LightMagenta,
+        colored::Color::BrightCyan => Color::LightCyan,
+        colored::Color::BrightWhite => Color::White,
+    };
+    Style::default().fg(color)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pane {
+    Tasks,
+    Notes,
+}
+
+enum Mode {
+    Normal,
+    Filter,
+    Add(String),
+}
+
+/// Dashboard state. Works on the same `Tasks`/`Notes` the CLI uses and
+/// writes them back when the dashboard closes.
+struct App {
+    tasks: todo::Tasks,
+    notes: note::Notes,
+    trash: Trash,
+    pane: Pane,
+    mode: Mode,
+    filter: String,
+    show_done: bool,
+    task_state: ListState,
+    note_state: ListState,
+    status: String,
+    dirty: bool,
+}
+
+impl App {
+    fn new(tasks: todo::Tasks, notes: note::Notes, trash: Trash) -> Self {
+        let mut app = Self {
+            tasks,
+            notes,
+            trash,
+            pane: Pane::Tasks,
+            mode: Mode::Normal,
+            filter: String::new(),
+            show_done: false,
+            task_state: ListState::default(),
+            note_state: ListState::default(),
+            status: String::new(),
+            dirty: false,
+        };
+        app.clamp_selection();
+        app
+    }
+
+    fn matches_filter(&self, content: &str) -> bool {
+        self.filter.is_empty() || content.to_lowercase().contains(&self.filter.to_lowercase())
+    }
+
+    fn visible_tasks(&self) -> Vec<&todo::Task> {
+        let mut visible: Vec<&todo::Task> = self
+            .tasks
+            .get_tasks()
+            .iter()
+            .filter(|task| self.show_done || !task.is_done())
+            .filter(|task| self.matches_filter(&task.content))
+            .collect();
+        visible.sort_by(|left, right| {
+            right
+                .created
+                .cmp(&left.created)
+                .then_with(|| left.listing_order(right))
+        });
+        visible
+    }
+
+    fn visible_notes(&self) -> Vec<&note::Note> {
+        let mut visible: Vec<&note::Note> = self
+            .notes
+            .get_notes()
+            .iter()
+            .filter(|note| self.matches_filter(&note.content))
+            .collect();
+        visible.sort_by_key(|k| std::cmp::Reverse(k.created));
+        visible
+    }
+
+    fn selected_task(&self) -> Option<Uuid> {
+        let index = self.task_state.selected()?;
+        self.visible_tasks().get(index).map(|task| task.id)
+    }
+
+    fn selected_note(&self) -> Option<Uuid> {
+        let index = self.note_state.selected()?;
+        self.visible_notes().get(index).map(|note| note.id)
+    }
+
+    /// Keeps both selections inside their (possibly filtered) lists.
+    fn clamp_selection(&mut self) {
+        let task_len = self.visible_tasks().len();
+        let note_len = self.visible_notes().len();
+        for (state, len) in [
+            (&mut self.task_state, task_len),
+            (&mut self.note_state, note_len),
+        ] {
+            if len == 0 {
+                state.select(None);
+            } else {
+                let index = state.selected().unwrap_or(0).min(len - 1);
+                state.select(Some(index));
+            }
+        }
+    }
+
+    fn move_selection(&mut self, delta: i64) {
+        let state = match self.pane {
+            Pane::Tasks => &mut self.task_state,
+            Pane::Notes => &mut self.note_state,
+        };
+        if let Some(index) = state.selected() {
+            let next = (index as i64 + delta).max(0) as usize;
+            state.select(Some(next));
+        }
+        self.clamp_selection();
+    }
+
+    fn complete_selected(&mut self) {
+        if self.pane != Pane::Tasks {
+            return;
+        }
+        let id = match self.selected_task() {
+            Some(id) => id,
+            None => return,
+        };
+        let blocked = {
+            let task = self.tasks.get_task(&id).unwrap();
+            !self.tasks.open_dependencies(task).is_empty()
+        };
+        if blocked {
+            self.status = String::from("Task has open dependencies");
+            return;
+        }
+        if let Some(mut task) = self.tasks.get_task_mut(&id) {
+            task.complete();
+            self.status = format!("Done: {}", task.content);
+        }
+        let unblocked: Vec<&str> = self
+            .tasks
+            .unblocked_by(&[id])
+            .into_iter()
+            .map(|task| task.content.as_str())
+            .collect();
+        if !unblocked.is_empty() {
+            self.status = format!("{}, now unblocked: {}", self.status, unblocked.join(", "));
+        }
+        schedule::tick(&mut self.tasks, Utc::now(), &Local);
+        self.dirty = true;
+        self.clamp_selection();
+    }
+
+    fn delete_selected(&mut self) {
+        match self.pane {
+            Pane::Tasks => {
+                if let Some(id) = self.selected_task() {
+                    self.trash.delete_task(&mut self.tasks, id, Utc::now());
+                    self.status = String::from("Task deleted");
+                    self.dirty = true;
+                }
+            }
+            Pane::Notes => {
+                if let Some(id) = self.selected_note() {
+                    self.trash.delete_note(&mut self.notes, id, Utc::now());
+                    self.status = String::from("Note deleted");
+                    self.dirty = true;
+                }
+            }
+        }
+        self.clamp_selection();
+    }
+
+    fn add(&mut self, content: &str) {
+        let content = content.trim();
+        if content.is_empty() {
+            return;
+        }
+        match self.pane {
+            Pane::Tasks => self.tasks.add(todo::Task::new(String::from(content), 0)),
+            Pane::Notes => self.notes.add(note::Note::new(content)),
+        }
+        self.status = format!("Added: {}", content);
+        self.dirty = true;
+        self.clamp_selection();
+    }
+
+    /// Handles one key press, returning false once the dashboard should close.
+    fn on_key(&mut self, code: KeyCode) -> bool {
+        match &mut self.mode {
+            Mode::Add(input) => match code {
+                KeyCode::Enter => {
+                    let content = input.clone();
+                    self.mode = Mode::Normal;
+                    self.add(&content);
+                }
+                KeyCode::Esc => self.mode = Mode::Normal,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            },
+            Mode::Filter => {
+                match code {
+                    KeyCode::Enter => self.mode = Mode::Normal,
+                    KeyCode::Esc => {
+                        self.filter.clear();
+                        self.mode = Mode::Normal;
+                    }
+                    KeyCode::Backspace => {
+                        self.filter.pop();
+                    }
+                    KeyCode::Char(c) => self.filter.push(c),
+                    _ => {}
+                }
+                self.clamp_selection();
+            }
+            Mode::Normal => match code {
+                KeyCode::Char('q') | KeyCode::Esc => return false,
+                KeyCode::Tab | KeyCode::Left | KeyCode::Right => {
+                    self.pane = match self.pane {
+                        Pane::Tasks => Pane::Notes,
+                        Pane::Notes => Pane::Tasks,
+                    }
+                }
+                KeyCode::Char('j') | KeyCode::Down => self.move_selection(1),
+                KeyCode::Char('k') | KeyCode::Up => self.move_selection(-1),
+                KeyCode::Char('a') => self.mode = Mode::Add(String::new()),
+                KeyCode::Char('x') | KeyCode::Char(' ') => self.complete_selected(),
+                KeyCode::Char('d') | KeyCode::Delete => self.delete_selected(),
+                KeyCode::Char('/') => self.mode = Mode::Filter,
+                KeyCode::Char('A') => {
+                    self.show_done = !self.show_done;
+                    self.clamp_selection();
+                }
+                _ => {}
+            },
+        }
+        true
+    }
+
+    fn pane_block(&self, pane: Pane, title: &str) -> Block<'static> {
+        let style = if self.pane == pane {
+            themed(Element::Header)
+        } else {
+            Style::default()
+        };
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(style)
+            .title(String::from(title))
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(3), Constraint::Length(1)])
+            .split(frame.area());
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(rows[0]);
+
+        let highlight = Style::default().add_modifier(Modifier::REVERSED);
+
+        let task_items: Vec<ListItem> = self
+            .visible_tasks()
+            .iter()
+            .map(|task| {
+                let blocked = !task.is_done() && !self.tasks.open_dependencies(task).is_empty();
+                let bullet = if task.is_done() { "x" } else { "*" };
+                let style = if blocked || task.is_done() {
+                    Style::default().add_modifier(Modifier::DIM)
+                } else {
+                    Style::default()
+                };
+                let mut spans = vec![Span::styled(format!("{} {}", bullet, task.content), style)];
+                let mut tags: Vec<&String> = task.tags.iter().collect();
+                tags.sort();
+                for tag in tags {
+                    spans.push(Span::styled(format!(" #{}", tag), themed(Element::Tag)));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let task_list = List::new(task_items)
+            .block(self.pane_block(Pane::Tasks, "Tasks"))
+            .highlight_style(highlight);
+        frame.render_stateful_widget(task_list, panes[0], &mut self.task_state);
+
+        let note_items: Vec<ListItem> = self
+            .visible_notes()
+            .iter()
+            .map(|note| ListItem::new(format!("* {}", note.content)))
+            .collect();
+        let note_list = List::new(note_items)
+            .block(self.pane_block(Pane::Notes, "Notes"))
+            .highlight_style(highlight);
+        frame.render_stateful_widget(note_list, panes[1], &mut self.note_state);
+
+        let footer = match &self.mode {
+            Mode::Add(input) => format!("add> {}", input),
+            Mode::Filter => format!("/{}", self.filter),
+            Mode::Normal if !self.status.is_empty() => self.status.clone(),
+            Mode::Normal if !self.filter.is_empty() => format!("filter: {}", self.filter),
+            Mode::Normal => String::from(
+                "q quit  tab switch  j/k move  a add  x done  d delete  / filter  A all",
+            ),
+        };
+        frame.render_widget(Paragraph::new(footer), rows[1]);
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> std::io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                self.status.clear();
+                if !self.on_key(key.code) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+pub fn handle_it(_matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    let mut db = db::open(doc)?;
+
+    let mut tasks = std::mem::take(&mut db.tasks);
+    let spawned = schedule::tick(&mut tasks, Utc::now(), &Local);
+    let mut app = App::new(
+        tasks,
+        std::mem::take(&mut db.notes),
+        std::mem::take(&mut db.trash),
+    );
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result?;
+
+    if app.dirty || !spawned.is_empty() {
+        db.tasks = app.tasks;
+        db.notes = app.notes;
+        db.trash = app.trash;
+        db.to_disk(db_path)
+    } else {
+        Ok(())
+    }
+}
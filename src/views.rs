@@ -0,0 +1,102 @@
+use clap::ArgMatches;
+use colored::*;
+use serde::Serialize;
+
+use crate::output::{self, Output};
+use crate::taskmaster;
+
+use regia_core::conf::Config;
+use regia_core::db;
+use regia_core::error::{self, RegiaError};
+use regia_core::theme::Themed;
+use regia_core::view::View;
+
+/// Names `regia view NAME` would read as a subcommand instead.
+const RESERVED: [&str; 3] = ["save", "ls", "rm"];
+
+#[derive(Serialize)]
+struct ViewRecord {
+    name: String,
+    #[serde(flatten)]
+    view: View,
+}
+
+impl output::Record for ViewRecord {
+    fn plain(&self) -> String {
+        output::fields(&[self.name.clone(), self.view.args().join(" ")])
+    }
+}
+
+fn handle_view_save(matches: &ArgMatches, db: &mut db::Database) -> error::Result<()> {
+    let name = matches.value_of("name").unwrap().trim();
+    if name.is_empty() || RESERVED.contains(&name) {
+        return Err(RegiaError::Parse(format!("can't name a view: {}", name)));
+    }
+    let view = taskmaster::view_from_args(matches);
+    // Catch bad values now rather than every time the view is used
+    taskmaster::query_from_view(&view)?;
+    let verb = if db.views.contains_key(name) {
+        "Updated view"
+    } else {
+        "Saved view"
+    };
+    println!(
+        "{} {} {}",
+        verb.header(),
+        name.bold(),
+        view.args().join(" ")
+    );
+    db.views.insert(name.to_string(), view);
+    Ok(())
+}
+
+fn handle_view_rm(matches: &ArgMatches, db: &mut db::Database) -> error::Result<()> {
+    let name = matches.value_of("name").unwrap();
+    if db.views.remove(name).is_none() {
+        return Err(RegiaError::NotFound(format!("no view named: {}", name)));
+    }
+    println!("{} {}", "Removed view".header(), name.bold());
+    Ok(())
+}
+
+fn handle_view_list(db: &db::Database, doc: &Config) -> error::Result<()> {
+    let format = Output::from_config(doc);
+    if format != Output::Text {
+        let records: Vec<ViewRecord> = db
+            .views
+            .iter()
+            .map(|(name, view)| ViewRecord {
+                name: name.clone(),
+                view: view.clone(),
+            })
+            .collect();
+        return output::print(format, &records);
+    }
+
+    if db.views.is_empty() {
+        println!("No saved views");
+    }
+    for (name, view) in db.views.iter() {
+        println!("{} {}", name.bold(), view.args().join(" ").dimmed());
+    }
+    Ok(())
+}
+
+pub fn handle_it(matches: &ArgMatches, doc: &Config) -> error::Result<()> {
+    let db_path = db::db_path(doc);
+    // Like projects, views sit outside the journal and undo
+    let mut db = db::open(doc)?;
+
+    if let Some(matches) = matches.subcommand_matches("save") {
+        handle_view_save(matches, &mut db)?;
+    } else if let Some(matches) = matches.subcommand_matches("rm") {
+        handle_view_rm(matches, &mut db)?;
+    } else if matches.subcommand_matches("ls").is_some() {
+        return handle_view_list(&db, doc);
+    } else {
+        let view = taskmaster::requested_view(matches, matches.value_of("name"), &db.views)?;
+        return taskmaster::list_tasks(&view, &db, doc);
+    }
+
+    db.to_disk(db_path)
+}